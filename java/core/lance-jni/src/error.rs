@@ -16,7 +16,7 @@ use std::str::Utf8Error;
 
 use arrow_schema::ArrowError;
 use jni::{errors::Error as JniError, JNIEnv};
-use lance::error::Error as LanceError;
+use lance::error::{EncodingError, Error as LanceError};
 use serde_json::Error as JsonError;
 
 #[derive(Debug)]
@@ -94,6 +94,13 @@ impl From<LanceError> for Error {
             | LanceError::InvalidInput { .. } => Self::input_error(err.to_string()),
             LanceError::IO { .. } => Self::io_error(err.to_string()),
             LanceError::NotSupported { .. } => Self::unsupported_error(err.to_string()),
+            LanceError::Encoding { ref source, .. } => match source {
+                EncodingError::UnsupportedType { .. } => Self::unsupported_error(err.to_string()),
+                EncodingError::UnknownScheme { .. } => Self::input_error(err.to_string()),
+                EncodingError::CorruptMetadata { .. } | EncodingError::BufferTooShort { .. } => {
+                    Self::io_error(err.to_string())
+                }
+            },
             _ => Self::runtime_error(err.to_string()),
         }
     }
@@ -950,6 +950,9 @@ class LanceDataset(pa.dataset.Dataset):
         order of the newly inserted rows may fluctuate randomly because a
         hash-join operation is used internally.
 
+        The key is expected to be unique in the source table.  If a key appears
+        more than once in the source table the operation fails.
+
         Parameters
         ----------
 
@@ -1289,7 +1292,7 @@ class LanceDataset(pa.dataset.Dataset):
             The column to be indexed.
         index_type : str
             The type of the index.
-            ``"IVF_PQ, IVF_HNSW_PQ and IVF_HNSW_SQ"`` are supported now.
+            ``"IVF_PQ, IVF_SQ, IVF_HNSW_PQ and IVF_HNSW_SQ"`` are supported now.
         name : str, optional
             The index name. If not provided, it will be generated from the
             column name.
@@ -1336,10 +1339,11 @@ class LanceDataset(pa.dataset.Dataset):
         kwargs :
             Parameters passed to the index building process.
 
-        The SQ (Scalar Quantization) is available for only "IVF_HNSW_SQ" index type,
-        this quantization method is used to reduce the memory usage of the index,
-        it maps the float vectors to integer vectors, each integer is of ``num_bits``,
-        now only 8 bits are supported.
+        The SQ (Scalar Quantization) is available for "IVF_SQ" and "IVF_HNSW_SQ"
+        index types, this quantization method is used to reduce the memory usage of
+        the index, it maps each dimension of the float vectors to an integer of
+        ``num_bits``, using the value range of that dimension. Now only 8 bits are
+        supported. It usually has a better recall than PQ, at a lower compression.
 
         If ``index_type`` is "IVF_*", then the following parameters are required:
             num_partitions
@@ -1451,7 +1455,7 @@ class LanceDataset(pa.dataset.Dataset):
         kwargs["metric_type"] = metric
 
         index_type = index_type.upper()
-        valid_index_types = ["IVF_PQ", "IVF_HNSW_PQ", "IVF_HNSW_SQ"]
+        valid_index_types = ["IVF_PQ", "IVF_SQ", "IVF_HNSW_PQ", "IVF_HNSW_SQ"]
         if index_type not in valid_index_types:
             raise NotImplementedError(
                 f"Only {valid_index_types} index types supported. " f"Got {index_type}"
@@ -2441,6 +2445,16 @@ class DatasetOptimizer:
         the new data to existing partitions.  This means an update is much quicker
         than retraining the entire index but may have less accuracy (especially
         if the new data exhibits new patterns, concepts, or trends)
+
+        Parameters
+        ----------
+        num_indices_to_merge: int, default 1
+            The number of existing delta indices to merge the new data into.  If
+            0 the new data is written to a new delta index.
+        retrain_threshold: float, optional
+            If the new, unindexed, rows are more than this fraction of the rows
+            covered by a vector index then the index is retrained from scratch
+            over all of the data instead.
         """
         self._dataset._ds.optimize_indices(**kwargs)
 
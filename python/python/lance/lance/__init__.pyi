@@ -74,6 +74,7 @@ class LanceFileMetadata:
     num_data_bytes: int
     num_column_metadata_bytes: int
     num_global_buffer_bytes: int
+    page_alignment: int
     global_buffers: List[LanceBufferDescriptor]
     columns: List[LanceColumnMetadata]
 
@@ -911,6 +911,9 @@ impl Dataset {
             if let Some(num_indices_to_merge) = kwargs.get_item("num_indices_to_merge")? {
                 options.num_indices_to_merge = num_indices_to_merge.extract()?;
             }
+            if let Some(retrain_threshold) = kwargs.get_item("retrain_threshold")? {
+                options.retrain_threshold = retrain_threshold.extract()?;
+            }
         }
         RT.block_on(
             None,
@@ -935,7 +938,7 @@ impl Dataset {
         let index_type = index_type.to_uppercase();
         let idx_type = match index_type.as_str() {
             "BTREE" | "BITMAP" | "LABEL_LIST" => IndexType::Scalar,
-            "IVF_PQ" | "IVF_HNSW_PQ" | "IVF_HNSW_SQ" => IndexType::Vector,
+            "IVF_PQ" | "IVF_SQ" | "IVF_HNSW_PQ" | "IVF_HNSW_SQ" => IndexType::Vector,
             _ => {
                 return Err(PyValueError::new_err(format!(
                     "Index type '{index_type}' is not supported."
@@ -1380,6 +1383,10 @@ fn prepare_vector_index_params(
             m_type, ivf_params, pq_params,
         ))),
 
+        "IVF_SQ" => Ok(Box::new(VectorIndexParams::with_ivf_sq_params(
+            m_type, ivf_params, sq_params,
+        ))),
+
         "IVF_HNSW_PQ" => Ok(Box::new(VectorIndexParams::with_ivf_hnsw_pq_params(
             m_type,
             ivf_params,
@@ -17,7 +17,7 @@ use pyo3::{
     PyResult,
 };
 
-use lance::error::Error as LanceError;
+use lance::error::{EncodingError, Error as LanceError};
 
 pub trait PythonErrorExt<T> {
     /// Convert to a python error based on the Lance error type
@@ -40,6 +40,12 @@ impl<T> PythonErrorExt<T> for std::result::Result<T, LanceError> {
                 LanceError::InvalidInput { .. } => self.value_error(),
                 LanceError::NotSupported { .. } => self.not_implemented(),
                 LanceError::IO { .. } => self.io_error(),
+                LanceError::Encoding { source, .. } => match source {
+                    EncodingError::UnsupportedType { .. } => self.not_implemented(),
+                    EncodingError::UnknownScheme { .. } => self.value_error(),
+                    EncodingError::CorruptMetadata { .. }
+                    | EncodingError::BufferTooShort { .. } => self.io_error(),
+                },
                 _ => self.runtime_error(),
             },
         }
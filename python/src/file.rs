@@ -123,6 +123,8 @@ pub struct LanceFileMetadata {
     pub num_column_metadata_bytes: u64,
     /// The number of bytes in the global buffer section of the file
     pub num_global_buffer_bytes: u64,
+    /// The alignment (in bytes) of the start of every page and column buffer
+    pub page_alignment: u64,
     /// The global buffers
     pub global_buffers: Vec<LanceBufferDescriptor>,
     /// The column metadata, an entry might be None if the metadata for a column
@@ -142,6 +144,7 @@ impl LanceFileMetadata {
             num_data_bytes: inner.num_data_bytes,
             num_column_metadata_bytes: inner.num_column_metadata_bytes,
             num_global_buffer_bytes: inner.num_global_buffer_bytes,
+            page_alignment: inner.page_alignment,
             global_buffers: inner
                 .file_buffers
                 .iter()
@@ -219,7 +222,10 @@ impl LanceFileWriter {
     }
 
     pub fn finish(&mut self) -> PyResult<u64> {
-        RT.runtime.block_on(self.inner.finish()).infer_error()
+        RT.runtime
+            .block_on(self.inner.finish())
+            .map(|statistics| statistics.num_rows)
+            .infer_error()
     }
 }
 
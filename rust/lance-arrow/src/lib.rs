@@ -94,6 +94,7 @@ impl DataTypeExt for DataType {
                 | Date64
                 | Time32(_)
                 | Time64(_)
+                | Interval(_)
         )
     }
 
@@ -33,6 +33,12 @@ pub struct SchemaCompareOptions {
     pub compare_dictionary: bool,
     /// Should the field ids be compared (default false)
     pub compare_field_ids: bool,
+    /// Allow top level fields of the expected schema to be missing, as long as they are
+    /// nullable (default false)
+    pub allow_missing_if_nullable: bool,
+    /// Allow top level fields to be in a different order than in the expected schema
+    /// (default false)
+    pub ignore_field_order: bool,
 }
 /// Encoding enum.
 #[derive(Debug, Clone, PartialEq, Eq, DeepSizeOf)]
@@ -60,14 +60,92 @@ impl<'a> Iterator for SchemaFieldIterPreOrder<'a> {
 
 impl Schema {
     pub fn compare_with_options(&self, expected: &Self, options: &SchemaCompareOptions) -> bool {
-        if self.fields.len() != expected.fields.len() {
-            false
+        let fields_match = if options.allow_missing_if_nullable || options.ignore_field_order {
+            self.relaxed_field_differences(expected, options).is_none()
+                && self.fields.iter().all(|field| {
+                    expected
+                        .field(&field.name)
+                        .is_some_and(|expected| field.compare_with_options(expected, options))
+                })
         } else {
-            self.fields
-                .iter()
-                .zip(&expected.fields)
-                .all(|(lhs, rhs)| lhs.compare_with_options(rhs, options))
-                && (!options.compare_metadata || self.metadata == expected.metadata)
+            self.fields.len() == expected.fields.len()
+                && self
+                    .fields
+                    .iter()
+                    .zip(&expected.fields)
+                    .all(|(lhs, rhs)| lhs.compare_with_options(rhs, options))
+        };
+        fields_match && (!options.compare_metadata || self.metadata == expected.metadata)
+    }
+
+    // Explains how the top level fields differ from `expected` when fields may be missing
+    // or out of order, `None` if they are compatible
+    fn relaxed_field_differences(
+        &self,
+        expected: &Self,
+        options: &SchemaCompareOptions,
+    ) -> Option<String> {
+        let missing = expected
+            .fields
+            .iter()
+            .filter(|field| self.field(&field.name).is_none())
+            .filter(|field| !options.allow_missing_if_nullable || !field.nullable)
+            .map(|field| field.name.clone())
+            .collect::<Vec<_>>();
+        let unexpected = self
+            .fields
+            .iter()
+            .filter(|field| expected.field(&field.name).is_none())
+            .map(|field| field.name.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() || !unexpected.is_empty() {
+            return Some(format!(
+                "fields did not match, missing=[{}], unexpected=[{}]",
+                missing.join(", "),
+                unexpected.join(", ")
+            ));
+        }
+        let expected_positions = self
+            .fields
+            .iter()
+            .map(|field| {
+                expected
+                    .fields
+                    .iter()
+                    .position(|expected| expected.name == field.name)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        if !options.ignore_field_order
+            && expected_positions.windows(2).any(|pair| pair[0] > pair[1])
+        {
+            return Some(format!(
+                "fields in different order, expected: [{}], actual: [{}]",
+                expected
+                    .fields
+                    .iter()
+                    .map(|f| f.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.fields
+                    .iter()
+                    .map(|f| f.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+        let differences = self
+            .fields
+            .iter()
+            .zip(expected_positions)
+            .flat_map(|(field, position)| {
+                field.explain_difference(&expected.fields[position], options)
+            })
+            .collect::<Vec<_>>();
+        if differences.is_empty() {
+            None
+        } else {
+            Some(differences.join(", "))
         }
     }
 
@@ -76,6 +154,16 @@ impl Schema {
         expected: &Self,
         options: &SchemaCompareOptions,
     ) -> Option<String> {
+        if options.allow_missing_if_nullable || options.ignore_field_order {
+            let difference = self.relaxed_field_differences(expected, options);
+            if difference.is_none()
+                && options.compare_metadata
+                && self.metadata != expected.metadata
+            {
+                return Some("schema metadata did not match expected schema metadata".to_string());
+            }
+            return difference;
+        }
         if self.fields.len() != expected.fields.len()
             || !self
                 .fields
@@ -192,11 +280,9 @@ impl Schema {
     }
 
     /// Check that the top level fields don't contain `.` in their names
-    /// to distinguish from nested fields.
+    /// to distinguish from nested fields, and that no two fields have the same path.
     // TODO: pub(crate)
     pub fn validate(&self) -> Result<()> {
-        let mut seen_names = HashSet::new();
-
         for field in self.fields.iter() {
             if field.name.contains('.') {
                 return Err(Error::Schema{message:format!(
@@ -204,7 +290,10 @@ impl Schema {
                     field.name.clone()
                 ), location: location!(),});
             }
+        }
 
+        let mut seen_names = HashSet::new();
+        for field in self.fields_pre_order() {
             let column_path = self
                 .field_ancestry_by_id(field.id)
                 .unwrap()
@@ -747,6 +836,25 @@ mod tests {
         assert_eq!(field.data_type(), DataType::Boolean);
     }
 
+    #[test]
+    fn test_validate_duplicate_names() {
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Struct(ArrowFields::from(vec![
+                ArrowField::new("f1", DataType::Utf8, true),
+                ArrowField::new("f1", DataType::Boolean, false),
+            ])),
+            true,
+        )]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let err = schema.validate().unwrap_err();
+        assert!(
+            err.to_string().contains("Duplicate field name \"b.f1\""),
+            "{}",
+            err
+        );
+    }
+
     #[test]
     fn test_exclude_fields() {
         let arrow_schema = ArrowSchema::new(vec![
@@ -1032,4 +1140,52 @@ mod tests {
 
         assert_eq!(mismatched.explain_difference(&expected, &SchemaCompareOptions::default()), Some("`b` had mismatched children, missing=[f2] unexpected=[], `c` should have nullable=false but nullable=true".to_string()));
     }
+
+    #[test]
+    fn test_compare_missing_and_reordered_fields() {
+        let expected = Schema::try_from(&ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, true),
+            ArrowField::new("c", DataType::Float64, true),
+        ]))
+        .unwrap();
+        let subset = Schema::try_from(&ArrowSchema::new(vec![
+            ArrowField::new("c", DataType::Float64, true),
+            ArrowField::new("a", DataType::Int32, false),
+        ]))
+        .unwrap();
+        let relaxed = SchemaCompareOptions {
+            allow_missing_if_nullable: true,
+            ignore_field_order: true,
+            ..Default::default()
+        };
+
+        assert!(subset.check_compatible(&expected, &relaxed).is_ok());
+        assert!(!subset.compare_with_options(&expected, &SchemaCompareOptions::default()));
+        assert_eq!(
+            subset.explain_difference(
+                &expected,
+                &SchemaCompareOptions {
+                    allow_missing_if_nullable: true,
+                    ..Default::default()
+                }
+            ),
+            Some("fields in different order, expected: [a, b, c], actual: [c, a]".to_string())
+        );
+
+        // Non-nullable fields can't be missing
+        let missing_a = expected.project(&["b", "c"]).unwrap();
+        assert_eq!(
+            missing_a.explain_difference(&expected, &relaxed),
+            Some("fields did not match, missing=[a], unexpected=[]".to_string())
+        );
+        // Fields that are present must still match
+        let wrong_type = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Int64,
+            true,
+        )]))
+        .unwrap();
+        assert!(wrong_type.check_compatible(&expected, &relaxed).is_err());
+    }
 }
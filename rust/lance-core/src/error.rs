@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use arrow_schema::ArrowError;
+use arrow_schema::{ArrowError, DataType};
 use snafu::{Location, Snafu};
 
 type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
@@ -84,6 +84,41 @@ pub enum Error {
     Cloned { message: String, location: Location },
     #[snafu(display("Query Execution error: {message}, {location}"))]
     Execution { message: String, location: Location },
+    #[snafu(display("LanceError(Encoding): {source}, {location}"))]
+    Encoding {
+        source: EncodingError,
+        location: Location,
+    },
+}
+
+/// The ways that encoding or decoding Lance data can fail
+///
+/// These are wrapped in [`Error::Encoding`] so that callers can tell the failures apart
+/// without inspecting error messages.
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum EncodingError {
+    /// The data type cannot be encoded (or decoded) in the requested way
+    #[snafu(display("Unsupported data type {data_type}: {message}"))]
+    UnsupportedType {
+        data_type: DataType,
+        message: String,
+    },
+    /// The encoding metadata is invalid or uses an encoding this version does not recognize
+    #[snafu(display("Corrupt encoding metadata: {message}"))]
+    CorruptMetadata { message: String },
+    /// A buffer is shorter than its encoding says it should be
+    #[snafu(display(
+        "Buffer too short: {context} needs {expected} bytes but the buffer has {actual} bytes"
+    ))]
+    BufferTooShort {
+        context: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// The compression scheme is not one that this version knows about
+    #[snafu(display("Unknown compression scheme: {scheme}"))]
+    UnknownScheme { scheme: String },
 }
 
 impl Error {
@@ -107,6 +142,33 @@ impl Error {
             location,
         }
     }
+    pub fn encoding(source: EncodingError, location: Location) -> Self {
+        Self::Encoding { source, location }
+    }
+
+    pub fn unsupported_type(
+        data_type: &DataType,
+        message: impl Into<String>,
+        location: Location,
+    ) -> Self {
+        Self::encoding(
+            EncodingError::UnsupportedType {
+                data_type: data_type.clone(),
+                message: message.into(),
+            },
+            location,
+        )
+    }
+
+    pub fn corrupt_metadata(message: impl Into<String>, location: Location) -> Self {
+        Self::encoding(
+            EncodingError::CorruptMetadata {
+                message: message.into(),
+            },
+            location,
+        )
+    }
+
     pub fn io(message: impl Into<String>, location: Location) -> Self {
         let message: String = message.into();
         Self::IO {
@@ -75,6 +75,19 @@ impl DeletionVector {
         }
     }
 
+    /// Like [`Self::into_sorted_iter`] but borrows the deletion vector
+    pub fn to_sorted_iter(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        match self {
+            Self::NoDeletions => Box::new(std::iter::empty()),
+            Self::Set(set) => {
+                let mut values = Vec::from_iter(set.iter().copied());
+                values.sort();
+                Box::new(values.into_iter())
+            }
+            Self::Bitmap(bitmap) => Box::new(bitmap.iter()),
+        }
+    }
+
     // Note: deletion vectors are based on 32-bit offsets.  However, this function works
     // even when given 64-bit row addresses.  That is because `id as u32` returns the lower
     // 32 bits (the row offset) and the upper 32 bits are ignored.
@@ -212,6 +225,21 @@ mod test {
         assert_eq!(set_dv, bitmap_dv);
     }
 
+    #[test]
+    fn test_sorted_iter() {
+        let values = [7, 3, 100, 0, 42];
+        let mut expected = values.to_vec();
+        expected.sort();
+        for dv in [
+            DeletionVector::Set(HashSet::from_iter(values)),
+            DeletionVector::Bitmap(RoaringBitmap::from_iter(values)),
+        ] {
+            assert_eq!(dv.to_sorted_iter().collect::<Vec<_>>(), expected);
+            assert_eq!(dv.into_sorted_iter().collect::<Vec<_>>(), expected);
+        }
+        assert_eq!(DeletionVector::NoDeletions.to_sorted_iter().count(), 0);
+    }
+
     #[test]
     fn test_threshold() {
         let dv = DeletionVector::from_iter(0..(BITMAP_THRESDHOLD as u32));
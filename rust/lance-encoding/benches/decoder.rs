@@ -130,6 +130,44 @@ fn bench_decode_fsl(c: &mut Criterion) {
     }
 }
 
+// Full-column scans of uncompressed floats can use the loaded data without copying it
+fn bench_decode_float_scan(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("decode_float_scan");
+    for data_type in [DataType::Float32, DataType::Float64] {
+        let data = lance_datagen::gen()
+            .anon_col(lance_datagen::array::rand_type(&data_type))
+            .into_batch_rows(lance_datagen::RowCount::from(8 * 1024 * 1024))
+            .unwrap();
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(data.schema().as_ref()).unwrap());
+        let input_bytes = data.get_array_memory_size();
+        group.throughput(criterion::Throughput::Bytes(input_bytes as u64));
+        let encoding_strategy = CoreFieldEncodingStrategy::default();
+        let encoded = rt
+            .block_on(encode_batch(
+                &data,
+                lance_schema,
+                &encoding_strategy,
+                1024 * 1024,
+            ))
+            .unwrap();
+        let func_name = format!("{:?}", data_type).to_lowercase();
+        group.bench_function(func_name, |b| {
+            b.iter(|| {
+                let batch = rt
+                    .block_on(lance_encoding::decoder::decode_batch(
+                        &encoded,
+                        &FilterExpression::no_filter(),
+                        &DecoderMiddlewareChain::default(),
+                    ))
+                    .unwrap();
+                assert_eq!(data.num_rows(), batch.num_rows());
+            })
+        });
+    }
+}
+
 fn bench_decode_str_with_dict_encoding(c: &mut Criterion) {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let mut group = c.benchmark_group("decode_primitive");
@@ -190,12 +228,12 @@ criterion_group!(
     name=benches;
     config = Criterion::default().significance_level(0.1).sample_size(10)
         .with_profiler(pprof::criterion::PProfProfiler::new(100, pprof::criterion::Output::Flamegraph(None)));
-    targets = bench_decode, bench_decode_fsl, bench_decode_str_with_dict_encoding);
+    targets = bench_decode, bench_decode_fsl, bench_decode_float_scan, bench_decode_str_with_dict_encoding);
 
 // Non-linux version does not support pprof.
 #[cfg(not(target_os = "linux"))]
 criterion_group!(
     name=benches;
     config = Criterion::default().significance_level(0.1).sample_size(10);
-    targets = bench_decode, bench_decode_fsl);
+    targets = bench_decode, bench_decode_fsl, bench_decode_float_scan);
 criterion_main!(benches);
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Estimation of the number of distinct values in a page
+//!
+//! The estimate is computed with HyperLogLog while a page is encoded.  It is used to decide
+//! whether to dictionary encode the page and is recorded in the page statistics so that it
+//! can be used later (e.g. by a query planner) without reading the page.
+
+use std::collections::hash_map::RandomState;
+
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, ArrayRef};
+use arrow_schema::DataType;
+use hyperloglogplus::{HyperLogLog, HyperLogLogPlus};
+
+/// The precision of the estimate
+///
+/// The error rate is 1.04 / sqrt(2^p), where p is the precision, and so the error rate is
+/// 1.04 / sqrt(2^12) = 1.6%.  The sketch is kept sparse (and small) while the number of
+/// distinct values is low.
+const PRECISION: u8 = 12;
+
+/// Estimates the number of distinct non-null values in `arrays`
+///
+/// Returns `None` if estimates are not supported for the data type.  Estimates are supported
+/// for fixed-width primitive types and for strings and binary.
+pub fn estimate_distinct_count(arrays: &[ArrayRef]) -> Option<u64> {
+    let data_type = arrays.first()?.data_type();
+    if !supports_distinct_count(data_type) {
+        return None;
+    }
+    let mut hll: HyperLogLogPlus<[u8], RandomState> =
+        HyperLogLogPlus::new(PRECISION, RandomState::new()).unwrap();
+    for array in arrays {
+        match data_type {
+            DataType::Utf8 => insert_all(&mut hll, array.as_string::<i32>().iter()),
+            DataType::LargeUtf8 => insert_all(&mut hll, array.as_string::<i64>().iter()),
+            DataType::Binary => insert_all(&mut hll, array.as_binary::<i32>().iter()),
+            DataType::LargeBinary => insert_all(&mut hll, array.as_binary::<i64>().iter()),
+            _ => {
+                // Fixed-width values are hashed by their bytes
+                let byte_width = data_type.primitive_width().unwrap();
+                let data = array.to_data();
+                let values = &data.buffers()[0].as_slice()[data.offset() * byte_width..];
+                let values = values
+                    .chunks_exact(byte_width)
+                    .take(array.len())
+                    .enumerate()
+                    .map(|(idx, value)| array.is_valid(idx).then_some(value));
+                insert_all(&mut hll, values);
+            }
+        }
+    }
+    Some(hll.count().round() as u64)
+}
+
+fn insert_all<'a, T: AsRef<[u8]> + ?Sized + 'a>(
+    hll: &mut HyperLogLogPlus<[u8], RandomState>,
+    values: impl Iterator<Item = Option<&'a T>>,
+) {
+    for value in values.flatten() {
+        hll.insert_any(value.as_ref());
+    }
+}
+
+fn supports_distinct_count(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary => true,
+        // Booleans are bit-packed and have no more than two values anyway
+        DataType::Boolean => false,
+        _ => data_type.primitive_width().is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Float64Array, Int32Array, LargeBinaryArray, StringArray};
+
+    use super::*;
+
+    fn assert_estimate(arrays: &[ArrayRef], expected: u64) {
+        let estimate = estimate_distinct_count(arrays).unwrap();
+        let error = (estimate as f64 - expected as f64).abs() / expected as f64;
+        assert!(
+            error < 0.05,
+            "estimated {} distinct values but there are {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_low_cardinality() {
+        let ints = Arc::new(Int32Array::from_iter_values((0..10_000).map(|i| i % 10)));
+        assert_estimate(&[ints], 10);
+
+        let strings = (0..10_000)
+            .map(|i| format!("category-{}", i % 37))
+            .collect::<Vec<_>>();
+        let strings: ArrayRef = Arc::new(StringArray::from(strings));
+        // The estimate is over all the arrays of the page
+        assert_estimate(&[strings.clone(), strings.slice(100, 500)], 37);
+    }
+
+    #[test]
+    fn test_high_cardinality() {
+        let floats = Arc::new(Float64Array::from_iter_values(
+            (0..100_000).map(|i| i as f64 * 0.5),
+        ));
+        assert_estimate(&[floats], 100_000);
+
+        let binary = Arc::new(LargeBinaryArray::from_iter_values(
+            (0..50_000_u64).map(|i| (i / 2).to_le_bytes()),
+        ));
+        assert_estimate(&[binary], 25_000);
+    }
+
+    #[test]
+    fn test_nulls_and_slices() {
+        let ints: ArrayRef = Arc::new(Int32Array::from_iter(
+            (0..1000).map(|i| (i % 2 == 0).then_some(i)),
+        ));
+        // Null values are not counted
+        assert_estimate(&[ints.clone()], 500);
+        // Only the values of a slice are counted
+        assert_estimate(&[ints.slice(500, 100)], 50);
+
+        assert_eq!(
+            estimate_distinct_count(&[Arc::new(arrow_array::BooleanArray::from(vec![true]))]),
+            None
+        );
+    }
+}
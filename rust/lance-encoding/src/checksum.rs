@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Checksums of encoded buffers
+//!
+//! Checksums are CRC32 (IEEE).  Large buffers are split into blocks of a fixed size (by
+//! default [`CHECKSUM_BLOCK_SIZE`]) and the blocks are checksummed in parallel.  CRCs can be
+//! combined and so the checksum of the whole buffer is computed from the block checksums,
+//! in block order.  The result does not depend on the block size or on the number of threads
+//! and is the same as the checksum computed serially over the buffer.
+
+use crc32fast::Hasher;
+use rayon::prelude::*;
+
+/// The default size of the blocks that are checksummed in parallel
+pub const CHECKSUM_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Computes the checksum of `data` on the current thread
+pub fn checksum(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+/// Computes the checksum of each `block_size` block of `data` in parallel
+///
+/// The last block may be shorter than `block_size`.
+pub fn block_checksums(data: &[u8], block_size: usize) -> Vec<u32> {
+    assert!(block_size > 0, "block_size must be greater than 0");
+    data.par_chunks(block_size).map(checksum).collect()
+}
+
+/// Combines checksums of consecutive pieces of data into the checksum of all the data
+///
+/// Each item is the checksum of a piece and the length of the piece in bytes.  Pieces must
+/// be given in the order they appear in the data.
+pub fn combine_checksums(checksums: impl IntoIterator<Item = (u32, u64)>) -> u32 {
+    let mut combined = Hasher::new();
+    for (checksum, len) in checksums {
+        combined.combine(&Hasher::new_with_initial_len(checksum, len));
+    }
+    combined.finalize()
+}
+
+/// Computes the checksum of `data`, checksumming blocks of `block_size` bytes in parallel
+///
+/// This always returns the same value as [`checksum`].
+pub fn checksum_parallel(data: &[u8], block_size: usize) -> u32 {
+    let block_lens = data.chunks(block_size).map(|block| block.len() as u64);
+    combine_checksums(
+        block_checksums(data, block_size)
+            .into_iter()
+            .zip(block_lens),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_buffer::Buffer;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::encoder::EncodedArrayBuffer;
+
+    #[test]
+    fn test_parallel_checksum_matches_serial() {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(42);
+        for len in [0, 1, 1000, 4096, 4097, 100_000] {
+            let data = (0..len).map(|_| rng.gen()).collect::<Vec<u8>>();
+            let expected = checksum(&data);
+            for block_size in [1, 7, 1024, 4096, CHECKSUM_BLOCK_SIZE] {
+                assert_eq!(
+                    checksum_parallel(&data, block_size),
+                    expected,
+                    "len={} block_size={}",
+                    len,
+                    block_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_encoded_buffer_checksum() {
+        let data = (0..3 * CHECKSUM_BLOCK_SIZE + 17)
+            .map(|i| (i % 253) as u8)
+            .collect::<Vec<_>>();
+        let (first, rest) = data.split_at(CHECKSUM_BLOCK_SIZE + 5);
+        let buffer = EncodedArrayBuffer {
+            parts: vec![
+                Buffer::from(first),
+                Buffer::from(&[][..]),
+                Buffer::from(rest),
+            ],
+            index: 0,
+        };
+        assert_eq!(buffer.checksum(), checksum(&data));
+    }
+}
@@ -217,26 +217,34 @@ use std::{ops::Range, sync::Arc};
 
 use arrow_array::cast::AsArray;
 use arrow_array::{ArrayRef, RecordBatch};
+use arrow_buffer::MutableBuffer;
 use arrow_schema::{DataType, Field as ArrowField, Fields, Schema as ArrowSchema};
 use bytes::{Bytes, BytesMut};
 use futures::future::BoxFuture;
 use futures::stream::BoxStream;
 use futures::{FutureExt, StreamExt};
 use lance_arrow::DataTypeExt;
-use lance_core::datatypes::{Field, Schema};
+use lance_core::datatypes::{Field, LogicalType, Schema};
 use log::trace;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
 use snafu::{location, Location};
 use tokio::sync::mpsc::{self, unbounded_channel};
 
-use lance_core::{Error, Result};
+use lance_core::{error::EncodingError, Error, Result};
 use tracing::instrument;
 
 use crate::encoder::{values_column_encoding, EncodedBatch};
 use crate::encodings::logical::list::{ListFieldScheduler, OffsetPageInfo};
-use crate::encodings::logical::primitive::PrimitiveFieldScheduler;
+use crate::encodings::logical::primitive::{decode_primitive_page, PrimitiveFieldScheduler};
 use crate::encodings::logical::r#struct::{SimpleStructDecoder, SimpleStructScheduler};
-use crate::encodings::physical::{ColumnBuffers, FileBuffers};
+use crate::encodings::physical::{
+    buffers::DEFAULT_MAX_DECOMPRESSED_SIZE, decoder_from_array_encoding, ColumnBuffers,
+    FileBuffers, PageBuffers,
+};
 use crate::format::pb;
+use crate::page_cache::FilePageCache;
+use crate::stats::{DecodeStats, StatsIo};
 use crate::{BufferScheduler, EncodingsIo};
 
 /// Metadata describing a page in a file
@@ -252,6 +260,32 @@ pub struct PageInfo {
     pub buffer_offsets_and_sizes: Arc<[(u64, u64)]>,
 }
 
+impl PageInfo {
+    /// The version of the encoder that wrote the page
+    ///
+    /// This is `None` for pages that were written before the encoder version was recorded
+    pub fn producer(&self) -> Option<&pb::EncoderVersion> {
+        self.encoding.producer.as_ref()
+    }
+
+    /// The estimated number of distinct non-null values in the page
+    ///
+    /// This is `None` if the estimate was not recorded (see [`crate::cardinality`])
+    pub fn approx_distinct_count(&self) -> Option<u64> {
+        self.encoding
+            .statistics
+            .as_ref()
+            .map(|statistics| statistics.approx_distinct_count)
+    }
+
+    /// The data type of the values in the page
+    ///
+    /// This is `None` unless the writer embedded the data type, see [`embedded_data_type`]
+    pub fn data_type(&self) -> Result<Option<DataType>> {
+        embedded_data_type(&self.encoding)
+    }
+}
+
 /// Metadata describing a column in a file
 ///
 /// This is typically created by reading the metadata section of a Lance file
@@ -283,6 +317,32 @@ impl ColumnInfo {
     }
 }
 
+/// Options that control how pages are decoded
+#[derive(Debug, Clone, Copy)]
+pub struct DecoderConfig {
+    /// Fail to unpack a bitpacked page if any of its padding bits are set
+    ///
+    /// Padding bits are always written as zero so a set padding bit means the page is
+    /// corrupt, although a lenient unpack still decodes the values.  This is a diagnostic
+    /// and costs some decode time so it is off by default.
+    pub strict_bitpacking: bool,
+    /// The largest size, in bytes, that a compressed buffer may decompress to
+    ///
+    /// Compressed data that claims, or turns out, to be larger than this fails to decode
+    /// with an invalid input error instead of exhausting memory (e.g. a maliciously crafted
+    /// "decompression bomb").  Defaults to [`DEFAULT_MAX_DECOMPRESSED_SIZE`].
+    pub max_decompressed_size: usize,
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self {
+            strict_bitpacking: false,
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+}
+
 /// The scheduler for decoding batches
 ///
 /// Lance decoding is done in two steps, scheduling, and decoding.  The
@@ -307,6 +367,8 @@ impl ColumnInfo {
 pub struct DecodeBatchScheduler {
     pub root_scheduler: Arc<dyn FieldScheduler>,
     pub root_fields: Fields,
+    page_cache: Option<FilePageCache>,
+    decode_stats: Option<Arc<DecodeStats>>,
 }
 
 /// Represents a series of decoder strategies
@@ -530,7 +592,7 @@ impl CoreFieldDecoderStrategy {
             .column_encoding
             .as_ref()
             .ok_or_else(|| {
-                Error::invalid_input(
+                Error::corrupt_metadata(
                     format!(
                         "the column at index {} was missing a ColumnEncoding",
                         column_info.index
@@ -556,6 +618,8 @@ impl CoreFieldDecoderStrategy {
                 // DataType::is_primitive doesn't consider these primitive but we do
                 DataType::Boolean | DataType::Null | DataType::FixedSizeBinary(_) => true,
                 DataType::FixedSizeList(inner, _) => Self::is_primitive(inner.data_type()),
+                // Dictionaries are stored as their values (possibly with a dictionary encoding)
+                DataType::Dictionary(_, value_type) => Self::is_primitive(value_type),
                 _ => false,
             }
         }
@@ -573,11 +637,12 @@ impl CoreFieldDecoderStrategy {
             file_buffers: buffers,
             positions_and_sizes: &column.buffer_offsets_and_sizes,
         };
-        Ok(Arc::new(PrimitiveFieldScheduler::new(
+        Ok(Arc::new(PrimitiveFieldScheduler::try_new(
+            column.index,
             data_type.clone(),
             column.page_infos.clone(),
             column_buffers,
-        )))
+        )?))
     }
 
     /// Helper method to verify the page encoding of a struct header column
@@ -637,7 +702,6 @@ impl FieldDecoderStrategy for CoreFieldDecoderStrategy {
                     file_buffers: buffers,
                     positions_and_sizes: &offsets_column.buffer_offsets_and_sizes,
                 };
-                let item_field_name = items_field.name().clone();
                 let (chain, items_scheduler) = chain.new_child(
                     /*child_idx=*/ 0,
                     &field.children[0],
@@ -674,22 +738,21 @@ impl FieldDecoderStrategy for CoreFieldDecoderStrategy {
                         }
                     })
                     .unzip();
-                let inner = Arc::new(PrimitiveFieldScheduler::new(
+                let inner = Arc::new(PrimitiveFieldScheduler::try_new(
+                    offsets_column.index,
                     DataType::UInt64,
                     Arc::from(inner_infos.into_boxed_slice()),
                     offsets_column_buffers,
-                )) as Arc<dyn FieldScheduler>;
+                )?) as Arc<dyn FieldScheduler>;
                 let offset_type = if matches!(data_type, DataType::List(_)) {
                     DataType::Int32
                 } else {
                     DataType::Int64
                 };
-                let items_type = items_field.data_type().clone();
                 let list_scheduler = Ok(Arc::new(ListFieldScheduler::new(
                     inner,
                     items_scheduler,
-                    item_field_name.clone(),
-                    items_type,
+                    items_field.clone(),
                     offset_type,
                     null_offset_adjustments,
                 )) as Arc<dyn FieldScheduler>);
@@ -727,7 +790,8 @@ impl FieldDecoderStrategy for CoreFieldDecoderStrategy {
 
 /// Create's a dummy ColumnInfo for the root column
 fn root_column(num_rows: u64) -> ColumnInfo {
-    let num_root_pages = num_rows.div_ceil(u32::MAX as u64);
+    // An empty file still has a single (empty) root page
+    let num_root_pages = num_rows.div_ceil(u32::MAX as u64).max(1);
     let final_page_num_rows = num_rows % (u32::MAX as u64);
     let root_pages = (0..num_root_pages)
         .map(|i| PageInfo {
@@ -740,6 +804,9 @@ fn root_column(num_rows: u64) -> ColumnInfo {
                 array_encoding: Some(pb::array_encoding::ArrayEncoding::Struct(
                     pb::SimpleStruct {},
                 )),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
             buffer_offsets_and_sizes: Arc::new([]),
         })
@@ -755,16 +822,24 @@ fn root_column(num_rows: u64) -> ColumnInfo {
 impl DecodeBatchScheduler {
     /// Creates a new decode scheduler with the expected schema and the column
     /// metadata of the file.
+    ///
+    /// `shared_dictionaries` are the dictionaries that pages of the file refer to by id,
+    /// see [`FileBuffers::shared_dictionaries`].
+    #[allow(clippy::too_many_arguments)]
     pub fn try_new<'a>(
         schema: &'a Schema,
         column_infos: &[Arc<ColumnInfo>],
         file_buffer_positions_and_sizes: &'a Vec<(u64, u64)>,
+        shared_dictionaries: &'a [(u32, ArrayRef)],
         num_rows: u64,
         decoder_strategy: &DecoderMiddlewareChain,
+        decoder_config: DecoderConfig,
         io: &Arc<dyn EncodingsIo>,
     ) -> Result<Self> {
         let buffers = FileBuffers {
             positions_and_sizes: file_buffer_positions_and_sizes,
+            shared_dictionaries,
+            decoder_config,
         };
         let arrow_schema = ArrowSchema::from(schema);
         let root_fields = arrow_schema.fields().clone();
@@ -781,6 +856,8 @@ impl DecodeBatchScheduler {
         Ok(Self {
             root_scheduler,
             root_fields,
+            page_cache: None,
+            decode_stats: None,
         })
     }
 
@@ -788,9 +865,25 @@ impl DecodeBatchScheduler {
         Self {
             root_scheduler,
             root_fields,
+            page_cache: None,
+            decode_stats: None,
         }
     }
 
+    /// Consults (and fills) the given cache of decoded pages when scheduling pages
+    pub fn with_page_cache(mut self, page_cache: FilePageCache) -> Self {
+        self.page_cache = Some(page_cache);
+        self
+    }
+
+    /// Reports the I/O and decode work done by this scheduler (and its decoders) to `stats`
+    ///
+    /// The same stats can be given to many schedulers to get totals across a whole scan
+    pub fn with_decode_stats(mut self, stats: Arc<DecodeStats>) -> Self {
+        self.decode_stats = Some(stats);
+        self
+    }
+
     fn do_schedule_ranges(
         &mut self,
         ranges: &[Range<u64>],
@@ -801,7 +894,13 @@ impl DecodeBatchScheduler {
         let rows_requested = ranges.iter().map(|r| r.end - r.start).sum::<u64>();
         trace!("Scheduling ranges {:?} ({} rows)", ranges, rows_requested);
 
+        let io = match &self.decode_stats {
+            Some(stats) => Arc::new(StatsIo::new(io, stats.clone())) as Arc<dyn EncodingsIo>,
+            None => io,
+        };
         let mut context = SchedulerContext::new(io);
+        context.page_cache = self.page_cache.clone();
+        context.decode_stats = self.decode_stats.clone();
         let maybe_root_job = self.root_scheduler.schedule_ranges(ranges, filter);
         if let Err(schedule_ranges_err) = maybe_root_job {
             schedule_action(Err(schedule_ranges_err));
@@ -926,6 +1025,32 @@ impl DecodeBatchScheduler {
     }
 }
 
+/// Picks a deterministic random sample of rows for approximate scans
+///
+/// Roughly `fraction * num_rows` rows are picked.  The same `seed` will always pick
+/// the same rows.  The returned row offsets are sorted and can be passed directly to
+/// [`DecodeBatchScheduler::schedule_take`] which only loads the pages (and, for flat
+/// pages, the byte ranges) that contain sampled rows.
+pub fn sample_rows(num_rows: u64, fraction: f64, seed: u64) -> Result<Vec<u64>> {
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(Error::invalid_input(
+            format!(
+                "sample fraction must be between 0 and 1 but got {}",
+                fraction
+            ),
+            location!(),
+        ));
+    }
+    let num_samples = (num_rows as f64 * fraction).round() as usize;
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut indices = rand::seq::index::sample(&mut rng, num_rows as usize, num_samples)
+        .into_iter()
+        .map(|idx| idx as u64)
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    Ok(indices)
+}
+
 pub struct ReadBatchTask {
     pub task: BoxFuture<'static, Result<RecordBatch>>,
     pub num_rows: u32,
@@ -1094,6 +1219,62 @@ impl BatchDecodeStream {
     }
 }
 
+/// A set of the output buffers of a [`PrimitivePageDecoder`], by index
+///
+/// This is used to load and decode only some of the buffers of a page, for example only
+/// the validity or only the values of a nullable page.  Buffers past the 64th are all
+/// represented by the last bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferMask(u64);
+
+impl BufferMask {
+    /// Every buffer
+    pub const ALL: Self = Self(u64::MAX);
+    /// No buffers
+    pub const NONE: Self = Self(0);
+
+    /// Only the buffer at `index`
+    pub fn only(index: u32) -> Self {
+        Self::NONE.with(index)
+    }
+
+    /// Adds the buffer at `index`
+    pub fn with(self, index: u32) -> Self {
+        Self(self.0 | Self::bit(index))
+    }
+
+    /// Removes the buffer at `index`
+    pub fn without(self, index: u32) -> Self {
+        Self(self.0 & !Self::bit(index))
+    }
+
+    pub fn contains(&self, index: u32) -> bool {
+        self.0 & Self::bit(index) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The buffers from `start` onwards, renumbered so that `start` is buffer 0
+    ///
+    /// This is used by decoders that place the buffers of a child decoder after their own.
+    pub fn shift(self, start: u32) -> Self {
+        let start = start.min(63);
+        let shifted = self.0 >> start;
+        // The last bit stands for all of the remaining buffers
+        if self.contains(63) {
+            Self(shifted | !(u64::MAX >> start))
+        } else {
+            Self(shifted)
+        }
+    }
+
+    fn bit(index: u32) -> u64 {
+        1 << index.min(63)
+    }
+}
+
 /// A decoder for single-column encodings of primitive data (this includes fixed size
 /// lists of primitive data)
 ///
@@ -1143,9 +1324,345 @@ pub trait PrimitivePageDecoder: Send + Sync {
         num_rows: u64,
         all_null: &mut bool,
     ) -> Result<Vec<BytesMut>>;
+
+    /// Decodes only the output buffers in `buffers`
+    ///
+    /// Buffers that are not in `buffers` are empty.  Decoders can skip the work for these
+    /// buffers and, if they were scheduled with [`PageScheduler::schedule_buffers`], the
+    /// I/O as well.  Trailing buffers that were not loaded may be missing entirely.
+    ///
+    /// The default implementation decodes every buffer and then discards the unneeded ones.
+    fn decode_buffers(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        buffers: BufferMask,
+        all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut decoded = self.decode(rows_to_skip, num_rows, all_null)?;
+        for (index, buffer) in decoded.iter_mut().enumerate() {
+            if !buffers.contains(index as u32) {
+                *buffer = BytesMut::new();
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Returns the decoded buffers without copying them, if possible
+    ///
+    /// Some decoders (e.g. flat values) already hold the requested rows in the decoded layout,
+    /// either as loaded or, for compressed pages, once the page has been decompressed.  These
+    /// decoders can return slices of that data instead of copying it into new buffers with
+    /// [`Self::decode`].  Buffers have the same layout as they would with [`Self::decode`] but
+    /// have no alignment guarantees.
+    ///
+    /// Returns `None` if this is not possible for the requested rows (e.g. the rows span more
+    /// than one buffer or the page could not be decompressed) in which case [`Self::decode`]
+    /// should be used instead.
+    fn decode_shared(&self, _rows_to_skip: u64, _num_rows: u64) -> Option<Vec<Bytes>> {
+        None
+    }
+
+    /// Returns true if rows can be skipped without decoding them
+    ///
+    /// This is true when [`Self::decode`] can start at any row without decoding the rows
+    /// before it (e.g. flat or bitpacked values) and false when the rows before it must be
+    /// decoded first (e.g. the data is compressed).
+    fn can_skip(&self) -> bool {
+        false
+    }
+
+    /// Decodes the rows by appending them to `dest`
+    ///
+    /// This is only supported by decodings that produce a single buffer (e.g. fixed-width
+    /// values).  Unlike [`Self::decode`] the caller owns the allocation.  A [`MutableBuffer`]
+    /// is aligned for any Arrow type and can be turned into an Arrow buffer without a copy,
+    /// so a buffer sized for several pages can be filled by decoding each page in turn.
+    ///
+    /// The default implementation decodes into a temporary buffer and copies it into `dest`.
+    fn decode_into(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        dest: &mut MutableBuffer,
+    ) -> Result<()> {
+        let mut all_null = false;
+        let buffers = self.decode(rows_to_skip, num_rows, &mut all_null)?;
+        match buffers.as_slice() {
+            [buffer] => {
+                dest.extend_from_slice(buffer);
+                Ok(())
+            }
+            _ => Err(Error::NotSupported {
+                source: format!(
+                    "decoding into a single buffer is not supported by decoders with {} output buffers",
+                    buffers.len()
+                )
+                .into(),
+                location: location!(),
+            }),
+        }
+    }
+
+    /// Decodes several ranges of rows, returning one buffer for each range
+    ///
+    /// Like [`Self::decode_into`] this is only supported by decodings that produce a single
+    /// buffer.  Rows are counted from the first loaded row, as with `rows_to_skip`, and the
+    /// ranges can be in any order.  This is useful when the rows of each range go to a
+    /// different output (e.g. one per group) and saves tracking the boundaries of the ranges
+    /// in a combined buffer.
+    ///
+    /// The default implementation decodes each range on its own.
+    fn decode_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<BytesMut>> {
+        ranges
+            .iter()
+            .map(|range| {
+                let mut all_null = false;
+                let mut buffers =
+                    self.decode(range.start, range.end - range.start, &mut all_null)?;
+                match buffers.len() {
+                    1 => Ok(buffers.pop().unwrap()),
+                    num_buffers => Err(Error::NotSupported {
+                        source: format!(
+                            "decoding ranges is not supported by decoders with {} output buffers",
+                            num_buffers
+                        )
+                        .into(),
+                        location: location!(),
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    /// Decodes the `num_rows` rows after the position saved in `cursor` and moves the
+    /// cursor past them
+    ///
+    /// This lets a consumer decode a page in pieces, possibly across async yields, by
+    /// holding on to a [`DecodeCursor`] between calls.  Decoders that produce a single
+    /// buffer remember where the next row is in their loaded buffers, so resuming does not
+    /// walk the buffers again.  A cursor must only be used with the decoder it was first
+    /// passed to.  The cursor is not moved if decoding fails.
+    ///
+    /// The default implementation decodes the rows with [`Self::decode`].
+    fn decode_more(&self, cursor: &mut DecodeCursor, num_rows: u64) -> Result<Vec<BytesMut>> {
+        let mut all_null = false;
+        let buffers = self.decode(cursor.row, num_rows, &mut all_null)?;
+        cursor.row += num_rows;
+        Ok(buffers)
+    }
+
+    /// Decodes the rows into an Arrow dictionary array without expanding the dictionary
+    ///
+    /// This is used when the reader asks for a dictionary type.  Returns `None` if the page
+    /// is not dictionary encoded, in which case the values are decoded with [`Self::decode`]
+    /// and then dictionary encoded.
+    fn decode_dictionary(&self, _rows_to_skip: u64, _num_rows: u64) -> Result<Option<ArrayRef>> {
+        Ok(None)
+    }
+
     fn num_buffers(&self) -> u32;
 }
 
+/// The result of [`LogicalPageDecoder::skip`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipResult {
+    /// The rows were skipped without decoding them
+    Skipped,
+    /// The rows can only be skipped by decoding them
+    ///
+    /// The decoder has not moved and the caller should drain (and discard) the rows instead
+    RequiresDecode,
+}
+
+/// The position of the next row to decode with [`PrimitivePageDecoder::decode_more`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodeCursor {
+    /// The next row, counted from the first loaded row
+    pub(crate) row: u64,
+    /// The index of the loaded buffer holding the next row and the byte offset of the row
+    /// in that buffer, `None` until a decoder has found it
+    pub(crate) buffer_position: Option<(usize, u64)>,
+}
+
+impl DecodeCursor {
+    /// Creates a cursor that starts after skipping `rows_to_skip` loaded rows
+    pub fn new(rows_to_skip: u64) -> Self {
+        Self {
+            row: rows_to_skip,
+            buffer_position: None,
+        }
+    }
+
+    /// The next row to decode, counted from the first loaded row
+    pub fn row(&self) -> u64 {
+        self.row
+    }
+}
+
+/// The alignment, in bytes, of the tiles produced by a [`TileDecoder`]
+pub const TILE_ALIGNMENT: usize = 64;
+
+/// A tile of values decoded by a [`TileDecoder`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile<'a> {
+    /// The values of the tile, [`TILE_ALIGNMENT`] aligned and always the size of a full tile
+    pub values: &'a [u8],
+    /// The number of decoded values in the tile, the rest of a final partial tile is zeroed
+    pub num_values: u64,
+}
+
+/// Decodes rows from a page in fixed-size, aligned tiles
+///
+/// Vectorized kernels can operate on each tile without copying it or checking its
+/// alignment.  Every tile has room for `tile_size` values, the final tile is zero-padded
+/// if there are not enough rows to fill it.  The tiles are decoded with
+/// [`PrimitivePageDecoder::decode_into`] into a single buffer that is reused from one tile
+/// to the next, so only decodings that produce a single buffer are supported.
+pub struct TileDecoder<'a> {
+    decoder: &'a dyn PrimitivePageDecoder,
+    tile_size: u64,
+    tile_bytes: usize,
+    next_row: u64,
+    end_row: u64,
+    tile: MutableBuffer,
+}
+
+impl<'a> TileDecoder<'a> {
+    /// Creates a decoder for `num_rows` rows, after skipping `rows_to_skip` loaded rows
+    ///
+    /// `bits_per_value` is the width of the decoded values (e.g. 1 for booleans)
+    pub fn try_new(
+        decoder: &'a dyn PrimitivePageDecoder,
+        rows_to_skip: u64,
+        num_rows: u64,
+        tile_size: u64,
+        bits_per_value: u64,
+    ) -> Result<Self> {
+        if tile_size == 0 {
+            return Err(Error::invalid_input(
+                "the tile size must be positive",
+                location!(),
+            ));
+        }
+        if bits_per_value == 0 {
+            return Err(Error::invalid_input(
+                "the values of a tile must be at least one bit wide",
+                location!(),
+            ));
+        }
+        Ok(Self {
+            decoder,
+            tile_size,
+            tile_bytes: (tile_size * bits_per_value).div_ceil(8) as usize,
+            next_row: rows_to_skip,
+            end_row: rows_to_skip + num_rows,
+            tile: MutableBuffer::new(0),
+        })
+    }
+
+    /// The number of tiles that have not been decoded yet
+    pub fn remaining_tiles(&self) -> u64 {
+        (self.end_row - self.next_row).div_ceil(self.tile_size)
+    }
+
+    /// Decodes the next tile, returns `None` once all of the rows have been decoded
+    pub fn next_tile(&mut self) -> Result<Option<Tile<'_>>> {
+        if self.next_row >= self.end_row {
+            return Ok(None);
+        }
+        let num_values = self.tile_size.min(self.end_row - self.next_row);
+        self.tile.truncate(0);
+        self.decoder
+            .decode_into(self.next_row, num_values, &mut self.tile)?;
+        if self.tile.len() < self.tile_bytes {
+            self.tile.resize(self.tile_bytes, 0);
+        }
+        self.next_row += num_values;
+        debug_assert_eq!(self.tile.as_ptr() as usize % TILE_ALIGNMENT, 0);
+        Ok(Some(Tile {
+            values: self.tile.as_slice(),
+            num_values,
+        }))
+    }
+}
+
+/// A rough classification of the CPU work needed to decode values from a page
+///
+/// Variants are ordered from cheapest to most expensive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DecodeCpuClass {
+    /// The fetched bytes are copied into the output as they are
+    #[default]
+    Copy,
+    /// Each value must be unpacked or reconstructed (e.g. bitpacked or delta encoded values)
+    Unpack,
+    /// The fetched bytes must be decompressed before any value can be read
+    Decompress,
+}
+
+impl DecodeCpuClass {
+    /// A rough estimate of how many fetched bytes a single core can decode per second
+    ///
+    /// These are ballpark figures for comparing the classes (e.g. to balance CPU work
+    /// against I/O), they do not predict how long a particular decode will take.
+    pub fn bytes_per_second(&self) -> u64 {
+        match self {
+            Self::Copy => 8 * 1024 * 1024 * 1024,
+            Self::Unpack => 2 * 1024 * 1024 * 1024,
+            Self::Decompress => 512 * 1024 * 1024,
+        }
+    }
+}
+
+/// An estimate of the cost of decoding some rows from a page
+///
+/// This is calculated from the page metadata alone and does not require any I/O
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeCost {
+    /// The number of bytes that must be fetched from storage
+    pub bytes_to_fetch: u64,
+    /// The most expensive kind of CPU work needed to decode the rows
+    pub cpu_class: DecodeCpuClass,
+}
+
+impl DecodeCost {
+    pub fn new(bytes_to_fetch: u64, cpu_class: DecodeCpuClass) -> Self {
+        Self {
+            bytes_to_fetch,
+            cpu_class,
+        }
+    }
+
+    /// Combines two costs that are both paid as part of the same decode (e.g. the cost of the
+    /// validity and the values of a page, or the costs of several pages)
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            bytes_to_fetch: self.bytes_to_fetch + other.bytes_to_fetch,
+            cpu_class: self.cpu_class.max(other.cpu_class),
+        }
+    }
+
+    /// Returns a copy of this cost that requires at least `cpu_class` work
+    pub fn with_min_cpu_class(self, cpu_class: DecodeCpuClass) -> Self {
+        Self {
+            bytes_to_fetch: self.bytes_to_fetch,
+            cpu_class: self.cpu_class.max(cpu_class),
+        }
+    }
+
+    /// A rough estimate of the CPU time needed to decode the fetched bytes on one core
+    ///
+    /// All of the bytes are assumed to need the work of [`Self::cpu_class`], see
+    /// [`DecodeCpuClass::bytes_per_second`].  This is meant for scheduling decisions, such
+    /// as how to split decode work between threads.
+    pub fn estimate_decode_time(&self) -> std::time::Duration {
+        let nanos =
+            self.bytes_to_fetch as u128 * 1_000_000_000 / self.cpu_class.bytes_per_second() as u128;
+        std::time::Duration::from_nanos(nanos as u64)
+    }
+}
+
 /// A scheduler for single-column encodings of primitive data
 ///
 /// The scheduler is responsible for calculating what I/O is needed for the requested rows
@@ -1172,6 +1689,27 @@ pub trait PageScheduler: Send + Sync + std::fmt::Debug {
         scheduler: &Arc<dyn EncodingsIo>,
         top_level_row: u64,
     ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>>;
+
+    /// Schedules the I/O needed to decode the output buffers in `buffers`
+    ///
+    /// Like [`Self::schedule_ranges`] but the returned decoder may only be able to decode the
+    /// buffers in `buffers` with [`PrimitivePageDecoder::decode_buffers`].
+    ///
+    /// The default implementation loads every buffer.
+    fn schedule_buffers(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+        _buffers: BufferMask,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        self.schedule_ranges(ranges, scheduler, top_level_row)
+    }
+
+    /// Estimates the cost of decoding the requested ranges without performing any I/O
+    ///
+    /// The ranges have the same meaning as in [`Self::schedule_ranges`]
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost;
 }
 
 /// Contains the context for a scheduler
@@ -1181,6 +1719,8 @@ pub struct SchedulerContext {
     name: String,
     path: Vec<u32>,
     path_names: Vec<String>,
+    page_cache: Option<FilePageCache>,
+    decode_stats: Option<Arc<DecodeStats>>,
 }
 
 pub struct ScopedSchedulerContext<'a> {
@@ -1202,6 +1742,8 @@ impl SchedulerContext {
             name: "".to_string(),
             path: Vec::new(),
             path_names: Vec::new(),
+            page_cache: None,
+            decode_stats: None,
         }
     }
 
@@ -1209,6 +1751,16 @@ impl SchedulerContext {
         &self.io
     }
 
+    /// The cache of decoded pages to consult when scheduling pages, if any
+    pub fn page_cache(&self) -> Option<&FilePageCache> {
+        self.page_cache.as_ref()
+    }
+
+    /// The stats that decoders should report into, if any
+    pub fn decode_stats(&self) -> Option<&Arc<DecodeStats>> {
+        self.decode_stats.as_ref()
+    }
+
     pub fn push(&mut self, name: &str, index: u32) -> ScopedSchedulerContext {
         self.path.push(index);
         self.path_names.push(name.to_string());
@@ -1397,6 +1949,15 @@ pub trait LogicalPageDecoder: std::fmt::Debug + Send {
     fn wait(&mut self, num_rows: u64) -> BoxFuture<Result<()>>;
     /// Creates a task to decode `num_rows` of data into an array
     fn drain(&mut self, num_rows: u64) -> Result<NextDecodeTask>;
+    /// Advances past `num_rows` of data without decoding them, if that is cheap
+    ///
+    /// The rows must have been "waited".  If the rows cannot be skipped without decoding
+    /// them then [`SkipResult::RequiresDecode`] is returned and nothing is skipped.
+    ///
+    /// The default implementation never skips.
+    fn skip(&mut self, _num_rows: u64) -> Result<SkipResult> {
+        Ok(SkipResult::RequiresDecode)
+    }
     /// The number of rows that are in the page but haven't yet been "waited"
     fn unawaited(&self) -> u64;
     /// The number of rows that have been "waited" but not yet decoded
@@ -1405,19 +1966,372 @@ pub trait LogicalPageDecoder: std::fmt::Debug + Send {
     fn data_type(&self) -> &DataType;
 }
 
+/// An I/O service over the buffers of a single page that are already in memory
+///
+/// Each buffer is given a position as if the buffers were laid out one after the other
+struct PageBuffersIo {
+    buffers: Vec<Bytes>,
+    positions_and_sizes: Vec<(u64, u64)>,
+}
+
+impl PageBuffersIo {
+    fn new(buffers: &[Bytes]) -> Self {
+        let mut position = 0;
+        let positions_and_sizes = buffers
+            .iter()
+            .map(|buffer| {
+                let size = buffer.len() as u64;
+                position += size;
+                (position - size, size)
+            })
+            .collect();
+        Self {
+            buffers: buffers.to_vec(),
+            positions_and_sizes,
+        }
+    }
+
+    fn satisfy_request(&self, range: Range<u64>) -> Result<Bytes> {
+        let total_size = self
+            .positions_and_sizes
+            .last()
+            .map_or(0, |(position, size)| position + size);
+        if range.end > total_size {
+            return Err(Error::encoding(
+                EncodingError::BufferTooShort {
+                    context: format!("reading bytes {:?} of the page", range),
+                    expected: range.end,
+                    actual: total_size,
+                },
+                location!(),
+            ));
+        }
+        // Find the first buffer that ends after the start of the range
+        let first_index = self
+            .positions_and_sizes
+            .partition_point(|(position, size)| position + size <= range.start);
+        if first_index == self.buffers.len() {
+            return Ok(Bytes::new());
+        }
+        let (position, size) = self.positions_and_sizes[first_index];
+        if range.end <= position + size {
+            let start = (range.start - position) as usize;
+            let end = (range.end - position) as usize;
+            return Ok(self.buffers[first_index].slice(start..end));
+        }
+        // Requests for adjacent buffers may be coalesced and these have to be copied
+        let mut bytes = BytesMut::with_capacity((range.end - range.start) as usize);
+        for (buffer, (position, size)) in self.buffers[first_index..]
+            .iter()
+            .zip(&self.positions_and_sizes[first_index..])
+        {
+            if *position >= range.end {
+                break;
+            }
+            let start = range.start.saturating_sub(*position) as usize;
+            let end = (range.end.min(position + size) - position) as usize;
+            bytes.extend_from_slice(&buffer[start..end]);
+        }
+        Ok(bytes.freeze())
+    }
+}
+
+impl EncodingsIo for PageBuffersIo {
+    fn submit_request(
+        &self,
+        ranges: Vec<Range<u64>>,
+        _priority: u64,
+    ) -> BoxFuture<'static, Result<Vec<Bytes>>> {
+        std::future::ready(
+            ranges
+                .into_iter()
+                .map(|range| self.satisfy_request(range))
+                .collect(),
+        )
+        .boxed()
+    }
+}
+
+// Returns an error if decoding the encoding needs an async runtime or data that is not
+// part of the page
+fn check_decodable_in_memory(encoding: &pb::ArrayEncoding) -> Result<()> {
+    use pb::array_encoding::ArrayEncoding;
+    use pb::nullable::Nullability;
+    let check_buffer = |buffer: Option<&pb::Buffer>| {
+        match buffer {
+        Some(buffer) if buffer.buffer_type != pb::buffer::BufferType::Page as i32 => {
+            Err(Error::NotSupported {
+                source: "pages that reference column or file buffers cannot be decoded from the page buffers alone".into(),
+                location: location!(),
+            })
+        }
+        _ => Ok(()),
+    }
+    };
+    let check_nested =
+        |nested: Option<&pb::ArrayEncoding>| nested.map_or(Ok(()), check_decodable_in_memory);
+    match encoding.array_encoding.as_ref() {
+        Some(ArrayEncoding::Flat(flat)) => check_buffer(flat.buffer.as_ref()),
+        Some(ArrayEncoding::Bitpacked(bitpacked)) => check_buffer(bitpacked.buffer.as_ref()),
+        Some(ArrayEncoding::ChunkedBitpacked(chunked)) => check_buffer(chunked.buffer.as_ref()),
+        Some(ArrayEncoding::BooleanRle(boolean_rle)) => check_buffer(boolean_rle.buffer.as_ref()),
+        Some(ArrayEncoding::TransformPipeline(pipeline)) => check_buffer(pipeline.buffer.as_ref()),
+        Some(ArrayEncoding::Nullable(nullable)) => match nullable.nullability.as_ref() {
+            Some(Nullability::NoNulls(no_nulls)) => check_nested(no_nulls.values.as_deref()),
+            Some(Nullability::SomeNulls(some_nulls)) => {
+                check_nested(some_nulls.validity.as_deref())?;
+                check_nested(some_nulls.values.as_deref())
+            }
+            _ => Ok(()),
+        },
+        Some(ArrayEncoding::DeltaOfDelta(delta_of_delta)) => {
+            check_nested(delta_of_delta.deltas.as_deref())
+        }
+        Some(ArrayEncoding::Quantized(quantized)) => check_nested(quantized.values.as_deref()),
+        Some(ArrayEncoding::Sparse(sparse)) => {
+            check_nested(sparse.positions.as_deref())?;
+            check_nested(sparse.values.as_deref())
+        }
+        Some(ArrayEncoding::BloomFiltered(bloom_filtered)) => {
+            check_nested(bloom_filtered.values.as_deref())
+        }
+        Some(ArrayEncoding::FixedSizeList(fixed_size_list)) => {
+            check_nested(fixed_size_list.items.as_deref())
+        }
+        Some(ArrayEncoding::List(list)) => check_nested(list.offsets.as_deref()),
+        Some(ArrayEncoding::Binary(_) | ArrayEncoding::Fsst(_) | ArrayEncoding::Dictionary(_)) => {
+            Err(Error::NotSupported {
+                source: "pages with binary, FSST or dictionary encodings can only be decoded by the asynchronous decoder".into(),
+                location: location!(),
+            })
+        }
+        // Unknown and struct encodings are reported when creating the scheduler
+        _ => Ok(()),
+    }
+}
+
+/// Decodes rows from a single page whose buffers are already in memory
+///
+/// This is a synchronous alternative to [`DecodeBatchScheduler`] for callers that already
+/// hold the page (e.g. a memory mapped file) and do not want to set up an async runtime
+/// and an [`EncodingsIo`].  `buffers` are the page buffers, in order, as described by
+/// [`PageInfo::buffer_offsets_and_sizes`].  `data_type` is the type of the field that the
+/// page belongs to and `range` the rows of the page to decode.
+///
+/// Only pages of fixed-width values (flat, compressed, bitpacked, nullable, fixed size
+/// list, delta-of-delta, quantized and sparse encodings, possibly with a Bloom filter) can
+/// be decoded this way.  Other pages return a [`Error::NotSupported`] error and must be
+/// decoded with the async decoder.
+pub fn decode_page(
+    encoding: &pb::ArrayEncoding,
+    buffers: &[Bytes],
+    data_type: &DataType,
+    range: Range<u64>,
+) -> Result<ArrayRef> {
+    check_decodable_in_memory(encoding)?;
+    let io = PageBuffersIo::new(buffers);
+    let page_buffers = PageBuffers {
+        column_buffers: ColumnBuffers {
+            file_buffers: FileBuffers {
+                positions_and_sizes: &[],
+                shared_dictionaries: &[],
+                decoder_config: Default::default(),
+            },
+            positions_and_sizes: &[],
+        },
+        positions_and_sizes: &io.positions_and_sizes,
+    };
+    let scheduler = decoder_from_array_encoding(encoding, &page_buffers, data_type)?;
+    let io = Arc::new(io) as Arc<dyn EncodingsIo>;
+    let num_rows = range.end - range.start;
+    // All of the data is in memory and so there is no I/O to wait on and no runtime is needed
+    let physical_decoder = futures::executor::block_on(scheduler.schedule_ranges(
+        std::slice::from_ref(&range),
+        &io,
+        0,
+    ))?;
+    let (array, _) = decode_primitive_page(physical_decoder.as_ref(), data_type, 0, num_rows)?;
+    Ok(array)
+}
+
+/// Returns the data type recorded in the encoding of a page, if any
+///
+/// Pages only record the data type of their values if the writer was asked to embed it
+/// (see [`crate::encoder::ArrayEncodingStrategy::embeds_data_type`]).  Such pages are
+/// self-describing and can be decoded with [`decode_self_describing_page`].
+pub fn embedded_data_type(encoding: &pb::ArrayEncoding) -> Result<Option<DataType>> {
+    encoding
+        .data_type
+        .as_deref()
+        .map(|data_type| DataType::try_from(&LogicalType::from(data_type)))
+        .transpose()
+}
+
+/// Decodes rows from a single self-describing page whose buffers are already in memory
+///
+/// This is the same as [`decode_page`] except that the data type is taken from the page
+/// itself (see [`embedded_data_type`]) and so the page can be decoded without the schema
+/// of the file.  Pages that do not record their data type return an error.
+pub fn decode_self_describing_page(
+    encoding: &pb::ArrayEncoding,
+    buffers: &[Bytes],
+    range: Range<u64>,
+) -> Result<ArrayRef> {
+    let data_type = embedded_data_type(encoding)?.ok_or_else(|| {
+        Error::invalid_input("the page does not record its data type", location!())
+    })?;
+    decode_page(encoding, buffers, &data_type, range)
+}
+
+/// Decodes rows from a single in-memory page as raw little-endian bytes
+///
+/// This is the same as [`decode_page`] but returns the bytes of each value instead of an
+/// Arrow array, which is all that is needed for things like hashing or exporting the values.
+/// The result has one [`Bytes`] per entry in `rows`, each holding the little-endian bytes of
+/// the value (e.g. 4 bytes for an `Int32` column).  Bitpacked values are unpacked to the full
+/// width of `data_type`.  The bytes of null values are unspecified (usually zeros).
+///
+/// `data_type` must be a fixed-width primitive type and `rows` must be sorted in increasing
+/// order.
+pub fn decode_raw(
+    encoding: &pb::ArrayEncoding,
+    buffers: &[Bytes],
+    data_type: &DataType,
+    rows: &[u64],
+) -> Result<Vec<Bytes>> {
+    let bytes_per_value = data_type
+        .primitive_width()
+        .ok_or_else(|| Error::NotSupported {
+            source: format!("raw decoding of {} values", data_type).into(),
+            location: location!(),
+        })?;
+    if rows.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(Error::invalid_input(
+            "rows to decode must be sorted in increasing order",
+            location!(),
+        ));
+    }
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    check_decodable_in_memory(encoding)?;
+
+    // Neighbouring rows are decoded together
+    let mut ranges: Vec<Range<u64>> = Vec::new();
+    for &row in rows {
+        match ranges.last_mut() {
+            Some(range) if range.end == row => range.end += 1,
+            _ => ranges.push(row..row + 1),
+        }
+    }
+
+    let io = PageBuffersIo::new(buffers);
+    let page_buffers = PageBuffers {
+        column_buffers: ColumnBuffers {
+            file_buffers: FileBuffers {
+                positions_and_sizes: &[],
+                shared_dictionaries: &[],
+                decoder_config: Default::default(),
+            },
+            positions_and_sizes: &[],
+        },
+        positions_and_sizes: &io.positions_and_sizes,
+    };
+    let scheduler = decoder_from_array_encoding(encoding, &page_buffers, data_type)?;
+    let io = Arc::new(io) as Arc<dyn EncodingsIo>;
+    let physical_decoder = futures::executor::block_on(scheduler.schedule_ranges(&ranges, &io, 0))?;
+    let mut all_null = false;
+    let decoded = physical_decoder.decode(0, rows.len() as u64, &mut all_null)?;
+    let values = if all_null {
+        BytesMut::zeroed(rows.len() * bytes_per_value)
+    } else {
+        // Nullable pages put the validity buffer before the values
+        match decoded.into_iter().last() {
+            Some(values) if values.len() == rows.len() * bytes_per_value => values,
+            _ => {
+                return Err(Error::NotSupported {
+                    source: format!(
+                        "raw decoding of a page that does not decode to {} byte values",
+                        bytes_per_value
+                    )
+                    .into(),
+                    location: location!(),
+                })
+            }
+        }
+    };
+    let mut values = values.freeze();
+    Ok((0..rows.len())
+        .map(|_| to_little_endian(values.split_to(bytes_per_value)))
+        .collect())
+}
+
+// Decoded values are in native byte order
+#[cfg(target_endian = "little")]
+fn to_little_endian(value: Bytes) -> Bytes {
+    value
+}
+
+#[cfg(target_endian = "big")]
+fn to_little_endian(value: Bytes) -> Bytes {
+    value.iter().rev().copied().collect()
+}
+
+/// A deferred decode of some rows from a loaded page
+///
+/// Calling the closure performs the CPU work (e.g. unpacking or decompressing) and returns
+/// the decoded array.
+pub type DecodeThunk = Box<dyn FnOnce() -> Result<ArrayRef> + Send>;
+
+/// Waits for a page to load and returns a [`DecodeThunk`] that decodes rows from it
+///
+/// `physical_decoder` is the future returned by [`PageScheduler::schedule_ranges`].  The
+/// returned future resolves once the I/O is complete but no decoding is done until the
+/// thunk is called.  This lets the caller decide when (and on which thread) the CPU-heavy
+/// part of decoding runs.  `rows_to_skip` and `num_rows` are relative to the rows that were
+/// scheduled.
+pub fn decode_lazily(
+    physical_decoder: BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>>,
+    data_type: DataType,
+    rows_to_skip: u64,
+    num_rows: u64,
+) -> BoxFuture<'static, Result<DecodeThunk>> {
+    async move {
+        let physical_decoder = physical_decoder.await?;
+        let thunk: DecodeThunk = Box::new(move || {
+            decode_primitive_page(
+                physical_decoder.as_ref(),
+                &data_type,
+                rows_to_skip,
+                num_rows,
+            )
+            .map(|(array, _)| array)
+        });
+        Ok(thunk)
+    }
+    .boxed()
+}
+
 /// Decodes a batch of data from an in-memory structure created by [`crate::encoder::encode_batch`]
 pub async fn decode_batch(
     batch: &EncodedBatch,
     filter: &FilterExpression,
     field_decoder_strategy: &DecoderMiddlewareChain,
 ) -> Result<RecordBatch> {
+    if batch.num_rows == 0 {
+        // There are no pages to schedule and the stream would never yield a batch
+        let arrow_schema = ArrowSchema::from(batch.schema.as_ref());
+        return Ok(RecordBatch::new_empty(Arc::new(arrow_schema)));
+    }
     let io_scheduler = Arc::new(BufferScheduler::new(batch.data.clone())) as Arc<dyn EncodingsIo>;
     let mut decode_scheduler = DecodeBatchScheduler::try_new(
         batch.schema.as_ref(),
         &batch.page_table,
         &vec![],
+        &[],
         batch.num_rows,
         field_decoder_strategy,
+        DecoderConfig::default(),
         &io_scheduler,
     )?;
     let (tx, rx) = unbounded_channel();
@@ -1427,3 +2341,369 @@ pub async fn decode_batch(
     let stream = BatchDecodeStream::new(rx, batch.num_rows as u32, batch.num_rows, root_decoder);
     stream.into_stream().next().await.unwrap().task.await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use arrow_array::{
+        ArrayRef, BooleanArray, FixedSizeListArray, Float32Array, Int32Array, RecordBatch,
+        StringArray, UInt32Array,
+    };
+    use arrow_schema::{DataType, Field, Schema};
+    use bytes::{Bytes, BytesMut};
+    use futures::{future::BoxFuture, FutureExt, TryFutureExt};
+    use lance_core::Error;
+
+    use crate::{
+        encoder::{
+            encode_batch, ArrayEncoder, CoreArrayEncodingStrategy, CoreFieldEncodingStrategy,
+        },
+        encodings::{
+            logical::primitive::PrimitiveFieldDecoder,
+            physical::{
+                basic::BasicEncoder,
+                value::{CompressionScheme, ValueEncoderBuilder},
+            },
+        },
+        testing::InMemoryPage,
+    };
+
+    use super::{
+        decode_lazily, decode_page, decode_raw, decode_self_describing_page, BufferMask,
+        LogicalPageDecoder, PageInfo, PrimitivePageDecoder, SkipResult,
+    };
+
+    #[test]
+    fn test_buffer_mask() {
+        let mask = BufferMask::only(0).with(2);
+        assert!(mask.contains(0) && !mask.contains(1) && mask.contains(2));
+        assert_eq!(mask.shift(1), BufferMask::only(1));
+        assert!(mask.shift(3).is_empty());
+        assert!(BufferMask::ALL.without(0).shift(1).contains(100));
+        assert!(!BufferMask::ALL.without(0).contains(0));
+        assert_eq!(BufferMask::ALL.shift(70), BufferMask::ALL);
+    }
+
+    #[test]
+    fn test_decode_page() {
+        let ints = Arc::new(Int32Array::from_iter(
+            (0..1000).map(|i| (i % 7 != 0).then_some(i)),
+        )) as ArrayRef;
+        let bools =
+            Arc::new(BooleanArray::from_iter((0..1000).map(|i| Some(i % 3 == 0)))) as ArrayRef;
+        let vectors = Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            4,
+            Arc::new(Float32Array::from_iter_values((0..4000).map(|i| i as f32))),
+            None,
+        )) as ArrayRef;
+        let strings = Arc::new(StringArray::from_iter_values(
+            (0..1000).map(|i| format!("s{}", i)),
+        )) as ArrayRef;
+        let columns = vec![ints, bools, vectors, strings];
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .enumerate()
+                .map(|(idx, array)| {
+                    Field::new(format!("c{}", idx), array.data_type().clone(), true)
+                })
+                .collect::<Vec<_>>(),
+        ));
+        let batch = RecordBatch::try_new(schema.clone(), columns.clone()).unwrap();
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap());
+
+        // Pages are written by the normal (async) writer but decoded without a runtime
+        let encoded = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(encode_batch(
+                &batch,
+                lance_schema,
+                &CoreFieldEncodingStrategy::default(),
+                4096,
+            ))
+            .unwrap();
+
+        for (column_info, expected) in encoded.page_table.iter().zip(&columns) {
+            let data_type = expected.data_type();
+            let mut page_start = 0;
+            for page in column_info.page_infos.iter() {
+                let buffers = page
+                    .buffer_offsets_and_sizes
+                    .iter()
+                    .map(|(offset, size)| {
+                        encoded
+                            .data
+                            .slice(*offset as usize..(*offset + *size) as usize)
+                    })
+                    .collect::<Vec<_>>();
+                let result = decode_page(&page.encoding, &buffers, data_type, 3..page.num_rows);
+                if *data_type == DataType::Utf8 {
+                    assert!(matches!(result, Err(Error::NotSupported { .. })));
+                    continue;
+                }
+                let expected = expected.slice(page_start as usize + 3, page.num_rows as usize - 3);
+                assert_eq!(result.unwrap().as_ref(), expected.as_ref());
+                page_start += page.num_rows;
+            }
+            if *data_type != DataType::Utf8 {
+                assert_eq!(page_start, 1000);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_self_describing_page() {
+        let ints = Arc::new(Int32Array::from_iter(
+            (0..1000).map(|i| (i % 7 != 0).then_some(i)),
+        )) as ArrayRef;
+        let vectors = Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            4,
+            Arc::new(Float32Array::from_iter_values((0..4000).map(|i| i as f32))),
+            None,
+        )) as ArrayRef;
+        let columns = vec![ints, vectors];
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ints", columns[0].data_type().clone(), true),
+            Field::new("vectors", columns[1].data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(schema.clone(), columns.clone()).unwrap();
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap());
+
+        let encode = |strategy: CoreArrayEncodingStrategy| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(encode_batch(
+                    &batch,
+                    lance_schema.clone(),
+                    &CoreFieldEncodingStrategy::new(Arc::new(strategy)),
+                    4096,
+                ))
+                .unwrap()
+        };
+        let page_buffers = |data: &Bytes, page: &PageInfo| {
+            page.buffer_offsets_and_sizes
+                .iter()
+                .map(|(offset, size)| data.slice(*offset as usize..(*offset + *size) as usize))
+                .collect::<Vec<_>>()
+        };
+
+        let encoded = encode(CoreArrayEncodingStrategy::default().with_embedded_data_type());
+        for (column_info, expected) in encoded.page_table.iter().zip(&columns) {
+            let page = &column_info.page_infos[0];
+            // The type is recovered from the page alone
+            assert_eq!(
+                page.data_type().unwrap().as_ref(),
+                Some(expected.data_type())
+            );
+            let buffers = page_buffers(&encoded.data, page);
+            let decoded =
+                decode_self_describing_page(&page.encoding, &buffers, 0..page.num_rows).unwrap();
+            assert_eq!(
+                decoded.as_ref(),
+                expected.slice(0, page.num_rows as usize).as_ref()
+            );
+        }
+
+        // By default the type is not embedded
+        let encoded = encode(CoreArrayEncodingStrategy::default());
+        let page = &encoded.page_table[0].page_infos[0];
+        assert_eq!(page.data_type().unwrap(), None);
+        let buffers = page_buffers(&encoded.data, page);
+        assert!(matches!(
+            decode_self_describing_page(&page.encoding, &buffers, 0..page.num_rows),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_page_bitpacked_and_compressed() {
+        let values = Arc::new(UInt32Array::from_iter_values((0..1000).map(|i| i % 50))) as ArrayRef;
+        for builder in [
+            ValueEncoderBuilder::default().enable_bitpacking(true),
+            ValueEncoderBuilder::default().compression(CompressionScheme::Zstd),
+        ] {
+            let encoder = BasicEncoder::new(Box::new(builder.build(&DataType::UInt32).unwrap()));
+            let page = InMemoryPage::new(&encoder.encode(&[values.clone()], &mut 0).unwrap());
+            let buffers = page.buffers();
+            let decoded =
+                decode_page(&page.encoding, &buffers, &DataType::UInt32, 100..900).unwrap();
+            assert_eq!(decoded.as_ref(), values.slice(100, 800).as_ref());
+        }
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        let values = Arc::new(Int32Array::from(vec![1, 2, 300, 70000, 5, 6])) as ArrayRef;
+        let rows = [0, 1, 2, 3, 5];
+        // The little-endian encoding of the selected values
+        let expected: Vec<&[u8]> = vec![
+            &[0x01, 0x00, 0x00, 0x00],
+            &[0x02, 0x00, 0x00, 0x00],
+            &[0x2C, 0x01, 0x00, 0x00],
+            &[0x70, 0x11, 0x01, 0x00],
+            &[0x06, 0x00, 0x00, 0x00],
+        ];
+        for builder in [
+            ValueEncoderBuilder::default(),
+            ValueEncoderBuilder::default().enable_bitpacking(true),
+        ] {
+            let encoder = BasicEncoder::new(Box::new(builder.build(&DataType::Int32).unwrap()));
+            let page = InMemoryPage::new(
+                &encoder
+                    .encode(std::slice::from_ref(&values), &mut 0)
+                    .unwrap(),
+            );
+            let buffers = page.buffers();
+            let raw = decode_raw(&page.encoding, &buffers, &DataType::Int32, &rows).unwrap();
+            assert_eq!(raw.iter().map(|b| b.as_ref()).collect::<Vec<_>>(), expected);
+
+            assert!(decode_raw(&page.encoding, &buffers, &DataType::Int32, &[])
+                .unwrap()
+                .is_empty());
+            assert!(matches!(
+                decode_raw(&page.encoding, &buffers, &DataType::Int32, &[3, 1]),
+                Err(Error::InvalidInput { .. })
+            ));
+            assert!(matches!(
+                decode_raw(&page.encoding, &buffers, &DataType::Utf8, &[0]),
+                Err(Error::NotSupported { .. })
+            ));
+        }
+    }
+
+    struct CountingDecoder {
+        inner: Box<dyn PrimitivePageDecoder>,
+        num_decodes: Arc<AtomicUsize>,
+    }
+
+    impl PrimitivePageDecoder for CountingDecoder {
+        fn decode(
+            &self,
+            rows_to_skip: u64,
+            num_rows: u64,
+            all_null: &mut bool,
+        ) -> lance_core::Result<Vec<BytesMut>> {
+            self.num_decodes.fetch_add(1, Ordering::SeqCst);
+            self.inner.decode(rows_to_skip, num_rows, all_null)
+        }
+
+        fn decode_shared(&self, rows_to_skip: u64, num_rows: u64) -> Option<Vec<Bytes>> {
+            self.num_decodes.fetch_add(1, Ordering::SeqCst);
+            self.inner.decode_shared(rows_to_skip, num_rows)
+        }
+
+        fn can_skip(&self) -> bool {
+            self.inner.can_skip()
+        }
+
+        fn num_buffers(&self) -> u32 {
+            self.inner.num_buffers()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_lazily() {
+        let values = Arc::new(UInt32Array::from_iter_values((0..1000).map(|i| i % 50))) as ArrayRef;
+        let builder = ValueEncoderBuilder::default().compression(CompressionScheme::Zstd);
+        let encoder = BasicEncoder::new(Box::new(builder.build(&DataType::UInt32).unwrap()));
+        let page = InMemoryPage::new(&encoder.encode(&[values.clone()], &mut 0).unwrap());
+        let scheduler = page.scheduler(&DataType::UInt32, &[]).unwrap();
+
+        let num_decodes = Arc::new(AtomicUsize::new(0));
+        let counter = num_decodes.clone();
+        #[allow(clippy::single_range_in_vec_init)]
+        let physical_decoder = scheduler
+            .schedule_ranges(&[0..1000], &page.io(), 0)
+            .map_ok(move |inner| {
+                Box::new(CountingDecoder {
+                    inner,
+                    num_decodes: counter,
+                }) as Box<dyn PrimitivePageDecoder>
+            })
+            .boxed();
+
+        let thunk = decode_lazily(physical_decoder, DataType::UInt32, 100, 800)
+            .await
+            .unwrap();
+        // The page is loaded but nothing has been decoded yet
+        assert_eq!(num_decodes.load(Ordering::SeqCst), 0);
+
+        let decoded = thunk().unwrap();
+        assert!(num_decodes.load(Ordering::SeqCst) > 0);
+        assert_eq!(decoded.as_ref(), values.slice(100, 800).as_ref());
+    }
+
+    // Encodes `values` as a single page and schedules all of it, counting the decodes
+    fn schedule_counted_page(
+        values: &ArrayRef,
+        compression: CompressionScheme,
+        num_decodes: Arc<AtomicUsize>,
+    ) -> BoxFuture<'static, lance_core::Result<Box<dyn PrimitivePageDecoder>>> {
+        let builder = ValueEncoderBuilder::default().compression(compression);
+        let encoder = BasicEncoder::new(Box::new(builder.build(values.data_type()).unwrap()));
+        let page = InMemoryPage::new(
+            &encoder
+                .encode(std::slice::from_ref(values), &mut 0)
+                .unwrap(),
+        );
+        let scheduler = page.scheduler(values.data_type(), &[]).unwrap();
+
+        #[allow(clippy::single_range_in_vec_init)]
+        scheduler
+            .schedule_ranges(&[0..values.len() as u64], &page.io(), 0)
+            .map_ok(move |inner| {
+                Box::new(CountingDecoder { inner, num_decodes }) as Box<dyn PrimitivePageDecoder>
+            })
+            .boxed()
+    }
+
+    #[tokio::test]
+    async fn test_skip() {
+        let values = Arc::new(UInt32Array::from_iter_values(0..1000)) as ArrayRef;
+        let num_decodes = Arc::new(AtomicUsize::new(0));
+        let physical_decoder =
+            schedule_counted_page(&values, CompressionScheme::None, num_decodes.clone())
+                .await
+                .unwrap();
+        let mut decoder =
+            PrimitiveFieldDecoder::new_from_data(physical_decoder.into(), DataType::UInt32, 1000);
+
+        // Skipping a flat page only moves the cursor, nothing is decoded or copied
+        assert_eq!(decoder.skip(300).unwrap(), SkipResult::Skipped);
+        assert_eq!(decoder.skip(100).unwrap(), SkipResult::Skipped);
+        assert_eq!(num_decodes.load(Ordering::SeqCst), 0);
+        assert_eq!(decoder.avail(), 600);
+        assert!(decoder.skip(601).is_err());
+
+        let task = decoder.drain(200).unwrap();
+        assert!(task.has_more);
+        assert_eq!(
+            task.task.decode().unwrap().as_ref(),
+            values.slice(400, 200).as_ref()
+        );
+        assert_eq!(decoder.skip(400).unwrap(), SkipResult::Skipped);
+        assert_eq!(decoder.avail(), 0);
+
+        // Compressed pages must be decoded to skip
+        let num_decodes = Arc::new(AtomicUsize::new(0));
+        let physical_decoder =
+            schedule_counted_page(&values, CompressionScheme::Zstd, num_decodes.clone())
+                .await
+                .unwrap();
+        let mut decoder =
+            PrimitiveFieldDecoder::new_from_data(physical_decoder.into(), DataType::UInt32, 1000);
+        assert_eq!(decoder.skip(300).unwrap(), SkipResult::RequiresDecode);
+        assert_eq!(decoder.avail(), 1000);
+        assert_eq!(num_decodes.load(Ordering::SeqCst), 0);
+    }
+}
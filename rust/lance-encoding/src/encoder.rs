@@ -7,28 +7,48 @@ use arrow_buffer::Buffer;
 use arrow_schema::DataType;
 use bytes::{Bytes, BytesMut};
 use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt, TryStreamExt};
+use lance_arrow::DataTypeExt;
 use lance_core::datatypes::{Field, Schema};
-use lance_core::Result;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
 
+use crate::encodings::physical::buffers::CompressionDecision;
 use crate::encodings::physical::fsst::FsstArrayEncoder;
-use crate::encodings::physical::value::{parse_compression_scheme, CompressionScheme};
+use crate::encodings::physical::value::{
+    default_scheme_for, parse_compression_scheme, parse_requested_compression_scheme,
+    CompressionScheme,
+};
 use crate::{
+    cardinality::estimate_distinct_count,
+    checksum,
     decoder::{ColumnInfo, PageInfo},
     encodings::{
         logical::{
             list::ListFieldEncoder, primitive::PrimitiveFieldEncoder, r#struct::StructFieldEncoder,
         },
         physical::{
-            basic::BasicEncoder, binary::BinaryEncoder, dictionary::DictionaryEncoder,
-            fixed_size_list::FslEncoder, value::ValueEncoder,
+            basic::BasicEncoder,
+            binary::BinaryEncoder,
+            bitpack::{
+                is_bitpackable, num_compressed_bits, BitpackedArrayEncoder,
+                ChunkedBitpackedArrayEncoder,
+            },
+            bloom::{supports_bloom_filter, BloomFilterEncoder},
+            boolean_rle::BooleanRleEncoder,
+            delta_of_delta::{is_regular_temporal, supports_delta_of_delta, DeltaOfDeltaEncoder},
+            dictionary::DictionaryEncoder,
+            fixed_size_list::FslEncoder,
+            quantize::{supports_quantization, QuantizeEncoder, QuantizeParams},
+            sparse::{non_zero_fraction, supports_sparse, SparseEncoder},
+            value::{ValueEncoder, ValueEncoderBuilder},
+            FLAT_ENCODING_VERSION,
         },
     },
     format::pb,
 };
 
-use hyperloglogplus::{HyperLogLog, HyperLogLogPlus};
-use std::collections::hash_map::RandomState;
-
 /// An encoded buffer
 pub struct EncodedBuffer {
     /// Buffers that make up the encoded buffer
@@ -58,6 +78,20 @@ pub struct EncodedArrayBuffer {
     pub index: u32,
 }
 
+impl EncodedArrayBuffer {
+    /// Computes the checksum of the buffer
+    ///
+    /// Large parts are checksummed in parallel, see [`crate::checksum`].
+    pub fn checksum(&self) -> u32 {
+        checksum::combine_checksums(self.parts.iter().map(|part| {
+            (
+                checksum::checksum_parallel(part, checksum::CHECKSUM_BLOCK_SIZE),
+                part.len() as u64,
+            )
+        }))
+    }
+}
+
 // Custom impl because buffers shouldn't be included in debug output
 impl std::fmt::Debug for EncodedArrayBuffer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -93,6 +127,221 @@ impl EncodedArray {
             self.encoding,
         )
     }
+
+    /// Iterates over the buffers, in index order, as `(index, buffer_type, parts)`
+    ///
+    /// The type of each buffer comes from the [`pb::Buffer`] that references it in
+    /// `encoding`.  Buffers that the encoding does not reference are reported as page
+    /// buffers.
+    pub fn buffers_with_types(
+        &self,
+    ) -> impl Iterator<Item = (u32, pb::buffer::BufferType, &[Buffer])> + '_ {
+        let mut buffer_types = HashMap::new();
+        collect_buffer_types(&self.encoding, &mut buffer_types);
+        let mut buffers = self.buffers.iter().collect::<Vec<_>>();
+        buffers.sort_by_key(|buffer| buffer.index);
+        buffers.into_iter().map(move |buffer| {
+            let buffer_type = buffer_types
+                .get(&buffer.index)
+                .copied()
+                .unwrap_or(pb::buffer::BufferType::Page);
+            (buffer.index, buffer_type, buffer.parts.as_slice())
+        })
+    }
+}
+
+// Records the type of every buffer referenced by `encoding`, keyed by buffer index
+fn collect_buffer_types(
+    encoding: &pb::ArrayEncoding,
+    buffer_types: &mut HashMap<u32, pb::buffer::BufferType>,
+) {
+    use pb::array_encoding::ArrayEncoding;
+    use pb::nullable::Nullability;
+    let mut add_buffer = |buffer: Option<&pb::Buffer>| {
+        if let Some(buffer) = buffer {
+            buffer_types.insert(buffer.buffer_index, buffer.buffer_type());
+        }
+    };
+    let nested: Vec<Option<&pb::ArrayEncoding>> = match encoding.array_encoding.as_ref() {
+        Some(ArrayEncoding::Flat(flat)) => {
+            add_buffer(flat.buffer.as_ref());
+            vec![]
+        }
+        Some(ArrayEncoding::Bitpacked(bitpacked)) => {
+            add_buffer(bitpacked.buffer.as_ref());
+            vec![]
+        }
+        Some(ArrayEncoding::ChunkedBitpacked(chunked)) => {
+            add_buffer(chunked.buffer.as_ref());
+            vec![]
+        }
+        Some(ArrayEncoding::BooleanRle(boolean_rle)) => {
+            add_buffer(boolean_rle.buffer.as_ref());
+            vec![]
+        }
+        Some(ArrayEncoding::TransformPipeline(pipeline)) => {
+            add_buffer(pipeline.buffer.as_ref());
+            vec![]
+        }
+        Some(ArrayEncoding::Nullable(nullable)) => match nullable.nullability.as_ref() {
+            Some(Nullability::NoNulls(no_nulls)) => vec![no_nulls.values.as_deref()],
+            Some(Nullability::SomeNulls(some_nulls)) => {
+                vec![some_nulls.validity.as_deref(), some_nulls.values.as_deref()]
+            }
+            _ => vec![],
+        },
+        Some(ArrayEncoding::DeltaOfDelta(delta_of_delta)) => {
+            vec![delta_of_delta.deltas.as_deref()]
+        }
+        Some(ArrayEncoding::Quantized(quantized)) => vec![quantized.values.as_deref()],
+        Some(ArrayEncoding::Sparse(sparse)) => {
+            vec![sparse.positions.as_deref(), sparse.values.as_deref()]
+        }
+        Some(ArrayEncoding::BloomFiltered(bloom_filtered)) => {
+            add_buffer(
+                bloom_filtered
+                    .filter
+                    .as_ref()
+                    .and_then(|filter| filter.buffer.as_ref()),
+            );
+            vec![bloom_filtered.values.as_deref()]
+        }
+        Some(ArrayEncoding::FixedSizeList(fixed_size_list)) => {
+            vec![fixed_size_list.items.as_deref()]
+        }
+        Some(ArrayEncoding::List(list)) => vec![list.offsets.as_deref()],
+        Some(ArrayEncoding::Binary(binary)) => {
+            vec![binary.indices.as_deref(), binary.bytes.as_deref()]
+        }
+        Some(ArrayEncoding::Fsst(fsst)) => vec![fsst.binary.as_deref()],
+        Some(ArrayEncoding::Dictionary(dictionary)) => {
+            vec![dictionary.indices.as_deref(), dictionary.items.as_deref()]
+        }
+        Some(ArrayEncoding::Struct(_)) | None => vec![],
+    };
+    for nested in nested.into_iter().flatten() {
+        collect_buffer_types(nested, buffer_types);
+    }
+}
+
+/// Concatenates two encoded pages without decoding them
+///
+/// This is only possible when both pages use the same flat encoding (same width and same
+/// compression, zstd and LZ4 frames can be concatenated) possibly wrapped in a nullable encoding with
+/// no nulls.  Anything else returns an error and the pages must be decoded and re-encoded
+/// instead.  The caller is responsible for summing the row counts of the two pages.
+pub fn concat_encoded(a: &EncodedArray, b: &EncodedArray) -> Result<EncodedArray> {
+    let encoding = concat_encodings(&a.encoding, &b.encoding)?;
+    let ([a_buffer], [b_buffer]) = (a.buffers.as_slice(), b.buffers.as_slice()) else {
+        return Err(cannot_concat("pages with more than one buffer"));
+    };
+    let parts = a_buffer
+        .parts
+        .iter()
+        .chain(&b_buffer.parts)
+        .cloned()
+        .collect();
+    Ok(EncodedArray {
+        buffers: vec![EncodedArrayBuffer {
+            parts,
+            index: a_buffer.index,
+        }],
+        encoding,
+    })
+}
+
+fn cannot_concat(reason: &str) -> Error {
+    Error::invalid_input(
+        format!(
+            "Cannot concatenate {} without decoding, the pages must be decoded and re-encoded",
+            reason
+        ),
+        location!(),
+    )
+}
+
+fn concat_encodings(a: &pb::ArrayEncoding, b: &pb::ArrayEncoding) -> Result<pb::ArrayEncoding> {
+    use pb::array_encoding::ArrayEncoding;
+    use pb::nullable::Nullability;
+    match (a.array_encoding.as_ref(), b.array_encoding.as_ref()) {
+        (Some(ArrayEncoding::Flat(flat_a)), Some(ArrayEncoding::Flat(flat_b))) => {
+            if flat_a.bits_per_value != flat_b.bits_per_value {
+                return Err(cannot_concat("flat pages with different widths"));
+            }
+            // Bitmaps could only be concatenated if the first page ends on a byte boundary
+            if flat_a.bits_per_value % 8 != 0 {
+                return Err(cannot_concat("flat pages that are not byte aligned"));
+            }
+            // Only the scheme matters, the sampling details can differ from page to page
+            let scheme = |flat: &pb::Flat| -> Result<CompressionScheme> {
+                flat.compression
+                    .as_ref()
+                    .map(|compression| parse_compression_scheme(&compression.scheme))
+                    .unwrap_or(Ok(CompressionScheme::None))
+            };
+            if scheme(flat_a)? != scheme(flat_b)? {
+                return Err(cannot_concat("flat pages with different compression"));
+            }
+            // Pages written before the version was recorded have the first layout
+            let version = |flat: &pb::Flat| flat.version.max(FLAT_ENCODING_VERSION);
+            if version(flat_a) != version(flat_b) {
+                return Err(cannot_concat("flat pages with different layouts"));
+            }
+            if flat_a.little_endian != flat_b.little_endian {
+                return Err(cannot_concat("flat pages with different byte orders"));
+            }
+            let is_page_buffer = |flat: &pb::Flat| {
+                flat.buffer.as_ref().map(|buffer| buffer.buffer_type)
+                    == Some(pb::buffer::BufferType::Page as i32)
+            };
+            if !is_page_buffer(flat_a) || !is_page_buffer(flat_b) {
+                return Err(cannot_concat("flat pages that do not use page buffers"));
+            }
+            Ok(a.clone())
+        }
+        (Some(ArrayEncoding::Nullable(nullable_a)), Some(ArrayEncoding::Nullable(nullable_b))) => {
+            match (&nullable_a.nullability, &nullable_b.nullability) {
+                (
+                    Some(Nullability::NoNulls(no_nulls_a)),
+                    Some(Nullability::NoNulls(no_nulls_b)),
+                ) => {
+                    let (Some(values_a), Some(values_b)) = (&no_nulls_a.values, &no_nulls_b.values)
+                    else {
+                        return Err(cannot_concat("pages with missing encodings"));
+                    };
+                    let values = concat_encodings(values_a, values_b)?;
+                    Ok(pb::ArrayEncoding {
+                        array_encoding: Some(ArrayEncoding::Nullable(Box::new(pb::Nullable {
+                            nullability: Some(Nullability::NoNulls(Box::new(
+                                pb::nullable::NoNull {
+                                    values: Some(Box::new(values)),
+                                },
+                            ))),
+                        }))),
+                        producer: None,
+                        statistics: None,
+                        data_type: None,
+                    })
+                }
+                _ => Err(cannot_concat("pages with nulls")),
+            }
+        }
+        _ => Err(cannot_concat("pages that are not both flat encoded")),
+    }
+}
+
+/// The revision of the encoders in this crate
+///
+/// This is bumped when a change to an encoder changes the pages it writes (e.g. the fix of
+/// an encoding bug) so that pages written before the change can be told apart.
+pub const ENCODER_REVISION: u32 = 1;
+
+/// The version of the encoders in this crate, this is recorded in each page they write
+pub fn encoder_version() -> pb::EncoderVersion {
+    pb::EncoderVersion {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        revision: ENCODER_REVISION,
+    }
 }
 
 /// An encoded page of data
@@ -110,6 +359,18 @@ pub struct EncodedPage {
     pub column_idx: u32,
 }
 
+impl EncodedPage {
+    /// Creates a page, recording the current [`encoder_version`] in its encoding
+    pub fn new(mut array: EncodedArray, num_rows: u64, column_idx: u32) -> Self {
+        array.encoding.producer = Some(encoder_version());
+        Self {
+            array,
+            num_rows,
+            column_idx,
+        }
+    }
+}
+
 /// Encodes data into a single buffer
 pub trait BufferEncoder: std::fmt::Debug + Send + Sync {
     /// Encode data
@@ -118,6 +379,19 @@ pub trait BufferEncoder: std::fmt::Debug + Send + Sync {
     /// a single EncodedBuffer (though that buffer may have multiple parts).  All
     /// parts will be written to the file as one contiguous block.
     fn encode(&self, arrays: &[ArrayRef]) -> Result<EncodedBuffer>;
+
+    /// Encode data, reporting whether the encoder decided to compress it
+    ///
+    /// Compressing encoders may sample the data first and skip compression if it is not
+    /// expected to pay off.  Callers must then record the decision in the encoding so the
+    /// buffer can be decoded.  The default implementation calls [`Self::encode`] and does
+    /// not make a decision.
+    fn encode_with_decision(
+        &self,
+        arrays: &[ArrayRef],
+    ) -> Result<(EncodedBuffer, Option<CompressionDecision>)> {
+        Ok((self.encode(arrays)?, None))
+    }
 }
 
 /// Encodes data from Arrow format into some kind of on-disk format
@@ -219,26 +493,225 @@ pub trait FieldEncoder: Send {
 /// array statistics.
 pub trait ArrayEncodingStrategy: Send + Sync + std::fmt::Debug {
     fn create_array_encoder(&self, arrays: &[ArrayRef]) -> Result<Box<dyn ArrayEncoder>>;
+
+    /// Creates an encoder for a page of `arrays` given the estimated number of distinct
+    /// values in the page (see [`crate::cardinality`])
+    ///
+    /// The estimate is computed once per page and recorded in the page statistics.  By
+    /// default it is ignored.
+    fn create_page_encoder(
+        &self,
+        arrays: &[ArrayRef],
+        approx_distinct_count: Option<u64>,
+    ) -> Result<Box<dyn ArrayEncoder>> {
+        let _ = approx_distinct_count;
+        self.create_array_encoder(arrays)
+    }
+
+    /// Whether to record the data type of the values in each page
+    ///
+    /// This makes the pages self-describing, see [`crate::decoder::decode_self_describing_page`].
+    /// By default the data type is not recorded since it is part of the file schema.
+    fn embeds_data_type(&self) -> bool {
+        false
+    }
+}
+
+/// Forces a specific physical encoding, bypassing the automatic selection
+///
+/// This is intended for tests and benchmarks (e.g. forcing flat encoding to get a
+/// baseline).  Forcing an encoding that cannot represent the data type is an error.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingOverride {
+    /// Pick the encoding automatically
+    #[default]
+    Auto,
+    /// Store fixed-width values as-is, without compression
+    ForceFlat,
+    /// Bitpack integers, even if no bits would be saved
+    ForceBitpack,
+    /// Bitpack integers in chunks, each with its own width
+    ForceChunkedBitpack,
+    /// Store integers as second order differences
+    ForceDeltaOfDelta,
+    /// Store only the positions and values of the non-zero values
+    ForceSparse,
+    /// Run-length encode booleans, even if the runs are short
+    ForceRle,
+}
+
+/// How the offsets of variable-width data (e.g. strings and binary) are encoded
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetsEncoding {
+    /// Offsets are stored as flat 64-bit values, compressed with the page compression scheme
+    #[default]
+    Flat,
+    /// Offsets are delta encoded (see [`DeltaOfDeltaEncoder`]) and the differences are
+    /// bitpacked.  Offsets are increasing and often evenly spaced and so this tends to be
+    /// much smaller than general purpose compression.
+    DeltaBitpacked,
 }
 
 /// The core array encoding strategy is a set of basic encodings that
 /// are generally applicable in most scenarios.
-#[derive(Debug, Default)]
-pub struct CoreArrayEncodingStrategy;
+#[derive(Debug, Default, Clone)]
+pub struct CoreArrayEncodingStrategy {
+    encoding_override: EncodingOverride,
+    offsets_encoding: OffsetsEncoding,
+    bytes_compression: Option<CompressionScheme>,
+    quantization: Option<QuantizeParams>,
+    embed_data_type: bool,
+    little_endian: bool,
+    sparse: bool,
+    bitpacking: bool,
+    bloom_filter_false_positive_rate: Option<f64>,
+}
 
 fn get_compression_scheme() -> CompressionScheme {
     let compression_scheme = std::env::var("LANCE_PAGE_COMPRESSION").unwrap_or("none".to_string());
-    parse_compression_scheme(&compression_scheme).unwrap_or(CompressionScheme::None)
+    parse_requested_compression_scheme(&compression_scheme).unwrap_or(CompressionScheme::None)
 }
 
 impl CoreArrayEncodingStrategy {
+    /// Creates a strategy that always uses the given encoding (unless it is `Auto`)
+    pub fn with_override(encoding_override: EncodingOverride) -> Self {
+        Self {
+            encoding_override,
+            ..Default::default()
+        }
+    }
+
+    /// Sets how the offsets of variable-width data are encoded
+    pub fn with_offsets_encoding(mut self, offsets_encoding: OffsetsEncoding) -> Self {
+        self.offsets_encoding = offsets_encoding;
+        self
+    }
+
+    /// Sets the compression scheme used for the bytes of variable-width data
+    ///
+    /// If this is not set then the page compression scheme is used.  A
+    /// [`CompressionScheme::Default`] request picks the default for the variable-width
+    /// type (and not for the underlying bytes).
+    pub fn with_bytes_compression(mut self, compression_scheme: CompressionScheme) -> Self {
+        self.bytes_compression = Some(compression_scheme);
+        self
+    }
+
+    /// Lossily stores floats (including the items of fixed size lists) as quantized
+    /// integers, see [`QuantizeEncoder`]
+    ///
+    /// This is lossy and so it is never used unless explicitly requested.  Encoding fails
+    /// if a value (e.g. NaN) cannot be quantized with the given parameters.
+    pub fn with_quantization(mut self, params: QuantizeParams) -> Self {
+        self.quantization = Some(params);
+        self
+    }
+
+    /// Records the data type of the values in each page, see
+    /// [`ArrayEncodingStrategy::embeds_data_type`]
+    pub fn with_embedded_data_type(mut self) -> Self {
+        self.embed_data_type = true;
+        self
+    }
+
+    /// Stores flat values in little-endian byte order on every host, see
+    /// [`ValueEncoderBuilder::little_endian`]
+    pub fn with_little_endian_values(mut self) -> Self {
+        self.little_endian = true;
+        self
+    }
+
+    /// Stores pages of fixed-width values that are almost all zero with
+    /// [`SparseEncoder`]
+    pub fn with_sparse_values(mut self) -> Self {
+        self.sparse = true;
+        self
+    }
+
+    /// Packs integers into the minimum number of bits needed for the values in the page,
+    /// see [`BitpackedArrayEncoder`]
+    pub fn with_bitpacking(mut self) -> Self {
+        self.bitpacking = true;
+        self
+    }
+
+    /// Adds a Bloom filter with the given false positive rate to every page of primitive,
+    /// binary and string values, see [`BloomFilterEncoder`]
+    pub fn with_bloom_filters(mut self, false_positive_rate: f64) -> Self {
+        self.bloom_filter_false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    fn value_encoder(
+        &self,
+        data_type: &DataType,
+        compression_scheme: CompressionScheme,
+    ) -> Result<ValueEncoder> {
+        ValueEncoderBuilder::default()
+            .compression(compression_scheme)
+            .little_endian(self.little_endian)
+            .build(data_type)
+    }
+
+    fn forced_array_encoder(&self, arrays: &[ArrayRef]) -> Result<Box<dyn ArrayEncoder>> {
+        let encoding_override = self.encoding_override;
+        let data_type = arrays[0].data_type();
+        let values_encoder: Option<Box<dyn ArrayEncoder>> = match encoding_override {
+            EncodingOverride::Auto => unreachable!(),
+            EncodingOverride::ForceFlat => data_type.is_fixed_stride().then(|| {
+                self.value_encoder(data_type, CompressionScheme::None)
+                    .map(|encoder| Box::new(encoder) as Box<dyn ArrayEncoder>)
+            }),
+            EncodingOverride::ForceBitpack => num_compressed_bits(arrays)
+                .map(|num_bits| Ok(Box::new(BitpackedArrayEncoder::new(num_bits)) as _)),
+            EncodingOverride::ForceChunkedBitpack => is_bitpackable(data_type)
+                .then(|| Ok(Box::new(ChunkedBitpackedArrayEncoder::default()) as _)),
+            EncodingOverride::ForceDeltaOfDelta => supports_delta_of_delta(data_type)
+                .then(|| Ok(Box::new(DeltaOfDeltaEncoder::new()) as _)),
+            EncodingOverride::ForceSparse => {
+                supports_sparse(data_type).then(|| Ok(Box::new(SparseEncoder::new()) as _))
+            }
+            EncodingOverride::ForceRle => (*data_type == DataType::Boolean)
+                .then(|| Ok(Box::new(BooleanRleEncoder::new()) as _)),
+        }
+        .transpose()?;
+        let values_encoder = values_encoder.ok_or_else(|| {
+            Error::unsupported_type(
+                data_type,
+                format!(
+                    "the encoding override {:?} cannot be used",
+                    encoding_override
+                ),
+                location!(),
+            )
+        })?;
+        Ok(Box::new(BasicEncoder::new(values_encoder)))
+    }
+
     fn can_use_fsst(data_type: &DataType, data_size: u64) -> bool {
         std::env::var("LANCE_USE_FSST").is_ok()
             && matches!(data_type, DataType::Utf8 | DataType::Binary)
             && data_size > 4 * 1024 * 1024
     }
 
+    fn can_use_bitpacking(&self, data_type: &DataType) -> bool {
+        self.bitpacking && is_bitpackable(data_type)
+    }
+
+    fn can_use_delta_of_delta(data_type: &DataType) -> bool {
+        std::env::var("LANCE_USE_DELTA_OF_DELTA").is_ok() && is_regular_temporal(data_type)
+    }
+
+    // Sparse encoding stores a position with every non-zero value and so it only pays off
+    // if almost all values are zero
+    fn can_use_sparse(&self, arrays: &[ArrayRef]) -> bool {
+        self.sparse
+            && supports_sparse(arrays[0].data_type())
+            && non_zero_fraction(arrays) <= 1.0 / 32.0
+    }
+
     fn array_encoder_from_type(
+        &self,
         data_type: &DataType,
         data_size: u64,
         use_dict_encoding: bool,
@@ -246,26 +719,42 @@ impl CoreArrayEncodingStrategy {
         match data_type {
             DataType::FixedSizeList(inner, dimension) => {
                 Ok(Box::new(BasicEncoder::new(Box::new(FslEncoder::new(
-                    Self::array_encoder_from_type(inner.data_type(), data_size, use_dict_encoding)?,
+                    self.array_encoder_from_type(inner.data_type(), data_size, use_dict_encoding)?,
                     *dimension as u32,
                 )))))
             }
             DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary => {
                 if use_dict_encoding {
                     let dict_indices_encoder =
-                        Self::array_encoder_from_type(&DataType::UInt8, data_size, false)?;
+                        self.array_encoder_from_type(&DataType::UInt8, data_size, false)?;
                     let dict_items_encoder =
-                        Self::array_encoder_from_type(&DataType::Utf8, data_size, false)?;
+                        self.array_encoder_from_type(&DataType::Utf8, data_size, false)?;
 
                     Ok(Box::new(DictionaryEncoder::new(
                         dict_indices_encoder,
                         dict_items_encoder,
                     )))
                 } else {
-                    let bin_indices_encoder =
-                        Self::array_encoder_from_type(&DataType::UInt64, data_size, false)?;
-                    let bin_bytes_encoder =
-                        Self::array_encoder_from_type(&DataType::UInt8, data_size, false)?;
+                    let bin_indices_encoder: Box<dyn ArrayEncoder> = match self.offsets_encoding {
+                        OffsetsEncoding::Flat => {
+                            self.array_encoder_from_type(&DataType::UInt64, data_size, false)?
+                        }
+                        OffsetsEncoding::DeltaBitpacked => {
+                            Box::new(BasicEncoder::new(Box::new(DeltaOfDeltaEncoder::new())))
+                        }
+                    };
+                    // The bytes are stored as UInt8 values but the default scheme should be
+                    // the one for the variable-width type
+                    let bytes_compression = match self
+                        .bytes_compression
+                        .unwrap_or_else(get_compression_scheme)
+                    {
+                        CompressionScheme::Default => default_scheme_for(data_type).compression,
+                        compression_scheme => compression_scheme,
+                    };
+                    let bin_bytes_encoder = Box::new(BasicEncoder::new(Box::new(
+                        self.value_encoder(&DataType::UInt8, bytes_compression)?,
+                    )));
 
                     let bin_encoder =
                         Box::new(BinaryEncoder::new(bin_indices_encoder, bin_bytes_encoder));
@@ -276,8 +765,13 @@ impl CoreArrayEncodingStrategy {
                     }
                 }
             }
+            _ if supports_quantization(data_type) && self.quantization.is_some() => {
+                Ok(Box::new(BasicEncoder::new(Box::new(
+                    QuantizeEncoder::try_new(self.quantization.unwrap())?,
+                ))))
+            }
             _ => Ok(Box::new(BasicEncoder::new(Box::new(
-                ValueEncoder::try_new(data_type, get_compression_scheme())?,
+                self.value_encoder(data_type, get_compression_scheme())?,
             )))),
         }
     }
@@ -294,43 +788,86 @@ fn get_dict_encoding_threshold() -> u64 {
 // by applying a threshold on cardinality
 // returns true if cardinality < threshold but false if the total number of rows is less than the threshold
 // The choice to use 100 is just a heuristic for now
-// hyperloglog is used for cardinality estimation
-// error rate = 1.04 / sqrt(2^p), where p is the precision
-// and error rate is 1.04 / sqrt(2^12) = 1.56%
-fn check_dict_encoding(arrays: &[ArrayRef], threshold: u64) -> bool {
+// The cardinality is estimated with hyperloglog, see [`crate::cardinality`]
+fn check_dict_encoding(
+    arrays: &[ArrayRef],
+    approx_distinct_count: Option<u64>,
+    threshold: u64,
+) -> bool {
     let num_total_rows = arrays.iter().map(|arr| arr.len()).sum::<usize>();
     if num_total_rows < threshold as usize {
         return false;
     }
-    const PRECISION: u8 = 12;
+    approx_distinct_count.is_some_and(|count| count < threshold)
+}
 
-    let mut hll: HyperLogLogPlus<String, RandomState> =
-        HyperLogLogPlus::new(PRECISION, RandomState::new()).unwrap();
+impl ArrayEncodingStrategy for CoreArrayEncodingStrategy {
+    fn create_array_encoder(&self, arrays: &[ArrayRef]) -> Result<Box<dyn ArrayEncoder>> {
+        // Only strings are considered for dictionary encoding
+        let approx_distinct_count = if arrays[0].data_type() == &DataType::Utf8 {
+            estimate_distinct_count(arrays)
+        } else {
+            None
+        };
+        self.create_page_encoder(arrays, approx_distinct_count)
+    }
+
+    fn embeds_data_type(&self) -> bool {
+        self.embed_data_type
+    }
 
-    for arr in arrays {
-        let string_array = arrow_array::cast::as_string_array(arr);
-        for value in string_array.iter().flatten() {
-            hll.insert(value);
-            let estimated_cardinality = hll.count() as u64;
-            if estimated_cardinality >= threshold {
-                return false;
+    fn create_page_encoder(
+        &self,
+        arrays: &[ArrayRef],
+        approx_distinct_count: Option<u64>,
+    ) -> Result<Box<dyn ArrayEncoder>> {
+        let encoder = self.values_page_encoder(arrays, approx_distinct_count)?;
+        match self.bloom_filter_false_positive_rate {
+            Some(false_positive_rate) if supports_bloom_filter(arrays[0].data_type()) => {
+                Ok(Box::new(
+                    BloomFilterEncoder::new(encoder).with_false_positive_rate(false_positive_rate),
+                ))
             }
+            _ => Ok(encoder),
         }
     }
-
-    true
 }
 
-impl ArrayEncodingStrategy for CoreArrayEncodingStrategy {
-    fn create_array_encoder(&self, arrays: &[ArrayRef]) -> Result<Box<dyn ArrayEncoder>> {
+impl CoreArrayEncodingStrategy {
+    // The encoder of a page, without a Bloom filter
+    fn values_page_encoder(
+        &self,
+        arrays: &[ArrayRef],
+        approx_distinct_count: Option<u64>,
+    ) -> Result<Box<dyn ArrayEncoder>> {
         let data_size = arrays
             .iter()
             .map(|arr| arr.get_buffer_memory_size() as u64)
             .sum::<u64>();
+        if self.encoding_override != EncodingOverride::Auto {
+            return self.forced_array_encoder(arrays);
+        }
         let data_type = arrays[0].data_type();
         let use_dict_encoding = data_type == &DataType::Utf8
-            && check_dict_encoding(arrays, get_dict_encoding_threshold());
-        Self::array_encoder_from_type(data_type, data_size, use_dict_encoding)
+            && check_dict_encoding(arrays, approx_distinct_count, get_dict_encoding_threshold());
+        if Self::can_use_delta_of_delta(data_type) {
+            return Ok(Box::new(BasicEncoder::new(Box::new(
+                DeltaOfDeltaEncoder::new(),
+            ))));
+        }
+        if self.can_use_sparse(arrays) {
+            return Ok(Box::new(BasicEncoder::new(Box::new(SparseEncoder::new()))));
+        }
+        if self.can_use_bitpacking(data_type) {
+            if let Some(num_bits) = num_compressed_bits(arrays) {
+                if num_bits < 8 * data_type.byte_width() as u64 {
+                    return Ok(Box::new(BasicEncoder::new(Box::new(
+                        BitpackedArrayEncoder::new(num_bits),
+                    ))));
+                }
+            }
+        }
+        self.array_encoder_from_type(data_type, data_size, use_dict_encoding)
     }
 }
 
@@ -393,7 +930,16 @@ pub struct CoreFieldEncodingStrategy {
 impl Default for CoreFieldEncodingStrategy {
     fn default() -> Self {
         Self {
-            array_encoding_strategy: Arc::new(CoreArrayEncodingStrategy),
+            array_encoding_strategy: Arc::new(CoreArrayEncodingStrategy::default()),
+        }
+    }
+}
+
+impl CoreFieldEncodingStrategy {
+    /// Creates a field encoding strategy that uses the given strategy to encode pages
+    pub fn new(array_encoding_strategy: Arc<dyn ArrayEncodingStrategy>) -> Self {
+        Self {
+            array_encoding_strategy,
         }
     }
 }
@@ -437,7 +983,9 @@ impl FieldEncodingStrategy for CoreFieldEncodingStrategy {
             | DataType::Binary
             | DataType::LargeBinary
             | DataType::Utf8
-            | DataType::LargeUtf8 => Ok(Box::new(PrimitiveFieldEncoder::try_new(
+            | DataType::LargeUtf8
+            // Dictionaries are stored as their values
+            | DataType::Dictionary(_, _) => Ok(Box::new(PrimitiveFieldEncoder::try_new(
                 cache_bytes_per_column,
                 keep_original_array,
                 self.array_encoding_strategy.clone(),
@@ -561,6 +1109,66 @@ fn write_page_to_data_buffer(page: EncodedPage, data_buffer: &mut BytesMut) -> P
     }
 }
 
+/// Encodes a stream of record batches, yielding pages as they are completed
+///
+/// The columns of each batch are pushed into `field_encoders` (one encoder per top-level
+/// column, in order) as the batches arrive and encoding starts as soon as an encoder has
+/// buffered enough data for a page.  Batches are only pulled from `batches` as pages are
+/// consumed and at most `max_pages_in_flight` pages are encoded ahead of the consumer, so
+/// memory use is bounded by the page size targets of the encoders and not by the size of
+/// the input.
+///
+/// Once `batches` is exhausted any remaining buffered data is flushed.  Afterwards the
+/// caller should call [`FieldEncoder::finish`] on each encoder to get the column metadata.
+pub fn encode_stream<'a>(
+    field_encoders: &'a mut [Box<dyn FieldEncoder>],
+    batches: impl Stream<Item = Result<RecordBatch>> + Send + 'a,
+    max_pages_in_flight: usize,
+) -> BoxStream<'a, Result<EncodedPage>> {
+    // Each item is the (possibly empty) set of encode tasks triggered by one batch, the
+    // final item is the set of tasks created by flushing the encoders
+    let tasks = stream::unfold(
+        (field_encoders, batches.boxed(), false),
+        |(field_encoders, mut batches, flushed)| async move {
+            if flushed {
+                return None;
+            }
+            let Some(batch) = batches.next().await else {
+                let tasks = field_encoders
+                    .iter_mut()
+                    .map(|encoder| encoder.flush())
+                    .collect::<Result<Vec<_>>>();
+                return Some((tasks, (field_encoders, batches, true)));
+            };
+            let tasks = batch.and_then(|batch| {
+                if batch.num_columns() != field_encoders.len() {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Cannot encode a batch with {} columns using {} field encoders",
+                            batch.num_columns(),
+                            field_encoders.len()
+                        ),
+                        location!(),
+                    ));
+                }
+                batch
+                    .columns()
+                    .iter()
+                    .zip(field_encoders.iter_mut())
+                    .map(|(array, encoder)| encoder.maybe_encode(array.clone()))
+                    .collect::<Result<Vec<_>>>()
+            });
+            Some((tasks, (field_encoders, batches, false)))
+        },
+    );
+    tasks
+        .map_ok(|tasks| stream::iter(tasks.into_iter().flatten().map(Ok::<_, Error>)))
+        .try_flatten()
+        .map(|task: Result<EncodeTask>| async move { task?.await })
+        .buffered(max_pages_in_flight)
+        .boxed()
+}
+
 /// Helper method to encode a batch of data into memory
 ///
 /// This is primarily for testing and benchmarking but could be useful in other
@@ -633,15 +1241,41 @@ pub async fn encode_batch(
 
 #[cfg(test)]
 pub mod tests {
-    use arrow_array::{ArrayRef, StringArray};
+    use arrow_array::{
+        ArrayRef, BooleanArray, Float32Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
 
-    use super::check_dict_encoding;
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use futures::{stream, StreamExt};
+
+    use crate::{
+        decoder::{
+            decode_batch, decode_page, ColumnInfo, DecoderMiddlewareChain, FilterExpression,
+            PageInfo,
+        },
+        encodings::physical::{
+            basic::BasicEncoder,
+            bitpack::BitpackedArrayEncoder,
+            value::{CompressionScheme, ValueEncoder, ValueEncoderBuilder},
+        },
+        format::pb,
+        testing::InMemoryPage,
+    };
+
+    use super::{
+        check_dict_encoding, concat_encoded, encode_batch, encode_stream, encoder_version,
+        estimate_distinct_count, ArrayEncoder, ArrayEncodingStrategy, BatchEncoder,
+        CoreArrayEncodingStrategy, CoreFieldEncodingStrategy, EncodingOverride, Schema,
+        ENCODER_REVISION,
+    };
 
     fn is_dict_encoding_applicable(arr: Vec<Option<&str>>, threshold: u64) -> bool {
         let arr = StringArray::from(arr);
         let arr = Arc::new(arr) as ArrayRef;
-        check_dict_encoding(&[arr], threshold)
+        let arrays = [arr];
+        check_dict_encoding(&arrays, estimate_distinct_count(&arrays), threshold)
     }
 
     #[test]
@@ -677,4 +1311,392 @@ pub mod tests {
     fn test_dict_encoding_should_not_be_applied_for_smaller_than_threshold_arrays() {
         assert!(!is_dict_encoding_applicable(vec![Some("a"), Some("a")], 3));
     }
+
+    #[test]
+    fn test_encoding_override_rejects_unsupported_types() {
+        let floats = Arc::new(Float32Array::from(vec![1.0, 2.0])) as ArrayRef;
+        let strings = Arc::new(StringArray::from(vec!["a", "b"])) as ArrayRef;
+        let bools = Arc::new(BooleanArray::from(vec![true, false])) as ArrayRef;
+        let ints = Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef;
+
+        let create = |encoding_override, arr: &ArrayRef| {
+            CoreArrayEncodingStrategy::with_override(encoding_override)
+                .create_array_encoder(&[arr.clone()])
+        };
+
+        assert!(create(EncodingOverride::ForceBitpack, &floats).is_err());
+        assert!(create(EncodingOverride::ForceDeltaOfDelta, &floats).is_err());
+        assert!(create(EncodingOverride::ForceDeltaOfDelta, &bools).is_err());
+        assert!(create(EncodingOverride::ForceFlat, &strings).is_err());
+        assert!(create(EncodingOverride::ForceRle, &ints).is_err());
+
+        assert!(create(EncodingOverride::ForceFlat, &floats).is_ok());
+        assert!(create(EncodingOverride::ForceFlat, &bools).is_ok());
+        assert!(create(EncodingOverride::ForceBitpack, &ints).is_ok());
+        assert!(create(EncodingOverride::ForceDeltaOfDelta, &ints).is_ok());
+        assert!(create(EncodingOverride::ForceRle, &bools).is_ok());
+        assert!(create(EncodingOverride::Auto, &strings).is_ok());
+
+        let err = create(EncodingOverride::ForceBitpack, &strings)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ForceBitpack"), "{}", err);
+    }
+
+    #[test]
+    fn test_sparse_values() {
+        let mut values = vec![0; 1000];
+        values[10] = 5;
+        let sparse = Arc::new(Int32Array::from(values)) as ArrayRef;
+        let dense = Arc::new(Int32Array::from_iter_values(0..1000)) as ArrayRef;
+        let is_sparse = |strategy: &CoreArrayEncodingStrategy, arr: &ArrayRef| {
+            let encoded = strategy
+                .create_array_encoder(&[arr.clone()])
+                .unwrap()
+                .encode(&[arr.clone()], &mut 0)
+                .unwrap();
+            format!("{:?}", encoded.encoding).contains("Sparse {")
+        };
+
+        // Sparse encoding is only used when requested and for mostly zero values
+        let strategy = CoreArrayEncodingStrategy::default().with_sparse_values();
+        assert!(is_sparse(&strategy, &sparse));
+        assert!(!is_sparse(&strategy, &dense));
+        assert!(!is_sparse(&CoreArrayEncodingStrategy::default(), &sparse));
+    }
+
+    #[test]
+    fn test_bitpacking() {
+        let small = Arc::new(Int32Array::from_iter_values(0..1000)) as ArrayRef;
+        let large = Arc::new(Int32Array::from_iter_values(
+            (0..1000).map(|i| i32::MIN + i),
+        )) as ArrayRef;
+        let is_bitpacked = |strategy: &CoreArrayEncodingStrategy, arr: &ArrayRef| {
+            let encoded = strategy
+                .create_array_encoder(&[arr.clone()])
+                .unwrap()
+                .encode(&[arr.clone()], &mut 0)
+                .unwrap();
+            format!("{:?}", encoded.encoding).contains("Bitpacked {")
+        };
+
+        // Bitpacking is only used when requested and when it saves space
+        let strategy = CoreArrayEncodingStrategy::default().with_bitpacking();
+        assert!(is_bitpacked(&strategy, &small));
+        assert!(!is_bitpacked(&strategy, &large));
+        assert!(!is_bitpacked(&CoreArrayEncodingStrategy::default(), &small));
+    }
+
+    #[test]
+    fn test_concat_encoded() {
+        let a = Arc::new(Int32Array::from_iter_values(0..100)) as ArrayRef;
+        let b = Arc::new(Int32Array::from_iter_values(1000..1050)) as ArrayRef;
+        let expected = arrow_select::concat::concat(&[a.as_ref(), b.as_ref()]).unwrap();
+
+        for compression in [CompressionScheme::None, CompressionScheme::Zstd] {
+            let encoder = BasicEncoder::new(Box::new(
+                ValueEncoder::try_new(&DataType::Int32, compression).unwrap(),
+            ));
+            let encoded_a = encoder.encode(&[a.clone()], &mut 0).unwrap();
+            let encoded_b = encoder.encode(&[b.clone()], &mut 0).unwrap();
+            let concatenated = concat_encoded(&encoded_a, &encoded_b).unwrap();
+            let page = InMemoryPage::new(&concatenated);
+            let decoded =
+                decode_page(&page.encoding, &page.buffers(), &DataType::Int32, 0..150).unwrap();
+            assert_eq!(decoded.as_ref(), expected.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_concat_encoded_requires_decode() {
+        let encode = |arr: ArrayRef, compression| {
+            let encoder = BasicEncoder::new(Box::new(
+                ValueEncoder::try_new(arr.data_type(), compression).unwrap(),
+            ));
+            encoder.encode(&[arr], &mut 0).unwrap()
+        };
+        let ints = encode(
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            CompressionScheme::None,
+        );
+        let longs = encode(
+            Arc::new(Int64Array::from(vec![1, 2, 3])),
+            CompressionScheme::None,
+        );
+        // Small buffers do not compress well and would be stored uncompressed
+        let compressed = encode(
+            Arc::new(Int32Array::from(vec![1; 1000])),
+            CompressionScheme::Zstd,
+        );
+        let nulls = encode(
+            Arc::new(Int32Array::from(vec![Some(1), None])),
+            CompressionScheme::None,
+        );
+        let bools = encode(
+            Arc::new(BooleanArray::from(vec![true, false])),
+            CompressionScheme::None,
+        );
+        let compressed_in_blocks = BasicEncoder::new(Box::new(
+            ValueEncoderBuilder::default()
+                .compression(CompressionScheme::Zstd)
+                .compression_block_size(1024)
+                .build(&DataType::Int32)
+                .unwrap(),
+        ))
+        .encode(&[Arc::new(Int32Array::from(vec![1; 1000]))], &mut 0)
+        .unwrap();
+        let little_endian_ints = BasicEncoder::new(Box::new(
+            ValueEncoderBuilder::default()
+                .little_endian(true)
+                .build(&DataType::Int32)
+                .unwrap(),
+        ))
+        .encode(&[Arc::new(Int32Array::from(vec![1, 2, 3]))], &mut 0)
+        .unwrap();
+
+        for (a, b) in [
+            (&ints, &longs),
+            (&ints, &compressed),
+            (&ints, &nulls),
+            (&bools, &bools),
+            (&compressed, &compressed_in_blocks),
+            (&compressed_in_blocks, &compressed),
+            (&ints, &little_endian_ints),
+        ] {
+            let err = concat_encoded(a, b).unwrap_err();
+            assert!(err.to_string().contains("must be decoded"), "{}", err);
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_encode_stream() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "values",
+            DataType::Int32,
+            false,
+        )]));
+        let lance_schema = lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap();
+        let page_size = 4096;
+        let mut encoder = BatchEncoder::try_new(
+            &lance_schema,
+            &CoreFieldEncodingStrategy::default(),
+            page_size,
+            true,
+        )
+        .unwrap();
+
+        let batches_pulled = AtomicUsize::new(0);
+        let batches = stream::iter(0..1000).map(|i| {
+            batches_pulled.fetch_add(1, Ordering::Relaxed);
+            let values = Int32Array::from_iter_values(i * 100..(i + 1) * 100);
+            Ok(RecordBatch::try_new(schema.clone(), vec![Arc::new(values)]).unwrap())
+        });
+
+        let max_pages_in_flight = 2;
+        let mut pages = encode_stream(&mut encoder.field_encoders, batches, max_pages_in_flight);
+        let mut num_pages = 0;
+        let mut num_rows = 0;
+        let mut batches_per_page = 0;
+        while let Some(page) = pages.next().await {
+            let page = page.unwrap();
+            num_pages += 1;
+            num_rows += page.num_rows;
+            // Each batch is a little over 400 bytes (in memory) and so a page is made from
+            // a handful of batches
+            if num_pages == 1 {
+                batches_per_page = page.num_rows as usize / 100;
+                assert!(batches_per_page > 1);
+            }
+            // The input is only read as far as needed to fill the pages in flight
+            let pulled = batches_pulled.load(Ordering::Relaxed);
+            assert!(
+                pulled <= (num_pages + max_pages_in_flight) * batches_per_page,
+                "{} batches pulled after {} pages",
+                pulled,
+                num_pages
+            );
+            let page_bytes = page
+                .array
+                .buffers
+                .iter()
+                .flat_map(|buffer| &buffer.parts)
+                .map(|part| part.len() as u64)
+                .sum::<u64>();
+            assert!(page_bytes <= page_size + 400);
+        }
+        drop(pages);
+        assert_eq!(num_rows, 100_000);
+        assert_eq!(num_pages, 1000_usize.div_ceil(batches_per_page));
+        assert_eq!(batches_pulled.load(Ordering::Relaxed), 1000);
+
+        let columns = encoder.field_encoders[0].finish().await.unwrap();
+        assert_eq!(columns.len(), 1);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_encode_stream_wrong_columns() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "values",
+            DataType::Int32,
+            false,
+        )]));
+        let lance_schema = lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap();
+        let mut encoder = BatchEncoder::try_new(
+            &lance_schema,
+            &CoreFieldEncodingStrategy::default(),
+            4096,
+            true,
+        )
+        .unwrap();
+        let two_columns = Arc::new(ArrowSchema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            two_columns,
+            vec![
+                Arc::new(Int32Array::from(vec![1])),
+                Arc::new(Int32Array::from(vec![2])),
+            ],
+        )
+        .unwrap();
+        let mut pages = encode_stream(&mut encoder.field_encoders, stream::iter([Ok(batch)]), 2);
+        assert!(pages.next().await.unwrap().is_err());
+    }
+
+    #[test]
+    fn test_buffers_with_types() {
+        let values = Int32Array::from_iter((0..100).map(|i| (i % 5 != 0).then_some(i % 16)));
+        let encoder = BasicEncoder::new(Box::new(BitpackedArrayEncoder::new(4)));
+        let mut buffer_index = 3;
+        let encoded = encoder
+            .encode(&[Arc::new(values) as ArrayRef], &mut buffer_index)
+            .unwrap();
+
+        // A validity buffer and a bitpacked values buffer, numbered from the starting index
+        let buffers = encoded.buffers_with_types().collect::<Vec<_>>();
+        assert_eq!(
+            buffers
+                .iter()
+                .map(|(index, buffer_type, _)| (*index, *buffer_type))
+                .collect::<Vec<_>>(),
+            vec![
+                (3, pb::buffer::BufferType::Page),
+                (4, pb::buffer::BufferType::Page)
+            ]
+        );
+        let values_size = buffers[1].2.iter().map(|part| part.len()).sum::<usize>();
+        assert_eq!(values_size, 100 * 4 / 8);
+    }
+
+    #[tokio::test]
+    async fn test_page_statistics() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("ints", DataType::Int32, true),
+            Field::new("strings", DataType::Utf8, false),
+            Field::new("bools", DataType::Boolean, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter(
+                    (0..1000).map(|i| (i % 3 != 0).then_some(i % 7)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..1000).map(|i| i.to_string()),
+                )),
+                Arc::new(BooleanArray::from_iter((0..1000).map(|i| Some(i % 2 == 0)))),
+            ],
+        )
+        .unwrap();
+        let lance_schema = Arc::new(Schema::try_from(schema.as_ref()).unwrap());
+        let encoded = encode_batch(
+            &batch,
+            lance_schema,
+            &CoreFieldEncodingStrategy::default(),
+            1024 * 1024,
+        )
+        .await
+        .unwrap();
+        let distinct_counts = encoded
+            .page_table
+            .iter()
+            .map(|column| column.page_infos[0].approx_distinct_count())
+            .collect::<Vec<_>>();
+        assert_eq!(distinct_counts[0], Some(7));
+        let strings = distinct_counts[1].unwrap();
+        assert!((980..=1020).contains(&strings), "{}", strings);
+        // Statistics are not computed for booleans
+        assert_eq!(distinct_counts[2], None);
+    }
+
+    #[tokio::test]
+    async fn test_encoder_version() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("ints", DataType::Int32, true),
+            Field::new("strings", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter(
+                    (0..100).map(|i| (i % 3 != 0).then_some(i)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| i.to_string()),
+                )),
+            ],
+        )
+        .unwrap();
+        let lance_schema = Arc::new(Schema::try_from(schema.as_ref()).unwrap());
+        let mut encoded = encode_batch(
+            &batch,
+            lance_schema,
+            &CoreFieldEncodingStrategy::default(),
+            1024 * 1024,
+        )
+        .await
+        .unwrap();
+        for column in &encoded.page_table {
+            for page in column.page_infos.iter() {
+                let producer = page.producer().unwrap();
+                assert_eq!(producer, &encoder_version());
+                assert_eq!(producer.crate_version, env!("CARGO_PKG_VERSION"));
+                assert_eq!(producer.revision, ENCODER_REVISION);
+            }
+        }
+
+        // Pages written before the version was recorded don't have it
+        encoded.page_table = encoded
+            .page_table
+            .iter()
+            .map(|column| {
+                let page_infos = column
+                    .page_infos
+                    .iter()
+                    .map(|page| PageInfo {
+                        num_rows: page.num_rows,
+                        encoding: pb::ArrayEncoding {
+                            producer: None,
+                            ..page.encoding.clone()
+                        },
+                        buffer_offsets_and_sizes: page.buffer_offsets_and_sizes.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                Arc::new(ColumnInfo {
+                    page_infos: page_infos.into(),
+                    ..column.as_ref().clone()
+                })
+            })
+            .collect();
+        assert!(encoded.page_table[0].page_infos[0].producer().is_none());
+        let decoded = decode_batch(
+            &encoded,
+            &FilterExpression::no_filter(),
+            &DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(decoded, batch);
+    }
 }
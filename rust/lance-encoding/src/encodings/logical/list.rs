@@ -10,7 +10,7 @@ use arrow_array::{
     Array, ArrayRef, BooleanArray, Int32Array, Int64Array, LargeListArray, ListArray, UInt64Array,
 };
 use arrow_buffer::{BooleanBuffer, BooleanBufferBuilder, Buffer, NullBuffer, OffsetBuffer};
-use arrow_schema::{DataType, Field, Fields};
+use arrow_schema::{DataType, Field, FieldRef, Fields};
 use futures::{future::BoxFuture, FutureExt};
 use log::trace;
 use snafu::{location, Location};
@@ -455,7 +455,7 @@ impl<'a> SchedulingJob for ListFieldSchedulingJob<'a> {
             item_decoder: None,
             rows_drained: 0,
             lists_available: 0,
-            item_field_name: self.scheduler.item_field_name.clone(),
+            items_field: self.scheduler.items_field.clone(),
             num_rows,
             unloaded: Some(indirect_fut),
             items_type: self.scheduler.items_type.clone(),
@@ -492,7 +492,7 @@ impl<'a> SchedulingJob for ListFieldSchedulingJob<'a> {
 pub struct ListFieldScheduler {
     offsets_scheduler: Arc<dyn FieldScheduler>,
     items_scheduler: Arc<dyn FieldScheduler>,
-    item_field_name: String,
+    items_field: FieldRef,
     items_type: DataType,
     offset_type: DataType,
     list_type: DataType,
@@ -514,26 +514,27 @@ impl ListFieldScheduler {
     pub fn new(
         offsets_scheduler: Arc<dyn FieldScheduler>,
         items_scheduler: Arc<dyn FieldScheduler>,
-        item_field_name: String,
-        items_type: DataType,
+        items_field: FieldRef,
         // Should be int32 or int64
         offset_type: DataType,
         offset_page_info: Vec<OffsetPageInfo>,
     ) -> Self {
+        // TODO: we default to nullable true here, should probably use the nullability given to
+        // us from the input schema
+        let items_field = Arc::new(
+            Field::new(items_field.name(), items_field.data_type().clone(), true)
+                .with_metadata(items_field.metadata().clone()),
+        );
         let list_type = match &offset_type {
-            DataType::Int32 => {
-                DataType::List(Arc::new(Field::new("item", items_type.clone(), true)))
-            }
-            DataType::Int64 => {
-                DataType::LargeList(Arc::new(Field::new("item", items_type.clone(), true)))
-            }
+            DataType::Int32 => DataType::List(items_field.clone()),
+            DataType::Int64 => DataType::LargeList(items_field.clone()),
             _ => panic!("Unexpected offset type {}", offset_type),
         };
         Self {
             offsets_scheduler,
             items_scheduler,
-            item_field_name,
-            items_type,
+            items_type: items_field.data_type().clone(),
+            items_field,
             offset_type,
             offset_page_info,
             list_type,
@@ -577,7 +578,7 @@ struct ListPageDecoder {
     lists_available: u64,
     num_rows: u64,
     rows_drained: u64,
-    item_field_name: String,
+    items_field: FieldRef,
     items_type: DataType,
     offset_type: DataType,
     data_type: DataType,
@@ -588,7 +589,7 @@ struct ListDecodeTask {
     validity: BooleanBuffer,
     // Will be None if there are no items (all empty / null lists)
     items: Option<Box<dyn DecodeArrayTask>>,
-    item_field_name: String,
+    items_field: FieldRef,
     items_type: DataType,
     offset_type: DataType,
 }
@@ -605,13 +606,7 @@ impl DecodeArrayTask for ListDecodeTask {
             })
             .unwrap_or_else(|| Ok(new_empty_array(&self.items_type)))?;
 
-        // TODO: we default to nullable true here, should probably use the nullability given to
-        // us from the input schema
-        let item_field = Arc::new(Field::new(
-            self.item_field_name,
-            self.items_type.clone(),
-            true,
-        ));
+        let item_field = self.items_field;
 
         // The offsets are already decoded but they need to be shifted back to 0 and cast
         // to the appropriate type
@@ -766,7 +761,7 @@ impl LogicalPageDecoder for ListPageDecoder {
             task: Box::new(ListDecodeTask {
                 offsets,
                 validity,
-                item_field_name: self.item_field_name.clone(),
+                items_field: self.items_field.clone(),
                 items: item_decode,
                 items_type: self.items_type.clone(),
                 offset_type: self.offset_type.clone(),
@@ -898,11 +893,7 @@ impl ListOffsetsEncoder {
                 num_rows,
                 inner_encoder,
             )?;
-            Ok(EncodedPage {
-                array,
-                num_rows,
-                column_idx,
-            })
+            Ok(EncodedPage::new(array, num_rows, column_idx))
         })
         .map(|res_res| res_res.unwrap())
         .boxed()
@@ -1084,6 +1075,9 @@ impl ListOffsetsEncoder {
                         num_items: total_span,
                     },
                 ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
         })
     }
@@ -1124,6 +1118,11 @@ impl ListFieldEncoder {
 
 impl FieldEncoder for ListFieldEncoder {
     fn maybe_encode(&mut self, array: ArrayRef) -> Result<Vec<EncodeTask>> {
+        // An empty list array still has a single offset but there are no lists to
+        // write so we skip it entirely
+        if array.is_empty() {
+            return Ok(vec![]);
+        }
         // The list may have an offset / shorter length which means the underlying
         // values array could be longer than what we need to encode and so we need
         // to slice down to the region of interest.
@@ -1291,6 +1290,28 @@ mod tests {
         check_round_trip_encoding_of_data(vec![list_array], &test_cases).await;
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_empty_list_array() {
+        // A list array with zero rows (not to be confused with a list array of empty lists)
+        // between non-empty arrays should be skipped by the encoder
+        let mut list_builder = ListBuilder::new(Int32Builder::new());
+        list_builder.append_value([Some(1), None, Some(3)]);
+        list_builder.append_null();
+        let list_array = Arc::new(list_builder.finish()) as ArrayRef;
+        let empty_array = list_array.slice(0, 0);
+
+        let test_cases = TestCases::default()
+            .with_range(0..0)
+            .with_range(1..3)
+            .with_indices(vec![])
+            .with_indices(vec![0, 3]);
+        check_round_trip_encoding_of_data(
+            vec![list_array.clone(), empty_array, list_array],
+            &test_cases,
+        )
+        .await;
+    }
+
     #[test_log::test(tokio::test)]
     #[ignore] // This test is quite slow in debug mode
     async fn test_jumbo_list() {
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::{fmt::Debug, ops::Range, sync::Arc};
+use std::{fmt::Debug, ops::Range, sync::Arc, time::Instant};
 
 use arrow_array::{new_null_array, ArrayRef};
 use arrow_schema::DataType;
@@ -9,24 +9,32 @@ use futures::{future::BoxFuture, FutureExt};
 use lance_arrow::deepcopy::deep_copy_array;
 use log::{debug, trace};
 
-use lance_core::Result;
+use lance_core::datatypes::LogicalType;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
 
 use crate::{
+    cardinality::estimate_distinct_count,
     decoder::{
         DecodeArrayTask, FieldScheduler, FilterExpression, LogicalPageDecoder, NextDecodeTask,
         PageInfo, PageScheduler, PrimitivePageDecoder, ScheduledScanLine, SchedulerContext,
-        SchedulingJob,
+        SchedulingJob, SkipResult,
     },
     encoder::{ArrayEncodingStrategy, EncodeTask, EncodedColumn, EncodedPage, FieldEncoder},
     encodings::physical::{decoder_from_array_encoding, ColumnBuffers, PageBuffers},
+    format::pb,
 };
 
-use crate::encodings::utils::primitive_array_from_buffers;
+use crate::encodings::utils::{primitive_array_from_buffers, primitive_array_from_shared_buffers};
+use crate::page_cache::DecodedPageCache;
+use crate::stats::DecodeStats;
 
 #[derive(Debug)]
 struct PrimitivePage {
     scheduler: Box<dyn PageScheduler>,
     num_rows: u64,
+    // True if the decoded page should be kept in the decoded page cache (if there is one)
+    cacheable: bool,
 }
 
 /// A field scheduler for primitive fields
@@ -40,13 +48,22 @@ struct PrimitivePage {
 /// primitive types.  This is slightly different than arrow-rs's definition
 #[derive(Debug)]
 pub struct PrimitiveFieldScheduler {
+    column_index: u32,
     data_type: DataType,
     page_schedulers: Vec<PrimitivePage>,
     num_rows: u64,
 }
 
 impl PrimitiveFieldScheduler {
-    pub fn new(data_type: DataType, pages: Arc<[PageInfo]>, buffers: ColumnBuffers) -> Self {
+    /// Creates a scheduler for the pages of a primitive column
+    ///
+    /// Returns an error if any of the pages use an encoding that is not recognized
+    pub fn try_new(
+        column_index: u32,
+        data_type: DataType,
+        pages: Arc<[PageInfo]>,
+        buffers: ColumnBuffers,
+    ) -> Result<Self> {
         let page_schedulers = pages
             .iter()
             .map(|page| {
@@ -54,20 +71,27 @@ impl PrimitiveFieldScheduler {
                     column_buffers: buffers,
                     positions_and_sizes: &page.buffer_offsets_and_sizes,
                 };
-                let scheduler =
-                    decoder_from_array_encoding(&page.encoding, &page_buffers, &data_type);
-                PrimitivePage {
+                let scheduler = decoder_from_array_encoding(
+                    &page.encoding,
+                    &page_buffers,
+                    stored_type(&data_type),
+                )?;
+                let cacheable =
+                    DecodedPageCache::is_cacheable(&data_type, scheduler.as_ref(), page.num_rows);
+                Ok(PrimitivePage {
                     scheduler,
                     num_rows: page.num_rows,
-                }
+                    cacheable,
+                })
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>>>()?;
         let num_rows = page_schedulers.iter().map(|p| p.num_rows).sum();
-        Self {
+        Ok(Self {
+            column_index,
             data_type,
             page_schedulers,
             num_rows,
-        }
+        })
     }
 }
 
@@ -150,13 +174,31 @@ impl<'a> SchedulingJob for PrimitiveFieldSchedulingJob<'a> {
             cur_page.num_rows
         );
 
+        let page_idx = self.page_idx;
         self.global_row_offset += cur_page.num_rows;
         self.page_idx += 1;
 
-        let physical_decoder =
-            cur_page
+        let decode_stats = context.decode_stats().cloned();
+        if let Some(stats) = &decode_stats {
+            stats.record_page_scheduled();
+        }
+
+        let physical_decoder = match context.page_cache() {
+            Some(page_cache) if cur_page.cacheable => page_cache.schedule_ranges(
+                self.scheduler.column_index,
+                page_idx as u32,
+                cur_page.scheduler.as_ref(),
+                cur_page.num_rows,
+                &self.scheduler.data_type,
+                &ranges_in_page,
+                context.io(),
+                top_level_row,
+                decode_stats.as_deref(),
+            ),
+            _ => cur_page
                 .scheduler
-                .schedule_ranges(&ranges_in_page, context.io(), top_level_row);
+                .schedule_ranges(&ranges_in_page, context.io(), top_level_row),
+        };
 
         let logical_decoder = PrimitiveFieldDecoder {
             data_type: self.scheduler.data_type.clone(),
@@ -164,6 +206,7 @@ impl<'a> SchedulingJob for PrimitiveFieldSchedulingJob<'a> {
             physical_decoder: None,
             rows_drained: 0,
             num_rows: num_rows_in_next,
+            decode_stats,
         };
 
         let decoder = Box::new(logical_decoder);
@@ -203,6 +246,7 @@ pub struct PrimitiveFieldDecoder {
     physical_decoder: Option<Arc<dyn PrimitivePageDecoder>>,
     num_rows: u64,
     rows_drained: u64,
+    decode_stats: Option<Arc<DecodeStats>>,
 }
 
 impl PrimitiveFieldDecoder {
@@ -217,6 +261,7 @@ impl PrimitiveFieldDecoder {
             physical_decoder: Some(physical_decoder),
             num_rows,
             rows_drained: 0,
+            decode_stats: None,
         }
     }
 }
@@ -236,25 +281,82 @@ struct PrimitiveFieldDecodeTask {
     rows_to_take: u64,
     physical_decoder: Arc<dyn PrimitivePageDecoder>,
     data_type: DataType,
+    decode_stats: Option<Arc<DecodeStats>>,
 }
 
-impl DecodeArrayTask for PrimitiveFieldDecodeTask {
-    fn decode(self: Box<Self>) -> Result<ArrayRef> {
-        let mut all_null = false;
-
-        // The number of buffers needed is based on the data type.
-        // Most data types need two buffers but each layer of fixed-size-list, for
-        // example, adds another validity buffer.
-        let bufs =
-            self.physical_decoder
-                .decode(self.rows_to_skip, self.rows_to_take, &mut all_null)?;
+// The type of the values on disk, dictionaries are stored as their values
+fn stored_type(data_type: &DataType) -> &DataType {
+    match data_type {
+        DataType::Dictionary(_, value_type) => value_type,
+        _ => data_type,
+    }
+}
 
-        if all_null {
-            return Ok(new_null_array(&self.data_type, self.rows_to_take as usize));
+/// Decodes `rows_to_take` rows, after skipping `rows_to_skip` rows, from a loaded page
+///
+/// Returns the array along with the number of decoded bytes.  This is the CPU half of
+/// decoding a primitive page, the I/O half is [`PageScheduler::schedule_ranges`].
+pub(crate) fn decode_primitive_page(
+    physical_decoder: &dyn PrimitivePageDecoder,
+    data_type: &DataType,
+    rows_to_skip: u64,
+    rows_to_take: u64,
+) -> Result<(ArrayRef, u64)> {
+    if let DataType::Dictionary(_, value_type) = data_type {
+        let array = match physical_decoder.decode_dictionary(rows_to_skip, rows_to_take)? {
+            Some(array) => array,
+            // The page stores plain values and so the dictionary has to be built
+            None => {
+                decode_primitive_page(physical_decoder, value_type, rows_to_skip, rows_to_take)?.0
+            }
+        };
+        let num_bytes = array.get_array_memory_size() as u64;
+        let array = if array.data_type() == data_type {
+            array
+        } else {
+            arrow_cast::cast(&array, data_type)?
+        };
+        return Ok((array, num_bytes));
+    }
+    // Fast path, use the loaded data as-is if the decoder can provide it
+    if data_type.is_primitive() {
+        if let Some(bufs) = physical_decoder.decode_shared(rows_to_skip, rows_to_take) {
+            let num_bytes = bufs.iter().map(|buf| buf.len() as u64).sum();
+            let array = primitive_array_from_shared_buffers(data_type, bufs, rows_to_take)?;
+            return Ok((array, num_bytes));
         }
+    }
+
+    let mut all_null = false;
+
+    // The number of buffers needed is based on the data type.
+    // Most data types need two buffers but each layer of fixed-size-list, for
+    // example, adds another validity buffer.
+    let bufs = physical_decoder.decode(rows_to_skip, rows_to_take, &mut all_null)?;
 
-        // Convert the buffers into an Arrow array
-        primitive_array_from_buffers(&self.data_type, bufs, self.rows_to_take)
+    if all_null {
+        return Ok((new_null_array(data_type, rows_to_take as usize), 0));
+    }
+
+    // Convert the buffers into an Arrow array
+    let num_bytes = bufs.iter().map(|buf| buf.len() as u64).sum();
+    let array = primitive_array_from_buffers(data_type, bufs, rows_to_take)?;
+    Ok((array, num_bytes))
+}
+
+impl DecodeArrayTask for PrimitiveFieldDecodeTask {
+    fn decode(self: Box<Self>) -> Result<ArrayRef> {
+        let start = Instant::now();
+        let (array, num_bytes) = decode_primitive_page(
+            self.physical_decoder.as_ref(),
+            &self.data_type,
+            self.rows_to_skip,
+            self.rows_to_take,
+        )?;
+        if let Some(stats) = &self.decode_stats {
+            stats.record_decode(self.rows_to_take, num_bytes, start.elapsed());
+        }
+        Ok(array)
     }
 }
 
@@ -281,6 +383,7 @@ impl LogicalPageDecoder for PrimitiveFieldDecoder {
             rows_to_take,
             physical_decoder: self.physical_decoder.as_ref().unwrap().clone(),
             data_type: self.data_type.clone(),
+            decode_stats: self.decode_stats.clone(),
         });
 
         Ok(NextDecodeTask {
@@ -290,6 +393,27 @@ impl LogicalPageDecoder for PrimitiveFieldDecoder {
         })
     }
 
+    fn skip(&mut self, num_rows: u64) -> Result<SkipResult> {
+        if num_rows > self.avail() {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot skip {} rows, only {} rows are available",
+                    num_rows,
+                    self.avail()
+                ),
+                location!(),
+            ));
+        }
+        // The physical decoder is stateless and decodes from any row and so skipping is
+        // only a matter of moving the cursor
+        if self.physical_decoder.as_ref().unwrap().can_skip() {
+            self.rows_drained += num_rows;
+            Ok(SkipResult::Skipped)
+        } else {
+            Ok(SkipResult::RequiresDecode)
+        }
+    }
+
     fn unawaited(&self) -> u64 {
         if self.unloaded_physical_decoder.is_some() {
             self.num_rows
@@ -405,18 +529,28 @@ impl PrimitiveFieldEncoder {
 
     // Creates an encode task, consuming all buffered data
     fn do_flush(&mut self, arrays: Vec<ArrayRef>) -> Result<EncodeTask> {
-        let encoder = self.array_encoding_strategy.create_array_encoder(&arrays)?;
+        let approx_distinct_count = estimate_distinct_count(&arrays);
+        let encoder = self
+            .array_encoding_strategy
+            .create_page_encoder(&arrays, approx_distinct_count)?;
         let column_idx = self.column_index;
+        let data_type = if self.array_encoding_strategy.embeds_data_type() {
+            Some(LogicalType::try_from(arrays[0].data_type())?.to_string())
+        } else {
+            None
+        };
 
         Ok(tokio::task::spawn(async move {
             let num_rows = arrays.iter().map(|arr| arr.len() as u64).sum();
             let mut buffer_index = 0;
             let array = encoder.encode(&arrays, &mut buffer_index)?;
-            Ok(EncodedPage {
-                array,
-                num_rows,
-                column_idx,
-            })
+            let mut page = EncodedPage::new(array, num_rows, column_idx);
+            page.array.encoding.statistics =
+                approx_distinct_count.map(|approx_distinct_count| pb::PageStatistics {
+                    approx_distinct_count,
+                });
+            page.array.encoding.data_type = data_type;
+            Ok(page)
         })
         .map(|res_res| res_res.unwrap())
         .boxed())
@@ -426,6 +560,15 @@ impl PrimitiveFieldEncoder {
 impl FieldEncoder for PrimitiveFieldEncoder {
     // Buffers data, if there is enough to write a page then we create an encode task
     fn maybe_encode(&mut self, array: ArrayRef) -> Result<Vec<EncodeTask>> {
+        // Empty arrays contribute nothing to a page and are simply skipped
+        if array.is_empty() {
+            return Ok(vec![]);
+        }
+        // Dictionaries are stored as their values, see `stored_type`
+        let array = match array.data_type() {
+            DataType::Dictionary(_, value_type) => arrow_cast::cast(&array, value_type)?,
+            _ => array,
+        };
         if let Some(arrays) = self.accumulation_queue.insert(array) {
             Ok(vec![self.do_flush(arrays)?])
         } else {
@@ -489,18 +489,21 @@ impl FieldEncoder for StructFieldEncoder {
         // In this "simple struct / no nulls" case we emit a single header page at
         // the very end which covers the entire struct.
         child_tasks.push(
-            std::future::ready(Ok(EncodedPage {
-                array: EncodedArray {
+            std::future::ready(Ok(EncodedPage::new(
+                EncodedArray {
                     buffers: vec![],
                     encoding: pb::ArrayEncoding {
                         array_encoding: Some(pb::array_encoding::ArrayEncoding::Struct(
                             pb::SimpleStruct {},
                         )),
+                        producer: None,
+                        statistics: None,
+                        data_type: None,
                     },
                 },
-                num_rows: num_rows_seen,
-                column_idx: column_index,
-            }))
+                num_rows_seen,
+                column_index,
+            )))
             .boxed(),
         );
         Ok(child_tasks)
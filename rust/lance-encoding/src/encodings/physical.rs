@@ -1,32 +1,66 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use arrow_array::ArrayRef;
 use arrow_schema::DataType;
+use bytes::Bytes;
 use fsst::FsstPageScheduler;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
 
 use crate::encodings::physical::value::CompressionScheme;
-use crate::{decoder::PageScheduler, format::pb};
+use crate::{
+    decoder::{DecoderConfig, PageScheduler},
+    format::pb,
+};
 
 use self::value::parse_compression_scheme;
 use self::{
-    basic::BasicPageScheduler, binary::BinaryPageScheduler, bitmap::DenseBitmapScheduler,
-    dictionary::DictionaryPageScheduler, fixed_size_list::FixedListScheduler,
+    basic::BasicPageScheduler,
+    binary::BinaryPageScheduler,
+    bitmap::DenseBitmapScheduler,
+    bitpack::{BitpackedScheduler, ChunkedBitpackedScheduler},
+    boolean_rle::BooleanRleScheduler,
+    delta_of_delta::DeltaOfDeltaScheduler,
+    dictionary::DictionaryPageScheduler,
+    fixed_size_list::FixedListScheduler,
+    quantize::{QuantizeParams, QuantizedScheduler},
+    sparse::SparseScheduler,
+    transform::TransformPipelineScheduler,
     value::ValuePageScheduler,
 };
 
 pub mod basic;
 pub mod binary;
 pub mod bitmap;
+pub mod bitpack;
+pub(crate) mod bits;
+pub mod bloom;
+pub mod boolean_rle;
 pub mod buffers;
+pub mod default_value;
+pub mod delta_of_delta;
 pub mod dictionary;
 pub mod fixed_size_list;
 pub mod fsst;
+pub mod multi_page;
+pub mod quantize;
+pub mod sparse;
+pub mod transform;
 pub mod value;
+pub mod zero_fill;
 
 /// These contain the file buffers shared across the entire file
 #[derive(Clone, Copy, Debug)]
 pub struct FileBuffers<'a> {
     pub positions_and_sizes: &'a [(u64, u64)],
+    /// Dictionaries shared by many pages, keyed by the id that pages refer to them with
+    ///
+    /// These are not part of the pages and so they must be loaded (e.g. from the file or
+    /// dataset metadata) before decoding pages that use them.
+    pub shared_dictionaries: &'a [(u32, ArrayRef)],
+    /// Options that control how the pages of the file are decoded
+    pub decoder_config: DecoderConfig,
 }
 
 /// These contain the file buffers and also buffers specific to a column
@@ -57,58 +91,284 @@ fn get_buffer(buffer_desc: &pb::Buffer, buffers: &PageBuffers) -> (u64, u64) {
     }
 }
 
-/// Convert a protobuf buffer encoding into a physical page scheduler
-fn get_buffer_decoder(encoding: &pb::Flat, buffers: &PageBuffers) -> Box<dyn PageScheduler> {
-    let (buffer_offset, buffer_size) = get_buffer(encoding.buffer.as_ref().unwrap(), buffers);
-    let compression_scheme = if encoding.compression.is_none() {
-        CompressionScheme::None
-    } else {
-        parse_compression_scheme(encoding.compression.as_ref().unwrap().scheme.as_str()).unwrap()
-    };
+/// The version of the flat encoding that is written by this version of Lance
+///
+/// Pages that are compressed in blocks are written as [`FLAT_ENCODING_VERSION_BLOCKS`].
+pub const FLAT_ENCODING_VERSION: u32 = 1;
+/// The version of the flat encoding for pages compressed in independent blocks
+pub const FLAT_ENCODING_VERSION_BLOCKS: u32 = 2;
+
+/// The widths of the components of the values of a flat page whose byte order has to be
+/// swapped to read them on this host, empty if the values are in the host's byte order
+///
+/// Only values with a single byte-ordered component are stored little-endian.
+fn flat_byte_swap(encoding: &pb::Flat) -> Result<&'static [usize]> {
+    if !encoding.little_endian || cfg!(target_endian = "little") {
+        return Ok(&[]);
+    }
     match encoding.bits_per_value {
+        16 => Ok(&[2]),
+        32 => Ok(&[4]),
+        64 => Ok(&[8]),
+        128 => Ok(&[16]),
+        bits_per_value => Err(Error::corrupt_metadata(
+            format!(
+                "{}-bit values cannot be stored in little-endian byte order",
+                bits_per_value
+            ),
+            location!(),
+        )),
+    }
+}
+
+/// Convert a protobuf flat encoding into a physical page scheduler
+///
+/// The layout of a flat page depends on its version.  Every version that has ever been
+/// written must keep decoding, even as the current layout changes.
+fn flat_scheduler(
+    encoding: &pb::Flat,
+    buffer_offset: u64,
+    buffer_size: u64,
+    max_decompressed_size: usize,
+) -> Result<Box<dyn PageScheduler>> {
+    let byte_swap = flat_byte_swap(encoding)?;
+    match encoding.version {
+        // Version 0 means the page was written before the version was recorded
+        0 | 1 => flat_v1_scheduler(
+            encoding,
+            buffer_offset,
+            buffer_size,
+            byte_swap,
+            max_decompressed_size,
+        ),
+        FLAT_ENCODING_VERSION_BLOCKS => {
+            if encoding.bits_per_value % 8 != 0 || encoding.compression.is_none() {
+                return Err(Error::corrupt_metadata(
+                    format!(
+                        "a block-compressed flat page must be compressed and have whole bytes per value, not {} bits",
+                        encoding.bits_per_value
+                    ),
+                    location!(),
+                ));
+            }
+            let compression_scheme =
+                parse_compression_scheme(&encoding.compression.as_ref().unwrap().scheme)?;
+            if compression_scheme == CompressionScheme::None {
+                return Err(Error::corrupt_metadata(
+                    "a block-compressed flat page is not compressed",
+                    location!(),
+                ));
+            }
+            Ok(Box::new(
+                ValuePageScheduler::new(
+                    encoding.bits_per_value / 8,
+                    buffer_offset,
+                    buffer_size,
+                    compression_scheme,
+                )
+                .with_compression_blocks()
+                .with_byte_swap(byte_swap)
+                .with_max_decompressed_size(max_decompressed_size),
+            ))
+        }
+        version => Err(Error::corrupt_metadata(
+            format!(
+                "flat encoding version {} is not supported (the newest supported version is {}), the file may have been written by a newer version of Lance",
+                version, FLAT_ENCODING_VERSION_BLOCKS
+            ),
+            location!(),
+        )),
+    }
+}
+
+/// A v1 flat page is a single buffer of values, optionally compressed as a whole
+fn flat_v1_scheduler(
+    encoding: &pb::Flat,
+    buffer_offset: u64,
+    buffer_size: u64,
+    byte_swap: &'static [usize],
+    max_decompressed_size: usize,
+) -> Result<Box<dyn PageScheduler>> {
+    let compression_scheme = match encoding.compression.as_ref() {
+        None => CompressionScheme::None,
+        Some(compression) => parse_compression_scheme(&compression.scheme)?,
+    };
+    Ok(match encoding.bits_per_value {
         1 => Box::new(DenseBitmapScheduler::new(buffer_offset)),
         bits_per_value => {
             if bits_per_value % 8 != 0 {
-                todo!("bits_per_value that are not multiples of 8");
+                return Err(Error::corrupt_metadata(
+                    format!(
+                        "flat encoding with {} bits per value, only bitmaps and whole bytes are supported",
+                        bits_per_value
+                    ),
+                    location!(),
+                ));
             }
-            Box::new(ValuePageScheduler::new(
-                bits_per_value / 8,
+            Box::new(
+                ValuePageScheduler::new(
+                    bits_per_value / 8,
+                    buffer_offset,
+                    buffer_size,
+                    compression_scheme,
+                )
+                .with_byte_swap(byte_swap)
+                .with_max_decompressed_size(max_decompressed_size),
+            )
+        }
+    })
+}
+
+fn chunked_bitpacked_scheduler(
+    chunked: &pb::ChunkedBitpacked,
+    buffer_offset: u64,
+    strict: bool,
+) -> Result<Box<dyn PageScheduler>> {
+    let bits = chunked.uncompressed_bits_per_value;
+    if chunked.values_per_chunk == 0
+        || chunked
+            .chunk_bits_per_value
+            .iter()
+            .any(|chunk_bits| *chunk_bits == 0 || *chunk_bits as u64 > bits)
+    {
+        return Err(Error::corrupt_metadata(
+            format!(
+                "invalid chunk widths for {}-bit values in a chunked bitpacked page",
+                bits
+            ),
+            location!(),
+        ));
+    }
+    Ok(Box::new(
+        ChunkedBitpackedScheduler::new(
+            chunked.values_per_chunk,
+            chunked.chunk_bits_per_value.clone(),
+            bits,
+            buffer_offset,
+            chunked.signed,
+        )
+        .with_num_values(chunked.num_values)
+        .with_strict(strict),
+    ))
+}
+
+/// Convert a protobuf array encoding that stores its data in a single buffer into a physical
+/// page scheduler
+///
+/// Unlike [`decoder_from_array_encoding`] this does not need the buffer tables of the file, the
+/// position and size of the buffer are given directly.  Only leaf encodings are supported (flat
+/// values, which may be compressed, bitmaps, bitpacked values and run-length encoded booleans).
+/// Other encodings return an error.
+pub fn scheduler_from_encoding(
+    encoding: &pb::ArrayEncoding,
+    buffer_offset: u64,
+    buffer_size: u64,
+    decoder_config: DecoderConfig,
+) -> Result<Box<dyn PageScheduler>> {
+    match required(encoding.array_encoding.as_ref(), "array encoding")? {
+        pb::array_encoding::ArrayEncoding::Flat(flat) => {
+            flat_scheduler(
+                flat,
                 buffer_offset,
                 buffer_size,
-                compression_scheme,
+                decoder_config.max_decompressed_size,
+            )
+        }
+        pb::array_encoding::ArrayEncoding::Bitpacked(bitpacked) => {
+            if bitpacked.word_aligned
+                && !(1..=32).contains(&bitpacked.compressed_bits_per_value)
+            {
+                return Err(Error::corrupt_metadata(
+                    format!(
+                        "cannot word align {}-bit values in a bitpacked page",
+                        bitpacked.compressed_bits_per_value
+                    ),
+                    location!(),
+                ));
+            }
+            Ok(Box::new(
+                BitpackedScheduler::new(
+                    bitpacked.compressed_bits_per_value,
+                    bitpacked.uncompressed_bits_per_value,
+                    buffer_offset,
+                    bitpacked.signed,
+                )
+                .with_word_alignment(bitpacked.word_aligned)
+                .with_num_values(bitpacked.num_values)
+                .with_strict(decoder_config.strict_bitpacking),
             ))
         }
+        pb::array_encoding::ArrayEncoding::ChunkedBitpacked(chunked) => {
+            chunked_bitpacked_scheduler(chunked, buffer_offset, decoder_config.strict_bitpacking)
+        }
+        pb::array_encoding::ArrayEncoding::BooleanRle(boolean_rle) => {
+            Ok(Box::new(BooleanRleScheduler::new(
+                buffer_offset,
+                buffer_size,
+                boolean_rle.num_values,
+                boolean_rle.first_value,
+            )))
+        }
+        _ => Err(Error::invalid_input(
+            "Only flat, bitpacked and boolean run-length encodings can be scheduled from a single buffer, use decoder_from_array_encoding for other encodings",
+            location!(),
+        )),
     }
 }
 
+/// Returns a nested encoding or an error if the encoding is missing
+///
+/// Prost drops oneof variants it does not recognize so an encoding written by a newer
+/// version of Lance shows up here as `None`.
+fn required<'a, T>(encoding: Option<&'a T>, description: &str) -> Result<&'a T> {
+    encoding.ok_or_else(|| {
+        Error::corrupt_metadata(
+            format!(
+                "The {} is missing or uses an encoding that this version of Lance does not recognize (the file may have been written by a newer version of Lance)",
+                description
+            ),
+            location!(),
+        )
+    })
+}
+
 /// Convert a protobuf array encoding into a physical page scheduler
+///
+/// Returns an error if the encoding (or any nested encoding) is not recognized
 pub fn decoder_from_array_encoding(
     encoding: &pb::ArrayEncoding,
     buffers: &PageBuffers,
     data_type: &DataType,
-) -> Box<dyn PageScheduler> {
-    match encoding.array_encoding.as_ref().unwrap() {
+) -> Result<Box<dyn PageScheduler>> {
+    let array_encoding = required(encoding.array_encoding.as_ref(), "array encoding")?;
+    Ok(match array_encoding {
         pb::array_encoding::ArrayEncoding::Nullable(basic) => {
-            match basic.nullability.as_ref().unwrap() {
+            match required(
+                basic.nullability.as_ref(),
+                "nullability of a nullable encoding",
+            )? {
                 pb::nullable::Nullability::NoNulls(no_nulls) => Box::new(
                     BasicPageScheduler::new_non_nullable(decoder_from_array_encoding(
-                        no_nulls.values.as_ref().unwrap(),
+                        required(no_nulls.values.as_ref(), "values of a nullable encoding")?,
                         buffers,
                         data_type,
-                    )),
+                    )?),
                 ),
                 pb::nullable::Nullability::SomeNulls(some_nulls) => {
                     Box::new(BasicPageScheduler::new_nullable(
                         decoder_from_array_encoding(
-                            some_nulls.validity.as_ref().unwrap(),
+                            required(
+                                some_nulls.validity.as_ref(),
+                                "validity of a nullable encoding",
+                            )?,
                             buffers,
                             data_type,
-                        ),
+                        )?,
                         decoder_from_array_encoding(
-                            some_nulls.values.as_ref().unwrap(),
+                            required(some_nulls.values.as_ref(), "values of a nullable encoding")?,
                             buffers,
                             data_type,
-                        ),
+                        )?,
                     ))
                 }
                 pb::nullable::Nullability::AllNulls(_) => {
@@ -116,10 +376,137 @@ pub fn decoder_from_array_encoding(
                 }
             }
         }
-        pb::array_encoding::ArrayEncoding::Flat(flat) => get_buffer_decoder(flat, buffers),
+        pb::array_encoding::ArrayEncoding::Flat(flat) => {
+            let (buffer_offset, buffer_size) = get_buffer(
+                required(flat.buffer.as_ref(), "buffer of a flat encoding")?,
+                buffers,
+            );
+            scheduler_from_encoding(
+                encoding,
+                buffer_offset,
+                buffer_size,
+                buffers.column_buffers.file_buffers.decoder_config,
+            )?
+        }
+        pb::array_encoding::ArrayEncoding::Bitpacked(bitpacked) => {
+            let (buffer_offset, buffer_size) = get_buffer(
+                required(bitpacked.buffer.as_ref(), "buffer of a bitpacked encoding")?,
+                buffers,
+            );
+            scheduler_from_encoding(
+                encoding,
+                buffer_offset,
+                buffer_size,
+                buffers.column_buffers.file_buffers.decoder_config,
+            )?
+        }
+        pb::array_encoding::ArrayEncoding::ChunkedBitpacked(chunked) => {
+            let (buffer_offset, buffer_size) = get_buffer(
+                required(
+                    chunked.buffer.as_ref(),
+                    "buffer of a chunked bitpacked encoding",
+                )?,
+                buffers,
+            );
+            scheduler_from_encoding(
+                encoding,
+                buffer_offset,
+                buffer_size,
+                buffers.column_buffers.file_buffers.decoder_config,
+            )?
+        }
+        pb::array_encoding::ArrayEncoding::BooleanRle(boolean_rle) => {
+            let (buffer_offset, buffer_size) = get_buffer(
+                required(
+                    boolean_rle.buffer.as_ref(),
+                    "buffer of a boolean run-length encoding",
+                )?,
+                buffers,
+            );
+            scheduler_from_encoding(
+                encoding,
+                buffer_offset,
+                buffer_size,
+                buffers.column_buffers.file_buffers.decoder_config,
+            )?
+        }
+        pb::array_encoding::ArrayEncoding::TransformPipeline(pipeline) => {
+            let (buffer_offset, buffer_size) = get_buffer(
+                required(
+                    pipeline.buffer.as_ref(),
+                    "buffer of a transform pipeline encoding",
+                )?,
+                buffers,
+            );
+            Box::new(TransformPipelineScheduler::try_new(
+                pipeline,
+                buffer_offset,
+                buffer_size,
+            )?)
+        }
+        pb::array_encoding::ArrayEncoding::DeltaOfDelta(delta_of_delta) => {
+            let deltas_scheduler = decoder_from_array_encoding(
+                required(
+                    delta_of_delta.deltas.as_ref(),
+                    "deltas of a delta-of-delta encoding",
+                )?,
+                buffers,
+                data_type,
+            )?;
+            Box::new(DeltaOfDeltaScheduler::new(
+                deltas_scheduler,
+                delta_of_delta.base,
+                delta_of_delta.first_delta,
+                delta_of_delta.uncompressed_bits_per_value / 8,
+            ))
+        }
+        pb::array_encoding::ArrayEncoding::Quantized(quantized) => {
+            let values_scheduler = decoder_from_array_encoding(
+                required(quantized.values.as_ref(), "values of a quantized encoding")?,
+                buffers,
+                data_type,
+            )?;
+            Box::new(QuantizedScheduler::new(
+                values_scheduler,
+                QuantizeParams::new(quantized.scale, quantized.zero_point),
+                quantized.uncompressed_bits_per_value / 8,
+            ))
+        }
+        // The filter is only used to skip pages, it isn't needed to decode them
+        pb::array_encoding::ArrayEncoding::BloomFiltered(bloom_filtered) => {
+            decoder_from_array_encoding(
+                required(
+                    bloom_filtered.values.as_ref(),
+                    "values of a bloom filtered encoding",
+                )?,
+                buffers,
+                data_type,
+            )?
+        }
+        pb::array_encoding::ArrayEncoding::Sparse(sparse) => {
+            let positions_scheduler = decoder_from_array_encoding(
+                required(sparse.positions.as_ref(), "positions of a sparse encoding")?,
+                buffers,
+                &DataType::UInt64,
+            )?;
+            let values_scheduler = decoder_from_array_encoding(
+                required(sparse.values.as_ref(), "values of a sparse encoding")?,
+                buffers,
+                data_type,
+            )?;
+            Box::new(SparseScheduler::try_new(
+                positions_scheduler,
+                values_scheduler,
+                Bytes::from(sparse.default_value.clone()),
+                sparse.num_values,
+            )?)
+        }
         pb::array_encoding::ArrayEncoding::FixedSizeList(fixed_size_list) => {
-            let item_encoding = fixed_size_list.items.as_ref().unwrap();
-            let item_scheduler = decoder_from_array_encoding(item_encoding, buffers, data_type);
+            let item_encoding = required(
+                fixed_size_list.items.as_ref(),
+                "items of a fixed size list encoding",
+            )?;
+            let item_scheduler = decoder_from_array_encoding(item_encoding, buffers, data_type)?;
             Box::new(FixedListScheduler::new(
                 item_scheduler,
                 fixed_size_list.dimension,
@@ -128,16 +515,19 @@ pub fn decoder_from_array_encoding(
         // This is a column containing the list offsets.  This wrapper is superfluous at the moment
         // since we know it is a list based on the schema.  In the future there may be different ways
         // of storing the list offsets.
-        pb::array_encoding::ArrayEncoding::List(list) => {
-            decoder_from_array_encoding(list.offsets.as_ref().unwrap(), buffers, data_type)
-        }
+        pb::array_encoding::ArrayEncoding::List(list) => decoder_from_array_encoding(
+            required(list.offsets.as_ref(), "offsets of a list encoding")?,
+            buffers,
+            data_type,
+        )?,
         pb::array_encoding::ArrayEncoding::Binary(binary) => {
-            let indices_encoding = binary.indices.as_ref().unwrap();
-            let bytes_encoding = binary.bytes.as_ref().unwrap();
+            let indices_encoding =
+                required(binary.indices.as_ref(), "indices of a binary encoding")?;
+            let bytes_encoding = required(binary.bytes.as_ref(), "bytes of a binary encoding")?;
 
             let indices_scheduler =
-                decoder_from_array_encoding(indices_encoding, buffers, data_type);
-            let bytes_scheduler = decoder_from_array_encoding(bytes_encoding, buffers, data_type);
+                decoder_from_array_encoding(indices_encoding, buffers, data_type)?;
+            let bytes_scheduler = decoder_from_array_encoding(bytes_encoding, buffers, data_type)?;
 
             let offset_type = match data_type {
                 DataType::LargeBinary | DataType::LargeUtf8 => DataType::Int64,
@@ -152,19 +542,46 @@ pub fn decoder_from_array_encoding(
             ))
         }
         pb::array_encoding::ArrayEncoding::Fsst(fsst) => {
-            let inner =
-                decoder_from_array_encoding(fsst.binary.as_ref().unwrap(), buffers, data_type);
+            let inner = decoder_from_array_encoding(
+                required(fsst.binary.as_ref(), "binary data of an FSST encoding")?,
+                buffers,
+                data_type,
+            )?;
 
             Box::new(FsstPageScheduler::new(inner, fsst.symbol_table.clone()))
         }
         pb::array_encoding::ArrayEncoding::Dictionary(dictionary) => {
-            let indices_encoding = dictionary.indices.as_ref().unwrap();
-            let items_encoding = dictionary.items.as_ref().unwrap();
+            let indices_encoding = required(
+                dictionary.indices.as_ref(),
+                "indices of a dictionary encoding",
+            )?;
+            let indices_scheduler =
+                decoder_from_array_encoding(indices_encoding, buffers, data_type)?;
+            if let Some(shared_dictionary) = &dictionary.shared_dictionary {
+                let shared_dictionaries = buffers.column_buffers.file_buffers.shared_dictionaries;
+                let Some((_, items)) = shared_dictionaries
+                    .iter()
+                    .find(|(id, _)| *id == shared_dictionary.id)
+                else {
+                    return Err(Error::corrupt_metadata(
+                        format!(
+                            "the page uses the shared dictionary {} which was not provided",
+                            shared_dictionary.id
+                        ),
+                        location!(),
+                    ));
+                };
+                return Ok(Box::new(DictionaryPageScheduler::try_new_shared(
+                    indices_scheduler.into(),
+                    items.clone(),
+                    dictionary.num_dictionary_items,
+                )?));
+            }
+            let items_encoding =
+                required(dictionary.items.as_ref(), "items of a dictionary encoding")?;
             let num_dictionary_items = dictionary.num_dictionary_items;
 
-            let indices_scheduler =
-                decoder_from_array_encoding(indices_encoding, buffers, data_type);
-            let items_scheduler = decoder_from_array_encoding(items_encoding, buffers, data_type);
+            let items_scheduler = decoder_from_array_encoding(items_encoding, buffers, data_type)?;
 
             Box::new(DictionaryPageScheduler::new(
                 indices_scheduler.into(),
@@ -173,9 +590,368 @@ pub fn decoder_from_array_encoding(
             ))
         }
         // Currently there is no way to encode struct nullability and structs are encoded with a "header" column
-        // (that has no data).  We never actually decode that column and so this branch is never actually encountered.
+        // (that has no data).  We never actually decode that column and so this branch should never be encountered.
         //
         // This will change in the future when we add support for struct nullability.
-        pb::array_encoding::ArrayEncoding::Struct(_) => unreachable!(),
+        pb::array_encoding::ArrayEncoding::Struct(_) => {
+            return Err(Error::corrupt_metadata(
+                "A struct encoding was found on a page but struct pages cannot be decoded directly",
+                location!(),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, ArrayRef, Int32Array};
+    use arrow_schema::DataType;
+    use bytes::Bytes;
+    use lance_core::{error::EncodingError, Error};
+
+    use crate::{
+        decoder::DecoderConfig,
+        encoder::{ArrayEncoder, EncodedArray},
+        encodings::{
+            physical::{
+                bitpack::{BitpackedArrayEncoder, ChunkedBitpackedArrayEncoder},
+                value::{CompressionScheme, ValueEncoder, ValueEncoderBuilder},
+            },
+            utils::primitive_array_from_buffers,
+        },
+        format::pb,
+        testing::page_buffers,
+        BufferScheduler, EncodingsIo,
+    };
+
+    use super::{
+        decoder_from_array_encoding, scheduler_from_encoding, FLAT_ENCODING_VERSION,
+        FLAT_ENCODING_VERSION_BLOCKS,
+    };
+
+    #[test]
+    fn test_unknown_encoding() {
+        let unknown = pb::ArrayEncoding {
+            array_encoding: None,
+            producer: None,
+            statistics: None,
+            data_type: None,
+        };
+        let err = decoder_from_array_encoding(&unknown, &page_buffers(&[], &[]), &DataType::Int32)
+            .err()
+            .unwrap();
+        assert!(
+            matches!(
+                err,
+                Error::Encoding {
+                    source: EncodingError::CorruptMetadata { .. },
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        let message = err.to_string();
+        assert!(message.contains("array encoding"), "{}", message);
+        assert!(message.contains("newer version of Lance"), "{}", message);
+
+        // Unknown encodings nested inside a known encoding are also reported
+        let nested = pb::ArrayEncoding {
+            array_encoding: Some(pb::array_encoding::ArrayEncoding::Nullable(Box::new(
+                pb::Nullable {
+                    nullability: Some(pb::nullable::Nullability::NoNulls(Box::new(
+                        pb::nullable::NoNull {
+                            values: Some(Box::new(unknown)),
+                        },
+                    ))),
+                },
+            ))),
+            producer: None,
+            statistics: None,
+            data_type: None,
+        };
+        let err = decoder_from_array_encoding(&nested, &page_buffers(&[], &[]), &DataType::Int32)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("array encoding"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_buffer() {
+        let encodings = [
+            pb::array_encoding::ArrayEncoding::Flat(pb::Flat {
+                bits_per_value: 32,
+                ..Default::default()
+            }),
+            pb::array_encoding::ArrayEncoding::Bitpacked(pb::Bitpacked {
+                compressed_bits_per_value: 4,
+                uncompressed_bits_per_value: 32,
+                ..Default::default()
+            }),
+        ];
+        for array_encoding in encodings {
+            let encoding = pb::ArrayEncoding {
+                array_encoding: Some(array_encoding),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            };
+            let err =
+                decoder_from_array_encoding(&encoding, &page_buffers(&[], &[]), &DataType::Int32)
+                    .err()
+                    .unwrap();
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+            assert!(err.to_string().contains("buffer of a"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_unknown_compression_scheme() {
+        let flat = pb::ArrayEncoding {
+            array_encoding: Some(pb::array_encoding::ArrayEncoding::Flat(pb::Flat {
+                bits_per_value: 32,
+                buffer: Some(pb::Buffer {
+                    buffer_index: 0,
+                    buffer_type: pb::buffer::BufferType::Page as i32,
+                }),
+                compression: Some(pb::Compression {
+                    scheme: "lz5".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            producer: None,
+            statistics: None,
+            data_type: None,
+        };
+        let buffers = page_buffers(&[(0, 400)], &[]);
+        let err = decoder_from_array_encoding(&flat, &buffers, &DataType::Int32)
+            .err()
+            .unwrap();
+        match err {
+            Error::Encoding {
+                source: EncodingError::UnknownScheme { scheme },
+                ..
+            } => assert_eq!(scheme, "lz5"),
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_flat_v1_compat() {
+        // A flat page as it was written before the encoding recorded a version
+        let values = (0..100).map(|i| i * 3).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let io = Arc::new(BufferScheduler::new(Bytes::from(data))) as Arc<dyn EncodingsIo>;
+        let buffers = page_buffers(&[(0, 400)], &[]);
+        let flat = |version| pb::ArrayEncoding {
+            array_encoding: Some(pb::array_encoding::ArrayEncoding::Flat(pb::Flat {
+                bits_per_value: 32,
+                buffer: Some(pb::Buffer {
+                    buffer_index: 0,
+                    buffer_type: pb::buffer::BufferType::Page as i32,
+                }),
+                compression: None,
+                version,
+                little_endian: false,
+            })),
+            producer: None,
+            statistics: None,
+            data_type: None,
+        };
+
+        for version in [0, FLAT_ENCODING_VERSION] {
+            let scheduler =
+                decoder_from_array_encoding(&flat(version), &buffers, &DataType::Int32).unwrap();
+            let decoder = scheduler
+                .schedule_ranges(&[20..30, 90..100], &io, 0)
+                .await
+                .unwrap();
+            let buffers = decoder.decode(0, 20, &mut false).unwrap();
+            let mut all_buffers = vec![bytes::BytesMut::default()];
+            all_buffers.extend(buffers);
+            let decoded = primitive_array_from_buffers(&DataType::Int32, all_buffers, 20).unwrap();
+            let expected = values[20..30]
+                .iter()
+                .chain(&values[90..100])
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(
+                decoded.as_primitive::<Int32Type>().values(),
+                expected.as_slice(),
+                "version {}",
+                version
+            );
+        }
+
+        let err = decoder_from_array_encoding(
+            &flat(FLAT_ENCODING_VERSION_BLOCKS + 1),
+            &buffers,
+            &DataType::Int32,
+        )
+        .err()
+        .unwrap();
+        assert!(
+            matches!(
+                err,
+                Error::Encoding {
+                    source: EncodingError::CorruptMetadata { .. },
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("version"), "{}", err);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_scheduler_from_encoding() {
+        let values = (0..1000).map(|i| i % 7).collect::<Vec<i32>>();
+        let arrays = vec![Arc::new(Int32Array::from(values.clone())) as ArrayRef];
+        let encoders: Vec<(&str, Box<dyn ArrayEncoder>)> = vec![
+            (
+                "flat",
+                Box::new(ValueEncoder::try_new(&DataType::Int32, CompressionScheme::None).unwrap()),
+            ),
+            (
+                "compressed",
+                Box::new(ValueEncoder::try_new(&DataType::Int32, CompressionScheme::Zstd).unwrap()),
+            ),
+            (
+                "compressed_blocks",
+                Box::new(
+                    ValueEncoderBuilder::default()
+                        .compression(CompressionScheme::Zstd)
+                        .compression_block_size(512)
+                        .build(&DataType::Int32)
+                        .unwrap(),
+                ),
+            ),
+            ("bitpacked", Box::new(BitpackedArrayEncoder::new(4))),
+            (
+                "chunked_bitpacked",
+                Box::new(ChunkedBitpackedArrayEncoder::new(256)),
+            ),
+        ];
+        for (name, encoder) in encoders {
+            let EncodedArray {
+                mut buffers,
+                encoding,
+            } = encoder.encode(&arrays, &mut 0).unwrap();
+            assert_eq!(buffers.len(), 1, "{}", name);
+            let data = buffers
+                .pop()
+                .unwrap()
+                .parts
+                .into_iter()
+                .flat_map(|part| part.to_vec())
+                .collect::<Vec<_>>();
+            if name.starts_with("compressed") {
+                assert!(data.len() < 4000, "{}", name);
+            }
+            if let Some(pb::array_encoding::ArrayEncoding::Flat(flat)) = &encoding.array_encoding {
+                let expected_version = if name == "compressed_blocks" {
+                    FLAT_ENCODING_VERSION_BLOCKS
+                } else {
+                    FLAT_ENCODING_VERSION
+                };
+                assert_eq!(flat.version, expected_version, "{}", name);
+            }
+
+            // The buffer does not need to start at the beginning of the file
+            let buffer_size = data.len() as u64;
+            let mut file = vec![0_u8; 7];
+            file.extend(data);
+            let io = Arc::new(BufferScheduler::new(Bytes::from(file))) as Arc<dyn EncodingsIo>;
+            let scheduler =
+                scheduler_from_encoding(&encoding, 7, buffer_size, DecoderConfig::default())
+                    .unwrap();
+            let decoder = scheduler
+                .schedule_ranges(&[10..20, 500..1000], &io, 0)
+                .await
+                .unwrap();
+            let buffers = decoder.decode(5, 500, &mut false).unwrap();
+            let mut all_buffers = vec![bytes::BytesMut::default()];
+            all_buffers.extend(buffers);
+            let decoded = primitive_array_from_buffers(&DataType::Int32, all_buffers, 500).unwrap();
+            let expected = values[15..20]
+                .iter()
+                .chain(&values[500..995])
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(
+                decoded.as_primitive::<Int32Type>().values(),
+                expected.as_slice(),
+                "{}",
+                name
+            );
+
+            // Compressed pages may not decompress to more than the configured limit
+            if name.starts_with("compressed") {
+                let config = DecoderConfig {
+                    max_decompressed_size: 1000,
+                    ..Default::default()
+                };
+                let decoder = scheduler_from_encoding(&encoding, 7, buffer_size, config)
+                    .unwrap()
+                    .schedule_ranges(&[10..20, 500..1000], &io, 0)
+                    .await
+                    .unwrap();
+                let err = decoder.decode(5, 500, &mut false).unwrap_err();
+                assert!(
+                    matches!(
+                        err,
+                        Error::Encoding {
+                            source: EncodingError::CorruptMetadata { .. },
+                            ..
+                        }
+                    ),
+                    "{}",
+                    err
+                );
+            }
+        }
+
+        // Encodings that span several buffers can't be scheduled from a single buffer
+        let list = pb::ArrayEncoding {
+            array_encoding: Some(pb::array_encoding::ArrayEncoding::List(Box::default())),
+            producer: None,
+            statistics: None,
+            data_type: None,
+        };
+        let err = scheduler_from_encoding(&list, 0, 100, DecoderConfig::default())
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        let unknown = pb::ArrayEncoding {
+            array_encoding: None,
+            producer: None,
+            statistics: None,
+            data_type: None,
+        };
+        let err = scheduler_from_encoding(&unknown, 0, 100, DecoderConfig::default())
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("newer version of Lance"),
+            "{}",
+            err
+        );
     }
 }
@@ -1,24 +1,27 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::sync::Arc;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 
 use arrow_array::{ArrayRef, BooleanArray};
 use arrow_buffer::BooleanBuffer;
-use bytes::BytesMut;
-use futures::{future::BoxFuture, FutureExt};
+use bytes::{Bytes, BytesMut};
+use futures::{channel::oneshot, future::BoxFuture, FutureExt};
 use log::trace;
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{BufferMask, DecodeCost, PageScheduler, PrimitivePageDecoder},
     encoder::{ArrayEncoder, BufferEncoder, EncodedArray, EncodedArrayBuffer},
     format::pb,
     EncodingsIo,
 };
 
-use lance_core::Result;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
 
 use super::buffers::BitmapBufferEncoder;
+use super::FLAT_ENCODING_VERSION;
 
 struct DataDecoders {
     validity: Box<dyn PrimitivePageDecoder>,
@@ -32,14 +35,28 @@ enum DataNullStatus {
     None(Box<dyn PrimitivePageDecoder>),
     // Validity and values
     Some(DataDecoders),
+    // Only the buffers requested from `BasicPageScheduler::schedule_buffers`
+    Partial {
+        validity: Option<Box<dyn PrimitivePageDecoder>>,
+        values: Option<Box<dyn PrimitivePageDecoder>>,
+    },
 }
 
 impl DataNullStatus {
+    fn validity_decoder(&self) -> Option<&dyn PrimitivePageDecoder> {
+        match self {
+            Self::All | Self::None(_) => None,
+            Self::Some(decoders) => Some(decoders.validity.as_ref()),
+            Self::Partial { validity, .. } => validity.as_deref(),
+        }
+    }
+
     fn values_decoder(&self) -> Option<&dyn PrimitivePageDecoder> {
         match self {
             Self::All => None,
             Self::Some(decoders) => Some(decoders.values.as_ref()),
             Self::None(values) => Some(values.as_ref()),
+            Self::Partial { values, .. } => values.as_deref(),
         }
     }
 }
@@ -126,28 +143,74 @@ impl PageScheduler for BasicPageScheduler {
         scheduler: &Arc<dyn EncodingsIo>,
         top_level_row: u64,
     ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
-        let validity_future = match &self.mode {
-            SchedulerNullStatus::None(_) | SchedulerNullStatus::All => None,
-            SchedulerNullStatus::Some(schedulers) => Some(schedulers.validity.schedule_ranges(
-                ranges,
-                scheduler,
-                top_level_row,
-            )),
+        self.schedule_buffers(ranges, scheduler, top_level_row, BufferMask::ALL)
+    }
+
+    fn schedule_buffers(
+        &self,
+        ranges: &[std::ops::Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+        buffers: BufferMask,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        // Buffer 0 is the validity and the values buffers follow it
+        let values_buffers = buffers.shift(1);
+        let validity_scheduler = match &self.mode {
+            SchedulerNullStatus::Some(schedulers) if buffers.contains(0) => {
+                Some(schedulers.validity.as_ref())
+            }
+            _ => None,
         };
+        let values_scheduler = self
+            .mode
+            .values_scheduler()
+            .filter(|_| !values_buffers.is_empty());
+        let is_partial = (validity_scheduler.is_none()
+            && matches!(self.mode, SchedulerNullStatus::Some(_)))
+            || (values_scheduler.is_none() && self.mode.values_scheduler().is_some());
+
+        // The validity and values buffers are usually adjacent and so we hold back their
+        // requests and submit them together as a single request
+        let deferred_io = (validity_scheduler.is_some() && values_scheduler.is_some())
+            .then(|| Arc::new(DeferredIo::new(scheduler.clone())));
+        let child_io = deferred_io
+            .clone()
+            .map(|io| io as Arc<dyn EncodingsIo>)
+            .unwrap_or_else(|| scheduler.clone());
 
-        let values_future = if let Some(values_scheduler) = self.mode.values_scheduler() {
-            Some(
-                values_scheduler
-                    .schedule_ranges(ranges, scheduler, top_level_row)
-                    .boxed(),
-            )
+        let validity_future = validity_scheduler
+            .map(|validity| validity.schedule_ranges(ranges, &child_io, top_level_row));
+
+        let values_future = if let Some(values_scheduler) = values_scheduler {
+            Some(values_scheduler.schedule_buffers(
+                ranges,
+                &child_io,
+                top_level_row,
+                values_buffers,
+            ))
         } else {
-            trace!("No values fetch needed since values all null");
+            trace!("No values fetch needed since values all null or not requested");
             None
         };
 
+        let io_future = deferred_io.map(|io| io.flush(MAX_COALESCE_GAP_BYTES));
+
         async move {
+            if let Some(io_future) = io_future {
+                io_future.await?;
+            }
             let mode = match (values_future, validity_future) {
+                (values_future, validity_future) if is_partial => {
+                    let values = match values_future {
+                        Some(values_future) => Some(values_future.await?),
+                        None => None,
+                    };
+                    let validity = match validity_future {
+                        Some(validity_future) => Some(validity_future.await?),
+                        None => None,
+                    };
+                    DataNullStatus::Partial { validity, values }
+                }
                 (None, None) => DataNullStatus::All,
                 (Some(values_future), None) => DataNullStatus::None(values_future.await?),
                 (Some(values_future), Some(validity_future)) => {
@@ -162,6 +225,127 @@ impl PageScheduler for BasicPageScheduler {
         }
         .boxed()
     }
+
+    fn estimate_cost(&self, ranges: &[std::ops::Range<u64>]) -> DecodeCost {
+        match &self.mode {
+            SchedulerNullStatus::All => DecodeCost::default(),
+            SchedulerNullStatus::None(values) => values.estimate_cost(ranges),
+            SchedulerNullStatus::Some(schedulers) => schedulers
+                .validity
+                .estimate_cost(ranges)
+                .combine(schedulers.values.estimate_cost(ranges)),
+        }
+    }
+}
+
+/// Requests that are further apart than this are not merged into a single range when
+/// the validity and values requests of a page are combined
+const MAX_COALESCE_GAP_BYTES: u64 = 4 * 1024;
+
+struct DeferredRequest {
+    ranges: Vec<Range<u64>>,
+    priority: u64,
+    tx: oneshot::Sender<Vec<Bytes>>,
+}
+
+/// An I/O service that holds back requests until they are flushed
+///
+/// Flushing submits all of the held requests as a single request to the underlying I/O
+/// service, merging ranges that are adjacent (or close), and then splits up the response.
+/// Requests made after the flush are passed straight through.
+struct DeferredIo {
+    inner: Arc<dyn EncodingsIo>,
+    // None once flushed
+    pending: Mutex<Option<Vec<DeferredRequest>>>,
+}
+
+impl DeferredIo {
+    fn new(inner: Arc<dyn EncodingsIo>) -> Self {
+        Self {
+            inner,
+            pending: Mutex::new(Some(Vec::new())),
+        }
+    }
+
+    /// Submits the held requests, the returned future must be polled for them to complete
+    fn flush(&self, max_gap: u64) -> BoxFuture<'static, Result<()>> {
+        let requests = self.pending.lock().unwrap().take().unwrap_or_default();
+        if requests.is_empty() {
+            return std::future::ready(Ok(())).boxed();
+        }
+        let priority = requests.iter().map(|req| req.priority).min().unwrap();
+
+        let mut sorted = requests
+            .iter()
+            .flat_map(|req| req.ranges.iter().cloned())
+            .collect::<Vec<_>>();
+        sorted.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(sorted.len());
+        for range in sorted {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end + max_gap => {
+                    last.end = last.end.max(range.end);
+                }
+                _ => merged.push(range),
+            }
+        }
+        trace!(
+            "Combining {} requests into a single request for {} ranges",
+            requests.len(),
+            merged.len()
+        );
+
+        let data = self.inner.submit_request(merged.clone(), priority);
+        async move {
+            let data = data.await?;
+            for req in requests {
+                let bytes = req
+                    .ranges
+                    .iter()
+                    .map(|range| {
+                        // The merged ranges are sorted and disjoint and one of them
+                        // contains this range
+                        let idx = merged.partition_point(|m| m.end < range.end);
+                        let merged_range = &merged[idx];
+                        data[idx].slice(
+                            (range.start - merged_range.start) as usize
+                                ..(range.end - merged_range.start) as usize,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                // The receiver may have been dropped if the decode was abandoned
+                let _ = req.tx.send(bytes);
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl EncodingsIo for DeferredIo {
+    fn submit_request(
+        &self,
+        ranges: Vec<Range<u64>>,
+        priority: u64,
+    ) -> BoxFuture<'static, Result<Vec<Bytes>>> {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(pending) = pending.as_mut() else {
+            return self.inner.submit_request(ranges, priority);
+        };
+        let (tx, rx) = oneshot::channel();
+        pending.push(DeferredRequest {
+            ranges,
+            priority,
+            tx,
+        });
+        rx.map(|res| {
+            res.map_err(|_| Error::Internal {
+                message: "A deferred I/O request was never fulfilled".to_string(),
+                location: location!(),
+            })
+        })
+        .boxed()
+    }
 }
 
 struct BasicPageDecoder {
@@ -175,32 +359,72 @@ impl PrimitivePageDecoder for BasicPageDecoder {
         num_rows: u64,
         all_null: &mut bool,
     ) -> Result<Vec<BytesMut>> {
-        let dest_buffers = match &self.mode {
-            DataNullStatus::Some(decoders) => {
-                let mut buffers = decoders.validity.decode(rows_to_skip, num_rows, all_null)?; // buffer 0
-                let mut values_bytesmut =
-                    decoders.values.decode(rows_to_skip, num_rows, all_null)?; // buffer 1 onwards
+        self.decode_buffers(rows_to_skip, num_rows, BufferMask::ALL, all_null)
+    }
 
-                buffers.append(&mut values_bytesmut);
-                buffers
+    fn decode_buffers(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        buffers: BufferMask,
+        all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        // Buffer 0 is the validity, which is empty if there are no nulls (or it wasn't
+        // requested)
+        let mut dest_buffers = match self.mode.validity_decoder() {
+            Some(validity) if buffers.contains(0) => {
+                validity.decode(rows_to_skip, num_rows, all_null)?
             }
-            // Either dest_buffers[0] is empty, in which case these are no-ops, or one of the
-            // other pages needed the buffer, in which case we need to fill our section
-            DataNullStatus::All => {
-                let buffers = vec![BytesMut::default()];
-                *all_null = true;
-                buffers
+            _ => vec![BytesMut::default()],
+        };
+        // Buffer 1 onwards
+        if let Some(values) = self.mode.values_decoder() {
+            let values_buffers = buffers.shift(1);
+            if values_buffers.is_empty() {
+                dest_buffers.extend((0..values.num_buffers()).map(|_| BytesMut::default()));
+            } else {
+                dest_buffers.append(&mut values.decode_buffers(
+                    rows_to_skip,
+                    num_rows,
+                    values_buffers,
+                    all_null,
+                )?);
             }
-            DataNullStatus::None(values) => {
-                let mut dest_buffers = vec![BytesMut::default()];
+        }
+        if matches!(self.mode, DataNullStatus::All) {
+            *all_null = true;
+        }
+
+        Ok(dest_buffers)
+    }
 
-                let mut values_bytesmut = values.decode(rows_to_skip, num_rows, all_null)?;
-                dest_buffers.append(&mut values_bytesmut);
-                dest_buffers
+    fn decode_shared(&self, rows_to_skip: u64, num_rows: u64) -> Option<Vec<Bytes>> {
+        // Validity bitmaps are not byte aligned when skipping rows so we only do this when
+        // there are no nulls
+        match &self.mode {
+            DataNullStatus::None(values) => {
+                let mut buffers = values.decode_shared(rows_to_skip, num_rows)?;
+                buffers.insert(0, Bytes::new());
+                Some(buffers)
             }
-        };
+            _ => None,
+        }
+    }
 
-        Ok(dest_buffers)
+    fn can_skip(&self) -> bool {
+        match &self.mode {
+            DataNullStatus::Some(decoders) => {
+                decoders.validity.can_skip() && decoders.values.can_skip()
+            }
+            DataNullStatus::All => true,
+            DataNullStatus::None(values) => values.can_skip(),
+            DataNullStatus::Partial { validity, values } => {
+                validity
+                    .as_ref()
+                    .map_or(true, |validity| validity.can_skip())
+                    && values.as_ref().map_or(true, |values| values.can_skip())
+            }
+        }
     }
 
     fn num_buffers(&self) -> u32 {
@@ -227,7 +451,7 @@ impl ArrayEncoder for BasicEncoder {
     fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
         let (null_count, row_count) = arrays
             .iter()
-            .map(|arr| (arr.null_count() as u32, arr.len() as u32))
+            .map(|arr| (arr.null_count() as u64, arr.len() as u64))
             .fold((0, 0), |acc, val| (acc.0 + val.0, acc.1 + val.1));
         let (buffers, nullability) = if null_count == 0 {
             let arr_encoding = self.values_encoder.encode(arrays, buffer_index)?;
@@ -262,7 +486,12 @@ impl ArrayEncoder for BasicEncoder {
                         buffer_type: pb::buffer::BufferType::Page as i32,
                     }),
                     compression: None,
+                    version: FLAT_ENCODING_VERSION,
+                    little_endian: false,
                 })),
+                producer: None,
+                statistics: None,
+                data_type: None,
             });
 
             let arr_encoding = self.values_encoder.encode(arrays, buffer_index)?;
@@ -287,7 +516,166 @@ impl ArrayEncoder for BasicEncoder {
                         nullability: Some(nullability),
                     },
                 ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use crate::{
+        decoder::{BufferMask, PageScheduler},
+        encodings::physical::{
+            bitmap::DenseBitmapScheduler,
+            value::{CompressionScheme, ValuePageScheduler},
+        },
+        testing::RecordingIo,
+        EncodingsIo,
+    };
+
+    use super::BasicPageScheduler;
+
+    // Lays out a page of 100 nullable int32 values (every third value is null) with
+    // `gap` bytes between the validity and values buffers
+    async fn check_nullable_page(gap: usize) -> Vec<Vec<Range<u64>>> {
+        let validity = (0..100).map(|i| i % 3 != 0).collect::<Vec<_>>();
+        let mut data = arrow_buffer::BooleanBuffer::from(validity.clone())
+            .values()
+            .to_vec();
+        data.resize(data.len() + gap, 0);
+        let values_offset = data.len() as u64;
+        data.extend((0..100_i32).flat_map(|i| i.to_le_bytes()));
+
+        let scheduler = BasicPageScheduler::new_nullable(
+            Box::new(DenseBitmapScheduler::new(0)),
+            Box::new(ValuePageScheduler::new(
+                4,
+                values_offset,
+                400,
+                CompressionScheme::None,
+            )),
+        );
+        let io = Arc::new(RecordingIo::new(Bytes::from(data)));
+        let decoder = scheduler
+            .schedule_ranges(&[10..20, 50..60], &(io.clone() as Arc<dyn EncodingsIo>), 0)
+            .await
+            .unwrap();
+
+        let buffers = decoder.decode(5, 10, &mut false).unwrap();
+        let decoded_validity =
+            arrow_buffer::BooleanBuffer::new(buffers[0].clone().freeze().into(), 0, 10);
+        let expected_rows = (15..20).chain(50..55).collect::<Vec<_>>();
+        assert_eq!(
+            decoded_validity.iter().collect::<Vec<_>>(),
+            expected_rows
+                .iter()
+                .map(|i| validity[*i])
+                .collect::<Vec<_>>()
+        );
+        let decoded_values = buffers[1]
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decoded_values,
+            expected_rows.iter().map(|i| *i as i32).collect::<Vec<_>>()
+        );
+
+        io.requests()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_nullable_page_single_request() {
+        // Adjacent buffers are fetched with one request and nearby ranges are merged
+        let requests = check_nullable_page(0).await;
+        assert_eq!(requests.len(), 1);
+        // Bitmap bytes 1..8 and values bytes 13 + 40..13 + 240
+        assert_eq!(requests[0], vec![1..253]);
+
+        // Buffers that are far apart are still fetched with one request but the ranges
+        // of one buffer are not merged with the ranges of the other
+        let requests = check_nullable_page(1024 * 1024).await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].len(), 2);
+        assert_eq!(requests[0][0], 1..8);
+    }
+
+    #[allow(clippy::single_range_in_vec_init)]
+    #[test_log::test(tokio::test)]
+    async fn test_decode_subset_of_buffers() {
+        // 100 nullable int32 values where every third value is null
+        let validity = (0..100).map(|i| i % 3 != 0).collect::<Vec<_>>();
+        let mut data = arrow_buffer::BooleanBuffer::from(validity.clone())
+            .values()
+            .to_vec();
+        let values_offset = data.len() as u64;
+        data.extend((0..100_i32).flat_map(|i| i.to_le_bytes()));
+        let scheduler = BasicPageScheduler::new_nullable(
+            Box::new(DenseBitmapScheduler::new(0)),
+            Box::new(ValuePageScheduler::new(
+                4,
+                values_offset,
+                400,
+                CompressionScheme::None,
+            )),
+        );
+        let schedule = |buffers: BufferMask| {
+            let io = Arc::new(RecordingIo::new(Bytes::from(data.clone())));
+            let decoder = scheduler.schedule_buffers(
+                &[10..20],
+                &(io.clone() as Arc<dyn EncodingsIo>),
+                0,
+                buffers,
+            );
+            async move {
+                let decoder = decoder.await.unwrap();
+                (decoder, io.requests())
+            }
+        };
+
+        // Only the values, the validity is neither read nor decoded
+        let values_only = BufferMask::ALL.without(0);
+        let (decoder, requests) = schedule(values_only).await;
+        assert_eq!(requests, vec![vec![values_offset + 40..values_offset + 80]]);
+        let buffers = decoder
+            .decode_buffers(0, 10, values_only, &mut false)
+            .unwrap();
+        assert_eq!(buffers.len(), 2);
+        assert!(buffers[0].is_empty());
+        let decoded_values = buffers[1]
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(decoded_values, (10..20).collect::<Vec<_>>());
+
+        // Only the validity, the values are neither read nor decoded
+        let (decoder, requests) = schedule(BufferMask::only(0)).await;
+        assert_eq!(requests, vec![vec![1..3]]);
+        let buffers = decoder
+            .decode_buffers(0, 10, BufferMask::only(0), &mut false)
+            .unwrap();
+        assert_eq!(buffers.len(), 1);
+        let decoded_validity =
+            arrow_buffer::BooleanBuffer::new(buffers[0].clone().freeze().into(), 0, 10);
+        assert_eq!(
+            decoded_validity.iter().collect::<Vec<_>>(),
+            validity[10..20].to_vec()
+        );
+
+        // A decoder with every buffer loaded can still skip decoding some of them
+        let (decoder, _) = schedule(BufferMask::ALL).await;
+        let buffers = decoder
+            .decode_buffers(0, 10, values_only, &mut false)
+            .unwrap();
+        assert!(buffers[0].is_empty());
+        assert_eq!(buffers[1].len(), 40);
+    }
+}
@@ -13,7 +13,7 @@ use futures::stream::StreamExt;
 use futures::{future::BoxFuture, stream::FuturesOrdered, FutureExt};
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{DecodeCost, PageScheduler, PrimitivePageDecoder},
     encoder::{ArrayEncoder, EncodedArray},
     format::pb,
     EncodingsIo,
@@ -24,7 +24,8 @@ use crate::encodings::logical::primitive::PrimitiveFieldDecoder;
 
 use arrow_array::{PrimitiveArray, UInt64Array, UInt8Array};
 use arrow_schema::DataType;
-use lance_core::Result;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
 
 struct IndicesNormalizer {
     indices: Vec<u64>,
@@ -213,6 +214,16 @@ impl PageScheduler for BinaryPageScheduler {
         .map(|join_handle| join_handle.unwrap())
         .boxed()
     }
+
+    /// The size of the bytes can't be known until the indices are loaded and so this
+    /// only includes the cost of the indices.  It is a lower bound on the true cost.
+    fn estimate_cost(&self, ranges: &[std::ops::Range<u64>]) -> DecodeCost {
+        let indices_ranges = ranges
+            .iter()
+            .map(|range| range.start.saturating_sub(1)..range.end)
+            .collect::<Vec<_>>();
+        self.indices_scheduler.estimate_cost(&indices_ranges)
+    }
 }
 
 struct BinaryPageDecoder {
@@ -286,6 +297,16 @@ impl PrimitivePageDecoder for BinaryPageDecoder {
         // Normalize and cast (TODO: could fuse these into one pass for micro-optimization)
         let target_vec = target_offsets.values();
         let start = target_vec[0];
+        let num_target_bytes = target_vec[target_vec.len() - 1] - start;
+        if bytes_per_offset == 4 && num_target_bytes > i32::MAX as u64 {
+            return Err(Error::invalid_input(
+                format!(
+                    "{} rows of {} bytes do not fit in 32-bit offsets, read the data as a large type",
+                    num_rows, num_target_bytes
+                ),
+                location!(),
+            ));
+        }
         let offsets_buffer =
             match bytes_per_offset {
                 4 => ScalarBuffer::from_iter(target_vec.iter().map(|x| (x - start) as i32))
@@ -472,6 +493,9 @@ impl ArrayEncoder for BinaryEncoder {
                         null_adjustment,
                     },
                 ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
         })
     }
@@ -487,8 +511,11 @@ pub mod tests {
     use arrow_schema::{DataType, Field};
     use std::{sync::Arc, vec};
 
-    use crate::testing::{
-        check_round_trip_encoding_of_data, check_round_trip_encoding_random, TestCases,
+    use crate::{
+        encoder::{ArrayEncodingStrategy, CoreArrayEncodingStrategy, OffsetsEncoding},
+        encodings::physical::value::CompressionScheme,
+        format::pb,
+        testing::{check_round_trip_encoding_of_data, check_round_trip_encoding_random, TestCases},
     };
 
     use super::get_indices_from_string_arrays;
@@ -644,4 +671,79 @@ pub mod tests {
         let test_cases = TestCases::default().without_validation();
         check_round_trip_encoding_of_data(arrs, &test_cases).await;
     }
+
+    // Returns the encoding of the values of a nullable encoding
+    fn values_encoding(encoding: &pb::ArrayEncoding) -> &pb::ArrayEncoding {
+        match encoding.array_encoding.as_ref().unwrap() {
+            pb::array_encoding::ArrayEncoding::Nullable(nullable) => {
+                match nullable.nullability.as_ref().unwrap() {
+                    pb::nullable::Nullability::NoNulls(no_nulls) => {
+                        no_nulls.values.as_ref().unwrap()
+                    }
+                    other => panic!("Expected no nulls but got {:?}", other),
+                }
+            }
+            other => panic!("Expected a nullable encoding but got {:?}", other),
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_independent_offsets_and_bytes_encodings() {
+        let strategy = CoreArrayEncodingStrategy::default()
+            .with_offsets_encoding(OffsetsEncoding::DeltaBitpacked)
+            .with_bytes_compression(CompressionScheme::Zstd);
+
+        // Enough distinct values that dictionary encoding is not used
+        let values = (0..5000)
+            .map(|i| {
+                if i % 13 == 0 {
+                    None
+                } else {
+                    Some(format!("value-{}", i % 1000))
+                }
+            })
+            .collect::<StringArray>();
+        let values = Arc::new(values) as ArrayRef;
+
+        let encoded = strategy
+            .create_array_encoder(&[values.clone()])
+            .unwrap()
+            .encode(&[values.clone()], &mut 0)
+            .unwrap();
+        let pb::array_encoding::ArrayEncoding::Binary(binary) =
+            encoded.encoding.array_encoding.as_ref().unwrap()
+        else {
+            panic!("Expected a binary encoding");
+        };
+        // The offsets use an integer encoding
+        assert!(matches!(
+            values_encoding(binary.indices.as_ref().unwrap()).array_encoding,
+            Some(pb::array_encoding::ArrayEncoding::DeltaOfDelta(_))
+        ));
+        // The bytes use byte-oriented compression
+        match values_encoding(binary.bytes.as_ref().unwrap())
+            .array_encoding
+            .as_ref()
+            .unwrap()
+        {
+            pb::array_encoding::ArrayEncoding::Flat(flat) => {
+                assert_eq!(flat.compression.as_ref().unwrap().scheme, "zstd");
+            }
+            other => panic!("Expected a flat encoding but got {:?}", other),
+        }
+
+        // Zstd compressed pages only support reading in full
+        let test_cases = TestCases::default().with_array_encoding_strategy(strategy.clone());
+        check_round_trip_encoding_of_data(vec![values.clone()], &test_cases).await;
+
+        // Delta encoded offsets still support random access
+        let strategy = CoreArrayEncodingStrategy::default()
+            .with_offsets_encoding(OffsetsEncoding::DeltaBitpacked);
+        let test_cases = TestCases::default()
+            .with_range(0..10)
+            .with_range(1000..3500)
+            .with_indices(vec![0, 13, 4999])
+            .with_array_encoding_strategy(strategy);
+        check_round_trip_encoding_of_data(vec![values.clone(), values], &test_cases).await;
+    }
 }
@@ -3,7 +3,6 @@
 
 use std::{ops::Range, sync::Arc};
 
-use arrow_buffer::BooleanBufferBuilder;
 use bytes::{Bytes, BytesMut};
 
 use futures::{future::BoxFuture, FutureExt};
@@ -11,7 +10,7 @@ use lance_core::Result;
 use log::trace;
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
     EncodingsIo,
 };
 
@@ -80,6 +79,14 @@ impl PageScheduler for DenseBitmapScheduler {
         }
         .boxed()
     }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        let num_bytes = ranges
+            .iter()
+            .map(|range| range.end.div_ceil(8) - range.start / 8)
+            .sum();
+        DecodeCost::new(num_bytes, DecodeCpuClass::Copy)
+    }
 }
 
 struct BitmapData {
@@ -92,6 +99,22 @@ struct BitmapDecoder {
     chunks: Vec<BitmapData>,
 }
 
+/// Copies `num_bits` bits from `src` (starting at bit `src_offset`) into `dest`
+/// (starting at bit `dest_offset`)
+///
+/// Neither offset needs to be byte-aligned.  The destination bits must be zeroed.
+fn copy_bits(src: &[u8], src_offset: u64, dest: &mut [u8], dest_offset: u64, num_bits: u64) {
+    debug_assert!(src.len() as u64 * 8 >= src_offset + num_bits);
+    debug_assert!(dest.len() as u64 * 8 >= dest_offset + num_bits);
+    arrow_buffer::bit_mask::set_bits(
+        dest,
+        src,
+        dest_offset as usize,
+        src_offset as usize,
+        num_bits as usize,
+    );
+}
+
 impl PrimitivePageDecoder for BitmapDecoder {
     fn decode(
         &self,
@@ -100,45 +123,34 @@ impl PrimitivePageDecoder for BitmapDecoder {
         _all_null: &mut bool,
     ) -> Result<Vec<BytesMut>> {
         let num_bytes = arrow_buffer::bit_util::ceil(num_rows as usize, 8);
-        let mut dest_buffers = vec![BytesMut::with_capacity(num_bytes)];
+        // copy_bits only sets bits and so we need to start with a zeroed buffer
+        let mut dest = BytesMut::zeroed(num_bytes);
 
         let mut rows_to_skip = rows_to_skip;
-
-        let mut dest_builder = BooleanBufferBuilder::new(num_rows as usize);
-
         let mut rows_remaining = num_rows;
+        let mut dest_offset = 0;
         for chunk in &self.chunks {
+            if rows_remaining == 0 {
+                break;
+            }
             if chunk.length <= rows_to_skip {
                 rows_to_skip -= chunk.length;
             } else {
                 let start = rows_to_skip + chunk.bit_offset;
-                let num_vals_to_take = rows_remaining.min(chunk.length);
-                let end = start + num_vals_to_take;
-                dest_builder.append_packed_range(start as usize..end as usize, &chunk.data);
+                let num_vals_to_take = rows_remaining.min(chunk.length - rows_to_skip);
+                copy_bits(&chunk.data, start, &mut dest, dest_offset, num_vals_to_take);
+                dest_offset += num_vals_to_take;
                 rows_to_skip = 0;
                 rows_remaining -= num_vals_to_take;
             }
         }
+        debug_assert_eq!(rows_remaining, 0);
 
-        let bool_buffer = dest_builder.finish().into_inner();
-        unsafe { dest_buffers[0].set_len(bool_buffer.len()) }
-        // TODO: This requires an extra copy.  First we copy the data from the read buffer(s)
-        // into dest_builder (one copy is inevitable).  Then we copy the data from dest_builder
-        // into dest_buffers.  This second copy could be avoided (e.g. BooleanBufferBuilder
-        // has a new_from_buffer but that requires MutableBuffer and we can't easily get there
-        // from BytesMut [or can we?])
-        //
-        // Worst case, we vendor our own copy of BooleanBufferBuilder based on BytesMut.  We could
-        // also use MutableBuffer ourselves instead of BytesMut but arrow-rs claims MutableBuffer may
-        // be deprecated in the future (though that discussion seems to have died)
-
-        // TODO: Will this work at the boundaries?  If we have to skip 3 bits for example then the first
-        // bytes of bool_buffer.as_slice will be 000XXXXX and if we copy it on top of YYY00000 then the YYY
-        // will be clobbered.
-        //
-        // It's a moot point at the moment since we don't support page bridging
-        dest_buffers[0].copy_from_slice(bool_buffer.as_slice());
-        Ok(dest_buffers)
+        Ok(vec![dest])
+    }
+
+    fn can_skip(&self) -> bool {
+        true
     }
 
     fn num_buffers(&self) -> u32 {
@@ -148,12 +160,18 @@ impl PrimitivePageDecoder for BitmapDecoder {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, BooleanArray};
+    use arrow_buffer::{BooleanBuffer, Buffer};
     use arrow_schema::{DataType, Field};
     use bytes::Bytes;
 
-    use crate::decoder::PrimitivePageDecoder;
+    use crate::decoder::{PrimitivePageDecoder, TileDecoder};
     use crate::encodings::physical::bitmap::BitmapData;
-    use crate::testing::check_round_trip_encoding_random;
+    use crate::testing::{
+        check_round_trip_encoding_of_data, check_round_trip_encoding_random, TestCases,
+    };
 
     use super::BitmapDecoder;
 
@@ -185,4 +203,84 @@ mod tests {
         let result = decoder.decode(5, 1, &mut false);
         assert!(result.is_ok());
     }
+
+    #[test_log::test(tokio::test)]
+    async fn test_bitmap_unaligned_batches() {
+        let values = BooleanArray::from_iter((0..1000).map(|i| Some(i % 3 == 0 || i % 7 == 0)));
+        let values = Arc::new(values) as ArrayRef;
+        for batch_size in [3, 7, 13] {
+            let test_cases = TestCases::default()
+                .with_batch_size(batch_size)
+                .with_range(3..500)
+                .with_range(11..989)
+                .with_indices(vec![
+                    1, 2, 3, 5, 13, 21, 34, 55, 89, 144, 233, 377, 610, 987,
+                ]);
+            check_round_trip_encoding_of_data(vec![values.clone()], &test_cases).await;
+        }
+    }
+
+    #[test]
+    fn test_bitmap_tiles() {
+        // 200 booleans are three full tiles of 8 bytes and a partial tile of one byte
+        let data = (0..25).map(|i| i as u8 * 7).collect::<Vec<_>>();
+        let decoder = BitmapDecoder {
+            chunks: vec![BitmapData {
+                data: Bytes::from(data.clone()),
+                bit_offset: 0,
+                length: 200,
+            }],
+        };
+        let mut tiles = TileDecoder::try_new(&decoder, 0, 200, 64, 1).unwrap();
+        let mut tile_index = 0;
+        while let Some(tile) = tiles.next_tile().unwrap() {
+            assert_eq!(tile.values.len(), 8);
+            let start = tile_index * 8;
+            let end = (start + 8).min(data.len());
+            assert_eq!(tile.num_values, (end - start) as u64 * 8);
+            let mut expected = data[start..end].to_vec();
+            expected.resize(8, 0);
+            assert_eq!(tile.values, expected.as_slice());
+            tile_index += 1;
+        }
+        assert_eq!(tile_index, 4);
+    }
+
+    #[test]
+    fn test_bitmap_decoder_spans_chunks() {
+        // Two chunks, both with a bit offset, and a skip that lands mid-byte
+        let decoder = BitmapDecoder {
+            chunks: vec![
+                BitmapData {
+                    data: Bytes::from_static(&[0b10101010, 0b00001111]),
+                    bit_offset: 1,
+                    length: 10,
+                },
+                BitmapData {
+                    data: Bytes::from_static(&[0b11001100]),
+                    bit_offset: 2,
+                    length: 5,
+                },
+            ],
+        };
+
+        let expected = [
+            // Chunk one (bits 1..11)
+            true, false, true, false, true, false, true, true, true, true,
+            // Chunk two (bits 2..7)
+            true, true, false, false, true,
+        ];
+
+        for rows_to_skip in 0..expected.len() {
+            for num_rows in 1..=(expected.len() - rows_to_skip) {
+                let buffers = decoder
+                    .decode(rows_to_skip as u64, num_rows as u64, &mut false)
+                    .unwrap();
+                let actual =
+                    BooleanBuffer::new(Buffer::from(buffers[0].clone().freeze()), 0, num_rows);
+                let actual = actual.iter().collect::<Vec<_>>();
+                assert_eq!(actual, &expected[rows_to_skip..rows_to_skip + num_rows]);
+            }
+        }
+    }
 }
@@ -0,0 +1,1249 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use arrow_array::{Array, ArrayRef};
+use arrow_buffer::Buffer;
+use arrow_schema::DataType;
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt};
+use log::trace;
+use snafu::{location, Location};
+
+use lance_arrow::DataTypeExt;
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encoder::{ArrayEncoder, EncodedArray, EncodedArrayBuffer},
+    format::pb,
+    EncodingsIo,
+};
+
+use super::bits::{BitReader, BitWriter};
+
+/// Returns true if the data type is an integer type that can be bitpacked
+pub fn is_bitpackable(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+    )
+}
+
+fn is_signed(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64
+    )
+}
+
+// Calls `f` with the raw (zero extended) bits of every value in the array, including
+// any values hidden behind a null
+fn for_each_raw_value(arr: &dyn Array, mut f: impl FnMut(u64)) {
+    let byte_width = arr.data_type().byte_width();
+    let data = arr.to_data();
+    let start = data.offset() * byte_width;
+    let end = start + data.len() * byte_width;
+    for chunk in data.buffers()[0][start..end].chunks_exact(byte_width) {
+        let mut le_bytes = [0_u8; 8];
+        le_bytes[..byte_width].copy_from_slice(chunk);
+        f(u64::from_le_bytes(le_bytes));
+    }
+}
+
+// Like `for_each_raw_value` but skips any null slots
+fn for_each_valid_raw_value(arr: &dyn Array, mut f: impl FnMut(u64)) {
+    let Some(nulls) = arr.nulls().filter(|nulls| nulls.null_count() > 0) else {
+        return for_each_raw_value(arr, f);
+    };
+    let mut valid = nulls.iter();
+    for_each_raw_value(arr, |value| {
+        if valid.next().unwrap_or(true) {
+            f(value);
+        }
+    });
+}
+
+// Sign extends the lowest `num_bits` bits of `value` to a full 64-bit value
+fn sign_extend(value: u64, num_bits: u64) -> u64 {
+    if num_bits >= 64 {
+        value
+    } else {
+        let shift = 64 - num_bits;
+        (((value << shift) as i64) >> shift) as u64
+    }
+}
+
+// The number of bits needed to represent `value`, including a sign bit if `signed`
+fn bits_needed(value: u64, uncompressed_bits: u64, signed: bool) -> u64 {
+    if signed {
+        let value = sign_extend(value, uncompressed_bits) as i64;
+        let magnitude = if value < 0 { !value } else { value } as u64;
+        (64 - magnitude.leading_zeros() as u64) + 1
+    } else {
+        64 - value.leading_zeros() as u64
+    }
+}
+
+fn check_bitpackable(data_type: &DataType) -> Result<u64> {
+    if !is_bitpackable(data_type) {
+        return Err(Error::unsupported_type(
+            data_type,
+            "only integers can be bitpacked",
+            location!(),
+        ));
+    }
+    Ok(8 * data_type.byte_width() as u64)
+}
+
+/// Calculates the minimum number of bits needed to represent every value in the arrays
+///
+/// Returns None if the arrays are not a bitpackable integer type.  Signed values include
+/// a sign bit in the count.  Null slots are ignored, they may hold arbitrary values and
+/// the decoder masks them with the validity bitmap anyways.
+pub fn num_compressed_bits(arrays: &[ArrayRef]) -> Option<u64> {
+    let data_type = arrays.first()?.data_type();
+    if !is_bitpackable(data_type) {
+        return None;
+    }
+    let uncompressed_bits = 8 * data_type.byte_width() as u64;
+    let signed = is_signed(data_type);
+    let mut num_bits = 1;
+    for arr in arrays {
+        for_each_valid_raw_value(arr.as_ref(), |value| {
+            num_bits = num_bits.max(bits_needed(value, uncompressed_bits, signed));
+        });
+    }
+    Some(num_bits)
+}
+
+// The number of bits in a word of a word aligned bitpacked page
+const WORD_BITS: u64 = 32;
+
+/// Encodes integer arrays by packing each value into `num_bits` bits
+///
+/// The caller is responsible for ensuring every value fits (see [`num_compressed_bits`])
+#[derive(Debug)]
+pub struct BitpackedArrayEncoder {
+    num_bits: u64,
+    word_aligned: bool,
+}
+
+impl BitpackedArrayEncoder {
+    pub fn new(num_bits: u64) -> Self {
+        Self {
+            num_bits,
+            word_aligned: false,
+        }
+    }
+
+    /// Packs the values into 32-bit words so that no value crosses a word boundary
+    ///
+    /// Widths that don't divide 32 waste the leftover bits of each word but every value
+    /// can be unpacked from a single aligned 32-bit load, which suits GPU style decoders.
+    /// Only widths up to 32 bits can be word aligned.
+    pub fn with_word_alignment(mut self, word_aligned: bool) -> Self {
+        self.word_aligned = word_aligned;
+        self
+    }
+}
+
+impl ArrayEncoder for BitpackedArrayEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        let uncompressed_bits_per_value = check_bitpackable(data_type)?;
+        if self.num_bits == 0 || self.num_bits > uncompressed_bits_per_value {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot bitpack {} values into {} bits",
+                    data_type, self.num_bits
+                ),
+                location!(),
+            ));
+        }
+
+        if self.word_aligned && self.num_bits > WORD_BITS {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot word align {}-bit values, at most {} bits are allowed",
+                    self.num_bits, WORD_BITS
+                ),
+                location!(),
+            ));
+        }
+
+        let num_values = arrays.iter().map(|arr| arr.len() as u64).sum::<u64>();
+        let packed = if self.word_aligned {
+            let values_per_word = WORD_BITS / self.num_bits;
+            let padding = WORD_BITS - values_per_word * self.num_bits;
+            let num_bits = num_values.div_ceil(values_per_word) * WORD_BITS;
+            let mut writer = BitWriter::with_capacity(num_bits);
+            let mut values_in_word = 0;
+            for arr in arrays {
+                for_each_raw_value(arr.as_ref(), |value| {
+                    writer.write(value, self.num_bits);
+                    values_in_word += 1;
+                    if values_in_word == values_per_word {
+                        writer.write(0, padding);
+                        values_in_word = 0;
+                    }
+                });
+            }
+            // Pad out the last (partial) word
+            writer.write(0, (WORD_BITS - writer.position() % WORD_BITS) % WORD_BITS);
+            debug_assert_eq!(writer.position(), num_bits);
+            writer.finish()
+        } else {
+            let mut writer = BitWriter::with_capacity(num_values * self.num_bits);
+            for arr in arrays {
+                for_each_raw_value(arr.as_ref(), |value| writer.write(value, self.num_bits));
+            }
+            debug_assert_eq!(writer.position(), num_values * self.num_bits);
+            writer.finish()
+        };
+
+        let index = *buffer_index;
+        *buffer_index += 1;
+
+        Ok(EncodedArray {
+            buffers: vec![EncodedArrayBuffer {
+                parts: vec![Buffer::from_vec(packed)],
+                index,
+            }],
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::Bitpacked(
+                    pb::Bitpacked {
+                        compressed_bits_per_value: self.num_bits,
+                        uncompressed_bits_per_value,
+                        buffer: Some(pb::Buffer {
+                            buffer_index: index,
+                            buffer_type: pb::buffer::BufferType::Page as i32,
+                        }),
+                        signed: is_signed(data_type),
+                        word_aligned: self.word_aligned,
+                        num_values,
+                    },
+                )),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// Scheduler for a page of bitpacked integers
+#[derive(Debug, Clone, Copy)]
+pub struct BitpackedScheduler {
+    bits_per_value: u64,
+    uncompressed_bits_per_value: u64,
+    buffer_offset: u64,
+    signed: bool,
+    // The number of values in each 32-bit word if the page is word aligned
+    values_per_word: Option<u64>,
+    // The number of values in the page, 0 if unknown
+    num_values: u64,
+    strict: bool,
+}
+
+impl BitpackedScheduler {
+    pub fn new(
+        bits_per_value: u64,
+        uncompressed_bits_per_value: u64,
+        buffer_offset: u64,
+        signed: bool,
+    ) -> Self {
+        Self {
+            bits_per_value,
+            uncompressed_bits_per_value,
+            buffer_offset,
+            signed,
+            values_per_word: None,
+            num_values: 0,
+            strict: false,
+        }
+    }
+
+    /// Enables strict mode, in which unpacking fails if any padding bit is set
+    ///
+    /// The padding bits are the unused high bits of each word of a word aligned page and
+    /// the bits after the last value of the page (if the number of values in the page is
+    /// known, see [`Self::with_num_values`]).  A set padding bit means the page is corrupt,
+    /// although a lenient unpack still decodes the values.  This is a diagnostic and costs
+    /// some decode time.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the number of values in the page, 0 if unknown
+    ///
+    /// This is only needed to find the padding after the last value in strict mode
+    pub fn with_num_values(mut self, num_values: u64) -> Self {
+        self.num_values = num_values;
+        self
+    }
+
+    /// Marks the page as word aligned (see [`BitpackedArrayEncoder::with_word_alignment`])
+    ///
+    /// The caller must ensure `bits_per_value` is between 1 and 32 if `word_aligned` is true
+    pub fn with_word_alignment(mut self, word_aligned: bool) -> Self {
+        self.values_per_word = word_aligned.then(|| WORD_BITS / self.bits_per_value);
+        self
+    }
+
+    // The range of bytes (relative to the start of the page) holding the values in `range`
+    // and the decode info for those values
+    fn chunk(&self, range: &Range<u64>) -> (Range<u64>, BitpackedChunk) {
+        let num_values = range.end - range.start;
+        let padded_end = self.num_values > 0 && range.end == self.num_values;
+        if let Some(values_per_word) = self.values_per_word {
+            let start_word = range.start / values_per_word;
+            let end_word = range.end.div_ceil(values_per_word);
+            (
+                (start_word * WORD_BITS / 8)..(end_word * WORD_BITS / 8),
+                BitpackedChunk {
+                    bit_offset: (range.start % values_per_word) * self.bits_per_value,
+                    num_values,
+                    bits_per_value: self.bits_per_value,
+                    values_per_word: Some(values_per_word),
+                    padded_end,
+                },
+            )
+        } else {
+            let start_bit = range.start * self.bits_per_value;
+            let end_bit = range.end * self.bits_per_value;
+            (
+                (start_bit / 8)..end_bit.div_ceil(8),
+                BitpackedChunk {
+                    bit_offset: start_bit % 8,
+                    num_values,
+                    bits_per_value: self.bits_per_value,
+                    values_per_word: None,
+                    padded_end,
+                },
+            )
+        }
+    }
+}
+
+impl PageScheduler for BitpackedScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let mut chunks = Vec::with_capacity(ranges.len());
+        let byte_ranges = ranges
+            .iter()
+            .map(|range| {
+                let (byte_range, chunk) = self.chunk(range);
+                chunks.push(chunk);
+                (self.buffer_offset + byte_range.start)..(self.buffer_offset + byte_range.end)
+            })
+            .collect::<Vec<_>>();
+
+        trace!(
+            "Scheduling I/O for {} ranges of bitpacked data ({} bits per value)",
+            byte_ranges.len(),
+            self.bits_per_value
+        );
+        let bytes = scheduler.submit_request(byte_ranges, top_level_row);
+        let uncompressed_bits_per_value = self.uncompressed_bits_per_value;
+        let signed = self.signed;
+        let strict = self.strict;
+
+        async move {
+            let data = bytes.await?;
+            Ok(Box::new(BitpackedPageDecoder {
+                uncompressed_bits_per_value,
+                signed,
+                strict,
+                data,
+                chunks,
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        let num_bytes = ranges
+            .iter()
+            .map(|range| {
+                let (byte_range, _) = self.chunk(range);
+                byte_range.end - byte_range.start
+            })
+            .sum();
+        DecodeCost::new(num_bytes, DecodeCpuClass::Unpack)
+    }
+}
+
+/// The default number of values in each chunk of a chunked bitpacked page
+pub const DEFAULT_VALUES_PER_CHUNK: u64 = 1024;
+
+/// Encodes integer arrays in chunks, packing the values of each chunk into the minimum
+/// number of bits needed for that chunk
+///
+/// This is smaller than [`BitpackedArrayEncoder`] when the magnitude of the values varies
+/// across the page (e.g. a few large values in an otherwise small page) at the cost of a
+/// width table with one byte per chunk.
+#[derive(Debug)]
+pub struct ChunkedBitpackedArrayEncoder {
+    values_per_chunk: u64,
+}
+
+impl ChunkedBitpackedArrayEncoder {
+    pub fn new(values_per_chunk: u64) -> Self {
+        Self { values_per_chunk }
+    }
+}
+
+impl Default for ChunkedBitpackedArrayEncoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_VALUES_PER_CHUNK)
+    }
+}
+
+impl ArrayEncoder for ChunkedBitpackedArrayEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        let uncompressed_bits_per_value = check_bitpackable(data_type)?;
+        if self.values_per_chunk == 0 {
+            return Err(Error::invalid_input(
+                "Chunked bitpacking needs at least one value per chunk",
+                location!(),
+            ));
+        }
+        let signed = is_signed(data_type);
+
+        let num_values = arrays.iter().map(|arr| arr.len()).sum::<usize>();
+        let mut values = Vec::with_capacity(num_values);
+        let mut validity = Vec::with_capacity(num_values);
+        for arr in arrays {
+            for_each_raw_value(arr.as_ref(), |value| values.push(value));
+            validity.extend((0..arr.len()).map(|idx| arr.is_valid(idx)));
+        }
+
+        // Null slots are ignored when picking a chunk's width, just like `num_compressed_bits`
+        let chunk_size = self.values_per_chunk as usize;
+        let chunk_bits_per_value = values
+            .chunks(chunk_size)
+            .zip(validity.chunks(chunk_size))
+            .map(|(chunk, valid)| {
+                chunk
+                    .iter()
+                    .zip(valid)
+                    .filter(|(_, valid)| **valid)
+                    .map(|(value, _)| bits_needed(*value, uncompressed_bits_per_value, signed))
+                    .fold(1, u64::max) as u8
+            })
+            .collect::<Vec<_>>();
+
+        let num_bits = values
+            .chunks(chunk_size)
+            .zip(&chunk_bits_per_value)
+            .map(|(chunk, bits)| (chunk.len() as u64 * *bits as u64).next_multiple_of(8))
+            .sum();
+        let mut writer = BitWriter::with_capacity(num_bits);
+        for (chunk, bits) in values.chunks(chunk_size).zip(&chunk_bits_per_value) {
+            for value in chunk {
+                writer.write(*value, *bits as u64);
+            }
+            // Pad so the next chunk starts on a byte boundary
+            let padding = (8 - writer.position() % 8) % 8;
+            writer.write(0, padding);
+        }
+        debug_assert_eq!(writer.position(), num_bits);
+        let packed = writer.finish();
+
+        let index = *buffer_index;
+        *buffer_index += 1;
+
+        Ok(EncodedArray {
+            buffers: vec![EncodedArrayBuffer {
+                parts: vec![Buffer::from_vec(packed)],
+                index,
+            }],
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::ChunkedBitpacked(
+                    pb::ChunkedBitpacked {
+                        values_per_chunk: self.values_per_chunk,
+                        chunk_bits_per_value,
+                        uncompressed_bits_per_value,
+                        buffer: Some(pb::Buffer {
+                            buffer_index: index,
+                            buffer_type: pb::buffer::BufferType::Page as i32,
+                        }),
+                        signed,
+                        num_values: num_values as u64,
+                    },
+                )),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// Scheduler for a page of integers bitpacked in chunks of varying width
+#[derive(Debug, Clone)]
+pub struct ChunkedBitpackedScheduler {
+    values_per_chunk: u64,
+    chunk_bits_per_value: Vec<u8>,
+    // The offset of each chunk, relative to the start of the page
+    chunk_offsets: Vec<u64>,
+    uncompressed_bits_per_value: u64,
+    buffer_offset: u64,
+    signed: bool,
+    // The number of values in the page, 0 if unknown
+    num_values: u64,
+    strict: bool,
+}
+
+impl ChunkedBitpackedScheduler {
+    pub fn new(
+        values_per_chunk: u64,
+        chunk_bits_per_value: Vec<u8>,
+        uncompressed_bits_per_value: u64,
+        buffer_offset: u64,
+        signed: bool,
+    ) -> Self {
+        // Every chunk except the last is full and the last chunk's size doesn't matter
+        let chunk_offsets = chunk_bits_per_value
+            .iter()
+            .scan(0, |offset, bits| {
+                let chunk_offset = *offset;
+                *offset += (values_per_chunk * *bits as u64).div_ceil(8);
+                Some(chunk_offset)
+            })
+            .collect();
+        Self {
+            values_per_chunk,
+            chunk_bits_per_value,
+            chunk_offsets,
+            uncompressed_bits_per_value,
+            buffer_offset,
+            signed,
+            num_values: 0,
+            strict: false,
+        }
+    }
+
+    /// Enables strict mode, in which unpacking fails if any padding bit is set
+    ///
+    /// Each chunk is padded to a whole number of bytes and the padding bits after the last
+    /// value of every full chunk (and of the last chunk, if the number of values in the page
+    /// is known) are checked.  See [`BitpackedScheduler::with_strict`].
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the number of values in the page, see [`BitpackedScheduler::with_num_values`]
+    pub fn with_num_values(mut self, num_values: u64) -> Self {
+        self.num_values = num_values;
+        self
+    }
+
+    // Splits the ranges into pieces that each fall within a single chunk and returns the
+    // byte range and decode info for each piece
+    fn chunk_pieces<'a>(
+        &'a self,
+        ranges: &'a [Range<u64>],
+    ) -> impl Iterator<Item = (Range<u64>, BitpackedChunk)> + 'a {
+        ranges
+            .iter()
+            .filter(|range| range.start < range.end)
+            .flat_map(move |range| {
+                let first_chunk = range.start / self.values_per_chunk;
+                let last_chunk = (range.end - 1) / self.values_per_chunk;
+                (first_chunk..=last_chunk).map(move |chunk_idx| {
+                    let chunk_start = chunk_idx * self.values_per_chunk;
+                    let start = range.start.max(chunk_start) - chunk_start;
+                    let end = range.end.min(chunk_start + self.values_per_chunk) - chunk_start;
+                    let bits_per_value = self.chunk_bits_per_value[chunk_idx as usize] as u64;
+                    let chunk_offset = self.buffer_offset + self.chunk_offsets[chunk_idx as usize];
+                    let start_bit = start * bits_per_value;
+                    let end_bit = end * bits_per_value;
+                    (
+                        (chunk_offset + start_bit / 8)..(chunk_offset + end_bit.div_ceil(8)),
+                        BitpackedChunk {
+                            bit_offset: start_bit % 8,
+                            num_values: end - start,
+                            bits_per_value,
+                            values_per_word: None,
+                            // Only full chunks, and the last chunk if the page size is
+                            // known, are known to end here
+                            padded_end: end == self.values_per_chunk
+                                || (self.num_values > 0 && chunk_start + end == self.num_values),
+                        },
+                    )
+                })
+            })
+    }
+}
+
+impl PageScheduler for ChunkedBitpackedScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let (byte_ranges, chunks): (Vec<_>, Vec<_>) = self.chunk_pieces(ranges).unzip();
+
+        trace!(
+            "Scheduling I/O for {} ranges of chunked bitpacked data",
+            byte_ranges.len(),
+        );
+        let bytes = scheduler.submit_request(byte_ranges, top_level_row);
+        let uncompressed_bits_per_value = self.uncompressed_bits_per_value;
+        let signed = self.signed;
+        let strict = self.strict;
+
+        async move {
+            let data = bytes.await?;
+            Ok(Box::new(BitpackedPageDecoder {
+                uncompressed_bits_per_value,
+                signed,
+                strict,
+                data,
+                chunks,
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        let num_bytes = self
+            .chunk_pieces(ranges)
+            .map(|(byte_range, _)| byte_range.end - byte_range.start)
+            .sum();
+        DecodeCost::new(num_bytes, DecodeCpuClass::Unpack)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BitpackedChunk {
+    // The bit (within the first byte of the chunk's data) where the first value starts
+    bit_offset: u64,
+    num_values: u64,
+    bits_per_value: u64,
+    // Set if the values are packed into 32-bit words, in which case the chunk's data
+    // starts on a word boundary
+    values_per_word: Option<u64>,
+    // Set if the chunk's data is padded after its last value, to a whole byte or to the
+    // end of the word if the values are packed into words
+    padded_end: bool,
+}
+
+impl BitpackedChunk {
+    // The bit position of the `index`-th value of the chunk
+    fn value_position(&self, index: u64) -> u64 {
+        match self.values_per_word {
+            None => self.bit_offset + index * self.bits_per_value,
+            Some(values_per_word) => {
+                let slot = self.bit_offset / self.bits_per_value + index;
+                (slot / values_per_word) * WORD_BITS
+                    + (slot % values_per_word) * self.bits_per_value
+            }
+        }
+    }
+
+    // Fails if any padding bit around the values `start..end` (indices within the chunk)
+    // is set
+    fn check_padding(&self, reader: &mut BitReader, start: u64, end: u64) -> Result<()> {
+        let mut padding = Vec::new();
+        if let Some(values_per_word) = self.values_per_word {
+            let first_word = self.value_position(start) / WORD_BITS;
+            let last_word = self.value_position(end - 1) / WORD_BITS;
+            let used_bits = values_per_word * self.bits_per_value;
+            padding.extend(
+                (first_word..=last_word)
+                    .map(|word| (word * WORD_BITS + used_bits)..((word + 1) * WORD_BITS)),
+            );
+        }
+        if self.padded_end && end == self.num_values {
+            let end_bit = self.value_position(end);
+            let alignment = if self.values_per_word.is_some() {
+                WORD_BITS
+            } else {
+                8
+            };
+            padding.push(end_bit..end_bit.next_multiple_of(alignment));
+        }
+        for bits in padding {
+            if bits.is_empty() {
+                continue;
+            }
+            reader.seek(bits.start);
+            if reader.read(bits.end - bits.start) != 0 {
+                return Err(Error::corrupt_metadata(
+                    format!(
+                        "Corrupt bitpacked data: padding bits {}..{} are not zero",
+                        bits.start, bits.end
+                    ),
+                    location!(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct BitpackedPageDecoder {
+    uncompressed_bits_per_value: u64,
+    signed: bool,
+    strict: bool,
+    data: Vec<Bytes>,
+    chunks: Vec<BitpackedChunk>,
+}
+
+impl PrimitivePageDecoder for BitpackedPageDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let bytes_per_value = (self.uncompressed_bits_per_value / 8) as usize;
+        let mut dest = BytesMut::with_capacity(num_rows as usize * bytes_per_value);
+
+        let mut rows_to_skip = rows_to_skip;
+        let mut rows_remaining = num_rows;
+        for (buf, chunk) in self.data.iter().zip(self.chunks.iter()) {
+            if rows_remaining == 0 {
+                break;
+            }
+            if rows_to_skip >= chunk.num_values {
+                rows_to_skip -= chunk.num_values;
+                continue;
+            }
+            let num_vals_to_take = rows_remaining.min(chunk.num_values - rows_to_skip);
+            let mut reader = BitReader::new(buf);
+            reader.seek(chunk.value_position(rows_to_skip));
+            for idx in 0..num_vals_to_take {
+                if chunk.values_per_word.is_some() {
+                    reader.seek(chunk.value_position(rows_to_skip + idx));
+                }
+                let mut value = reader.read(chunk.bits_per_value);
+                if self.signed {
+                    value = sign_extend(value, chunk.bits_per_value);
+                }
+                dest.extend_from_slice(&value.to_le_bytes()[..bytes_per_value]);
+            }
+            debug_assert!(reader.position() <= 8 * buf.len() as u64);
+            if self.strict {
+                chunk.check_padding(&mut reader, rows_to_skip, rows_to_skip + num_vals_to_take)?;
+            }
+            rows_to_skip = 0;
+            rows_remaining -= num_vals_to_take;
+        }
+
+        Ok(vec![dest])
+    }
+
+    fn can_skip(&self) -> bool {
+        true
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Range;
+    use std::sync::Arc;
+
+    use arrow_array::{
+        Array, ArrayRef, Float32Array, Int16Array, Int32Array, Int64Array, UInt32Array, UInt8Array,
+    };
+    use arrow_buffer::{Buffer, NullBuffer, ScalarBuffer};
+    use arrow_schema::{DataType, Field};
+    use bytes::Bytes;
+    use lance_core::{error::EncodingError, Error, Result};
+
+    use crate::{
+        decoder::PageScheduler,
+        encoder::{ArrayEncoder, EncodedArray, EncodingOverride},
+        format::pb,
+        testing::check_round_trip_encoding_random_with_override,
+        BufferScheduler, EncodingsIo,
+    };
+
+    use super::{
+        num_compressed_bits, BitpackedArrayEncoder, BitpackedScheduler,
+        ChunkedBitpackedArrayEncoder, ChunkedBitpackedScheduler,
+    };
+
+    /// Bitpacks the arrays (using the minimum width) and returns a scheduler for the page
+    /// (assuming the page is placed at `buffer_offset`), the packed data, and the number
+    /// of bits used
+    pub fn bitpack_page(
+        arrays: &[ArrayRef],
+        buffer_offset: u64,
+    ) -> (BitpackedScheduler, Bytes, u64) {
+        let num_bits = num_compressed_bits(arrays).unwrap();
+        let EncodedArray {
+            mut buffers,
+            encoding,
+        } = BitpackedArrayEncoder::new(num_bits)
+            .encode(arrays, &mut 0)
+            .unwrap();
+        let data = buffers.pop().unwrap().parts.remove(0);
+        let Some(pb::array_encoding::ArrayEncoding::Bitpacked(bitpacked)) = encoding.array_encoding
+        else {
+            panic!("Expected bitpacked encoding")
+        };
+        let scheduler = BitpackedScheduler::new(
+            bitpacked.compressed_bits_per_value,
+            bitpacked.uncompressed_bits_per_value,
+            buffer_offset,
+            bitpacked.signed,
+        );
+        (scheduler, Bytes::from(data.to_vec()), num_bits)
+    }
+
+    #[test]
+    fn test_num_compressed_bits() {
+        let arr = |values: Vec<u32>| vec![Arc::new(UInt32Array::from(values)) as ArrayRef];
+        assert_eq!(num_compressed_bits(&arr(vec![0, 0])), Some(1));
+        assert_eq!(num_compressed_bits(&arr(vec![1, 7])), Some(3));
+        assert_eq!(num_compressed_bits(&arr(vec![8])), Some(4));
+        assert_eq!(num_compressed_bits(&arr(vec![u32::MAX])), Some(32));
+
+        let arr = |values: Vec<i64>| vec![Arc::new(Int64Array::from(values)) as ArrayRef];
+        assert_eq!(num_compressed_bits(&arr(vec![0])), Some(1));
+        assert_eq!(num_compressed_bits(&arr(vec![-1])), Some(1));
+        assert_eq!(num_compressed_bits(&arr(vec![3, -4])), Some(3));
+        assert_eq!(num_compressed_bits(&arr(vec![4])), Some(4));
+        assert_eq!(num_compressed_bits(&arr(vec![i64::MIN])), Some(64));
+
+        let floats = vec![Arc::new(Float32Array::from(vec![1.0])) as ArrayRef];
+        assert_eq!(num_compressed_bits(&floats), None);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_nulls_ignored_for_width() {
+        // The null slots hold large garbage values
+        let values = ScalarBuffer::from(
+            (0..100)
+                .map(|i| {
+                    if i % 2 == 0 {
+                        i % 8
+                    } else {
+                        -1_000_000_000 + i
+                    }
+                })
+                .collect::<Vec<i32>>(),
+        );
+        let nulls = NullBuffer::from((0..100).map(|i| i % 2 == 0).collect::<Vec<_>>());
+        let arr = Int32Array::new(values, Some(nulls));
+        let arrays = vec![Arc::new(arr.clone()) as ArrayRef];
+
+        let (scheduler, data, num_bits) = bitpack_page(&arrays, 0);
+        // Values 0..8 need 3 bits plus a sign bit
+        assert_eq!(num_bits, 4);
+
+        let io = Arc::new(BufferScheduler::new(data)) as Arc<dyn EncodingsIo>;
+        let decoder = scheduler
+            .schedule_ranges(std::slice::from_ref(&(0..100)), &io, 0)
+            .await
+            .unwrap();
+        let buffers = decoder.decode(0, 100, &mut false).unwrap();
+        let actual = Int32Array::new(
+            ScalarBuffer::new(Buffer::from(buffers[0].clone().freeze()), 0, 100),
+            arr.nulls().cloned(),
+        );
+        assert_eq!(actual, arr);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_bitpacked_round_trip() {
+        let values = (0..1000).map(|i| (i * 7) % 300).collect::<Vec<u32>>();
+        let arrays = vec![
+            Arc::new(UInt32Array::from(values[..400].to_vec())) as ArrayRef,
+            Arc::new(UInt32Array::from(values[400..].to_vec())) as ArrayRef,
+        ];
+        let (scheduler, data, num_bits) = bitpack_page(&arrays, 0);
+        let io = Arc::new(BufferScheduler::new(data)) as Arc<dyn EncodingsIo>;
+        assert_eq!(num_bits, 9);
+
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = [vec![0..1000], vec![3..5, 17..400, 999..1000], vec![]];
+        for ranges in ranges {
+            let decoder = scheduler.schedule_ranges(&ranges, &io, 0).await.unwrap();
+            let expected = ranges
+                .iter()
+                .flat_map(|range| values[range.start as usize..range.end as usize].to_vec())
+                .collect::<Vec<_>>();
+            let num_rows = expected.len() as u64;
+            for rows_to_skip in [0, num_rows / 3] {
+                let buffers = decoder
+                    .decode(rows_to_skip, num_rows - rows_to_skip, &mut false)
+                    .unwrap();
+                let actual = ScalarBuffer::<u32>::from(Buffer::from(buffers[0].clone().freeze()));
+                assert_eq!(actual.as_ref(), &expected[rows_to_skip as usize..]);
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_bitpacked_signed() {
+        let values = Int16Array::from(vec![-300, 5, 0, -1, 255, i16::MIN / 64]);
+        let (scheduler, data, num_bits) = bitpack_page(&[Arc::new(values.clone()) as ArrayRef], 0);
+        let io = Arc::new(BufferScheduler::new(data)) as Arc<dyn EncodingsIo>;
+        assert_eq!(num_bits, 10);
+
+        #[allow(clippy::single_range_in_vec_init)]
+        let decoder = scheduler.schedule_ranges(&[0..6], &io, 0).await.unwrap();
+        let buffers = decoder.decode(0, 6, &mut false).unwrap();
+        let actual = Int16Array::new(
+            ScalarBuffer::new(Buffer::from(buffers[0].clone().freeze()), 0, 6),
+            None,
+        );
+        assert_eq!(actual, values);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_chunked_bitpacked_spike() {
+        // Small values with a spike of large (negative) values in the middle of the page
+        let values = (0..5000)
+            .map(|i| match i {
+                2100..=2200 => -(1 << 20) - i,
+                _ => i % 16,
+            })
+            .collect::<Vec<i32>>();
+        let nulls = NullBuffer::from((0..5000).map(|i| i % 13 != 0).collect::<Vec<_>>());
+        let arr = Int32Array::new(ScalarBuffer::from(values.clone()), Some(nulls));
+        let arrays = vec![arr.slice(0, 1500), arr.slice(1500, 3500)]
+            .into_iter()
+            .map(|arr| Arc::new(arr) as ArrayRef)
+            .collect::<Vec<_>>();
+
+        let (_, single_width, num_bits) = bitpack_page(&arrays, 0);
+        assert_eq!(num_bits, 22);
+        let EncodedArray {
+            mut buffers,
+            encoding,
+        } = ChunkedBitpackedArrayEncoder::new(1024)
+            .encode(&arrays, &mut 0)
+            .unwrap();
+        let data = buffers.pop().unwrap().parts.remove(0);
+        let Some(pb::array_encoding::ArrayEncoding::ChunkedBitpacked(chunked)) =
+            encoding.array_encoding
+        else {
+            panic!("Expected chunked bitpacked encoding")
+        };
+        // Only the chunk with the spike needs the full width
+        assert_eq!(chunked.chunk_bits_per_value, vec![5, 5, 22, 5, 5]);
+        assert!(data.len() + chunked.chunk_bits_per_value.len() < single_width.len() / 2);
+
+        let scheduler = ChunkedBitpackedScheduler::new(
+            chunked.values_per_chunk,
+            chunked.chunk_bits_per_value,
+            chunked.uncompressed_bits_per_value,
+            10,
+            chunked.signed,
+        );
+        let mut page = vec![0_u8; 10];
+        page.extend_from_slice(&data);
+        let io = Arc::new(BufferScheduler::new(Bytes::from(page))) as Arc<dyn EncodingsIo>;
+
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = [
+            vec![0..5000],
+            vec![1000..1030, 2040..3100, 4999..5000],
+            vec![2150..2150, 4096..4100],
+            vec![],
+        ];
+        for ranges in ranges {
+            let decoder = scheduler.schedule_ranges(&ranges, &io, 0).await.unwrap();
+            let rows = ranges
+                .iter()
+                .flat_map(|range| range.start as usize..range.end as usize)
+                .collect::<Vec<_>>();
+            let num_rows = rows.len() as u64;
+            for rows_to_skip in [0, num_rows / 3] {
+                let buffers = decoder
+                    .decode(rows_to_skip, num_rows - rows_to_skip, &mut false)
+                    .unwrap();
+                let actual = ScalarBuffer::<i32>::from(Buffer::from(buffers[0].clone().freeze()));
+                assert_eq!(actual.len() as u64, num_rows - rows_to_skip);
+                // Null slots may decode to anything
+                for (actual, row) in actual.iter().zip(&rows[rows_to_skip as usize..]) {
+                    if arr.is_valid(*row) {
+                        assert_eq!(*actual, values[*row]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_word_aligned_bitpacking() {
+        for num_bits in [5_u64, 7, 11, 16, 32] {
+            let values = (0..1000_u64)
+                .map(|i| ((i * 2654435761) % (1 << num_bits)) as u32)
+                .collect::<Vec<_>>();
+            let arrays = vec![
+                Arc::new(UInt32Array::from(values[..333].to_vec())) as ArrayRef,
+                Arc::new(UInt32Array::from(values[333..].to_vec())) as ArrayRef,
+            ];
+            let EncodedArray {
+                mut buffers,
+                encoding,
+            } = BitpackedArrayEncoder::new(num_bits)
+                .with_word_alignment(true)
+                .encode(&arrays, &mut 0)
+                .unwrap();
+            let data = buffers.pop().unwrap().parts.remove(0);
+            let Some(pb::array_encoding::ArrayEncoding::Bitpacked(bitpacked)) =
+                encoding.array_encoding
+            else {
+                panic!("Expected bitpacked encoding")
+            };
+            assert!(bitpacked.word_aligned);
+
+            // Every value can be extracted from its own word and the leftover bits are zero
+            let values_per_word = (32 / num_bits) as usize;
+            let words = data
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(words.len(), values.len().div_ceil(values_per_word));
+            let mask = u32::MAX >> (32 - num_bits);
+            for (word, expected) in words.iter().zip(values.chunks(values_per_word)) {
+                for (slot, value) in expected.iter().enumerate() {
+                    assert_eq!((word >> (slot as u64 * num_bits)) & mask, *value);
+                }
+                let used_bits = expected.len() as u64 * num_bits;
+                if used_bits < 32 {
+                    assert_eq!(word >> used_bits, 0);
+                }
+            }
+
+            let scheduler = BitpackedScheduler::new(
+                bitpacked.compressed_bits_per_value,
+                bitpacked.uncompressed_bits_per_value,
+                3,
+                bitpacked.signed,
+            )
+            .with_word_alignment(bitpacked.word_aligned);
+            let mut page = vec![0_u8; 3];
+            page.extend_from_slice(&data);
+            let io = Arc::new(BufferScheduler::new(Bytes::from(page))) as Arc<dyn EncodingsIo>;
+
+            #[allow(clippy::single_range_in_vec_init)]
+            let ranges = [vec![0..1000], vec![1..2, 5..333, 998..1000], vec![7..7]];
+            for ranges in ranges {
+                let decoder = scheduler.schedule_ranges(&ranges, &io, 0).await.unwrap();
+                let expected = ranges
+                    .iter()
+                    .flat_map(|range| values[range.start as usize..range.end as usize].to_vec())
+                    .collect::<Vec<_>>();
+                let num_rows = expected.len() as u64;
+                for rows_to_skip in [0, num_rows / 3] {
+                    let buffers = decoder
+                        .decode(rows_to_skip, num_rows - rows_to_skip, &mut false)
+                        .unwrap();
+                    let actual =
+                        ScalarBuffer::<u32>::from(Buffer::from(buffers[0].clone().freeze()));
+                    assert_eq!(actual.as_ref(), &expected[rows_to_skip as usize..]);
+                }
+            }
+        }
+
+        let longs = vec![Arc::new(Int64Array::from(vec![1_i64 << 40])) as ArrayRef];
+        assert!(BitpackedArrayEncoder::new(42)
+            .with_word_alignment(true)
+            .encode(&longs, &mut 0)
+            .is_err());
+    }
+
+    #[allow(clippy::single_range_in_vec_init)]
+    #[tokio::test]
+    async fn test_strict_padding() {
+        // Decodes `ranges` of the page with a strict and a lenient scheduler
+        async fn decode(
+            scheduler: &dyn PageScheduler,
+            strict_scheduler: &dyn PageScheduler,
+            data: &[u8],
+            ranges: &[Range<u64>],
+        ) -> (Result<Vec<u32>>, Vec<u32>) {
+            let io = Arc::new(BufferScheduler::new(Bytes::copy_from_slice(data)))
+                as Arc<dyn EncodingsIo>;
+            let num_rows = ranges.iter().map(|range| range.end - range.start).sum();
+            let mut decoded = Vec::new();
+            for scheduler in [strict_scheduler, scheduler] {
+                let decoder = scheduler.schedule_ranges(ranges, &io, 0).await.unwrap();
+                decoded.push(decoder.decode(0, num_rows, &mut false).map(|buffers| {
+                    ScalarBuffer::<u32>::from(Buffer::from(buffers[0].clone().freeze())).to_vec()
+                }));
+            }
+            let lenient = decoded.pop().unwrap().unwrap();
+            (decoded.pop().unwrap(), lenient)
+        }
+
+        // 7 bit values leave 4 padding bits in each word
+        let values = (0..100_u32).map(|i| i % 128).collect::<Vec<_>>();
+        let arrays = vec![Arc::new(UInt32Array::from(values.clone())) as ArrayRef];
+        let EncodedArray { mut buffers, .. } = BitpackedArrayEncoder::new(7)
+            .with_word_alignment(true)
+            .encode(&arrays, &mut 0)
+            .unwrap();
+        let mut data = buffers.pop().unwrap().parts.remove(0).to_vec();
+        let scheduler = BitpackedScheduler::new(7, 32, 0, false).with_word_alignment(true);
+        let strict_scheduler = scheduler.with_strict(true);
+
+        let (strict, lenient) = decode(&scheduler, &strict_scheduler, &data, &[0..100]).await;
+        assert_eq!(strict.unwrap(), values);
+        assert_eq!(lenient, values);
+
+        // Set the highest bit of the 4th word (values 12..16)
+        data[15] |= 0x80;
+        let (strict, lenient) = decode(&scheduler, &strict_scheduler, &data, &[0..100]).await;
+        let err = strict.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::Encoding {
+                    source: EncodingError::CorruptMetadata { .. },
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("padding bits"));
+        assert_eq!(lenient, values);
+        // Reads that don't touch the word are fine
+        let (strict, _) = decode(&scheduler, &strict_scheduler, &data, &[0..12, 16..20]).await;
+        assert!(strict.is_ok());
+        let (strict, _) = decode(&scheduler, &strict_scheduler, &data, &[13..14]).await;
+        assert!(strict.is_err());
+
+        // 10 3-bit values are padded with 2 bits to 4 bytes
+        let values = (0..10_u32).map(|i| i % 8).collect::<Vec<_>>();
+        let arrays = vec![Arc::new(UInt32Array::from(values.clone())) as ArrayRef];
+        let EncodedArray {
+            mut buffers,
+            encoding,
+        } = BitpackedArrayEncoder::new(3)
+            .encode(&arrays, &mut 0)
+            .unwrap();
+        let mut data = buffers.pop().unwrap().parts.remove(0).to_vec();
+        let Some(pb::array_encoding::ArrayEncoding::Bitpacked(bitpacked)) = encoding.array_encoding
+        else {
+            panic!("Expected bitpacked encoding")
+        };
+        assert_eq!(bitpacked.num_values, 10);
+        let scheduler = BitpackedScheduler::new(3, 32, 0, false).with_num_values(10);
+        let strict_scheduler = scheduler.with_strict(true);
+
+        // Set the last bit of the page
+        data[3] |= 0x80;
+        let (strict, lenient) = decode(&scheduler, &strict_scheduler, &data, &[0..10]).await;
+        assert!(strict.unwrap_err().to_string().contains("padding bits"));
+        assert_eq!(lenient, values);
+        let (strict, _) = decode(&scheduler, &strict_scheduler, &data, &[2..9]).await;
+        assert!(strict.is_ok());
+        // The padding can't be told apart from the values if the page size is unknown
+        let strict_scheduler = strict_scheduler.with_num_values(0);
+        let (strict, _) = decode(&scheduler, &strict_scheduler, &data, &[0..10]).await;
+        assert!(strict.is_ok());
+
+        // Chunks of 10 3-bit values are padded with 2 bits to 4 bytes
+        let values = (0..25_u32).map(|i| i % 8).collect::<Vec<_>>();
+        let arrays = vec![Arc::new(UInt32Array::from(values.clone())) as ArrayRef];
+        let EncodedArray {
+            mut buffers,
+            encoding,
+        } = ChunkedBitpackedArrayEncoder::new(10)
+            .encode(&arrays, &mut 0)
+            .unwrap();
+        let mut data = buffers.pop().unwrap().parts.remove(0).to_vec();
+        let Some(pb::array_encoding::ArrayEncoding::ChunkedBitpacked(chunked)) =
+            encoding.array_encoding
+        else {
+            panic!("Expected chunked bitpacked encoding")
+        };
+        assert_eq!(chunked.chunk_bits_per_value, vec![3, 3, 3]);
+        assert_eq!(chunked.num_values, 25);
+        let scheduler =
+            ChunkedBitpackedScheduler::new(10, vec![3, 3, 3], 32, 0, false).with_num_values(25);
+        let strict_scheduler = scheduler.clone().with_strict(true);
+
+        let (strict, lenient) = decode(&scheduler, &strict_scheduler, &data, &[0..25]).await;
+        assert_eq!(strict.unwrap(), values);
+        assert_eq!(lenient, values);
+
+        // Set the last bit of the second chunk
+        data[7] |= 0x80;
+        let (strict, lenient) = decode(&scheduler, &strict_scheduler, &data, &[0..25]).await;
+        assert!(strict.is_err());
+        assert_eq!(lenient, values);
+        let (strict, _) = decode(&scheduler, &strict_scheduler, &data, &[10..15]).await;
+        assert!(strict.is_ok());
+
+        // The last chunk has 5 values, padded with 1 bit to 2 bytes
+        data[7] &= 0x7f;
+        data[9] |= 0x80;
+        let (strict, lenient) = decode(&scheduler, &strict_scheduler, &data, &[0..25]).await;
+        assert!(strict.is_err());
+        assert_eq!(lenient, values);
+        let (strict, _) = decode(&scheduler, &strict_scheduler, &data, &[20..24]).await;
+        assert!(strict.is_ok());
+    }
+
+    #[test]
+    fn test_bitpack_rejects_invalid_input() {
+        let floats = vec![Arc::new(Float32Array::from(vec![1.0])) as ArrayRef];
+        assert!(BitpackedArrayEncoder::new(4)
+            .encode(&floats, &mut 0)
+            .is_err());
+
+        let bytes = vec![Arc::new(UInt8Array::from(vec![1])) as ArrayRef];
+        assert!(BitpackedArrayEncoder::new(9)
+            .encode(&bytes, &mut 0)
+            .is_err());
+        assert!(BitpackedArrayEncoder::new(0)
+            .encode(&bytes, &mut 0)
+            .is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_forced_bitpacking() {
+        for encoding_override in [
+            EncodingOverride::ForceBitpack,
+            EncodingOverride::ForceChunkedBitpack,
+        ] {
+            for data_type in [
+                DataType::UInt8,
+                DataType::UInt16,
+                DataType::UInt32,
+                DataType::UInt64,
+                DataType::Int8,
+                DataType::Int16,
+                DataType::Int32,
+                DataType::Int64,
+            ] {
+                let field = Field::new("", data_type, true);
+                check_round_trip_encoding_random_with_override(field, encoding_override).await;
+            }
+        }
+    }
+}
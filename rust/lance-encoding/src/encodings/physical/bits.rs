@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Utilities for reading and writing values of arbitrary bit widths
+//!
+//! Values are laid out in little-endian bit order.  The first value starts at the
+//! least significant bit of the first byte and a value that does not fit in the rest
+//! of a byte continues in the least significant bits of the next byte.
+
+/// Reads values of arbitrary bit widths (up to 64 bits) from a buffer
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    position: u64,
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader positioned at the first bit of `data`
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    /// The position (in bits) of the next value that will be read
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Moves the cursor to the given bit position
+    pub fn seek(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Reads the next `num_bits` bits as an unsigned value and advances the cursor
+    ///
+    /// Panics if `num_bits` is greater than 64 or if the read goes past the end of the data
+    pub fn read(&mut self, num_bits: u64) -> u64 {
+        debug_assert!(num_bits <= 64);
+        let mut value = 0_u64;
+        let mut bits_read = 0;
+        while bits_read < num_bits {
+            let pos = self.position + bits_read;
+            let bit_in_byte = pos % 8;
+            let bits_here = (8 - bit_in_byte).min(num_bits - bits_read);
+            let mask = (1_u64 << bits_here) - 1;
+            let chunk = (self.data[(pos / 8) as usize] as u64 >> bit_in_byte) & mask;
+            value |= chunk << bits_read;
+            bits_read += bits_here;
+        }
+        self.position += num_bits;
+        value
+    }
+}
+
+/// Writes values of arbitrary bit widths (up to 64 bits) into a growable buffer
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    data: Vec<u8>,
+    position: u64,
+}
+
+impl BitWriter {
+    /// Creates a writer with enough space reserved for `num_bits` bits
+    pub fn with_capacity(num_bits: u64) -> Self {
+        Self {
+            data: Vec::with_capacity(num_bits.div_ceil(8) as usize),
+            position: 0,
+        }
+    }
+
+    /// The number of bits written so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Writes the lowest `num_bits` bits of `value` and advances the cursor
+    ///
+    /// Any higher bits of `value` are ignored.  Panics (in debug builds) if `num_bits`
+    /// is greater than 64.
+    pub fn write(&mut self, value: u64, num_bits: u64) {
+        debug_assert!(num_bits <= 64);
+        self.data
+            .resize((self.position + num_bits).div_ceil(8) as usize, 0);
+        let mut bits_written = 0;
+        while bits_written < num_bits {
+            let pos = self.position + bits_written;
+            let bit_in_byte = pos % 8;
+            let bits_here = (8 - bit_in_byte).min(num_bits - bits_written);
+            let mask = (1_u64 << bits_here) - 1;
+            let chunk = (value >> bits_written) & mask;
+            self.data[(pos / 8) as usize] |= (chunk << bit_in_byte) as u8;
+            bits_written += bits_here;
+        }
+        self.position += num_bits;
+    }
+
+    /// Returns the written bytes, any unused bits in the last byte are zero
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitReader, BitWriter};
+
+    #[test]
+    fn test_round_trip_widths() {
+        for num_bits in 1..=64 {
+            let mask = if num_bits == 64 {
+                u64::MAX
+            } else {
+                (1 << num_bits) - 1
+            };
+            let values = (0..100_u64)
+                .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) & mask)
+                .collect::<Vec<_>>();
+
+            let mut writer = BitWriter::with_capacity(100 * num_bits);
+            for value in &values {
+                writer.write(*value, num_bits);
+            }
+            assert_eq!(writer.position(), 100 * num_bits);
+            let data = writer.finish();
+            assert_eq!(data.len() as u64, (100 * num_bits).div_ceil(8));
+
+            let mut reader = BitReader::new(&data);
+            for value in &values {
+                assert_eq!(reader.read(num_bits), *value);
+            }
+            assert_eq!(reader.position(), 100 * num_bits);
+        }
+    }
+
+    #[test]
+    fn test_cross_word() {
+        // A 64-bit value starting at bit 60 straddles two 64-bit words
+        let mut writer = BitWriter::default();
+        writer.write(0b1010, 4);
+        writer.write(u64::MAX, 56);
+        writer.write(0x0123_4567_89AB_CDEF, 64);
+        writer.write(0b101, 3);
+        let data = writer.finish();
+        assert_eq!(data.len(), 16);
+        assert_eq!(data[0], 0xFA);
+
+        let mut reader = BitReader::new(&data);
+        reader.seek(60);
+        assert_eq!(reader.read(64), 0x0123_4567_89AB_CDEF);
+        assert_eq!(reader.read(3), 0b101);
+        reader.seek(0);
+        assert_eq!(reader.read(4), 0b1010);
+        assert_eq!(reader.read(12), 0xFFF);
+    }
+
+    #[test]
+    fn test_write_ignores_high_bits() {
+        let mut writer = BitWriter::default();
+        writer.write(u64::MAX, 3);
+        writer.write(0, 5);
+        assert_eq!(writer.finish(), vec![0b0000_0111]);
+    }
+}
@@ -0,0 +1,477 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::borrow::Cow;
+use std::f64::consts::LN_2;
+
+use arrow_array::{cast::AsArray, Array, ArrayRef};
+use arrow_buffer::Buffer;
+use arrow_schema::DataType;
+use bytes::Bytes;
+use snafu::{location, Location};
+
+use lance_arrow::DataTypeExt;
+use lance_core::{Error, Result};
+
+use crate::{
+    encoder::{ArrayEncoder, EncodedArray, EncodedArrayBuffer},
+    format::pb,
+};
+
+/// The default false positive rate of the Bloom filters built by [`BloomFilterEncoder`]
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+// Filters never have fewer bits than this, even for tiny (or empty) pages
+const MIN_FILTER_BITS: u64 = 64;
+const MAX_NUM_HASHES: u32 = 30;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Returns true if a Bloom filter can be built over values of the data type
+pub fn supports_bloom_filter(data_type: &DataType) -> bool {
+    data_type.is_primitive()
+        || matches!(
+            data_type,
+            DataType::FixedSizeBinary(_)
+                | DataType::Utf8
+                | DataType::LargeUtf8
+                | DataType::Binary
+                | DataType::LargeBinary
+        )
+}
+
+/// The bytes of a fixed-width value as they are added to a Bloom filter
+///
+/// Floats that compare equal must hash equally, so negative zero is stored as zero and
+/// every NaN as the canonical NaN.  Other values are returned as they are.  `value` holds
+/// the little-endian bytes of a single value of type `data_type`.
+pub fn canonical_value<'a>(data_type: &DataType, value: &'a [u8]) -> Cow<'a, [u8]> {
+    match data_type {
+        DataType::Float16 => {
+            let bits = u16::from_le_bytes([value[0], value[1]]);
+            let canonical = if bits & 0x7FFF == 0 {
+                0
+            } else if bits & 0x7C00 == 0x7C00 && bits & 0x03FF != 0 {
+                0x7E00
+            } else {
+                return Cow::Borrowed(value);
+            };
+            Cow::Owned(u16::to_le_bytes(canonical).to_vec())
+        }
+        DataType::Float32 => {
+            let float = f32::from_le_bytes(value.try_into().unwrap());
+            if float == 0.0 {
+                Cow::Owned(0_f32.to_le_bytes().to_vec())
+            } else if float.is_nan() {
+                Cow::Owned(f32::NAN.to_le_bytes().to_vec())
+            } else {
+                Cow::Borrowed(value)
+            }
+        }
+        DataType::Float64 => {
+            let float = f64::from_le_bytes(value.try_into().unwrap());
+            if float == 0.0 {
+                Cow::Owned(0_f64.to_le_bytes().to_vec())
+            } else if float.is_nan() {
+                Cow::Owned(f64::NAN.to_le_bytes().to_vec())
+            } else {
+                Cow::Borrowed(value)
+            }
+        }
+        _ => Cow::Borrowed(value),
+    }
+}
+
+// Calls `f` with the (canonical) bytes of every valid value in the array
+fn for_each_valid_value(arr: &dyn Array, mut f: impl FnMut(&[u8])) -> Result<()> {
+    match arr.data_type() {
+        DataType::Utf8 => arr
+            .as_string::<i32>()
+            .iter()
+            .flatten()
+            .for_each(|value| f(value.as_bytes())),
+        DataType::LargeUtf8 => arr
+            .as_string::<i64>()
+            .iter()
+            .flatten()
+            .for_each(|value| f(value.as_bytes())),
+        DataType::Binary => arr.as_binary::<i32>().iter().flatten().for_each(f),
+        DataType::LargeBinary => arr.as_binary::<i64>().iter().flatten().for_each(f),
+        data_type if supports_bloom_filter(data_type) => {
+            let byte_width = data_type.byte_width();
+            let data = arr.to_data();
+            let start = data.offset() * byte_width;
+            let end = start + data.len() * byte_width;
+            for (idx, value) in data.buffers()[0][start..end]
+                .chunks_exact(byte_width)
+                .enumerate()
+            {
+                if arr.is_valid(idx) {
+                    f(&canonical_value(data_type, value));
+                }
+            }
+        }
+        data_type => {
+            return Err(Error::unsupported_type(
+                data_type,
+                "Bloom filters can only be built over primitive, binary and string values",
+                location!(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+// The two hashes used to derive the bit positions of a value
+fn hash_value(value: &[u8]) -> (u64, u64) {
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in value {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    // FNV mixes the last few bytes poorly so finish with the splitmix64 finalizer
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^= hash >> 31;
+    (hash & 0xFFFF_FFFF, hash >> 32)
+}
+
+// The bits of a filter that are set for a value (double hashing)
+fn bit_positions(value: &[u8], num_bits: u64, num_hashes: u32) -> impl Iterator<Item = u64> {
+    let (h1, h2) = hash_value(value);
+    (0..num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+}
+
+/// A Bloom filter over the values of a page
+///
+/// A filter answers whether a page might contain a value.  There are no false negatives,
+/// if [`Self::might_contain`] returns false then the page definitely does not contain the
+/// value.  Values are the little-endian bytes of fixed-width values (e.g.
+/// `5_i32.to_le_bytes()`), passed through [`canonical_value`], or the raw bytes of binary
+/// and string values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for `num_values` values at the given false
+    /// positive rate
+    pub fn with_capacity(num_values: u64, false_positive_rate: f64) -> Result<Self> {
+        if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            return Err(Error::invalid_input(
+                format!(
+                    "Invalid Bloom filter false positive rate {}, it must be between 0 and 1",
+                    false_positive_rate
+                ),
+                location!(),
+            ));
+        }
+        let num_values = num_values.max(1) as f64;
+        let num_bits = (-num_values * false_positive_rate.ln() / (LN_2 * LN_2)).ceil() as u64;
+        let num_bits = num_bits.max(MIN_FILTER_BITS).next_multiple_of(8);
+        let num_hashes =
+            ((num_bits as f64 / num_values * LN_2).round() as u32).clamp(1, MAX_NUM_HASHES);
+        Ok(Self {
+            bits: vec![0; (num_bits / 8) as usize],
+            num_bits,
+            num_hashes,
+        })
+    }
+
+    /// Loads a filter written by [`BloomFilterEncoder`]
+    ///
+    /// `data` is the contents of the filter's buffer
+    pub fn try_from_pb(filter: &pb::BloomFilter, data: Bytes) -> Result<Self> {
+        if filter.num_bits == 0
+            || filter.num_hashes == 0
+            || data.len() as u64 != filter.num_bits.div_ceil(8)
+        {
+            return Err(Error::corrupt_metadata(
+                format!(
+                    "invalid Bloom filter with {} bits and {} hashes in a buffer of {} bytes",
+                    filter.num_bits,
+                    filter.num_hashes,
+                    data.len()
+                ),
+                location!(),
+            ));
+        }
+        Ok(Self {
+            bits: data.to_vec(),
+            num_bits: filter.num_bits,
+            num_hashes: filter.num_hashes,
+        })
+    }
+
+    /// The number of bits in the filter
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    /// The number of bits set for each value
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Adds a value to the filter
+    pub fn insert(&mut self, value: &[u8]) {
+        for position in bit_positions(value, self.num_bits, self.num_hashes) {
+            self.bits[(position / 8) as usize] |= 1 << (position % 8);
+        }
+    }
+
+    /// Returns false if the value was definitely not added to the filter
+    pub fn might_contain(&self, value: &[u8]) -> bool {
+        bit_positions(value, self.num_bits, self.num_hashes)
+            .all(|position| self.bits[(position / 8) as usize] & (1 << (position % 8)) != 0)
+    }
+}
+
+/// Returns the Bloom filter of a page, if the page was encoded with one
+pub fn page_bloom_filter(encoding: &pb::ArrayEncoding) -> Option<&pb::BloomFilter> {
+    match encoding.array_encoding.as_ref() {
+        Some(pb::array_encoding::ArrayEncoding::BloomFiltered(bloom_filtered)) => {
+            bloom_filtered.filter.as_ref()
+        }
+        _ => None,
+    }
+}
+
+/// Encodes values with another encoder and adds a Bloom filter over the valid values
+///
+/// The filter is stored in an extra page buffer and is ignored when decoding.  Readers
+/// looking for particular values (e.g. point lookups or anti-joins) can load it with
+/// [`page_bloom_filter`] and [`BloomFilter::try_from_pb`] to skip pages that cannot
+/// contain the values.
+#[derive(Debug)]
+pub struct BloomFilterEncoder {
+    values_encoder: Box<dyn ArrayEncoder>,
+    false_positive_rate: f64,
+}
+
+impl BloomFilterEncoder {
+    pub fn new(values_encoder: Box<dyn ArrayEncoder>) -> Self {
+        Self {
+            values_encoder,
+            false_positive_rate: DEFAULT_FALSE_POSITIVE_RATE,
+        }
+    }
+
+    /// Sets the target false positive rate of the filter
+    ///
+    /// Lower rates need larger filters, a 1% rate needs about 10 bits per value.  The
+    /// rate must be between 0 and 1 (exclusive).
+    pub fn with_false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = false_positive_rate;
+        self
+    }
+}
+
+impl ArrayEncoder for BloomFilterEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let num_values = arrays
+            .iter()
+            .map(|arr| (arr.len() - arr.null_count()) as u64)
+            .sum::<u64>();
+        let mut filter = BloomFilter::with_capacity(num_values, self.false_positive_rate)?;
+        for arr in arrays {
+            for_each_valid_value(arr.as_ref(), |value| filter.insert(value))?;
+        }
+
+        let encoded_values = self.values_encoder.encode(arrays, buffer_index)?;
+
+        let index = *buffer_index;
+        *buffer_index += 1;
+
+        let mut buffers = encoded_values.buffers;
+        buffers.push(EncodedArrayBuffer {
+            parts: vec![Buffer::from_vec(filter.bits)],
+            index,
+        });
+
+        Ok(EncodedArray {
+            buffers,
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::BloomFiltered(Box::new(
+                    pb::BloomFiltered {
+                        values: Some(Box::new(encoded_values.encoding)),
+                        filter: Some(pb::BloomFilter {
+                            num_bits: filter.num_bits,
+                            num_hashes: filter.num_hashes,
+                            buffer: Some(pb::Buffer {
+                                buffer_index: index,
+                                buffer_type: pb::buffer::BufferType::Page as i32,
+                            }),
+                        }),
+                    },
+                ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        Array, ArrayRef, Float32Array, Float64Array, Int64Array, ListArray, StringArray,
+    };
+    use arrow_schema::{DataType, Field};
+
+    use crate::{
+        decoder::decode_page,
+        encoder::ArrayEncoder,
+        encodings::physical::{
+            basic::BasicEncoder,
+            value::{CompressionScheme, ValueEncoder},
+        },
+        testing::InMemoryPage,
+    };
+
+    use super::{
+        canonical_value, for_each_valid_value, page_bloom_filter, BloomFilter, BloomFilterEncoder,
+    };
+
+    // Encodes the arrays and returns the decoded page and the page's Bloom filter
+    fn encode(arrays: &[ArrayRef], false_positive_rate: f64) -> (ArrayRef, BloomFilter) {
+        let data_type = arrays[0].data_type();
+        let values_encoder = Box::new(BasicEncoder::new(Box::new(
+            ValueEncoder::try_new(data_type, CompressionScheme::None).unwrap(),
+        )));
+        let encoded = BloomFilterEncoder::new(values_encoder)
+            .with_false_positive_rate(false_positive_rate)
+            .encode(arrays, &mut 0)
+            .unwrap();
+        let buffers = InMemoryPage::new(&encoded).buffers();
+
+        let filter_pb = page_bloom_filter(&encoded.encoding).unwrap();
+        let filter_buffer = filter_pb.buffer.as_ref().unwrap().buffer_index as usize;
+        let filter = BloomFilter::try_from_pb(filter_pb, buffers[filter_buffer].clone()).unwrap();
+
+        let num_rows = arrays.iter().map(|arr| arr.len() as u64).sum::<u64>();
+        let decoded = decode_page(&encoded.encoding, &buffers, data_type, 0..num_rows).unwrap();
+        (decoded, filter)
+    }
+
+    #[test]
+    fn test_bloom_filter_integers() {
+        // Even values are in the page, odd values are not
+        let values = (0..20_000).map(|i| i * 2).collect::<Vec<i64>>();
+        let arrays = vec![
+            Arc::new(Int64Array::from(values[..5_000].to_vec())) as ArrayRef,
+            Arc::new(Int64Array::from(values[5_000..].to_vec())) as ArrayRef,
+        ];
+        for false_positive_rate in [0.01, 0.1] {
+            let (decoded, filter) = encode(&arrays, false_positive_rate);
+            assert_eq!(
+                decoded.as_ref(),
+                &Int64Array::from(values.clone()) as &dyn Array
+            );
+
+            for value in &values {
+                assert!(filter.might_contain(&value.to_le_bytes()));
+            }
+            let num_false_positives = values
+                .iter()
+                .filter(|value| filter.might_contain(&(*value + 1).to_le_bytes()))
+                .count();
+            let measured_rate = num_false_positives as f64 / values.len() as f64;
+            assert!(
+                measured_rate < 2.0 * false_positive_rate,
+                "measured {} for a target of {}",
+                measured_rate,
+                false_positive_rate
+            );
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter_nulls() {
+        // Null slots hold garbage values that must not be added to the filter
+        let values = (0..1000)
+            .map(|i| if i % 7 == 0 { None } else { Some(i) })
+            .collect::<Vec<Option<i64>>>();
+        let arr = Arc::new(Int64Array::from(values.clone())) as ArrayRef;
+        let arrays = vec![arr.slice(0, 300), arr.slice(300, 700)];
+        let (decoded, filter) = encode(&arrays, 0.01);
+        assert_eq!(decoded.as_ref(), arr.as_ref());
+        for value in values.iter().flatten() {
+            assert!(filter.might_contain(&value.to_le_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter_strings() {
+        let strings = (0..1000)
+            .map(|i| (i % 7 != 0).then(|| format!("value-{}", i)))
+            .collect::<Vec<_>>();
+        let arr = Arc::new(StringArray::from(strings.clone())) as ArrayRef;
+
+        let mut filter = BloomFilter::with_capacity(1000, 0.01).unwrap();
+        for arr in [arr.slice(0, 300), arr.slice(300, 700)] {
+            for_each_valid_value(arr.as_ref(), |value| filter.insert(value)).unwrap();
+        }
+        for value in strings.iter().flatten() {
+            assert!(filter.might_contain(value.as_bytes()));
+        }
+        let num_false_positives = (1000..11_000)
+            .filter(|i| filter.might_contain(format!("value-{}", i).as_bytes()))
+            .count();
+        assert!(num_false_positives < 200, "{}", num_false_positives);
+    }
+
+    #[test]
+    fn test_bloom_filter_floats() {
+        // Floats that compare equal are found, whatever their bits
+        let arr = Arc::new(Float64Array::from(vec![
+            -0.0,
+            f64::from_bits(0x7FF8_0000_0000_0001),
+            1.5,
+        ])) as ArrayRef;
+        let (_, filter) = encode(&[arr], 0.01);
+        for value in [0.0, -0.0, f64::NAN, -f64::NAN, 1.5] {
+            let bytes = value.to_le_bytes();
+            assert!(filter.might_contain(&canonical_value(&DataType::Float64, &bytes)));
+        }
+
+        let arr = Arc::new(Float32Array::from(vec![0.0, f32::NAN])) as ArrayRef;
+        let (_, filter) = encode(&[arr], 0.01);
+        for value in [-0.0, f32::from_bits(0xFFC0_0001)] {
+            let bytes = value.to_le_bytes();
+            assert!(filter.might_contain(&canonical_value(&DataType::Float32, &bytes)));
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter_invalid() {
+        assert!(BloomFilter::with_capacity(10, 0.0).is_err());
+        assert!(BloomFilter::with_capacity(10, 1.0).is_err());
+        assert!(BloomFilter::with_capacity(10, f64::NAN).is_err());
+        // Tiny pages still get a usable filter
+        let filter = BloomFilter::with_capacity(0, 0.01).unwrap();
+        assert!(filter.num_bits() >= 64 && filter.num_hashes() >= 1);
+
+        let floats = vec![Arc::new(Float32Array::from(vec![1.0])) as ArrayRef];
+        let encoder = BloomFilterEncoder::new(Box::new(BasicEncoder::new(Box::new(
+            ValueEncoder::try_new(&DataType::Float32, CompressionScheme::None).unwrap(),
+        ))));
+        assert!(encoder
+            .with_false_positive_rate(2.0)
+            .encode(&floats, &mut 0)
+            .is_err());
+
+        let list = Arc::new(ListArray::new_null(
+            Arc::new(Field::new("item", DataType::Int32, true)),
+            1,
+        )) as ArrayRef;
+        assert!(for_each_valid_value(list.as_ref(), |_| {}).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use arrow_array::{cast::AsArray, Array, ArrayRef};
+use arrow_buffer::{BooleanBufferBuilder, Buffer};
+use arrow_schema::DataType;
+use bytes::BytesMut;
+use futures::{future::BoxFuture, FutureExt};
+use log::trace;
+use snafu::{location, Location};
+
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encoder::{ArrayEncoder, EncodedArray, EncodedArrayBuffer},
+    format::pb,
+    EncodingsIo,
+};
+
+/// The runs of equal values in some booleans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BooleanRuns {
+    /// The value of the first run, the runs alternate between true and false
+    pub first_value: bool,
+    /// The length of each run, every run is at most `u32::MAX` long
+    ///
+    /// Longer runs are split by zero-length runs of the other value.
+    pub run_lengths: Vec<u32>,
+    /// The number of values
+    pub num_values: u64,
+}
+
+impl BooleanRuns {
+    /// Collects the runs of the given boolean arrays, as if they were concatenated
+    ///
+    /// Only the values are considered, nulls are handled by the validity encoding
+    pub fn from_arrays(arrays: &[ArrayRef]) -> Self {
+        let mut runs = Self {
+            first_value: false,
+            run_lengths: Vec::new(),
+            num_values: 0,
+        };
+        for arr in arrays {
+            let values = arr.as_boolean().values();
+            let mut pos = 0;
+            for (start, end) in values.set_slices() {
+                runs.push(false, (start - pos) as u64);
+                runs.push(true, (end - start) as u64);
+                pos = end;
+            }
+            runs.push(false, (values.len() - pos) as u64);
+        }
+        runs
+    }
+
+    fn last_value(&self) -> bool {
+        self.first_value ^ (self.run_lengths.len() % 2 == 0)
+    }
+
+    fn push(&mut self, value: bool, len: u64) {
+        if len == 0 {
+            return;
+        }
+        self.num_values += len;
+        if self.run_lengths.is_empty() {
+            self.first_value = value;
+        } else if self.last_value() == value {
+            // Extend the last run
+            let last = self.run_lengths.pop().unwrap() as u64;
+            self.push_lengths(last + len);
+            return;
+        }
+        self.push_lengths(len);
+    }
+
+    // Pushes a run of `len` values, which may need to be split
+    fn push_lengths(&mut self, mut len: u64) {
+        while len > u32::MAX as u64 {
+            self.run_lengths.push(u32::MAX);
+            self.run_lengths.push(0);
+            len -= u32::MAX as u64;
+        }
+        self.run_lengths.push(len as u32);
+    }
+
+    /// The size of the run lengths buffer in bytes
+    pub fn encoded_size(&self) -> u64 {
+        4 * self.run_lengths.len() as u64
+    }
+
+    /// Returns true if the runs are long enough that the run lengths are at most half the
+    /// size of a bitmap of the values
+    pub fn is_worth_encoding(&self) -> bool {
+        self.num_values > 0 && 2 * self.encoded_size() <= self.num_values.div_ceil(8)
+    }
+
+    /// Encodes the runs into a single buffer at `buffer_index`
+    pub fn encode(&self, buffer_index: &mut u32) -> EncodedArray {
+        let index = *buffer_index;
+        *buffer_index += 1;
+        let data = self
+            .run_lengths
+            .iter()
+            .flat_map(|len| len.to_le_bytes())
+            .collect::<Vec<_>>();
+        EncodedArray {
+            buffers: vec![EncodedArrayBuffer {
+                parts: vec![Buffer::from_vec(data)],
+                index,
+            }],
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::BooleanRle(
+                    pb::BooleanRle {
+                        num_values: self.num_values,
+                        first_value: self.first_value,
+                        buffer: Some(pb::Buffer {
+                            buffer_index: index,
+                            buffer_type: pb::buffer::BufferType::Page as i32,
+                        }),
+                    },
+                )),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        }
+    }
+}
+
+/// Encodes booleans as the lengths of the runs of equal values
+///
+/// This is much smaller than a bitmap when the values are mostly true or mostly false in
+/// long stretches.  [`crate::encodings::physical::value::ValueEncoder`] uses this instead
+/// of a bitmap when the runs are long.
+#[derive(Debug, Default)]
+pub struct BooleanRleEncoder {}
+
+impl BooleanRleEncoder {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ArrayEncoder for BooleanRleEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        if *data_type != DataType::Boolean {
+            return Err(Error::unsupported_type(
+                data_type,
+                "run-length encoding is only supported for booleans",
+                location!(),
+            ));
+        }
+        Ok(BooleanRuns::from_arrays(arrays).encode(buffer_index))
+    }
+}
+
+/// Schedules reads of a page of run-length encoded booleans
+///
+/// The run lengths are small and so the entire buffer is read for any range.
+#[derive(Debug, Clone, Copy)]
+pub struct BooleanRleScheduler {
+    buffer_offset: u64,
+    buffer_size: u64,
+    num_values: u64,
+    first_value: bool,
+}
+
+impl BooleanRleScheduler {
+    pub fn new(buffer_offset: u64, buffer_size: u64, num_values: u64, first_value: bool) -> Self {
+        Self {
+            buffer_offset,
+            buffer_size,
+            num_values,
+            first_value,
+        }
+    }
+}
+
+impl PageScheduler for BooleanRleScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let ranges = ranges.to_vec();
+        let num_values = self.num_values;
+        let first_value = self.first_value;
+        if let Some(range) = ranges.iter().find(|range| range.end > num_values) {
+            return std::future::ready(Err(Error::invalid_input(
+                format!(
+                    "Cannot read rows {:?} of a boolean page with {} rows",
+                    range, num_values
+                ),
+                location!(),
+            )))
+            .boxed();
+        }
+        trace!(
+            "Scheduling I/O for {} bytes of boolean run lengths",
+            self.buffer_size
+        );
+        let bytes = scheduler.submit_request(
+            vec![self.buffer_offset..self.buffer_offset + self.buffer_size],
+            top_level_row,
+        );
+        async move {
+            let bytes = bytes.await?;
+            let mut run_ends = Vec::with_capacity(bytes[0].len() / 4);
+            let mut end = 0;
+            for len in bytes[0].chunks_exact(4) {
+                end += u32::from_le_bytes(len.try_into().unwrap()) as u64;
+                run_ends.push(end);
+            }
+            if end != num_values || bytes[0].len() % 4 != 0 {
+                return Err(Error::corrupt_metadata(
+                    format!(
+                        "The runs of a boolean page have {} values but the page has {} values",
+                        end, num_values
+                    ),
+                    location!(),
+                ));
+            }
+            Ok(Box::new(BooleanRleDecoder {
+                run_ends,
+                first_value,
+                ranges,
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, _ranges: &[Range<u64>]) -> DecodeCost {
+        DecodeCost::new(self.buffer_size, DecodeCpuClass::Unpack)
+    }
+}
+
+struct BooleanRleDecoder {
+    // The (exclusive) end of each run
+    run_ends: Vec<u64>,
+    first_value: bool,
+    // The scheduled ranges, the rows to decode are relative to these
+    ranges: Vec<Range<u64>>,
+}
+
+impl BooleanRleDecoder {
+    // Appends the values of `range` (rows of the page) to `bits`
+    fn append_range(&self, range: Range<u64>, bits: &mut BooleanBufferBuilder) {
+        // Zero-length runs end where the run before them ends and so are skipped
+        let mut run = self.run_ends.partition_point(|end| *end <= range.start);
+        let mut pos = range.start;
+        while pos < range.end {
+            let run_end = self.run_ends[run].min(range.end);
+            bits.append_n((run_end - pos) as usize, self.first_value ^ (run % 2 == 1));
+            pos = run_end;
+            run += 1;
+        }
+    }
+}
+
+impl PrimitivePageDecoder for BooleanRleDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut bits = BooleanBufferBuilder::new(num_rows as usize);
+        let mut rows_to_skip = rows_to_skip;
+        let mut rows_remaining = num_rows;
+        for range in &self.ranges {
+            if rows_remaining == 0 {
+                break;
+            }
+            let range_len = range.end - range.start;
+            if range_len <= rows_to_skip {
+                rows_to_skip -= range_len;
+                continue;
+            }
+            let start = range.start + rows_to_skip;
+            let end = (start + rows_remaining).min(range.end);
+            self.append_range(start..end, &mut bits);
+            rows_remaining -= end - start;
+            rows_to_skip = 0;
+        }
+        if rows_remaining > 0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot decode {} rows after skipping {} rows of the scheduled boolean ranges",
+                    num_rows, rows_to_skip
+                ),
+                location!(),
+            ));
+        }
+        Ok(vec![BytesMut::from(bits.finish().inner().as_slice())])
+    }
+
+    fn can_skip(&self) -> bool {
+        true
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, ArrayRef, BooleanArray};
+    use arrow_schema::{DataType, Field};
+
+    use crate::{
+        encoder::{ArrayEncoder, EncodingOverride},
+        encodings::physical::value::{CompressionScheme, ValueEncoder},
+        format::pb,
+        testing::{
+            check_round_trip_encoding_of_data, check_round_trip_encoding_random_with_override,
+            InMemoryPage, TestCases,
+        },
+    };
+
+    use super::{BooleanRleEncoder, BooleanRuns};
+
+    // Booleans with runs of `run_length` values, starting with `first_value`
+    fn runs_of(num_values: usize, run_length: usize, first_value: bool) -> BooleanArray {
+        BooleanArray::from_iter(
+            (0..num_values).map(|i| Some(first_value ^ ((i / run_length) % 2 == 1))),
+        )
+    }
+
+    fn encoded_size(encoder: &dyn ArrayEncoder, arrays: &[ArrayRef]) -> (usize, pb::ArrayEncoding) {
+        let encoded = encoder.encode(arrays, &mut 0).unwrap();
+        let size = encoded
+            .buffers
+            .iter()
+            .flat_map(|buffer| &buffer.parts)
+            .map(|part| part.len())
+            .sum();
+        (size, encoded.encoding)
+    }
+
+    #[test]
+    fn test_boolean_runs() {
+        let arrays = vec![
+            Arc::new(BooleanArray::from(vec![true, true, false])) as ArrayRef,
+            // A run continues across arrays, and arrays may be slices
+            Arc::new(BooleanArray::from(vec![true, false, false, true, true]).slice(1, 3)),
+            Arc::new(BooleanArray::from(Vec::<bool>::new())),
+        ];
+        let runs = BooleanRuns::from_arrays(&arrays);
+        assert_eq!(
+            runs,
+            BooleanRuns {
+                first_value: true,
+                run_lengths: vec![2, 3, 1],
+                num_values: 6,
+            }
+        );
+
+        // Runs that don't fit in a u32 are split
+        let mut runs = BooleanRuns::from_arrays(&[]);
+        runs.push(false, 5);
+        runs.push(true, u32::MAX as u64 + 10);
+        assert!(!runs.first_value);
+        assert_eq!(runs.run_lengths, vec![5, u32::MAX, 0, 10]);
+    }
+
+    #[test]
+    fn test_long_runs_are_smaller() {
+        let arrays = vec![Arc::new(runs_of(100_000, 5000, true)) as ArrayRef];
+        let value_encoder =
+            ValueEncoder::try_new(&DataType::Boolean, CompressionScheme::None).unwrap();
+        let (rle_size, encoding) = encoded_size(&value_encoder, &arrays);
+        assert!(matches!(
+            encoding.array_encoding,
+            Some(pb::array_encoding::ArrayEncoding::BooleanRle(_))
+        ));
+        // 20 runs of 4 bytes instead of a 12.5KB bitmap
+        assert_eq!(rle_size, 80);
+        let bitmap_size = 100_000 / 8;
+        assert!(rle_size * 100 < bitmap_size);
+
+        // Short runs are still stored as a bitmap
+        let arrays = vec![Arc::new(runs_of(100_000, 3, false)) as ArrayRef];
+        let (size, encoding) = encoded_size(&value_encoder, &arrays);
+        assert!(matches!(
+            encoding.array_encoding,
+            Some(pb::array_encoding::ArrayEncoding::Flat(_))
+        ));
+        assert_eq!(size, bitmap_size);
+        let (rle_size, _) = encoded_size(&BooleanRleEncoder::new(), &arrays);
+        assert!(rle_size > size);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_ranges() {
+        let values = runs_of(1000, 70, false);
+        let arrays = vec![
+            Arc::new(values.slice(0, 500)) as ArrayRef,
+            Arc::new(values.slice(500, 500)) as ArrayRef,
+        ];
+        let encoded = BooleanRleEncoder::new().encode(&arrays, &mut 0).unwrap();
+        let page = InMemoryPage::new(&encoded);
+        let scheduler = page.scheduler(&DataType::Boolean, &[]).unwrap();
+        let io = page.io();
+
+        let ranges = vec![3..75, 139..141, 600..1000];
+        let decoder = scheduler.schedule_ranges(&ranges, &io, 0).await.unwrap();
+        let expected = ranges
+            .iter()
+            .flat_map(|range| (range.start..range.end).map(|i| values.value(i as usize)))
+            .collect::<Vec<_>>();
+        // Decode the rows in two parts
+        for (rows_to_skip, num_rows) in [(0, 100_u64), (100, 374)] {
+            let buffers = decoder.decode(rows_to_skip, num_rows, &mut false).unwrap();
+            let decoded = BooleanArray::new(
+                arrow_buffer::BooleanBuffer::new(
+                    buffers[0].clone().freeze().into(),
+                    0,
+                    num_rows as usize,
+                ),
+                None,
+            );
+            assert_eq!(
+                decoded,
+                BooleanArray::from(
+                    expected[rows_to_skip as usize..(rows_to_skip + num_rows) as usize].to_vec()
+                )
+            );
+        }
+
+        // Rows beyond the page are an error
+        #[allow(clippy::single_range_in_vec_init)]
+        let result = scheduler.schedule_ranges(&[900..1001], &io, 0).await;
+        assert!(result.is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_round_trip_boolean_runs() {
+        let test_cases = TestCases::default()
+            .with_range(0..500)
+            .with_range(4000..8000)
+            .with_indices(vec![0, 1, 2999, 3000, 9999]);
+        let nullable = runs_of(10_000, 1000, true)
+            .iter()
+            .enumerate()
+            .map(|(i, v)| if (2000..2100).contains(&i) { None } else { v })
+            .collect::<BooleanArray>();
+        check_round_trip_encoding_of_data(
+            vec![Arc::new(runs_of(10_000, 1000, true)), Arc::new(nullable)],
+            &test_cases,
+        )
+        .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_forced_rle() {
+        let field = Field::new("", DataType::Boolean, true);
+        check_round_trip_encoding_random_with_override(field, EncodingOverride::ForceRle).await;
+    }
+}
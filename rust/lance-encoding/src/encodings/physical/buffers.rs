@@ -2,38 +2,172 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 use arrow_array::{cast::AsArray, ArrayRef};
-use std::io::{Cursor, Write};
+use std::io::{Read, Write};
 
 use arrow_buffer::{BooleanBufferBuilder, Buffer};
-use arrow_schema::DataType;
-use lance_core::Result;
+use arrow_schema::{DataType, IntervalUnit};
+use lance_core::{error::EncodingError, Error, Result};
+use snafu::{location, Location};
 
 use crate::encoder::{BufferEncoder, EncodedBuffer};
 
 #[derive(Debug, Default)]
-pub struct FlatBufferEncoder {}
+pub struct FlatBufferEncoder {
+    little_endian: bool,
+}
+
+impl FlatBufferEncoder {
+    /// Creates an encoder that stores values in little-endian byte order on every host
+    ///
+    /// By default values are stored in the host's byte order.  With this option files
+    /// written on big-endian hosts can be read anywhere.  On little-endian hosts the
+    /// values are stored as they are, only big-endian hosts pay for swapping them.
+    pub fn little_endian() -> Self {
+        Self {
+            little_endian: true,
+        }
+    }
+}
 
 impl BufferEncoder for FlatBufferEncoder {
     fn encode(&self, arrays: &[ArrayRef]) -> Result<EncodedBuffer> {
-        let parts = arrays
-            .iter()
-            .map(|arr| arr.to_data().buffers()[0].clone())
-            .collect::<Vec<_>>();
+        let parts = values_buffers(arrays, self.little_endian);
         Ok(EncodedBuffer { parts })
     }
 }
 
+/// The widths of the byte-ordered components of a value of `data_type`
+///
+/// Each component is swapped on its own when changing the byte order of a value (e.g. an
+/// interval is several integers).  Empty for values that have no byte order such as bytes
+/// and bitmaps.
+pub(crate) fn byte_order_components(data_type: &DataType) -> &'static [usize] {
+    match data_type {
+        DataType::Interval(IntervalUnit::DayTime) => &[4, 4],
+        DataType::Interval(IntervalUnit::MonthDayNano) => &[4, 4, 8],
+        data_type if data_type.is_primitive() => match data_type.primitive_width() {
+            Some(2) => &[2],
+            Some(4) => &[4],
+            Some(8) => &[8],
+            Some(16) => &[16],
+            _ => &[],
+        },
+        _ => &[],
+    }
+}
+
+// Swaps the byte order of each component of each value in `bytes`
+pub(crate) fn swap_byte_order(bytes: &[u8], components: &[usize]) -> Vec<u8> {
+    let value_width = components.iter().sum::<usize>();
+    let mut swapped = Vec::with_capacity(bytes.len());
+    for value in bytes.chunks_exact(value_width) {
+        let mut start = 0;
+        for width in components {
+            swapped.extend(value[start..start + width].iter().rev());
+            start += width;
+        }
+    }
+    swapped
+}
+
+// The values buffers of `arrays`, converted to little-endian if `little_endian` is set and
+// the host is big-endian
+fn values_buffers(arrays: &[ArrayRef], little_endian: bool) -> Vec<Buffer> {
+    values_buffers_for_host(arrays, little_endian && cfg!(target_endian = "big"))
+}
+
+fn values_buffers_for_host(arrays: &[ArrayRef], swap: bool) -> Vec<Buffer> {
+    arrays
+        .iter()
+        .map(|arr| {
+            let buffer = arr.to_data().buffers()[0].clone();
+            match byte_order_components(arr.data_type()) {
+                components if swap && !components.is_empty() => {
+                    Buffer::from_vec(swap_byte_order(&buffer, components))
+                }
+                _ => buffer,
+            }
+        })
+        .collect()
+}
+
+/// The default for the largest size a compressed buffer may decompress to, see
+/// [`crate::decoder::DecoderConfig::max_decompressed_size`]
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 2 * 1024 * 1024 * 1024;
+
+/// Fails if a buffer that decompresses to `size` bytes exceeds the limit of `max_size` bytes
+pub fn check_decompressed_size(size: u64, max_size: usize) -> Result<()> {
+    if size > max_size as u64 {
+        Err(Error::corrupt_metadata(
+            format!(
+                "a compressed buffer decompresses to {} bytes which is more than the maximum of {} bytes (see DecoderConfig::max_decompressed_size)",
+                size, max_size
+            ),
+            location!(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// Appends all of `reader` to `output_buf`, failing as soon as more than `max_size` bytes
+// have been read
+fn read_with_limit(reader: impl Read, output_buf: &mut Vec<u8>, max_size: usize) -> Result<()> {
+    let start = output_buf.len();
+    reader
+        .take((max_size as u64).saturating_add(1))
+        .read_to_end(output_buf)?;
+    let size = output_buf.len() - start;
+    if size > max_size {
+        // The data was cut off at the limit and so the real size is unknown
+        output_buf.truncate(start);
+        return Err(Error::corrupt_metadata(
+            format!(
+                "a compressed buffer decompresses to more than the maximum of {} bytes (see DecoderConfig::max_decompressed_size)",
+                max_size
+            ),
+            location!(),
+        ));
+    }
+    Ok(())
+}
+
 pub trait BufferCompressor: std::fmt::Debug + Send + Sync {
     fn compress(&self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()>;
-    fn decompress(&self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()>;
+
+    /// Decompresses `input_buf`, appending the result to `output_buf`
+    ///
+    /// Fails without decompressing any further once the result is larger than `max_size`
+    fn decompress_with_limit(
+        &self,
+        input_buf: &[u8],
+        output_buf: &mut Vec<u8>,
+        max_size: usize,
+    ) -> Result<()>;
+
+    /// Decompresses `input_buf`, appending the result to `output_buf`
+    ///
+    /// The result may be at most [`DEFAULT_MAX_DECOMPRESSED_SIZE`] bytes
+    fn decompress(&self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()> {
+        self.decompress_with_limit(input_buf, output_buf, DEFAULT_MAX_DECOMPRESSED_SIZE)
+    }
 }
 
 #[derive(Debug, Default)]
-pub struct ZstdBufferCompressor {}
+pub struct ZstdBufferCompressor {
+    level: i32,
+}
+
+impl ZstdBufferCompressor {
+    /// Creates a compressor with the given zstd level (0 means the zstd default)
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
 
 impl BufferCompressor for ZstdBufferCompressor {
     fn compress(&self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()> {
-        let mut encoder = zstd::Encoder::new(output_buf, 0)?;
+        let mut encoder = zstd::Encoder::new(output_buf, self.level)?;
         encoder.write_all(input_buf)?;
         match encoder.finish() {
             Ok(_) => Ok(()),
@@ -41,9 +175,50 @@ impl BufferCompressor for ZstdBufferCompressor {
         }
     }
 
-    fn decompress(&self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()> {
-        let source = Cursor::new(input_buf);
-        zstd::stream::copy_decode(source, output_buf)?;
+    fn decompress_with_limit(
+        &self,
+        input_buf: &[u8],
+        output_buf: &mut Vec<u8>,
+        max_size: usize,
+    ) -> Result<()> {
+        let decoder = zstd::stream::read::Decoder::with_buffer(input_buf)?;
+        read_with_limit(decoder, output_buf, max_size)
+    }
+}
+
+/// Compresses buffers with LZ4, trading compression ratio for faster decompression than zstd
+///
+/// Buffers are written in the LZ4 frame format.  Frames can be concatenated, like zstd
+/// frames, and a concatenation decompresses to the concatenated data.
+#[derive(Debug, Default)]
+pub struct Lz4BufferCompressor {}
+
+impl BufferCompressor for Lz4BufferCompressor {
+    fn compress(&self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()> {
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(output_buf);
+        encoder.write_all(input_buf)?;
+        encoder
+            .finish()
+            .map_err(|e| Error::io(format!("LZ4 compression failed: {}", e), location!()))?;
+        Ok(())
+    }
+
+    fn decompress_with_limit(
+        &self,
+        input_buf: &[u8],
+        output_buf: &mut Vec<u8>,
+        max_size: usize,
+    ) -> Result<()> {
+        // The decoder stops at the end of a frame and so each frame is decoded on its own
+        let start = output_buf.len();
+        let mut input = input_buf;
+        while !input.is_empty() {
+            let decoder = lz4_flex::frame::FrameDecoder::new(&mut input);
+            let remaining = max_size - (output_buf.len() - start);
+            read_with_limit(decoder, output_buf, remaining).inspect_err(|_| {
+                output_buf.truncate(start);
+            })?;
+        }
         Ok(())
     }
 }
@@ -55,45 +230,269 @@ impl GeneralBufferCompressor {
         match compression_type {
             "" => Box::<ZstdBufferCompressor>::default(),
             "zstd" => Box::<ZstdBufferCompressor>::default(),
+            "lz4" => Box::<Lz4BufferCompressor>::default(),
             _ => panic!("Unsupported compression type: {}", compression_type),
         }
     }
 }
 
+/// The maximum number of bytes sampled to estimate how well a buffer compresses
+pub const COMPRESSION_SAMPLE_BYTES: usize = 64 * 1024;
+// The sample is taken from this many evenly spaced chunks so that it covers the whole buffer
+const NUM_SAMPLE_CHUNKS: usize = 16;
+/// Buffers that are estimated to compress worse than this are stored uncompressed
+pub const DEFAULT_MIN_COMPRESSION_RATIO: f64 = 1.1;
+
+/// The outcome of sampling a buffer to decide whether to compress it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionDecision {
+    /// True if the buffer was compressed
+    pub compressed: bool,
+    /// The compression ratio (uncompressed size / compressed size) measured on the sample
+    pub estimated_ratio: f64,
+}
+
+// Copies `len` bytes, starting at `offset`, from the concatenation of `parts`
+fn copy_from_parts(parts: &[Buffer], mut offset: usize, mut len: usize, dest: &mut Vec<u8>) {
+    for part in parts {
+        if len == 0 {
+            break;
+        }
+        if offset >= part.len() {
+            offset -= part.len();
+            continue;
+        }
+        let end = part.len().min(offset + len);
+        dest.extend_from_slice(&part[offset..end]);
+        len -= end - offset;
+        offset = 0;
+    }
+}
+
+/// Estimates the compression ratio of the concatenation of `parts`
+///
+/// Up to [`COMPRESSION_SAMPLE_BYTES`] bytes, spread across the data, are compressed with
+/// zstd's fastest level.  This is much cheaper than compressing the entire buffer and is
+/// a good predictor of whether compression will pay off.
+pub fn estimate_compression_ratio(parts: &[Buffer]) -> Result<f64> {
+    let total_len = parts.iter().map(|part| part.len()).sum::<usize>();
+    if total_len == 0 {
+        return Ok(1.0);
+    }
+    let mut sample = Vec::with_capacity(total_len.min(COMPRESSION_SAMPLE_BYTES));
+    if total_len <= COMPRESSION_SAMPLE_BYTES {
+        copy_from_parts(parts, 0, total_len, &mut sample);
+    } else {
+        let chunk_len = COMPRESSION_SAMPLE_BYTES / NUM_SAMPLE_CHUNKS;
+        let stride = total_len / NUM_SAMPLE_CHUNKS;
+        for chunk_idx in 0..NUM_SAMPLE_CHUNKS {
+            copy_from_parts(parts, chunk_idx * stride, chunk_len, &mut sample);
+        }
+    }
+    let compressed = zstd::bulk::compress(&sample, 1)?;
+    Ok(sample.len() as f64 / compressed.len() as f64)
+}
+
+/// The size of the header in front of every block of a block-compressed buffer
+///
+/// The header is the uncompressed size of the block followed by its compressed size, both
+/// as little-endian u32 values.
+pub const BLOCK_HEADER_SIZE: usize = 8;
+
+/// A block of a block-compressed buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedBlock {
+    /// The position of the block's data in the uncompressed buffer
+    pub uncompressed_offset: u64,
+    /// The size of the block once decompressed
+    pub uncompressed_size: u64,
+    /// The compressed data of the block (without the header)
+    pub data: bytes::Bytes,
+}
+
+/// Compresses `data` as a sequence of independently compressed blocks of (at most)
+/// `block_size` uncompressed bytes
+///
+/// Every block is preceded by a [`BLOCK_HEADER_SIZE`] byte header.  The header is always
+/// written little-endian so the buffer reads the same on any host.
+pub fn compress_blocks(
+    compressor: &dyn BufferCompressor,
+    data: &[u8],
+    block_size: usize,
+    output_buf: &mut Vec<u8>,
+) -> Result<()> {
+    let mut compressed = Vec::new();
+    for block in data.chunks(block_size) {
+        compressed.clear();
+        compressor.compress(block, &mut compressed)?;
+        output_buf.extend_from_slice(&block_header_value(block.len())?.to_le_bytes());
+        output_buf.extend_from_slice(&block_header_value(compressed.len())?.to_le_bytes());
+        output_buf.extend_from_slice(&compressed);
+    }
+    Ok(())
+}
+
+fn block_header_value(size: usize) -> Result<u32> {
+    u32::try_from(size).map_err(|_| {
+        Error::invalid_input(
+            format!(
+                "compression blocks are limited to 4GiB but a block is {} bytes",
+                size
+            ),
+            location!(),
+        )
+    })
+}
+
+/// Splits a buffer written by [`compress_blocks`] into its blocks
+///
+/// The headers are read as little-endian, whatever the byte order of the host.
+pub fn parse_blocks(buf: &bytes::Bytes) -> Result<Vec<CompressedBlock>> {
+    parse_blocks_with(buf, u32::from_le_bytes)
+}
+
+// Parses the block framing, `read_u32` converts the four header bytes of a size into a value
+fn parse_blocks_with(
+    buf: &bytes::Bytes,
+    read_u32: impl Fn([u8; 4]) -> u32,
+) -> Result<Vec<CompressedBlock>> {
+    let too_short = |expected: usize| {
+        Error::encoding(
+            EncodingError::BufferTooShort {
+                context: "a block-compressed buffer".to_string(),
+                expected: expected as u64,
+                actual: buf.len() as u64,
+            },
+            location!(),
+        )
+    };
+    let mut blocks = Vec::new();
+    let mut position = 0;
+    let mut uncompressed_offset = 0;
+    while position < buf.len() {
+        let header_end = position + BLOCK_HEADER_SIZE;
+        if header_end > buf.len() {
+            return Err(too_short(header_end));
+        }
+        let header = &buf[position..header_end];
+        let uncompressed_size = read_u32(header[..4].try_into().unwrap()) as u64;
+        let compressed_size = read_u32(header[4..].try_into().unwrap()) as usize;
+        let data_end = header_end + compressed_size;
+        if data_end > buf.len() {
+            return Err(too_short(data_end));
+        }
+        blocks.push(CompressedBlock {
+            uncompressed_offset,
+            uncompressed_size,
+            data: buf.slice(header_end..data_end),
+        });
+        uncompressed_offset += uncompressed_size;
+        position = data_end;
+    }
+    Ok(blocks)
+}
+
 // An encoder which uses lightweight compression, such as zstd/lz4 to encode buffers
 #[derive(Debug)]
 pub struct CompressedBufferEncoder {
     compressor: Box<dyn BufferCompressor>,
+    min_compression_ratio: f64,
+    block_size: Option<usize>,
+    little_endian: bool,
 }
 
 impl Default for CompressedBufferEncoder {
     fn default() -> Self {
-        Self {
-            compressor: GeneralBufferCompressor::get_compressor("zstd"),
-        }
+        Self::new("zstd")
     }
 }
 
 impl CompressedBufferEncoder {
     pub fn new(compression_type: &str) -> Self {
         let compressor = GeneralBufferCompressor::get_compressor(compression_type);
-        Self { compressor }
+        Self::with_compressor(compressor)
+    }
+
+    pub fn with_compressor(compressor: Box<dyn BufferCompressor>) -> Self {
+        Self {
+            compressor,
+            min_compression_ratio: DEFAULT_MIN_COMPRESSION_RATIO,
+            block_size: None,
+            little_endian: false,
+        }
+    }
+
+    /// Stores values in little-endian byte order on every host, see
+    /// [`FlatBufferEncoder::little_endian`]
+    pub fn with_little_endian(mut self, little_endian: bool) -> Self {
+        self.little_endian = little_endian;
+        self
+    }
+
+    /// Compresses buffers in independent blocks of `block_size` bytes, see [`compress_blocks`]
+    ///
+    /// By default a buffer is compressed as a whole.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Sets the estimated compression ratio below which [`BufferEncoder::encode_with_decision`]
+    /// stores buffers uncompressed
+    ///
+    /// A ratio of 0 always compresses.
+    pub fn with_min_compression_ratio(mut self, min_compression_ratio: f64) -> Self {
+        self.min_compression_ratio = min_compression_ratio;
+        self
+    }
+
+    fn compress(&self, parts: Vec<Buffer>) -> Result<Vec<Buffer>> {
+        if let Some(block_size) = self.block_size {
+            // Blocks span parts so the parts are compressed as one buffer
+            let total_len = parts.iter().map(|part| part.len()).sum::<usize>();
+            let mut data = Vec::with_capacity(total_len);
+            copy_from_parts(&parts, 0, total_len, &mut data);
+            let mut compressed = Vec::with_capacity(total_len);
+            compress_blocks(self.compressor.as_ref(), &data, block_size, &mut compressed)?;
+            return Ok(vec![Buffer::from(compressed)]);
+        }
+        parts
+            .into_iter()
+            .map(|buffer| {
+                let mut compressed = Vec::with_capacity(buffer.len());
+                self.compressor
+                    .compress(buffer.as_slice(), &mut compressed)?;
+                Ok(Buffer::from(compressed))
+            })
+            .collect()
     }
 }
 
 impl BufferEncoder for CompressedBufferEncoder {
+    // Always compresses, the encoding written by callers of this method will say so
     fn encode(&self, arrays: &[ArrayRef]) -> Result<EncodedBuffer> {
-        let mut parts = Vec::with_capacity(arrays.len());
-        for arr in arrays {
-            let buffer = arr.to_data().buffers()[0].clone();
-            let buffer_len = buffer.len();
-            let buffer_data = buffer.as_slice();
-            let mut compressed = Vec::with_capacity(buffer_len);
-            self.compressor.compress(buffer_data, &mut compressed)?;
-            parts.push(Buffer::from(compressed));
-        }
+        let parts = self.compress(values_buffers(arrays, self.little_endian))?;
         Ok(EncodedBuffer { parts })
     }
+
+    fn encode_with_decision(
+        &self,
+        arrays: &[ArrayRef],
+    ) -> Result<(EncodedBuffer, Option<CompressionDecision>)> {
+        let parts = values_buffers(arrays, self.little_endian);
+        let estimated_ratio = estimate_compression_ratio(&parts)?;
+        let compressed = estimated_ratio >= self.min_compression_ratio;
+        let parts = if compressed {
+            self.compress(parts)?
+        } else {
+            parts
+        };
+        let decision = CompressionDecision {
+            compressed,
+            estimated_ratio,
+        };
+        Ok((EncodedBuffer { parts }, Some(decision)))
+    }
 }
 
 // Encoder for writing boolean arrays as dense bitmaps
@@ -105,7 +504,7 @@ impl BufferEncoder for BitmapBufferEncoder {
         debug_assert!(arrays
             .iter()
             .all(|arr| *arr.data_type() == DataType::Boolean));
-        let num_rows: u32 = arrays.iter().map(|arr| arr.len() as u32).sum();
+        let num_rows: usize = arrays.iter().map(|arr| arr.len()).sum();
         // Empty pages don't make sense, this should be prevented before we
         // get here
         debug_assert_ne!(num_rows, 0);
@@ -115,7 +514,7 @@ impl BufferEncoder for BitmapBufferEncoder {
         // garbage).  So we go ahead and pay the cost of a copy (we could avoid this
         // if we really needed to, at the expense of more complicated code and a slightly
         // larger encoded size but writer cost generally doesn't matter as much as reader cost)
-        let mut builder = BooleanBufferBuilder::new(num_rows as usize);
+        let mut builder = BooleanBufferBuilder::new(num_rows);
         for arr in arrays {
             let bool_arr = arr.as_boolean();
             builder.append_buffer(bool_arr.values());
@@ -126,3 +525,156 @@ impl BufferEncoder for BitmapBufferEncoder {
         Ok(buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        ArrayRef, Float64Array, Int32Array, Int64Array, IntervalMonthDayNanoArray, UInt8Array,
+    };
+    use arrow_buffer::IntervalMonthDayNano;
+    use bytes::Bytes;
+
+    use lance_core::{error::EncodingError, Error};
+
+    use crate::encoder::BufferEncoder;
+
+    use super::{
+        compress_blocks, parse_blocks, parse_blocks_with, values_buffers_for_host,
+        BufferCompressor, FlatBufferEncoder, Lz4BufferCompressor, ZstdBufferCompressor,
+    };
+
+    #[test]
+    fn test_little_endian_values() {
+        let ints = [1_i32, -2, 0x0102_0304];
+        let longs = [1_i64, -2, 0x0102_0304_0506_0708];
+        let floats = [1.5_f64, -2.25];
+        let intervals = [IntervalMonthDayNano::new(1, -2, 0x0102_0304_0506)];
+        let bytes = [1_u8, 2, 3];
+
+        // Simulate a big-endian host, whose arrays hold the big-endian bytes of each value
+        let big_endian_arrays: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from_iter_values(ints.map(i32::swap_bytes))),
+            Arc::new(Int64Array::from_iter_values(longs.map(i64::swap_bytes))),
+            Arc::new(Float64Array::from_iter_values(
+                floats.map(|v| f64::from_bits(v.to_bits().swap_bytes())),
+            )),
+            Arc::new(IntervalMonthDayNanoArray::from_iter_values(intervals.map(
+                |v| {
+                    IntervalMonthDayNano::new(
+                        v.months.swap_bytes(),
+                        v.days.swap_bytes(),
+                        v.nanoseconds.swap_bytes(),
+                    )
+                },
+            ))),
+            Arc::new(UInt8Array::from_iter_values(bytes)),
+        ];
+        let expected: Vec<Vec<u8>> = vec![
+            ints.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            longs.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            floats.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            intervals
+                .iter()
+                .flat_map(|v| {
+                    [
+                        v.months.to_le_bytes().as_slice(),
+                        v.days.to_le_bytes().as_slice(),
+                        v.nanoseconds.to_le_bytes().as_slice(),
+                    ]
+                    .concat()
+                })
+                .collect(),
+            bytes.to_vec(),
+        ];
+        let stored = values_buffers_for_host(&big_endian_arrays, true);
+        for (stored, expected) in stored.iter().zip(&expected) {
+            assert_eq!(stored.as_slice(), expected.as_slice());
+        }
+
+        // On a little-endian host the values are stored without a copy
+        if cfg!(target_endian = "little") {
+            let arrays: Vec<ArrayRef> = vec![Arc::new(Int32Array::from_iter_values(ints))];
+            let encoded = FlatBufferEncoder::little_endian().encode(&arrays).unwrap();
+            assert_eq!(
+                encoded.parts[0].as_ptr(),
+                arrays[0].to_data().buffers()[0].as_ptr()
+            );
+            assert_eq!(encoded.parts[0].as_slice(), expected[0].as_slice());
+        }
+    }
+
+    #[test]
+    fn test_decompress_with_limit() {
+        let data = vec![7_u8; 10_000];
+        let compressors: [Box<dyn BufferCompressor>; 2] = [
+            Box::<ZstdBufferCompressor>::default(),
+            Box::<Lz4BufferCompressor>::default(),
+        ];
+        for compressor in compressors {
+            // Two concatenated frames
+            let mut compressed = Vec::new();
+            compressor.compress(&data, &mut compressed).unwrap();
+            compressor.compress(&data, &mut compressed).unwrap();
+
+            let mut output = vec![1, 2, 3];
+            compressor
+                .decompress_with_limit(&compressed, &mut output, 20_000)
+                .unwrap();
+            assert_eq!(output.len(), 20_003);
+
+            // Going over the limit is an error and leaves the output as it was
+            let mut output = vec![1, 2, 3];
+            let err = compressor
+                .decompress_with_limit(&compressed, &mut output, 19_999)
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+            assert_eq!(output, vec![1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn test_block_framing_is_little_endian() {
+        let data = (0..3000_u32)
+            .flat_map(|v| (v % 11).to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut buf = Vec::new();
+        compress_blocks(&ZstdBufferCompressor::default(), &data, 5000, &mut buf).unwrap();
+        let buf = Bytes::from(buf);
+        // The first header holds the size of the first block in little-endian order
+        assert_eq!(&buf[..4], &5000_u32.to_le_bytes());
+
+        let blocks = parse_blocks(&buf).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|block| (block.uncompressed_offset, block.uncompressed_size))
+                .collect::<Vec<_>>(),
+            vec![(0, 5000), (5000, 5000), (10000, 2000)]
+        );
+
+        // On a big-endian host a native read gives the swapped value, which a little-endian
+        // read converts back, so both hosts find the same blocks
+        let big_endian_host =
+            parse_blocks_with(&buf, |bytes| u32::from_be_bytes(bytes).swap_bytes());
+        assert_eq!(big_endian_host.unwrap(), blocks);
+        // Reading the headers in the host's native order would misread the framing there
+        assert!(parse_blocks_with(&buf, u32::from_be_bytes).is_err());
+
+        // A truncated buffer is an error, not a panic
+        assert!(parse_blocks(&buf.slice(..buf.len() - 1)).is_err());
+        assert!(parse_blocks(&buf.slice(..4)).is_err());
+    }
+}
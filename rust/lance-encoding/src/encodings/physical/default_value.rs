@@ -0,0 +1,312 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Pages where every row has the same (default) value
+//!
+//! When a column is added to a schema the files written before it do not contain the
+//! column.  Readers can use these to synthesize the missing pages, without any I/O, and
+//! then treat the column like any other.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use arrow_array::{cast::AsArray, new_null_array, ArrayRef};
+use arrow_schema::DataType;
+use bytes::BytesMut;
+use futures::{future::BoxFuture, FutureExt};
+use snafu::{location, Location};
+
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, PageScheduler, PrimitivePageDecoder},
+    EncodingsIo,
+};
+
+// The bytes of a single value, or None for booleans (which are bit-packed)
+fn value_bytes(default_value: &ArrayRef) -> Result<Option<Vec<u8>>> {
+    let data_type = default_value.data_type();
+    let width = match data_type {
+        DataType::Boolean => return Ok(None),
+        DataType::FixedSizeBinary(width) => *width as usize,
+        _ => data_type.primitive_width().ok_or_else(|| {
+            Error::invalid_input(
+                format!(
+                    "A default value page can only be created for fixed-width types and not {}",
+                    data_type
+                ),
+                location!(),
+            )
+        })?,
+    };
+    let data = default_value.to_data();
+    let start = data.offset() * width;
+    Ok(Some(data.buffers()[0][start..start + width].to_vec()))
+}
+
+/// A decoder that produces the same value for every row
+///
+/// The decoded buffers have the usual layout of a fixed-width type: a validity bitmap
+/// (empty unless the default is null) followed by the values.
+#[derive(Debug)]
+pub struct DefaultValueDecoder {
+    default_value: ArrayRef,
+    value_bytes: Option<Vec<u8>>,
+    num_rows: u64,
+}
+
+impl DefaultValueDecoder {
+    /// Creates a decoder for `num_rows` rows of `data_type` that are all `default_value`
+    ///
+    /// `default_value` must be an array with a single value (which may be null) of type
+    /// `data_type`.  Only fixed-width types (including booleans) are supported.
+    pub fn try_new(data_type: &DataType, num_rows: u64, default_value: ArrayRef) -> Result<Self> {
+        if default_value.len() != 1 || default_value.data_type() != data_type {
+            return Err(Error::invalid_input(
+                format!(
+                    "The default value of a {} page must be a single {} value but got {} values of type {}",
+                    data_type,
+                    data_type,
+                    default_value.len(),
+                    default_value.data_type()
+                ),
+                location!(),
+            ));
+        }
+        let value_bytes = value_bytes(&default_value)?;
+        Ok(Self {
+            default_value,
+            value_bytes,
+            num_rows,
+        })
+    }
+
+    /// Creates a decoder for `num_rows` rows of `data_type` that are all null
+    pub fn try_new_null(data_type: &DataType, num_rows: u64) -> Result<Self> {
+        Self::try_new(data_type, num_rows, new_null_array(data_type, 1))
+    }
+}
+
+impl PrimitivePageDecoder for DefaultValueDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        if rows_to_skip + num_rows > self.num_rows {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot decode rows {}..{} of a default value page with {} rows",
+                    rows_to_skip,
+                    rows_to_skip + num_rows,
+                    self.num_rows
+                ),
+                location!(),
+            ));
+        }
+        let num_rows = num_rows as usize;
+        let num_bitmap_bytes = num_rows.div_ceil(8);
+        if self.default_value.is_null(0) {
+            *all_null = true;
+            let values_len = match &self.value_bytes {
+                Some(value) => value.len() * num_rows,
+                None => num_bitmap_bytes,
+            };
+            return Ok(vec![
+                BytesMut::zeroed(num_bitmap_bytes),
+                BytesMut::zeroed(values_len),
+            ]);
+        }
+        let values = match &self.value_bytes {
+            Some(value) => BytesMut::from(value.repeat(num_rows).as_slice()),
+            None => {
+                let mut bits = if self.default_value.as_boolean().value(0) {
+                    vec![0xFF; num_bitmap_bytes]
+                } else {
+                    vec![0; num_bitmap_bytes]
+                };
+                // Bits beyond the last row are always zero
+                if num_rows % 8 != 0 {
+                    bits[num_bitmap_bytes - 1] &= (1 << (num_rows % 8)) - 1;
+                }
+                BytesMut::from(bits.as_slice())
+            }
+        };
+        Ok(vec![BytesMut::new(), values])
+    }
+
+    fn can_skip(&self) -> bool {
+        true
+    }
+
+    fn num_buffers(&self) -> u32 {
+        2
+    }
+}
+
+/// A page scheduler for a page where every row has the same (default) value
+///
+/// Scheduling never submits any I/O.  See [`DefaultValueDecoder`].
+#[derive(Debug)]
+pub struct DefaultValueScheduler {
+    data_type: DataType,
+    default_value: ArrayRef,
+}
+
+impl DefaultValueScheduler {
+    /// Creates a scheduler for a page of `data_type` values that are all `default_value`
+    ///
+    /// See [`DefaultValueDecoder::try_new`] for the requirements on `default_value`
+    pub fn try_new(data_type: DataType, default_value: ArrayRef) -> Result<Self> {
+        // Validate the default value up front
+        DefaultValueDecoder::try_new(&data_type, 0, default_value.clone())?;
+        Ok(Self {
+            data_type,
+            default_value,
+        })
+    }
+}
+
+impl PageScheduler for DefaultValueScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        _scheduler: &Arc<dyn EncodingsIo>,
+        _top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let num_rows = ranges.iter().map(|range| range.end - range.start).sum();
+        let decoder =
+            DefaultValueDecoder::try_new(&self.data_type, num_rows, self.default_value.clone())
+                .map(|decoder| Box::new(decoder) as Box<dyn PrimitivePageDecoder>);
+        std::future::ready(decoder).boxed()
+    }
+
+    fn estimate_cost(&self, _ranges: &[Range<u64>]) -> DecodeCost {
+        DecodeCost::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        cast::AsArray, types::Int32Type, ArrayRef, BooleanArray, FixedSizeBinaryArray,
+        Float64Array, Int32Array, StringArray,
+    };
+    use arrow_schema::DataType;
+    use bytes::Bytes;
+
+    use crate::{
+        decoder::{PageScheduler, PrimitivePageDecoder},
+        encodings::utils::primitive_array_from_buffers,
+        testing::RecordingIo,
+        EncodingsIo,
+    };
+
+    use super::{DefaultValueDecoder, DefaultValueScheduler};
+
+    fn decode(decoder: &dyn PrimitivePageDecoder, data_type: &DataType, num_rows: u64) -> ArrayRef {
+        let mut all_null = false;
+        let buffers = decoder.decode(0, num_rows, &mut all_null).unwrap();
+        primitive_array_from_buffers(data_type, buffers, num_rows).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_default_int32() {
+        let io = Arc::new(RecordingIo::new(Bytes::new()));
+        let scheduler =
+            DefaultValueScheduler::try_new(DataType::Int32, Arc::new(Int32Array::from(vec![0])))
+                .unwrap();
+        let decoder = scheduler
+            .schedule_ranges(
+                &[0..600, 1000..1100],
+                &(io.clone() as Arc<dyn EncodingsIo>),
+                0,
+            )
+            .await
+            .unwrap();
+        assert!(io.requests().is_empty());
+
+        let array = decode(decoder.as_ref(), &DataType::Int32, 700);
+        assert_eq!(array.as_ref(), &Int32Array::from(vec![0; 700]));
+        assert_eq!(array.null_count(), 0);
+
+        // Rows beyond the page are an error
+        assert!(decoder.decode(600, 101, &mut false).is_err());
+    }
+
+    #[test]
+    fn test_default_values() {
+        let decoder = DefaultValueDecoder::try_new(
+            &DataType::Float64,
+            5,
+            Arc::new(Float64Array::from(vec![1.5])),
+        )
+        .unwrap();
+        assert_eq!(
+            decode(&decoder, &DataType::Float64, 3).as_ref(),
+            &Float64Array::from(vec![1.5; 3])
+        );
+
+        // The value may come from a slice of a larger array
+        let values = Int32Array::from(vec![1, 2, 3]);
+        let decoder =
+            DefaultValueDecoder::try_new(&DataType::Int32, 4, Arc::new(values.slice(1, 1)))
+                .unwrap();
+        assert_eq!(
+            decode(&decoder, &DataType::Int32, 4)
+                .as_primitive::<Int32Type>()
+                .values(),
+            &[2, 2, 2, 2]
+        );
+
+        let decoder = DefaultValueDecoder::try_new(
+            &DataType::Boolean,
+            10,
+            Arc::new(BooleanArray::from(vec![true])),
+        )
+        .unwrap();
+        assert_eq!(
+            decode(&decoder, &DataType::Boolean, 10).as_ref(),
+            &BooleanArray::from(vec![true; 10])
+        );
+
+        let data_type = DataType::FixedSizeBinary(2);
+        let value = FixedSizeBinaryArray::try_from_iter([[7_u8, 8]].into_iter()).unwrap();
+        let decoder = DefaultValueDecoder::try_new(&data_type, 3, Arc::new(value)).unwrap();
+        let mut all_null = false;
+        let buffers = decoder.decode(0, 3, &mut all_null).unwrap();
+        assert!(buffers[0].is_empty());
+        assert_eq!(buffers[1].as_ref(), &[7, 8, 7, 8, 7, 8]);
+
+        // A null default makes every row null
+        let decoder = DefaultValueDecoder::try_new_null(&DataType::Int32, 10).unwrap();
+        let mut all_null = false;
+        let buffers = decoder.decode(2, 5, &mut all_null).unwrap();
+        assert!(all_null);
+        let array = primitive_array_from_buffers(&DataType::Int32, buffers, 5).unwrap();
+        assert_eq!(array.null_count(), 5);
+
+        // Defaults must be a single value of the page's (fixed-width) type
+        assert!(DefaultValueDecoder::try_new(
+            &DataType::Int64,
+            1,
+            Arc::new(Int32Array::from(vec![0]))
+        )
+        .is_err());
+        assert!(DefaultValueDecoder::try_new(
+            &DataType::Int32,
+            1,
+            Arc::new(Int32Array::from(vec![0, 1]))
+        )
+        .is_err());
+        assert!(DefaultValueDecoder::try_new(
+            &DataType::Utf8,
+            1,
+            Arc::new(StringArray::from(vec!["a"]))
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{Array, ArrayRef, Int64Array};
+use arrow_schema::{DataType, TimeUnit};
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt};
+use log::trace;
+use snafu::{location, Location};
+
+use lance_arrow::DataTypeExt;
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encoder::{ArrayEncoder, EncodedArray},
+    format::pb,
+    EncodingsIo,
+};
+
+use super::bitpack::{num_compressed_bits, BitpackedArrayEncoder};
+
+/// Returns true if the data type can be encoded with [`DeltaOfDeltaEncoder`]
+pub fn supports_delta_of_delta(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int32
+            | DataType::Int64
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Time32(_)
+            | DataType::Time64(_)
+            | DataType::Timestamp(_, _)
+            | DataType::Duration(_)
+    )
+}
+
+/// Returns true if the data type is a timestamp-like type that tends to be evenly spaced
+pub fn is_regular_temporal(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Timestamp(_, _) | DataType::Date64 | DataType::Time64(TimeUnit::Nanosecond)
+    )
+}
+
+fn is_unsigned(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::UInt32 | DataType::UInt64)
+}
+
+// Collects the values of the arrays as i64 (sign extended unless the type is unsigned)
+pub(super) fn collect_values(arrays: &[ArrayRef]) -> Vec<i64> {
+    let data_type = arrays[0].data_type();
+    let byte_width = data_type.byte_width();
+    let sign_extend = !is_unsigned(data_type);
+    let mut values = Vec::with_capacity(arrays.iter().map(|arr| arr.len()).sum());
+    for arr in arrays {
+        let data = arr.to_data();
+        let start = data.offset() * byte_width;
+        let end = start + data.len() * byte_width;
+        for chunk in data.buffers()[0][start..end].chunks_exact(byte_width) {
+            let is_negative = sign_extend && chunk[byte_width - 1] & 0x80 != 0;
+            let mut le_bytes = if is_negative { [0xFF; 8] } else { [0; 8] };
+            le_bytes[..byte_width].copy_from_slice(chunk);
+            values.push(i64::from_le_bytes(le_bytes));
+        }
+    }
+    values
+}
+
+/// Encodes integers as a base value, a first delta, and the second order differences
+///
+/// Evenly spaced values (e.g. timestamps with a fixed sampling interval) have constant deltas
+/// and so the second order differences are all zero and bitpack into a single bit each.
+/// Irregular values still round trip, the differences just need more bits.
+///
+/// All arithmetic wraps so any input can be represented.
+#[derive(Debug, Default)]
+pub struct DeltaOfDeltaEncoder {}
+
+impl DeltaOfDeltaEncoder {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ArrayEncoder for DeltaOfDeltaEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        if !supports_delta_of_delta(data_type) {
+            return Err(Error::unsupported_type(
+                data_type,
+                "delta-of-delta encoding is only supported for integers",
+                location!(),
+            ));
+        }
+        let values = collect_values(arrays);
+
+        let base = values.first().copied().unwrap_or(0);
+        let first_delta = match values.as_slice() {
+            [first, second, ..] => second.wrapping_sub(*first),
+            _ => 0,
+        };
+        let deltas_of_deltas = values
+            .windows(3)
+            .map(|w| {
+                let delta = w[2].wrapping_sub(w[1]);
+                let prev_delta = w[1].wrapping_sub(w[0]);
+                delta.wrapping_sub(prev_delta)
+            })
+            .collect::<Vec<_>>();
+        let deltas_of_deltas = vec![Arc::new(Int64Array::from(deltas_of_deltas)) as ArrayRef];
+
+        // Every value fits in 64 bits so this never fails for an Int64Array
+        let num_bits = num_compressed_bits(&deltas_of_deltas).unwrap();
+        let encoded_deltas =
+            BitpackedArrayEncoder::new(num_bits).encode(&deltas_of_deltas, buffer_index)?;
+
+        Ok(EncodedArray {
+            buffers: encoded_deltas.buffers,
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::DeltaOfDelta(Box::new(
+                    pb::DeltaOfDelta {
+                        base,
+                        first_delta,
+                        deltas: Some(Box::new(encoded_deltas.encoding)),
+                        uncompressed_bits_per_value: 8 * data_type.byte_width() as u64,
+                    },
+                ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// Scheduler for a page of delta-of-delta encoded values
+///
+/// Values can only be recovered by integrating the differences from the start of the page
+/// and so the differences are always loaded from the start of the page.
+#[derive(Debug)]
+pub struct DeltaOfDeltaScheduler {
+    deltas: Box<dyn PageScheduler>,
+    base: i64,
+    first_delta: i64,
+    bytes_per_value: u64,
+}
+
+impl DeltaOfDeltaScheduler {
+    pub fn new(
+        deltas: Box<dyn PageScheduler>,
+        base: i64,
+        first_delta: i64,
+        bytes_per_value: u64,
+    ) -> Self {
+        Self {
+            deltas,
+            base,
+            first_delta,
+            bytes_per_value,
+        }
+    }
+}
+
+impl DeltaOfDeltaScheduler {
+    // Returns the number of differences needed to decode the ranges and the range of
+    // differences to load
+    fn deltas_ranges(ranges: &[Range<u64>]) -> (u64, Vec<Range<u64>>) {
+        // The first two values don't have a second order difference
+        let num_values = ranges.last().map(|range| range.end).unwrap_or(0);
+        let num_deltas = num_values.saturating_sub(2);
+        #[allow(clippy::single_range_in_vec_init)]
+        let deltas_ranges = if num_deltas > 0 {
+            vec![0..num_deltas]
+        } else {
+            vec![]
+        };
+        (num_deltas, deltas_ranges)
+    }
+}
+
+impl PageScheduler for DeltaOfDeltaScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let (num_deltas, deltas_ranges) = Self::deltas_ranges(ranges);
+        trace!(
+            "Scheduling delta-of-delta page, loading {} differences for {} ranges",
+            num_deltas,
+            ranges.len()
+        );
+        let deltas = self
+            .deltas
+            .schedule_ranges(&deltas_ranges, scheduler, top_level_row);
+        let base = self.base;
+        let first_delta = self.first_delta;
+        let bytes_per_value = self.bytes_per_value;
+        let ranges = ranges.to_vec();
+
+        async move {
+            let deltas = deltas.await?;
+            Ok(Box::new(DeltaOfDeltaPageDecoder {
+                deltas,
+                num_deltas,
+                base,
+                first_delta,
+                bytes_per_value,
+                ranges,
+                decoded: Mutex::new(None),
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        let (_, deltas_ranges) = Self::deltas_ranges(ranges);
+        self.deltas
+            .estimate_cost(&deltas_ranges)
+            .with_min_cpu_class(DecodeCpuClass::Unpack)
+    }
+}
+
+struct DeltaOfDeltaPageDecoder {
+    deltas: Box<dyn PrimitivePageDecoder>,
+    num_deltas: u64,
+    base: i64,
+    first_delta: i64,
+    bytes_per_value: u64,
+    ranges: Vec<Range<u64>>,
+    // The values of the requested ranges, computed on first use
+    decoded: Mutex<Option<Bytes>>,
+}
+
+impl DeltaOfDeltaPageDecoder {
+    // Integrates the differences twice and gathers the requested ranges
+    fn integrate(&self) -> Result<Bytes> {
+        let deltas = self.deltas.decode(0, self.num_deltas, &mut false)?;
+        let deltas = deltas[0]
+            .chunks_exact(8)
+            .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()));
+
+        let num_values = self.ranges.last().map(|range| range.end).unwrap_or(0);
+        let mut values = Vec::with_capacity(num_values as usize);
+        if num_values > 0 {
+            values.push(self.base);
+        }
+        if num_values > 1 {
+            values.push(self.base.wrapping_add(self.first_delta));
+        }
+        let mut delta = self.first_delta;
+        for delta_of_delta in deltas {
+            delta = delta.wrapping_add(delta_of_delta);
+            values.push(values[values.len() - 1].wrapping_add(delta));
+        }
+
+        let bytes_per_value = self.bytes_per_value as usize;
+        let num_rows = self.ranges.iter().map(|r| r.end - r.start).sum::<u64>();
+        let mut dest = BytesMut::with_capacity(num_rows as usize * bytes_per_value);
+        for range in &self.ranges {
+            for value in &values[range.start as usize..range.end as usize] {
+                dest.extend_from_slice(&value.to_le_bytes()[..bytes_per_value]);
+            }
+        }
+        Ok(dest.freeze())
+    }
+}
+
+impl PrimitivePageDecoder for DeltaOfDeltaPageDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut decoded = self.decoded.lock().unwrap();
+        if decoded.is_none() {
+            *decoded = Some(self.integrate()?);
+        }
+        let decoded = decoded.as_ref().unwrap();
+        let start = (rows_to_skip * self.bytes_per_value) as usize;
+        let end = start + (num_rows * self.bytes_per_value) as usize;
+        Ok(vec![BytesMut::from(&decoded[start..end])])
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, ArrayRef, Int32Array, TimestampMillisecondArray, UInt64Array};
+    use arrow_schema::{DataType, Field, TimeUnit};
+    use rand::Rng;
+
+    use crate::{
+        encoder::{ArrayEncoder, EncodingOverride},
+        testing::{check_round_trip_encoding_random_with_override, InMemoryPage},
+    };
+
+    use super::DeltaOfDeltaEncoder;
+
+    // Encodes the arrays and returns the total encoded size and the decoded values
+    // for each of the given ranges
+    async fn round_trip(
+        arrays: &[ArrayRef],
+        ranges: &[Vec<std::ops::Range<u64>>],
+    ) -> (usize, Vec<Vec<u8>>) {
+        let encoded = DeltaOfDeltaEncoder::new().encode(arrays, &mut 0).unwrap();
+        assert_eq!(encoded.buffers.len(), 1);
+        let page = InMemoryPage::new(&encoded);
+        let mut decoded = Vec::new();
+        for ranges in ranges {
+            let buffers = page.decode(arrays[0].data_type(), ranges).await.unwrap();
+            decoded.push(buffers[0].to_vec());
+        }
+        (page.data.len(), decoded)
+    }
+
+    fn timestamps_to_bytes(values: &[i64]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_regular_timestamps() {
+        let values = (0..10000)
+            .map(|i| 1_700_000_000_000 + i * 1000)
+            .collect::<Vec<i64>>();
+        let arrays = vec![
+            Arc::new(TimestampMillisecondArray::from(values[..5000].to_vec())) as ArrayRef,
+            Arc::new(TimestampMillisecondArray::from(values[5000..].to_vec())) as ArrayRef,
+        ];
+
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = vec![vec![0..10000], vec![5..10, 4000..6000], vec![9999..10000]];
+        let (encoded_size, decoded) = round_trip(&arrays, &ranges).await;
+
+        // Every second order difference is zero and needs a single bit
+        assert_eq!(encoded_size, (10000 - 2_usize).div_ceil(8));
+        for (ranges, decoded) in ranges.iter().zip(decoded) {
+            let expected = ranges
+                .iter()
+                .flat_map(|r| values[r.start as usize..r.end as usize].to_vec())
+                .collect::<Vec<_>>();
+            assert_eq!(decoded, timestamps_to_bytes(&expected));
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_jittery_timestamps() {
+        let mut rng = rand::thread_rng();
+        let mut ts = 1_700_000_000_000_i64;
+        let values = (0..5000)
+            .map(|i| {
+                // Occasionally there is a large gap in the series
+                ts += if i % 1000 == 999 {
+                    3_600_000
+                } else {
+                    1000 + rng.gen_range(-20..20)
+                };
+                ts
+            })
+            .collect::<Vec<_>>();
+        let arr = Arc::new(TimestampMillisecondArray::from(values.clone())) as ArrayRef;
+
+        #[allow(clippy::single_range_in_vec_init)]
+        let (encoded_size, decoded) = round_trip(&[arr], &[vec![0..5000]]).await;
+        assert!(encoded_size < values.len() * 8);
+        assert_eq!(decoded[0], timestamps_to_bytes(&values));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_short_and_extreme_arrays() {
+        // Two elements (no second order differences), one element, and values that overflow
+        // when differenced
+        let cases = vec![
+            Arc::new(Int32Array::from(vec![5, -7])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![42])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![i32::MAX, i32::MIN, 0, i32::MAX])) as ArrayRef,
+            Arc::new(UInt64Array::from(vec![u64::MAX, 0, u64::MAX, 1])) as ArrayRef,
+        ];
+        for arr in cases {
+            let len = arr.len() as u64;
+            #[allow(clippy::single_range_in_vec_init)]
+            let (_, decoded) = round_trip(&[arr.clone()], &[vec![0..len]]).await;
+            assert_eq!(decoded[0], arr.to_data().buffers()[0].as_slice());
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_forced_delta_of_delta() {
+        for data_type in [
+            DataType::Int32,
+            DataType::UInt64,
+            DataType::Date32,
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            DataType::Duration(TimeUnit::Second),
+        ] {
+            let field = Field::new("", data_type, true);
+            check_round_trip_encoding_random_with_override(
+                field,
+                EncodingOverride::ForceDeltaOfDelta,
+            )
+            .await;
+        }
+    }
+}
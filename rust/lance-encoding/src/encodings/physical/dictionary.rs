@@ -4,12 +4,14 @@
 use std::sync::Arc;
 
 use arrow_array::builder::{ArrayBuilder, StringBuilder};
-use arrow_array::types::UInt8Type;
-use arrow_array::{Array, ArrayRef, DictionaryArray, StringArray, UInt8Array};
+use arrow_array::types::{Int32Type, UInt8Type};
+use arrow_array::{
+    new_empty_array, Array, ArrayRef, DictionaryArray, Int32Array, StringArray, UInt8Array,
+};
 use futures::{future::BoxFuture, FutureExt};
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
     encoder::{ArrayEncoder, EncodedArray},
     format::pb,
     EncodingsIo,
@@ -20,17 +22,29 @@ use crate::encodings::logical::primitive::PrimitiveFieldDecoder;
 
 use arrow_schema::DataType;
 use bytes::BytesMut;
-use lance_core::Result;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
 use std::collections::HashMap;
 
 use crate::encodings::utils::new_primitive_array;
 use arrow_array::cast::AsArray;
 
+// Where the dictionary of a page comes from
+#[derive(Debug)]
+enum DictionaryItems {
+    // The dictionary is stored in the page
+    InPage {
+        items_scheduler: Arc<dyn PageScheduler>,
+        num_dictionary_items: u32,
+    },
+    // The dictionary is shared by many pages and was provided by the reader
+    Shared(ArrayRef),
+}
+
 #[derive(Debug)]
 pub struct DictionaryPageScheduler {
     indices_scheduler: Arc<dyn PageScheduler>,
-    items_scheduler: Arc<dyn PageScheduler>,
-    num_dictionary_items: u32,
+    items: DictionaryItems,
 }
 
 impl DictionaryPageScheduler {
@@ -41,9 +55,121 @@ impl DictionaryPageScheduler {
     ) -> Self {
         Self {
             indices_scheduler,
-            items_scheduler,
-            num_dictionary_items,
+            items: DictionaryItems::InPage {
+                items_scheduler,
+                num_dictionary_items,
+            },
+        }
+    }
+
+    /// Creates a scheduler for a page that only stores indices into `dictionary`
+    ///
+    /// The dictionary is shared by many pages, it is not read from the page.  It must be
+    /// a string array with at most 255 values (index 0 is reserved for nulls) and it must
+    /// have the `num_dictionary_items` values that the page was written with.
+    pub fn try_new_shared(
+        indices_scheduler: Arc<dyn PageScheduler>,
+        dictionary: ArrayRef,
+        num_dictionary_items: u32,
+    ) -> Result<Self> {
+        if *dictionary.data_type() != DataType::Utf8 {
+            return Err(Error::unsupported_type(
+                dictionary.data_type(),
+                "shared dictionaries must be strings",
+                location!(),
+            ));
+        }
+        if dictionary.len() > MAX_SHARED_DICTIONARY_SIZE {
+            return Err(Error::invalid_input(
+                format!(
+                    "a shared dictionary can have at most {} values but it has {}",
+                    MAX_SHARED_DICTIONARY_SIZE,
+                    dictionary.len()
+                ),
+                location!(),
+            ));
+        }
+        if dictionary.len() != num_dictionary_items as usize {
+            return Err(Error::invalid_input(
+                format!(
+                    "the page was written with a shared dictionary of {} values but the dictionary provided has {} values",
+                    num_dictionary_items,
+                    dictionary.len()
+                ),
+                location!(),
+            ));
+        }
+        Ok(Self {
+            indices_scheduler,
+            items: DictionaryItems::Shared(dictionary),
+        })
+    }
+
+    // Schedules the dictionary items and, once loaded, decodes all of them
+    //
+    // Returns the number of buffers of the items and the decoded items
+    fn schedule_items(
+        &self,
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<(u32, ArrayRef)>> {
+        let (items_scheduler, num_dictionary_items) = match &self.items {
+            DictionaryItems::InPage {
+                items_scheduler,
+                num_dictionary_items,
+            } => (items_scheduler, *num_dictionary_items),
+            // The same buffers as a binary page decoder of flat bytes
+            DictionaryItems::Shared(dictionary) => {
+                return std::future::ready(Ok((3, dictionary.clone()))).boxed()
+            }
+        };
+        let items_range = 0..(num_dictionary_items as u64);
+        let items_page_decoder = items_scheduler.schedule_ranges(
+            std::slice::from_ref(&items_range),
+            scheduler,
+            top_level_row,
+        );
+
+        let copy_size = num_dictionary_items as u64;
+
+        async move {
+            let items_decoder: Arc<dyn PrimitivePageDecoder> = Arc::from(items_page_decoder.await?);
+
+            let mut primitive_wrapper = PrimitiveFieldDecoder::new_from_data(
+                items_decoder.clone(),
+                DataType::Utf8,
+                copy_size,
+            );
+
+            // Decode all items
+            let drained_task = primitive_wrapper.drain(copy_size)?;
+            let items_decode_task = drained_task.task;
+            let decoded_dict = items_decode_task.decode()?;
+            Ok((items_decoder.num_buffers(), decoded_dict))
         }
+        .boxed()
+    }
+
+    /// Loads and decodes only the dictionary of the page, without reading the indices
+    ///
+    /// This returns the distinct non-null values of the page, in order of first appearance,
+    /// which is useful for cardinality estimation or for building a global dictionary.
+    pub fn decode_dictionary_only(
+        &self,
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<ArrayRef>> {
+        self.schedule_items(scheduler, top_level_row)
+            .map(|items| {
+                let (_, decoded_dict) = items?;
+                // A page that only has nulls is encoded with a dictionary of a single null
+                if decoded_dict.null_count() == decoded_dict.len() {
+                    Ok(new_empty_array(decoded_dict.data_type()))
+                } else {
+                    Ok(decoded_dict)
+                }
+            })
+            .boxed()
     }
 }
 
@@ -67,46 +193,45 @@ impl PageScheduler for DictionaryPageScheduler {
                 .schedule_ranges(ranges, scheduler, top_level_row);
 
         // Schedule items for decoding
-        let items_range = 0..(self.num_dictionary_items as u64);
-        let items_page_decoder = self.items_scheduler.schedule_ranges(
-            std::slice::from_ref(&items_range),
-            scheduler,
-            top_level_row,
-        );
-
-        let copy_size = self.num_dictionary_items as u64;
+        let items = self.schedule_items(scheduler, top_level_row);
 
         tokio::spawn(async move {
-            let items_decoder: Arc<dyn PrimitivePageDecoder> = Arc::from(items_page_decoder.await?);
-
-            let mut primitive_wrapper = PrimitiveFieldDecoder::new_from_data(
-                items_decoder.clone(),
-                DataType::Utf8,
-                copy_size,
-            );
-
-            // Decode all items
-            let drained_task = primitive_wrapper.drain(copy_size)?;
-            let items_decode_task = drained_task.task;
-            let decoded_dict = items_decode_task.decode()?;
+            let (num_item_buffers, decoded_dict) = items.await?;
 
             let indices_decoder: Box<dyn PrimitivePageDecoder> = indices_page_decoder.await?;
 
             Ok(Box::new(DictionaryPageDecoder {
                 decoded_dict,
                 indices_decoder,
-                items_decoder,
+                num_item_buffers,
             }) as Box<dyn PrimitivePageDecoder>)
         })
         .map(|join_handle| join_handle.unwrap())
         .boxed()
     }
+
+    fn estimate_cost(&self, ranges: &[std::ops::Range<u64>]) -> DecodeCost {
+        let indices_cost = self.indices_scheduler.estimate_cost(ranges);
+        match &self.items {
+            // The entire dictionary is always loaded
+            DictionaryItems::InPage {
+                items_scheduler,
+                num_dictionary_items,
+            } => {
+                let items_range = 0..(*num_dictionary_items as u64);
+                indices_cost
+                    .combine(items_scheduler.estimate_cost(std::slice::from_ref(&items_range)))
+            }
+            DictionaryItems::Shared(_) => indices_cost,
+        }
+        .with_min_cpu_class(DecodeCpuClass::Unpack)
+    }
 }
 
 struct DictionaryPageDecoder {
     decoded_dict: Arc<dyn Array>,
     indices_decoder: Box<dyn PrimitivePageDecoder>,
-    items_decoder: Arc<dyn PrimitivePageDecoder>,
+    num_item_buffers: u32,
 }
 
 impl PrimitivePageDecoder for DictionaryPageDecoder {
@@ -136,9 +261,9 @@ impl PrimitivePageDecoder for DictionaryPageDecoder {
             })
             .collect();
 
-        // Build dictionary array using indices and items
-        let dict_array =
-            DictionaryArray::<UInt8Type>::try_new(adjusted_indices, dictionary).unwrap();
+        // Build dictionary array using indices and items, this fails if an index is out of
+        // the bounds of the dictionary
+        let dict_array = DictionaryArray::<UInt8Type>::try_new(adjusted_indices, dictionary)?;
         let string_array = arrow_cast::cast(&dict_array, &DataType::Utf8).unwrap();
         let string_array = string_array.as_any().downcast_ref::<StringArray>().unwrap();
 
@@ -165,8 +290,27 @@ impl PrimitivePageDecoder for DictionaryPageDecoder {
         ])
     }
 
+    fn decode_dictionary(&self, rows_to_skip: u64, num_rows: u64) -> Result<Option<ArrayRef>> {
+        let indices_buffers = self
+            .indices_decoder
+            .decode(rows_to_skip, num_rows, &mut false)?;
+        let indices_array =
+            new_primitive_array::<UInt8Type>(indices_buffers, num_rows, &DataType::UInt8);
+        // Index 0 is reserved for nulls
+        let keys = indices_array
+            .as_primitive::<UInt8Type>()
+            .iter()
+            .map(|index| match index {
+                Some(0) | None => None,
+                Some(index) => Some(index as i32 - 1),
+            })
+            .collect::<Int32Array>();
+        let dict_array = DictionaryArray::<Int32Type>::try_new(keys, self.decoded_dict.clone())?;
+        Ok(Some(Arc::new(dict_array)))
+    }
+
     fn num_buffers(&self) -> u32 {
-        self.items_decoder.num_buffers() + 2
+        self.num_item_buffers + 2
     }
 }
 
@@ -263,8 +407,116 @@ impl ArrayEncoder for DictionaryEncoder {
                         indices: Some(Box::new(encoded_indices.encoding)),
                         items: Some(Box::new(encoded_items.encoding)),
                         num_dictionary_items: dict_size,
+                        shared_dictionary: None,
+                    },
+                ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// The most values a shared dictionary can have (index 0 is reserved for nulls)
+pub const MAX_SHARED_DICTIONARY_SIZE: usize = u8::MAX as usize;
+
+/// Encodes strings as indices into a dictionary that is shared by many pages
+///
+/// Only the indices are written to the page.  The dictionary must be stored elsewhere
+/// (e.g. once per file) and provided to the reader, with the same id, through
+/// [`super::FileBuffers::shared_dictionaries`].
+#[derive(Debug)]
+pub struct SharedDictionaryEncoder {
+    indices_encoder: Box<dyn ArrayEncoder>,
+    dictionary_id: u32,
+    num_dictionary_items: u32,
+    indices: HashMap<String, u8>,
+}
+
+impl SharedDictionaryEncoder {
+    /// Creates an encoder for indices into `dictionary`, which has the id `dictionary_id`
+    ///
+    /// The dictionary values must be distinct and not null
+    pub fn try_new(
+        indices_encoder: Box<dyn ArrayEncoder>,
+        dictionary_id: u32,
+        dictionary: &StringArray,
+    ) -> Result<Self> {
+        if dictionary.len() > MAX_SHARED_DICTIONARY_SIZE {
+            return Err(Error::invalid_input(
+                format!(
+                    "a shared dictionary can have at most {} values but it has {}",
+                    MAX_SHARED_DICTIONARY_SIZE,
+                    dictionary.len()
+                ),
+                location!(),
+            ));
+        }
+        let mut indices = HashMap::with_capacity(dictionary.len());
+        for (idx, value) in dictionary.iter().enumerate() {
+            let Some(value) = value else {
+                return Err(Error::invalid_input(
+                    "a shared dictionary cannot contain nulls",
+                    location!(),
+                ));
+            };
+            // Index 0 is reserved for nulls
+            if indices.insert(value.to_string(), idx as u8 + 1).is_some() {
+                return Err(Error::invalid_input(
+                    format!("the shared dictionary contains {:?} twice", value),
+                    location!(),
+                ));
+            }
+        }
+        Ok(Self {
+            indices_encoder,
+            dictionary_id,
+            num_dictionary_items: dictionary.len() as u32,
+            indices,
+        })
+    }
+}
+
+impl ArrayEncoder for SharedDictionaryEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let total_len = arrays.iter().map(|arr| arr.len()).sum();
+        let mut indices = Vec::with_capacity(total_len);
+        for arr in arrays {
+            for value in arrow_array::cast::as_string_array(arr) {
+                indices.push(match value {
+                    None => 0,
+                    Some(value) => *self.indices.get(value).ok_or_else(|| {
+                        Error::invalid_input(
+                            format!(
+                                "{:?} is not in the shared dictionary {}",
+                                value, self.dictionary_id
+                            ),
+                            location!(),
+                        )
+                    })?,
+                });
+            }
+        }
+        let index_array = Arc::new(UInt8Array::from(indices)) as ArrayRef;
+        let encoded_indices = self.indices_encoder.encode(&[index_array], buffer_index)?;
+
+        Ok(EncodedArray {
+            buffers: encoded_indices.buffers,
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::Dictionary(Box::new(
+                    pb::Dictionary {
+                        indices: Some(Box::new(encoded_indices.encoding)),
+                        items: None,
+                        num_dictionary_items: self.num_dictionary_items,
+                        shared_dictionary: Some(pb::SharedDictionary {
+                            id: self.dictionary_id,
+                        }),
                     },
                 ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
         })
     }
@@ -275,16 +527,34 @@ pub mod tests {
 
     use arrow_array::{
         builder::{LargeStringBuilder, StringBuilder},
-        ArrayRef, StringArray, UInt8Array,
+        cast::AsArray,
+        types::Int32Type,
+        Array, ArrayRef, Int32Array, RecordBatch, StringArray, UInt8Array,
     };
-    use arrow_schema::{DataType, Field};
+    use arrow_schema::{DataType, Field, Schema};
+    use lance_core::{error::EncodingError, Error};
     use std::{sync::Arc, vec};
 
-    use crate::testing::{
-        check_round_trip_encoding_of_data, check_round_trip_encoding_random, TestCases,
+    use crate::{
+        decoder::{decode_batch, DecoderMiddlewareChain, FilterExpression},
+        encoder::{
+            encode_batch, ArrayEncoder, ArrayEncodingStrategy, CoreArrayEncodingStrategy,
+            CoreFieldEncodingStrategy,
+        },
+        encodings::physical::{
+            basic::BasicEncoder,
+            decoder_from_array_encoding,
+            value::{CompressionScheme, ValueEncoder},
+        },
+        format::pb,
+        testing::{
+            check_round_trip_encoding_of_data, check_round_trip_encoding_random, page_buffers,
+            InMemoryPage, RecordingIo, TestCases,
+        },
+        EncodingsIo,
     };
 
-    use super::encode_dict_indices_and_items;
+    use super::{encode_dict_indices_and_items, DictionaryPageScheduler, SharedDictionaryEncoder};
 
     #[test]
     fn test_encode_dict_nulls() {
@@ -325,6 +595,214 @@ pub mod tests {
         check_round_trip_encoding_random(field).await;
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_decode_as_dictionary() {
+        let values = ["apple", "banana", "cherry"];
+        let strings = Arc::new(StringArray::from_iter(
+            (0..1000).map(|i| (i % 10 != 0).then(|| values[i % 3])),
+        )) as ArrayRef;
+        let schema = Arc::new(Schema::new(vec![Field::new("fruit", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![strings.clone()]).unwrap();
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap());
+        let mut encoded = encode_batch(
+            &batch,
+            lance_schema,
+            &CoreFieldEncodingStrategy::default(),
+            1024 * 1024,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            encoded.page_table[0].page_infos[0].encoding.array_encoding,
+            Some(pb::array_encoding::ArrayEncoding::Dictionary(_))
+        ));
+
+        // Ask for a dictionary instead of the stored string type
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let dict_schema = Schema::new(vec![Field::new("fruit", dict_type.clone(), true)]);
+        encoded.schema = Arc::new(lance_core::datatypes::Schema::try_from(&dict_schema).unwrap());
+        let decoded = decode_batch(
+            &encoded,
+            &FilterExpression::no_filter(),
+            &DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+
+        let dict_array = decoded.column(0).as_dictionary::<Int32Type>();
+        assert_eq!(dict_array.data_type(), &dict_type);
+        // The dictionary holds each distinct value once, in order of first appearance
+        assert_eq!(
+            dict_array.values().as_string::<i32>(),
+            &StringArray::from(vec!["banana", "cherry", "apple"])
+        );
+        let expected_keys = (0..1000_i32)
+            .map(|i| (i % 10 != 0).then(|| (i + 2) % 3))
+            .collect::<Int32Array>();
+        assert_eq!(dict_array.keys(), &expected_keys);
+        assert_eq!(
+            arrow_cast::cast(dict_array, &DataType::Utf8)
+                .unwrap()
+                .as_ref(),
+            strings.as_ref()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_dictionary_only() {
+        let values = ["apple", "banana", "cherry"];
+        for (strings, expected) in [
+            (
+                StringArray::from_iter((0..1000).map(|i| (i % 10 != 0).then(|| values[i % 3]))),
+                vec!["banana", "cherry", "apple"],
+            ),
+            (StringArray::from(vec![None as Option<&str>; 200]), vec![]),
+        ] {
+            let strings = Arc::new(strings) as ArrayRef;
+            let encoded = CoreArrayEncodingStrategy::default()
+                .create_array_encoder(&[strings.clone()])
+                .unwrap()
+                .encode(&[strings.clone()], &mut 0)
+                .unwrap();
+            let Some(pb::array_encoding::ArrayEncoding::Dictionary(dictionary)) =
+                encoded.encoding.array_encoding.as_ref()
+            else {
+                panic!("Expected a dictionary encoding");
+            };
+
+            let page = InMemoryPage::new(&encoded);
+            let buffers = page_buffers(&page.positions_and_sizes, &[]);
+            let indices_scheduler = decoder_from_array_encoding(
+                dictionary.indices.as_ref().unwrap(),
+                &buffers,
+                &DataType::Utf8,
+            )
+            .unwrap();
+            let items_scheduler = decoder_from_array_encoding(
+                dictionary.items.as_ref().unwrap(),
+                &buffers,
+                &DataType::Utf8,
+            )
+            .unwrap();
+            let scheduler = DictionaryPageScheduler::new(
+                indices_scheduler.into(),
+                items_scheduler.into(),
+                dictionary.num_dictionary_items,
+            );
+
+            let recording_io = Arc::new(RecordingIo::new(page.data.clone()));
+            let io = recording_io.clone() as Arc<dyn EncodingsIo>;
+            let distinct = scheduler.decode_dictionary_only(&io, 0).await.unwrap();
+            assert_eq!(distinct.as_string::<i32>(), &StringArray::from(expected));
+
+            // The indices are a single flat buffer which must not have been read
+            let Some(pb::array_encoding::ArrayEncoding::Nullable(nullable)) =
+                dictionary.indices.as_ref().unwrap().array_encoding.as_ref()
+            else {
+                panic!("Expected nullable indices");
+            };
+            let Some(pb::nullable::Nullability::NoNulls(no_nulls)) = nullable.nullability.as_ref()
+            else {
+                panic!("Expected indices without nulls");
+            };
+            let Some(pb::array_encoding::ArrayEncoding::Flat(flat)) =
+                no_nulls.values.as_ref().unwrap().array_encoding.as_ref()
+            else {
+                panic!("Expected flat indices");
+            };
+            let (indices_start, indices_size) =
+                page.positions_and_sizes[flat.buffer.as_ref().unwrap().buffer_index as usize];
+            let indices_range = indices_start..indices_start + indices_size;
+            let requests = recording_io.requests().concat();
+            assert!(!requests.is_empty());
+            for request in requests.iter() {
+                assert!(
+                    request.end <= indices_range.start || request.start >= indices_range.end,
+                    "{:?} overlaps the indices {:?}",
+                    request,
+                    indices_range
+                );
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_shared_dictionary() {
+        let dictionary = StringArray::from(vec!["apple", "banana", "cherry"]);
+        let pages = [
+            StringArray::from(vec![Some("cherry"), None, Some("apple"), Some("cherry")]),
+            StringArray::from(vec![Some("banana"), Some("banana"), None]),
+        ];
+        let encoder = SharedDictionaryEncoder::try_new(
+            Box::new(BasicEncoder::new(Box::new(
+                ValueEncoder::try_new(&DataType::UInt8, CompressionScheme::None).unwrap(),
+            ))),
+            7,
+            &dictionary,
+        )
+        .unwrap();
+
+        let shared_dictionaries = [(7, Arc::new(dictionary) as ArrayRef)];
+        for values in &pages {
+            let encoded = encoder
+                .encode(&[Arc::new(values.clone()) as ArrayRef], &mut 0)
+                .unwrap();
+            // The page doesn't hold the dictionary
+            let page = InMemoryPage::new(&encoded);
+            let Some(pb::array_encoding::ArrayEncoding::Dictionary(dictionary)) =
+                page.encoding.array_encoding.as_ref()
+            else {
+                panic!("Expected a dictionary encoding");
+            };
+            assert!(dictionary.items.is_none());
+            assert_eq!(dictionary.shared_dictionary.as_ref().unwrap().id, 7);
+
+            let scheduler = page
+                .scheduler(&DataType::Utf8, &shared_dictionaries)
+                .unwrap();
+            let num_rows = values.len() as u64;
+            let decoder = scheduler
+                .schedule_ranges(std::slice::from_ref(&(0..num_rows)), &page.io(), 0)
+                .await
+                .unwrap();
+            let decoded = decoder.decode_dictionary(0, num_rows).unwrap().unwrap();
+            assert_eq!(
+                arrow_cast::cast(&decoded, &DataType::Utf8)
+                    .unwrap()
+                    .as_string::<i32>(),
+                values
+            );
+
+            // The dictionary must be provided
+            let err = page.scheduler(&DataType::Utf8, &[]).unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+            // and it must have as many values as the dictionary the page was written with
+            let other_dictionaries = [(7, Arc::new(StringArray::from(vec!["apple"])) as ArrayRef)];
+            assert!(page
+                .scheduler(&DataType::Utf8, &other_dictionaries)
+                .is_err());
+        }
+
+        // Values that are not in the dictionary can't be encoded
+        assert!(encoder
+            .encode(
+                &[Arc::new(StringArray::from(vec!["durian"])) as ArrayRef],
+                &mut 0
+            )
+            .is_err());
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_simple_utf8() {
         let string_array = StringArray::from(vec![Some("abc"), Some("de"), None, Some("fgh")]);
@@ -10,7 +10,7 @@ use lance_core::Result;
 use log::trace;
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{DecodeCost, PageScheduler, PrimitivePageDecoder},
     encoder::{ArrayEncoder, EncodedArray},
     format::pb,
     EncodingsIo,
@@ -45,14 +45,16 @@ impl PageScheduler for FixedListScheduler {
             .iter()
             .map(|range| (range.start * self.dimension as u64)..(range.end * self.dimension as u64))
             .collect::<Vec<_>>();
-        trace!(
-            "Expanding {} fsl ranges across {}..{} to item ranges across {}..{}",
-            ranges.len(),
-            ranges[0].start,
-            ranges[ranges.len() - 1].end,
-            expanded_ranges[0].start,
-            expanded_ranges[expanded_ranges.len() - 1].end
-        );
+        if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+            trace!(
+                "Expanding {} fsl ranges across {}..{} to item ranges across {}..{}",
+                ranges.len(),
+                first.start,
+                last.end,
+                first.start * self.dimension as u64,
+                last.end * self.dimension as u64
+            );
+        }
         let inner_page_decoder =
             self.items_scheduler
                 .schedule_ranges(&expanded_ranges, scheduler, top_level_row);
@@ -66,6 +68,14 @@ impl PageScheduler for FixedListScheduler {
         }
         .boxed()
     }
+
+    fn estimate_cost(&self, ranges: &[std::ops::Range<u64>]) -> DecodeCost {
+        let expanded_ranges = ranges
+            .iter()
+            .map(|range| (range.start * self.dimension as u64)..(range.end * self.dimension as u64))
+            .collect::<Vec<_>>();
+        self.items_scheduler.estimate_cost(&expanded_ranges)
+    }
 }
 
 pub struct FixedListDecoder {
@@ -85,6 +95,10 @@ impl PrimitivePageDecoder for FixedListDecoder {
         self.items_decoder.decode(rows_to_skip, num_rows, all_null)
     }
 
+    fn can_skip(&self) -> bool {
+        self.items_decoder.can_skip()
+    }
+
     fn num_buffers(&self) -> u32 {
         self.items_decoder.num_buffers()
     }
@@ -121,6 +135,9 @@ impl ArrayEncoder for FslEncoder {
                         items: Some(Box::new(items_page.encoding)),
                     },
                 ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
         })
     }
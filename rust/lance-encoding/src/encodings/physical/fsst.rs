@@ -13,7 +13,7 @@ use futures::{future::BoxFuture, FutureExt};
 use lance_core::Result;
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
     encoder::{ArrayEncoder, EncodedArray},
     format::pb,
     EncodingsIo,
@@ -55,6 +55,12 @@ impl PageScheduler for FsstPageScheduler {
         }
         .boxed()
     }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        self.inner_scheduler
+            .estimate_cost(ranges)
+            .with_min_cpu_class(DecodeCpuClass::Decompress)
+    }
 }
 
 struct FsstPageDecoder {
@@ -184,6 +190,9 @@ impl ArrayEncoder for FsstArrayEncoder {
                         symbol_table,
                     },
                 ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
             },
         })
     }
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use futures::{future::BoxFuture, FutureExt};
+use log::trace;
+use snafu::{location, Location};
+
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{PageScheduler, PrimitivePageDecoder},
+    EncodingsIo,
+};
+
+/// A range of rows from a single page that should be read as part of a [`MultiPageDecoder`]
+#[derive(Debug, Clone)]
+pub struct PageSlice {
+    pub scheduler: Arc<dyn PageScheduler>,
+    /// The rows to read, relative to the start of the page
+    pub range: Range<u64>,
+    /// The width of a single value as produced by the page's decoder
+    pub bytes_per_value: u64,
+}
+
+struct LoadedPage {
+    decoder: Box<dyn PrimitivePageDecoder>,
+    num_rows: u64,
+    bytes_per_value: u64,
+}
+
+/// A decoder for fixed-width values that spans several pages
+///
+/// The rows of each page slice are presented, in order, as one contiguous run of rows so
+/// that a single call to `decode` can cross page boundaries.
+///
+/// Pages may decode into different widths (e.g. pages written before a column was
+/// widened from int16 to int32).  Narrower values are widened to the output width,
+/// sign extending them if the values are signed.
+pub struct MultiPageDecoder {
+    pages: Vec<LoadedPage>,
+    bytes_per_value: u64,
+    signed: bool,
+}
+
+impl MultiPageDecoder {
+    /// Schedules the I/O for every page slice and returns a future that yields the decoder
+    /// once all pages are loaded
+    ///
+    /// * `bytes_per_value` - the width of a single value in the decoded output
+    /// * `signed` - true if narrower values need to be sign extended when widened
+    pub fn schedule(
+        slices: &[PageSlice],
+        bytes_per_value: u64,
+        signed: bool,
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Self>> {
+        if let Some(slice) = slices
+            .iter()
+            .find(|slice| slice.bytes_per_value == 0 || slice.bytes_per_value > bytes_per_value)
+        {
+            let err = Error::invalid_input(
+                format!(
+                    "Cannot decode a page with {} bytes per value into {} bytes per value",
+                    slice.bytes_per_value, bytes_per_value
+                ),
+                location!(),
+            );
+            return std::future::ready(Err(err)).boxed();
+        }
+        trace!(
+            "Scheduling a multi-page decode across {} pages",
+            slices.len()
+        );
+        let page_decoders = slices
+            .iter()
+            .map(|slice| {
+                let decoder = slice.scheduler.schedule_ranges(
+                    std::slice::from_ref(&slice.range),
+                    scheduler,
+                    top_level_row,
+                );
+                (
+                    decoder,
+                    slice.range.end - slice.range.start,
+                    slice.bytes_per_value,
+                )
+            })
+            .collect::<Vec<_>>();
+        async move {
+            let mut pages = Vec::with_capacity(page_decoders.len());
+            for (decoder, num_rows, page_bytes_per_value) in page_decoders {
+                let decoder = decoder.await?;
+                if decoder.num_buffers() != 1 {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "A multi-page decoder requires pages with one buffer but a page has {}",
+                            decoder.num_buffers()
+                        ),
+                        location!(),
+                    ));
+                }
+                pages.push(LoadedPage {
+                    decoder,
+                    num_rows,
+                    bytes_per_value: page_bytes_per_value,
+                });
+            }
+            Ok(Self {
+                pages,
+                bytes_per_value,
+                signed,
+            })
+        }
+        .boxed()
+    }
+
+    /// The total number of rows across all pages
+    pub fn num_rows(&self) -> u64 {
+        self.pages.iter().map(|page| page.num_rows).sum()
+    }
+
+    // Appends the page values to `dest`, widening each of them if needed
+    fn widen_into(&self, src: &[u8], src_bytes_per_value: u64, dest: &mut BytesMut) {
+        if src_bytes_per_value == self.bytes_per_value {
+            dest.extend_from_slice(src);
+            return;
+        }
+        let padding = (self.bytes_per_value - src_bytes_per_value) as usize;
+        for value in src.chunks_exact(src_bytes_per_value as usize) {
+            let is_negative = self.signed && value[value.len() - 1] & 0x80 != 0;
+            dest.extend_from_slice(value);
+            dest.extend(std::iter::repeat(if is_negative { 0xFF } else { 0 }).take(padding));
+        }
+    }
+}
+
+impl PrimitivePageDecoder for MultiPageDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut dest = BytesMut::with_capacity((num_rows * self.bytes_per_value) as usize);
+        let mut rows_to_skip = rows_to_skip;
+        let mut rows_remaining = num_rows;
+        for page in &self.pages {
+            if rows_remaining == 0 {
+                break;
+            }
+            if rows_to_skip >= page.num_rows {
+                rows_to_skip -= page.num_rows;
+                continue;
+            }
+            let rows_to_take = rows_remaining.min(page.num_rows - rows_to_skip);
+            let buffers = page.decoder.decode(rows_to_skip, rows_to_take, all_null)?;
+            self.widen_into(&buffers[0], page.bytes_per_value, &mut dest);
+            rows_to_skip = 0;
+            rows_remaining -= rows_to_take;
+        }
+        if rows_remaining > 0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot decode {} rows from a multi-page decoder with {} rows",
+                    num_rows,
+                    self.num_rows()
+                ),
+                location!(),
+            ));
+        }
+        Ok(vec![dest])
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int16Array, Int32Array};
+    use arrow_buffer::{Buffer, ScalarBuffer};
+    use bytes::BytesMut;
+
+    use crate::{
+        decoder::{PageScheduler, PrimitivePageDecoder},
+        encodings::physical::bitpack::tests::bitpack_page,
+        BufferScheduler, EncodingsIo,
+    };
+
+    use super::{MultiPageDecoder, PageSlice};
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_across_bitpacked_pages() {
+        // Two pages of 1000 rows.  The first was written as int16 and packs into 4 bits, the
+        // second was written as int32 and packs into 10 bits
+        let first_values = (0..1000).map(|i| (i % 16) - 8).collect::<Vec<i16>>();
+        let second_values = (0..1000).map(|i| i - 500).collect::<Vec<i32>>();
+        let first = Arc::new(Int16Array::from(first_values.clone())) as ArrayRef;
+        let second = Arc::new(Int32Array::from(second_values.clone())) as ArrayRef;
+
+        let (first_scheduler, first_data, first_bits) = bitpack_page(&[first], 0);
+        let (second_scheduler, second_data, second_bits) =
+            bitpack_page(&[second], first_data.len() as u64);
+        assert_eq!(first_bits, 4);
+        assert_eq!(second_bits, 10);
+
+        let mut data = BytesMut::from(first_data.as_ref());
+        data.extend_from_slice(&second_data);
+        let io = Arc::new(BufferScheduler::new(data.freeze())) as Arc<dyn EncodingsIo>;
+
+        let expected = first_values
+            .iter()
+            .map(|v| *v as i32)
+            .chain(second_values.iter().copied())
+            .collect::<Vec<_>>();
+
+        let first_scheduler = Arc::new(first_scheduler) as Arc<dyn PageScheduler>;
+        let second_scheduler = Arc::new(second_scheduler) as Arc<dyn PageScheduler>;
+        let slices = vec![
+            PageSlice {
+                scheduler: first_scheduler.clone(),
+                range: 0..1000,
+                bytes_per_value: 2,
+            },
+            PageSlice {
+                scheduler: second_scheduler.clone(),
+                range: 0..1000,
+                bytes_per_value: 4,
+            },
+        ];
+        let decoder = MultiPageDecoder::schedule(&slices, 4, true, &io, 0)
+            .await
+            .unwrap();
+        assert_eq!(decoder.num_rows(), 2000);
+
+        let decode = |decoder: &MultiPageDecoder, rows_to_skip: u64, num_rows: u64| {
+            let buffers = decoder.decode(rows_to_skip, num_rows, &mut false).unwrap();
+            assert_eq!(buffers.len(), 1);
+            ScalarBuffer::<i32>::from(Buffer::from(buffers[0].clone().freeze())).to_vec()
+        };
+
+        // A 1500 row range that spans the page boundary
+        assert_eq!(decode(&decoder, 250, 1500), &expected[250..1750]);
+        // Ranges entirely within one page
+        assert_eq!(decode(&decoder, 10, 20), &expected[10..30]);
+        assert_eq!(decode(&decoder, 1990, 10), &expected[1990..2000]);
+        assert!(decoder.decode(1990, 20, &mut false).is_err());
+
+        // Slices that only cover part of each page
+        let slices = vec![
+            PageSlice {
+                scheduler: first_scheduler,
+                range: 500..1000,
+                bytes_per_value: 2,
+            },
+            PageSlice {
+                scheduler: second_scheduler,
+                range: 0..1000,
+                bytes_per_value: 4,
+            },
+        ];
+        let decoder = MultiPageDecoder::schedule(&slices, 4, true, &io, 0)
+            .await
+            .unwrap();
+        assert_eq!(decode(&decoder, 0, 1500), &expected[500..2000]);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_rejects_narrowing() {
+        let values = Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef;
+        let (scheduler, data, _) = bitpack_page(&[values], 0);
+        let io = Arc::new(BufferScheduler::new(data)) as Arc<dyn EncodingsIo>;
+        let slices = vec![PageSlice {
+            scheduler: Arc::new(scheduler),
+            range: 0..3,
+            bytes_per_value: 4,
+        }];
+        assert!(MultiPageDecoder::schedule(&slices, 2, true, &io, 0)
+            .await
+            .is_err());
+    }
+}
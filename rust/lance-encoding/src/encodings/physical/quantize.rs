@@ -0,0 +1,447 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Float32Type, Float64Type},
+    Array, ArrayRef, Int64Array,
+};
+use arrow_schema::DataType;
+use bytes::BytesMut;
+use futures::{future::BoxFuture, FutureExt};
+use snafu::{location, Location};
+
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encoder::{ArrayEncoder, EncodedArray},
+    format::pb,
+    EncodingsIo,
+};
+
+use super::bitpack::{num_compressed_bits, BitpackedArrayEncoder};
+
+/// Returns true if the data type can be encoded with [`QuantizeEncoder`]
+pub fn supports_quantization(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Float32 | DataType::Float64)
+}
+
+/// The affine mapping used to quantize floats
+///
+/// A value `x` is stored as `round(x / scale) + zero_point` and decoded as
+/// `(q - zero_point) * scale` so the reconstruction error is at most `scale / 2`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizeParams {
+    /// The distance between two consecutive representable values
+    pub scale: f64,
+    /// The integer that represents 0.0
+    pub zero_point: i64,
+}
+
+impl QuantizeParams {
+    pub fn new(scale: f64, zero_point: i64) -> Self {
+        Self { scale, zero_point }
+    }
+
+    fn quantize(&self, value: f64) -> Result<i64> {
+        let scaled = (value / self.scale).round();
+        // i64::MAX as f64 rounds up to 2^63 and so the upper bound is exclusive
+        if scaled.is_finite() && scaled >= i64::MIN as f64 && scaled < i64::MAX as f64 {
+            if let Some(quantized) = (scaled as i64).checked_add(self.zero_point) {
+                return Ok(quantized);
+            }
+        }
+        Err(Error::invalid_input(
+            format!(
+                "Cannot quantize the value {} with scale {} and zero point {}, the result does not fit in a 64-bit integer",
+                value, self.scale, self.zero_point
+            ),
+            location!(),
+        ))
+    }
+
+    fn dequantize(&self, quantized: i64) -> f64 {
+        quantized.wrapping_sub(self.zero_point) as f64 * self.scale
+    }
+}
+
+/// Lossily encodes floats as bitpacked integers
+///
+/// This is never picked automatically, it must be enabled with
+/// [`crate::encoder::CoreArrayEncodingStrategy::with_quantization`].  It works well for
+/// values that only need a fixed absolute precision (e.g. ML features) since the
+/// quantized integers are small and bitpack into a few bits each.
+///
+/// Null slots are stored as the zero point.
+#[derive(Debug)]
+pub struct QuantizeEncoder {
+    params: QuantizeParams,
+}
+
+impl QuantizeEncoder {
+    pub fn try_new(params: QuantizeParams) -> Result<Self> {
+        if !params.scale.is_finite() || params.scale <= 0.0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "The quantization scale must be a positive finite number, received {}",
+                    params.scale
+                ),
+                location!(),
+            ));
+        }
+        Ok(Self { params })
+    }
+
+    fn quantize_array(&self, arr: &dyn Array, dest: &mut Vec<i64>) -> Result<()> {
+        let values = match arr.data_type() {
+            DataType::Float32 => arr
+                .as_primitive::<Float32Type>()
+                .values()
+                .iter()
+                .map(|v| *v as f64)
+                .collect::<Vec<_>>(),
+            DataType::Float64 => arr.as_primitive::<Float64Type>().values().to_vec(),
+            data_type => {
+                return Err(Error::unsupported_type(
+                    data_type,
+                    "quantization is only supported for Float32 and Float64",
+                    location!(),
+                ))
+            }
+        };
+        for (idx, value) in values.into_iter().enumerate() {
+            if arr.is_null(idx) {
+                dest.push(self.params.zero_point);
+            } else {
+                dest.push(self.params.quantize(value)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ArrayEncoder for QuantizeEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        let mut quantized = Vec::with_capacity(arrays.iter().map(|arr| arr.len()).sum());
+        for arr in arrays {
+            self.quantize_array(arr.as_ref(), &mut quantized)?;
+        }
+        let quantized = vec![Arc::new(Int64Array::from(quantized)) as ArrayRef];
+
+        // Every value fits in 64 bits so this never fails for an Int64Array
+        let num_bits = num_compressed_bits(&quantized).unwrap();
+        let encoded_values =
+            BitpackedArrayEncoder::new(num_bits).encode(&quantized, buffer_index)?;
+
+        Ok(EncodedArray {
+            buffers: encoded_values.buffers,
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::Quantized(Box::new(
+                    pb::Quantized {
+                        scale: self.params.scale,
+                        zero_point: self.params.zero_point,
+                        values: Some(Box::new(encoded_values.encoding)),
+                        uncompressed_bits_per_value: if data_type == &DataType::Float32 {
+                            32
+                        } else {
+                            64
+                        },
+                    },
+                ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// Scheduler for a page of quantized floats
+#[derive(Debug)]
+pub struct QuantizedScheduler {
+    values: Box<dyn PageScheduler>,
+    params: QuantizeParams,
+    bytes_per_value: u64,
+}
+
+impl QuantizedScheduler {
+    pub fn new(
+        values: Box<dyn PageScheduler>,
+        params: QuantizeParams,
+        bytes_per_value: u64,
+    ) -> Self {
+        Self {
+            values,
+            params,
+            bytes_per_value,
+        }
+    }
+}
+
+impl PageScheduler for QuantizedScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let values = self
+            .values
+            .schedule_ranges(ranges, scheduler, top_level_row);
+        let params = self.params;
+        let bytes_per_value = self.bytes_per_value;
+        async move {
+            let values = values.await?;
+            Ok(Box::new(QuantizedPageDecoder {
+                values,
+                params,
+                bytes_per_value,
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        self.values
+            .estimate_cost(ranges)
+            .with_min_cpu_class(DecodeCpuClass::Unpack)
+    }
+}
+
+struct QuantizedPageDecoder {
+    values: Box<dyn PrimitivePageDecoder>,
+    params: QuantizeParams,
+    bytes_per_value: u64,
+}
+
+impl PrimitivePageDecoder for QuantizedPageDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let quantized = self.values.decode(rows_to_skip, num_rows, &mut false)?;
+        let mut dest = BytesMut::with_capacity((num_rows * self.bytes_per_value) as usize);
+        for chunk in quantized[0].chunks_exact(8) {
+            let value = self
+                .params
+                .dequantize(i64::from_le_bytes(chunk.try_into().unwrap()));
+            if self.bytes_per_value == 4 {
+                dest.extend_from_slice(&(value as f32).to_le_bytes());
+            } else {
+                dest.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        Ok(vec![dest])
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        cast::AsArray,
+        types::{Float32Type, Float64Type},
+        Array, ArrayRef, FixedSizeListArray, Float32Array, Float64Array, RecordBatch,
+    };
+    use arrow_schema::{DataType, Field, Schema};
+    use rand::Rng;
+
+    use crate::{
+        decoder::{decode_batch, DecoderMiddlewareChain, FilterExpression},
+        encoder::{
+            encode_batch, ArrayEncoder, CoreArrayEncodingStrategy, CoreFieldEncodingStrategy,
+        },
+        format::pb,
+        testing::InMemoryPage,
+    };
+
+    use super::{QuantizeEncoder, QuantizeParams};
+
+    // Encodes the array and returns the encoding, the encoded size, and the decoded values
+    async fn round_trip(
+        arr: ArrayRef,
+        params: QuantizeParams,
+    ) -> (pb::ArrayEncoding, usize, Vec<f64>) {
+        let encoded = QuantizeEncoder::try_new(params)
+            .unwrap()
+            .encode(&[arr.clone()], &mut 0)
+            .unwrap();
+        assert_eq!(encoded.buffers.len(), 1);
+        let page = InMemoryPage::new(&encoded);
+        #[allow(clippy::single_range_in_vec_init)]
+        let decoded = page
+            .decode(arr.data_type(), &[0..arr.len() as u64])
+            .await
+            .unwrap();
+        let decoded = match arr.data_type() {
+            DataType::Float32 => decoded[0]
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()) as f64)
+                .collect(),
+            _ => decoded[0]
+                .chunks_exact(8)
+                .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect(),
+        };
+        (page.encoding, page.data.len(), decoded)
+    }
+
+    fn compressed_bits(encoding: &pb::ArrayEncoding) -> u64 {
+        let Some(pb::array_encoding::ArrayEncoding::Quantized(quantized)) =
+            encoding.array_encoding.as_ref()
+        else {
+            panic!("Expected a quantized encoding");
+        };
+        let Some(pb::array_encoding::ArrayEncoding::Bitpacked(bitpacked)) =
+            quantized.values.as_ref().unwrap().array_encoding.as_ref()
+        else {
+            panic!("Expected bitpacked values");
+        };
+        bitpacked.compressed_bits_per_value
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_quantize_within_error_bound() {
+        let mut rng = rand::thread_rng();
+        let num_values = 10000;
+        let values = (0..num_values)
+            .map(|_| rng.gen_range(-1.0..1.0))
+            .collect::<Vec<f64>>();
+        let params = QuantizeParams::new(0.001, 0);
+
+        let arrays = vec![
+            Arc::new(Float64Array::from(values.clone())) as ArrayRef,
+            Arc::new(Float32Array::from_iter_values(
+                values.iter().map(|v| *v as f32),
+            )) as ArrayRef,
+        ];
+        for arr in arrays {
+            let (encoding, encoded_size, decoded) = round_trip(arr.clone(), params).await;
+            // Values in [-1000, 1000] need 11 bits (including the sign bit)
+            assert_eq!(compressed_bits(&encoding), 11);
+            assert_eq!(encoded_size, (num_values * 11_usize).div_ceil(8));
+            // The rounding error is at most half a step, plus a little for the f32 cases
+            let bound = params.scale / 2.0 + 1e-6;
+            for (idx, decoded) in decoded.into_iter().enumerate() {
+                let original = match arr.data_type() {
+                    DataType::Float32 => arr.as_primitive::<Float32Type>().value(idx) as f64,
+                    _ => arr.as_primitive::<Float64Type>().value(idx),
+                };
+                assert!(
+                    (decoded - original).abs() <= bound,
+                    "{} decoded as {}",
+                    original,
+                    decoded
+                );
+            }
+        }
+
+        // A zero point shifts the integers, e.g. to keep positive values unsigned
+        let positive = Arc::new(Float64Array::from(vec![0.0, 0.5, 1.25, 2.0])) as ArrayRef;
+        let (_, _, decoded) = round_trip(positive, QuantizeParams::new(0.25, -4)).await;
+        assert_eq!(decoded, vec![0.0, 0.5, 1.25, 2.0]);
+    }
+
+    #[test]
+    fn test_quantize_invalid() {
+        assert!(QuantizeEncoder::try_new(QuantizeParams::new(0.0, 0)).is_err());
+        assert!(QuantizeEncoder::try_new(QuantizeParams::new(f64::NAN, 0)).is_err());
+
+        let encoder = QuantizeEncoder::try_new(QuantizeParams::new(1e-6, 0)).unwrap();
+        for values in [vec![1.0, f64::NAN], vec![f64::INFINITY], vec![1e20]] {
+            let arr = Arc::new(Float64Array::from(values)) as ArrayRef;
+            assert!(encoder.encode(&[arr], &mut 0).is_err());
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_quantize_strategy() {
+        let floats = Arc::new(Float32Array::from(vec![
+            Some(0.1),
+            None,
+            Some(-0.5),
+            Some(f32::NAN),
+        ])) as ArrayRef;
+        let vectors = Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float64, true)),
+            2,
+            Arc::new(Float64Array::from(vec![
+                0.31, -0.62, 0.93, 0.04, 0.5, 0.6, 0.7, 0.8,
+            ])),
+            None,
+        )) as ArrayRef;
+
+        // Quantization is lossy and so it is never used unless requested, NaN is fine here
+        let default_strategy = CoreFieldEncodingStrategy::default();
+        let quantizing_strategy = CoreFieldEncodingStrategy::new(Arc::new(
+            CoreArrayEncodingStrategy::default().with_quantization(QuantizeParams::new(0.01, 0)),
+        ));
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("floats", DataType::Float32, true),
+                Field::new("vectors", vectors.data_type().clone(), true),
+            ])),
+            vec![floats.clone(), vectors.clone()],
+        )
+        .unwrap();
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(batch.schema().as_ref()).unwrap());
+        let encoded = encode_batch(&batch, lance_schema.clone(), &default_strategy, 0)
+            .await
+            .unwrap();
+        let decoded = decode_batch(
+            &encoded,
+            &FilterExpression::no_filter(),
+            &DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(decoded.column(1), &vectors);
+
+        // NaN cannot be quantized
+        assert!(
+            encode_batch(&batch, lance_schema.clone(), &quantizing_strategy, 0)
+                .await
+                .is_err()
+        );
+
+        let batch = batch.slice(0, 3);
+        let encoded = encode_batch(&batch, lance_schema, &quantizing_strategy, 0)
+            .await
+            .unwrap();
+        let decoded = decode_batch(
+            &encoded,
+            &FilterExpression::no_filter(),
+            &DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+        let floats = decoded.column(0).as_primitive::<Float32Type>();
+        assert_eq!(floats.null_count(), 1);
+        assert!(floats.is_null(1));
+        assert_eq!(floats.value(0), 0.1);
+        assert_eq!(floats.value(2), -0.5);
+        let items = decoded.column(1).as_fixed_size_list().values().clone();
+        let items = items.as_primitive::<Float64Type>();
+        assert_eq!(items.len(), 6);
+        for (decoded, expected) in items
+            .values()
+            .iter()
+            .zip([0.31, -0.62, 0.93, 0.04, 0.5, 0.6])
+        {
+            assert!((decoded - expected).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{make_array, Array, ArrayRef, UInt64Array};
+use arrow_buffer::MutableBuffer;
+use arrow_data::ArrayData;
+use arrow_schema::DataType;
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt};
+use log::trace;
+use snafu::{location, Location};
+
+use lance_arrow::DataTypeExt;
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encoder::{ArrayEncoder, EncodedArray},
+    format::pb,
+    EncodingsIo,
+};
+
+use super::{
+    bitpack::{num_compressed_bits, BitpackedArrayEncoder},
+    value::{CompressionScheme, ValueEncoder},
+};
+
+/// Returns true if the data type can be encoded with [`SparseEncoder`]
+pub fn supports_sparse(data_type: &DataType) -> bool {
+    data_type.is_primitive()
+}
+
+/// Returns the fraction of the values in the arrays that are valid and not zero
+pub fn non_zero_fraction(arrays: &[ArrayRef]) -> f64 {
+    let num_values = arrays.iter().map(|arr| arr.len()).sum::<usize>();
+    if num_values == 0 {
+        return 0.0;
+    }
+    let num_non_zero = arrays
+        .iter()
+        .map(|arr| non_default_positions(arr.as_ref(), &[]).count())
+        .sum::<usize>();
+    num_non_zero as f64 / num_values as f64
+}
+
+// Iterates over the offsets (within the array) of the valid values that differ from
+// `default_value`.  An empty `default_value` is treated as zero.
+fn non_default_positions<'a>(
+    arr: &'a dyn Array,
+    default_value: &'a [u8],
+) -> impl Iterator<Item = usize> + 'a {
+    let byte_width = arr.data_type().byte_width();
+    let data = arr.to_data();
+    let start = data.offset() * byte_width;
+    let end = start + data.len() * byte_width;
+    let values = data.buffers()[0].slice_with_length(start, end - start);
+    (0..arr.len()).filter(move |idx| {
+        let value = &values[idx * byte_width..(idx + 1) * byte_width];
+        let is_default = if default_value.is_empty() {
+            value.iter().all(|byte| *byte == 0)
+        } else {
+            value == default_value
+        };
+        arr.is_valid(*idx) && !is_default
+    })
+}
+
+/// Encodes fixed-width values that are almost all zero
+///
+/// Only the positions of the non-zero values (bitpacked) and the non-zero values
+/// themselves are stored.  Null slots are treated as zero, the validity is stored
+/// separately.  This works well for very sparse columns (e.g. counters or one-hot
+/// features) but is much larger than flat encoding for dense data.
+#[derive(Debug, Default)]
+pub struct SparseEncoder {}
+
+impl SparseEncoder {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ArrayEncoder for SparseEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        if !supports_sparse(data_type) {
+            return Err(Error::unsupported_type(
+                data_type,
+                "sparse encoding is only supported for fixed-width primitive values",
+                location!(),
+            ));
+        }
+        let byte_width = data_type.byte_width();
+        let default_value = vec![0; byte_width];
+
+        let mut positions = Vec::new();
+        // MutableBuffer is aligned for any primitive type
+        let mut values = MutableBuffer::new(0);
+        let mut array_offset = 0;
+        for arr in arrays {
+            let data = arr.to_data();
+            let start = data.offset() * byte_width;
+            for idx in non_default_positions(arr.as_ref(), &default_value) {
+                positions.push((array_offset + idx) as u64);
+                let value_start = start + idx * byte_width;
+                values.extend_from_slice(&data.buffers()[0][value_start..value_start + byte_width]);
+            }
+            array_offset += arr.len();
+        }
+        let num_values = positions.len() as u64;
+
+        let positions = vec![Arc::new(UInt64Array::from(positions)) as ArrayRef];
+        // Every position fits in 64 bits so this never fails for a UInt64Array
+        let num_bits = num_compressed_bits(&positions).unwrap();
+        let encoded_positions =
+            BitpackedArrayEncoder::new(num_bits).encode(&positions, buffer_index)?;
+
+        let values = make_array(ArrayData::try_new(
+            data_type.clone(),
+            num_values as usize,
+            None,
+            0,
+            vec![values.into()],
+            vec![],
+        )?);
+        let encoded_values = ValueEncoder::try_new(data_type, CompressionScheme::None)?
+            .encode(&[values], buffer_index)?;
+
+        let mut buffers = encoded_positions.buffers;
+        buffers.extend(encoded_values.buffers);
+
+        Ok(EncodedArray {
+            buffers,
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::Sparse(Box::new(
+                    pb::Sparse {
+                        default_value,
+                        positions: Some(Box::new(encoded_positions.encoding)),
+                        values: Some(Box::new(encoded_values.encoding)),
+                        num_values,
+                        uncompressed_bits_per_value: 8 * byte_width as u64,
+                    },
+                ))),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// Scheduler for a page of sparse values
+///
+/// The positions are needed to find the values in any range and so all of the positions
+/// and values of the page are always loaded.  There should be few of them.
+#[derive(Debug)]
+pub struct SparseScheduler {
+    positions: Box<dyn PageScheduler>,
+    values: Box<dyn PageScheduler>,
+    default_value: Bytes,
+    num_values: u64,
+}
+
+impl SparseScheduler {
+    pub fn try_new(
+        positions: Box<dyn PageScheduler>,
+        values: Box<dyn PageScheduler>,
+        default_value: Bytes,
+        num_values: u64,
+    ) -> Result<Self> {
+        // The default value has the width of the values
+        if default_value.is_empty() {
+            return Err(Error::corrupt_metadata(
+                "a sparse encoding has an empty default value",
+                location!(),
+            ));
+        }
+        Ok(Self {
+            positions,
+            values,
+            default_value,
+            num_values,
+        })
+    }
+
+    fn values_ranges(&self) -> Vec<Range<u64>> {
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = if self.num_values > 0 {
+            vec![0..self.num_values]
+        } else {
+            vec![]
+        };
+        ranges
+    }
+}
+
+impl PageScheduler for SparseScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        trace!(
+            "Scheduling sparse page, loading {} positions and values for {} ranges",
+            self.num_values,
+            ranges.len()
+        );
+        let values_ranges = self.values_ranges();
+        let positions = self
+            .positions
+            .schedule_ranges(&values_ranges, scheduler, top_level_row);
+        let values = self
+            .values
+            .schedule_ranges(&values_ranges, scheduler, top_level_row);
+        let default_value = self.default_value.clone();
+        let num_values = self.num_values;
+        let ranges = ranges.to_vec();
+
+        async move {
+            let positions = positions.await?;
+            let values = values.await?;
+            Ok(Box::new(SparsePageDecoder {
+                positions,
+                values,
+                default_value,
+                num_values,
+                ranges,
+                decoded: Mutex::new(None),
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, _ranges: &[Range<u64>]) -> DecodeCost {
+        let values_ranges = self.values_ranges();
+        self.positions
+            .estimate_cost(&values_ranges)
+            .combine(self.values.estimate_cost(&values_ranges))
+            .with_min_cpu_class(DecodeCpuClass::Unpack)
+    }
+}
+
+struct SparsePageDecoder {
+    positions: Box<dyn PrimitivePageDecoder>,
+    values: Box<dyn PrimitivePageDecoder>,
+    default_value: Bytes,
+    num_values: u64,
+    ranges: Vec<Range<u64>>,
+    // The positions and values of the non-default values, decoded on first use
+    decoded: Mutex<Option<(Vec<u64>, BytesMut)>>,
+}
+
+impl SparsePageDecoder {
+    fn decode_positions_and_values(&self) -> Result<(Vec<u64>, BytesMut)> {
+        let positions = self.positions.decode(0, self.num_values, &mut false)?;
+        let positions = positions[0]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let mut values = self.values.decode(0, self.num_values, &mut false)?;
+        let values = values.swap_remove(0);
+        if positions.len() as u64 != self.num_values
+            || values.len() != positions.len() * self.default_value.len()
+        {
+            return Err(Error::corrupt_metadata(
+                format!(
+                    "a sparse page has {} positions and {} bytes of values for {} values of {} bytes",
+                    positions.len(),
+                    values.len(),
+                    self.num_values,
+                    self.default_value.len()
+                ),
+                location!(),
+            ));
+        }
+        if positions.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(Error::corrupt_metadata(
+                "the positions of a sparse page are not increasing",
+                location!(),
+            ));
+        }
+        Ok((positions, values))
+    }
+}
+
+impl PrimitivePageDecoder for SparsePageDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut decoded = self.decoded.lock().unwrap();
+        if decoded.is_none() {
+            *decoded = Some(self.decode_positions_and_values()?);
+        }
+        let (positions, values) = decoded.as_ref().unwrap();
+
+        let byte_width = self.default_value.len();
+        let mut dest = BytesMut::with_capacity(num_rows as usize * byte_width);
+        for _ in 0..num_rows {
+            dest.extend_from_slice(&self.default_value);
+        }
+
+        // Scatter the non-default values of each requested range into the output
+        let mut rows_to_skip = rows_to_skip;
+        let mut dest_row = 0;
+        for range in &self.ranges {
+            if dest_row == num_rows {
+                break;
+            }
+            let range_len = range.end - range.start;
+            if rows_to_skip >= range_len {
+                rows_to_skip -= range_len;
+                continue;
+            }
+            let start = range.start + rows_to_skip;
+            let end = start + (range_len - rows_to_skip).min(num_rows - dest_row);
+            rows_to_skip = 0;
+
+            let first = positions.partition_point(|position| *position < start);
+            let last = positions.partition_point(|position| *position < end);
+            for (value_idx, position) in positions.iter().enumerate().take(last).skip(first) {
+                let dest_start = (dest_row + position - start) as usize * byte_width;
+                let value_start = value_idx * byte_width;
+                dest[dest_start..dest_start + byte_width]
+                    .copy_from_slice(&values[value_start..value_start + byte_width]);
+            }
+            dest_row += end - start;
+        }
+        Ok(vec![dest])
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, ArrayRef, Float64Array, Int32Array, UInt16Array};
+    use arrow_schema::{DataType, Field};
+    use rand::seq::index::sample;
+
+    use lance_core::{error::EncodingError, Error, Result};
+
+    use crate::{
+        encoder::{ArrayEncoder, EncodingOverride},
+        format::pb,
+        testing::{check_round_trip_encoding_random_with_override, InMemoryPage},
+    };
+
+    use super::{non_zero_fraction, SparseEncoder};
+
+    // Encodes the arrays and returns the total encoded size and the decoded bytes for
+    // each of the given ranges
+    async fn round_trip(
+        arrays: &[ArrayRef],
+        ranges: &[Vec<std::ops::Range<u64>>],
+    ) -> (usize, Vec<Vec<u8>>) {
+        round_trip_with(arrays, ranges, |_| {}).await.unwrap()
+    }
+
+    // Like `round_trip` but `corrupt` may change the encoding before it is decoded
+    async fn round_trip_with(
+        arrays: &[ArrayRef],
+        ranges: &[Vec<std::ops::Range<u64>>],
+        corrupt: impl FnOnce(&mut pb::Sparse),
+    ) -> Result<(usize, Vec<Vec<u8>>)> {
+        let mut encoded = SparseEncoder::new().encode(arrays, &mut 0).unwrap();
+        match encoded.encoding.array_encoding.as_mut() {
+            Some(pb::array_encoding::ArrayEncoding::Sparse(sparse)) => corrupt(sparse),
+            _ => panic!("expected a sparse encoding"),
+        }
+        let page = InMemoryPage::new(&encoded);
+        let mut decoded = Vec::new();
+        for ranges in ranges {
+            let buffers = page.decode(arrays[0].data_type(), ranges).await?;
+            decoded.push(buffers[0].to_vec());
+        }
+        Ok((page.data.len(), decoded))
+    }
+
+    fn values_to_bytes(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_sparse_round_trip() {
+        let mut rng = rand::thread_rng();
+        let mut values = vec![0; 10_000];
+        for position in sample(&mut rng, values.len(), 50) {
+            values[position] = position as i32 + 1;
+        }
+        let arrays = vec![
+            Arc::new(Int32Array::from(values[..4000].to_vec())) as ArrayRef,
+            Arc::new(Int32Array::from(values[4000..].to_vec())) as ArrayRef,
+        ];
+        assert_eq!(non_zero_fraction(&arrays), 50.0 / 10_000.0);
+
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = vec![vec![0..10_000], vec![5..10, 3990..6000], vec![9999..10_000]];
+        let (encoded_size, decoded) = round_trip(&arrays, &ranges).await;
+
+        // 50 positions need at most 14 bits each and 50 values need 4 bytes each, compared
+        // with 40KB for flat values
+        assert!(encoded_size <= (50 * 14_usize).div_ceil(8) + 50 * 4);
+        for (ranges, decoded) in ranges.iter().zip(decoded) {
+            let expected = ranges
+                .iter()
+                .flat_map(|r| values[r.start as usize..r.end as usize].to_vec())
+                .collect::<Vec<_>>();
+            assert_eq!(decoded, values_to_bytes(&expected));
+        }
+
+        // The positions and the values are stored in separate buffers
+        let encoded = SparseEncoder::new().encode(&arrays, &mut 0).unwrap();
+        assert_eq!(encoded.buffers.len(), 2);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_sparse_edge_cases() {
+        // All zero, all non-zero, nulls (treated as zero), -0.0 (not zero), and slices
+        let cases = vec![
+            Arc::new(Int32Array::from(vec![0; 100])) as ArrayRef,
+            Arc::new(UInt16Array::from_iter_values(1..100)) as ArrayRef,
+            Arc::new(Int32Array::from(vec![Some(0), None, Some(3), None])) as ArrayRef,
+            Arc::new(Float64Array::from(vec![0.0, -0.0, 1.5, 0.0])) as ArrayRef,
+            Arc::new(Int32Array::from_iter_values(0..100).slice(10, 20)) as ArrayRef,
+        ];
+        for arr in cases {
+            let len = arr.len() as u64;
+            #[allow(clippy::single_range_in_vec_init)]
+            let (_, decoded) = round_trip(&[arr.clone()], &[vec![0..len]]).await;
+            let data = arr.to_data();
+            let byte_width = decoded[0].len() / arr.len();
+            let expected = &data.buffers()[0]
+                [data.offset() * byte_width..(data.offset() + data.len()) * byte_width];
+            for idx in 0..arr.len() {
+                let decoded = &decoded[0][idx * byte_width..(idx + 1) * byte_width];
+                if arr.is_valid(idx) {
+                    assert_eq!(decoded, &expected[idx * byte_width..(idx + 1) * byte_width]);
+                } else {
+                    assert!(decoded.iter().all(|byte| *byte == 0));
+                }
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_corrupt_sparse_page() {
+        let arrays = vec![Arc::new(Int32Array::from(vec![0, 0, 7, 0, 9])) as ArrayRef];
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = vec![vec![0..5]];
+        let is_corrupt = |result: Result<(usize, Vec<Vec<u8>>)>| {
+            let err = result.unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+        };
+
+        // A default value that does not have the width of the values
+        for default_value in [vec![], vec![0; 2], vec![0; 8]] {
+            is_corrupt(
+                round_trip_with(&arrays, &ranges, |sparse| {
+                    sparse.default_value = default_value
+                })
+                .await,
+            );
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_forced_sparse() {
+        for data_type in [DataType::Int8, DataType::UInt32, DataType::Float64] {
+            let field = Field::new("", data_type, true);
+            check_round_trip_encoding_random_with_override(field, EncodingOverride::ForceSparse)
+                .await;
+        }
+    }
+}
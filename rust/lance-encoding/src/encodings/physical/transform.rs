@@ -0,0 +1,751 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Integers encoded by a pipeline of transforms
+//!
+//! Rather than a dedicated encoding for every combination (e.g. frame of reference, then
+//! delta, then bitpacking, then zstd) a page describes the ordered list of [`Transform`]s
+//! that were applied to it.  Encoding applies the transforms in order and decoding undoes
+//! them in reverse order.
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::ArrayRef;
+use arrow_buffer::Buffer;
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt};
+use log::trace;
+use snafu::{location, Location};
+
+use lance_arrow::DataTypeExt;
+use lance_core::{Error, Result};
+
+use crate::{
+    decoder::{DecodeCost, DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encoder::{ArrayEncoder, EncodedArray, EncodedArrayBuffer},
+    format::pb,
+    EncodingsIo,
+};
+
+use super::bits::{BitReader, BitWriter};
+use super::buffers::{BufferCompressor, ZstdBufferCompressor};
+use super::delta_of_delta::{collect_values, supports_delta_of_delta};
+
+/// The data of a page in between two transforms
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformData {
+    /// Integers, sign extended to 64 bits (unsigned integers are zero extended)
+    Values(Vec<i64>),
+    /// Opaque bytes (e.g. bitpacked or compressed values)
+    Bytes(Vec<u8>),
+}
+
+impl TransformData {
+    /// The data as integers, bytes are read as little-endian 64-bit integers
+    pub fn into_values(self) -> Result<Vec<i64>> {
+        match self {
+            Self::Values(values) => Ok(values),
+            Self::Bytes(bytes) => {
+                if bytes.len() % 8 != 0 {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "{} bytes cannot be read as 64-bit integers, the transform expects values",
+                            bytes.len()
+                        ),
+                        location!(),
+                    ));
+                }
+                Ok(bytes
+                    .chunks_exact(8)
+                    .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect())
+            }
+        }
+    }
+
+    /// The data as bytes, integers are written as little-endian 64-bit integers
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Values(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+/// A step of a transform pipeline
+///
+/// A transform describes itself, with any parameters it derived from the data (e.g. the
+/// number of bits to bitpack to), in the page metadata when encoding.  The description is
+/// given back to it when decoding.
+pub trait Transform: std::fmt::Debug + Send + Sync {
+    /// Applies the transform, returning the transformed data and its description
+    fn forward(&self, data: TransformData) -> Result<(TransformData, pb::Transform)>;
+
+    /// Undoes [`Self::forward`], `num_values` is the number of values in the page
+    fn backward(
+        &self,
+        data: TransformData,
+        description: &pb::Transform,
+        num_values: usize,
+    ) -> Result<TransformData>;
+}
+
+fn mismatched_description(transform: &str, description: &pb::Transform) -> Error {
+    Error::corrupt_metadata(
+        format!(
+            "The {} transform cannot undo the transform {:?}",
+            transform, description.transform
+        ),
+        location!(),
+    )
+}
+
+/// Subtracts the smallest value from every value
+///
+/// Values that are close together become small non-negative values that bitpack well.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameOfReference {}
+
+impl Transform for FrameOfReference {
+    fn forward(&self, data: TransformData) -> Result<(TransformData, pb::Transform)> {
+        let values = data.into_values()?;
+        let reference = values.iter().copied().min().unwrap_or(0);
+        let values = values.iter().map(|v| v.wrapping_sub(reference)).collect();
+        let description = pb::Transform {
+            transform: Some(pb::transform::Transform::FrameOfReference(
+                pb::transform::FrameOfReference { reference },
+            )),
+        };
+        Ok((TransformData::Values(values), description))
+    }
+
+    fn backward(
+        &self,
+        data: TransformData,
+        description: &pb::Transform,
+        _num_values: usize,
+    ) -> Result<TransformData> {
+        let Some(pb::transform::Transform::FrameOfReference(params)) = &description.transform
+        else {
+            return Err(mismatched_description("frame of reference", description));
+        };
+        let mut values = data.into_values()?;
+        values
+            .iter_mut()
+            .for_each(|v| *v = v.wrapping_add(params.reference));
+        Ok(TransformData::Values(values))
+    }
+}
+
+/// Replaces every value, except the first, with its difference from the previous value
+///
+/// Sorted or slowly changing values become small differences.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Delta {}
+
+impl Transform for Delta {
+    fn forward(&self, data: TransformData) -> Result<(TransformData, pb::Transform)> {
+        let mut values = data.into_values()?;
+        for idx in (1..values.len()).rev() {
+            values[idx] = values[idx].wrapping_sub(values[idx - 1]);
+        }
+        let description = pb::Transform {
+            transform: Some(pb::transform::Transform::Delta(pb::transform::Delta {})),
+        };
+        Ok((TransformData::Values(values), description))
+    }
+
+    fn backward(
+        &self,
+        data: TransformData,
+        description: &pb::Transform,
+        _num_values: usize,
+    ) -> Result<TransformData> {
+        let Some(pb::transform::Transform::Delta(_)) = &description.transform else {
+            return Err(mismatched_description("delta", description));
+        };
+        let mut values = data.into_values()?;
+        for idx in 1..values.len() {
+            values[idx] = values[idx].wrapping_add(values[idx - 1]);
+        }
+        Ok(TransformData::Values(values))
+    }
+}
+
+/// Packs every value into the fewest bits that can hold all values
+///
+/// Values are read as unsigned and so negative values need all 64 bits.  This is best
+/// applied after [`FrameOfReference`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bitpack {}
+
+impl Transform for Bitpack {
+    fn forward(&self, data: TransformData) -> Result<(TransformData, pb::Transform)> {
+        let values = data.into_values()?;
+        let all_bits = values.iter().fold(0_u64, |bits, v| bits | *v as u64);
+        let num_bits = 64 - all_bits.leading_zeros() as u64;
+
+        let mut writer = BitWriter::with_capacity(values.len() as u64 * num_bits);
+        for value in &values {
+            writer.write(*value as u64, num_bits);
+        }
+        let description = pb::Transform {
+            transform: Some(pb::transform::Transform::Bitpack(pb::transform::Bitpack {
+                num_bits,
+            })),
+        };
+        Ok((TransformData::Bytes(writer.finish()), description))
+    }
+
+    fn backward(
+        &self,
+        data: TransformData,
+        description: &pb::Transform,
+        num_values: usize,
+    ) -> Result<TransformData> {
+        let Some(pb::transform::Transform::Bitpack(params)) = &description.transform else {
+            return Err(mismatched_description("bitpack", description));
+        };
+        let num_bits = params.num_bits;
+        let packed = data.into_bytes();
+        let needed_bytes = (num_values as u64 * num_bits).div_ceil(8);
+        if num_bits > 64 || (packed.len() as u64) < needed_bytes {
+            return Err(Error::corrupt_metadata(
+                format!(
+                    "{} values of {} bits cannot be unpacked from {} bytes",
+                    num_values,
+                    num_bits,
+                    packed.len()
+                ),
+                location!(),
+            ));
+        }
+        let mut reader = BitReader::new(&packed);
+        let values = (0..num_values)
+            .map(|_| reader.read(num_bits) as i64)
+            .collect();
+        Ok(TransformData::Values(values))
+    }
+}
+
+/// Compresses the bytes with zstd
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Zstd {}
+
+impl Transform for Zstd {
+    fn forward(&self, data: TransformData) -> Result<(TransformData, pb::Transform)> {
+        let mut compressed = Vec::new();
+        ZstdBufferCompressor::default().compress(&data.into_bytes(), &mut compressed)?;
+        let description = pb::Transform {
+            transform: Some(pb::transform::Transform::Zstd(pb::transform::Zstd {})),
+        };
+        Ok((TransformData::Bytes(compressed), description))
+    }
+
+    fn backward(
+        &self,
+        data: TransformData,
+        description: &pb::Transform,
+        _num_values: usize,
+    ) -> Result<TransformData> {
+        let Some(pb::transform::Transform::Zstd(_)) = &description.transform else {
+            return Err(mismatched_description("zstd", description));
+        };
+        let mut decompressed = Vec::new();
+        ZstdBufferCompressor::default().decompress(&data.into_bytes(), &mut decompressed)?;
+        Ok(TransformData::Bytes(decompressed))
+    }
+}
+
+/// The transform that undoes a step described in the page metadata
+pub fn transform_from_pb(description: &pb::Transform) -> Result<Arc<dyn Transform>> {
+    match &description.transform {
+        Some(pb::transform::Transform::FrameOfReference(_)) => Ok(Arc::new(FrameOfReference {})),
+        Some(pb::transform::Transform::Delta(_)) => Ok(Arc::new(Delta {})),
+        Some(pb::transform::Transform::Bitpack(_)) => Ok(Arc::new(Bitpack {})),
+        Some(pb::transform::Transform::Zstd(_)) => Ok(Arc::new(Zstd {})),
+        None => Err(Error::corrupt_metadata(
+            "The transform is missing or is not recognized by this version of Lance (the file may have been written by a newer version of Lance)",
+            location!(),
+        )),
+    }
+}
+
+/// Applies `transforms` in order, returning the result and the description of each step
+pub fn apply_transforms(
+    transforms: &[Arc<dyn Transform>],
+    data: TransformData,
+) -> Result<(TransformData, Vec<pb::Transform>)> {
+    let mut descriptions = Vec::with_capacity(transforms.len());
+    let data = transforms.iter().try_fold(data, |data, transform| {
+        let (data, description) = transform.forward(data)?;
+        descriptions.push(description);
+        Ok::<_, Error>(data)
+    })?;
+    Ok((data, descriptions))
+}
+
+/// Undoes the steps applied by [`apply_transforms`], in reverse order
+///
+/// Each step is given with the description that [`Transform::forward`] returned for it.
+pub fn undo_transforms(
+    steps: &[(Arc<dyn Transform>, pb::Transform)],
+    data: TransformData,
+    num_values: usize,
+) -> Result<TransformData> {
+    steps
+        .iter()
+        .rev()
+        .try_fold(data, |data, (transform, description)| {
+            transform.backward(data, description, num_values)
+        })
+}
+
+/// Encodes integers with a pipeline of [`Transform`]s
+#[derive(Debug)]
+pub struct TransformPipelineEncoder {
+    transforms: Vec<Arc<dyn Transform>>,
+}
+
+impl TransformPipelineEncoder {
+    /// Creates an encoder that applies `transforms` in order
+    pub fn new(transforms: Vec<Arc<dyn Transform>>) -> Self {
+        Self { transforms }
+    }
+}
+
+impl ArrayEncoder for TransformPipelineEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let data_type = arrays[0].data_type();
+        if !supports_delta_of_delta(data_type) {
+            return Err(Error::unsupported_type(
+                data_type,
+                "transform pipelines are only supported for integers",
+                location!(),
+            ));
+        }
+        let values = collect_values(arrays);
+        let num_values = values.len() as u64;
+        let (data, transforms) = apply_transforms(&self.transforms, TransformData::Values(values))?;
+
+        let index = *buffer_index;
+        *buffer_index += 1;
+        Ok(EncodedArray {
+            buffers: vec![EncodedArrayBuffer {
+                parts: vec![Buffer::from_vec(data.into_bytes())],
+                index,
+            }],
+            encoding: pb::ArrayEncoding {
+                array_encoding: Some(pb::array_encoding::ArrayEncoding::TransformPipeline(
+                    pb::TransformPipeline {
+                        transforms,
+                        bits_per_value: 8 * data_type.byte_width() as u64,
+                        num_values,
+                        buffer: Some(pb::Buffer {
+                            buffer_index: index,
+                            buffer_type: pb::buffer::BufferType::Page as i32,
+                        }),
+                    },
+                )),
+                producer: None,
+                statistics: None,
+                data_type: None,
+            },
+        })
+    }
+}
+
+/// Scheduler for a page encoded with a transform pipeline
+///
+/// The transforms can only be undone for the whole page and so the whole page is always
+/// loaded.
+#[derive(Debug)]
+pub struct TransformPipelineScheduler {
+    steps: Arc<[(Arc<dyn Transform>, pb::Transform)]>,
+    bytes_per_value: u64,
+    num_values: u64,
+    buffer_offset: u64,
+    buffer_size: u64,
+}
+
+impl TransformPipelineScheduler {
+    pub fn try_new(
+        encoding: &pb::TransformPipeline,
+        buffer_offset: u64,
+        buffer_size: u64,
+    ) -> Result<Self> {
+        let steps = encoding
+            .transforms
+            .iter()
+            .map(|description| Ok((transform_from_pb(description)?, description.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        if !matches!(encoding.bits_per_value, 8 | 16 | 32 | 64) {
+            return Err(Error::corrupt_metadata(
+                format!(
+                    "A transform pipeline page cannot hold values of {} bits",
+                    encoding.bits_per_value
+                ),
+                location!(),
+            ));
+        }
+        Ok(Self {
+            steps: steps.into(),
+            bytes_per_value: encoding.bits_per_value / 8,
+            num_values: encoding.num_values,
+            buffer_offset,
+            buffer_size,
+        })
+    }
+}
+
+impl PageScheduler for TransformPipelineScheduler {
+    fn schedule_ranges(
+        &self,
+        ranges: &[Range<u64>],
+        scheduler: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        trace!(
+            "Scheduling transform pipeline page with {} steps for {} ranges",
+            self.steps.len(),
+            ranges.len()
+        );
+        let data = scheduler.submit_request(
+            vec![self.buffer_offset..self.buffer_offset + self.buffer_size],
+            top_level_row,
+        );
+        let steps = self.steps.clone();
+        let bytes_per_value = self.bytes_per_value;
+        let num_values = self.num_values;
+        let ranges = ranges.to_vec();
+
+        async move {
+            let data = data.await?;
+            Ok(Box::new(TransformPipelineDecoder {
+                data: data.into_iter().next().unwrap_or_default(),
+                steps,
+                bytes_per_value,
+                num_values,
+                ranges,
+                decoded: Mutex::new(None),
+            }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+
+    fn estimate_cost(&self, ranges: &[Range<u64>]) -> DecodeCost {
+        if ranges.is_empty() {
+            return DecodeCost::default();
+        }
+        let is_compressed = self.steps.iter().any(|(_, description)| {
+            matches!(
+                description.transform,
+                Some(pb::transform::Transform::Zstd(_))
+            )
+        });
+        let cpu_class = if is_compressed {
+            DecodeCpuClass::Decompress
+        } else {
+            DecodeCpuClass::Unpack
+        };
+        DecodeCost::new(self.buffer_size, cpu_class)
+    }
+}
+
+struct TransformPipelineDecoder {
+    data: Bytes,
+    steps: Arc<[(Arc<dyn Transform>, pb::Transform)]>,
+    bytes_per_value: u64,
+    num_values: u64,
+    ranges: Vec<Range<u64>>,
+    // The values of the requested ranges, computed on first use
+    decoded: Mutex<Option<Bytes>>,
+}
+
+impl TransformPipelineDecoder {
+    // Undoes the transforms and gathers the requested ranges
+    fn undo(&self) -> Result<Bytes> {
+        let values = undo_transforms(
+            &self.steps,
+            TransformData::Bytes(self.data.to_vec()),
+            self.num_values as usize,
+        )?
+        .into_values()?;
+        if values.len() as u64 != self.num_values {
+            return Err(Error::corrupt_metadata(
+                format!(
+                    "The transform pipeline produced {} values but the page has {}",
+                    values.len(),
+                    self.num_values
+                ),
+                location!(),
+            ));
+        }
+
+        let bytes_per_value = self.bytes_per_value as usize;
+        let num_rows = self.ranges.iter().map(|r| r.end - r.start).sum::<u64>();
+        let mut dest = BytesMut::with_capacity(num_rows as usize * bytes_per_value);
+        for range in &self.ranges {
+            let range_values = values
+                .get(range.start as usize..range.end as usize)
+                .ok_or_else(|| {
+                    Error::corrupt_metadata(
+                        format!(
+                            "Rows {:?} were requested but the page only has {} values",
+                            range, self.num_values
+                        ),
+                        location!(),
+                    )
+                })?;
+            for value in range_values {
+                dest.extend_from_slice(&value.to_le_bytes()[..bytes_per_value]);
+            }
+        }
+        Ok(dest.freeze())
+    }
+}
+
+impl PrimitivePageDecoder for TransformPipelineDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut decoded = self.decoded.lock().unwrap();
+        if decoded.is_none() {
+            *decoded = Some(self.undo()?);
+        }
+        let decoded = decoded.as_ref().unwrap();
+        let start = (rows_to_skip * self.bytes_per_value) as usize;
+        let end = start + (num_rows * self.bytes_per_value) as usize;
+        let bytes = decoded.get(start..end).ok_or_else(|| {
+            Error::corrupt_metadata(
+                format!(
+                    "Cannot decode {} rows after skipping {} rows because only {} rows were scheduled",
+                    num_rows,
+                    rows_to_skip,
+                    decoded.len() as u64 / self.bytes_per_value
+                ),
+                location!(),
+            )
+        })?;
+        Ok(vec![BytesMut::from(bytes)])
+    }
+
+    fn num_buffers(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use arrow_array::{ArrayRef, Int64Array, UInt32Array};
+    use bytes::Bytes;
+    use lance_core::error::EncodingError;
+
+    use crate::{encoder::ArrayEncoder, format::pb, testing::InMemoryPage};
+
+    use super::*;
+
+    // Encodes the array with the transforms and decodes the given ranges
+    async fn round_trip(
+        transforms: Vec<Arc<dyn Transform>>,
+        array: ArrayRef,
+        ranges: &[Range<u64>],
+    ) -> (usize, Vec<u8>) {
+        let encoded = TransformPipelineEncoder::new(transforms)
+            .encode(&[array.clone()], &mut 0)
+            .unwrap();
+        let page = InMemoryPage::new(&encoded);
+        let decoded = page.decode(array.data_type(), ranges).await.unwrap();
+        (page.data.len(), decoded[0].to_vec())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_three_stage_pipeline() {
+        // Slowly increasing values far from zero
+        let values = (0..1000)
+            .map(|i| 1_000_000_000_000 + i * 3 + i % 2)
+            .collect::<Vec<i64>>();
+        let array = Arc::new(Int64Array::from(values.clone())) as ArrayRef;
+        let transforms: Vec<Arc<dyn Transform>> = vec![
+            Arc::new(FrameOfReference {}),
+            Arc::new(Delta {}),
+            Arc::new(Bitpack {}),
+        ];
+
+        let ranges = [0..10, 500..600, 999..1000];
+        let (encoded_size, decoded) = round_trip(transforms, array, &ranges).await;
+        // The differences are at most 4 and so need 3 bits each
+        assert_eq!(encoded_size, (1000 * 3_usize).div_ceil(8));
+        let expected = ranges
+            .iter()
+            .flat_map(|r| &values[r.start as usize..r.end as usize])
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, expected);
+
+        // Unsigned values and a final compression step
+        let values = (0..1000).map(|i| u32::MAX - (i % 10)).collect::<Vec<_>>();
+        let array = Arc::new(UInt32Array::from(values.clone())) as ArrayRef;
+        let transforms: Vec<Arc<dyn Transform>> = vec![
+            Arc::new(FrameOfReference {}),
+            Arc::new(Bitpack {}),
+            Arc::new(Zstd {}),
+        ];
+        let (encoded_size, decoded) = round_trip(transforms, array, &[0..1000]).await;
+        assert!(encoded_size < 1000 * 4 / 8);
+        let expected = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, expected);
+    }
+
+    // Records the order in which it is applied and undone
+    #[derive(Debug)]
+    struct RecordingTransform {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        inner: Arc<dyn Transform>,
+    }
+
+    impl Transform for RecordingTransform {
+        fn forward(&self, data: TransformData) -> Result<(TransformData, pb::Transform)> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("forward {}", self.name));
+            self.inner.forward(data)
+        }
+
+        fn backward(
+            &self,
+            data: TransformData,
+            description: &pb::Transform,
+            num_values: usize,
+        ) -> Result<TransformData> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("backward {}", self.name));
+            self.inner.backward(data, description, num_values)
+        }
+    }
+
+    #[test]
+    fn test_undo_in_reverse_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recording = |name, inner: Arc<dyn Transform>| {
+            Arc::new(RecordingTransform {
+                name,
+                log: log.clone(),
+                inner,
+            }) as Arc<dyn Transform>
+        };
+        let transforms = vec![
+            recording("delta", Arc::new(Delta {})),
+            recording("frame of reference", Arc::new(FrameOfReference {})),
+            recording("bitpack", Arc::new(Bitpack {})),
+        ];
+        let values = vec![10, 7, 7, -3, 20, 21];
+        let (encoded, descriptions) =
+            apply_transforms(&transforms, TransformData::Values(values.clone())).unwrap();
+
+        let steps = transforms
+            .into_iter()
+            .zip(descriptions.iter().cloned())
+            .collect::<Vec<_>>();
+        let decoded = undo_transforms(&steps, encoded.clone(), values.len()).unwrap();
+        assert_eq!(decoded, TransformData::Values(values.clone()));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "forward delta",
+                "forward frame of reference",
+                "forward bitpack",
+                "backward bitpack",
+                "backward frame of reference",
+                "backward delta",
+            ]
+        );
+
+        // The steps don't commute and so undoing them in another order gives other values
+        let misordered = [steps[1].clone(), steps[0].clone(), steps[2].clone()];
+        assert_ne!(
+            undo_transforms(&misordered, encoded, values.len()).unwrap(),
+            TransformData::Values(values)
+        );
+    }
+
+    #[test]
+    fn test_corrupt_num_values() {
+        let transforms: Vec<Arc<dyn Transform>> = vec![Arc::new(Bitpack {})];
+        let values = (0..100).collect::<Vec<i64>>();
+        let (encoded, descriptions) =
+            apply_transforms(&transforms, TransformData::Values(values)).unwrap();
+        let decoder = |num_values, range: Range<u64>| TransformPipelineDecoder {
+            data: Bytes::from(encoded.clone().into_bytes()),
+            steps: transforms
+                .iter()
+                .cloned()
+                .zip(descriptions.iter().cloned())
+                .collect(),
+            bytes_per_value: 8,
+            num_values,
+            ranges: vec![range],
+            decoded: Mutex::new(None),
+        };
+        let is_corrupt = |result: Result<Vec<BytesMut>>| {
+            let err = result.unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+        };
+
+        // The page claims fewer values than the rows that were scheduled
+        is_corrupt(decoder(10, 0..100).decode(0, 100, &mut false));
+        // More rows are decoded than were scheduled
+        is_corrupt(decoder(100, 0..10).decode(5, 10, &mut false));
+        assert_eq!(
+            decoder(100, 0..10).decode(5, 5, &mut false).unwrap()[0].len(),
+            5 * 8
+        );
+    }
+
+    #[test]
+    fn test_invalid_bits_per_value() {
+        for bits_per_value in [0, 12, 128] {
+            let encoding = pb::TransformPipeline {
+                transforms: vec![],
+                bits_per_value,
+                num_values: 10,
+                buffer: None,
+            };
+            let err = TransformPipelineScheduler::try_new(&encoding, 0, 0).unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+        }
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 use arrow_array::ArrayRef;
+use arrow_buffer::MutableBuffer;
 use arrow_schema::DataType;
 use bytes::{Bytes, BytesMut};
 use futures::{future::BoxFuture, FutureExt};
@@ -13,42 +14,194 @@ use std::ops::Range;
 use std::sync::{Arc, Mutex};
 
 use crate::{
-    decoder::{PageScheduler, PrimitivePageDecoder},
+    decoder::{DecodeCost, DecodeCpuClass, DecodeCursor, PageScheduler, PrimitivePageDecoder},
     encoder::{ArrayEncoder, BufferEncoder, EncodedArray, EncodedArrayBuffer},
     format::pb,
     EncodingsIo,
 };
 
-use lance_core::{Error, Result};
+use lance_core::{error::EncodingError, Error, Result};
 
+use super::bitpack::{is_bitpackable, num_compressed_bits, BitpackedArrayEncoder};
+use super::boolean_rle::BooleanRuns;
 use super::buffers::{
-    BitmapBufferEncoder, CompressedBufferEncoder, FlatBufferEncoder, GeneralBufferCompressor,
+    byte_order_components, check_decompressed_size, compress_blocks, parse_blocks, swap_byte_order,
+    BitmapBufferEncoder, BufferCompressor, CompressedBufferEncoder, CompressionDecision,
+    FlatBufferEncoder, GeneralBufferCompressor, Lz4BufferCompressor, ZstdBufferCompressor,
+    DEFAULT_MAX_DECOMPRESSED_SIZE, DEFAULT_MIN_COMPRESSION_RATIO,
 };
+use super::{FLAT_ENCODING_VERSION, FLAT_ENCODING_VERSION_BLOCKS};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CompressionScheme {
     None,
     Zstd,
+    /// Faster to decompress than zstd but usually compresses less
+    Lz4,
+    /// Picks a scheme based on the data type, see [`default_scheme_for`]
+    ///
+    /// This is only a request, the concrete scheme it resolves to is what gets recorded
+    /// in the page encoding.
+    Default,
 }
 
 impl fmt::Display for CompressionScheme {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let scheme_str = match self {
             Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
             Self::None => "none",
+            Self::Default => "default",
         };
         write!(f, "{}", scheme_str)
     }
 }
 
+/// Describes the features supported by a [`CompressionScheme`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionCapabilities {
+    /// True if the scheme accepts a compression level
+    pub supports_levels: bool,
+    /// True if the scheme can make use of a (trained) dictionary
+    pub supports_dictionaries: bool,
+    /// True if a range of values can be read without decompressing the entire buffer
+    pub supports_random_access: bool,
+}
+
+impl CompressionScheme {
+    /// Every concrete compression scheme, in declaration order
+    ///
+    /// This does not include [`Self::Default`] which always resolves to one of these.
+    pub fn all() -> &'static [Self] {
+        &[Self::None, Self::Zstd, Self::Lz4]
+    }
+
+    /// Returns the features supported by this scheme
+    pub fn capabilities(&self) -> CompressionCapabilities {
+        match self {
+            Self::None => CompressionCapabilities {
+                supports_levels: false,
+                supports_dictionaries: false,
+                supports_random_access: true,
+            },
+            // Zstd can use trained dictionaries but the compressor doesn't train any
+            Self::Zstd => CompressionCapabilities {
+                supports_levels: true,
+                supports_dictionaries: false,
+                supports_random_access: false,
+            },
+            Self::Lz4 => CompressionCapabilities {
+                supports_levels: false,
+                supports_dictionaries: false,
+                supports_random_access: false,
+            },
+            // Only the features shared by every scheme it can resolve to
+            Self::Default => CompressionCapabilities {
+                supports_levels: false,
+                supports_dictionaries: false,
+                supports_random_access: false,
+            },
+        }
+    }
+}
+
+/// The concrete choices a [`CompressionScheme::Default`] request resolves to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DefaultScheme {
+    /// The compression scheme applied to the values
+    pub compression: CompressionScheme,
+    /// If true, integers are bitpacked first and `compression` is only used for pages
+    /// where bitpacking would not save any space
+    pub bitpacking: bool,
+}
+
+/// Picks the scheme used for `data_type` when [`CompressionScheme::Default`] is requested
+///
+/// Integers are bitpacked (small values are common and bitpacking keeps random access),
+/// floats and variable-width data are compressed with zstd, and everything else is
+/// stored as-is.
+pub fn default_scheme_for(data_type: &DataType) -> DefaultScheme {
+    let (compression, bitpacking) = if is_bitpackable(data_type) {
+        (CompressionScheme::None, true)
+    } else {
+        match data_type {
+            // TODO: use byte stream split for floats once it is available
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+                (CompressionScheme::Zstd, false)
+            }
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary => {
+                (CompressionScheme::Zstd, false)
+            }
+            _ => (CompressionScheme::None, false),
+        }
+    };
+    DefaultScheme {
+        compression,
+        bitpacking,
+    }
+}
+
+/// Parses the compression scheme recorded in the encoding of a page
+///
+/// Pages record the concrete scheme that a [`CompressionScheme::Default`] request resolved
+/// to and so "default" is rejected as corrupt metadata.  Use
+/// [`parse_requested_compression_scheme`] to parse a scheme that is requested for writing.
 pub fn parse_compression_scheme(scheme: &str) -> Result<CompressionScheme> {
     match scheme {
         "none" => Ok(CompressionScheme::None),
         "zstd" => Ok(CompressionScheme::Zstd),
-        _ => Err(Error::invalid_input(
-            format!("Unknown compression scheme: {}", scheme),
+        "lz4" => Ok(CompressionScheme::Lz4),
+        "default" => Err(Error::corrupt_metadata(
+            "the compression scheme \"default\" is only a request and is never written to a page",
             location!(),
         )),
+        _ => Err(Error::encoding(
+            EncodingError::UnknownScheme {
+                scheme: scheme.to_string(),
+            },
+            location!(),
+        )),
+    }
+}
+
+/// Parses a compression scheme that is requested for writing, which may be "default"
+pub fn parse_requested_compression_scheme(scheme: &str) -> Result<CompressionScheme> {
+    match scheme {
+        "default" => Ok(CompressionScheme::Default),
+        _ => parse_compression_scheme(scheme),
+    }
+}
+
+// Checks that `level` (if any) is a valid compression level for `scheme`
+fn check_compression_level(scheme: CompressionScheme, level: Option<i32>) -> Result<()> {
+    if let Some(level) = level {
+        if !scheme.capabilities().supports_levels {
+            return Err(Error::invalid_input(
+                format!(
+                    "The compression scheme {} does not accept a compression level",
+                    scheme
+                ),
+                location!(),
+            ));
+        }
+        if !zstd::compression_level_range().contains(&level) {
+            return Err(Error::invalid_input(
+                format!("Invalid {} compression level {}", scheme, level),
+                location!(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The compressor for a scheme that compresses, the level must have been checked
+fn buffer_compressor(scheme: CompressionScheme, level: Option<i32>) -> Box<dyn BufferCompressor> {
+    match scheme {
+        CompressionScheme::Zstd => Box::new(ZstdBufferCompressor::new(level.unwrap_or(0))),
+        CompressionScheme::Lz4 => Box::<Lz4BufferCompressor>::default(),
+        CompressionScheme::None | CompressionScheme::Default => {
+            unreachable!("{} does not compress buffers", scheme)
+        }
     }
 }
 
@@ -61,6 +214,9 @@ pub struct ValuePageScheduler {
     buffer_offset: u64,
     buffer_size: u64,
     compression_scheme: CompressionScheme,
+    compression_blocks: bool,
+    byte_swap: &'static [usize],
+    max_decompressed_size: usize,
 }
 
 impl ValuePageScheduler {
@@ -75,8 +231,31 @@ impl ValuePageScheduler {
             buffer_offset,
             buffer_size,
             compression_scheme,
+            compression_blocks: false,
+            byte_swap: &[],
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
         }
     }
+
+    /// The page is compressed in independent blocks (see [`super::buffers::compress_blocks`])
+    pub fn with_compression_blocks(mut self) -> Self {
+        self.compression_blocks = true;
+        self
+    }
+
+    /// The values are not stored in the host's byte order, the byte order of each of the
+    /// components (of the given widths) of each value is swapped when decoding
+    pub fn with_byte_swap(mut self, components: &'static [usize]) -> Self {
+        self.byte_swap = components;
+        self
+    }
+
+    /// Sets the largest size, in bytes, that a compressed page may decompress to, see
+    /// [`crate::decoder::DecoderConfig::max_decompressed_size`]
+    pub fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
 }
 
 impl PageScheduler for ValuePageScheduler {
@@ -117,6 +296,10 @@ impl PageScheduler for ValuePageScheduler {
         );
         let bytes = scheduler.submit_request(byte_ranges, top_level_row);
         let bytes_per_value = self.bytes_per_value;
+        let compression_scheme = self.compression_scheme;
+        let compression_blocks = self.compression_blocks;
+        let byte_swap = self.byte_swap;
+        let max_decompressed_size = self.max_decompressed_size;
 
         let range_offsets = if self.compression_scheme != CompressionScheme::None {
             ranges
@@ -132,17 +315,41 @@ impl PageScheduler for ValuePageScheduler {
         };
 
         async move {
-            let bytes = bytes.await?;
+            let mut bytes = bytes.await?;
+            // Compressed pages are swapped once they are decompressed
+            if !byte_swap.is_empty() && compression_scheme == CompressionScheme::None {
+                bytes = bytes
+                    .iter()
+                    .map(|range| Bytes::from(swap_byte_order(range, byte_swap)))
+                    .collect();
+            }
 
             Ok(Box::new(ValuePageDecoder {
                 bytes_per_value,
                 data: bytes,
                 uncompressed_data: Arc::new(Mutex::new(None)),
                 uncompressed_range_offsets: range_offsets,
+                compression_scheme,
+                compression_blocks,
+                byte_swap,
+                max_decompressed_size,
             }) as Box<dyn PrimitivePageDecoder>)
         }
         .boxed()
     }
+
+    fn estimate_cost(&self, ranges: &[std::ops::Range<u64>]) -> DecodeCost {
+        if ranges.is_empty() {
+            return DecodeCost::default();
+        }
+        if self.compression_scheme == CompressionScheme::None {
+            let num_values = ranges.iter().map(|r| r.end - r.start).sum::<u64>();
+            DecodeCost::new(num_values * self.bytes_per_value, DecodeCpuClass::Copy)
+        } else {
+            // A compressed page must always be loaded and decompressed in full
+            DecodeCost::new(self.buffer_size, DecodeCpuClass::Decompress)
+        }
+    }
 }
 
 struct ValuePageDecoder {
@@ -150,22 +357,93 @@ struct ValuePageDecoder {
     data: Vec<Bytes>,
     uncompressed_data: Arc<Mutex<Option<Vec<Bytes>>>>,
     uncompressed_range_offsets: Vec<std::ops::Range<usize>>,
+    compression_scheme: CompressionScheme,
+    compression_blocks: bool,
+    // The widths of the components of a value to swap, empty if the values are in the
+    // host's byte order
+    byte_swap: &'static [usize],
+    // The page may not decompress to more than this many bytes
+    max_decompressed_size: usize,
 }
 
 impl ValuePageDecoder {
     fn decompress(&self) -> Result<Vec<Bytes>> {
         // for compressed page, it is guaranteed that only one range is passed
-        let bytes_u8: Vec<u8> = self.data[0].to_vec();
-        let buffer_compressor = GeneralBufferCompressor::get_compressor("");
+        let buffer_compressor =
+            GeneralBufferCompressor::get_compressor(&self.compression_scheme.to_string());
         let mut uncompressed_bytes: Vec<u8> = Vec::new();
-        buffer_compressor.decompress(&bytes_u8, &mut uncompressed_bytes)?;
+        // The position of `uncompressed_bytes` in the decompressed page
+        let mut base_offset = 0;
+        if self.compression_blocks {
+            // Only the blocks that hold requested values are decompressed
+            let ranges = &self.uncompressed_range_offsets;
+            let start = ranges.iter().map(|range| range.start).min().unwrap_or(0) as u64;
+            let end = ranges.iter().map(|range| range.end).max().unwrap_or(0) as u64;
+            let blocks = parse_blocks(&self.data[0])?
+                .into_iter()
+                .filter(|block| {
+                    block.uncompressed_offset < end
+                        && block.uncompressed_offset + block.uncompressed_size > start
+                })
+                .collect::<Vec<_>>();
+            if let Some(first_block) = blocks.first() {
+                base_offset = first_block.uncompressed_offset as usize;
+            }
+            // The recorded sizes are checked before anything is allocated and each block may
+            // not decompress to more than its recorded size.  Space is only reserved for one
+            // block at a time so that a corrupt size can't allocate the whole limit up front.
+            let total_size = blocks
+                .iter()
+                .map(|block| block.uncompressed_size)
+                .sum::<u64>();
+            check_decompressed_size(total_size, self.max_decompressed_size)?;
+            for block in blocks {
+                uncompressed_bytes.reserve(block.uncompressed_size as usize);
+                buffer_compressor.decompress_with_limit(
+                    &block.data,
+                    &mut uncompressed_bytes,
+                    block.uncompressed_size as usize,
+                )?;
+            }
+        } else {
+            buffer_compressor.decompress_with_limit(
+                &self.data[0],
+                &mut uncompressed_bytes,
+                self.max_decompressed_size,
+            )?;
+        }
+
+        // A full page scan can use the decompressed buffer as-is
+        if let [range] = self.uncompressed_range_offsets.as_slice() {
+            if range.start == base_offset && range.end == base_offset + uncompressed_bytes.len() {
+                if !self.byte_swap.is_empty() {
+                    uncompressed_bytes = swap_byte_order(&uncompressed_bytes, self.byte_swap);
+                }
+                return Ok(vec![Bytes::from(uncompressed_bytes)]);
+            }
+        }
 
         let mut bytes_in_ranges: Vec<Bytes> =
             Vec::with_capacity(self.uncompressed_range_offsets.len());
         for range in &self.uncompressed_range_offsets {
-            let start = range.start;
-            let end = range.end;
-            bytes_in_ranges.push(Bytes::from(uncompressed_bytes[start..end].to_vec()));
+            let start = range.start - base_offset;
+            let end = range.end - base_offset;
+            if end > uncompressed_bytes.len() {
+                return Err(Error::encoding(
+                    EncodingError::BufferTooShort {
+                        context: "the decompressed page".to_string(),
+                        expected: end as u64,
+                        actual: uncompressed_bytes.len() as u64,
+                    },
+                    location!(),
+                ));
+            }
+            let range_bytes = &uncompressed_bytes[start..end];
+            bytes_in_ranges.push(Bytes::from(if self.byte_swap.is_empty() {
+                range_bytes.to_vec()
+            } else {
+                swap_byte_order(range_bytes, self.byte_swap)
+            }));
         }
         Ok(bytes_in_ranges)
     }
@@ -187,7 +465,7 @@ impl ValuePageDecoder {
         buf: &Bytes,
         bytes_to_skip: &mut u64,
         bytes_to_take: &mut u64,
-        dest: &mut bytes::BytesMut,
+        dest: &mut impl FnMut(&[u8]),
     ) {
         let buf_len = buf.len() as u64;
         if *bytes_to_skip > buf_len {
@@ -197,10 +475,83 @@ impl ValuePageDecoder {
             *bytes_to_take -= bytes_to_take_here;
             let start = *bytes_to_skip as usize;
             let end = start + bytes_to_take_here as usize;
-            dest.extend_from_slice(&buf.slice(start..end));
+            dest(&buf[start..end]);
             *bytes_to_skip = 0;
         }
     }
+
+    /// Calls `f` with the loaded buffers, decompressing them first if needed
+    fn with_buffers<T>(&self, f: impl FnOnce(&[Bytes]) -> Result<T>) -> Result<T> {
+        if self.is_compressed() {
+            let decoding_data = self.get_uncompressed_bytes()?;
+            let buffers = decoding_data.lock().unwrap();
+            f(buffers.as_ref().unwrap())
+        } else {
+            f(&self.data)
+        }
+    }
+
+    /// Passes the bytes of the requested rows to `dest`, in order, one loaded buffer at a time
+    fn decode_with(&self, rows_to_skip: u64, num_rows: u64, dest: impl FnMut(&[u8])) -> Result<()> {
+        self.with_buffers(|buffers| self.decode_from(buffers, rows_to_skip, num_rows, dest))
+    }
+
+    fn decode_from(
+        &self,
+        buffers: &[Bytes],
+        rows_to_skip: u64,
+        num_rows: u64,
+        mut dest: impl FnMut(&[u8]),
+    ) -> Result<()> {
+        let mut bytes_to_skip = rows_to_skip * self.bytes_per_value;
+        let mut bytes_to_take = num_rows * self.bytes_per_value;
+        let bytes_needed = bytes_to_skip + bytes_to_take;
+
+        for buf in buffers {
+            self.decode_buffer(buf, &mut bytes_to_skip, &mut bytes_to_take, &mut dest);
+        }
+        if bytes_to_take > 0 {
+            return Err(Error::encoding(
+                EncodingError::BufferTooShort {
+                    context: format!("decoding {} rows after skipping {}", num_rows, rows_to_skip),
+                    expected: bytes_needed,
+                    actual: bytes_needed - bytes_to_take,
+                },
+                location!(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The index of the buffer holding the byte at `offset` and the offset of the byte in it
+    fn locate(buffers: &[Bytes], mut offset: u64) -> (usize, u64) {
+        for (index, buf) in buffers.iter().enumerate() {
+            let buf_len = buf.len() as u64;
+            if offset < buf_len {
+                return (index, offset);
+            }
+            offset -= buf_len;
+        }
+        (buffers.len(), offset)
+    }
+
+    fn slice_buffers(
+        buffers: &[Bytes],
+        mut bytes_to_skip: usize,
+        bytes_to_take: usize,
+    ) -> Option<Vec<Bytes>> {
+        for buf in buffers {
+            if bytes_to_skip >= buf.len() {
+                bytes_to_skip -= buf.len();
+                continue;
+            }
+            // Rows that span multiple buffers have to be copied together
+            return (bytes_to_skip + bytes_to_take <= buf.len())
+                .then(|| vec![buf.slice(bytes_to_skip..bytes_to_skip + bytes_to_take)]);
+        }
+        // All rows were skipped (e.g. num_rows is 0)
+        (bytes_to_take == 0).then(|| vec![Bytes::new()])
+    }
 }
 
 impl PrimitivePageDecoder for ValuePageDecoder {
@@ -210,26 +561,105 @@ impl PrimitivePageDecoder for ValuePageDecoder {
         num_rows: u64,
         _all_null: &mut bool,
     ) -> Result<Vec<BytesMut>> {
-        let mut bytes_to_skip = rows_to_skip * self.bytes_per_value;
-        let mut bytes_to_take = num_rows * self.bytes_per_value;
+        let bytes_to_take = num_rows * self.bytes_per_value;
+        let mut dest = BytesMut::with_capacity(bytes_to_take as usize);
 
-        let mut dest_buffers = vec![BytesMut::with_capacity(bytes_to_take as usize)];
+        debug_assert!(dest.capacity() as u64 >= bytes_to_take);
 
-        let dest = &mut dest_buffers[0];
+        self.decode_with(rows_to_skip, num_rows, |bytes| {
+            dest.extend_from_slice(bytes)
+        })?;
+        Ok(vec![dest])
+    }
 
-        debug_assert!(dest.capacity() as u64 >= bytes_to_take);
+    fn decode_into(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        dest: &mut MutableBuffer,
+    ) -> Result<()> {
+        let bytes_to_take = num_rows * self.bytes_per_value;
+        dest.reserve(bytes_to_take as usize);
 
-        if self.is_compressed() {
-            let decoding_data = self.get_uncompressed_bytes()?;
-            for buf in decoding_data.lock().unwrap().as_ref().unwrap() {
-                self.decode_buffer(buf, &mut bytes_to_skip, &mut bytes_to_take, dest);
+        debug_assert!((dest.capacity() - dest.len()) as u64 >= bytes_to_take);
+
+        self.decode_with(rows_to_skip, num_rows, |bytes| {
+            dest.extend_from_slice(bytes)
+        })
+    }
+
+    fn decode_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<BytesMut>> {
+        // The page is decompressed once and each range is copied out of it
+        self.with_buffers(|buffers| {
+            ranges
+                .iter()
+                .map(|range| {
+                    let num_rows = range.end - range.start;
+                    let mut dest =
+                        BytesMut::with_capacity((num_rows * self.bytes_per_value) as usize);
+                    self.decode_from(buffers, range.start, num_rows, |bytes| {
+                        dest.extend_from_slice(bytes)
+                    })?;
+                    Ok(dest)
+                })
+                .collect()
+        })
+    }
+
+    fn decode_more(&self, cursor: &mut DecodeCursor, num_rows: u64) -> Result<Vec<BytesMut>> {
+        let mut dest = BytesMut::with_capacity((num_rows * self.bytes_per_value) as usize);
+        // A compressed page stays decompressed between calls
+        self.with_buffers(|buffers| {
+            let (mut index, mut offset) = cursor
+                .buffer_position
+                .unwrap_or_else(|| Self::locate(buffers, cursor.row * self.bytes_per_value));
+            let mut bytes_to_take = num_rows * self.bytes_per_value;
+            while bytes_to_take > 0 {
+                let Some(buf) = buffers.get(index) else {
+                    let bytes_needed = (cursor.row + num_rows) * self.bytes_per_value;
+                    return Err(Error::encoding(
+                        EncodingError::BufferTooShort {
+                            context: format!("decoding {} rows after row {}", num_rows, cursor.row),
+                            expected: bytes_needed,
+                            actual: bytes_needed - bytes_to_take,
+                        },
+                        location!(),
+                    ));
+                };
+                let buf_len = buf.len() as u64;
+                let bytes_to_take_here = (buf_len - offset).min(bytes_to_take);
+                dest.extend_from_slice(
+                    &buf[offset as usize..(offset + bytes_to_take_here) as usize],
+                );
+                bytes_to_take -= bytes_to_take_here;
+                offset += bytes_to_take_here;
+                if offset == buf_len {
+                    index += 1;
+                    offset = 0;
+                }
             }
+            cursor.row += num_rows;
+            cursor.buffer_position = Some((index, offset));
+            Ok(())
+        })?;
+        Ok(vec![dest])
+    }
+
+    fn decode_shared(&self, rows_to_skip: u64, num_rows: u64) -> Option<Vec<Bytes>> {
+        let bytes_to_skip = (rows_to_skip * self.bytes_per_value) as usize;
+        let bytes_to_take = (num_rows * self.bytes_per_value) as usize;
+        if self.is_compressed() {
+            // Errors are left for `decode` to report
+            let decoding_data = self.get_uncompressed_bytes().ok()?;
+            let buffers = decoding_data.lock().unwrap();
+            Self::slice_buffers(buffers.as_ref().unwrap(), bytes_to_skip, bytes_to_take)
         } else {
-            for buf in &self.data {
-                self.decode_buffer(buf, &mut bytes_to_skip, &mut bytes_to_take, dest);
-            }
+            Self::slice_buffers(&self.data, bytes_to_skip, bytes_to_take)
         }
-        Ok(dest_buffers)
+    }
+
+    fn can_skip(&self) -> bool {
+        !self.is_compressed()
     }
 
     fn num_buffers(&self) -> u32 {
@@ -237,43 +667,316 @@ impl PrimitivePageDecoder for ValuePageDecoder {
     }
 }
 
+/// Statistics about the data written by a [`ValueEncoder`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueEncoderStats {
+    /// The number of values encoded
+    pub num_values: u64,
+    /// The size of the values before encoding
+    pub raw_bytes: u64,
+    /// The size of the values after encoding
+    pub encoded_bytes: u64,
+}
+
+/// Configures and builds a [`ValueEncoder`]
+///
+/// ```
+/// # use arrow_schema::DataType;
+/// # use lance_encoding::encodings::physical::value::{CompressionScheme, ValueEncoderBuilder};
+/// let encoder = ValueEncoderBuilder::default()
+///     .compression(CompressionScheme::Zstd)
+///     .level(3)
+///     .collect_stats(true)
+///     .build(&DataType::Int32)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ValueEncoderBuilder {
+    compression: CompressionScheme,
+    level: Option<i32>,
+    min_compression_ratio: f64,
+    enable_bitpacking: bool,
+    collect_stats: bool,
+    compression_block_size: Option<u64>,
+    little_endian: bool,
+}
+
+impl Default for ValueEncoderBuilder {
+    fn default() -> Self {
+        Self {
+            compression: CompressionScheme::None,
+            level: None,
+            min_compression_ratio: DEFAULT_MIN_COMPRESSION_RATIO,
+            enable_bitpacking: false,
+            collect_stats: false,
+            compression_block_size: None,
+            little_endian: false,
+        }
+    }
+}
+
+impl ValueEncoderBuilder {
+    /// Sets the compression scheme applied to the values buffer
+    ///
+    /// [`CompressionScheme::Default`] resolves to a scheme (and possibly bitpacking) based
+    /// on the data type when the encoder is built.
+    pub fn compression(mut self, compression: CompressionScheme) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the compression level, only valid for schemes that support levels
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Sets the estimated compression ratio below which pages are stored uncompressed
+    ///
+    /// Before compressing a page a sample of the values is compressed to estimate how well
+    /// the page will compress.  Pages that are not expected to shrink by at least this
+    /// factor are stored as-is, which saves the cost of compressing (and decompressing)
+    /// incompressible data.  Defaults to [`DEFAULT_MIN_COMPRESSION_RATIO`], a ratio of 0
+    /// always compresses.
+    pub fn min_compression_ratio(mut self, min_compression_ratio: f64) -> Self {
+        self.min_compression_ratio = min_compression_ratio;
+        self
+    }
+
+    /// Bitpacks integer values when they fit in fewer bits than their type
+    ///
+    /// Bitpacked pages are not compressed and so this cannot be combined with a
+    /// compression scheme.
+    pub fn enable_bitpacking(mut self, enable_bitpacking: bool) -> Self {
+        self.enable_bitpacking = enable_bitpacking;
+        self
+    }
+
+    /// Records [`ValueEncoderStats`] for the data encoded by the encoder
+    pub fn collect_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+        self
+    }
+
+    /// Compresses pages in independent blocks of `block_size` uncompressed bytes
+    ///
+    /// Reading a few values from a page then only decompresses the blocks holding them
+    /// instead of the entire page.  Requires a compression scheme and blocks may be at
+    /// most `u32::MAX` bytes.  Block-compressed pages cannot be read by older versions.
+    pub fn compression_block_size(mut self, block_size: u64) -> Self {
+        self.compression_block_size = Some(block_size);
+        self
+    }
+
+    /// Stores values in little-endian byte order regardless of the host's byte order
+    ///
+    /// This makes the files canonical so they can be read on hosts of either byte order.
+    /// Values are only byte-swapped when writing on a big-endian host and the pages record
+    /// that they are little-endian.  Values made of several integers (intervals) are still
+    /// stored in the host's byte order.  Defaults to false, which stores values in the
+    /// host's byte order.
+    pub fn little_endian(mut self, little_endian: bool) -> Self {
+        self.little_endian = little_endian;
+        self
+    }
+
+    /// Builds an encoder for arrays of type `data_type`
+    ///
+    /// Fails if `data_type` cannot be encoded by a [`ValueEncoder`] or if the options
+    /// are not valid together
+    pub fn build(&self, data_type: &DataType) -> Result<ValueEncoder> {
+        if *data_type != DataType::Boolean && !data_type.is_fixed_stride() {
+            return Err(Error::unsupported_type(
+                data_type,
+                "a ValueEncoder can only encode fixed-width values",
+                location!(),
+            ));
+        }
+        if let DataType::FixedSizeBinary(width) = data_type {
+            if *width <= 0 {
+                return Err(Error::unsupported_type(
+                    data_type,
+                    "fixed size binary values must have a width of at least one byte",
+                    location!(),
+                ));
+            }
+        }
+        let (compression, enable_bitpacking) = match self.compression {
+            CompressionScheme::Default => {
+                let default_scheme = default_scheme_for(data_type);
+                (
+                    default_scheme.compression,
+                    self.enable_bitpacking || default_scheme.bitpacking,
+                )
+            }
+            compression => (compression, self.enable_bitpacking),
+        };
+        check_compression_level(compression, self.level)?;
+        if enable_bitpacking {
+            if compression != CompressionScheme::None {
+                return Err(Error::invalid_input(
+                    "Bitpacking cannot be combined with compression",
+                    location!(),
+                ));
+            }
+            if !is_bitpackable(data_type) {
+                return Err(Error::unsupported_type(
+                    data_type,
+                    "only integers can be bitpacked",
+                    location!(),
+                ));
+            }
+        }
+        if let Some(block_size) = self.compression_block_size {
+            if compression == CompressionScheme::None || *data_type == DataType::Boolean {
+                return Err(Error::invalid_input(
+                    "A compression block size requires a compressed, non-boolean page",
+                    location!(),
+                ));
+            }
+            if block_size == 0 || block_size > u32::MAX as u64 {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Invalid compression block size {}, it must be between 1 and {}",
+                        block_size,
+                        u32::MAX
+                    ),
+                    location!(),
+                ));
+            }
+        }
+
+        // Values without a byte order (e.g. bytes and bitmaps) are the same either way and
+        // values made of several integers (e.g. intervals) are left in the host's byte order
+        let little_endian = self.little_endian && byte_order_components(data_type).len() == 1;
+        let buffer_encoder: Box<dyn BufferEncoder> = if *data_type == DataType::Boolean {
+            Box::<BitmapBufferEncoder>::default()
+        } else {
+            match compression {
+                CompressionScheme::None if little_endian => {
+                    Box::new(FlatBufferEncoder::little_endian())
+                }
+                CompressionScheme::None => Box::<FlatBufferEncoder>::default(),
+                CompressionScheme::Zstd | CompressionScheme::Lz4 => {
+                    let mut encoder = CompressedBufferEncoder::with_compressor(buffer_compressor(
+                        compression,
+                        self.level,
+                    ))
+                    .with_min_compression_ratio(self.min_compression_ratio)
+                    .with_little_endian(little_endian);
+                    if let Some(block_size) = self.compression_block_size {
+                        encoder = encoder.with_block_size(block_size as usize);
+                    }
+                    Box::new(encoder)
+                }
+                CompressionScheme::Default => unreachable!("the default scheme is resolved above"),
+            }
+        };
+        Ok(ValueEncoder {
+            buffer_encoder,
+            compression_scheme: compression,
+            enable_bitpacking,
+            compression_blocks: self.compression_block_size.is_some(),
+            little_endian,
+            stats: self
+                .collect_stats
+                .then(|| Arc::new(Mutex::new(ValueEncoderStats::default()))),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct ValueEncoder {
     buffer_encoder: Box<dyn BufferEncoder>,
     compression_scheme: CompressionScheme,
+    enable_bitpacking: bool,
+    compression_blocks: bool,
+    little_endian: bool,
+    stats: Option<Arc<Mutex<ValueEncoderStats>>>,
+}
+
+// The width of the values of a fixed-width type, e.g. 1 for booleans
+fn bits_per_value(data_type: &DataType) -> u64 {
+    match data_type {
+        DataType::Boolean => 1,
+        DataType::FixedSizeBinary(width) => *width as u64 * 8,
+        DataType::FixedSizeList(field, dimension) => {
+            *dimension as u64 * bits_per_value(field.data_type())
+        }
+        _ => data_type.primitive_width().unwrap_or_default() as u64 * 8,
+    }
 }
 
 impl ValueEncoder {
     pub fn try_new(data_type: &DataType, compression_scheme: CompressionScheme) -> Result<Self> {
-        if *data_type == DataType::Boolean {
-            Ok(Self {
-                buffer_encoder: Box::<BitmapBufferEncoder>::default(),
-                compression_scheme,
-            })
-        } else if data_type.is_fixed_stride() {
-            Ok(Self {
-                buffer_encoder: if compression_scheme != CompressionScheme::None {
-                    Box::<CompressedBufferEncoder>::default()
-                } else {
-                    Box::<FlatBufferEncoder>::default()
-                },
-                compression_scheme,
-            })
-        } else {
-            Err(Error::invalid_input(
-                format!("Cannot use ValueEncoder to encode {}", data_type),
-                location!(),
-            ))
+        ValueEncoderBuilder::default()
+            .compression(compression_scheme)
+            .build(data_type)
+    }
+
+    /// The statistics collected so far, if the encoder was built to collect them
+    pub fn stats(&self) -> Option<ValueEncoderStats> {
+        self.stats.as_ref().map(|stats| *stats.lock().unwrap())
+    }
+
+    fn record_stats(&self, arrays: &[ArrayRef], encoded: &EncodedArray) {
+        if let Some(stats) = &self.stats {
+            let mut stats = stats.lock().unwrap();
+            for arr in arrays {
+                stats.num_values += arr.len() as u64;
+                // The values buffer of a slice holds values outside of the slice
+                stats.raw_bytes += (arr.len() as u64 * bits_per_value(arr.data_type())).div_ceil(8);
+            }
+            stats.encoded_bytes += encoded
+                .buffers
+                .iter()
+                .flat_map(|buffer| &buffer.parts)
+                .map(|part| part.len() as u64)
+                .sum::<u64>();
         }
     }
-}
 
-impl ArrayEncoder for ValueEncoder {
-    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+    // Describes the compression of a page, recording whether the buffer encoder decided
+    // to skip compression
+    fn compression(&self, decision: Option<CompressionDecision>) -> Option<pb::Compression> {
+        if self.compression_scheme == CompressionScheme::None {
+            return None;
+        }
+        let estimated_ratio = decision.map_or(0.0, |decision| decision.estimated_ratio as f32);
+        Some(match decision {
+            Some(decision) if !decision.compressed => pb::Compression {
+                scheme: CompressionScheme::None.to_string(),
+                requested_scheme: self.compression_scheme.to_string(),
+                estimated_ratio,
+            },
+            _ => pb::Compression {
+                scheme: self.compression_scheme.to_string(),
+                requested_scheme: String::new(),
+                estimated_ratio,
+            },
+        })
+    }
+
+    fn do_encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        if self.enable_bitpacking {
+            let data_type = arrays[0].data_type();
+            if let Some(num_bits) = num_compressed_bits(arrays) {
+                if num_bits < 8 * data_type.byte_width() as u64 {
+                    return BitpackedArrayEncoder::new(num_bits).encode(arrays, buffer_index);
+                }
+            }
+        }
+        // Booleans with long runs are stored as the run lengths instead of a bitmap
+        if *arrays[0].data_type() == DataType::Boolean {
+            let runs = BooleanRuns::from_arrays(arrays);
+            if runs.is_worth_encoding() {
+                return Ok(runs.encode(buffer_index));
+            }
+        }
         let index = *buffer_index;
         *buffer_index += 1;
 
-        let encoded_buffer = self.buffer_encoder.encode(arrays)?;
+        let (encoded_buffer, decision) = self.buffer_encoder.encode_with_decision(arrays)?;
         let array_bufs = vec![EncodedArrayBuffer {
             parts: encoded_buffer.parts,
             index,
@@ -284,6 +987,12 @@ impl ArrayEncoder for ValueEncoder {
             DataType::Boolean => 1,
             _ => 8 * data_type.byte_width() as u64,
         };
+        // Pages that end up stored uncompressed have no blocks
+        let version = if self.compression_blocks && decision.map_or(true, |d| d.compressed) {
+            FLAT_ENCODING_VERSION_BLOCKS
+        } else {
+            FLAT_ENCODING_VERSION
+        };
         let flat_encoding = pb::ArrayEncoding {
             array_encoding: Some(pb::array_encoding::ArrayEncoding::Flat(pb::Flat {
                 bits_per_value,
@@ -291,14 +1000,13 @@ impl ArrayEncoder for ValueEncoder {
                     buffer_index: index,
                     buffer_type: pb::buffer::BufferType::Page as i32,
                 }),
-                compression: if self.compression_scheme != CompressionScheme::None {
-                    Some(pb::Compression {
-                        scheme: self.compression_scheme.to_string(),
-                    })
-                } else {
-                    None
-                },
+                compression: self.compression(decision),
+                version,
+                little_endian: self.little_endian,
             })),
+            producer: None,
+            statistics: None,
+            data_type: None,
         };
 
         Ok(EncodedArray {
@@ -308,12 +1016,230 @@ impl ArrayEncoder for ValueEncoder {
     }
 }
 
+impl ArrayEncoder for ValueEncoder {
+    fn encode(&self, arrays: &[ArrayRef], buffer_index: &mut u32) -> Result<EncodedArray> {
+        let encoded = self.do_encode(arrays, buffer_index)?;
+        self.record_stats(arrays, &encoded);
+        Ok(encoded)
+    }
+}
+
+// Collects the flat encodings in `encoding`, at any depth
+fn flat_encodings_mut<'a>(encoding: &'a mut pb::ArrayEncoding, flats: &mut Vec<&'a mut pb::Flat>) {
+    use pb::array_encoding::ArrayEncoding;
+    use pb::nullable::Nullability;
+    let nested: Vec<Option<&mut pb::ArrayEncoding>> = match encoding.array_encoding.as_mut() {
+        Some(ArrayEncoding::Flat(flat)) => {
+            flats.push(flat);
+            vec![]
+        }
+        Some(ArrayEncoding::Nullable(nullable)) => match nullable.nullability.as_mut() {
+            Some(Nullability::NoNulls(no_nulls)) => vec![no_nulls.values.as_deref_mut()],
+            Some(Nullability::SomeNulls(some_nulls)) => vec![
+                some_nulls.validity.as_deref_mut(),
+                some_nulls.values.as_deref_mut(),
+            ],
+            _ => vec![],
+        },
+        Some(ArrayEncoding::DeltaOfDelta(delta_of_delta)) => {
+            vec![delta_of_delta.deltas.as_deref_mut()]
+        }
+        Some(ArrayEncoding::Quantized(quantized)) => vec![quantized.values.as_deref_mut()],
+        Some(ArrayEncoding::Sparse(sparse)) => {
+            vec![
+                sparse.positions.as_deref_mut(),
+                sparse.values.as_deref_mut(),
+            ]
+        }
+        Some(ArrayEncoding::BloomFiltered(bloom_filtered)) => {
+            vec![bloom_filtered.values.as_deref_mut()]
+        }
+        Some(ArrayEncoding::FixedSizeList(fixed_size_list)) => {
+            vec![fixed_size_list.items.as_deref_mut()]
+        }
+        Some(ArrayEncoding::List(list)) => vec![list.offsets.as_deref_mut()],
+        Some(ArrayEncoding::Binary(binary)) => {
+            vec![binary.indices.as_deref_mut(), binary.bytes.as_deref_mut()]
+        }
+        Some(ArrayEncoding::Fsst(fsst)) => vec![fsst.binary.as_deref_mut()],
+        Some(ArrayEncoding::Dictionary(dictionary)) => vec![
+            dictionary.indices.as_deref_mut(),
+            dictionary.items.as_deref_mut(),
+        ],
+        // Bitpacked buffers are never compressed, and the compression of a transform
+        // pipeline is one of its steps
+        Some(
+            ArrayEncoding::Bitpacked(_)
+            | ArrayEncoding::ChunkedBitpacked(_)
+            | ArrayEncoding::BooleanRle(_)
+            | ArrayEncoding::TransformPipeline(_)
+            | ArrayEncoding::Struct(_),
+        )
+        | None => vec![],
+    };
+    for nested in nested.into_iter().flatten() {
+        flat_encodings_mut(nested, flats);
+    }
+}
+
+/// Recompresses the compressed buffers of an encoded page with a different scheme or level
+///
+/// Each compressed flat buffer is decompressed and compressed again with `scheme` (and
+/// `level`, if the scheme supports levels) without decoding the values, e.g. to move a page
+/// from zstd level 3 to level 19 or from zstd to LZ4 in a background job.  `buffers` are the
+/// page buffers as passed to [`crate::decoder::decode_page`].  Everything else about the page
+/// is kept: buffers that are bitpacked or stored uncompressed are left as they are and pages
+/// compressed in blocks keep their blocks.  [`CompressionScheme::None`] stores the compressed
+/// buffers uncompressed.  A buffer may decompress to at most [`DEFAULT_MAX_DECOMPRESSED_SIZE`]
+/// bytes.
+///
+/// Returns the encoding and the buffers of the recompressed page.
+pub fn recompress(
+    encoding: &pb::ArrayEncoding,
+    buffers: &[Bytes],
+    scheme: CompressionScheme,
+    level: Option<i32>,
+) -> Result<(pb::ArrayEncoding, Vec<Bytes>)> {
+    if scheme == CompressionScheme::Default {
+        return Err(Error::invalid_input(
+            "A page must be recompressed with a concrete compression scheme",
+            location!(),
+        ));
+    }
+    check_compression_level(scheme, level)?;
+    let mut encoding = encoding.clone();
+    let mut buffers = buffers.to_vec();
+    let mut flats = Vec::new();
+    flat_encodings_mut(&mut encoding, &mut flats);
+    for flat in flats {
+        let old_scheme = match flat.compression.as_ref() {
+            Some(compression) => parse_compression_scheme(&compression.scheme)?,
+            None => CompressionScheme::None,
+        };
+        // Column and file buffers are not part of the page
+        let index = match flat.buffer.as_ref() {
+            Some(buffer) if buffer.buffer_type() == pb::buffer::BufferType::Page => {
+                buffer.buffer_index as usize
+            }
+            _ => continue,
+        };
+        if old_scheme == CompressionScheme::None {
+            continue;
+        }
+        let data = buffers.get(index).ok_or_else(|| {
+            Error::invalid_input(
+                format!(
+                    "The page encoding refers to buffer {} but only {} buffers were given",
+                    index,
+                    buffers.len()
+                ),
+                location!(),
+            )
+        })?;
+
+        let decompressor = GeneralBufferCompressor::get_compressor(&old_scheme.to_string());
+        let max_size = DEFAULT_MAX_DECOMPRESSED_SIZE;
+        let decompress = |data: &[u8], size_hint: usize, max_size: usize| -> Result<Vec<u8>> {
+            let mut uncompressed = Vec::with_capacity(size_hint);
+            decompressor.decompress_with_limit(data, &mut uncompressed, max_size)?;
+            Ok(uncompressed)
+        };
+        let blocks = if flat.version == FLAT_ENCODING_VERSION_BLOCKS {
+            let blocks = parse_blocks(data)?;
+            check_decompressed_size(
+                blocks.iter().map(|block| block.uncompressed_size).sum(),
+                max_size,
+            )?;
+            blocks
+                .iter()
+                .map(|block| {
+                    let size = block.uncompressed_size as usize;
+                    decompress(&block.data, size, size)
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![decompress(data, data.len(), max_size)?]
+        };
+        let uncompressed_size = blocks.iter().map(|block| block.len()).sum::<usize>();
+
+        let mut recompressed = Vec::with_capacity(uncompressed_size);
+        if scheme == CompressionScheme::None {
+            for block in blocks {
+                recompressed.extend_from_slice(&block);
+            }
+            flat.compression = None;
+            flat.version = FLAT_ENCODING_VERSION;
+        } else {
+            let compressor = buffer_compressor(scheme, level);
+            if flat.version == FLAT_ENCODING_VERSION_BLOCKS {
+                for block in blocks {
+                    // Each block becomes a single block again
+                    compress_blocks(
+                        compressor.as_ref(),
+                        &block,
+                        block.len().max(1),
+                        &mut recompressed,
+                    )?;
+                }
+            } else {
+                compressor.compress(&blocks[0], &mut recompressed)?;
+            }
+            flat.compression = Some(pb::Compression {
+                scheme: scheme.to_string(),
+                requested_scheme: String::new(),
+                estimated_ratio: uncompressed_size as f32 / recompressed.len().max(1) as f32,
+            });
+        }
+        buffers[index] = Bytes::from(recompressed);
+    }
+    Ok((encoding, buffers))
+}
+
 // public tests module because we share the PRIMITIVE_TYPES constant with fixed_size_list
 #[cfg(test)]
 pub(crate) mod tests {
+    use std::ops::Range;
+    use std::sync::{Arc, Mutex};
+
+    use arrow_array::{
+        cast::AsArray, types::Int32Type, ArrayRef, BooleanArray, FixedSizeBinaryArray,
+        Float64Array, Int32Array, IntervalDayTimeArray, IntervalMonthDayNanoArray, UInt8Array,
+    };
+    use arrow_buffer::{
+        Buffer, IntervalDayTime, IntervalMonthDayNano, MutableBuffer, ScalarBuffer,
+    };
     use arrow_schema::{DataType, Field, TimeUnit};
+    use bytes::{Bytes, BytesMut};
+    use lance_core::{error::EncodingError, Error};
+    use rand::Rng;
+
+    use crate::{
+        decoder::{
+            decode_page, DecodeCost, DecodeCpuClass, DecodeCursor, PageScheduler,
+            PrimitivePageDecoder, TileDecoder, TILE_ALIGNMENT,
+        },
+        encoder::{ArrayEncoder, EncodingOverride},
+        encodings::{
+            physical::{
+                basic::BasicEncoder,
+                bitpack::BitpackedScheduler,
+                buffers::{BufferCompressor, ZstdBufferCompressor, DEFAULT_MAX_DECOMPRESSED_SIZE},
+            },
+            utils::primitive_array_from_shared_buffers,
+        },
+        format::pb,
+        testing::{
+            check_round_trip_encoding_of_data, check_round_trip_encoding_random,
+            check_round_trip_encoding_random_with_override, TestCases,
+        },
+        BufferScheduler, EncodingsIo,
+    };
 
-    use crate::testing::check_round_trip_encoding_random;
+    use super::{
+        default_scheme_for, parse_compression_scheme, parse_requested_compression_scheme,
+        recompress, CompressionScheme, DefaultScheme, ValueEncoder, ValueEncoderBuilder,
+        ValuePageDecoder, ValuePageScheduler,
+    };
 
     const PRIMITIVE_TYPES: &[DataType] = &[
         DataType::FixedSizeBinary(2),
@@ -336,9 +1262,8 @@ pub(crate) mod tests {
         DataType::Time32(TimeUnit::Second),
         DataType::Time64(TimeUnit::Nanosecond),
         DataType::Duration(TimeUnit::Second),
-        // The Interval type is supported by the reader but the writer works with Lance schema
-        // at the moment and Lance schema can't parse interval
-        // DataType::Interval(IntervalUnit::DayTime),
+        // Lance schema can't parse the Interval type and so intervals are tested separately
+        // (see test_interval)
     ];
 
     #[test_log::test(tokio::test)]
@@ -348,4 +1273,1025 @@ pub(crate) mod tests {
             check_round_trip_encoding_random(field).await;
         }
     }
+
+    #[test]
+    fn test_interval() {
+        let day_time =
+            Arc::new(IntervalDayTimeArray::from_iter((0..1000).map(|i| {
+                (i % 5 != 0).then(|| IntervalDayTime::new(i, -i * 1000))
+            }))) as ArrayRef;
+        let month_day_nano = Arc::new(IntervalMonthDayNanoArray::from_iter_values(
+            (0..1000).map(|i| IntervalMonthDayNano::new(i % 12, i, i as i64 * 1_000_000_007)),
+        )) as ArrayRef;
+        for array in [day_time, month_day_nano] {
+            for compression in [
+                CompressionScheme::None,
+                CompressionScheme::Zstd,
+                CompressionScheme::Lz4,
+            ] {
+                let encoder = BasicEncoder::new(Box::new(
+                    ValueEncoder::try_new(array.data_type(), compression).unwrap(),
+                ));
+                let arrays = [array.slice(0, 300), array.slice(300, 700)];
+                let mut encoded = encoder.encode(&arrays, &mut 0).unwrap();
+                encoded.buffers.sort_by_key(|buffer| buffer.index);
+                let buffers = encoded
+                    .buffers
+                    .iter()
+                    .map(|buffer| {
+                        Bytes::from(
+                            buffer
+                                .parts
+                                .iter()
+                                .flat_map(|part| part.as_slice().to_vec())
+                                .collect::<Vec<_>>(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                for range in [0..1000, 299..301, 999..1000] {
+                    let decoded = decode_page(
+                        &encoded.encoding,
+                        &buffers,
+                        array.data_type(),
+                        range.clone(),
+                    )
+                    .unwrap();
+                    let expected =
+                        array.slice(range.start as usize, (range.end - range.start) as usize);
+                    assert_eq!(decoded.as_ref(), expected.as_ref());
+                }
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_fixed_size_binary_widths() {
+        // Widths of a byte and widths larger than any primitive type (e.g. hashes and
+        // signatures), with and without nulls
+        for width in [1, 16, 32, 64] {
+            for nullable in [false, true] {
+                let field = Field::new("", DataType::FixedSizeBinary(width), nullable);
+                check_round_trip_encoding_random(field).await;
+            }
+        }
+
+        // Partial reads must compute byte offsets with the full width of each value
+        let values = Arc::new(
+            FixedSizeBinaryArray::try_from_iter(
+                (0..1000_u32).map(|i| [i.to_le_bytes(); 8].concat()),
+            )
+            .unwrap(),
+        ) as ArrayRef;
+        let test_cases = TestCases::default()
+            .with_range(0..1)
+            .with_range(333..777)
+            .with_indices(vec![0, 1, 499, 500, 998, 999])
+            .with_indices(vec![250, 750]);
+        check_round_trip_encoding_of_data(
+            vec![values.slice(0, 500), values.slice(500, 500)],
+            &test_cases,
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_fixed_size_binary_zero_width() {
+        let err = ValueEncoderBuilder::default()
+            .build(&DataType::FixedSizeBinary(0))
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::Encoding {
+                    source: EncodingError::UnsupportedType { .. },
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("width"), "{}", err);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_forced_flat() {
+        for data_type in PRIMITIVE_TYPES.iter().chain([&DataType::Boolean]) {
+            let field = Field::new("", data_type.clone(), true);
+            check_round_trip_encoding_random_with_override(field, EncodingOverride::ForceFlat)
+                .await;
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_empty_arrays() {
+        let values = Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as ArrayRef;
+        let empty = Arc::new(Int32Array::from(Vec::<i32>::new())) as ArrayRef;
+
+        // Empty arrays at the start, middle, and end should all be skipped
+        let test_cases = TestCases::default()
+            .with_range(0..0)
+            .with_range(2..4)
+            .with_indices(vec![])
+            .with_indices(vec![1, 5]);
+        check_round_trip_encoding_of_data(
+            vec![empty.clone(), values.clone(), empty.clone(), values, empty],
+            &test_cases,
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_compression_scheme_all() {
+        let names = CompressionScheme::all()
+            .iter()
+            .map(|scheme| scheme.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["none", "zstd", "lz4"]);
+
+        for scheme in CompressionScheme::all() {
+            let parsed = parse_compression_scheme(&scheme.to_string()).unwrap();
+            assert_eq!(parsed, *scheme);
+        }
+
+        assert!(
+            CompressionScheme::None
+                .capabilities()
+                .supports_random_access
+        );
+        assert!(
+            !CompressionScheme::Zstd
+                .capabilities()
+                .supports_random_access
+        );
+        assert!(CompressionScheme::Zstd.capabilities().supports_levels);
+        for scheme in CompressionScheme::all() {
+            assert!(!scheme.capabilities().supports_dictionaries);
+        }
+    }
+
+    // A flat and a Zstd compressed page of the 4 byte values in `data`, each with its
+    // scheduler and an I/O service serving the page
+    fn int32_pages(data: &[u8]) -> [(ValuePageScheduler, Arc<dyn EncodingsIo>); 2] {
+        let mut compressed = Vec::new();
+        ZstdBufferCompressor::default()
+            .compress(data, &mut compressed)
+            .unwrap();
+        let page = |compression_scheme, page: Vec<u8>| {
+            (
+                ValuePageScheduler::new(4, 0, page.len() as u64, compression_scheme),
+                Arc::new(BufferScheduler::new(Bytes::from(page))) as Arc<dyn EncodingsIo>,
+            )
+        };
+        [
+            page(CompressionScheme::None, data.to_vec()),
+            page(CompressionScheme::Zstd, compressed),
+        ]
+    }
+
+    // Schedules the same ranges of both pages from `int32_pages`
+    async fn schedule_int32_pages(
+        data: &[u8],
+        ranges: &[Range<u64>],
+    ) -> [Box<dyn PrimitivePageDecoder>; 2] {
+        let [(flat, flat_io), (compressed, compressed_io)] = int32_pages(data);
+        [
+            flat.schedule_ranges(ranges, &flat_io, 0).await.unwrap(),
+            compressed
+                .schedule_ranges(ranges, &compressed_io, 0)
+                .await
+                .unwrap(),
+        ]
+    }
+
+    // A decoder of 4 byte values from a Zstd compressed page, as if the bytes at
+    // `range_offsets` of the decompressed page had been requested
+    fn zstd_page_decoder(
+        page: Vec<u8>,
+        range_offsets: &[Range<usize>],
+        compression_blocks: bool,
+        max_decompressed_size: usize,
+    ) -> ValuePageDecoder {
+        ValuePageDecoder {
+            bytes_per_value: 4,
+            data: vec![Bytes::from(page)],
+            uncompressed_data: Arc::new(Mutex::new(None)),
+            uncompressed_range_offsets: range_offsets.to_vec(),
+            compression_scheme: CompressionScheme::Zstd,
+            compression_blocks,
+            byte_swap: &[],
+            max_decompressed_size,
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_shared() {
+        let values = (0..100).collect::<Vec<i32>>();
+        // Start the buffer at an odd offset so the shared data is misaligned
+        let mut data = vec![0_u8];
+        data.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        let io = Arc::new(BufferScheduler::new(Bytes::from(data.clone()))) as Arc<dyn EncodingsIo>;
+        let scheduler = ValuePageScheduler::new(4, 1, 400, CompressionScheme::None);
+        let decoder = scheduler
+            .schedule_ranges(&[0..50, 60..100], &io, 0)
+            .await
+            .unwrap();
+
+        let check = |rows_to_skip: u64, num_rows: u64, expected: &[i32]| {
+            let buffers = decoder.decode_shared(rows_to_skip, num_rows).unwrap();
+            let array = primitive_array_from_shared_buffers(
+                &DataType::Int32,
+                vec![Bytes::new(), buffers[0].clone()],
+                num_rows,
+            )
+            .unwrap();
+            assert_eq!(array.as_primitive::<Int32Type>().values(), expected);
+        };
+        check(0, 50, &values[0..50]);
+        check(10, 20, &values[10..30]);
+        check(55, 10, &values[65..75]);
+
+        // Rows that span both loaded ranges must be copied
+        assert!(decoder.decode_shared(40, 20).is_none());
+
+        let [_, (scheduler, io)] = int32_pages(&data[1..]);
+        let decoder = scheduler
+            .schedule_ranges(std::slice::from_ref(&(0..100)), &io, 0)
+            .await
+            .unwrap();
+        let buffers = decoder.decode_shared(0, 100).unwrap();
+        let array = primitive_array_from_shared_buffers(
+            &DataType::Int32,
+            vec![Bytes::new(), buffers[0].clone()],
+            100,
+        )
+        .unwrap();
+        assert_eq!(array.as_primitive::<Int32Type>().values(), &values);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_into() {
+        let values = (0..100).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let [(flat, flat_io), (compressed, compressed_io)] = int32_pages(&data);
+        let flat = flat
+            .schedule_ranges(&[0..50, 60..100], &flat_io, 0)
+            .await
+            .unwrap();
+        let compressed = compressed
+            .schedule_ranges(std::slice::from_ref(&(0..100)), &compressed_io, 0)
+            .await
+            .unwrap();
+
+        // Several pages can be decoded, one after the other, into one pre-sized buffer
+        let mut dest = MutableBuffer::with_capacity(70 * 4);
+        let ptr = dest.as_ptr();
+        flat.decode_into(40, 20, &mut dest).unwrap();
+        compressed.decode_into(50, 50, &mut dest).unwrap();
+        assert_eq!(dest.as_ptr(), ptr);
+
+        let array = Int32Array::new(ScalarBuffer::from(Buffer::from(dest)), None);
+        let expected = values[40..50]
+            .iter()
+            .chain(&values[60..70])
+            .chain(&values[50..100])
+            .copied()
+            .collect::<Int32Array>();
+        assert_eq!(array, expected);
+
+        // Asking for more rows than were loaded is an error
+        let mut dest = MutableBuffer::new(0);
+        assert!(flat.decode_into(80, 20, &mut dest).is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_byte_swap() {
+        // Values in the other byte order than the host's, as a big-endian host sees a
+        // little-endian page
+        let values = (0..100).map(|i| i * 70_001).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.swap_bytes().to_ne_bytes())
+            .collect::<Vec<_>>();
+        for (scheduler, io) in int32_pages(&data) {
+            let decoder = scheduler
+                .with_byte_swap(&[4])
+                .schedule_ranges(&[10..20, 90..100], &io, 0)
+                .await
+                .unwrap();
+            let mut dest = MutableBuffer::new(0);
+            decoder.decode_into(0, 20, &mut dest).unwrap();
+            let array = Int32Array::new(ScalarBuffer::from(Buffer::from(dest)), None);
+            let expected = values[10..20]
+                .iter()
+                .chain(&values[90..100])
+                .copied()
+                .collect::<Int32Array>();
+            assert_eq!(array, expected);
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_tiles() {
+        // Start at 1 so the zero padding can be told apart from the values
+        let values = (1..=200).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let [flat, compressed] = schedule_int32_pages(&data, std::slice::from_ref(&(0..200))).await;
+
+        for decoder in [&flat, &compressed] {
+            let mut tiles = TileDecoder::try_new(decoder.as_ref(), 0, 200, 64, 32).unwrap();
+            assert_eq!(tiles.remaining_tiles(), 4);
+            let mut tile_index = 0;
+            while let Some(tile) = tiles.next_tile().unwrap() {
+                assert_eq!(tile.values.as_ptr() as usize % TILE_ALIGNMENT, 0);
+                assert_eq!(tile.values.len(), 64 * 4);
+                let start = tile_index * 64;
+                let num_values = (values.len() - start).min(64);
+                assert_eq!(tile.num_values, num_values as u64);
+
+                let mut expected = values[start..start + num_values].to_vec();
+                expected.resize(64, 0);
+                let actual = tile
+                    .values
+                    .chunks_exact(4)
+                    .map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()))
+                    .collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+                tile_index += 1;
+            }
+            assert_eq!(tile_index, 4);
+            assert_eq!(tiles.remaining_tiles(), 0);
+        }
+
+        assert!(TileDecoder::try_new(flat.as_ref(), 0, 200, 0, 32).is_err());
+        assert!(TileDecoder::try_new(flat.as_ref(), 0, 200, 64, 0).is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_more() {
+        let values = (0..100).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let [flat, compressed] = schedule_int32_pages(&data, &[0..50, 60..100]).await;
+
+        for decoder in [&flat, &compressed] {
+            let full = decoder.decode(5, 85, &mut false).unwrap();
+
+            // Three steps, the second one spans both loaded ranges
+            let mut cursor = DecodeCursor::new(5);
+            let mut resumed = BytesMut::new();
+            for num_rows in [40, 30, 15] {
+                let buffers = decoder.decode_more(&mut cursor, num_rows).unwrap();
+                assert_eq!(buffers.len(), 1);
+                resumed.extend_from_slice(&buffers[0]);
+            }
+            assert_eq!(cursor.row(), 90);
+            assert_eq!(resumed, full[0]);
+
+            // Asking for more rows than are left is an error and leaves the cursor in place
+            let mut before = cursor.clone();
+            assert!(decoder.decode_more(&mut cursor, 5).is_err());
+            assert_eq!(cursor, before);
+            let buffers = decoder.decode_more(&mut before, 0).unwrap();
+            assert!(buffers[0].is_empty());
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_ranges() {
+        let values = (0..100).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let [flat, compressed] = schedule_int32_pages(&data, &[0..50, 60..100]).await;
+
+        // Three disjoint ranges, out of order and one spanning both loaded ranges
+        let ranges = [70..90, 5..15, 45..55];
+        for decoder in [&flat, &compressed] {
+            let buffers = decoder.decode_ranges(&ranges).unwrap();
+            assert_eq!(buffers.len(), ranges.len());
+            for (buffer, range) in buffers.iter().zip(&ranges) {
+                let expected = decoder
+                    .decode(range.start, range.end - range.start, &mut false)
+                    .unwrap();
+                assert_eq!(buffer, &expected[0]);
+            }
+            assert_eq!(
+                buffers[2].as_ref(),
+                values[45..50]
+                    .iter()
+                    .chain(&values[60..65])
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect::<Vec<_>>()
+            );
+            // Ranges past the loaded rows are an error
+            assert!(decoder.decode_ranges(&[0..10, 85..95]).is_err());
+        }
+        assert_eq!(
+            compressed.decode_ranges(&[]).unwrap(),
+            Vec::<BytesMut>::new()
+        );
+    }
+
+    #[test]
+    fn test_decompress_full_page() {
+        let values = (0..1000)
+            .flat_map(|v: i32| (v % 7).to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut compressed = Vec::new();
+        ZstdBufferCompressor::default()
+            .compress(&values, &mut compressed)
+            .unwrap();
+        let decoder = |range_offsets: &[Range<usize>]| {
+            zstd_page_decoder(
+                compressed.clone(),
+                range_offsets,
+                false,
+                DEFAULT_MAX_DECOMPRESSED_SIZE,
+            )
+        };
+
+        // The whole page is requested so the decompressed buffer is shared, not copied per range
+        let full = decoder(std::slice::from_ref(&(0..4000)));
+        let shared = full.decode_shared(10, 20).unwrap();
+        {
+            let cached = full.uncompressed_data.lock().unwrap();
+            let cached = cached.as_ref().unwrap();
+            assert_eq!(cached.len(), 1);
+            assert_eq!(cached[0].as_ref(), values.as_slice());
+            assert_eq!(shared[0].as_ptr(), cached[0][40..].as_ptr());
+        }
+        assert_eq!(
+            full.decode(10, 20, &mut false).unwrap()[0].as_ref(),
+            &values[40..120]
+        );
+
+        // Partial ranges are still cut out of the decompressed page
+        let partial = decoder(&[0..400, 800..1200]);
+        assert_eq!(
+            partial.decode(95, 10, &mut false).unwrap()[0].as_ref(),
+            [&values[380..400], &values[800..820]].concat()
+        );
+        assert!(partial.decode_shared(95, 10).is_none());
+    }
+
+    #[test]
+    fn test_decompress_blocks() {
+        // Four blocks of 100 values, framed by hand with little-endian headers
+        let values = (0..400)
+            .flat_map(|v: i32| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut page = Vec::new();
+        for (block_idx, block) in values.chunks(400).enumerate() {
+            let mut compressed = Vec::new();
+            ZstdBufferCompressor::default()
+                .compress(block, &mut compressed)
+                .unwrap();
+            if block_idx == 1 || block_idx == 2 {
+                // Blocks that are not needed are never decompressed
+                compressed.iter_mut().for_each(|byte| *byte = 0xFF);
+            }
+            page.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            page.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            page.extend_from_slice(&compressed);
+        }
+        let decoder = |range_offsets: &[Range<usize>]| {
+            zstd_page_decoder(
+                page.clone(),
+                range_offsets,
+                true,
+                DEFAULT_MAX_DECOMPRESSED_SIZE,
+            )
+        };
+
+        let first_block = decoder(std::slice::from_ref(&(40..80)));
+        assert_eq!(
+            first_block.decode(0, 10, &mut false).unwrap()[0].as_ref(),
+            &values[40..80]
+        );
+        let last_block = decoder(std::slice::from_ref(&(1200..1600)));
+        assert_eq!(
+            last_block.decode(0, 100, &mut false).unwrap()[0].as_ref(),
+            &values[1200..1600]
+        );
+        assert_eq!(
+            last_block.decode_shared(90, 10).unwrap()[0].as_ref(),
+            &values[1560..1600]
+        );
+        // Reading through a corrupt block fails
+        let corrupt = decoder(&[40..80, 1200..1240]);
+        assert!(corrupt.decode(0, 20, &mut false).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_decompression_limit() {
+        let is_corrupt = |err: Error| {
+            assert!(
+                matches!(
+                    err,
+                    Error::Encoding {
+                        source: EncodingError::CorruptMetadata { .. },
+                        ..
+                    }
+                ),
+                "{}",
+                err
+            );
+        };
+        let mut compressed = Vec::new();
+        ZstdBufferCompressor::default()
+            .compress(&[0; 4000], &mut compressed)
+            .unwrap();
+        let decoder = |page, compression_blocks, max_decompressed_size| {
+            zstd_page_decoder(page, &[0..4000], compression_blocks, max_decompressed_size)
+        };
+
+        // A block that claims to be huge fails before anything is allocated
+        let mut page = Vec::new();
+        page.extend_from_slice(&u32::MAX.to_le_bytes());
+        page.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        page.extend_from_slice(&compressed);
+        let bomb = decoder(page, true, DEFAULT_MAX_DECOMPRESSED_SIZE);
+        is_corrupt(bomb.decode(0, 1000, &mut false).unwrap_err());
+
+        // A block may not decompress to more than its recorded size
+        let mut page = Vec::new();
+        page.extend_from_slice(&100_u32.to_le_bytes());
+        page.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        page.extend_from_slice(&compressed);
+        let liar = decoder(page, true, DEFAULT_MAX_DECOMPRESSED_SIZE);
+        is_corrupt(liar.decode(0, 1000, &mut false).unwrap_err());
+
+        // A page without recorded sizes stops decompressing at the limit
+        let over_limit = decoder(compressed.clone(), false, 1000);
+        is_corrupt(over_limit.decode(0, 1000, &mut false).unwrap_err());
+        let at_limit = decoder(compressed, false, 4000);
+        assert_eq!(
+            at_limit.decode(0, 1000, &mut false).unwrap()[0].as_ref(),
+            &[0; 4000]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_estimate_cost() {
+        // 1000 int32 values.  The compressed buffer is 700 bytes
+        let flat = ValuePageScheduler::new(4, 100, 4000, CompressionScheme::None);
+        let compressed = ValuePageScheduler::new(4, 100, 700, CompressionScheme::Zstd);
+        let bitpacked = BitpackedScheduler::new(5, 32, 100, true);
+
+        // A point read only needs to fetch the one value from a flat page
+        assert_eq!(
+            flat.estimate_cost(&[500..501]),
+            DecodeCost::new(4, DecodeCpuClass::Copy)
+        );
+        assert_eq!(
+            flat.estimate_cost(&[0..10, 500..600]),
+            DecodeCost::new(440, DecodeCpuClass::Copy)
+        );
+        // A compressed page must be fetched in full, no matter how few rows are read
+        assert_eq!(
+            compressed.estimate_cost(&[500..501]),
+            DecodeCost::new(700, DecodeCpuClass::Decompress)
+        );
+        assert_eq!(
+            compressed.estimate_cost(&[0..1000]),
+            DecodeCost::new(700, DecodeCpuClass::Decompress)
+        );
+        // Bits 2500..2505 span bytes 312..314
+        assert_eq!(
+            bitpacked.estimate_cost(&[500..501]),
+            DecodeCost::new(2, DecodeCpuClass::Unpack)
+        );
+        assert_eq!(
+            bitpacked.estimate_cost(&[0..1000]),
+            DecodeCost::new(625, DecodeCpuClass::Unpack)
+        );
+
+        assert_eq!(flat.estimate_cost(&[]), DecodeCost::default());
+        assert_eq!(compressed.estimate_cost(&[]), DecodeCost::default());
+
+        // Decompressing a page takes longer than copying a flat page of the same size
+        let flat_700 = ValuePageScheduler::new(4, 100, 700, CompressionScheme::None);
+        let flat_cost = flat_700.estimate_cost(&[0..175]);
+        let compressed_cost = compressed.estimate_cost(&[0..1000]);
+        assert_eq!(flat_cost.bytes_to_fetch, compressed_cost.bytes_to_fetch);
+        assert!(compressed_cost.estimate_decode_time() > flat_cost.estimate_decode_time());
+        assert!(
+            DecodeCost::new(700, DecodeCpuClass::Unpack).estimate_decode_time()
+                > flat_cost.estimate_decode_time()
+        );
+        assert_eq!(
+            compressed_cost.estimate_decode_time(),
+            std::time::Duration::from_nanos(700 * 1_000_000_000 / (512 * 1024 * 1024))
+        );
+        assert!(DecodeCost::default().estimate_decode_time().is_zero());
+    }
+
+    #[test]
+    fn test_builder() {
+        let int_values: ArrayRef = Arc::new(Int32Array::from_iter_values(0..1000));
+
+        let encoder = ValueEncoderBuilder::default()
+            .compression(CompressionScheme::Zstd)
+            .level(19)
+            .collect_stats(true)
+            .build(&DataType::Int32)
+            .unwrap();
+        let encoded = encoder.encode(&[int_values.clone()], &mut 0).unwrap();
+        let Some(pb::array_encoding::ArrayEncoding::Flat(flat)) = encoded.encoding.array_encoding
+        else {
+            panic!("expected a flat encoding")
+        };
+        assert_eq!(flat.compression.unwrap().scheme, "zstd");
+        let stats = encoder.stats().unwrap();
+        assert_eq!(stats.num_values, 1000);
+        assert_eq!(stats.raw_bytes, 4000);
+        assert!(stats.encoded_bytes < stats.raw_bytes);
+
+        // Values 0..1000 fit in 11 bits (10 bits and a sign bit)
+        let encoder = ValueEncoderBuilder::default()
+            .enable_bitpacking(true)
+            .collect_stats(true)
+            .build(&DataType::Int32)
+            .unwrap();
+        let encoded = encoder.encode(&[int_values], &mut 0).unwrap();
+        assert!(matches!(
+            encoded.encoding.array_encoding,
+            Some(pb::array_encoding::ArrayEncoding::Bitpacked(_))
+        ));
+        assert_eq!(encoder.stats().unwrap().encoded_bytes, 1375);
+
+        // Only the values in a slice count
+        let encoder = ValueEncoderBuilder::default()
+            .collect_stats(true)
+            .build(&DataType::Int32)
+            .unwrap();
+        let sliced = Int32Array::from_iter_values(0..1000).slice(100, 10);
+        encoder
+            .encode(&[Arc::new(sliced) as ArrayRef], &mut 0)
+            .unwrap();
+        let stats = encoder.stats().unwrap();
+        assert_eq!((stats.num_values, stats.raw_bytes), (10, 40));
+
+        // Booleans are bit-packed
+        let encoder = ValueEncoderBuilder::default()
+            .collect_stats(true)
+            .build(&DataType::Boolean)
+            .unwrap();
+        let booleans = BooleanArray::from_iter((0..100).map(|i| Some(i % 3 == 0)));
+        encoder
+            .encode(&[Arc::new(booleans.slice(4, 90)) as ArrayRef], &mut 0)
+            .unwrap();
+        let stats = encoder.stats().unwrap();
+        assert_eq!((stats.num_values, stats.raw_bytes), (90, 12));
+
+        let encoder = ValueEncoder::try_new(&DataType::Boolean, CompressionScheme::None).unwrap();
+        assert!(encoder.stats().is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_typed_errors() {
+        let encoding_error = |err: Error| match err {
+            Error::Encoding { source, .. } => source,
+            err => panic!("expected an encoding error but got {}", err),
+        };
+
+        let err = encoding_error(parse_compression_scheme("lz5").unwrap_err());
+        assert!(matches!(err, EncodingError::UnknownScheme { scheme } if scheme == "lz5"));
+
+        // A page never records the default scheme, only what it resolved to
+        let err = encoding_error(parse_compression_scheme("default").unwrap_err());
+        assert!(matches!(err, EncodingError::CorruptMetadata { .. }));
+
+        let err = encoding_error(
+            ValueEncoderBuilder::default()
+                .build(&DataType::Utf8)
+                .unwrap_err(),
+        );
+        assert!(matches!(
+            err,
+            EncodingError::UnsupportedType {
+                data_type: DataType::Utf8,
+                ..
+            }
+        ));
+
+        // A page that claims more rows than its buffer holds
+        let values = (0..100)
+            .flat_map(|v: i32| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let [(flat, flat_io), (compressed, compressed_io)] = int32_pages(&values);
+        let decoder = flat
+            .schedule_ranges(std::slice::from_ref(&(0..100)), &flat_io, 0)
+            .await
+            .unwrap();
+        let err = encoding_error(decoder.decode(90, 20, &mut false).unwrap_err());
+        assert!(matches!(
+            err,
+            EncodingError::BufferTooShort {
+                expected: 440,
+                actual: 400,
+                ..
+            }
+        ));
+
+        let decoder = compressed
+            .schedule_ranges(std::slice::from_ref(&(0..200)), &compressed_io, 0)
+            .await
+            .unwrap();
+        let err = encoding_error(decoder.decode(0, 200, &mut false).unwrap_err());
+        assert!(matches!(
+            err,
+            EncodingError::BufferTooShort {
+                expected: 800,
+                actual: 400,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_default_scheme() {
+        // There is no byte stream split scheme (yet) so floats use zstd
+        assert_eq!(
+            default_scheme_for(&DataType::Float64),
+            DefaultScheme {
+                compression: CompressionScheme::Zstd,
+                bitpacking: false,
+            }
+        );
+        assert_eq!(
+            default_scheme_for(&DataType::Int32),
+            DefaultScheme {
+                compression: CompressionScheme::None,
+                bitpacking: true,
+            }
+        );
+        assert_eq!(
+            default_scheme_for(&DataType::Utf8).compression,
+            CompressionScheme::Zstd
+        );
+        assert_eq!(
+            default_scheme_for(&DataType::Boolean).compression,
+            CompressionScheme::None
+        );
+        assert_eq!(
+            parse_requested_compression_scheme("default").unwrap(),
+            CompressionScheme::Default
+        );
+        assert_eq!(
+            parse_requested_compression_scheme("zstd").unwrap(),
+            CompressionScheme::Zstd
+        );
+
+        // The resolved scheme is recorded in the page encoding
+        let encode = |values: ArrayRef| {
+            let encoder = ValueEncoderBuilder::default()
+                .compression(CompressionScheme::Default)
+                .min_compression_ratio(0.0)
+                .build(values.data_type())
+                .unwrap();
+            encoder.encode(&[values], &mut 0).unwrap().encoding
+        };
+        let encoding = encode(Arc::new(Float64Array::from_iter_values(
+            (0..1000).map(|i| (i / 10) as f64),
+        )));
+        let Some(pb::array_encoding::ArrayEncoding::Flat(flat)) = encoding.array_encoding else {
+            panic!("expected a flat encoding")
+        };
+        assert_eq!(flat.compression.unwrap().scheme, "zstd");
+
+        let encoding = encode(Arc::new(Int32Array::from_iter_values(0..1000)));
+        assert!(matches!(
+            encoding.array_encoding,
+            Some(pb::array_encoding::ArrayEncoding::Bitpacked(_))
+        ));
+        // Integers that cannot be bitpacked are stored flat
+        let encoding = encode(Arc::new(Int32Array::from(vec![i32::MIN, i32::MAX])));
+        let Some(pb::array_encoding::ArrayEncoding::Flat(flat)) = encoding.array_encoding else {
+            panic!("expected a flat encoding")
+        };
+        assert!(flat.compression.is_none());
+
+        // Zstd levels can only be used if the default resolves to zstd
+        assert!(ValueEncoderBuilder::default()
+            .compression(CompressionScheme::Default)
+            .level(3)
+            .build(&DataType::Float32)
+            .is_ok());
+        assert!(ValueEncoderBuilder::default()
+            .compression(CompressionScheme::Default)
+            .level(3)
+            .build(&DataType::Int32)
+            .is_err());
+    }
+
+    #[test]
+    fn test_skip_incompressible() {
+        let encode = |values: ArrayRef| {
+            let encoder = ValueEncoderBuilder::default()
+                .compression(CompressionScheme::Zstd)
+                .build(values.data_type())
+                .unwrap();
+            let encoded = encoder.encode(&[values], &mut 0).unwrap();
+            let Some(pb::array_encoding::ArrayEncoding::Flat(flat)) =
+                encoded.encoding.array_encoding
+            else {
+                panic!("expected a flat encoding")
+            };
+            (flat.compression.unwrap(), encoded.buffers)
+        };
+
+        // Random bytes, larger than the sample, are stored as-is
+        let mut rng = rand::thread_rng();
+        let random_bytes = (0..256 * 1024).map(|_| rng.gen()).collect::<Vec<u8>>();
+        let (compression, buffers) = encode(Arc::new(UInt8Array::from(random_bytes.clone())));
+        assert_eq!(compression.scheme, "none");
+        assert_eq!(compression.requested_scheme, "zstd");
+        assert!(compression.estimated_ratio < 1.1);
+        assert_eq!(buffers[0].parts[0].as_slice(), random_bytes.as_slice());
+
+        // Structured values are compressed
+        let (compression, buffers) = encode(Arc::new(Int32Array::from_iter_values(
+            (0..64 * 1024).map(|i| i / 16),
+        )));
+        assert_eq!(compression.scheme, "zstd");
+        assert_eq!(compression.requested_scheme, "");
+        assert!(compression.estimated_ratio > 2.0);
+        assert!(buffers[0].parts[0].len() < 64 * 1024);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_options() {
+        // A level without a scheme that supports levels
+        assert!(ValueEncoderBuilder::default()
+            .level(3)
+            .build(&DataType::Int32)
+            .is_err());
+        // A level outside of the range supported by the scheme
+        assert!(ValueEncoderBuilder::default()
+            .compression(CompressionScheme::Zstd)
+            .level(1000)
+            .build(&DataType::Int32)
+            .is_err());
+        // Bitpacking is incompatible with compression and non-integer types
+        assert!(ValueEncoderBuilder::default()
+            .compression(CompressionScheme::Zstd)
+            .enable_bitpacking(true)
+            .build(&DataType::Int32)
+            .is_err());
+        assert!(ValueEncoderBuilder::default()
+            .enable_bitpacking(true)
+            .build(&DataType::Float32)
+            .is_err());
+        // Variable-width types cannot be encoded at all
+        assert!(ValueEncoderBuilder::default()
+            .build(&DataType::Utf8)
+            .is_err());
+        // Compression blocks need a compressed page and a block size that fits the framing
+        assert!(ValueEncoderBuilder::default()
+            .compression_block_size(1024)
+            .build(&DataType::Int32)
+            .is_err());
+        assert!(ValueEncoderBuilder::default()
+            .compression(CompressionScheme::Zstd)
+            .compression_block_size(1024)
+            .build(&DataType::Boolean)
+            .is_err());
+        for block_size in [0, u32::MAX as u64 + 1] {
+            assert!(ValueEncoderBuilder::default()
+                .compression(CompressionScheme::Zstd)
+                .compression_block_size(block_size)
+                .build(&DataType::Int32)
+                .is_err());
+        }
+    }
+
+    // Encodes `values` with `builder` (as a nullable page) and returns the page as
+    // `decode_page` expects it
+    fn encode_page(
+        builder: ValueEncoderBuilder,
+        values: &ArrayRef,
+    ) -> (pb::ArrayEncoding, Vec<Bytes>) {
+        let encoder = BasicEncoder::new(Box::new(builder.build(values.data_type()).unwrap()));
+        let mut encoded = encoder
+            .encode(std::slice::from_ref(values), &mut 0)
+            .unwrap();
+        encoded.buffers.sort_by_key(|buffer| buffer.index);
+        let buffers = encoded
+            .buffers
+            .iter()
+            .map(|buffer| {
+                Bytes::from(
+                    buffer
+                        .parts
+                        .iter()
+                        .flat_map(|part| part.as_slice().to_vec())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        (encoded.encoding, buffers)
+    }
+
+    fn flat_compression(encoding: &pb::ArrayEncoding) -> Option<(String, u32)> {
+        let mut encoding = encoding.clone();
+        let mut flats = Vec::new();
+        super::flat_encodings_mut(&mut encoding, &mut flats);
+        flats.into_iter().find_map(|flat| {
+            flat.compression
+                .as_ref()
+                .map(|compression| (compression.scheme.clone(), flat.version))
+        })
+    }
+
+    #[test]
+    fn test_recompress() {
+        let values = Arc::new(Int32Array::from_iter(
+            (0..10_000).map(|i| (i % 13 != 0).then_some(i / 7)),
+        )) as ArrayRef;
+        let check_decodes = |encoding: &pb::ArrayEncoding, buffers: &[Bytes]| {
+            for range in [0..10_000, 4000..4100, 9999..10_000] {
+                let decoded =
+                    decode_page(encoding, buffers, &DataType::Int32, range.clone()).unwrap();
+                let expected =
+                    values.slice(range.start as usize, (range.end - range.start) as usize);
+                assert_eq!(decoded.as_ref(), expected.as_ref());
+            }
+        };
+
+        // Zstd to LZ4
+        let (encoding, buffers) = encode_page(
+            ValueEncoderBuilder::default()
+                .compression(CompressionScheme::Zstd)
+                .level(3),
+            &values,
+        );
+        assert_eq!(flat_compression(&encoding), Some(("zstd".to_string(), 1)));
+        let (lz4_encoding, lz4_buffers) =
+            recompress(&encoding, &buffers, CompressionScheme::Lz4, None).unwrap();
+        assert_eq!(
+            flat_compression(&lz4_encoding),
+            Some(("lz4".to_string(), 1))
+        );
+        assert_ne!(lz4_buffers, buffers);
+        check_decodes(&lz4_encoding, &lz4_buffers);
+
+        // Back to zstd, at a different level, and to no compression
+        let (zstd_encoding, zstd_buffers) = recompress(
+            &lz4_encoding,
+            &lz4_buffers,
+            CompressionScheme::Zstd,
+            Some(19),
+        )
+        .unwrap();
+        assert_eq!(
+            flat_compression(&zstd_encoding),
+            Some(("zstd".to_string(), 1))
+        );
+        check_decodes(&zstd_encoding, &zstd_buffers);
+        let (flat_encoding, flat_buffers) =
+            recompress(&encoding, &buffers, CompressionScheme::None, None).unwrap();
+        assert_eq!(flat_compression(&flat_encoding), None);
+        check_decodes(&flat_encoding, &flat_buffers);
+
+        // Pages compressed in blocks keep their blocks
+        let (encoding, buffers) = encode_page(
+            ValueEncoderBuilder::default()
+                .compression(CompressionScheme::Zstd)
+                .compression_block_size(4096),
+            &values,
+        );
+        let (lz4_encoding, lz4_buffers) =
+            recompress(&encoding, &buffers, CompressionScheme::Lz4, None).unwrap();
+        assert_eq!(
+            flat_compression(&lz4_encoding),
+            Some(("lz4".to_string(), super::FLAT_ENCODING_VERSION_BLOCKS))
+        );
+        check_decodes(&lz4_encoding, &lz4_buffers);
+
+        // Bitpacked pages are left as they are
+        let (encoding, buffers) = encode_page(
+            ValueEncoderBuilder::default().enable_bitpacking(true),
+            &values,
+        );
+        let (bitpacked_encoding, bitpacked_buffers) =
+            recompress(&encoding, &buffers, CompressionScheme::Lz4, None).unwrap();
+        assert_eq!(bitpacked_encoding, encoding);
+        assert_eq!(bitpacked_buffers, buffers);
+
+        // The new scheme must be concrete and accept the level
+        assert!(recompress(&encoding, &buffers, CompressionScheme::Lz4, Some(3)).is_err());
+        assert!(recompress(&encoding, &buffers, CompressionScheme::Default, None).is_err());
+    }
 }
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use bytes::BytesMut;
+use snafu::{location, Location};
+
+use lance_core::{Error, Result};
+
+use crate::decoder::PrimitivePageDecoder;
+
+/// A decoder that zero-fills any requested rows beyond the end of the data
+///
+/// This is useful for kernels that process fixed size tiles and want a fixed size
+/// output buffer instead of an error when the data runs out.  The width of each
+/// buffer must be known up front and so this is limited to fixed-width layouts
+/// (e.g. an optional validity bitmap and a buffer of values).
+pub struct ZeroFillDecoder {
+    inner: Box<dyn PrimitivePageDecoder>,
+    num_rows: u64,
+    bits_per_value: Vec<u64>,
+}
+
+impl ZeroFillDecoder {
+    /// Creates a new zero-filling decoder
+    ///
+    /// * `inner` - the decoder for the real data
+    /// * `num_rows` - the number of rows `inner` can decode
+    /// * `bits_per_value` - the width, in bits, of each buffer produced by `inner`.  Buffers
+    ///   that are empty (e.g. a validity buffer when there are no nulls) are left empty.
+    pub fn try_new(
+        inner: Box<dyn PrimitivePageDecoder>,
+        num_rows: u64,
+        bits_per_value: Vec<u64>,
+    ) -> Result<Self> {
+        if bits_per_value.len() != inner.num_buffers() as usize {
+            return Err(Error::invalid_input(
+                format!(
+                    "A zero-fill decoder was given {} buffer widths for a decoder with {} buffers",
+                    bits_per_value.len(),
+                    inner.num_buffers()
+                ),
+                location!(),
+            ));
+        }
+        Ok(Self {
+            inner,
+            num_rows,
+            bits_per_value,
+        })
+    }
+
+    /// Decodes `num_rows` rows, filling any rows beyond the end of the data with zeros
+    ///
+    /// Returns the decoded buffers and the number of rows that were real (not filler)
+    pub fn decode_zero_filled(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        all_null: &mut bool,
+    ) -> Result<(Vec<BytesMut>, u64)> {
+        let num_real_rows = num_rows.min(self.num_rows.saturating_sub(rows_to_skip));
+        let mut buffers = if num_real_rows > 0 {
+            self.inner.decode(rows_to_skip, num_real_rows, all_null)?
+        } else {
+            vec![BytesMut::new(); self.bits_per_value.len()]
+        };
+        if num_real_rows == num_rows {
+            return Ok((buffers, num_real_rows));
+        }
+        for (buffer, bits_per_value) in buffers.iter_mut().zip(&self.bits_per_value) {
+            // Empty buffers are optional buffers that weren't needed, unless there was no
+            // real data in which case the filler needs them all
+            if buffer.is_empty() && num_real_rows > 0 {
+                continue;
+            }
+            let real_bits = num_real_rows * bits_per_value;
+            buffer.truncate(real_bits.div_ceil(8) as usize);
+            // Clear any bits of the last real byte that belong to filler rows
+            if real_bits % 8 != 0 {
+                let last = buffer.len() - 1;
+                buffer[last] &= (1 << (real_bits % 8)) - 1;
+            }
+            buffer.resize((num_rows * bits_per_value).div_ceil(8) as usize, 0);
+        }
+        Ok((buffers, num_real_rows))
+    }
+}
+
+impl PrimitivePageDecoder for ZeroFillDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        self.decode_zero_filled(rows_to_skip, num_rows, all_null)
+            .map(|(buffers, _)| buffers)
+    }
+
+    fn can_skip(&self) -> bool {
+        self.inner.can_skip()
+    }
+
+    fn num_buffers(&self) -> u32 {
+        self.bits_per_value.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::Int32Array;
+    use bytes::Bytes;
+
+    use crate::{
+        decoder::{PageScheduler, PrimitivePageDecoder},
+        encodings::physical::{
+            bitmap::DenseBitmapScheduler,
+            buffers::{BufferCompressor, ZstdBufferCompressor},
+            value::{CompressionScheme, ValuePageScheduler},
+        },
+        BufferScheduler, EncodingsIo,
+    };
+
+    use super::ZeroFillDecoder;
+
+    async fn load(
+        scheduler: &dyn PageScheduler,
+        data: Vec<u8>,
+        num_rows: u64,
+    ) -> Box<dyn PrimitivePageDecoder> {
+        let io = Arc::new(BufferScheduler::new(Bytes::from(data))) as Arc<dyn EncodingsIo>;
+        scheduler
+            .schedule_ranges(std::slice::from_ref(&(0..num_rows)), &io, 0)
+            .await
+            .unwrap()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_zero_fill_values() {
+        let values = (1..=100).collect::<Vec<i32>>();
+        let data = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let scheduler = ValuePageScheduler::new(4, 0, data.len() as u64, CompressionScheme::None);
+        let decoder =
+            ZeroFillDecoder::try_new(load(&scheduler, data.clone(), 100).await, 100, vec![32])
+                .unwrap();
+
+        let (buffers, num_real_rows) = decoder.decode_zero_filled(0, 128, &mut false).unwrap();
+        assert_eq!(num_real_rows, 100);
+        assert_eq!(buffers[0].len(), 128 * 4);
+        let decoded = Int32Array::from(
+            buffers[0]
+                .chunks_exact(4)
+                .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect::<Vec<_>>(),
+        );
+        let expected = values
+            .iter()
+            .copied()
+            .chain(std::iter::repeat(0).take(28))
+            .collect::<Int32Array>();
+        assert_eq!(decoded, expected);
+
+        // Requests that are entirely real or entirely filler
+        let (buffers, num_real_rows) = decoder.decode_zero_filled(10, 20, &mut false).unwrap();
+        assert_eq!(num_real_rows, 20);
+        assert_eq!(&buffers[0][..4], &11_i32.to_le_bytes());
+        let (buffers, num_real_rows) = decoder.decode_zero_filled(120, 8, &mut false).unwrap();
+        assert_eq!(num_real_rows, 0);
+        assert_eq!(buffers[0].as_ref(), &[0; 32]);
+
+        let inner = load(&scheduler, vec![0; 400], 100).await;
+        assert!(ZeroFillDecoder::try_new(inner, 100, vec![1, 32]).is_err());
+
+        // Rows can only be skipped cheaply if the inner decoder can skip them
+        assert!(decoder.can_skip());
+        let mut compressed = Vec::new();
+        ZstdBufferCompressor::default()
+            .compress(&data, &mut compressed)
+            .unwrap();
+        let scheduler =
+            ValuePageScheduler::new(4, 0, compressed.len() as u64, CompressionScheme::Zstd);
+        let decoder =
+            ZeroFillDecoder::try_new(load(&scheduler, compressed, 100).await, 100, vec![32])
+                .unwrap();
+        assert!(!decoder.can_skip());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_zero_fill_bitmap() {
+        // 10 rows, all set
+        let scheduler = DenseBitmapScheduler::new(0);
+        let decoder =
+            ZeroFillDecoder::try_new(load(&scheduler, vec![0xFF, 0xFF], 10).await, 10, vec![1])
+                .unwrap();
+        let (buffers, num_real_rows) = decoder.decode_zero_filled(0, 24, &mut false).unwrap();
+        assert_eq!(num_real_rows, 10);
+        assert_eq!(buffers[0].as_ref(), &[0xFF, 0x03, 0x00]);
+    }
+}
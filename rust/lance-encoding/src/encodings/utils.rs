@@ -4,7 +4,7 @@
 use std::sync::Arc;
 
 use arrow_array::{
-    new_null_array,
+    make_array, new_null_array,
     types::{
         ArrowPrimitiveType, ByteArrayType, Date32Type, Date64Type, Decimal128Type, Decimal256Type,
         DurationMicrosecondType, DurationMillisecondType, DurationNanosecondType,
@@ -15,12 +15,15 @@ use arrow_array::{
         TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type,
         UInt32Type, UInt64Type, UInt8Type,
     },
-    ArrayRef, BooleanArray, FixedSizeBinaryArray, FixedSizeListArray, GenericByteArray,
-    PrimitiveArray,
+    ArrayRef, BooleanArray, FixedSizeBinaryArray, FixedSizeListArray, Float64Array,
+    GenericByteArray, PrimitiveArray,
 };
-use arrow_buffer::{BooleanBuffer, Buffer, NullBuffer, OffsetBuffer, ScalarBuffer};
+use arrow_buffer::{
+    i256, ArrowNativeType, BooleanBuffer, Buffer, NullBuffer, OffsetBuffer, ScalarBuffer,
+};
+use arrow_data::ArrayDataBuilder;
 use arrow_schema::{DataType, IntervalUnit, TimeUnit};
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use snafu::{location, Location};
 
 use lance_core::{Error, Result};
@@ -93,6 +96,37 @@ pub fn new_generic_byte_array<T: ByteArrayType>(buffers: Vec<BytesMut>, num_rows
     ))
 }
 
+/// Creates a primitive array from buffers that were not copied out of the page
+///
+/// This is similar to [`primitive_array_from_buffers`] but the buffers are shared with the
+/// data that was read from storage.  Since that data has no alignment guarantees the values
+/// buffer is copied (once) into an aligned allocation if it is misaligned for `data_type`.
+///
+/// Only types where [`DataType::is_primitive`] is true are supported.
+pub fn primitive_array_from_shared_buffers(
+    data_type: &DataType,
+    buffers: Vec<Bytes>,
+    num_rows: u64,
+) -> Result<ArrayRef> {
+    debug_assert!(data_type.is_primitive());
+    let mut buffer_iter = buffers.into_iter();
+    let null_buffer = buffer_iter.next().unwrap();
+    let null_buffer = (!null_buffer.is_empty()).then(|| {
+        NullBuffer::new(BooleanBuffer::new(
+            Buffer::from_bytes(null_buffer.into()),
+            0,
+            num_rows as usize,
+        ))
+    });
+    let data_buffer = Buffer::from_bytes(buffer_iter.next().unwrap().into());
+    let data = ArrayDataBuilder::new(data_type.clone())
+        .len(num_rows as usize)
+        .nulls(null_buffer)
+        .add_buffer(data_buffer)
+        .build_aligned()?;
+    Ok(make_array(data))
+}
+
 pub fn bytes_to_validity(bytes: BytesMut, num_rows: u64) -> Option<NullBuffer> {
     if bytes.is_empty() {
         None
@@ -273,12 +307,160 @@ pub fn primitive_array_from_buffers(
         DataType::LargeBinary => Ok(new_generic_byte_array::<GenericBinaryType<i64>>(
             buffers, num_rows,
         )),
-        _ => Err(Error::io(
-            format!(
-                "The data type {} cannot be decoded from a primitive encoding",
-                data_type
-            ),
+        _ => Err(Error::unsupported_type(
+            data_type,
+            "the data type cannot be decoded from a primitive encoding",
             location!(),
         )),
     }
 }
+
+fn values_to_f64<T: ArrowNativeType>(
+    values: BytesMut,
+    num_rows: u64,
+    convert: impl Fn(T) -> f64,
+) -> ScalarBuffer<f64> {
+    let values = Buffer::from_bytes(values.freeze().into());
+    let values = ScalarBuffer::<T>::new(values, 0, num_rows as usize);
+    values.iter().map(|value| convert(*value)).collect()
+}
+
+/// Creates a `Float64Array` from the decoded buffers of a numeric fixed-stride type
+///
+/// This expects the same buffers as [`primitive_array_from_buffers`] but converts each
+/// value to `f64` as it is read, instead of materializing an array of the stored type
+/// that then needs to be cast.  Decimal values are divided by 10^scale.
+pub fn float64_array_from_buffers(
+    data_type: &DataType,
+    buffers: Vec<BytesMut>,
+    num_rows: u64,
+) -> Result<Float64Array> {
+    let mut buffer_iter = buffers.into_iter();
+    let nulls = bytes_to_validity(buffer_iter.next().unwrap(), num_rows);
+    let values = buffer_iter.next().unwrap();
+    let values = match data_type {
+        DataType::Int8 => values_to_f64::<i8>(values, num_rows, |v| v as f64),
+        DataType::Int16 => values_to_f64::<i16>(values, num_rows, |v| v as f64),
+        DataType::Int32 => values_to_f64::<i32>(values, num_rows, |v| v as f64),
+        DataType::Int64 => values_to_f64::<i64>(values, num_rows, |v| v as f64),
+        DataType::UInt8 => values_to_f64::<u8>(values, num_rows, |v| v as f64),
+        DataType::UInt16 => values_to_f64::<u16>(values, num_rows, |v| v as f64),
+        DataType::UInt32 => values_to_f64::<u32>(values, num_rows, |v| v as f64),
+        DataType::UInt64 => values_to_f64::<u64>(values, num_rows, |v| v as f64),
+        DataType::Float16 => {
+            values_to_f64::<<Float16Type as ArrowPrimitiveType>::Native>(values, num_rows, |v| {
+                v.to_f64()
+            })
+        }
+        DataType::Float32 => values_to_f64::<f32>(values, num_rows, |v| v as f64),
+        DataType::Float64 => values_to_f64::<f64>(values, num_rows, |v| v),
+        DataType::Decimal128(_, scale) => {
+            let divisor = 10_f64.powi(*scale as i32);
+            values_to_f64::<i128>(values, num_rows, |v| v as f64 / divisor)
+        }
+        DataType::Decimal256(_, scale) => {
+            let divisor = 10_f64.powi(*scale as i32);
+            values_to_f64::<i256>(values, num_rows, |v| {
+                let (low, high) = v.to_parts();
+                (high as f64 * 2_f64.powi(128) + low as f64) / divisor
+            })
+        }
+        _ => {
+            return Err(Error::unsupported_type(
+                data_type,
+                "the data type cannot be decoded to Float64",
+                location!(),
+            ))
+        }
+    };
+    Ok(Float64Array::new(values, nulls))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, ArrayRef, Decimal128Array, Float64Array, Int32Array};
+    use arrow_schema::DataType;
+    use bytes::BytesMut;
+
+    use crate::{
+        encoder::ArrayEncoder,
+        encodings::physical::{
+            basic::BasicEncoder,
+            value::{CompressionScheme, ValueEncoder},
+        },
+        testing::InMemoryPage,
+    };
+
+    use super::{float64_array_from_buffers, primitive_array_from_buffers};
+
+    // Writes the array as a single page and decodes `num_rows` rows, skipping `rows_to_skip`,
+    // into the raw decoded buffers
+    async fn decode_buffers(array: ArrayRef, rows_to_skip: u64, num_rows: u64) -> Vec<BytesMut> {
+        let encoder = BasicEncoder::new(Box::new(
+            ValueEncoder::try_new(array.data_type(), CompressionScheme::None).unwrap(),
+        ));
+        let page = InMemoryPage::new(&encoder.encode(&[array.clone()], &mut 0).unwrap());
+        let decoder = page
+            .schedule(
+                array.data_type(),
+                std::slice::from_ref(&(0..array.len() as u64)),
+            )
+            .await
+            .unwrap();
+        decoder.decode(rows_to_skip, num_rows, &mut false).unwrap()
+    }
+
+    async fn check_decode_to_f64(array: ArrayRef, rows_to_skip: u64, num_rows: u64) {
+        let data_type = array.data_type().clone();
+        let buffers = decode_buffers(array.clone(), rows_to_skip, num_rows).await;
+        let expected = primitive_array_from_buffers(&data_type, buffers.clone(), num_rows).unwrap();
+        let expected = arrow_cast::cast(&expected, &DataType::Float64).unwrap();
+
+        let actual = float64_array_from_buffers(&data_type, buffers, num_rows).unwrap();
+        assert_eq!(
+            &actual,
+            expected.as_any().downcast_ref::<Float64Array>().unwrap()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_int32_to_f64() {
+        let values = Int32Array::from_iter((0..1000).map(|i| {
+            if i % 7 == 0 {
+                None
+            } else {
+                Some(i * 1000 - 500_000)
+            }
+        }));
+        let values = Arc::new(values) as ArrayRef;
+        check_decode_to_f64(values.clone(), 0, 1000).await;
+        check_decode_to_f64(values, 100, 250).await;
+
+        let no_nulls = Arc::new(Int32Array::from(vec![i32::MIN, -1, 0, 1, i32::MAX])) as ArrayRef;
+        check_decode_to_f64(no_nulls, 0, 5).await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decode_decimal128_to_f64() {
+        let values = Decimal128Array::from_iter((0..1000).map(|i| {
+            if i % 5 == 0 {
+                None
+            } else {
+                Some(i as i128 * 12_345 - 6_000_000)
+            }
+        }))
+        .with_precision_and_scale(12, 3)
+        .unwrap();
+        let values = Arc::new(values) as ArrayRef;
+        check_decode_to_f64(values.clone(), 0, 1000).await;
+        check_decode_to_f64(values, 500, 100).await;
+    }
+
+    #[test]
+    fn test_decode_to_f64_rejects_non_numeric() {
+        let buffers = vec![BytesMut::new(), BytesMut::from(&[0_u8; 8][..])];
+        assert!(float64_array_from_buffers(&DataType::Utf8, buffers, 1).is_err());
+    }
+}
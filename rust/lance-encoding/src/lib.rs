@@ -8,10 +8,14 @@ use futures::{future::BoxFuture, FutureExt, TryFutureExt};
 
 use lance_core::Result;
 
+pub mod cardinality;
+pub mod checksum;
 pub mod decoder;
 pub mod encoder;
 pub mod encodings;
 pub mod format;
+pub mod page_cache;
+pub mod stats;
 #[cfg(test)]
 pub mod testing;
 
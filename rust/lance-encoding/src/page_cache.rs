@@ -0,0 +1,457 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A cache of decoded pages
+//!
+//! Workloads that repeatedly take rows from the same few pages would otherwise fetch,
+//! decompress, and unpack those pages on every request.  Files are immutable and so a
+//! decoded page never needs to be invalidated.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{new_null_array, Array, ArrayRef};
+use arrow_schema::DataType;
+use bytes::BytesMut;
+use futures::{future::BoxFuture, FutureExt};
+use lance_arrow::DataTypeExt;
+use log::trace;
+
+use lance_core::Result;
+
+use crate::{
+    decoder::{DecodeCpuClass, PageScheduler, PrimitivePageDecoder},
+    encodings::utils::primitive_array_from_buffers,
+    stats::DecodeStats,
+    EncodingsIo,
+};
+
+/// Identifies a page within a file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageKey {
+    pub file_id: Arc<str>,
+    pub column_index: u32,
+    pub page_index: u32,
+}
+
+/// Counters describing the effectiveness of a [`DecodedPageCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageCacheStats {
+    /// The number of page loads that were satisfied by the cache
+    pub hits: u64,
+    /// The number of page loads that had to fetch and decode the page
+    pub misses: u64,
+    /// The number of pages currently in the cache
+    pub num_pages: usize,
+    /// The (approximate) number of bytes currently used by the cache
+    pub size_bytes: usize,
+}
+
+struct CacheEntry {
+    page: ArrayRef,
+    size_bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<PageKey, CacheEntry>,
+    // Maps the time an entry was last used to the key of the entry
+    usage: BTreeMap<u64, PageKey>,
+    size_bytes: usize,
+    clock: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &PageKey) -> Option<ArrayRef> {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self.entries.get_mut(key)?;
+        self.usage.remove(&entry.last_used);
+        self.usage.insert(clock, key.clone());
+        entry.last_used = clock;
+        Some(entry.page.clone())
+    }
+
+    fn remove(&mut self, key: &PageKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.usage.remove(&entry.last_used);
+            self.size_bytes -= entry.size_bytes;
+        }
+    }
+}
+
+/// A cache of decoded pages, bounded by size and evicting the least recently used pages
+///
+/// Only pages of fixed-width primitive types that need more than a simple copy to decode
+/// (e.g. compressed or bitpacked pages) are cached.  The cache can be shared by several
+/// readers as long as each file is given a distinct file id.
+pub struct DecodedPageCache {
+    capacity_bytes: usize,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl std::fmt::Debug for DecodedPageCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodedPageCache")
+            .field("capacity_bytes", &self.capacity_bytes)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl DecodedPageCache {
+    /// Creates a new cache that will hold at most `capacity_bytes` of decoded data
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            state: Mutex::new(CacheState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn capacity_bytes(&self) -> usize {
+        self.capacity_bytes
+    }
+
+    /// Returns true if pages of this type, encoded in this way, should be cached
+    pub fn is_cacheable(
+        data_type: &DataType,
+        scheduler: &dyn PageScheduler,
+        num_rows: u64,
+    ) -> bool {
+        // Pages that can be sliced with a simple copy gain nothing from the cache and, worse,
+        // a cache miss would need to load the entire page
+        data_type.is_primitive()
+            && num_rows > 0
+            && scheduler
+                .estimate_cost(std::slice::from_ref(&(0..num_rows)))
+                .cpu_class
+                > DecodeCpuClass::Copy
+    }
+
+    /// Returns the decoded page, if it is in the cache
+    pub fn get(&self, key: &PageKey) -> Option<ArrayRef> {
+        let page = self.state.lock().unwrap().touch(key);
+        if page.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        page
+    }
+
+    /// Inserts a decoded page, evicting the least recently used pages to make room
+    ///
+    /// Pages larger than the capacity of the cache are not inserted
+    pub fn insert(&self, key: PageKey, page: ArrayRef) {
+        let size_bytes = page.get_array_memory_size();
+        if size_bytes > self.capacity_bytes {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.remove(&key);
+        while state.size_bytes + size_bytes > self.capacity_bytes {
+            let (_, oldest) = state.usage.pop_first().unwrap();
+            trace!("Evicting page {:?} from the decoded page cache", oldest);
+            if let Some(entry) = state.entries.remove(&oldest) {
+                state.size_bytes -= entry.size_bytes;
+            }
+        }
+        state.clock += 1;
+        let last_used = state.clock;
+        state.usage.insert(last_used, key.clone());
+        state.size_bytes += size_bytes;
+        state.entries.insert(
+            key,
+            CacheEntry {
+                page,
+                size_bytes,
+                last_used,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> PageCacheStats {
+        let state = self.state.lock().unwrap();
+        PageCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            num_pages: state.entries.len(),
+            size_bytes: state.size_bytes,
+        }
+    }
+}
+
+/// A decoded page cache, along with the id of the file being read
+#[derive(Debug, Clone)]
+pub struct FilePageCache {
+    pub cache: Arc<DecodedPageCache>,
+    pub file_id: Arc<str>,
+}
+
+impl FilePageCache {
+    pub fn new(cache: Arc<DecodedPageCache>, file_id: impl Into<Arc<str>>) -> Self {
+        Self {
+            cache,
+            file_id: file_id.into(),
+        }
+    }
+
+    /// Schedules the requested ranges of a page, using the cache if possible
+    ///
+    /// If the page is not cached then the entire page is loaded, decoded, and inserted
+    /// into the cache.  Only pages for which [`DecodedPageCache::is_cacheable`] is true
+    /// should be scheduled this way.  The cache lookup is reported to `decode_stats`, if given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn schedule_ranges(
+        &self,
+        column_index: u32,
+        page_index: u32,
+        page_scheduler: &dyn PageScheduler,
+        num_rows: u64,
+        data_type: &DataType,
+        ranges: &[Range<u64>],
+        io: &Arc<dyn EncodingsIo>,
+        top_level_row: u64,
+        decode_stats: Option<&DecodeStats>,
+    ) -> BoxFuture<'static, Result<Box<dyn PrimitivePageDecoder>>> {
+        let key = PageKey {
+            file_id: self.file_id.clone(),
+            column_index,
+            page_index,
+        };
+        let ranges = ranges.to_vec();
+        let cached = self.cache.get(&key);
+        if let Some(stats) = decode_stats {
+            stats.record_cache_lookup(cached.is_some());
+        }
+        if let Some(page) = cached {
+            trace!("Decoded page cache hit for {:?}", key);
+            return std::future::ready(Ok(
+                Box::new(CachedPageDecoder { page, ranges }) as Box<dyn PrimitivePageDecoder>
+            ))
+            .boxed();
+        }
+        trace!("Decoded page cache miss for {:?}", key);
+        #[allow(clippy::single_range_in_vec_init)]
+        let page_decoder = page_scheduler.schedule_ranges(&[0..num_rows], io, top_level_row);
+        let cache = self.cache.clone();
+        let data_type = data_type.clone();
+        async move {
+            let page_decoder = page_decoder.await?;
+            let mut all_null = false;
+            let buffers = page_decoder.decode(0, num_rows, &mut all_null)?;
+            let page = if all_null {
+                new_null_array(&data_type, num_rows as usize)
+            } else {
+                primitive_array_from_buffers(&data_type, buffers, num_rows)?
+            };
+            cache.insert(key, page.clone());
+            Ok(Box::new(CachedPageDecoder { page, ranges }) as Box<dyn PrimitivePageDecoder>)
+        }
+        .boxed()
+    }
+}
+
+/// Decodes the requested ranges from a fully decoded page
+struct CachedPageDecoder {
+    page: ArrayRef,
+    ranges: Vec<Range<u64>>,
+}
+
+impl PrimitivePageDecoder for CachedPageDecoder {
+    fn decode(
+        &self,
+        rows_to_skip: u64,
+        num_rows: u64,
+        _all_null: &mut bool,
+    ) -> Result<Vec<BytesMut>> {
+        let mut rows_to_skip = rows_to_skip;
+        let mut rows_remaining = num_rows;
+        let mut slices = Vec::new();
+        for range in &self.ranges {
+            if rows_remaining == 0 {
+                break;
+            }
+            let range_len = range.end - range.start;
+            if rows_to_skip >= range_len {
+                rows_to_skip -= range_len;
+                continue;
+            }
+            let rows_to_take = rows_remaining.min(range_len - rows_to_skip);
+            slices.push(
+                self.page
+                    .slice((range.start + rows_to_skip) as usize, rows_to_take as usize),
+            );
+            rows_to_skip = 0;
+            rows_remaining -= rows_to_take;
+        }
+        let slices = slices.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+        let selected = if slices.is_empty() {
+            self.page.slice(0, 0)
+        } else {
+            arrow_select::concat::concat(&slices)?
+        };
+
+        let data = selected.to_data();
+        let validity = data
+            .nulls()
+            .filter(|nulls| nulls.null_count() > 0)
+            .map(|nulls| BytesMut::from(nulls.inner().sliced().as_slice()))
+            .unwrap_or_default();
+        let byte_width = self.page.data_type().byte_width();
+        let values_start = data.offset() * byte_width;
+        let values_end = values_start + data.len() * byte_width;
+        let values = BytesMut::from(&data.buffers()[0].as_slice()[values_start..values_end]);
+        Ok(vec![validity, values])
+    }
+
+    fn can_skip(&self) -> bool {
+        true
+    }
+
+    fn num_buffers(&self) -> u32 {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int32Array, Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::StreamExt;
+    use tokio::sync::mpsc;
+
+    use crate::{
+        decoder::{
+            BatchDecodeStream, DecodeBatchScheduler, DecoderConfig, DecoderMiddlewareChain,
+            FilterExpression,
+        },
+        encoder::{
+            encode_batch, CoreArrayEncodingStrategy, CoreFieldEncodingStrategy, EncodingOverride,
+        },
+        stats::DecodeStats,
+        testing::RecordingIo,
+        EncodingsIo,
+    };
+
+    use super::{DecodedPageCache, FilePageCache, PageKey};
+
+    fn page(num_values: i64) -> ArrayRef {
+        Arc::new(Int64Array::from_iter_values(0..num_values))
+    }
+
+    fn key(page_index: u32) -> PageKey {
+        PageKey {
+            file_id: "file".into(),
+            column_index: 0,
+            page_index,
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let page_size = page(100).get_array_memory_size();
+        let cache = DecodedPageCache::new(page_size * 2);
+
+        cache.insert(key(0), page(100));
+        cache.insert(key(1), page(100));
+        // Page 0 is now more recently used than page 1
+        assert!(cache.get(&key(0)).is_some());
+        cache.insert(key(2), page(100));
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(0)).is_some());
+        assert!(cache.get(&key(2)).is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.num_pages, 2);
+        assert_eq!(stats.size_bytes, page_size * 2);
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.misses, 1);
+
+        // Pages larger than the cache are never inserted
+        cache.insert(key(3), page(1000));
+        assert!(cache.get(&key(3)).is_none());
+        assert_eq!(cache.stats().num_pages, 2);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_repeated_take_uses_cache() {
+        let values = Arc::new(Int32Array::from_iter((0..10000).map(|i| {
+            if i % 11 == 0 {
+                None
+            } else {
+                Some(i % 100)
+            }
+        }))) as ArrayRef;
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "values",
+            DataType::Int32,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![values.clone()]).unwrap();
+        let encoding_strategy = CoreFieldEncodingStrategy::new(Arc::new(
+            CoreArrayEncodingStrategy::with_override(EncodingOverride::ForceBitpack),
+        ));
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap());
+        let encoded = encode_batch(&batch, lance_schema, &encoding_strategy, 1024 * 1024)
+            .await
+            .unwrap();
+
+        let io = Arc::new(RecordingIo::new(encoded.data.clone()));
+        let cache = Arc::new(DecodedPageCache::new(1024 * 1024));
+        let decode_stats = Arc::new(DecodeStats::new());
+
+        let indices = vec![3, 11, 500, 501, 9999];
+        let expected = arrow_select::take::take(
+            &values,
+            &arrow_array::UInt64Array::from(indices.clone()),
+            None,
+        )
+        .unwrap();
+
+        let take = |io: Arc<dyn EncodingsIo>| {
+            let mut scheduler = DecodeBatchScheduler::try_new(
+                encoded.schema.as_ref(),
+                &encoded.page_table,
+                &vec![],
+                &[],
+                encoded.num_rows,
+                &DecoderMiddlewareChain::default(),
+                DecoderConfig::default(),
+                &io,
+            )
+            .unwrap()
+            .with_page_cache(FilePageCache::new(cache.clone(), "file"))
+            .with_decode_stats(decode_stats.clone());
+            let root_decoder = scheduler.new_root_decoder_indices(&indices);
+            let (tx, rx) = mpsc::unbounded_channel();
+            scheduler.schedule_take(&indices, &FilterExpression::no_filter(), tx, io);
+            BatchDecodeStream::new(rx, 1024, indices.len() as u64, root_decoder).into_stream()
+        };
+
+        let batch = take(io.clone()).next().await.unwrap().task.await.unwrap();
+        assert_eq!(batch.column(0).as_ref(), expected.as_ref());
+        let requests_after_first_take = io.num_ranges();
+        assert!(requests_after_first_take > 0);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 1));
+
+        // The second take is served entirely from the cache
+        let batch = take(io.clone()).next().await.unwrap().task.await.unwrap();
+        assert_eq!(batch.column(0).as_ref(), expected.as_ref());
+        assert_eq!(io.num_ranges(), requests_after_first_take);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(decode_stats.snapshot().cache_hit_rate(), Some(0.5));
+    }
+}
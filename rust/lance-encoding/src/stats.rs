@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Statistics describing the work done by a scan
+//!
+//! A single [`DecodeStats`] can be shared (via an `Arc`) by any number of schedulers
+//! and decoders, e.g. every file read by a dataset scan, to get totals for the whole scan.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+
+use lance_core::Result;
+
+use crate::EncodingsIo;
+
+/// Thread-safe counters that decoders report into
+#[derive(Debug, Default)]
+pub struct DecodeStats {
+    pages_scheduled: AtomicU64,
+    bytes_read: AtomicU64,
+    rows_decoded: AtomicU64,
+    bytes_decoded: AtomicU64,
+    decode_nanos: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+/// A point-in-time copy of the counters in a [`DecodeStats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStatsSnapshot {
+    /// The number of pages that were scheduled
+    pub pages_scheduled: u64,
+    /// The number of bytes requested from the I/O service
+    pub bytes_read: u64,
+    /// The number of rows that were decoded
+    pub rows_decoded: u64,
+    /// The number of bytes of decoded (decompressed / unpacked) data produced
+    pub bytes_decoded: u64,
+    /// The time spent decoding, summed across all decode tasks
+    pub decode_nanos: u64,
+    /// The number of pages that were served by the decoded page cache
+    pub cache_hits: u64,
+    /// The number of cacheable pages that had to be loaded and decoded
+    pub cache_misses: u64,
+}
+
+impl DecodeStatsSnapshot {
+    /// The time spent decoding, summed across all decode tasks
+    pub fn decode_time(&self) -> Duration {
+        Duration::from_nanos(self.decode_nanos)
+    }
+
+    /// The fraction of cacheable pages that were served by the cache
+    ///
+    /// Returns None if no cacheable pages were scheduled
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            None
+        } else {
+            Some(self.cache_hits as f64 / lookups as f64)
+        }
+    }
+}
+
+impl DecodeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record_page_scheduled(&self) {
+        self.pages_scheduled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_bytes_read(&self, num_bytes: u64) {
+        self.bytes_read.fetch_add(num_bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_decode(&self, num_rows: u64, num_bytes: u64, elapsed: Duration) {
+        self.rows_decoded.fetch_add(num_rows, Ordering::Relaxed);
+        self.bytes_decoded.fetch_add(num_bytes, Ordering::Relaxed);
+        self.decode_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_cache_lookup(&self, hit: bool) {
+        if hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the current value of each counter
+    ///
+    /// The counters are read independently and so, if decoding is still in progress, the
+    /// snapshot may not reflect a single instant.
+    pub fn snapshot(&self) -> DecodeStatsSnapshot {
+        DecodeStatsSnapshot {
+            pages_scheduled: self.pages_scheduled.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            rows_decoded: self.rows_decoded.load(Ordering::Relaxed),
+            bytes_decoded: self.bytes_decoded.load(Ordering::Relaxed),
+            decode_nanos: self.decode_nanos.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// An I/O service that reports the bytes requested to a [`DecodeStats`]
+pub(crate) struct StatsIo {
+    inner: Arc<dyn EncodingsIo>,
+    stats: Arc<DecodeStats>,
+}
+
+impl StatsIo {
+    pub(crate) fn new(inner: Arc<dyn EncodingsIo>, stats: Arc<DecodeStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+impl EncodingsIo for StatsIo {
+    fn submit_request(
+        &self,
+        ranges: Vec<Range<u64>>,
+        priority: u64,
+    ) -> BoxFuture<'static, Result<Vec<Bytes>>> {
+        self.stats
+            .record_bytes_read(ranges.iter().map(|r| r.end - r.start).sum());
+        self.inner.submit_request(ranges, priority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::StreamExt;
+    use tokio::sync::mpsc;
+
+    use crate::{
+        decoder::{
+            BatchDecodeStream, DecodeBatchScheduler, DecoderConfig, DecoderMiddlewareChain,
+            FilterExpression,
+        },
+        encoder::{encode_batch, CoreFieldEncodingStrategy},
+        BufferScheduler, EncodingsIo,
+    };
+
+    use super::{DecodeStats, DecodeStatsSnapshot};
+
+    #[test_log::test(tokio::test)]
+    async fn test_shared_stats() {
+        let values = Arc::new(Int32Array::from_iter_values(
+            (0..10000).map(|i: i32| i.wrapping_mul(0x9E37_79B9_u32 as i32)),
+        )) as ArrayRef;
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "values",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![values.clone()]).unwrap();
+        let lance_schema =
+            Arc::new(lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap());
+        let encoded = encode_batch(
+            &batch,
+            lance_schema,
+            &CoreFieldEncodingStrategy::default(),
+            1024 * 1024,
+        )
+        .await
+        .unwrap();
+        let pages = encoded.page_table[0].page_infos.clone();
+
+        let io = Arc::new(BufferScheduler::new(encoded.data.clone())) as Arc<dyn EncodingsIo>;
+        let scan = |stats: Arc<DecodeStats>| {
+            let mut scheduler = DecodeBatchScheduler::try_new(
+                encoded.schema.as_ref(),
+                &encoded.page_table,
+                &vec![],
+                &[],
+                encoded.num_rows,
+                &DecoderMiddlewareChain::default(),
+                DecoderConfig::default(),
+                &io,
+            )
+            .unwrap()
+            .with_decode_stats(stats);
+            let root_decoder =
+                scheduler.new_root_decoder_ranges(std::slice::from_ref(&(0..encoded.num_rows)));
+            let (tx, rx) = mpsc::unbounded_channel();
+            scheduler.schedule_range(
+                0..encoded.num_rows,
+                &FilterExpression::no_filter(),
+                tx,
+                io.clone(),
+            );
+            BatchDecodeStream::new(rx, 1000, encoded.num_rows, root_decoder).into_stream()
+        };
+
+        // Each scan decodes in several batches and all scans report into the same stats
+        let shared = Arc::new(DecodeStats::new());
+        let num_scans = 3;
+        for _ in 0..num_scans {
+            let mut batches = scan(shared.clone());
+            while let Some(batch) = batches.next().await {
+                batch.task.await.unwrap();
+            }
+        }
+
+        // Every page is fully read and decoded by each scan
+        let per_scan = pages
+            .iter()
+            .fold(DecodeStatsSnapshot::default(), |acc, page| {
+                DecodeStatsSnapshot {
+                    pages_scheduled: acc.pages_scheduled + 1,
+                    bytes_read: acc.bytes_read
+                        + page
+                            .buffer_offsets_and_sizes
+                            .iter()
+                            .map(|(_, size)| size)
+                            .sum::<u64>(),
+                    rows_decoded: acc.rows_decoded + page.num_rows,
+                    bytes_decoded: acc.bytes_decoded + page.num_rows * 4,
+                    ..acc
+                }
+            });
+        let totals = shared.snapshot();
+        assert_eq!(totals.pages_scheduled, per_scan.pages_scheduled * num_scans);
+        assert_eq!(totals.bytes_read, per_scan.bytes_read * num_scans);
+        assert_eq!(totals.rows_decoded, per_scan.rows_decoded * num_scans);
+        assert_eq!(totals.bytes_decoded, per_scan.bytes_decoded * num_scans);
+        assert!(totals.decode_nanos > 0);
+        assert_eq!(totals.cache_hit_rate(), None);
+    }
+}
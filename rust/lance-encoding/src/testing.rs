@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::{collections::HashMap, ops::Range, sync::Arc};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
 
-use arrow_array::{Array, UInt64Array};
+use arrow_array::{Array, ArrayRef, UInt64Array};
 use arrow_schema::{DataType, Field, Schema};
 use arrow_select::concat::concat;
 use bytes::{Bytes, BytesMut};
@@ -16,15 +20,19 @@ use lance_datagen::{array, gen, RowCount, Seed};
 
 use crate::{
     decoder::{
-        BatchDecodeStream, ColumnInfo, DecodeBatchScheduler, DecoderMessage,
-        DecoderMiddlewareChain, FilterExpression, PageInfo,
+        BatchDecodeStream, ColumnInfo, DecodeBatchScheduler, DecoderConfig, DecoderMessage,
+        DecoderMiddlewareChain, FilterExpression, PageInfo, PageScheduler, PrimitivePageDecoder,
     },
     encoder::{
-        ColumnIndexSequence, CoreFieldEncodingStrategy, EncodedBuffer, EncodedPage, FieldEncoder,
-        FieldEncodingStrategy,
+        ColumnIndexSequence, CoreArrayEncodingStrategy, CoreFieldEncodingStrategy, EncodedArray,
+        EncodedBuffer, EncodedPage, EncodingOverride, FieldEncoder, FieldEncodingStrategy,
+    },
+    encodings::{
+        logical::r#struct::SimpleStructDecoder,
+        physical::{decoder_from_array_encoding, ColumnBuffers, FileBuffers, PageBuffers},
     },
-    encodings::logical::r#struct::SimpleStructDecoder,
-    EncodingsIo,
+    format::pb,
+    BufferScheduler, EncodingsIo,
 };
 
 pub(crate) struct SimulatedScheduler {
@@ -59,6 +67,142 @@ impl EncodingsIo for SimulatedScheduler {
     }
 }
 
+/// An I/O service that records each request before serving it from a buffer
+pub(crate) struct RecordingIo {
+    inner: BufferScheduler,
+    requests: Mutex<Vec<Vec<Range<u64>>>>,
+}
+
+impl RecordingIo {
+    pub fn new(data: Bytes) -> Self {
+        Self {
+            inner: BufferScheduler::new(data),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The ranges of each request, in the order the requests were submitted
+    pub fn requests(&self) -> Vec<Vec<Range<u64>>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The total number of ranges requested
+    pub fn num_ranges(&self) -> usize {
+        self.requests.lock().unwrap().iter().map(Vec::len).sum()
+    }
+}
+
+impl EncodingsIo for RecordingIo {
+    fn submit_request(
+        &self,
+        ranges: Vec<Range<u64>>,
+        priority: u64,
+    ) -> BoxFuture<'static, Result<Vec<Bytes>>> {
+        self.requests.lock().unwrap().push(ranges.clone());
+        self.inner.submit_request(ranges, priority)
+    }
+}
+
+/// Buffers for a page that is decoded on its own, without any column or file buffers
+pub(crate) fn page_buffers<'a>(
+    positions_and_sizes: &'a [(u64, u64)],
+    shared_dictionaries: &'a [(u32, ArrayRef)],
+) -> PageBuffers<'a, 'static, 'a> {
+    PageBuffers {
+        column_buffers: ColumnBuffers {
+            file_buffers: FileBuffers {
+                positions_and_sizes: &[],
+                shared_dictionaries,
+                decoder_config: DecoderConfig::default(),
+            },
+            positions_and_sizes: &[],
+        },
+        positions_and_sizes,
+    }
+}
+
+/// An encoded array held in memory as a page, with its buffers one after the other in
+/// index order
+pub(crate) struct InMemoryPage {
+    pub encoding: pb::ArrayEncoding,
+    pub data: Bytes,
+    /// The position and size of each buffer within `data`
+    pub positions_and_sizes: Vec<(u64, u64)>,
+}
+
+impl InMemoryPage {
+    pub fn new(encoded: &EncodedArray) -> Self {
+        let mut buffers = encoded.buffers.iter().collect::<Vec<_>>();
+        buffers.sort_by_key(|buffer| buffer.index);
+        let mut data = Vec::new();
+        let mut positions_and_sizes = Vec::with_capacity(buffers.len());
+        for buffer in buffers {
+            let start = data.len() as u64;
+            for part in &buffer.parts {
+                data.extend_from_slice(part.as_slice());
+            }
+            positions_and_sizes.push((start, data.len() as u64 - start));
+        }
+        Self {
+            encoding: encoded.encoding.clone(),
+            data: Bytes::from(data),
+            positions_and_sizes,
+        }
+    }
+
+    /// The buffers of the page, in index order, as [`crate::decoder::decode_page`] takes them
+    pub fn buffers(&self) -> Vec<Bytes> {
+        self.positions_and_sizes
+            .iter()
+            .map(|(position, size)| {
+                self.data
+                    .slice(*position as usize..(*position + *size) as usize)
+            })
+            .collect()
+    }
+
+    /// An I/O service serving the data of the page
+    pub fn io(&self) -> Arc<dyn EncodingsIo> {
+        Arc::new(BufferScheduler::new(self.data.clone()))
+    }
+
+    /// Creates a scheduler for the page, the page may use the given shared dictionaries
+    pub fn scheduler(
+        &self,
+        data_type: &DataType,
+        shared_dictionaries: &[(u32, ArrayRef)],
+    ) -> Result<Box<dyn PageScheduler>> {
+        decoder_from_array_encoding(
+            &self.encoding,
+            &page_buffers(&self.positions_and_sizes, shared_dictionaries),
+            data_type,
+        )
+    }
+
+    /// Schedules the given ranges of the page
+    pub async fn schedule(
+        &self,
+        data_type: &DataType,
+        ranges: &[Range<u64>],
+    ) -> Result<Box<dyn PrimitivePageDecoder>> {
+        self.scheduler(data_type, &[])?
+            .schedule_ranges(ranges, &self.io(), 0)
+            .await
+    }
+
+    /// Schedules and decodes the given ranges of the page into the decoded buffers
+    pub async fn decode(
+        &self,
+        data_type: &DataType,
+        ranges: &[Range<u64>],
+    ) -> Result<Vec<BytesMut>> {
+        let num_rows = ranges.iter().map(|range| range.end - range.start).sum();
+        self.schedule(data_type, ranges)
+            .await?
+            .decode(0, num_rows, &mut false)
+    }
+}
+
 async fn test_decode(
     num_rows: u64,
     batch_size: u32,
@@ -76,8 +220,10 @@ async fn test_decode(
         &lance_schema,
         column_infos,
         &Vec::new(),
+        &[],
         num_rows,
         &DecoderMiddlewareChain::default(),
+        DecoderConfig::default(),
         io,
     )
     .unwrap();
@@ -104,12 +250,31 @@ async fn test_decode(
     }
 }
 
+fn encoding_strategy(
+    array_encoding_strategy: CoreArrayEncodingStrategy,
+) -> CoreFieldEncodingStrategy {
+    CoreFieldEncodingStrategy::new(Arc::new(array_encoding_strategy))
+}
+
 /// Given a field this will test the round trip encoding and decoding of random data
 pub async fn check_round_trip_encoding_random(field: Field) {
+    check_round_trip_encoding_random_with_override(field, EncodingOverride::Auto).await
+}
+
+/// Like [`check_round_trip_encoding_random`] but every page is encoded with the
+/// given (forced) encoding
+pub async fn check_round_trip_encoding_random_with_override(
+    field: Field,
+    encoding_override: EncodingOverride,
+) {
     let lance_field = lance_core::datatypes::Field::try_from(&field).unwrap();
     for page_size in [4096, 1024 * 1024] {
-        debug!("Testing random data with a page size of {}", page_size);
-        let encoding_strategy = CoreFieldEncodingStrategy::default();
+        debug!(
+            "Testing random data with a page size of {} and encoding override {:?}",
+            page_size, encoding_override
+        );
+        let encoding_strategy =
+            encoding_strategy(CoreArrayEncodingStrategy::with_override(encoding_override));
         let encoding_config = HashMap::new();
         let encoder_factory = || {
             let mut column_index_seq = ColumnIndexSequence::default();
@@ -141,6 +306,7 @@ pub struct TestCases {
     indices: Vec<Vec<u64>>,
     batch_size: u32,
     skip_validation: bool,
+    array_encoding_strategy: CoreArrayEncodingStrategy,
 }
 
 impl Default for TestCases {
@@ -150,6 +316,7 @@ impl Default for TestCases {
             ranges: Vec::new(),
             indices: Vec::new(),
             skip_validation: false,
+            array_encoding_strategy: CoreArrayEncodingStrategy::default(),
         }
     }
 }
@@ -174,6 +341,19 @@ impl TestCases {
         self.skip_validation = true;
         self
     }
+
+    pub fn with_encoding_override(mut self, encoding_override: EncodingOverride) -> Self {
+        self.array_encoding_strategy = CoreArrayEncodingStrategy::with_override(encoding_override);
+        self
+    }
+
+    pub fn with_array_encoding_strategy(
+        mut self,
+        array_encoding_strategy: CoreArrayEncodingStrategy,
+    ) -> Self {
+        self.array_encoding_strategy = array_encoding_strategy;
+        self
+    }
 }
 
 /// Given specific data and test cases we check round trip encoding and decoding
@@ -187,7 +367,7 @@ pub async fn check_round_trip_encoding_of_data(data: Vec<Arc<dyn Array>>, test_c
     let field = Field::new("", example_data.data_type().clone(), true);
     let lance_field = lance_core::datatypes::Field::try_from(&field).unwrap();
     for page_size in [4096, 1024 * 1024] {
-        let encoding_strategy = CoreFieldEncodingStrategy::default();
+        let encoding_strategy = encoding_strategy(test_cases.array_encoding_strategy.clone());
         let encoding_config = HashMap::new();
         let mut column_index_seq = ColumnIndexSequence::default();
         let encoder = encoding_strategy
@@ -370,7 +550,9 @@ async fn check_round_trip_encoding_inner(
 
     // Test take scheduling
     for indices in &test_cases.indices {
-        if indices.len() == 1 {
+        if indices.is_empty() {
+            debug!("Testing decode of zero indices");
+        } else if indices.len() == 1 {
             debug!("Testing decode of index {}", indices[0]);
         } else {
             debug!(
@@ -194,7 +194,8 @@ impl From<Encoding> for pb::Encoding {
 #[async_recursion]
 async fn load_field_dictionary<'a>(field: &mut Field, reader: &dyn Reader) -> Result<()> {
     if let DataType::Dictionary(_, value_type) = field.data_type() {
-        assert!(field.dictionary.is_some());
+        // Only legacy files have a dictionary in the schema, v2 files store dictionaries as
+        // their values
         if let Some(dict_info) = field.dictionary.as_mut() {
             use DataType::*;
             match value_type.as_ref() {
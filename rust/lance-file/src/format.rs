@@ -34,3 +34,9 @@ pub const MAJOR_VERSION: i16 = 0;
 pub const MINOR_VERSION: i16 = 2;
 pub const MINOR_VERSION_NEXT: u16 = 3;
 pub const MAGIC: &[u8; 4] = b"LANC";
+
+/// The prefix of the schema metadata keys that record the shared dictionaries of a v2 file
+///
+/// The key ends with the id of the dictionary and the value is the index of the global
+/// buffer that holds it.
+pub const SHARED_DICTIONARY_META_KEY_PREFIX: &str = "lance:shared_dictionary:";
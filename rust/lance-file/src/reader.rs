@@ -4,6 +4,7 @@
 //! Lance Data File Reader
 
 // Standard
+use std::collections::BTreeMap;
 use std::ops::{Range, RangeTo};
 use std::sync::Arc;
 
@@ -255,6 +256,51 @@ impl FileReader {
         self.metadata.is_empty()
     }
 
+    /// The number of bytes in the data pages of each field of [`Self::schema()`], by field id
+    ///
+    /// The page table only records where each page starts and how many values it has, and so
+    /// the sizes are inferred from the data types.  Fields without data pages (e.g. structs)
+    /// are not included and dictionary values are not counted.
+    pub fn field_data_sizes(&self) -> BTreeMap<i32, u64> {
+        // Pages are written one after another, so a page takes up the bytes from the end of
+        // the previous page to its own end (e.g. the values of a binary page are written
+        // before its offsets)
+        let mut page_ends = self
+            .page_ends()
+            .into_iter()
+            .map(|(field_id, _, end)| (end, field_id))
+            .collect::<Vec<_>>();
+        page_ends.sort_unstable();
+        let mut sizes = BTreeMap::new();
+        let mut previous_end = 0;
+        for (end, field_id) in page_ends {
+            *sizes.entry(field_id).or_default() += end - previous_end;
+            previous_end = end;
+        }
+        sizes
+    }
+
+    /// The position after the last byte of each data page of [`Self::schema()`], as
+    /// `(field id, batch id, end)`
+    ///
+    /// Like [`Self::field_data_sizes`] the ends are inferred from the data types and fields
+    /// without data pages (e.g. structs) are not included.
+    pub fn page_ends(&self) -> Vec<(i32, i32, u64)> {
+        let mut page_ends = Vec::new();
+        for field in self.schema.fields_pre_order() {
+            for batch_id in 0..self.num_batches() as i32 {
+                if let Some(end) = self
+                    .page_table
+                    .get(field.id, batch_id)
+                    .and_then(|page| page_end(&field.data_type(), page))
+                {
+                    page_ends.push((field.id, batch_id, end as u64));
+                }
+            }
+        }
+        page_ends
+    }
+
     /// Read a batch of data from the file.
     ///
     /// The schema of the returned [RecordBatch] is set by [`FileReader::schema()`].
@@ -381,6 +427,36 @@ impl FileReader {
     }
 }
 
+// The position after the last byte of a page of `data_type`, see [`FileWriter`](crate::writer::FileWriter)
+fn page_end(data_type: &DataType, page: &PageInfo) -> Option<usize> {
+    // The number of bytes of `num_values` values written by the plain encoder
+    fn fixed_stride_bytes(data_type: &DataType, num_values: usize) -> Option<usize> {
+        match data_type {
+            DataType::Boolean => Some(num_values.div_ceil(8)),
+            DataType::FixedSizeList(item, size) => {
+                fixed_stride_bytes(item.data_type(), num_values * *size as usize)
+            }
+            DataType::FixedSizeBinary(width) => Some(num_values * *width as usize),
+            _ => data_type.primitive_width().map(|width| num_values * width),
+        }
+    }
+
+    let size = match data_type {
+        DataType::Null => Some(0),
+        // The offsets of binary pages are i64 and the page points to them
+        DataType::Utf8 | DataType::Binary | DataType::LargeUtf8 | DataType::LargeBinary => {
+            Some((page.length + 1) * 8)
+        }
+        DataType::Dictionary(key_type, _) => fixed_stride_bytes(key_type, page.length),
+        // The length of a list page is the number of offsets
+        DataType::List(_) => Some(page.length * 4),
+        DataType::LargeList(_) => Some(page.length * 8),
+        DataType::Struct(_) => None,
+        _ => fixed_stride_bytes(data_type, page.length),
+    };
+    size.map(|size| page.position + size)
+}
+
 /// Stream desired full batches from the file.
 ///
 /// Parameters:
@@ -1449,4 +1525,85 @@ mod tests {
 
         assert_eq!(actual, batch);
     }
+
+    #[tokio::test]
+    async fn test_field_data_sizes() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, false),
+            ArrowField::new(
+                "st",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("b", DataType::Boolean, false),
+                    ArrowField::new(
+                        "l",
+                        DataType::List(Arc::new(ArrowField::new("item", DataType::Int32, true))),
+                        false,
+                    ),
+                ])),
+                false,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let store = ObjectStore::memory();
+        let path = Path::from("/field_data_sizes");
+        let mut file_writer = FileWriter::<NotSelfDescribing>::try_new(
+            &store,
+            &path,
+            schema.clone(),
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        for _ in 0..2 {
+            let mut list_builder = ListBuilder::new(Int32Builder::new());
+            for i in 0..100 {
+                list_builder.values().append_slice(&[i, i]);
+                list_builder.append(true);
+            }
+            let struct_arr = StructArray::from(vec![
+                (
+                    Arc::new(ArrowField::new("b", DataType::Boolean, false)),
+                    Arc::new(BooleanArray::from(vec![true; 100])) as ArrayRef,
+                ),
+                (
+                    Arc::new(ArrowField::new(
+                        "l",
+                        DataType::List(Arc::new(ArrowField::new("item", DataType::Int32, true))),
+                        false,
+                    )),
+                    Arc::new(list_builder.finish()) as ArrayRef,
+                ),
+            ]);
+            let batch = RecordBatch::try_new(
+                Arc::new(arrow_schema.clone()),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(0..100)),
+                    Arc::new(StringArray::from(vec!["abc"; 100])),
+                    Arc::new(struct_arr),
+                ],
+            )
+            .unwrap();
+            file_writer.write(&[batch]).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path, schema.clone())
+            .await
+            .unwrap();
+        let id = |name: &str| schema.field(name).unwrap().id;
+        let sizes = reader.field_data_sizes();
+        assert_eq!(
+            sizes,
+            BTreeMap::from([
+                (id("i"), 2 * 100 * 4),
+                // 100 values of 3 bytes and 101 i64 offsets
+                (id("s"), 2 * (300 + 101 * 8)),
+                (id("st.b"), 2 * 13),
+                (id("st.l"), 2 * 101 * 4),
+                (id("st.l.item"), 2 * 200 * 4),
+            ])
+        );
+    }
 }
@@ -3,16 +3,22 @@
 
 use std::{collections::BTreeSet, io::Cursor, ops::Range, pin::Pin, sync::Arc};
 
-use arrow_schema::Schema as ArrowSchema;
+use arrow_array::{ArrayRef, StringArray, UInt64Array};
+use arrow_buffer::{Buffer, OffsetBuffer};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
 use bytes::{Bytes, BytesMut};
 use futures::{stream::BoxStream, Stream, StreamExt};
+use lance_arrow::{RecordBatchExt, SchemaExt};
 use lance_encoding::{
     decoder::{
-        BatchDecodeStream, ColumnInfo, DecodeBatchScheduler, DecoderMiddlewareChain,
-        FilterExpression, PageInfo, ReadBatchTask,
+        sample_rows, BatchDecodeStream, ColumnInfo, DecodeBatchScheduler, DecodeCost,
+        DecoderConfig, DecoderMiddlewareChain, FilterExpression, PageInfo, ReadBatchTask,
     },
     encoder::EncodedBatch,
+    encodings::physical::{decoder_from_array_encoding, ColumnBuffers, FileBuffers, PageBuffers},
+    page_cache::{DecodedPageCache, FilePageCache, PageCacheStats},
+    stats::DecodeStats,
     EncodingsIo,
 };
 use log::debug;
@@ -33,11 +39,16 @@ use tokio::sync::mpsc;
 
 use crate::{
     datatypes::{Fields, FieldsWithMeta},
-    format::{pb, pbfile, MAGIC, MAJOR_VERSION, MINOR_VERSION_NEXT},
+    format::{
+        pb, pbfile, MAGIC, MAJOR_VERSION, MINOR_VERSION_NEXT, SHARED_DICTIONARY_META_KEY_PREFIX,
+    },
 };
 
 use super::io::LanceEncodingsIo;
 
+/// Column name for the offset (within the file) of each row in a sampled read
+pub const ROW_OFFSET: &str = "_rowoffset";
+
 // For now, we don't use global buffers for anything other than schema.  If we
 // use these later we should make them lazily loaded and then cached once loaded.
 //
@@ -70,6 +81,9 @@ pub struct CachedFileMetadata {
     pub num_footer_bytes: u64,
     pub major_version: u16,
     pub minor_version: u16,
+    /// The alignment (in bytes) of the start of every page and column buffer, 1 if
+    /// the buffers are not aligned
+    pub page_alignment: u64,
 }
 /// Selecting columns from a lance file requires specifying both the
 /// index of the column and the data type of the column
@@ -95,6 +109,34 @@ pub struct ReaderProjection {
     pub column_indices: Vec<u32>,
 }
 
+/// Options that control how a file is read
+#[derive(Debug, Clone, Default)]
+pub struct FileReaderOptions {
+    /// The maximum number of bytes of decoded pages to keep in memory
+    ///
+    /// Decoded pages are cached so that repeated reads of the same pages (e.g. repeated
+    /// takes) do not need to fetch, decompress, or unpack the page again.  If this is 0
+    /// (the default) then decoded pages are not cached.
+    pub decoded_page_cache_size: usize,
+    /// Counters that the I/O and decode work of reads from the file are reported to
+    ///
+    /// The same counters can be given to many readers to get totals across them.
+    pub decode_stats: Option<Arc<DecodeStats>>,
+    /// Options that control how the pages of the file are decoded
+    pub decoder_config: DecoderConfig,
+}
+
+/// A page exactly as it is stored in the file, see [`FileReader::read_raw_page`]
+#[derive(Debug, Clone)]
+pub struct RawPage {
+    /// The number of rows in the page
+    pub num_rows: u64,
+    /// The encoding that explains the buffers in the page
+    pub encoding: pbenc::ArrayEncoding,
+    /// The buffers of the page, as stored on disk (i.e. still compressed)
+    pub buffers: Vec<Bytes>,
+}
+
 #[derive(Debug)]
 pub struct FileReader {
     scheduler: Arc<LanceEncodingsIo>,
@@ -102,7 +144,12 @@ pub struct FileReader {
     base_projection: ReaderProjection,
     num_rows: u64,
     metadata: Arc<CachedFileMetadata>,
+    // The dictionaries that pages refer to by id, loaded when the file is opened
+    shared_dictionaries: Arc<[(u32, ArrayRef)]>,
     decoder_strategy: DecoderMiddlewareChain,
+    decoder_config: DecoderConfig,
+    page_cache: Option<FilePageCache>,
+    decode_stats: Option<Arc<DecodeStats>>,
 }
 
 #[derive(Debug)]
@@ -210,6 +257,16 @@ impl FileReader {
         let column_metadata_start = footer.column_meta_start;
         // cmo == column_metadata_offsets
         let cmo_table_size = 16 * footer.num_columns as usize;
+        if cmo_table_size > column_metadata_bytes.len() {
+            return Err(Error::corrupt_metadata(
+                format!(
+                    "the column metadata offsets of {} columns do not fit in {} bytes",
+                    footer.num_columns,
+                    column_metadata_bytes.len()
+                ),
+                location!(),
+            ));
+        }
         let cmo_table = column_metadata_bytes.slice(column_metadata_bytes.len() - cmo_table_size..);
 
         (0..footer.num_columns)
@@ -217,8 +274,25 @@ impl FileReader {
                 let offset = (col_idx * 16) as usize;
                 let position = LittleEndian::read_u64(&cmo_table[offset..offset + 8]);
                 let length = LittleEndian::read_u64(&cmo_table[offset + 8..offset + 16]);
-                let normalized_position = (position - column_metadata_start) as usize;
-                let normalized_end = normalized_position + (length as usize);
+                // The positions are file offsets and so they are checked before they are
+                // narrowed to offsets into the (in-memory) column metadata
+                let normalized_position = position
+                    .checked_sub(column_metadata_start)
+                    .filter(|start| {
+                        start
+                            .checked_add(length)
+                            .is_some_and(|end| end <= column_metadata_bytes.len() as u64)
+                    })
+                    .ok_or_else(|| {
+                        Error::corrupt_metadata(
+                            format!(
+                                "the metadata of column {} at {} ({} bytes) is outside of the column metadata",
+                                col_idx, position, length
+                            ),
+                            location!(),
+                        )
+                    })? as usize;
+                let normalized_end = normalized_position + length as usize;
                 Ok(pbfile::ColumnMetadata::decode(
                     &column_metadata_bytes[normalized_position..normalized_end],
                 )?)
@@ -282,16 +356,18 @@ impl FileReader {
         Self::do_decode_gbo_table(&gbo_bytes, footer)
     }
 
-    fn decode_schema(schema_bytes: Bytes) -> Result<(u64, lance_core::datatypes::Schema)> {
+    // Returns the number of rows, the page alignment, and the schema of the file
+    fn decode_schema(schema_bytes: Bytes) -> Result<(u64, u64, lance_core::datatypes::Schema)> {
         let file_descriptor = pb::FileDescriptor::decode(schema_bytes)?;
         let pb_schema = file_descriptor.schema.unwrap();
         let num_rows = file_descriptor.length;
+        let page_alignment = file_descriptor.page_alignment.max(1);
         let fields_with_meta = FieldsWithMeta {
             fields: Fields(pb_schema.fields),
             metadata: pb_schema.metadata,
         };
         let schema = lance_core::datatypes::Schema::from(fields_with_meta);
-        Ok((num_rows, schema))
+        Ok((num_rows, page_alignment, schema))
     }
 
     // TODO: Support late projection.  Currently, if we want to perform a
@@ -330,7 +406,7 @@ impl FileReader {
             Self::optimistic_tail_read(&tail_bytes, schema_start, scheduler, file_len).await?;
 
         let schema_bytes = all_metadata_bytes.slice(0..schema_size as usize);
-        let (num_rows, schema) = Self::decode_schema(schema_bytes)?;
+        let (num_rows, page_alignment, schema) = Self::decode_schema(schema_bytes)?;
 
         // Next, read the metadata for the columns
         // This is both the column metadata and the CMO table
@@ -360,6 +436,7 @@ impl FileReader {
             file_buffers: gbo_table,
             major_version: footer.major_version,
             minor_version: footer.minor_version,
+            page_alignment,
         })
     }
 
@@ -495,22 +572,154 @@ impl FileReader {
         scheduler: FileScheduler,
         base_projection: Option<ReaderProjection>,
         decoder_strategy: DecoderMiddlewareChain,
+    ) -> Result<Self> {
+        Self::try_open_with_options(
+            scheduler,
+            base_projection,
+            decoder_strategy,
+            &FileReaderOptions::default(),
+        )
+        .await
+    }
+
+    /// Opens a file, see [`Self::try_open`], with the given options
+    pub async fn try_open_with_options(
+        scheduler: FileScheduler,
+        base_projection: Option<ReaderProjection>,
+        decoder_strategy: DecoderMiddlewareChain,
+        options: &FileReaderOptions,
     ) -> Result<Self> {
         let file_metadata = Arc::new(Self::read_all_metadata(&scheduler).await?);
         if let Some(base_projection) = base_projection.as_ref() {
             Self::validate_projection(base_projection, &file_metadata)?;
         }
+        let shared_dictionaries =
+            Arc::from(Self::read_shared_dictionaries(&scheduler, &file_metadata).await?);
         let num_rows = file_metadata.num_rows;
+        let page_cache = if options.decoded_page_cache_size > 0 {
+            Some(FilePageCache::new(
+                Arc::new(DecodedPageCache::new(options.decoded_page_cache_size)),
+                scheduler.reader().path().to_string(),
+            ))
+        } else {
+            None
+        };
         Ok(Self {
             scheduler: Arc::new(LanceEncodingsIo(scheduler)),
             base_projection: base_projection
                 .unwrap_or(Self::default_projection(file_metadata.file_schema.as_ref())),
             num_rows,
             metadata: file_metadata,
+            shared_dictionaries,
             decoder_strategy,
+            decoder_config: options.decoder_config,
+            page_cache,
+            decode_stats: options.decode_stats.clone(),
+        })
+    }
+
+    // Loads the dictionaries that were added with `FileWriter::add_shared_dictionary`
+    async fn read_shared_dictionaries(
+        scheduler: &FileScheduler,
+        metadata: &CachedFileMetadata,
+    ) -> Result<Vec<(u32, ArrayRef)>> {
+        let mut dictionaries = Vec::new();
+        for (key, value) in metadata.file_schema.metadata.iter() {
+            let Some(id) = key.strip_prefix(SHARED_DICTIONARY_META_KEY_PREFIX) else {
+                continue;
+            };
+            let corrupt = || {
+                Error::corrupt_file(
+                    scheduler.reader().path().clone(),
+                    format!("the shared dictionary {:?} is invalid", key),
+                    location!(),
+                )
+            };
+            let id = id.parse::<u32>().map_err(|_| corrupt())?;
+            let buffer = value
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| metadata.file_buffers.get(index))
+                .ok_or_else(corrupt)?;
+            let data = scheduler
+                .submit_single(buffer.position..buffer.position + buffer.size, 0)
+                .await?;
+            let dictionary = Self::decode_shared_dictionary(&data).ok_or_else(corrupt)?;
+            dictionaries.push((id, Arc::new(dictionary) as ArrayRef));
+        }
+        Ok(dictionaries)
+    }
+
+    // The number of values, the offsets of the values and then the bytes of the values
+    fn decode_shared_dictionary(data: &[u8]) -> Option<StringArray> {
+        let num_values = LittleEndian::read_u32(data.get(..4)?) as usize;
+        let values_start = 4 * (num_values + 2);
+        let offsets = data
+            .get(4..values_start)?
+            .chunks_exact(4)
+            .map(|offset| i32::try_from(LittleEndian::read_u32(offset)).ok())
+            .collect::<Option<Vec<_>>>()?;
+        if offsets[0] != 0 || offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+            return None;
+        }
+        let values = Buffer::from(data.get(values_start..)?);
+        StringArray::try_new(OffsetBuffer::new(offsets.into()), values, None).ok()
+    }
+
+    /// Reads the buffers of a page without decoding (or decompressing) them
+    ///
+    /// This is a passthrough read for jobs that copy pages between files.  The buffers and
+    /// the encoding can be written to another file unchanged.  Column-level buffers (which
+    /// are shared by all pages of a column) are not included.
+    pub async fn read_raw_page(&self, column_index: u32, page_index: u32) -> Result<RawPage> {
+        let column_info = self
+            .metadata
+            .column_infos
+            .get(column_index as usize)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "request for column {} but there were only {} columns in the file",
+                        column_index,
+                        self.metadata.column_infos.len()
+                    ),
+                    location!(),
+                )
+            })?;
+        let page_info = column_info
+            .page_infos
+            .get(page_index as usize)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "request for page {} but there were only {} pages in column {}",
+                        page_index,
+                        column_info.page_infos.len(),
+                        column_index
+                    ),
+                    location!(),
+                )
+            })?;
+        let ranges = page_info
+            .buffer_offsets_and_sizes
+            .iter()
+            .map(|(offset, size)| *offset..*offset + *size)
+            .collect();
+        let buffers = self.scheduler.submit_request(ranges, 0).await?;
+        Ok(RawPage {
+            num_rows: page_info.num_rows,
+            encoding: page_info.encoding.clone(),
+            buffers,
         })
     }
 
+    /// The hit / miss counters of the decoded page cache, if it is enabled
+    pub fn page_cache_stats(&self) -> Option<PageCacheStats> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.cache.stats())
+    }
+
     fn collect_columns(
         &self,
         field: &Field,
@@ -520,12 +729,40 @@ impl FileReader {
         column_infos.push(self.metadata.column_infos[*column_idx].clone());
         *column_idx += 1;
 
+        // A struct may be projected with only some of its children.  In that case we
+        // skip over the columns of the children that were not projected.
+        if let Some(file_children) = self.projected_struct_children(field) {
+            for file_child in file_children {
+                if let Some(child) = field.children.iter().find(|c| c.id == file_child.id) {
+                    self.collect_columns(child, column_idx, column_infos)?;
+                } else {
+                    *column_idx += Self::default_column_count(file_child) as usize;
+                }
+            }
+            return Ok(());
+        }
+
         for child in &field.children {
             self.collect_columns(child, column_idx, column_infos)?;
         }
         Ok(())
     }
 
+    // If `field` is a struct that is missing some of the children it has in the file
+    // then this returns the children of the struct in the file.
+    fn projected_struct_children<'a>(&'a self, field: &Field) -> Option<&'a [Field]> {
+        if !matches!(field.data_type(), DataType::Struct(_)) {
+            return None;
+        }
+        let file_field = self.metadata.file_schema.field_by_id(field.id)?;
+        let is_subset = field.children.len() < file_field.children.len()
+            && field
+                .children
+                .iter()
+                .all(|c| file_field.children.iter().any(|fc| fc.id == c.id));
+        is_subset.then_some(file_field.children.as_slice())
+    }
+
     // The actual decoder needs all the column infos that make up a type.  In other words, if
     // the first type in the schema is Struct<i32, i32> then the decoder will need 3 column infos.
     //
@@ -560,8 +797,12 @@ impl FileReader {
     fn do_read_range(
         column_infos: Vec<Arc<ColumnInfo>>,
         scheduler: Arc<dyn EncodingsIo>,
+        shared_dictionaries: &[(u32, ArrayRef)],
         num_rows: u64,
         decoder_strategy: DecoderMiddlewareChain,
+        decoder_config: DecoderConfig,
+        page_cache: Option<FilePageCache>,
+        decode_stats: Option<Arc<DecodeStats>>,
         range: Range<u64>,
         batch_size: u32,
         projection: &ReaderProjection,
@@ -578,10 +819,18 @@ impl FileReader {
             &projection.schema,
             &column_infos,
             &vec![],
+            shared_dictionaries,
             num_rows,
             &decoder_strategy,
+            decoder_config,
             &scheduler,
         )?;
+        if let Some(page_cache) = page_cache {
+            decode_scheduler = decode_scheduler.with_page_cache(page_cache);
+        }
+        if let Some(decode_stats) = decode_stats {
+            decode_scheduler = decode_scheduler.with_decode_stats(decode_stats);
+        }
 
         let root_decoder = decode_scheduler.new_root_decoder_ranges(&[range.clone()]);
 
@@ -614,8 +863,12 @@ impl FileReader {
         Self::do_read_range(
             column_infos,
             scheduler,
+            &self.shared_dictionaries,
             num_rows,
             decoder_strategy,
+            self.decoder_config,
+            self.page_cache.clone(),
+            self.decode_stats.clone(),
             range,
             batch_size,
             &projection,
@@ -623,20 +876,25 @@ impl FileReader {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn do_take_rows(
         column_infos: Vec<Arc<ColumnInfo>>,
         scheduler: Arc<dyn EncodingsIo>,
+        shared_dictionaries: &[(u32, ArrayRef)],
         num_rows: u64,
         decoder_strategy: DecoderMiddlewareChain,
+        decoder_config: DecoderConfig,
+        page_cache: Option<FilePageCache>,
+        decode_stats: Option<Arc<DecodeStats>>,
         indices: Vec<u64>,
         batch_size: u32,
         projection: &ReaderProjection,
     ) -> Result<BoxStream<'static, ReadBatchTask>> {
         debug!(
-            "Taking {} rows spread across range {}..{} with batch_size {} from columns {:?}",
+            "Taking {} rows spread across range {:?}..{:?} with batch_size {} from columns {:?}",
             indices.len(),
-            indices[0],
-            indices[indices.len() - 1],
+            indices.first(),
+            indices.last(),
             batch_size,
             column_infos.iter().map(|ci| ci.index).collect::<Vec<_>>()
         );
@@ -645,10 +903,18 @@ impl FileReader {
             &projection.schema,
             &column_infos,
             &vec![],
+            shared_dictionaries,
             num_rows,
             &decoder_strategy,
+            decoder_config,
             &scheduler,
         )?;
+        if let Some(page_cache) = page_cache {
+            decode_scheduler = decode_scheduler.with_page_cache(page_cache);
+        }
+        if let Some(decode_stats) = decode_stats {
+            decode_scheduler = decode_scheduler.with_decode_stats(decode_stats);
+        }
 
         let root_decoder = decode_scheduler.new_root_decoder_indices(&indices);
 
@@ -679,8 +945,12 @@ impl FileReader {
         Self::do_take_rows(
             column_infos,
             scheduler,
+            &self.shared_dictionaries,
             num_rows,
             decoder_strategy,
+            self.decoder_config,
+            self.page_cache.clone(),
+            self.decode_stats.clone(),
             indices,
             batch_size,
             &projection,
@@ -826,9 +1096,122 @@ impl FileReader {
         )
     }
 
+    /// Reads a deterministic random sample of the rows in the file
+    ///
+    /// Roughly `fraction` of the rows are read.  Only the pages containing sampled rows
+    /// are loaded (and, for flat pages, only the bytes of the sampled rows) which makes
+    /// this much cheaper than a full scan for small fractions.
+    ///
+    /// Each batch has an extra [`ROW_OFFSET`] column containing the offset of each sampled
+    /// row in the file.  The same `seed` will always sample the same rows.
+    pub fn read_sampled_stream(
+        &self,
+        fraction: f64,
+        seed: u64,
+        batch_size: u32,
+        batch_readahead: u32,
+        projection: &ReaderProjection,
+    ) -> Result<Pin<Box<dyn RecordBatchStream>>> {
+        Self::validate_projection(projection, &self.metadata)?;
+        let indices: Arc<[u64]> = sample_rows(self.num_rows, fraction, seed)?.into();
+        let offset_field = ArrowField::new(ROW_OFFSET, DataType::UInt64, false);
+        let tasks_stream = self.take_rows(indices.to_vec(), batch_size, projection)?;
+        let batch_stream = tasks_stream
+            .scan(0_usize, move |offset, task| {
+                let task_indices = indices.clone();
+                let start = *offset;
+                *offset += task.num_rows as usize;
+                let end = *offset;
+                let offset_field = offset_field.clone();
+                std::future::ready(Some(async move {
+                    let batch = task.task.await?;
+                    let offsets = UInt64Array::from(task_indices[start..end].to_vec());
+                    Ok(batch.try_with_column(offset_field, Arc::new(offsets))?)
+                }))
+            })
+            .buffered(batch_readahead as usize)
+            .boxed();
+        let arrow_schema = ArrowSchema::from(projection.schema.as_ref())
+            .try_with_column(ArrowField::new(ROW_OFFSET, DataType::UInt64, false))?;
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            Arc::new(arrow_schema),
+            batch_stream,
+        )))
+    }
+
     pub fn schema(&self) -> &Arc<Schema> {
         &self.metadata.file_schema
     }
+
+    /// Estimates the cost of decoding rows from a column without performing any I/O
+    ///
+    /// * `column_index` - the index of the (physical) column in the file
+    /// * `ranges` - the rows to decode, relative to the start of the file.  These must be
+    ///   ordered and must not overlap
+    ///
+    /// The estimate is the combined cost of decoding the overlapping part of every page
+    /// in the column.
+    pub fn estimate_decode_cost(
+        &self,
+        column_index: u32,
+        ranges: &[Range<u64>],
+    ) -> Result<DecodeCost> {
+        let column = self
+            .metadata
+            .column_infos
+            .get(column_index as usize)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "cannot estimate the cost of column {} since the file only has {} columns",
+                        column_index,
+                        self.metadata.column_infos.len()
+                    ),
+                    location!(),
+                )
+            })?;
+        let column_buffers = ColumnBuffers {
+            file_buffers: FileBuffers {
+                positions_and_sizes: &[],
+                shared_dictionaries: &self.shared_dictionaries,
+                decoder_config: self.decoder_config,
+            },
+            positions_and_sizes: &column.buffer_offsets_and_sizes,
+        };
+        let mut cost = DecodeCost::default();
+        let mut page_start = 0;
+        for page in column.page_infos.iter() {
+            let page_end = page_start + page.num_rows;
+            let ranges_in_page = ranges
+                .iter()
+                .filter(|range| range.start < page_end && range.end > page_start)
+                .map(|range| {
+                    (range.start.max(page_start) - page_start)
+                        ..(range.end.min(page_end) - page_start)
+                })
+                .collect::<Vec<_>>();
+            page_start = page_end;
+            // Struct pages are placeholders that are never decoded
+            if ranges_in_page.is_empty()
+                || matches!(
+                    page.encoding.array_encoding,
+                    Some(pbenc::array_encoding::ArrayEncoding::Struct(_))
+                )
+            {
+                continue;
+            }
+            let page_buffers = PageBuffers {
+                column_buffers,
+                positions_and_sizes: &page.buffer_offsets_and_sizes,
+            };
+            // The data type only determines how values are materialized and has no
+            // impact on what needs to be fetched
+            let scheduler =
+                decoder_from_array_encoding(&page.encoding, &page_buffers, &DataType::Null)?;
+            cost = cost.combine(scheduler.estimate_cost(&ranges_in_page));
+        }
+        Ok(cost)
+    }
 }
 
 /// Inspects a page and returns a String describing the page's encoding
@@ -927,7 +1310,7 @@ impl EncodedBatchReaderExt for EncodedBatch {
         let schema_size = gbo_table[0].size as usize;
 
         let schema_bytes = bytes.slice(schema_start..(schema_start + schema_size));
-        let (_, schema) = FileReader::decode_schema(schema_bytes)?;
+        let (_, _, schema) = FileReader::decode_schema(schema_bytes)?;
 
         // Next, read the metadata for the columns
         // This is both the column metadata and the CMO table
@@ -953,29 +1336,52 @@ impl EncodedBatchReaderExt for EncodedBatch {
 
 #[cfg(test)]
 pub mod tests {
-    use std::{pin::Pin, sync::Arc};
+    use std::{collections::HashMap, pin::Pin, sync::Arc};
 
     use arrow_array::{
-        types::{Float64Type, Int32Type},
-        RecordBatch,
+        cast::AsArray,
+        types::{
+            Float64Type, Int32Type, Int64Type, TimestampMicrosecondType, TimestampMillisecondType,
+            UInt16Type, UInt64Type,
+        },
+        Array, ArrayRef, Float64Array, Int64Array, ListArray, RecordBatch, RecordBatchIterator,
+        StringArray, UInt32Array,
     };
     use arrow_schema::{DataType, Field, Fields, Schema as ArrowSchema};
+    use arrow_select::concat::concat_batches;
     use bytes::Bytes;
     use futures::{prelude::stream::TryStreamExt, StreamExt};
     use lance_arrow::RecordBatchExt;
     use lance_core::datatypes::Schema;
-    use lance_datagen::{array, gen, BatchCount, ByteCount, RowCount};
+    use lance_datagen::{array, gen, ArrayGeneratorExt, BatchCount, ByteCount, RowCount};
     use lance_encoding::{
-        decoder::{decode_batch, DecoderMiddlewareChain, FilterExpression},
-        encoder::{encode_batch, CoreFieldEncodingStrategy, EncodedBatch},
+        decoder::{
+            decode_batch, DecodeCost, DecodeCpuClass, DecoderMiddlewareChain, FilterExpression,
+        },
+        encoder::{
+            encode_batch, ArrayEncoder, ArrayEncodingStrategy, CoreArrayEncodingStrategy,
+            CoreFieldEncodingStrategy, EncodedBatch, FieldEncodingStrategy, OffsetsEncoding,
+        },
+        encodings::physical::{
+            basic::BasicEncoder,
+            bloom::{canonical_value, page_bloom_filter, BloomFilter},
+            dictionary::SharedDictionaryEncoder,
+            value::{CompressionScheme, ValueEncoder},
+        },
     };
     use lance_io::stream::RecordBatchStream;
     use log::debug;
 
-    use crate::v2::{
-        reader::{EncodedBatchReaderExt, FileReader, ReaderProjection},
-        testing::{write_lance_file, FsFixture},
-        writer::{EncodedBatchWriteExt, FileWriter, FileWriterOptions},
+    use crate::{
+        format::pbfile,
+        v2::{
+            reader::{
+                EncodedBatchReaderExt, FileReader, FileReaderOptions, Footer, ReaderProjection,
+                ROW_OFFSET,
+            },
+            testing::{read_lance_file, write_lance_file, FsFixture},
+            writer::{EncodedBatchWriteExt, FileWriter, FileWriterOptions},
+        },
     };
 
     async fn create_some_file(fs: &FsFixture) -> (Arc<Schema>, Vec<RecordBatch>) {
@@ -1077,6 +1483,80 @@ pub mod tests {
         }
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_field_metadata_round_trip() {
+        let ext = |name: &str, metadata: &str| {
+            HashMap::from([
+                ("ARROW:extension:name".to_string(), name.to_string()),
+                ("ARROW:extension:metadata".to_string(), metadata.to_string()),
+            ])
+        };
+        let point_type = DataType::Struct(Fields::from(vec![
+            Field::new("x", DataType::Float64, false)
+                .with_metadata(HashMap::from([("unit".to_string(), "m".to_string())])),
+            Field::new("y", DataType::Float64, false),
+        ]));
+        let tags_type = DataType::List(Arc::new(
+            Field::new("item", DataType::Utf8, true).with_metadata(ext("my.tag", "")),
+        ));
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::FixedSizeBinary(16), false)
+                .with_metadata(ext("arrow.uuid", "")),
+            Field::new("location", point_type, true)
+                .with_metadata(ext("geoarrow.point", r#"{"crs":"EPSG:4326"}"#)),
+            Field::new("tags", tags_type, true),
+        ]));
+
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("id", array::rand_type(&DataType::FixedSizeBinary(16)))
+            .col("location", array::rand_type(schema.field(1).data_type()))
+            .col("tags", array::rand_type(schema.field(2).data_type()))
+            .into_reader_rows(RowCount::from(100), BatchCount::from(2));
+        let data = reader
+            .map(|batch| {
+                // The generator doesn't carry over the metadata of the list items
+                let batch = batch.unwrap();
+                let tags = batch.column(2).as_list::<i32>();
+                let DataType::List(item_field) = schema.field(2).data_type() else {
+                    unreachable!()
+                };
+                let tags = ListArray::new(
+                    item_field.clone(),
+                    tags.offsets().clone(),
+                    tags.values().clone(),
+                    tags.nulls().cloned(),
+                );
+                let columns = vec![
+                    batch.column(0).clone(),
+                    batch.column(1).clone(),
+                    Arc::new(tags),
+                ];
+                RecordBatch::try_new(schema.clone(), columns).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let reader = RecordBatchIterator::new(data.into_iter().map(Ok), schema.clone());
+        write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        let read_schema = ArrowSchema::from(file_reader.schema().as_ref());
+        assert_eq!(read_schema.fields(), schema.fields());
+
+        let batches = read_lance_file(
+            &fs,
+            DecoderMiddlewareChain::default(),
+            FilterExpression::no_filter(),
+        )
+        .await;
+        for batch in batches {
+            assert_eq!(batch.schema().fields(), schema.fields());
+        }
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_encoded_batch_round_trip() {
         let data = gen()
@@ -1126,6 +1606,97 @@ pub mod tests {
         assert_eq!(data, decoded);
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_empty_batch_round_trip() {
+        let data = gen()
+            .col("x", array::rand::<Int32Type>())
+            .col("y", array::rand_utf8(ByteCount::from(16), false))
+            .into_batch_rows(RowCount::from(0))
+            .unwrap();
+
+        let lance_schema = Arc::new(Schema::try_from(data.schema().as_ref()).unwrap());
+
+        let encoded_batch = encode_batch(
+            &data,
+            lance_schema.clone(),
+            &CoreFieldEncodingStrategy::default(),
+            4096,
+        )
+        .await
+        .unwrap();
+
+        let bytes = encoded_batch.try_to_self_described_lance().unwrap();
+        let decoded_batch = EncodedBatch::try_from_self_described_lance(bytes).unwrap();
+        let decoded = decode_batch(
+            &decoded_batch,
+            &FilterExpression::no_filter(),
+            &DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(data, decoded);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_empty_file_and_batches() {
+        // An empty file
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("score", array::rand::<Float64Type>())
+            .into_reader_rows(RowCount::from(0), BatchCount::from(0));
+        write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+        let batches = read_lance_file(
+            &fs,
+            DecoderMiddlewareChain::default(),
+            FilterExpression::no_filter(),
+        )
+        .await;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+
+        // An empty batch in between non-empty batches
+        let fs = FsFixture::default();
+        let batch = gen()
+            .col("score", array::rand::<Float64Type>())
+            .col("categories", array::rand_type(&DataType::Utf8))
+            .into_batch_rows(RowCount::from(100))
+            .unwrap();
+        let data = vec![batch.clone(), batch.slice(0, 0), batch.clone()];
+        let reader = RecordBatchIterator::new(data.into_iter().map(Ok), batch.schema());
+        let (_, data) = write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        assert_eq!(file_reader.metadata().num_rows, 200);
+
+        let batch_stream = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap();
+        verify_expected(&data, batch_stream, 1024, None).await;
+
+        // A take of zero rows
+        let batches = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::Indices(UInt32Array::from(Vec::<u32>::new())),
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_projection() {
         let fs = FsFixture::default();
@@ -1315,6 +1886,525 @@ pub mod tests {
         .await;
     }
 
+    fn bitpacking_strategy() -> Arc<dyn FieldEncodingStrategy> {
+        Arc::new(CoreFieldEncodingStrategy::new(Arc::new(
+            CoreArrayEncodingStrategy::default().with_bitpacking(),
+        )))
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_bitpacked_pages() {
+        let fs = FsFixture::default();
+
+        let reader = gen()
+            .col("small", array::step::<Int32Type>())
+            .col("signed", array::cycle::<Int64Type>(vec![-3, 0, 70, -128]))
+            .col("nulls", array::step::<UInt16Type>().with_random_nulls(0.5))
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(10));
+        let options = FileWriterOptions {
+            encoding_strategy: Some(bitpacking_strategy()),
+            ..Default::default()
+        };
+        let (_, data) = write_lance_file(reader, &fs, options).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+
+        let batch_stream = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap();
+        verify_expected(&data, batch_stream, 1024, None).await;
+
+        let batch_stream = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::Range(1500..7777),
+                100,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap();
+        let expected = concat_batches(&data[0].schema(), &data)
+            .unwrap()
+            .slice(1500, 6277);
+        verify_expected(&[expected], batch_stream, 100, None).await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_writer_encoding_statistics() {
+        let fs = FsFixture::default();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("ints", DataType::UInt32, false),
+            Field::new("text", DataType::Utf8, false),
+        ]));
+        // Small values can be bitpacked, values that need all 32 bits are written flat
+        let batch = |ints: Vec<u32>| {
+            let text = (0..ints.len())
+                .map(|i| Some(["a", "b", "c"][i % 3]))
+                .collect::<StringArray>();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(UInt32Array::from(ints)), Arc::new(text)],
+            )
+            .unwrap()
+        };
+        let batches = vec![
+            batch((0..1000).collect()),
+            batch((0..1000).map(|i| u32::MAX - i).collect()),
+            batch((0..1000).map(|i| i % 16).collect()),
+        ];
+
+        let writer = fs.object_store.create(&fs.tmp_path).await.unwrap();
+        let lance_schema = Schema::try_from(schema.as_ref()).unwrap();
+        let options = FileWriterOptions {
+            // Small enough that every batch is written as its own page
+            data_cache_bytes: Some(2 * 1024),
+            encoding_strategy: Some(bitpacking_strategy()),
+            ..Default::default()
+        };
+        let mut file_writer = FileWriter::try_new(writer, lance_schema, options).unwrap();
+        for batch in &batches {
+            file_writer.write_batch(batch).await.unwrap();
+        }
+        let statistics = file_writer.finish().await.unwrap();
+
+        assert_eq!(statistics.num_rows, 3000);
+        assert_eq!(statistics.columns.len(), 2);
+        let ints = &statistics.columns[0];
+        assert_eq!(
+            ints.num_pages,
+            [("bitpacked", 2), ("flat", 1)].into_iter().collect()
+        );
+        assert_eq!(ints.num_bytes["flat"], 4000);
+        assert!(ints.num_bytes["bitpacked"] < 4000);
+        let text = &statistics.columns[1];
+        assert_eq!(text.num_pages, [("dictionary", 3)].into_iter().collect());
+
+        // The byte counts match the page sizes recorded in the file
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        for (column_statistics, column_metadata) in statistics
+            .columns
+            .iter()
+            .zip(&file_reader.metadata().column_metadatas)
+        {
+            let page_bytes = column_metadata
+                .pages
+                .iter()
+                .flat_map(|page| &page.buffer_sizes)
+                .sum::<u64>();
+            assert_eq!(
+                column_statistics.num_bytes.values().sum::<u64>(),
+                page_bytes
+            );
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_write_stream() {
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("score", array::rand::<Float64Type>())
+            .col("text", array::rand_utf8(ByteCount::from(16), false))
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(20));
+        let data = reader.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let schema = data[0].schema();
+
+        let writer = fs.object_store.create(&fs.tmp_path).await.unwrap();
+        let lance_schema = Schema::try_from(schema.as_ref()).unwrap();
+        let options = FileWriterOptions {
+            // Small pages so that several pages are encoded while the stream is read
+            data_cache_bytes: Some(32 * 1024),
+            ..Default::default()
+        };
+        let mut file_writer = FileWriter::try_new(writer, lance_schema, options).unwrap();
+        // Columns may arrive in any order and empty batches are skipped
+        let batches = data
+            .iter()
+            .map(|batch| Ok(batch.project(&[1, 0]).unwrap()))
+            .chain(std::iter::once(Ok(data[0].slice(0, 0))))
+            .collect::<Vec<_>>();
+        file_writer
+            .write_stream(futures::stream::iter(batches))
+            .await
+            .unwrap();
+        assert_eq!(file_writer.finish().await.unwrap().num_rows, 20 * 1000);
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        assert!(file_reader.metadata().column_metadatas[1].pages.len() > 1);
+        let batch_stream = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap();
+        verify_expected(&data, batch_stream, 1024, None).await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_page_alignment() {
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("nulls", array::step::<Int32Type>().with_random_nulls(0.5))
+            .col("text", array::rand_utf8(ByteCount::from(16), false))
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(10));
+        let options = FileWriterOptions {
+            page_alignment: Some(4096),
+            // Small pages so there are several pages per column
+            data_cache_bytes: Some(32 * 1024),
+            ..Default::default()
+        };
+        let (_, data) = write_lance_file(reader, &fs, options).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+
+        let metadata = file_reader.metadata();
+        assert_eq!(metadata.page_alignment, 4096);
+        let mut num_buffers = 0;
+        for column in &metadata.column_metadatas {
+            let page_offsets = column.pages.iter().flat_map(|page| &page.buffer_offsets);
+            for offset in page_offsets.chain(&column.buffer_offsets) {
+                assert_eq!(offset % 4096, 0);
+                num_buffers += 1;
+            }
+        }
+        assert!(num_buffers > 4);
+
+        let batch_stream = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap();
+        verify_expected(&data, batch_stream, 1024, None).await;
+
+        // Files written without an alignment report an alignment of 1
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("score", array::step::<Int32Type>())
+            .into_reader_rows(RowCount::from(10), BatchCount::from(1));
+        write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        assert_eq!(file_reader.metadata().page_alignment, 1);
+    }
+
+    #[test_log::test(tokio::test)]
+    #[allow(clippy::single_range_in_vec_init)]
+    async fn test_estimate_decode_cost() {
+        async fn open_reader(fs: &FsFixture) -> FileReader {
+            let reader = gen()
+                .col("values", array::step::<Int32Type>())
+                .into_reader_rows(RowCount::from(1000), BatchCount::from(10));
+            write_lance_file(reader, fs, FileWriterOptions::default()).await;
+            let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap()
+        }
+
+        let flat_fs = FsFixture::default();
+        let flat_reader = open_reader(&flat_fs).await;
+        let compressed_fs = FsFixture::default();
+        let compressed_reader = {
+            let _env_guard = EnvVarGuard::new("LANCE_PAGE_COMPRESSION", "zstd");
+            open_reader(&compressed_fs).await
+        };
+
+        // A point read from a flat page only fetches the one value
+        let flat_cost = flat_reader.estimate_decode_cost(0, &[5000..5001]).unwrap();
+        assert_eq!(flat_cost, DecodeCost::new(4, DecodeCpuClass::Copy));
+        let flat_cost = flat_reader
+            .estimate_decode_cost(0, &[0..10, 9990..10000])
+            .unwrap();
+        assert_eq!(flat_cost, DecodeCost::new(80, DecodeCpuClass::Copy));
+
+        // A point read from a compressed page fetches the entire page
+        let column = &compressed_reader.metadata().column_infos[0];
+        let page = column
+            .page_infos
+            .iter()
+            .scan(0, |page_start, page| {
+                let range = *page_start..*page_start + page.num_rows;
+                *page_start = range.end;
+                Some((range, page))
+            })
+            .find(|(range, _)| range.contains(&5000))
+            .unwrap()
+            .1;
+        let page_size = page
+            .buffer_offsets_and_sizes
+            .iter()
+            .map(|(_, size)| size)
+            .sum::<u64>();
+        let compressed_cost = compressed_reader
+            .estimate_decode_cost(0, &[5000..5001])
+            .unwrap();
+        assert_eq!(compressed_cost.cpu_class, DecodeCpuClass::Decompress);
+        assert_eq!(compressed_cost.bytes_to_fetch, page_size);
+        assert!(compressed_cost.bytes_to_fetch > flat_cost.bytes_to_fetch);
+
+        assert_eq!(
+            flat_reader.estimate_decode_cost(0, &[]).unwrap(),
+            DecodeCost::default()
+        );
+        assert!(flat_reader.estimate_decode_cost(1, &[0..1]).is_err());
+    }
+
+    // Writes (and reads back) a page with a single buffer larger than 2GiB.  This needs
+    // several GiB of RAM and disk and so it only runs with the `huge-page-tests` feature.
+    #[cfg(feature = "huge-page-tests")]
+    #[test_log::test(tokio::test)]
+    async fn test_page_buffer_larger_than_2gib() {
+        let fs = FsFixture::default();
+        let num_rows = (1_u64 << 31) + 4096;
+        let values =
+            arrow_array::UInt8Array::from_iter_values((0..num_rows).map(|i| (i % 251) as u8));
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "bytes",
+            DataType::UInt8,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(values)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        let page = &file_reader.metadata().column_infos[0].page_infos[0];
+        assert_eq!(page.num_rows, num_rows);
+        assert_eq!(page.buffer_offsets_and_sizes[0].1, num_rows);
+
+        // Read rows on both sides of the 2GiB boundary and at the very end
+        let indices = [0, (1 << 31) - 1, 1 << 31, num_rows - 1];
+        let batches = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::Indices(UInt32Array::from_iter_values(
+                    indices.iter().map(|&i| i as u32),
+                )),
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let actual = batches[0]
+            .column(0)
+            .as_primitive::<arrow_array::types::UInt8Type>();
+        let expected = indices.iter().map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        assert_eq!(actual.values().as_ref(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_corrupt_column_metadata_offsets() {
+        let footer = |num_columns| Footer {
+            column_meta_start: 100,
+            column_meta_offsets_start: 104,
+            global_buff_offsets_start: 120,
+            num_global_buffers: 0,
+            num_columns,
+            major_version: 2,
+            minor_version: 0,
+        };
+        let column_metadata = |position: u64, length: u64| {
+            let mut bytes = vec![0; 4];
+            bytes.extend_from_slice(&position.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+            Bytes::from(bytes)
+        };
+
+        let empty =
+            FileReader::read_all_column_metadata(column_metadata(100, 0), &footer(1)).unwrap();
+        assert_eq!(empty, vec![pbfile::ColumnMetadata::default()]);
+        for (position, length) in [(99, 0), (100, 21), (u64::MAX, 1), (100, u64::MAX)] {
+            let err =
+                FileReader::read_all_column_metadata(column_metadata(position, length), &footer(1))
+                    .unwrap_err();
+            assert!(
+                err.to_string().contains("outside of the column metadata"),
+                "{}",
+                err
+            );
+        }
+        // The offsets of more columns than there are bytes
+        assert!(FileReader::read_all_column_metadata(column_metadata(100, 0), &footer(2)).is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_read_sampled() {
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("values", array::step::<Int64Type>())
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(10));
+        write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+
+        let file_reader = &file_reader;
+        let sample = |seed| async move {
+            let batches = file_reader
+                .read_sampled_stream(0.01, seed, 32, 4, &file_reader.base_projection)
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            concat_batches(&batches[0].schema(), &batches).unwrap()
+        };
+
+        let sampled = sample(42).await;
+        assert_eq!(sampled.num_rows(), 100);
+        // The values are the row offsets so the offsets column should match them exactly
+        let values = sampled.column(0).as_primitive::<Int64Type>();
+        let offsets = sampled[ROW_OFFSET].as_primitive::<UInt64Type>();
+        for (value, offset) in values.values().iter().zip(offsets.values()) {
+            assert_eq!(*value as u64, *offset);
+        }
+        assert!(offsets.values().windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(sample(42).await, sampled);
+        assert_ne!(sample(7).await, sampled);
+
+        assert!(file_reader
+            .read_sampled_stream(1.5, 42, 32, 4, &file_reader.base_projection)
+            .is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_decoded_page_cache() {
+        let fs = FsFixture::default();
+        let reader = gen()
+            .col("values", array::step::<Int32Type>().with_random_nulls(0.2))
+            .col("text", array::rand_utf8(ByteCount::from(8), false))
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(10));
+        let (_, data) = {
+            let _env_guard = EnvVarGuard::new("LANCE_PAGE_COMPRESSION", "zstd");
+            write_lance_file(reader, &fs, FileWriterOptions::default()).await
+        };
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open_with_options(
+            file_scheduler,
+            None,
+            DecoderMiddlewareChain::default(),
+            &FileReaderOptions {
+                decoded_page_cache_size: 64 * 1024 * 1024,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let indices = vec![5_u32, 4000, 4001, 9999];
+        let expected = concat_batches(&data[0].schema(), &data).unwrap();
+        let expected =
+            arrow_select::take::take_record_batch(&expected, &UInt32Array::from(indices.clone()))
+                .unwrap();
+        let take = || async {
+            let batches = file_reader
+                .read_stream(
+                    lance_io::ReadBatchParams::Indices(UInt32Array::from(indices.clone())),
+                    1024,
+                    16,
+                    FilterExpression::no_filter(),
+                )
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            concat_batches(&batches[0].schema(), &batches).unwrap()
+        };
+
+        assert_eq!(take().await, expected);
+        // Only the compressed int32 pages are cached
+        let stats = file_reader.page_cache_stats().unwrap();
+        assert_eq!(stats.hits, 0);
+        assert!(stats.misses > 0);
+        assert_eq!(stats.num_pages as u64, stats.misses);
+
+        // The second take finds every page in the cache
+        assert_eq!(take().await, expected);
+        let second_stats = file_reader.page_cache_stats().unwrap();
+        assert_eq!(second_stats.hits, stats.misses);
+        assert_eq!(second_stats.misses, stats.misses);
+
+        // The cache is disabled by default
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        assert!(file_reader.page_cache_stats().is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_delta_of_delta_pages() {
+        let fs = FsFixture::default();
+        let _env_guard = EnvVarGuard::new("LANCE_USE_DELTA_OF_DELTA", "1");
+
+        let reader = gen()
+            .col("ts", array::step::<TimestampMillisecondType>())
+            .col(
+                "ts_nulls",
+                array::rand::<TimestampMicrosecondType>().with_random_nulls(0.3),
+            )
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(5));
+        let (_, data) = write_lance_file(reader, &fs, FileWriterOptions::default()).await;
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+
+        let batch_stream = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::Range(500..4321),
+                100,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap();
+        let expected = concat_batches(&data[0].schema(), &data)
+            .unwrap()
+            .slice(500, 3821);
+        verify_expected(&[expected], batch_stream, 100, None).await;
+    }
+
     #[tokio::test]
     async fn test_read_all() {
         let fs = FsFixture::default();
@@ -1387,4 +2477,285 @@ pub mod tests {
         let buf = file_reader.read_global_buffer(1).await.unwrap();
         assert_eq!(buf, test_bytes);
     }
+
+    // Encodes every page as indices into the shared dictionary 7
+    #[derive(Debug)]
+    struct SharedDictionaryStrategy(StringArray);
+
+    impl ArrayEncodingStrategy for SharedDictionaryStrategy {
+        fn create_array_encoder(
+            &self,
+            _arrays: &[ArrayRef],
+        ) -> lance_core::Result<Box<dyn ArrayEncoder>> {
+            Ok(Box::new(SharedDictionaryEncoder::try_new(
+                Box::new(BasicEncoder::new(Box::new(ValueEncoder::try_new(
+                    &DataType::UInt8,
+                    CompressionScheme::None,
+                )?))),
+                7,
+                &self.0,
+            )?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_dictionaries() {
+        let fs = FsFixture::default();
+        let dictionary = StringArray::from(vec!["red", "green", "blue"]);
+        let values: ArrayRef =
+            Arc::new(StringArray::from_iter((0..1000).map(|i| {
+                (i % 7 != 0).then_some(["red", "green", "blue"][i % 3])
+            })));
+        let schema = ArrowSchema::new(vec![Field::new("color", DataType::Utf8, true)]);
+        let batch = RecordBatch::try_new(Arc::new(schema.clone()), vec![values]).unwrap();
+
+        let mut file_writer = FileWriter::try_new(
+            fs.object_store.create(&fs.tmp_path).await.unwrap(),
+            Schema::try_from(&schema).unwrap(),
+            FileWriterOptions {
+                encoding_strategy: Some(Arc::new(CoreFieldEncodingStrategy::new(Arc::new(
+                    SharedDictionaryStrategy(dictionary.clone()),
+                )))),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        file_writer.write_batch(&batch).await.unwrap();
+        file_writer
+            .add_shared_dictionary(7, &dictionary)
+            .await
+            .unwrap();
+        // Dictionaries must be valid
+        assert!(file_writer
+            .add_shared_dictionary(8, &StringArray::from(vec![Some("a"), None]))
+            .await
+            .is_err());
+        file_writer.finish().await.unwrap();
+
+        // The pages only hold indices, the dictionary is loaded when the file is opened
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open(
+            file_scheduler.clone(),
+            None,
+            DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+        let raw_page = file_reader.read_raw_page(0, 0).await.unwrap();
+        assert!(format!("{:?}", raw_page.encoding).contains("SharedDictionary"));
+        let read = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(concat_batches(&batch.schema(), &read).unwrap(), batch);
+    }
+
+    #[tokio::test]
+    async fn test_read_raw_page() {
+        let fs = FsFixture::default();
+        let array_strategy = CoreArrayEncodingStrategy::default()
+            .with_bytes_compression(CompressionScheme::Zstd)
+            .with_offsets_encoding(OffsetsEncoding::DeltaBitpacked);
+        let values: ArrayRef = Arc::new(StringArray::from_iter_values(
+            (0..1000).map(|i| format!("value-{}", i % 100)),
+        ));
+        let schema = ArrowSchema::new(vec![Field::new("s", DataType::Utf8, false)]);
+        let batch = RecordBatch::try_new(Arc::new(schema.clone()), vec![values.clone()]).unwrap();
+
+        let mut file_writer = FileWriter::try_new(
+            fs.object_store.create(&fs.tmp_path).await.unwrap(),
+            Schema::try_from(&schema).unwrap(),
+            FileWriterOptions {
+                encoding_strategy: Some(Arc::new(CoreFieldEncodingStrategy::new(Arc::new(
+                    array_strategy.clone(),
+                )))),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        file_writer.write_batch(&batch).await.unwrap();
+        file_writer.finish().await.unwrap();
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open(
+            file_scheduler.clone(),
+            None,
+            DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(file_reader.metadata().column_infos[0].page_infos.len(), 1);
+        let raw_page = file_reader.read_raw_page(0, 0).await.unwrap();
+        assert_eq!(raw_page.num_rows, 1000);
+        assert!(format!("{:?}", raw_page.encoding).contains("zstd"));
+
+        // The page is exactly what the encoder produced
+        let expected = array_strategy
+            .create_array_encoder(std::slice::from_ref(&values))
+            .unwrap()
+            .encode(std::slice::from_ref(&values), &mut 0)
+            .unwrap();
+        assert_eq!(
+            raw_page.encoding.array_encoding,
+            expected.encoding.array_encoding
+        );
+        let (expected_buffers, _) = expected.into_parts();
+        assert_eq!(raw_page.buffers.len(), expected_buffers.len());
+        for (raw, expected) in raw_page.buffers.iter().zip(expected_buffers) {
+            let expected = expected
+                .parts
+                .iter()
+                .flat_map(|part| part.as_slice())
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(raw.as_ref(), expected.as_slice());
+        }
+        // The bytes are still compressed
+        let raw_bytes = raw_page.buffers.iter().map(|b| b.len()).sum::<usize>();
+        assert!(raw_bytes < values.get_array_memory_size() / 2);
+
+        assert!(file_reader.read_raw_page(0, 1).await.is_err());
+        assert!(file_reader.read_raw_page(1, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_little_endian_values() {
+        let fs = FsFixture::default();
+        let values = (0..1000).map(|i| i * 1_000_003).collect::<Vec<i64>>();
+        let schema = ArrowSchema::new(vec![Field::new("i", DataType::Int64, false)]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![Arc::new(Int64Array::from(values.clone()))],
+        )
+        .unwrap();
+
+        let options = FileWriterOptions {
+            little_endian_values: Some(true),
+            ..Default::default()
+        };
+        let mut file_writer = FileWriter::try_new(
+            fs.object_store.create(&fs.tmp_path).await.unwrap(),
+            Schema::try_from(&schema).unwrap(),
+            options.clone(),
+        )
+        .unwrap();
+        file_writer.write_batch(&batch).await.unwrap();
+        file_writer.finish().await.unwrap();
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        // The page records the byte order of its values, which are stored little-endian
+        let raw_page = file_reader.read_raw_page(0, 0).await.unwrap();
+        assert!(format!("{:?}", raw_page.encoding).contains("little_endian: true"));
+        let expected = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(raw_page.buffers.concat(), expected);
+
+        let batches = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(concat_batches(&batch.schema(), &batches).unwrap(), batch);
+
+        // The option configures the default strategy and so it can't be used with another one
+        let options = FileWriterOptions {
+            encoding_strategy: Some(Arc::new(CoreFieldEncodingStrategy::default())),
+            ..options
+        };
+        let result = FileWriter::try_new(
+            fs.object_store.create(&fs.tmp_path).await.unwrap(),
+            Schema::try_from(&schema).unwrap(),
+            options,
+        );
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bloom_filters() {
+        let fs = FsFixture::default();
+        let values = (0..1000)
+            .map(|i| match i {
+                0 => -0.0,
+                1 => f64::from_bits(0x7FF8_0000_0000_0001),
+                _ => i as f64 * 2.0,
+            })
+            .collect::<Vec<f64>>();
+        let schema = ArrowSchema::new(vec![Field::new("f", DataType::Float64, false)]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![Arc::new(Float64Array::from(values))],
+        )
+        .unwrap();
+
+        let options = FileWriterOptions {
+            bloom_filter_false_positive_rate: Some(0.01),
+            ..Default::default()
+        };
+        let mut file_writer = FileWriter::try_new(
+            fs.object_store.create(&fs.tmp_path).await.unwrap(),
+            Schema::try_from(&schema).unwrap(),
+            options,
+        )
+        .unwrap();
+        file_writer.write_batch(&batch).await.unwrap();
+        file_writer.finish().await.unwrap();
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader =
+            FileReader::try_open(file_scheduler, None, DecoderMiddlewareChain::default())
+                .await
+                .unwrap();
+        let raw_page = file_reader.read_raw_page(0, 0).await.unwrap();
+        let filter_pb = page_bloom_filter(&raw_page.encoding).unwrap();
+        let filter_buffer = filter_pb.buffer.as_ref().unwrap().buffer_index as usize;
+        let filter =
+            BloomFilter::try_from_pb(filter_pb, raw_page.buffers[filter_buffer].clone()).unwrap();
+
+        // Floats that compare equal are found, whatever their bits
+        let might_contain = |value: f64| {
+            filter.might_contain(&canonical_value(&DataType::Float64, &value.to_le_bytes()))
+        };
+        assert!(might_contain(0.0));
+        assert!(might_contain(-0.0));
+        assert!(might_contain(f64::NAN));
+        assert!(might_contain(1998.0));
+
+        let batches = file_reader
+            .read_stream(
+                lance_io::ReadBatchParams::RangeFull,
+                1024,
+                16,
+                FilterExpression::no_filter(),
+            )
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let decoded = concat_batches(&batch.schema(), &batches).unwrap();
+        let decoded = decoded.column(0).as_primitive::<Float64Type>().values();
+        let expected = batch.column(0).as_primitive::<Float64Type>().values();
+        assert!(decoded
+            .iter()
+            .zip(expected.iter())
+            .all(|(decoded, expected)| decoded.to_bits() == expected.to_bits()));
+    }
 }
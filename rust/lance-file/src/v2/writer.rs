@@ -1,20 +1,26 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
-use arrow_array::RecordBatch;
+use arrow_array::{Array, RecordBatch, StringArray};
+use arrow_schema::Schema as ArrowSchema;
 
 use bytes::{BufMut, Bytes, BytesMut};
 use futures::stream::FuturesUnordered;
-use futures::StreamExt;
+use futures::{Stream, StreamExt, TryStreamExt};
+use lance_arrow::RecordBatchExt;
 use lance_core::datatypes::Schema as LanceSchema;
+use lance_core::utils::tokio::get_num_compute_intensive_cpus;
 use lance_core::{Error, Result};
+use lance_encoding::checksum;
 use lance_encoding::encoder::{
-    BatchEncoder, CoreFieldEncodingStrategy, EncodeTask, EncodedBatch, EncodedPage, FieldEncoder,
-    FieldEncodingStrategy,
+    encode_stream, BatchEncoder, CoreArrayEncodingStrategy, CoreFieldEncodingStrategy, EncodeTask,
+    EncodedBatch, EncodedPage, FieldEncoder, FieldEncodingStrategy,
 };
+use lance_encoding::encodings::physical::dictionary::MAX_SHARED_DICTIONARY_SIZE;
+use lance_encoding::format::pb as pbenc;
 use lance_io::object_writer::ObjectWriter;
 use lance_io::traits::Writer;
 use log::debug;
@@ -30,6 +36,11 @@ use crate::format::pbfile::DirectEncoding;
 use crate::format::MAGIC;
 use crate::format::MAJOR_VERSION;
 use crate::format::MINOR_VERSION_NEXT;
+use crate::format::SHARED_DICTIONARY_META_KEY_PREFIX;
+
+/// The bytes of column data buffered per column when [`FileWriterOptions::data_cache_bytes`]
+/// is not set
+pub const DEFAULT_DATA_CACHE_BYTES_PER_COLUMN: u64 = 8 * 1024 * 1024;
 
 #[derive(Debug, Clone, Default)]
 pub struct FileWriterOptions {
@@ -67,6 +78,107 @@ pub struct FileWriterOptions {
     /// of that batch's data has been written to disk)
     pub keep_original_array: Option<bool>,
     pub encoding_strategy: Option<Arc<dyn FieldEncodingStrategy>>,
+    /// Stores values in little-endian byte order on every host, so that the file can be
+    /// read on hosts of either byte order
+    ///
+    /// This configures the default encoding strategy, see
+    /// [`CoreArrayEncodingStrategy::with_little_endian_values`].  It can't be combined
+    /// with `encoding_strategy`, a custom strategy has to enable it itself.  Defaults to
+    /// false, which stores values in the host's byte order.
+    pub little_endian_values: Option<bool>,
+    /// Adds a Bloom filter with this false positive rate to every page of primitive, binary
+    /// and string values
+    ///
+    /// Readers looking for particular values can use the filters to skip pages that cannot
+    /// contain them.  A 1% rate costs about 10 bits per value.  This configures the default
+    /// encoding strategy, see [`CoreArrayEncodingStrategy::with_bloom_filters`].  It can't be
+    /// combined with `encoding_strategy`.  Defaults to None, which writes no filters.
+    pub bloom_filter_false_positive_rate: Option<f64>,
+    /// The alignment, in bytes, of the start of each page and column buffer
+    ///
+    /// The writer will insert padding so that every buffer begins at a file offset that
+    /// is a multiple of this value.  This is useful for readers that use O_DIRECT, which
+    /// requires aligned reads.  The buffer sizes do not include the padding and so readers
+    /// are unaffected.
+    ///
+    /// The default (1) writes buffers back to back.
+    pub page_alignment: Option<u64>,
+}
+
+/// Statistics describing the pages written for a single column
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnWriteStatistics {
+    /// The number of pages written with each encoding, keyed by encoding name
+    ///
+    /// The name describes the final (innermost) encoding of the page's values, see
+    /// [`page_encoding_name`]
+    pub num_pages: BTreeMap<&'static str, u64>,
+    /// The number of bytes written with each encoding, keyed by encoding name
+    pub num_bytes: BTreeMap<&'static str, u64>,
+}
+
+/// Statistics describing what the writer wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriterStatistics {
+    /// The total number of rows written
+    pub num_rows: u64,
+    /// Statistics for each column, in column index order
+    pub columns: Vec<ColumnWriteStatistics>,
+}
+
+/// A short name for the encoding that ended up being used for the values of a page
+///
+/// Nullability and Bloom filter wrappers are looked through so, for example, a nullable
+/// page of bitpacked values is "bitpacked".  Flat pages that are compressed are "compressed".
+pub fn page_encoding_name(encoding: &pbenc::ArrayEncoding) -> &'static str {
+    use pbenc::array_encoding::ArrayEncoding;
+    match &encoding.array_encoding {
+        Some(ArrayEncoding::Nullable(nullable)) => match &nullable.nullability {
+            Some(pbenc::nullable::Nullability::NoNulls(no_nulls)) => no_nulls
+                .values
+                .as_deref()
+                .map(page_encoding_name)
+                .unwrap_or("unknown"),
+            Some(pbenc::nullable::Nullability::SomeNulls(some_nulls)) => some_nulls
+                .values
+                .as_deref()
+                .map(page_encoding_name)
+                .unwrap_or("unknown"),
+            Some(pbenc::nullable::Nullability::AllNulls(_)) => "all_null",
+            None => "unknown",
+        },
+        Some(ArrayEncoding::Flat(flat)) => {
+            // Compression is recorded as "none" if the writer decided not to compress the page
+            if flat
+                .compression
+                .as_ref()
+                .is_some_and(|compression| compression.scheme != "none")
+            {
+                "compressed"
+            } else {
+                "flat"
+            }
+        }
+        Some(ArrayEncoding::Bitpacked(_)) => "bitpacked",
+        Some(ArrayEncoding::ChunkedBitpacked(_)) => "chunked_bitpacked",
+        Some(ArrayEncoding::BooleanRle(_)) => "boolean_rle",
+        Some(ArrayEncoding::DeltaOfDelta(_)) => "delta_of_delta",
+        Some(ArrayEncoding::TransformPipeline(_)) => "transform_pipeline",
+        Some(ArrayEncoding::Quantized(_)) => "quantized",
+        Some(ArrayEncoding::Sparse(_)) => "sparse",
+        Some(ArrayEncoding::BloomFiltered(bloom_filtered)) => bloom_filtered
+            .values
+            .as_deref()
+            .map(page_encoding_name)
+            .unwrap_or("unknown"),
+        Some(ArrayEncoding::Dictionary(_)) => "dictionary",
+        Some(ArrayEncoding::Binary(_)) => "binary",
+        Some(ArrayEncoding::Fsst(_)) => "fsst",
+        Some(ArrayEncoding::FixedSizeList(_)) => "fixed_size_list",
+        Some(ArrayEncoding::List(_)) => "list",
+        Some(ArrayEncoding::Struct(_)) => "struct",
+        None => "unknown",
+    }
 }
 
 pub struct FileWriter {
@@ -80,6 +192,7 @@ pub struct FileWriter {
     global_buffers: Vec<(u64, u64)>,
     schema_metadata: HashMap<String, String>,
     options: FileWriterOptions,
+    statistics: WriterStatistics,
 }
 
 fn initial_column_metadata() -> pbfile::ColumnMetadata {
@@ -119,31 +232,59 @@ impl FileWriter {
             global_buffers: Vec::new(),
             schema_metadata: HashMap::new(),
             options,
+            statistics: WriterStatistics::default(),
+        }
+    }
+
+    fn page_alignment(&self) -> u64 {
+        self.options.page_alignment.unwrap_or(1)
+    }
+
+    // Writes zeros until the current position is a multiple of the page alignment,
+    // returns the new position
+    async fn pad_to_alignment(&mut self) -> Result<u64> {
+        let position = self.writer.tell().await? as u64;
+        let padding = position.next_multiple_of(self.page_alignment()) - position;
+        if padding > 0 {
+            self.writer.write_all(&vec![0; padding as usize]).await?;
         }
+        Ok(position + padding)
     }
 
     async fn write_page(&mut self, encoded_page: EncodedPage) -> Result<()> {
-        let mut buffers = encoded_page.array.buffers;
-        buffers.sort_by_key(|b| b.index);
-        let mut buffer_offsets = Vec::with_capacity(buffers.len());
-        let mut buffer_sizes = Vec::with_capacity(buffers.len());
-        for buffer in buffers {
-            buffer_offsets.push(self.writer.tell().await? as u64);
-            buffer_sizes.push(
-                buffer
-                    .parts
-                    .iter()
-                    .map(|part| part.len() as u64)
-                    .sum::<u64>(),
-            );
+        let num_buffers = encoded_page.array.buffers.len();
+        let mut buffer_offsets = Vec::with_capacity(num_buffers);
+        let mut buffer_sizes = Vec::with_capacity(num_buffers);
+        let mut buffer_checksums = Vec::with_capacity(num_buffers);
+        for (_, buffer_type, parts) in encoded_page.array.buffers_with_types() {
+            debug_assert_eq!(buffer_type, pbenc::buffer::BufferType::Page);
+            buffer_offsets.push(self.pad_to_alignment().await?);
+            buffer_sizes.push(parts.iter().map(|part| part.len() as u64).sum::<u64>());
+            buffer_checksums.push(checksum::combine_checksums(parts.iter().map(|part| {
+                (
+                    checksum::checksum_parallel(part, checksum::CHECKSUM_BLOCK_SIZE),
+                    part.len() as u64,
+                )
+            })));
             // Note: could potentially use write_vectored here but there is no
             // write_vectored_all and object_store doesn't support it anyways and
             // buffers won't normally be in *too* many parts so its unlikely to
             // have much benefit in most cases.
-            for part in &buffer.parts {
+            for part in parts {
                 self.writer.write_all(part).await?;
             }
         }
+        let column_statistics = &mut self.statistics.columns[encoded_page.column_idx as usize];
+        let encoding_name = page_encoding_name(&encoded_page.array.encoding);
+        *column_statistics
+            .num_pages
+            .entry(encoding_name)
+            .or_default() += 1;
+        *column_statistics
+            .num_bytes
+            .entry(encoding_name)
+            .or_default() += buffer_sizes.iter().sum::<u64>();
+
         let encoded_encoding = Any::from_msg(&encoded_page.array.encoding)?.encode_to_vec();
         let page = pbfile::column_metadata::Page {
             buffer_offsets,
@@ -154,6 +295,7 @@ impl FileWriter {
                 })),
             }),
             length: encoded_page.num_rows,
+            buffer_checksums,
         };
         self.column_metadata[encoded_page.column_idx as usize]
             .pages
@@ -185,6 +327,73 @@ impl FileWriter {
         Ok(())
     }
 
+    // Returns the number of rows written after writing `num_rows` more rows, or an error
+    // if the file would be too large
+    fn checked_rows_written(rows_written: u64, num_rows: u64) -> Result<u64> {
+        if num_rows > u32::MAX as u64 {
+            return Err(Error::InvalidInput {
+                source: "cannot write Lance files with more than 2^32 rows".into(),
+                location: location!(),
+            });
+        }
+        match rows_written.checked_add(num_rows) {
+            Some(rows_written) => Ok(rows_written),
+            None => Err(Error::InvalidInput { source: format!("cannot write batch with {} rows because {} rows have already been written and Lance files cannot contain more than 2^32 rows", num_rows, rows_written).into(), location: location!() }),
+        }
+    }
+
+    /// Write a stream of batches to the file
+    ///
+    /// Pages are written as soon as they are encoded, while later batches are still being
+    /// read, and the stream is only read as quickly as pages can be encoded and written.
+    /// This keeps memory use close to the page size targets regardless of the size of the
+    /// input.  Any data buffered by the column writers is flushed once the stream ends.
+    pub async fn write_stream(
+        &mut self,
+        batches: impl Stream<Item = Result<RecordBatch>> + Send,
+    ) -> Result<()> {
+        let mut batches = batches.boxed();
+        if self.schema.is_none() {
+            // The schema is determined by the first batch
+            match batches.next().await {
+                Some(batch) => self.write_batch(&batch?).await?,
+                None => return Ok(()),
+            }
+        }
+        let arrow_schema = Arc::new(ArrowSchema::from(self.schema.as_ref().unwrap()));
+        let mut rows_written = self.rows_written;
+        let batches = batches
+            .try_filter(|batch| std::future::ready(batch.num_rows() > 0))
+            .map(|batch| {
+                let batch = batch?;
+                rows_written = Self::checked_rows_written(rows_written, batch.num_rows() as u64)?;
+                // The column writers expect the columns in schema order
+                Ok(batch.project_by_schema(&arrow_schema)?)
+            });
+
+        let mut column_writers = std::mem::take(&mut self.column_writers);
+        let result = self
+            .write_encoded_stream(&mut column_writers, batches)
+            .await;
+        self.column_writers = column_writers;
+        result?;
+        self.rows_written = rows_written;
+        Ok(())
+    }
+
+    async fn write_encoded_stream(
+        &mut self,
+        column_writers: &mut [Box<dyn FieldEncoder>],
+        batches: impl Stream<Item = Result<RecordBatch>> + Send,
+    ) -> Result<()> {
+        let mut pages = encode_stream(column_writers, batches, get_num_compute_intensive_cpus());
+        while let Some(page) = pages.next().await {
+            self.write_page(page?).await?;
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+
     /// Schedule batches of data to be written to the file
     pub async fn write_batches(
         &mut self,
@@ -200,17 +409,46 @@ impl FileWriter {
         let cache_bytes_per_column = if let Some(data_cache_bytes) = self.options.data_cache_bytes {
             data_cache_bytes / schema.fields.len() as u64
         } else {
-            8 * 1024 * 1024
+            DEFAULT_DATA_CACHE_BYTES_PER_COLUMN
         };
 
         schema.validate()?;
 
+        if self.options.page_alignment == Some(0) {
+            return Err(Error::invalid_input(
+                "The page alignment must be greater than 0",
+                location!(),
+            ));
+        }
+
         let keep_original_array = self.options.keep_original_array.unwrap_or(false);
-        let encoding_strategy = self
-            .options
-            .encoding_strategy
-            .clone()
-            .unwrap_or_else(|| Arc::new(CoreFieldEncodingStrategy::default()));
+        let little_endian_values = self.options.little_endian_values.unwrap_or(false);
+        let bloom_filter_false_positive_rate = self.options.bloom_filter_false_positive_rate;
+        let encoding_strategy = match self.options.encoding_strategy.clone() {
+            Some(_) if little_endian_values => {
+                return Err(Error::invalid_input(
+                    "little_endian_values cannot be combined with a custom encoding strategy",
+                    location!(),
+                ));
+            }
+            Some(_) if bloom_filter_false_positive_rate.is_some() => {
+                return Err(Error::invalid_input(
+                    "bloom_filter_false_positive_rate cannot be combined with a custom encoding strategy",
+                    location!(),
+                ));
+            }
+            Some(encoding_strategy) => encoding_strategy,
+            None => {
+                let mut array_strategy = CoreArrayEncodingStrategy::default();
+                if little_endian_values {
+                    array_strategy = array_strategy.with_little_endian_values();
+                }
+                if let Some(false_positive_rate) = bloom_filter_false_positive_rate {
+                    array_strategy = array_strategy.with_bloom_filters(false_positive_rate);
+                }
+                Arc::new(CoreFieldEncodingStrategy::new(Arc::new(array_strategy)))
+            }
+        };
 
         let encoder = BatchEncoder::try_new(
             &schema,
@@ -222,6 +460,7 @@ impl FileWriter {
 
         self.column_writers = encoder.field_encoders;
         self.column_metadata = vec![initial_column_metadata(); self.num_columns as usize];
+        self.statistics.columns = vec![ColumnWriteStatistics::default(); self.num_columns as usize];
         self.field_id_to_column_indices = encoder.field_id_to_column_index;
         self.schema_metadata
             .extend(std::mem::take(&mut schema.metadata));
@@ -252,18 +491,7 @@ impl FileWriter {
         if num_rows == 0 {
             return Ok(());
         }
-        if num_rows > u32::MAX as u64 {
-            return Err(Error::InvalidInput {
-                source: "cannot write Lance files with more than 2^32 rows".into(),
-                location: location!(),
-            });
-        }
-        self.rows_written = match self.rows_written.checked_add(batch.num_rows() as u64) {
-            Some(rows_written) => rows_written,
-            None => {
-                return Err(Error::InvalidInput { source: format!("cannot write batch with {} rows because {} rows have already been written and Lance files cannot contain more than 2^32 rows", num_rows, self.rows_written).into(), location: location!() });
-            }
-        };
+        self.rows_written = Self::checked_rows_written(self.rows_written, num_rows)?;
         // First we push each array into its column writer.  This may or may not generate enough
         // data to trigger an encoding task.  We collect any encoding tasks into a queue.
         let encoding_tasks = schema
@@ -318,6 +546,7 @@ impl FileWriter {
     fn make_file_descriptor(
         schema: &lance_core::datatypes::Schema,
         num_rows: u64,
+        page_alignment: u64,
     ) -> Result<pb::FileDescriptor> {
         let fields_with_meta = FieldsWithMeta::from(schema);
         Ok(pb::FileDescriptor {
@@ -326,13 +555,16 @@ impl FileWriter {
                 metadata: fields_with_meta.metadata,
             }),
             length: num_rows,
+            page_alignment,
         })
     }
 
     async fn write_global_buffers(&mut self) -> Result<Vec<(u64, u64)>> {
+        let page_alignment = self.page_alignment();
         let schema = self.schema.as_mut().ok_or(Error::invalid_input("No schema provided on writer open and no data provided.  Schema is unknown and file cannot be created", location!()))?;
         schema.metadata = std::mem::take(&mut self.schema_metadata);
-        let file_descriptor = Self::make_file_descriptor(schema, self.rows_written)?;
+        let file_descriptor =
+            Self::make_file_descriptor(schema, self.rows_written, page_alignment)?;
         let file_descriptor_bytes = file_descriptor.encode_to_vec();
         let file_descriptor_len = file_descriptor_bytes.len() as u64;
         let file_descriptor_position = self.writer.tell().await? as u64;
@@ -365,6 +597,41 @@ impl FileWriter {
         Ok(self.global_buffers.len() as u32)
     }
 
+    /// Adds a dictionary that pages of the file can refer to by `id`
+    ///
+    /// Pages encoded with a
+    /// [`lance_encoding::encodings::physical::dictionary::SharedDictionaryEncoder`] only
+    /// store indices into the dictionary.  The dictionary is written to a global buffer immediately and readers
+    /// load it when they open the file.  It must be called before `finish` is called.
+    pub async fn add_shared_dictionary(&mut self, id: u32, dictionary: &StringArray) -> Result<()> {
+        if dictionary.len() > MAX_SHARED_DICTIONARY_SIZE || dictionary.null_count() > 0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "a shared dictionary must have at most {} values and no nulls",
+                    MAX_SHARED_DICTIONARY_SIZE
+                ),
+                location!(),
+            ));
+        }
+        // The number of values, the offsets of the values and then the bytes of the values
+        let offsets = dictionary.value_offsets();
+        let first_offset = offsets[0];
+        let values =
+            &dictionary.value_data()[first_offset as usize..offsets[offsets.len() - 1] as usize];
+        let mut buffer = BytesMut::with_capacity(4 * (offsets.len() + 1) + values.len());
+        buffer.put_u32_le(dictionary.len() as u32);
+        for offset in offsets {
+            buffer.put_u32_le((offset - first_offset) as u32);
+        }
+        buffer.put_slice(values);
+        let index = self.add_global_buffer(buffer.freeze()).await?;
+        self.add_schema_metadata(
+            format!("{}{}", SHARED_DICTIONARY_META_KEY_PREFIX, id),
+            index.to_string(),
+        );
+        Ok(())
+    }
+
     async fn finish_writers(&mut self) -> Result<()> {
         let mut col_idx = 0;
         for mut writer in std::mem::take(&mut self.column_writers) {
@@ -381,18 +648,18 @@ impl FileWriter {
                 for page in column.final_pages {
                     self.write_page(page).await?;
                 }
-                let column_metadata = &mut self.column_metadata[col_idx];
-                let mut buffer_pos = self.writer.tell().await? as u64;
                 for buffer in column.column_buffers {
-                    column_metadata.buffer_offsets.push(buffer_pos);
+                    let buffer_pos = self.pad_to_alignment().await?;
                     let mut size = 0;
                     for part in buffer.parts {
                         self.writer.write_all(&part).await?;
                         size += part.len() as u64;
                     }
-                    buffer_pos += size;
+                    let column_metadata = &mut self.column_metadata[col_idx];
+                    column_metadata.buffer_offsets.push(buffer_pos);
                     column_metadata.buffer_sizes.push(size);
                 }
+                let column_metadata = &mut self.column_metadata[col_idx];
                 let encoded_encoding = Any::from_msg(&column.encoding)?.encode_to_vec();
                 column_metadata.encoding = Some(pbfile::Encoding {
                     location: Some(pbfile::encoding::Location::Direct(pbfile::DirectEncoding {
@@ -418,8 +685,8 @@ impl FileWriter {
     /// will write the file metadata and the footer.  It will not return until all
     /// data has been flushed and the file has been closed.
     ///
-    /// Returns the total number of rows written
-    pub async fn finish(&mut self) -> Result<u64> {
+    /// Returns statistics describing what was written
+    pub async fn finish(&mut self) -> Result<WriterStatistics> {
         // 1. flush any remaining data and write out those pages
         let encoding_tasks = self
             .column_writers
@@ -468,7 +735,15 @@ impl FileWriter {
 
         // 7. close the writer
         self.writer.shutdown().await?;
-        Ok(self.rows_written)
+        self.statistics.num_rows = self.rows_written;
+        Ok(self.statistics.clone())
+    }
+
+    /// Stops writing the file without writing the footer
+    ///
+    /// Pages that are still being uploaded are cancelled and the file is not created.
+    pub async fn abort(&mut self) -> Result<()> {
+        self.writer.abort().await
     }
 
     pub async fn tell(&mut self) -> Result<u64> {
@@ -503,7 +778,7 @@ fn concat_lance_footer(batch: &EncodedBatch, write_schema: bool) -> Result<Bytes
     let global_buffers = if write_schema {
         let schema_start = data.len() as u64;
         let lance_schema = lance_core::datatypes::Schema::try_from(batch.schema.as_ref())?;
-        let descriptor = FileWriter::make_file_descriptor(&lance_schema, batch.num_rows)?;
+        let descriptor = FileWriter::make_file_descriptor(&lance_schema, batch.num_rows, 1)?;
         let descriptor_bytes = descriptor.encode_to_vec();
         let descriptor_len = descriptor_bytes.len() as u64;
         data.put(descriptor_bytes.as_slice());
@@ -538,6 +813,7 @@ fn concat_lance_footer(batch: &EncodedBatch, write_schema: bool) -> Result<Bytes
                         })),
                     }),
                     length: page_info.num_rows,
+                    buffer_checksums: Vec::new(),
                 })
             })
             .collect::<Result<Vec<_>>>()?;
@@ -236,6 +236,11 @@ impl<M: ManifestProvider + Send + Sync> FileWriter<M> {
         Ok(num_rows as usize)
     }
 
+    /// Stops writing the file, the object is not created
+    pub async fn abort(&mut self) -> Result<()> {
+        self.object_writer.abort().await
+    }
+
     /// Total records written in this file.
     pub fn len(&self) -> usize {
         self.metadata.len()
@@ -75,6 +75,7 @@ pub fn bench_storage(c: &mut Criterion) {
             8,
             DistanceType::L2,
             -1.0..1.0,
+            Vec::new(),
             repeat_with(|| create_sq_batch(0..(TOTAL / num_chunks) as u64, 512)).take(num_chunks),
         )
         .unwrap();
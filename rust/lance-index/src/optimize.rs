@@ -17,12 +17,24 @@ pub struct OptimizeOptions {
     /// A common usage pattern will be that, the caller can keep a large snapshot of the index of the base version,
     /// and accumulate a few delta indices, then merge them into the snapshot.
     pub num_indices_to_merge: usize,
+
+    /// Retrain vector indices from scratch once there is enough new data.
+    ///
+    /// New rows are normally added to a vector index by assigning them to the existing
+    /// IVF partitions and quantizing them with the existing codebooks, which describe the
+    /// data less well as it grows.  If set, and the unindexed rows are more than this
+    /// fraction of the rows covered by the index, new centroids and codebooks are trained
+    /// over the whole column and the new index replaces all of the deltas.
+    ///
+    /// Scalar indices ignore this option.  Default: None, never retrain.
+    pub retrain_threshold: Option<f64>,
 }
 
 impl Default for OptimizeOptions {
     fn default() -> Self {
         Self {
             num_indices_to_merge: 1,
+            retrain_threshold: None,
         }
     }
 }
@@ -80,6 +80,10 @@ pub struct Query {
 
     /// Whether to use an ANN index if available
     pub use_index: bool,
+
+    /// If set, only rows whose distance to the key is less than `radius` are
+    /// returned (a range search) and `k` caps the number of results.
+    pub radius: Option<f32>,
 }
 
 impl From<pb::VectorMetricType> for DistanceType {
@@ -4,7 +4,7 @@
 //! Flat Vector Index.
 //!
 
-use std::{collections::HashSet, sync::Arc};
+use std::sync::Arc;
 
 use arrow::array::AsArray;
 use arrow_array::{Array, ArrayRef, Float32Array, RecordBatch, UInt64Array};
@@ -97,12 +97,10 @@ impl IvfSubIndex for FlatIndex {
                 )
                 .unzip(),
             false => {
-                let filtered_row_ids = prefilter
+                // The prefilter selects the positions, in the storage, of the allowed rows
+                prefilter
                     .filter_row_ids(Box::new(storage.row_ids()))
                     .into_iter()
-                    .collect::<HashSet<_>>();
-                (0..storage.len())
-                    .filter(|&id| !filtered_row_ids.contains(&storage.row_id(id as u32)))
                     .map(|id| OrderedNode {
                         id: id as u32,
                         dist: OrderedFloat(dist_calc.distance(id as u32)),
@@ -242,15 +242,15 @@ impl Quantization for ScalarQuantizer {
             dim: self.dim,
             num_bits: self.num_bits(),
             bounds: self.bounds(),
+            dim_bounds: self.dim_bounds().to_vec(),
         })?)
     }
 
     fn from_metadata(metadata: &Self::Metadata, _: DistanceType) -> Result<Quantizer> {
-        Ok(Quantizer::Scalar(Self::with_bounds(
-            metadata.num_bits,
-            metadata.dim,
-            metadata.bounds.clone(),
-        )))
+        Ok(Quantizer::Scalar(
+            Self::with_bounds(metadata.num_bits, metadata.dim, metadata.bounds.clone())
+                .with_dim_bounds(metadata.dim_bounds.clone()),
+        ))
     }
 }
 
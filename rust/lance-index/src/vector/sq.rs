@@ -33,7 +33,14 @@ pub struct ScalarQuantizer {
     /// Original dimension of the vectors.
     pub dim: usize,
 
+    /// Value range over all dimensions.
     pub bounds: Range<f64>,
+
+    /// Value range of each dimension, learned at build time.
+    ///
+    /// Empty for indices written before the per-dimension ranges were
+    /// recorded, which scale every dimension by `bounds`.
+    pub dim_bounds: Vec<Range<f64>>,
 }
 
 impl DeepSizeOf for ScalarQuantizer {
@@ -51,6 +58,7 @@ impl ScalarQuantizer {
                 start: f64::MAX,
                 end: f64::MIN,
             },
+            dim_bounds: Vec::new(),
         }
     }
 
@@ -60,6 +68,11 @@ impl ScalarQuantizer {
         sq
     }
 
+    pub fn with_dim_bounds(mut self, dim_bounds: Vec<Range<f64>>) -> Self {
+        self.dim_bounds = dim_bounds;
+        self
+    }
+
     pub fn num_bits(&self) -> u16 {
         self.num_bits
     }
@@ -85,6 +98,16 @@ impl ScalarQuantizer {
             f.start.min(v.to_f64().unwrap())..f.end.max(v.to_f64().unwrap())
         });
 
+        if self.dim_bounds.len() != self.dim {
+            self.dim_bounds = vec![f64::MAX..f64::MIN; self.dim];
+        }
+        for vector in data.chunks_exact(self.dim) {
+            for (b, v) in self.dim_bounds.iter_mut().zip(vector) {
+                let v = v.to_f64().unwrap();
+                *b = b.start.min(v)..b.end.max(v);
+            }
+        }
+
         Ok(self.bounds.clone())
     }
 
@@ -113,7 +136,11 @@ impl ScalarQuantizer {
             .as_slice();
 
         // TODO: support SQ4
-        let builder: Vec<u8> = scale_to_u8::<T>(data, self.bounds.clone());
+        let builder: Vec<u8> = if self.dim_bounds.is_empty() {
+            scale_to_u8::<T>(data, self.bounds.clone())
+        } else {
+            scale_to_u8_by_dim::<T>(data, &self.dim_bounds)
+        };
 
         Ok(Arc::new(FixedSizeListArray::try_new_from_values(
             UInt8Array::from(builder),
@@ -125,6 +152,23 @@ impl ScalarQuantizer {
         self.bounds.clone()
     }
 
+    pub fn dim_bounds(&self) -> &[Range<f64>] {
+        &self.dim_bounds
+    }
+
+    /// The value each code of a dimension decodes to, as `(start, step)`
+    /// pairs: code `c` of dimension `i` decodes to `start[i] + c * step[i]`.
+    pub fn decode_params(&self) -> (Vec<f32>, Vec<f32>) {
+        // TODO: support SQ4, the codes are always one byte for now
+        let params = |b: &Range<f64>| (b.start as f32, ((b.end - b.start) / 255.0) as f32);
+        if self.dim_bounds.is_empty() {
+            let (start, step) = params(&self.bounds);
+            (vec![start; self.dim], vec![step; self.dim])
+        } else {
+            self.dim_bounds.iter().map(params).unzip()
+        }
+    }
+
     /// Whether to use residual as input or not.
     pub fn use_residual(&self) -> bool {
         false
@@ -144,28 +188,50 @@ impl TryFrom<Quantizer> for ScalarQuantizer {
     }
 }
 
-pub(crate) fn scale_to_u8<T: ArrowFloatType>(values: &[T::Native], bounds: Range<f64>) -> Vec<u8> {
+fn scale_value(v: f64, bounds: &Range<f64>) -> u8 {
     let range = bounds.end - bounds.start;
+    match v {
+        v if v <= bounds.start => 0,
+        v if v >= bounds.end => 255,
+        _ => ((v - bounds.start) * f64::from_u32(255).unwrap() / range)
+            .round()
+            .to_u8()
+            .unwrap(),
+    }
+}
+
+pub(crate) fn scale_to_u8<T: ArrowFloatType>(values: &[T::Native], bounds: Range<f64>) -> Vec<u8> {
     values
         .iter()
-        .map(|&v| {
-            let v = v.to_f64().unwrap();
-            match v {
-                v if v < bounds.start => 0,
-                v if v > bounds.end => 255,
-                _ => ((v - bounds.start) * f64::from_u32(255).unwrap() / range)
-                    .round()
-                    .to_u8()
-                    .unwrap(),
-            }
+        .map(|&v| scale_value(v.to_f64().unwrap(), &bounds))
+        .collect_vec()
+}
+
+/// Scale each dimension of the vectors by its own value range.
+pub(crate) fn scale_to_u8_by_dim<T: ArrowFloatType>(
+    values: &[T::Native],
+    dim_bounds: &[Range<f64>],
+) -> Vec<u8> {
+    values
+        .chunks_exact(dim_bounds.len())
+        .flat_map(|vector| {
+            vector
+                .iter()
+                .zip(dim_bounds)
+                .map(|(&v, bounds)| scale_value(v.to_f64().unwrap(), bounds))
         })
         .collect_vec()
 }
+
 #[cfg(test)]
 mod tests {
-    use arrow::datatypes::{Float16Type, Float32Type, Float64Type};
+    use arrow::datatypes::{Float16Type, Float32Type, Float64Type, UInt8Type};
     use arrow_array::{Float16Array, Float32Array, Float64Array};
     use half::f16;
+    use lance_linalg::distance::DistanceType;
+
+    use crate::vector::quantizer::{Quantization, Quantizer};
+    use crate::vector::sq::storage::ScalarQuantizationMetadata;
 
     use super::*;
 
@@ -252,4 +318,68 @@ mod tests {
             assert_eq!(*v, (i * 17) as u8,);
         });
     }
+
+    #[tokio::test]
+    async fn test_sq8_per_dimension_bounds() {
+        // The second dimension spans 10 times the range of the first one.
+        let float_values = (0..16)
+            .flat_map(|v| [v as f32, (v * 10) as f32])
+            .collect::<Vec<_>>();
+        let vectors =
+            FixedSizeListArray::try_new_from_values(Float32Array::from(float_values), 2).unwrap();
+        let mut sq = ScalarQuantizer::new(8, 2);
+
+        sq.update_bounds::<Float32Type>(&vectors).unwrap();
+        assert_eq!(sq.bounds, 0.0..150.0);
+        assert_eq!(sq.dim_bounds, vec![0.0..15.0, 0.0..150.0]);
+
+        let sq_code = sq.transform::<Float32Type>(&vectors).unwrap();
+        let sq_values = sq_code
+            .as_fixed_size_list()
+            .values()
+            .as_primitive::<UInt8Type>();
+        // Both dimensions use the full code range.
+        sq_values.values().chunks(2).enumerate().for_each(|(i, v)| {
+            assert_eq!(v, &[(i * 17) as u8, (i * 17) as u8]);
+        });
+
+        let (starts, steps) = sq.decode_params();
+        assert_eq!(starts, vec![0.0, 0.0]);
+        assert_eq!(
+            steps,
+            vec![(15.0f64 / 255.0) as f32, (150.0f64 / 255.0) as f32]
+        );
+    }
+
+    #[test]
+    fn test_sq_metadata_round_trip() {
+        let sq = ScalarQuantizer::with_bounds(8, 2, -1.0..3.0)
+            .with_dim_bounds(vec![-1.0..1.0, 0.5..3.0]);
+
+        let metadata = serde_json::to_string(&sq.metadata(None).unwrap()).unwrap();
+        let metadata: ScalarQuantizationMetadata = serde_json::from_str(&metadata).unwrap();
+        let Quantizer::Scalar(loaded) =
+            ScalarQuantizer::from_metadata(&metadata, DistanceType::L2).unwrap()
+        else {
+            panic!("expect a scalar quantizer");
+        };
+        assert_eq!(loaded.num_bits, sq.num_bits);
+        assert_eq!(loaded.dim, sq.dim);
+        assert_eq!(loaded.bounds, sq.bounds);
+        assert_eq!(loaded.dim_bounds, sq.dim_bounds);
+
+        // Indices written before the per-dimension bounds only have the global bounds.
+        let metadata: ScalarQuantizationMetadata =
+            serde_json::from_str(r#"{"dim":2,"num_bits":8,"bounds":{"start":-1.0,"end":3.0}}"#)
+                .unwrap();
+        assert!(metadata.dim_bounds.is_empty());
+        let Quantizer::Scalar(loaded) =
+            ScalarQuantizer::from_metadata(&metadata, DistanceType::L2).unwrap()
+        else {
+            panic!("expect a scalar quantizer");
+        };
+        let (starts, steps) = loaded.decode_params();
+        assert_eq!(starts, vec![-1.0, -1.0]);
+        assert_eq!(steps, vec![(4.0f64 / 255.0) as f32; 2]);
+    }
 }
@@ -32,7 +32,7 @@ use crate::{
     IndexMetadata, INDEX_METADATA_SCHEMA_KEY,
 };
 
-use super::{scale_to_u8, ScalarQuantizer};
+use super::ScalarQuantizer;
 
 pub const SQ_METADATA_KEY: &str = "lance:sq";
 
@@ -41,6 +41,8 @@ pub struct ScalarQuantizationMetadata {
     pub dim: usize,
     pub num_bits: u16,
     pub bounds: Range<f64>,
+    #[serde(default)]
+    pub dim_bounds: Vec<Range<f64>>,
 }
 
 impl DeepSizeOf for ScalarQuantizationMetadata {
@@ -157,6 +159,15 @@ pub struct ScalarQuantizationStorage {
 
     distance_type: DistanceType,
 
+    /// Per-dimension decoding params of the quantizer,
+    /// see [ScalarQuantizer::decode_params].
+    starts: Vec<f32>,
+    steps: Vec<f32>,
+
+    /// The mean of the squared steps, which scales distances between SQ codes
+    /// to the units of the original vectors.
+    code_scale: f32,
+
     /// Chunks of storage
     offsets: Vec<u32>,
     chunks: Vec<SQStorageChunk>,
@@ -178,6 +189,7 @@ impl ScalarQuantizationStorage {
         num_bits: u16,
         distance_type: DistanceType,
         bounds: Range<f64>,
+        dim_bounds: Vec<Range<f64>>,
         batches: impl IntoIterator<Item = RecordBatch>,
     ) -> Result<Self> {
         let mut chunks = Vec::with_capacity(SQ_CHUNK_CAPACITY);
@@ -188,11 +200,17 @@ impl ScalarQuantizationStorage {
             let chunk = SQStorageChunk::new(batch)?;
             chunks.push(chunk);
         }
-        let quantizer = ScalarQuantizer::with_bounds(num_bits, chunks[0].dim(), bounds);
+        let quantizer = ScalarQuantizer::with_bounds(num_bits, chunks[0].dim(), bounds)
+            .with_dim_bounds(dim_bounds);
+        let (starts, steps) = quantizer.decode_params();
+        let code_scale = steps.iter().map(|step| step * step).sum::<f32>() / steps.len() as f32;
 
         Ok(Self {
             quantizer,
             distance_type,
+            starts,
+            steps,
+            code_scale,
             offsets,
             chunks,
         })
@@ -237,6 +255,44 @@ impl ScalarQuantizationStorage {
         Self::load_partition(&reader, 0..reader.len(), distance_type, &metadata).await
     }
 
+    /// Decode a SQ code back to (approximate) float values.
+    fn decode<'a>(&'a self, sq_code: &'a [u8]) -> impl Iterator<Item = f32> + 'a {
+        sq_code
+            .iter()
+            .zip(self.starts.iter().zip(&self.steps))
+            .map(|(&code, (start, step))| start + code as f32 * step)
+    }
+
+    /// Distance between a float query and a vector decoded from SQ codes.
+    ///
+    /// The codes are decoded on the fly, so the distance is in the same units
+    /// as the distance between the original vectors.
+    #[inline]
+    fn query_distance(&self, query: &[f32], sq_code: &[u8]) -> f32 {
+        let values = query.iter().zip(self.decode(sq_code));
+        match self.distance_type {
+            DistanceType::L2 | DistanceType::Cosine => values.map(|(a, b)| (a - b) * (a - b)).sum(),
+            DistanceType::Dot => 1.0 - values.map(|(a, b)| a * b).sum::<f32>(),
+            _ => panic!("We should not reach here: sq distance can only be L2 or Dot"),
+        }
+    }
+
+    /// Distance between two SQ codes, used to build the graph of a sub-index.
+    ///
+    /// The codes are compared directly, which is much faster than decoding them,
+    /// and scaled by [Self::code_scale].  This is exact for L2 when every dimension
+    /// has the same bounds and an approximation with per-dimension bounds.
+    #[inline]
+    fn code_distance(&self, a: &[u8], b: &[u8]) -> f32 {
+        match self.distance_type {
+            DistanceType::L2 | DistanceType::Cosine => {
+                l2_distance_uint_scalar(a, b) * self.code_scale
+            }
+            DistanceType::Dot => dot_distance(a, b),
+            _ => panic!("We should not reach here: sq distance can only be L2 or Dot"),
+        }
+    }
+
     fn optimize(self) -> Result<Self> {
         if self.len() <= SQ_CHUNK_CAPACITY {
             Ok(self)
@@ -277,6 +333,7 @@ impl QuantizerStorage for ScalarQuantizationStorage {
             metadata.num_bits,
             distance_type,
             metadata.bounds.clone(),
+            metadata.dim_bounds.clone(),
             [batch],
         )
     }
@@ -299,7 +356,13 @@ impl VectorStore for ScalarQuantizationStorage {
             })?;
         let metadata: ScalarQuantizationMetadata = serde_json::from_str(metadata_json)?;
 
-        Self::try_new(metadata.num_bits, distance_type, metadata.bounds, [batch])
+        Self::try_new(
+            metadata.num_bits,
+            distance_type,
+            metadata.bounds,
+            metadata.dim_bounds,
+            [batch],
+        )
     }
 
     fn to_batches(&self) -> Result<impl Iterator<Item = RecordBatch>> {
@@ -357,14 +420,13 @@ impl VectorStore for ScalarQuantizationStorage {
     /// Using dist calcualtor can be more efficient as it can pre-compute some
     /// values.
     fn dist_calculator(&self, query: ArrayRef) -> Self::DistanceCalculator<'_> {
-        SQDistCalculator::new(query, self, self.quantizer.bounds.clone())
+        SQDistCalculator::new(query, self)
     }
 
     fn dist_calculator_from_id(&self, id: u32) -> Self::DistanceCalculator<'_> {
         let (offset, chunk) = self.chunk(id);
-        let query_sq_code = chunk.sq_code_slice(id - offset).to_vec();
         SQDistCalculator {
-            query_sq_code,
+            query: SQQuery::Code(chunk.sq_code_slice(id - offset)),
             storage: self,
         }
     }
@@ -372,27 +434,33 @@ impl VectorStore for ScalarQuantizationStorage {
     fn distance_between(&self, a: u32, b: u32) -> f32 {
         let (offset_a, chunk_a) = self.chunk(a);
         let (offset_b, chunk_b) = self.chunk(b);
-        let a_slice = chunk_a.sq_code_slice(a - offset_a);
-        let b_slice = chunk_b.sq_code_slice(b - offset_b);
-        match self.distance_type {
-            DistanceType::L2 | DistanceType::Cosine => l2_distance_uint_scalar(a_slice, b_slice),
-            DistanceType::Dot => dot_distance(a_slice, b_slice),
-            _ => panic!("We should not reach here: sq distance can only be L2 or Dot"),
-        }
+        self.code_distance(
+            chunk_a.sq_code_slice(a - offset_a),
+            chunk_b.sq_code_slice(b - offset_b),
+        )
     }
 }
 
+enum SQQuery<'a> {
+    /// A float query, which is not quantized
+    Vector(Vec<f32>),
+    /// The SQ code of a vector in the storage
+    Code(&'a [u8]),
+}
+
+/// Computes the distances from a query to the SQ codes.
+///
+/// A float query is compared to the decoded codes, without quantizing the query.
+/// A query that is already in the storage is compared code to code.
 pub struct SQDistCalculator<'a> {
-    query_sq_code: Vec<u8>,
+    query: SQQuery<'a>,
     storage: &'a ScalarQuantizationStorage,
 }
 
 impl<'a> SQDistCalculator<'a> {
-    fn new(query: ArrayRef, storage: &'a ScalarQuantizationStorage, bounds: Range<f64>) -> Self {
-        let query_sq_code =
-            scale_to_u8::<Float32Type>(query.as_primitive::<Float32Type>().values(), bounds);
+    fn new(query: ArrayRef, storage: &'a ScalarQuantizationStorage) -> Self {
         Self {
-            query_sq_code,
+            query: SQQuery::Vector(query.as_primitive::<Float32Type>().values().to_vec()),
             storage,
         }
     }
@@ -402,12 +470,9 @@ impl<'a> DistCalculator for SQDistCalculator<'a> {
     fn distance(&self, id: u32) -> f32 {
         let (offset, chunk) = self.storage.chunk(id);
         let sq_code = chunk.sq_code_slice(id - offset);
-        match self.storage.distance_type {
-            DistanceType::L2 | DistanceType::Cosine => {
-                l2_distance_uint_scalar(sq_code, &self.query_sq_code)
-            }
-            DistanceType::Dot => dot_distance(sq_code, &self.query_sq_code),
-            _ => panic!("We should not reach here: sq distance can only be L2 or Dot"),
+        match &self.query {
+            SQQuery::Vector(query) => self.storage.query_distance(query, sq_code),
+            SQQuery::Code(query_code) => self.storage.code_distance(query_code, sq_code),
         }
     }
 
@@ -444,7 +509,10 @@ mod tests {
     use arrow_array::FixedSizeListArray;
     use arrow_schema::{DataType, Field, Schema};
     use lance_arrow::FixedSizeListArrayExt;
+    use lance_linalg::distance::l2_distance;
     use lance_testing::datagen::generate_random_array;
+
+    use crate::vector::quantizer::Quantization;
     use rand::prelude::*;
 
     fn create_record_batch(row_ids: Range<u64>) -> RecordBatch {
@@ -478,6 +546,7 @@ mod tests {
             8,
             DistanceType::L2,
             -0.7..0.7,
+            Vec::new(),
             (0..4).map(|start| create_record_batch(start * 100..(start + 1) * 100)),
         )
         .unwrap();
@@ -520,4 +589,87 @@ mod tests {
         assert_eq!(offset, 400);
         assert_eq!(chunk.row_id(5), 105);
     }
+
+    #[test]
+    fn test_asymmetric_distance() {
+        const DIM: usize = 32;
+        const NUM_ROWS: usize = 100;
+
+        let vectors = FixedSizeListArray::try_new_from_values(
+            generate_random_array(NUM_ROWS * DIM),
+            DIM as i32,
+        )
+        .unwrap();
+        let quantizer =
+            ScalarQuantizer::build(&vectors, DistanceType::L2, &Default::default()).unwrap();
+        let sq_code = quantizer.quantize(&vectors).unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(ROW_ID, DataType::UInt64, false),
+            Field::new(SQ_CODE_COLUMN, sq_code.data_type().clone(), false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(UInt64Array::from_iter_values(0..NUM_ROWS as u64)),
+                sq_code,
+            ],
+        )
+        .unwrap();
+        let storage = ScalarQuantizationStorage::try_new(
+            8,
+            DistanceType::L2,
+            quantizer.bounds(),
+            quantizer.dim_bounds().to_vec(),
+            [batch],
+        )
+        .unwrap();
+
+        // The query is not quantized, so the distances are close to the exact ones.
+        let query = generate_random_array(DIM);
+        let dist_calc = storage.dist_calculator(Arc::new(query.clone()));
+        let values = vectors.values().as_primitive::<Float32Type>().values();
+        for id in 0..NUM_ROWS {
+            let expected = l2_distance(query.values(), &values[id * DIM..(id + 1) * DIM]);
+            let dist = dist_calc.distance(id as u32);
+            assert!(
+                (dist - expected).abs() <= expected * 0.05,
+                "id={}, dist={}, expected={}",
+                id,
+                dist,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_code_distance() {
+        let storage = ScalarQuantizationStorage::try_new(
+            8,
+            DistanceType::L2,
+            -0.7..0.7,
+            Vec::new(),
+            [create_record_batch(0..100)],
+        )
+        .unwrap();
+
+        // With the same bounds for every dimension, the distance between codes is
+        // the distance between the decoded vectors
+        let decode = |id: u32| {
+            storage
+                .decode(storage.chunk(id).1.sq_code_slice(id))
+                .collect::<Vec<_>>()
+        };
+        for (a, b) in [(0, 1), (5, 42), (99, 3)] {
+            let expected = l2_distance(&decode(a), &decode(b));
+            let dist = storage.distance_between(a, b);
+            assert!(
+                (dist - expected).abs() <= expected * 1e-3,
+                "dist={}, expected={}",
+                dist,
+                expected
+            );
+            assert_eq!(storage.dist_calculator_from_id(a).distance(b), dist);
+        }
+    }
 }
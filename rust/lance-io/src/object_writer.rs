@@ -272,6 +272,37 @@ impl ObjectWriter {
             )
         })
     }
+
+    /// Stops the upload without writing the object.
+    ///
+    /// Parts that are still being uploaded are cancelled and a multipart upload that
+    /// was already created is aborted, so the store doesn't keep the parts around.
+    pub async fn abort(&mut self) -> Result<()> {
+        let state = std::mem::replace(&mut self.state, UploadState::Done);
+        let mut upload = match state {
+            UploadState::CreatingUpload(fut) => match fut.await {
+                Ok(upload) => upload,
+                // Nothing was created, so there is nothing to clean up
+                Err(_) => return Ok(()),
+            },
+            UploadState::InProgress {
+                upload,
+                mut futures,
+                ..
+            } => {
+                futures.abort_all();
+                while futures.join_next().await.is_some() {}
+                upload
+            }
+            _ => return Ok(()),
+        };
+        upload.abort().await.map_err(|e| {
+            Error::io(
+                format!("failed to abort the upload of {}: {}", self.path, e),
+                location!(),
+            )
+        })
+    }
 }
 
 impl Drop for ObjectWriter {
@@ -471,4 +502,18 @@ mod tests {
 
         object_writer.shutdown().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_abort() {
+        let store = LanceObjectStore::memory();
+        let path = Path::from("/foo");
+
+        // Both a multipart upload and a small object that was never put
+        for size in [INITIAL_UPLOAD_SIZE * 2 + 10, 256] {
+            let mut object_writer = ObjectWriter::new(&store, &path).await.unwrap();
+            object_writer.write_all(&vec![0; size]).await.unwrap();
+            object_writer.abort().await.unwrap();
+            assert!(!store.exists(&path).await.unwrap());
+        }
+    }
 }
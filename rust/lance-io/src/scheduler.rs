@@ -11,6 +11,7 @@ use std::cmp::Reverse;
 use std::fmt::Debug;
 use std::future::Future;
 use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 use lance_core::{Error, Result};
@@ -88,11 +89,21 @@ struct IoTask {
 
 impl IoTask {
     async fn run(self) {
-        let bytes = self
-            .reader
-            .get_range(self.to_read.start as usize..self.to_read.end as usize)
-            .await
-            .map_err(Error::from);
+        let bytes = match (
+            usize::try_from(self.to_read.start),
+            usize::try_from(self.to_read.end),
+        ) {
+            (Ok(start), Ok(end)) => self.reader.get_range(start..end).await.map_err(Error::from),
+            // File offsets are u64 but readers address the bytes with usize
+            _ => Err(Error::invalid_input(
+                format!(
+                    "cannot read bytes {:?} of {}, which are beyond the address space of this platform",
+                    self.to_read,
+                    self.reader.path()
+                ),
+                location!(),
+            )),
+        };
         (self.when_done)(bytes);
     }
 }
@@ -126,6 +137,39 @@ async fn run_io_loop(
     }
 }
 
+/// Counters of the I/O submitted to a [`ScanScheduler`]
+///
+/// The same counters can be given to many schedulers to get totals across them
+#[derive(Debug, Default)]
+pub struct ScanIoStats {
+    iops: AtomicU64,
+    bytes_read: AtomicU64,
+}
+
+impl ScanIoStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record_request(&self, request: &[Range<u64>]) {
+        self.iops.fetch_add(request.len() as u64, Ordering::Relaxed);
+        self.bytes_read.fetch_add(
+            request.iter().map(|range| range.end - range.start).sum(),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// The number of I/O operations submitted
+    pub fn iops(&self) -> u64 {
+        self.iops.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes requested
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+}
+
 /// An I/O scheduler which wraps an ObjectStore and throttles the amount of
 /// parallel I/O that can be run.
 ///
@@ -134,6 +178,7 @@ pub struct ScanScheduler {
     object_store: Arc<ObjectStore>,
     io_submitter: async_priority_channel::Sender<IoTask, Reverse<u128>>,
     file_counter: Mutex<u32>,
+    io_stats: Option<Arc<ScanIoStats>>,
 }
 
 impl Debug for ScanScheduler {
@@ -153,6 +198,17 @@ impl ScanScheduler {
     /// * object_store - the store to wrap
     /// * io_capacity - the maximum number of parallel requests that will be allowed
     pub fn new(object_store: Arc<ObjectStore>) -> Arc<Self> {
+        Self::new_with_io_stats(object_store, None)
+    }
+
+    /// Create a new scheduler that reports the I/O it submits to `io_stats`
+    ///
+    /// Operations that bypass the scheduler (e.g. getting the size of a file from
+    /// [`FileScheduler::reader`]) are not counted.
+    pub fn new_with_io_stats(
+        object_store: Arc<ObjectStore>,
+        io_stats: Option<Arc<ScanIoStats>>,
+    ) -> Arc<Self> {
         // TODO: we don't have any backpressure in place if the compute thread falls
         // behind.  The scheduler thread will schedule ALL of the I/O and then the
         // loaded data will eventually pile up.
@@ -169,6 +225,7 @@ impl ScanScheduler {
             object_store,
             io_submitter: reg_tx,
             file_counter: Mutex::new(0),
+            io_stats,
         };
         tokio::task::spawn(async move { run_io_loop(reg_rx, io_capacity).await });
         Arc::new(scheduler)
@@ -194,6 +251,9 @@ impl ScanScheduler {
         tx: oneshot::Sender<Result<Vec<Bytes>>>,
         priority: u128,
     ) {
+        if let Some(io_stats) = &self.io_stats {
+            io_stats.record_request(&request);
+        }
         let num_iops = request.len() as u32;
 
         let when_all_io_done = move |bytes| {
@@ -346,6 +406,35 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_io_stats() {
+        let tmpdir = tempdir().unwrap();
+        let tmp_path = Path::parse(tmpdir.path().to_str().unwrap()).unwrap();
+        let obj_store = Arc::new(ObjectStore::local());
+        for name in ["a.file", "b.file"] {
+            obj_store
+                .put(&tmp_path.child(name), &[0; 1000])
+                .await
+                .unwrap();
+        }
+
+        // The stats are shared by both files
+        let io_stats = Arc::new(ScanIoStats::new());
+        let scheduler = ScanScheduler::new_with_io_stats(obj_store, Some(io_stats.clone()));
+        let a = scheduler
+            .open_file(&tmp_path.child("a.file"))
+            .await
+            .unwrap();
+        let b = scheduler
+            .open_file(&tmp_path.child("b.file"))
+            .await
+            .unwrap();
+        a.submit_request(vec![0..10, 100..300], 0).await.unwrap();
+        b.submit_single(500..1000, 0).await.unwrap();
+        assert_eq!(io_stats.iops(), 3);
+        assert_eq!(io_stats.bytes_read(), 710);
+    }
+
     #[tokio::test]
     async fn test_priority() {
         let some_path = Path::parse("foo").unwrap();
@@ -22,9 +22,10 @@ pub const FLAG_UNKNOWN: u64 = 8;
 
 /// Set the reader and writer feature flags in the manifest based on the contents of the manifest.
 pub fn apply_feature_flags(manifest: &mut Manifest, enable_stable_row_id: bool) -> Result<()> {
-    // Reset flags
+    // Reset flags, except for the file format which is chosen by the writer and can't
+    // be derived from the contents of the manifest
     manifest.reader_feature_flags = 0;
-    manifest.writer_feature_flags = 0;
+    manifest.writer_feature_flags &= FLAG_USE_V2_FORMAT;
 
     let has_deletion_files = manifest
         .fragments
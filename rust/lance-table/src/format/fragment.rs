@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::HashMap;
+use std::ops::Range;
+
 use lance_core::Error;
 use lance_file::format::{MAJOR_VERSION, MINOR_VERSION_NEXT};
 use object_store::path::Path;
@@ -8,10 +11,17 @@ use serde::{Deserialize, Serialize};
 use snafu::{location, Location};
 
 use crate::format::pb;
+use crate::rowids::{write_row_ids, RowIdSequence};
 
 use lance_core::datatypes::Schema;
 use lance_core::error::Result;
 
+/// The maximum number of row id ranges recorded for a fragment
+///
+/// Ranges that are close together are merged to stay within the limit, so the ranges
+/// of a fragment with scattered row ids may contain many row ids of other fragments.
+const MAX_ROW_ID_RANGES: usize = 8;
+
 /// Lance Data File
 ///
 /// A data file is one piece of file storing data.
@@ -33,6 +43,12 @@ pub struct DataFile {
     /// The minor version of the file format used to write this file.
     #[serde(default)]
     pub file_minor_version: u32,
+    /// The root of the dataset that owns the file, if it is not this dataset
+    ///
+    /// This is set for the files a shallow clone shares with its source.  The file is
+    /// then at `<base>/data/<path>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
 }
 
 impl DataFile {
@@ -49,6 +65,7 @@ impl DataFile {
             column_indices,
             file_major_version,
             file_minor_version,
+            base: None,
         }
     }
 
@@ -98,6 +115,7 @@ impl From<&DataFile> for pb::DataFile {
             column_indices: df.column_indices.clone(),
             file_major_version: df.file_major_version,
             file_minor_version: df.file_minor_version,
+            base: df.base.clone(),
         }
     }
 }
@@ -112,6 +130,7 @@ impl TryFrom<pb::DataFile> for DataFile {
             column_indices: proto.column_indices,
             file_major_version: proto.file_major_version,
             file_minor_version: proto.file_minor_version,
+            base: proto.base,
         })
     }
 }
@@ -226,6 +245,30 @@ pub struct Fragment {
     /// unknown. This is only optional for legacy reasons. All new tables should
     /// have this set.
     pub physical_rows: Option<usize>,
+
+    /// The ids of the fields (ascending, nulls first) that the rows of this fragment are
+    /// sorted by
+    ///
+    /// Fragments with the same sort order are also sorted relative to each other, in the
+    /// order they appear in the manifest.  Empty if the rows are not known to be sorted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sort_order: Vec<i32>,
+
+    /// Sorted, disjoint ranges of row ids that contain every row id of the fragment
+    ///
+    /// Used to skip fragments that can't contain a row id without loading their row id
+    /// sequence.  Empty if unknown.  Row ids keep their value when compaction moves rows
+    /// and so a fragment holds a few dense runs of row ids, which a handful of ranges
+    /// describe closely (a bloom filter would be larger and still have false positives).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub row_id_ranges: Vec<Range<u64>>,
+
+    /// Arbitrary user-provided key/values describing the fragment (e.g. its provenance)
+    ///
+    /// Preserved when the fragment is modified.  See [`Self::merge_custom_metadata`] for
+    /// what happens when fragments are compacted.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub custom_metadata: HashMap<String, String>,
 }
 
 impl Fragment {
@@ -236,6 +279,9 @@ impl Fragment {
             deletion_file: None,
             row_id_meta: None,
             physical_rows: None,
+            sort_order: vec![],
+            row_id_ranges: vec![],
+            custom_metadata: HashMap::new(),
         }
     }
 
@@ -273,6 +319,9 @@ impl Fragment {
             deletion_file: None,
             physical_rows,
             row_id_meta: None,
+            sort_order: vec![],
+            row_id_ranges: vec![],
+            custom_metadata: HashMap::new(),
         }
     }
 
@@ -296,6 +345,45 @@ impl Fragment {
         self.files.push(DataFile::new_legacy(path, schema));
     }
 
+    /// Set the row ids of the fragment
+    ///
+    /// The sequence is stored inline, along with the ranges of row ids it covers.
+    pub fn set_row_id_sequence(&mut self, sequence: &RowIdSequence) {
+        self.row_id_meta = Some(RowIdMeta::Inline(write_row_ids(sequence)));
+        self.row_id_ranges = sequence.covering_ranges(MAX_ROW_ID_RANGES);
+    }
+
+    /// Returns false if the fragment is known not to contain any of `sorted_row_ids`
+    pub fn may_contain_any_row_id(&self, sorted_row_ids: &[u64]) -> bool {
+        if self.row_id_ranges.is_empty() {
+            return true;
+        }
+        self.row_id_ranges.iter().any(|range| {
+            let idx = sorted_row_ids.partition_point(|row_id| *row_id < range.start);
+            sorted_row_ids
+                .get(idx)
+                .is_some_and(|row_id| *row_id < range.end)
+        })
+    }
+
+    /// The custom metadata of a fragment that replaces (e.g. compacts) `fragments`
+    ///
+    /// Only the key/values that all of the fragments share, with the same value, are kept.
+    /// Any other key is dropped since it no longer describes all of the rows.
+    pub fn merge_custom_metadata<'a>(
+        fragments: impl IntoIterator<Item = &'a Self>,
+    ) -> HashMap<String, String> {
+        let mut fragments = fragments.into_iter();
+        let Some(first) = fragments.next() else {
+            return HashMap::new();
+        };
+        let mut merged = first.custom_metadata.clone();
+        for fragment in fragments {
+            merged.retain(|key, value| fragment.custom_metadata.get(key) == Some(value));
+        }
+        merged
+    }
+
     // True if this fragment is made up of legacy v1 files, false otherwise
     pub fn has_legacy_files(&self) -> bool {
         // If any file in a fragment is legacy then all files in the fragment must be
@@ -322,6 +410,13 @@ impl TryFrom<pb::DataFragment> for Fragment {
             deletion_file: p.deletion_file.map(DeletionFile::try_from).transpose()?,
             row_id_meta: p.row_id_sequence.map(RowIdMeta::try_from).transpose()?,
             physical_rows,
+            sort_order: p.sort_order,
+            row_id_ranges: p
+                .row_id_ranges
+                .into_iter()
+                .map(|range| range.start..range.end)
+                .collect(),
+            custom_metadata: p.custom_metadata,
         })
     }
 }
@@ -358,6 +453,16 @@ impl From<&Fragment> for pb::DataFragment {
             deletion_file,
             row_id_sequence,
             physical_rows: f.physical_rows.unwrap_or_default() as u64,
+            sort_order: f.sort_order.clone(),
+            row_id_ranges: f
+                .row_id_ranges
+                .iter()
+                .map(|range| pb::RowIdRange {
+                    start: range.start,
+                    end: range.end,
+                })
+                .collect(),
+            custom_metadata: f.custom_metadata.clone(),
         }
     }
 }
@@ -420,6 +525,76 @@ mod tests {
         assert_eq!(fragment, fragment2);
     }
 
+    #[test]
+    fn test_merge_custom_metadata() {
+        let with_metadata = |id, pairs: &[(&str, &str)]| {
+            let mut fragment = Fragment::new(id);
+            fragment.custom_metadata = pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            fragment
+        };
+        let fragments = [
+            with_metadata(
+                0,
+                &[("source", "a.csv"), ("job", "1"), ("date", "2024-01-01")],
+            ),
+            with_metadata(
+                1,
+                &[("source", "b.csv"), ("job", "1"), ("date", "2024-01-01")],
+            ),
+            with_metadata(2, &[("job", "1"), ("date", "2024-01-01")]),
+        ];
+        let merged = Fragment::merge_custom_metadata(&fragments);
+        assert_eq!(
+            merged,
+            HashMap::from([
+                ("job".to_string(), "1".to_string()),
+                ("date".to_string(), "2024-01-01".to_string())
+            ])
+        );
+        assert!(Fragment::merge_custom_metadata(&[]).is_empty());
+
+        // The metadata survives a round trip through protobuf
+        let pb_fragment = pb::DataFragment::from(&fragments[0]);
+        let fragment = Fragment::try_from(pb_fragment).unwrap();
+        assert_eq!(fragment.custom_metadata, fragments[0].custom_metadata);
+    }
+
+    #[test]
+    fn test_row_id_ranges() {
+        let mut fragment = Fragment::new(1);
+        // Unknown ranges may contain any row id
+        assert!(fragment.may_contain_any_row_id(&[42]));
+
+        let mut sequence = RowIdSequence::from(100..200);
+        sequence.extend(RowIdSequence::from(300..310));
+        fragment.set_row_id_sequence(&sequence);
+        assert_eq!(fragment.row_id_ranges, vec![100..200, 300..310]);
+        for (row_id, expected) in [
+            (99, false),
+            (100, true),
+            (200, false),
+            (305, true),
+            (310, false),
+        ] {
+            assert_eq!(
+                fragment.may_contain_any_row_id(&[row_id]),
+                expected,
+                "{}",
+                row_id
+            );
+        }
+        assert!(fragment.may_contain_any_row_id(&[5, 250, 309]));
+        assert!(!fragment.may_contain_any_row_id(&[5, 250, 310]));
+
+        let proto = pb::DataFragment::from(&fragment);
+        assert_eq!(Fragment::try_from(proto).unwrap(), fragment);
+        let json = serde_json::to_string(&fragment).unwrap();
+        assert_eq!(Fragment::from_json(&json).unwrap(), fragment);
+    }
+
     #[test]
     fn test_to_json() {
         let mut fragment = Fragment::new(123);
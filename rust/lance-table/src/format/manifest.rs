@@ -14,7 +14,7 @@ use prost::Message;
 use prost_types::Timestamp;
 
 use super::Fragment;
-use crate::feature_flags::FLAG_MOVE_STABLE_ROW_IDS;
+use crate::feature_flags::{FLAG_MOVE_STABLE_ROW_IDS, FLAG_USE_V2_FORMAT};
 use crate::format::pb;
 use lance_core::cache::FileMetadataCache;
 use lance_core::datatypes::Schema;
@@ -126,7 +126,9 @@ impl Manifest {
             timestamp_nanos: 0,  // This will be set on commit
             tag: None,
             reader_feature_flags: 0, // These will be set on commit
-            writer_feature_flags: 0, // These will be set on commit
+            // These will be set on commit, except for the file format which can't be
+            // derived from the contents of the manifest and so is carried over
+            writer_feature_flags: previous.writer_feature_flags & FLAG_USE_V2_FORMAT,
             max_fragment_id: previous.max_fragment_id,
             transaction_file: None,
             fragment_offsets,
@@ -508,6 +510,8 @@ impl SelfDescribingFileReader for FileReader {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use crate::format::DataFile;
 
     use super::*;
@@ -603,6 +607,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
             Fragment {
                 id: 1,
@@ -613,6 +620,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
         ];
 
@@ -314,6 +314,49 @@ pub trait CommitHandler: Debug + Send + Sync {
         object_store: &ObjectStore,
         manifest_writer: ManifestWriter,
     ) -> std::result::Result<(), CommitError>;
+
+    /// Write a small object that isn't a manifest, unless it already exists.
+    ///
+    /// This is used for other files that concurrent writers must not overwrite, such as
+    /// the files of tags.  It should return [CommitError::CommitConflict] if the object
+    /// already exists.  By default a temporary object is written and renamed, which only
+    /// works for object stores that support atomic rename if not exist.
+    async fn put_if_not_exists(
+        &self,
+        path: &Path,
+        data: &[u8],
+        object_store: &ObjectStore,
+    ) -> std::result::Result<(), CommitError> {
+        let tmp_path = tmp_path_for(path);
+        object_store.put(&tmp_path, data).await?;
+        match object_store
+            .inner
+            .rename_if_not_exists(&tmp_path, path)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                // Attempt to clean up temporary object, but ignore errors if we can't
+                let _ = object_store.delete(&tmp_path).await;
+                match e {
+                    ObjectStoreError::AlreadyExists { .. } => Err(CommitError::CommitConflict),
+                    e => Err(CommitError::OtherError(e.into())),
+                }
+            }
+        }
+    }
+}
+
+// Add a .tmp_ prefix and a UUID to the file name, to avoid conflicts
+fn tmp_path_for(path: &Path) -> Path {
+    let mut parts: Vec<_> = path.parts().collect();
+    let new_name = format!(
+        ".tmp_{}_{}",
+        parts.last().unwrap().as_ref(),
+        uuid::Uuid::new_v4().as_hyphenated()
+    );
+    let _ = std::mem::replace(parts.last_mut().unwrap(), new_name.into());
+    parts.into_iter().collect()
 }
 
 /// Adapt an object_store credentials into AWS SDK creds
@@ -547,6 +590,22 @@ impl CommitHandler for UnsafeCommitHandler {
 
         Ok(())
     }
+
+    async fn put_if_not_exists(
+        &self,
+        path: &Path,
+        data: &[u8],
+        object_store: &ObjectStore,
+    ) -> std::result::Result<(), CommitError> {
+        // Like the manifests, this doesn't prevent concurrent writers from both succeeding
+        match object_store.inner.head(path).await {
+            Ok(_) => return Err(CommitError::CommitConflict),
+            Err(ObjectStoreError::NotFound { .. }) => {}
+            Err(e) => return Err(CommitError::OtherError(e.into())),
+        }
+        object_store.put(path, data).await?;
+        Ok(())
+    }
 }
 
 impl Debug for UnsafeCommitHandler {
@@ -641,6 +700,17 @@ impl<T: CommitLock + Send + Sync> CommitHandler for Arc<T> {
             .commit(manifest, indices, base_path, object_store, manifest_writer)
             .await
     }
+
+    async fn put_if_not_exists(
+        &self,
+        path: &Path,
+        data: &[u8],
+        object_store: &ObjectStore,
+    ) -> std::result::Result<(), CommitError> {
+        self.as_ref()
+            .put_if_not_exists(path, data, object_store)
+            .await
+    }
 }
 
 /// A commit implementation that uses a temporary path and renames the object.
@@ -665,17 +735,7 @@ impl CommitHandler for RenameCommitHandler {
             .resolve_version(base_path, manifest.version, &object_store.inner)
             .await?;
 
-        // Add .tmp_ prefix to the path
-        let mut parts: Vec<_> = path.parts().collect();
-        // Add a UUID to the end of the filename to avoid conflicts
-        let uuid = uuid::Uuid::new_v4();
-        let new_name = format!(
-            ".tmp_{}_{}",
-            parts.last().unwrap().as_ref(),
-            uuid.as_hyphenated()
-        );
-        let _ = std::mem::replace(parts.last_mut().unwrap(), new_name.into());
-        let tmp_path: Path = parts.into_iter().collect();
+        let tmp_path = tmp_path_for(&path);
 
         // Write the manifest to the temporary path
         manifest_writer(object_store, manifest, indices, &tmp_path).await?;
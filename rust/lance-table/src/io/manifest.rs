@@ -141,12 +141,11 @@ pub async fn write_manifest(
     for field_id in 0..max_field_id + 1 {
         if let Some(field) = manifest.schema.mut_field_by_id(field_id) {
             if field.data_type().is_dictionary() {
-                let dict_info = field.dictionary.as_mut().ok_or_else(|| {
-                    Error::io(
-                        format!("Lance field {} misses dictionary info", field.name),
-                        location!(),
-                    )
-                })?;
+                // Only legacy files have a dictionary in the schema, v2 files store
+                // dictionaries as their values
+                let Some(dict_info) = field.dictionary.as_mut() else {
+                    continue;
+                };
 
                 let value_arr = dict_info.values.as_ref().ok_or_else(|| {
                     Error::io(
@@ -253,6 +253,36 @@ impl RowIdSequence {
         }
     }
 
+    /// Sorted, disjoint ranges that contain every row id of the sequence
+    ///
+    /// The ranges of the segments are merged, closing the smallest gaps first, until
+    /// there are no more than `max_ranges` ranges.
+    pub fn covering_ranges(&self, max_ranges: usize) -> Vec<Range<u64>> {
+        let mut ranges = self
+            .0
+            .iter()
+            .filter_map(|segment| segment.range())
+            .map(|range| *range.start()..(*range.end() + 1))
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        while merged.len() > max_ranges.max(1) {
+            let idx = (1..merged.len())
+                .min_by_key(|&idx| merged[idx].start - merged[idx - 1].end)
+                .unwrap();
+            let range = merged.remove(idx);
+            merged[idx - 1].end = range.end;
+        }
+        merged
+    }
+
     /// Get the row id at the given index.
     ///
     /// If the index is out of bounds, this will return None.
@@ -484,6 +514,25 @@ mod test {
         assert_eq!(iter.collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
     }
 
+    #[test]
+    fn test_covering_ranges() {
+        let mut sequence = RowIdSequence::from(0..10);
+        sequence.extend(RowIdSequence::from(10..20));
+        assert_eq!(sequence.covering_ranges(8), vec![0..20]);
+
+        let mut sequence = RowIdSequence::from(100..110);
+        sequence.extend(RowIdSequence::from(0..10));
+        sequence.extend(RowIdSequence(vec![U64Segment::Array(
+            vec![60, 50, 55].into(),
+        )]));
+        assert_eq!(sequence.covering_ranges(8), vec![0..10, 50..61, 100..110]);
+        // The smallest gap is closed first
+        assert_eq!(sequence.covering_ranges(2), vec![0..10, 50..110]);
+        assert_eq!(sequence.covering_ranges(1), vec![0..110]);
+
+        assert!(RowIdSequence::from(0..0).covering_ranges(8).is_empty());
+    }
+
     #[test]
     fn test_row_id_sequence_extend() {
         let mut sequence = RowIdSequence::from(0..10);
@@ -275,39 +275,12 @@ impl U64Segment {
                 let start = range.start + offset as u64;
                 Self::Range(start..(start + len as u64))
             }
-            Self::RangeWithHoles { range, holes } => {
-                let start = range.start + offset as u64;
-                let end = start + len as u64;
-
-                let start = holes.binary_search(start).unwrap_or_else(|x| x) as u64;
-                let end = holes.binary_search(end).unwrap_or_else(|x| x) as u64;
-                let holes_len = end - start;
-
-                if holes_len == 0 {
-                    Self::Range(start..end)
-                } else {
-                    let holes = holes.slice(start as usize, holes_len as usize);
-                    Self::RangeWithHoles {
-                        range: start..end,
-                        holes,
-                    }
-                }
-            }
-            Self::RangeWithBitmap { range, bitmap } => {
-                let start = range.start + offset as u64;
-                let end = start + len as u64;
-
-                let bitmap = bitmap.slice(offset, len);
-                if bitmap.count_ones() == len {
-                    // Bitmap no longer serves a purpose
-                    Self::Range(start..end)
-                    // TODO: could also have a case where we switch back to RangeWithHoles
-                } else {
-                    Self::RangeWithBitmap {
-                        range: start..end,
-                        bitmap: bitmap.into(),
-                    }
-                }
+            Self::RangeWithHoles { .. } | Self::RangeWithBitmap { .. } => {
+                // The offset is a position in the segment and, with holes, positions don't
+                // map directly to values
+                let values = || self.iter().skip(offset).take(len);
+                let stats = Self::compute_stats(values());
+                Self::from_stats_and_sequence(stats, values())
             }
             Self::SortedArray(array) => Self::SortedArray(array.slice(offset, len)),
             Self::Array(array) => Self::Array(array.slice(offset, len)),
@@ -500,4 +473,24 @@ mod test {
             &U64Segment::Array(vec![7000, 1, 24000].into()),
         );
     }
+
+    #[test]
+    fn test_segment_slice() {
+        let with_holes = (0..1000).filter(|&x| x % 100 != 0).collect::<Vec<_>>();
+        let with_bitmap = (0..1000).filter(|&x| x % 2 == 0).collect::<Vec<_>>();
+        let sorted = vec![1, 7000, 24000, 30000];
+        let unsorted = vec![7000, 1, 24000, 3];
+        for values in [with_holes, with_bitmap, sorted, unsorted] {
+            let segment = U64Segment::from_slice(&values);
+            for (offset, len) in [(0, values.len()), (0, 2), (1, 2), (2, values.len() - 2)] {
+                // Offsets are positions and not values
+                let slice = segment.slice(offset, len);
+                assert_eq!(slice.len(), len);
+                assert_eq!(
+                    slice.iter().collect::<Vec<_>>(),
+                    values[offset..offset + len].to_vec()
+                );
+            }
+        }
+    }
 }
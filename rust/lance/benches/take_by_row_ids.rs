@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Benchmark of looking up the addresses of stable row ids in a 1B row dataset
+//!
+//! Only the metadata of the dataset (10,000 fragments of 100,000 rows, as they look after
+//! compaction) is generated, in memory.  A lookup either loads the row id sequences of
+//! every fragment, like [`lance::dataset::Dataset::take_rows`], or only of the fragments
+//! whose row id ranges contain one of the row ids, like
+//! [`lance::dataset::Dataset::take_by_row_ids`].
+//!
+//! Run benchmark.
+//! ```
+//! cargo bench --bench take_by_row_ids
+//! ```
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use lance_table::format::{Fragment, RowIdMeta};
+use lance_table::rowids::{read_row_ids, RowIdIndex, RowIdSequence};
+#[cfg(target_os = "linux")]
+use pprof::criterion::{Output, PProfProfiler};
+
+const NUM_FRAGMENTS: u64 = 10_000;
+const ROWS_PER_FRAGMENT: u64 = 100_000;
+
+// Each fragment has the rows of two earlier fragments, some of which were deleted
+fn compacted_fragments() -> Vec<Fragment> {
+    (0..NUM_FRAGMENTS)
+        .map(|id| {
+            let start = id * ROWS_PER_FRAGMENT;
+            let middle = start + ROWS_PER_FRAGMENT / 2;
+            let mut sequence = RowIdSequence::from(start..middle - 10);
+            sequence.extend(RowIdSequence::from(middle..start + ROWS_PER_FRAGMENT - 10));
+            let mut fragment = Fragment::new(id);
+            fragment.set_row_id_sequence(&sequence);
+            fragment
+        })
+        .collect()
+}
+
+fn load_row_id_sequences<'a>(
+    fragments: impl Iterator<Item = &'a Fragment>,
+) -> Vec<(u32, Arc<RowIdSequence>)> {
+    fragments
+        .map(|fragment| match &fragment.row_id_meta {
+            Some(RowIdMeta::Inline(data)) => {
+                (fragment.id as u32, Arc::new(read_row_ids(data).unwrap()))
+            }
+            _ => unreachable!("the row ids are stored inline"),
+        })
+        .collect()
+}
+
+fn bench_row_id_lookup(c: &mut Criterion) {
+    let fragments = compacted_fragments();
+    let row_ids = [987_654_321, 12_345_678];
+    let mut sorted_row_ids = row_ids.to_vec();
+    sorted_row_ids.sort_unstable();
+    let candidates = || {
+        fragments
+            .iter()
+            .filter(|fragment| fragment.may_contain_any_row_id(&sorted_row_ids))
+    };
+    println!(
+        "Row id sequences loaded per lookup: {} with row id ranges, {} without",
+        candidates().count(),
+        fragments.len()
+    );
+
+    let mut group = c.benchmark_group("Row id lookup in 1B rows");
+    group.bench_function("All fragments", |b| {
+        b.iter(|| {
+            let index = RowIdIndex::new(&load_row_id_sequences(fragments.iter())).unwrap();
+            let addresses = row_ids.map(|row_id| index.get(row_id));
+            assert!(addresses.iter().all(Option::is_some));
+        })
+    });
+    group.bench_function("Row id ranges", |b| {
+        b.iter(|| {
+            let index = RowIdIndex::new(&load_row_id_sequences(candidates())).unwrap();
+            let addresses = row_ids.map(|row_id| index.get(row_id));
+            assert!(addresses.iter().all(Option::is_some));
+        })
+    });
+    group.finish();
+}
+
+#[cfg(target_os = "linux")]
+criterion_group!(
+    name=benches;
+    config = Criterion::default().significance_level(0.1).sample_size(10)
+        .with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench_row_id_lookup);
+#[cfg(not(target_os = "linux"))]
+criterion_group!(
+    name=benches;
+    config = Criterion::default().significance_level(0.1).sample_size(10);
+    targets = bench_row_id_lookup);
+criterion_main!(benches);
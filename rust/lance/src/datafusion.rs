@@ -4,6 +4,6 @@
 //! Extends DataFusion
 //!
 
-pub(crate) mod dataframe;
+pub mod dataframe;
 pub(crate) mod logical_expr;
 pub(crate) mod logical_plan;
@@ -3,12 +3,19 @@
 
 use std::{
     any::Any,
+    cmp::Ordering,
     sync::{Arc, Mutex},
 };
 
+use arrow_array::{cast::AsArray, types::Int64Type, Array};
 use arrow_schema::{DataType, Field, Schema, SchemaRef};
 use async_trait::async_trait;
 use datafusion::{
+    common::{
+        stats::Precision,
+        tree_node::{TreeNode, TreeNodeRecursion},
+        ColumnStatistics, ScalarValue, Statistics,
+    },
     dataframe::DataFrame,
     datasource::{streaming::StreamingTable, TableProvider},
     error::DataFusionError,
@@ -16,21 +23,38 @@ use datafusion::{
         context::{SessionContext, SessionState},
         TaskContext,
     },
-    logical_expr::{Expr, TableProviderFilterPushDown, TableType},
-    physical_plan::{streaming::PartitionStream, ExecutionPlan, SendableRecordBatchStream},
+    logical_expr::{
+        expr_rewriter::unnormalize_col,
+        utils::{conjunction, split_conjunction},
+        Expr, TableProviderFilterPushDown, TableType, Volatility,
+    },
+    physical_expr::{expressions::Column, PhysicalExpr},
+    physical_plan::{
+        projection::ProjectionExec, streaming::PartitionStream, ExecutionPlan,
+        SendableRecordBatchStream,
+    },
 };
-use lance_core::ROW_ID;
+use futures::{StreamExt, TryStreamExt};
+use lance_core::{Result, ROW_ID};
 
 use crate::Dataset;
 
+/// A DataFusion [`TableProvider`] for a Lance dataset
+///
+/// Projections, filters and limits are pushed down into the Lance scanner.  Filters
+/// that Lance can evaluate are applied by the scan (and can use scalar indices and page
+/// statistics), the rest are left to DataFusion.  The row id can optionally be exposed
+/// as a `_rowid` column.
 pub struct LanceTableProvider {
     dataset: Arc<Dataset>,
     full_schema: Arc<Schema>,
     row_id_idx: Option<usize>,
+    column_statistics: Option<Vec<ColumnStatistics>>,
 }
 
 impl LanceTableProvider {
-    fn new(dataset: Arc<Dataset>, with_row_id: bool) -> Self {
+    /// Creates a provider for `dataset`, with a trailing `_rowid` column if `with_row_id`
+    pub fn new(dataset: Arc<Dataset>, with_row_id: bool) -> Self {
         let full_schema = if with_row_id {
             let mut full_schema = dataset.schema().clone();
             full_schema
@@ -48,8 +72,146 @@ impl LanceTableProvider {
             } else {
                 None
             },
+            column_statistics: None,
         }
     }
+
+    /// Loads the column statistics (min, max and null count) that are reported by
+    /// [`TableProvider::statistics`]
+    ///
+    /// The statistics are read from the page statistics of the data files.  Columns
+    /// without statistics (and all columns, if any file does not have page statistics)
+    /// are reported as unknown.  The bounds of string columns are truncated and deleted
+    /// rows are still counted and so all column statistics are inexact.
+    pub async fn with_column_statistics(mut self) -> Result<Self> {
+        self.column_statistics = Some(load_column_statistics(&self.dataset).await?);
+        Ok(self)
+    }
+
+    // The part of `filter` that can be pushed down into the scanner, and whether
+    // that is all of it
+    fn pushdown_filter(&self, filter: &Expr) -> (Option<Expr>, bool) {
+        let conjuncts = split_conjunction(filter);
+        let num_conjuncts = conjuncts.len();
+        let supported = conjuncts
+            .into_iter()
+            .filter(|expr| self.can_push_down(expr))
+            .cloned()
+            .collect::<Vec<_>>();
+        let exact = supported.len() == num_conjuncts;
+        (conjunction(supported), exact)
+    }
+
+    // Whether the scanner can evaluate `expr`
+    fn can_push_down(&self, expr: &Expr) -> bool {
+        let mut supported = true;
+        expr.apply(|expr| {
+            supported = match expr {
+                // The row id is not part of the dataset schema that the scanner
+                // evaluates filters against
+                Expr::Column(column) => {
+                    column.name != ROW_ID && self.dataset.schema().field(&column.name).is_some()
+                }
+                Expr::ScalarFunction(function) => {
+                    function.func.signature().volatility != Volatility::Volatile
+                }
+                Expr::AggregateFunction(_)
+                | Expr::WindowFunction(_)
+                | Expr::Exists(_)
+                | Expr::InSubquery(_)
+                | Expr::ScalarSubquery(_)
+                | Expr::Placeholder(_)
+                | Expr::OuterReferenceColumn(_, _)
+                | Expr::ScalarVariable(_, _)
+                | Expr::Wildcard { .. }
+                | Expr::GroupingSet(_)
+                | Expr::Unnest(_) => false,
+                _ => true,
+            };
+            Ok(if supported {
+                TreeNodeRecursion::Continue
+            } else {
+                TreeNodeRecursion::Stop
+            })
+        })
+        .unwrap();
+        supported
+    }
+}
+
+async fn load_column_statistics(dataset: &Dataset) -> Result<Vec<ColumnStatistics>> {
+    let fields = &dataset.schema().fields;
+    // (min, max, null count) of each column, or None if a file has no statistics for it
+    let mut bounds = vec![Some((None, None, 0)); fields.len()];
+    let fragments = dataset.get_fragments();
+    let has_page_stats = fragments.iter().all(|fragment| {
+        fragment
+            .metadata()
+            .files
+            .iter()
+            .all(|file| file.is_legacy_file())
+    });
+    if !has_page_stats {
+        bounds = vec![None; fields.len()];
+    }
+    let mut page_stats = futures::stream::iter(fragments.into_iter().filter(|_| has_page_stats))
+        .map(|fragment| async move {
+            let reader = fragment.open(dataset.schema(), false, false, None).await?;
+            reader.legacy_read_page_stats(None).await
+        })
+        .buffered(dataset.object_store.io_parallelism()? as usize);
+    while let Some(stats) = page_stats.try_next().await? {
+        for (field, bounds) in fields.iter().zip(bounds.iter_mut()) {
+            let field_stats = stats
+                .as_ref()
+                .and_then(|stats| stats.column_by_name(&field.id.to_string()))
+                .map(|field_stats| field_stats.as_struct());
+            let (Some(field_stats), Some((min, max, null_count))) = (field_stats, bounds.as_mut())
+            else {
+                *bounds = None;
+                continue;
+            };
+            if let Some(null_counts) = field_stats.column_by_name("null_count") {
+                *null_count += null_counts
+                    .as_primitive::<Int64Type>()
+                    .iter()
+                    .map(|count| count.unwrap_or_default() as usize)
+                    .sum::<usize>();
+            }
+            for (name, bound, keep) in [
+                ("min_value", min, Ordering::Less),
+                ("max_value", max, Ordering::Greater),
+            ] {
+                let Some(values) = field_stats.column_by_name(name) else {
+                    continue;
+                };
+                for idx in 0..values.len() {
+                    if values.is_null(idx) {
+                        continue;
+                    }
+                    let value = ScalarValue::try_from_array(values, idx)?;
+                    if bound
+                        .as_ref()
+                        .map_or(true, |bound| value.partial_cmp(bound) == Some(keep))
+                    {
+                        *bound = Some(value);
+                    }
+                }
+            }
+        }
+    }
+    Ok(bounds
+        .into_iter()
+        .map(|bounds| match bounds {
+            Some((min, max, null_count)) => ColumnStatistics {
+                null_count: Precision::Inexact(null_count),
+                max_value: max.map_or(Precision::Absent, Precision::Inexact),
+                min_value: min.map_or(Precision::Absent, Precision::Inexact),
+                distinct_count: Precision::Absent,
+            },
+            None => ColumnStatistics::new_unknown(),
+        })
+        .collect())
 }
 
 #[async_trait]
@@ -74,54 +236,116 @@ impl TableProvider for LanceTableProvider {
         limit: Option<usize>,
     ) -> datafusion::common::Result<Arc<dyn ExecutionPlan>> {
         let mut scan = self.dataset.scan();
-        if let Some(projection) = projection {
-            let mut columns = Vec::with_capacity(projection.len());
-            for field_idx in projection {
-                if Some(*field_idx) == self.row_id_idx {
-                    scan.with_row_id();
-                } else {
-                    columns.push(self.full_schema.field(*field_idx).name());
-                }
-            }
-            if !columns.is_empty() {
-                scan.project(&columns)?;
-            }
+        let projection = projection
+            .cloned()
+            .unwrap_or_else(|| (0..self.full_schema.fields().len()).collect());
+        let row_id_position = self
+            .row_id_idx
+            .and_then(|row_id_idx| projection.iter().position(|idx| *idx == row_id_idx));
+        if row_id_position.is_some() {
+            scan.with_row_id();
         }
-        let combined_filter = match filters.len() {
-            0 => None,
-            1 => Some(filters[0].clone()),
-            _ => {
-                let mut expr = filters[0].clone();
-                for filter in &filters[1..] {
-                    expr = Expr::and(expr, filter.clone());
-                }
-                Some(expr)
-            }
-        };
-        if let Some(combined_filter) = combined_filter {
-            scan.filter_expr(combined_filter);
+        let columns = projection
+            .iter()
+            .filter(|idx| Some(**idx) != self.row_id_idx)
+            .map(|idx| self.full_schema.field(*idx).name())
+            .collect::<Vec<_>>();
+        // DataFusion asks for no columns at all when only the number of rows is needed
+        // (e.g. `count(*)`).  The scanner needs something to read so the row id is
+        // scanned and then projected away, leaving batches that only carry a row count.
+        let count_only = columns.is_empty() && row_id_position.is_none();
+        if count_only {
+            scan.with_row_id();
+        }
+        scan.project(&columns)?;
+
+        let mut exact = true;
+        let mut pushed_down = Vec::with_capacity(filters.len());
+        for filter in filters {
+            let (filter, is_exact) = self.pushdown_filter(&unnormalize_col(filter.clone()));
+            exact &= is_exact;
+            pushed_down.extend(filter);
+        }
+        if let Some(filter) = conjunction(pushed_down) {
+            scan.filter_expr(filter);
+        }
+        // DataFusion only passes a limit if all the filters are applied exactly, but
+        // check anyway since an inexact filter would make the limit drop rows
+        if exact {
+            scan.limit(limit.map(|l| l as i64), None)?;
         }
-        scan.limit(limit.map(|l| l as i64), None)?;
 
-        scan.create_plan().await.map_err(DataFusionError::from)
+        let plan = scan.create_plan().await?;
+        if count_only {
+            return Ok(Arc::new(ProjectionExec::try_new(vec![], plan)?));
+        }
+        match row_id_position {
+            // The scanner returns the row id as the last column
+            Some(position) if position != columns.len() => {
+                let schema = plan.schema();
+                let mut exprs = schema
+                    .fields()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, field)| {
+                        (
+                            Arc::new(Column::new(field.name(), idx)) as Arc<dyn PhysicalExpr>,
+                            field.name().clone(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let row_id = exprs.pop().unwrap();
+                exprs.insert(position, row_id);
+                Ok(Arc::new(ProjectionExec::try_new(exprs, plan)?))
+            }
+            _ => Ok(plan),
+        }
     }
 
-    // Since we are using datafusion itself to apply the filters it should
-    // be safe to assume that we can exactly apply any of the given pushdown
-    // filters.
     fn supports_filters_pushdown(
         &self,
         filters: &[&Expr],
     ) -> datafusion::common::Result<Vec<TableProviderFilterPushDown>> {
         Ok(filters
             .iter()
-            .map(|_| TableProviderFilterPushDown::Exact)
+            .map(
+                |filter| match self.pushdown_filter(&unnormalize_col((*filter).clone())) {
+                    (None, _) => TableProviderFilterPushDown::Unsupported,
+                    (Some(_), true) => TableProviderFilterPushDown::Exact,
+                    (Some(_), false) => TableProviderFilterPushDown::Inexact,
+                },
+            )
             .collect())
     }
+
+    fn statistics(&self) -> Option<Statistics> {
+        // Some fragments from older datasets might have the row count missing
+        let num_rows = self
+            .dataset
+            .get_fragments()
+            .iter()
+            .map(|fragment| fragment.metadata().num_rows())
+            .sum::<Option<usize>>()
+            .map_or(Precision::Absent, Precision::Exact);
+        let mut column_statistics = self
+            .column_statistics
+            .clone()
+            .unwrap_or_else(|| Statistics::unknown_column(&Schema::from(self.dataset.schema())));
+        if self.row_id_idx.is_some() {
+            column_statistics.push(ColumnStatistics::new_unknown());
+        }
+        Some(Statistics {
+            num_rows,
+            total_byte_size: Precision::Absent,
+            column_statistics,
+        })
+    }
 }
 
 pub trait SessionContextExt {
     /// Creates a DataFrame for reading a Lance dataset
+    ///
+    /// See [`LanceTableProvider`]
     fn read_lance(
         &self,
         dataset: Arc<Dataset>,
@@ -183,3 +407,356 @@ impl SessionContextExt for SessionContext {
         self.read_table(Arc::new(provider))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{
+        cast::AsArray,
+        types::{Float64Type, Int64Type, UInt64Type},
+        ArrayRef, Date32Array, Float64Array, Int64Array, RecordBatch, RecordBatchIterator,
+        StringArray, StructArray,
+    };
+    use arrow_schema::{Field as ArrowField, Fields, Schema as ArrowSchema};
+    use datafusion::physical_plan::collect;
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use crate::dataset::{scanner::ScanStatistics, WriteParams};
+
+    const NUM_ROWS: i64 = 4000;
+
+    // A TPC-H like lineitem table, sorted by ship date
+    async fn lineitem(use_legacy_format: bool) -> (tempfile::TempDir, Arc<Dataset>, RecordBatch) {
+        let ship_info = Fields::from(vec![
+            ArrowField::new("mode", DataType::Utf8, false),
+            ArrowField::new("instruct", DataType::Utf8, false),
+        ]);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("l_orderkey", DataType::Int64, false),
+            ArrowField::new("l_quantity", DataType::Float64, false),
+            ArrowField::new("l_extendedprice", DataType::Float64, false),
+            ArrowField::new("l_discount", DataType::Float64, false),
+            ArrowField::new("l_returnflag", DataType::Utf8, false),
+            // Days since the epoch, from 1992-01-01 to 1998-12-01
+            ArrowField::new("l_shipdate", DataType::Date32, false),
+            ArrowField::new("l_shipinfo", DataType::Struct(ship_info.clone()), false),
+        ]));
+        let modes = ["AIR", "MAIL", "RAIL", "SHIP", "TRUCK"];
+        let flags = ["A", "N", "R"];
+        let ship_info = StructArray::new(
+            ship_info,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    (0..NUM_ROWS).map(|i| modes[(i % 5) as usize]),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    (0..NUM_ROWS).map(|i| format!("instruction {}", i % 4)),
+                )),
+            ],
+            None,
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..NUM_ROWS)),
+                Arc::new(Float64Array::from_iter_values(
+                    (0..NUM_ROWS).map(|i| (i % 50 + 1) as f64),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    (0..NUM_ROWS).map(|i| (i * 7 % 1000) as f64 + 900.0),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    (0..NUM_ROWS).map(|i| (i % 11) as f64 / 100.0),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..NUM_ROWS).map(|i| flags[(i % 3) as usize]),
+                )),
+                Arc::new(Date32Array::from_iter_values(
+                    (0..NUM_ROWS).map(|i| 8035 + (i * 2526 / NUM_ROWS) as i32),
+                )),
+                Arc::new(ship_info),
+            ],
+        )
+        .unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 1000,
+            max_rows_per_group: 100,
+            use_legacy_format,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema);
+        let dataset = Dataset::write(
+            reader,
+            test_dir.path().to_str().unwrap(),
+            Some(write_params),
+        )
+        .await
+        .unwrap();
+        (test_dir, Arc::new(dataset), batch)
+    }
+
+    // Runs `sql` and returns the results, the optimized logical plan and the work done
+    // by the scan
+    async fn run(ctx: &SessionContext, sql: &str) -> (Vec<RecordBatch>, String, ScanStatistics) {
+        let df = ctx.sql(sql).await.unwrap();
+        let logical_plan = df
+            .clone()
+            .into_optimized_plan()
+            .unwrap()
+            .display_indent()
+            .to_string();
+        let plan = df.create_physical_plan().await.unwrap();
+        let batches = collect(plan.clone(), ctx.task_ctx()).await.unwrap();
+        let stats = ScanStatistics::from_plan(plan.as_ref());
+        (batches, logical_plan, stats)
+    }
+
+    fn context(dataset: Arc<Dataset>, with_row_id: bool) -> SessionContext {
+        let ctx = SessionContext::new();
+        ctx.register_table(
+            "lineitem",
+            Arc::new(LanceTableProvider::new(dataset, with_row_id)),
+        )
+        .unwrap();
+        ctx
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_filter_and_projection_pushdown(#[values(false, true)] use_legacy_format: bool) {
+        let (_test_dir, dataset, data) = lineitem(use_legacy_format).await;
+        let ctx = context(dataset, false);
+
+        // TPC-H Q6
+        let (batches, plan, stats) = run(
+            &ctx,
+            "SELECT sum(l_extendedprice * l_discount) AS revenue
+             FROM lineitem
+             WHERE l_shipdate >= DATE '1994-01-01'
+               AND l_shipdate < DATE '1995-01-01'
+               AND l_discount BETWEEN 0.05 AND 0.07
+               AND l_quantity < 24",
+        )
+        .await;
+        let expected = (0..data.num_rows())
+            .filter(|&i| {
+                let shipdate = data["l_shipdate"].as_primitive::<arrow_array::types::Date32Type>();
+                let discount = data["l_discount"].as_primitive::<Float64Type>().value(i);
+                // 1994-01-01 and 1995-01-01
+                (8766..9131).contains(&shipdate.value(i))
+                    && (0.05..=0.07).contains(&discount)
+                    && data["l_quantity"].as_primitive::<Float64Type>().value(i) < 24.0
+            })
+            .map(|i| {
+                data["l_extendedprice"]
+                    .as_primitive::<Float64Type>()
+                    .value(i)
+                    * data["l_discount"].as_primitive::<Float64Type>().value(i)
+            })
+            .sum::<f64>();
+        let revenue = batches[0]["revenue"].as_primitive::<Float64Type>().value(0);
+        assert!((revenue - expected).abs() < 1e-6 * expected);
+
+        // The filter is applied by the scan.  Legacy files have page statistics and so
+        // the pages of other years are skipped.
+        assert!(!plan.contains("Filter:"), "{}", plan);
+        if use_legacy_format {
+            assert!(stats.pages_skipped > 0);
+        } else {
+            // Only the columns of the query are decoded (the I/O of legacy files is not
+            // reported)
+            let (_, _, full_scan) = run(&ctx, "SELECT * FROM lineitem").await;
+            assert!(stats.bytes_decoded > 0);
+            assert!(stats.bytes_decoded < full_scan.bytes_decoded / 2);
+        }
+
+        // TPC-H Q1 (abridged)
+        let (batches, plan, _) = run(
+            &ctx,
+            "SELECT l_returnflag, sum(l_quantity) AS sum_qty, count(*) AS count_order
+             FROM lineitem
+             WHERE l_shipdate <= DATE '1998-09-02'
+             GROUP BY l_returnflag
+             ORDER BY l_returnflag",
+        )
+        .await;
+        assert!(!plan.contains("Filter:"), "{}", plan);
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch["l_returnflag"].as_ref(),
+            &StringArray::from(vec!["A", "N", "R"])
+        );
+        let shipdates = data["l_shipdate"].as_primitive::<arrow_array::types::Date32Type>();
+        let counts = (0..3)
+            .map(|flag| {
+                (0..NUM_ROWS)
+                    .filter(|i| i % 3 == flag && shipdates.value(*i as usize) <= 10471)
+                    .count() as i64
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            batch["count_order"].as_primitive::<Int64Type>().values(),
+            counts.as_slice()
+        );
+
+        // A volatile filter can't be pushed down
+        let (batches, plan, _) = run(
+            &ctx,
+            "SELECT l_orderkey FROM lineitem WHERE l_orderkey < 10 AND random() < 2.0",
+        )
+        .await;
+        assert!(plan.contains("Filter:"), "{}", plan);
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_limit_pushdown() {
+        let (_test_dir, dataset, _) = lineitem(false).await;
+        let ctx = context(dataset, false);
+
+        let (_, _, full_scan) = run(&ctx, "SELECT l_orderkey FROM lineitem").await;
+        let (batches, _, stats) = run(&ctx, "SELECT l_orderkey FROM lineitem LIMIT 10").await;
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch["l_orderkey"].as_ref(),
+            &Int64Array::from_iter_values(0..10)
+        );
+        // Only the pages with the first rows are decoded
+        assert!(stats.bytes_decoded < full_scan.bytes_decoded / 2);
+
+        // The limit is applied after the filter
+        let (batches, _, _) = run(
+            &ctx,
+            "SELECT l_orderkey FROM lineitem WHERE l_orderkey >= 2500 LIMIT 10",
+        )
+        .await;
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch["l_orderkey"].as_ref(),
+            &Int64Array::from_iter_values(2500..2510)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_count_star() {
+        let (_test_dir, dataset, data) = lineitem(false).await;
+        let ctx = context(dataset, false);
+
+        let count =
+            |batches: Vec<RecordBatch>| batches[0]["count"].as_primitive::<Int64Type>().value(0);
+        let (batches, _, _) = run(&ctx, "SELECT count(*) AS count FROM lineitem").await;
+        assert_eq!(count(batches), NUM_ROWS);
+
+        // The filter is pushed down so the scan is asked for no columns at all
+        let (batches, plan, _) = run(
+            &ctx,
+            "SELECT count(*) AS count FROM lineitem WHERE l_orderkey < 100",
+        )
+        .await;
+        assert!(!plan.contains("Filter:"), "{}", plan);
+        assert_eq!(count(batches), 100);
+
+        let (batches, _, _) = run(
+            &ctx,
+            "SELECT count(*) AS count FROM lineitem WHERE l_returnflag = 'A'",
+        )
+        .await;
+        let expected = data["l_returnflag"]
+            .as_string::<i32>()
+            .iter()
+            .filter(|flag| *flag == Some("A"))
+            .count();
+        assert_eq!(count(batches), expected as i64);
+    }
+
+    #[tokio::test]
+    async fn test_row_id_and_nested_fields() {
+        let (_test_dir, dataset, _) = lineitem(true).await;
+        let provider = LanceTableProvider::new(dataset.clone(), true);
+        let filters = [
+            datafusion::prelude::col("l_orderkey").lt(datafusion::prelude::lit(10_i64)),
+            datafusion::prelude::col(ROW_ID).lt(datafusion::prelude::lit(10_u64)),
+            datafusion::prelude::col("l_orderkey")
+                .lt(datafusion::prelude::lit(10_i64))
+                .and(datafusion::prelude::col(ROW_ID).lt(datafusion::prelude::lit(10_u64))),
+        ];
+        assert_eq!(
+            provider
+                .supports_filters_pushdown(&filters.iter().collect::<Vec<_>>())
+                .unwrap(),
+            vec![
+                TableProviderFilterPushDown::Exact,
+                TableProviderFilterPushDown::Unsupported,
+                TableProviderFilterPushDown::Inexact,
+            ]
+        );
+
+        let ctx = context(dataset, true);
+        let (batches, plan, _) = run(
+            &ctx,
+            "SELECT l_shipinfo['mode'] AS mode, _rowid
+             FROM lineitem
+             WHERE l_shipinfo['mode'] = 'AIR' AND _rowid < 2000",
+        )
+        .await;
+        // Only the row id filter is left to DataFusion
+        assert!(plan.contains("Filter: lineitem._rowid <"), "{}", plan);
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 200);
+        assert!(batch["mode"]
+            .as_string::<i32>()
+            .iter()
+            .all(|mode| mode == Some("AIR")));
+        // The first file has the first 1000 rows
+        let row_ids = batch[ROW_ID].as_primitive::<UInt64Type>();
+        assert_eq!(row_ids.values()[..3], [0, 5, 10]);
+        assert!(row_ids.values().iter().all(|row_id| row_id < &2000));
+    }
+
+    #[tokio::test]
+    async fn test_statistics() {
+        let (_test_dir, dataset, _) = lineitem(true).await;
+        let provider = LanceTableProvider::new(dataset.clone(), true);
+        let stats = provider.statistics().unwrap();
+        assert_eq!(stats.num_rows, Precision::Exact(NUM_ROWS as usize));
+        assert_eq!(stats.column_statistics.len(), 8);
+        assert_eq!(stats.column_statistics[0], ColumnStatistics::new_unknown());
+
+        let provider = provider.with_column_statistics().await.unwrap();
+        let stats = provider.statistics().unwrap();
+        let orderkey = &stats.column_statistics[0];
+        assert_eq!(
+            orderkey.min_value,
+            Precision::Inexact(ScalarValue::Int64(Some(0)))
+        );
+        assert_eq!(
+            orderkey.max_value,
+            Precision::Inexact(ScalarValue::Int64(Some(NUM_ROWS - 1)))
+        );
+        assert_eq!(orderkey.null_count, Precision::Inexact(0));
+        let returnflag = &stats.column_statistics[4];
+        assert_eq!(
+            returnflag.min_value,
+            Precision::Inexact(ScalarValue::Utf8(Some("A".to_string())))
+        );
+        assert_eq!(
+            returnflag.max_value,
+            Precision::Inexact(ScalarValue::Utf8(Some("R".to_string())))
+        );
+        // The row id column has no statistics
+        assert_eq!(stats.column_statistics[7], ColumnStatistics::new_unknown());
+
+        // Deleted rows are not counted
+        let mut dataset = dataset.as_ref().clone();
+        dataset.delete("l_orderkey < 100").await.unwrap();
+        let provider = LanceTableProvider::new(Arc::new(dataset), false);
+        assert_eq!(
+            provider.statistics().unwrap().num_rows,
+            Precision::Exact(NUM_ROWS as usize - 100)
+        );
+    }
+}
@@ -5,22 +5,27 @@
 //!
 
 use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_schema::Schema as ArrowSchema;
 use byteorder::{ByteOrder, LittleEndian};
 use chrono::{prelude::*, Duration};
+use datafusion::physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream};
 use deepsize::DeepSizeOf;
 use futures::future::BoxFuture;
 use futures::stream::{self, StreamExt, TryStreamExt};
 use futures::{FutureExt, Stream};
 use lance_core::{datatypes::SchemaCompareOptions, traits::DatasetTakeRows};
+use lance_core::{ROW_ADDR_FIELD, ROW_ID_FIELD};
 use lance_datafusion::utils::{peek_reader_schema, reader_to_stream};
 use lance_file::datatypes::populate_schema_dictionary;
 use lance_io::object_store::{ObjectStore, ObjectStoreParams};
 use lance_io::object_writer::ObjectWriter;
 use lance_io::traits::WriteExt;
 use lance_io::utils::{read_last_block, read_metadata_offset, read_struct};
-use lance_table::format::{Fragment, Index, Manifest, MAGIC, MAJOR_VERSION, MINOR_VERSION};
+use lance_table::format::{
+    DataFile, Fragment, Index, Manifest, MAGIC, MAJOR_VERSION, MINOR_VERSION,
+};
 use lance_table::io::commit::{
-    commit_handler_from_url, CommitError, CommitHandler, CommitLock, ManifestLocation,
+    commit_handler_from_url, CommitConfig, CommitError, CommitHandler, CommitLock, ManifestLocation,
 };
 use lance_table::io::manifest::{read_manifest, write_manifest};
 use log::warn;
@@ -35,22 +40,27 @@ use tracing::instrument;
 
 pub mod builder;
 pub mod cleanup;
+mod clone;
+mod diff;
 pub mod fragment;
 mod hash_joiner;
 pub mod index;
+pub mod integrity;
 pub mod optimize;
 pub mod progress;
+pub mod refs;
 pub(crate) mod rowids;
 pub mod scanner;
 mod schema_evolution;
-mod take;
+pub mod statistics;
+pub(crate) mod take;
 pub mod transaction;
 pub mod updater;
 mod utils;
 mod write;
 
 use self::builder::DatasetBuilder;
-use self::cleanup::RemovalStats;
+use self::cleanup::{CleanupPolicy, CleanupReport, RemovalStats};
 use self::fragment::FileFragment;
 use self::scanner::{DatasetRecordBatchStream, Scanner};
 use self::transaction::{Operation, Transaction};
@@ -61,20 +71,26 @@ use crate::io::commit::{commit_new_dataset, commit_transaction};
 use crate::session::Session;
 use crate::utils::temporal::{timestamp_to_nanos, utc_now, SystemTime};
 use crate::{Error, Result};
+pub use diff::VersionDiff;
 use hash_joiner::HashJoiner;
+pub use integrity::IntegrityReport;
 pub use lance_core::ROW_ID;
 use lance_table::feature_flags::{
     apply_feature_flags, can_read_dataset, can_write_dataset, should_use_legacy_format,
     FLAG_USE_V2_FORMAT,
 };
+pub use refs::{TagContents, Tags};
 pub use schema_evolution::{
     BatchInfo, BatchUDF, ColumnAlteration, NewColumnTransform, UDFCheckpointStore,
 };
+pub use statistics::DatasetStatistics;
+pub use take::DeletedRowPolicy;
 pub use write::merge_insert::{
     MergeInsertBuilder, MergeInsertJob, WhenMatched, WhenNotMatched, WhenNotMatchedBySource,
 };
 pub use write::update::{UpdateBuilder, UpdateJob};
-pub use write::{write_fragments, WriteMode, WriteParams};
+use write::{append_compare_options, decode_encoded_columns, mark_blob_columns};
+pub use write::{write_fragments, WriteMode, WriteParams, BLOB_COLUMN_META_KEY};
 
 const INDICES_DIR: &str = "_indices";
 
@@ -120,6 +136,19 @@ impl From<&Manifest> for Version {
     }
 }
 
+/// Summary of a [`Dataset::delete`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteResult {
+    /// The number of rows deleted
+    ///
+    /// Rows that were already deleted before this operation are not counted
+    pub num_deleted_rows: u64,
+    /// The ids of the fragments that had some, but not all, of their rows deleted
+    pub updated_fragment_ids: Vec<u64>,
+    /// The ids of the fragments that had all of their rows deleted and were removed
+    pub removed_fragment_ids: Vec<u64>,
+}
+
 /// Customize read behavior of a dataset.
 #[derive(Clone, Debug)]
 pub struct ReadParams {
@@ -266,6 +295,63 @@ impl Dataset {
         .await
     }
 
+    /// Check out the version of this dataset that a tag points to
+    pub async fn checkout_tag(&self, tag: &str) -> Result<Self> {
+        let version = self.tags().get(tag).await?.version;
+        self.checkout_version(version).await
+    }
+
+    /// Creates a shallow clone of a version of this dataset at `target_uri`
+    ///
+    /// The clone is a new dataset whose first version has the same data as `version` of this
+    /// dataset.  The data files are not copied, the clone reads them from this dataset.  Later
+    /// writes to the clone, including deletes and compaction, only write files in the clone and
+    /// never change this dataset.
+    ///
+    /// The clone must be in the same object store (e.g. the same bucket) as this dataset.  The
+    /// shared data files must be kept in this dataset for as long as the clone needs them, for
+    /// example by tagging `version` so that it isn't cleaned up.
+    pub async fn shallow_clone(&self, target_uri: &str, version: u64) -> Result<Self> {
+        clone::shallow_clone(self, target_uri, version).await
+    }
+
+    /// Finds the rows that changed from `from_version` to `to_version`
+    ///
+    /// Inserted rows are reported by their address in `to_version` and deleted rows by their
+    /// address in `from_version`.  Updated rows are both deleted and inserted.  Rows that are
+    /// only moved, e.g. by compaction, are not reported.  The changes are found from the
+    /// metadata and deletion files of the versions in between, which must not have been
+    /// cleaned up, and no data is read.
+    pub async fn diff(&self, from_version: u64, to_version: u64) -> Result<VersionDiff> {
+        diff::diff(self, from_version, to_version).await
+    }
+
+    /// Computes the storage statistics of the dataset (e.g. the bytes stored for each field)
+    ///
+    /// Only metadata is read, see [`statistics`].
+    pub async fn stats(&self) -> Result<DatasetStatistics> {
+        statistics::dataset_statistics(self).await
+    }
+
+    /// Checks the data and deletion files of every fragment and reports all problems found
+    ///
+    /// Files are checked to exist, to have readable metadata, to hold the buffers their
+    /// metadata points at and to have the number of rows of their fragment.  If `deep` is
+    /// true, page buffers are also compared with the checksums recorded when they were
+    /// written and all data is decoded, which reads the whole dataset.  See [`integrity`].
+    pub async fn validate_integrity(&self, deep: bool) -> Result<IntegrityReport> {
+        integrity::validate_integrity(self, deep).await
+    }
+
+    /// The tags (named versions) of this dataset
+    pub fn tags(&self) -> Tags {
+        Tags::new(
+            self.object_store.clone(),
+            self.commit_handler.clone(),
+            self.base.clone(),
+        )
+    }
+
     async fn load_manifest(
         object_store: &ObjectStore,
         manifest_location: &ManifestLocation,
@@ -370,9 +456,6 @@ impl Dataset {
             Err(e) => return Err(e),
         };
 
-        let (batches, schema) = peek_reader_schema(Box::new(batches)).await?;
-        let stream = reader_to_stream(batches);
-
         // Running checks for the different write modes
         // create + dataset already exists = error
         if dataset_exists && matches!(params.mode, WriteMode::Create) {
@@ -410,6 +493,14 @@ impl Dataset {
             )
         };
 
+        let target_schema = dataset
+            .as_ref()
+            .filter(|_| matches!(params.mode, WriteMode::Append))
+            .map(|d| d.schema());
+        let batches = decode_encoded_columns(batches, target_schema, params.use_legacy_format)?;
+        let (batches, mut schema) = peek_reader_schema(batches).await?;
+        let stream = reader_to_stream(batches);
+
         // append + input schema different from existing schema = error
         if matches!(params.mode, WriteMode::Append) {
             if let Some(d) = dataset.as_ref() {
@@ -417,14 +508,20 @@ impl Dataset {
                 schema.check_compatible(
                     &m.schema,
                     &SchemaCompareOptions {
-                        compare_dictionary: true,
-                        ..Default::default()
+                        // Only legacy files share a dictionary, the v2 format stores
+                        // dictionaries as their values
+                        compare_dictionary: should_use_legacy_format(m.writer_feature_flags),
+                        ..append_compare_options()
                     },
                 )?;
                 params.use_legacy_format = should_use_legacy_format(m.writer_feature_flags);
             }
         }
 
+        if !matches!(params.mode, WriteMode::Append) {
+            mark_blob_columns(&mut schema, &params.blob_columns)?;
+        }
+
         let params = params; // discard mut
 
         if let Some(d) = dataset.as_ref() {
@@ -475,7 +572,9 @@ impl Dataset {
                 commit_handler.as_ref(),
                 &transaction,
                 &manifest_config,
-                &Default::default(),
+                &CommitConfig {
+                    num_retries: params.commit_retries,
+                },
             )
             .await?
         } else {
@@ -534,15 +633,17 @@ impl Dataset {
             });
         }
 
-        let (batches, schema) = peek_reader_schema(Box::new(batches)).await?;
+        let batches =
+            decode_encoded_columns(batches, Some(self.schema()), params.use_legacy_format)?;
+        let (batches, schema) = peek_reader_schema(batches).await?;
         let stream = reader_to_stream(batches);
 
         // Return Error if append and input schema differ
-        self.manifest.schema.check_compatible(
-            &schema,
+        schema.check_compatible(
+            &self.manifest.schema,
             &SchemaCompareOptions {
-                compare_dictionary: true,
-                ..Default::default()
+                compare_dictionary: should_use_legacy_format(self.manifest.writer_feature_flags),
+                ..append_compare_options()
             },
         )?;
 
@@ -565,7 +666,9 @@ impl Dataset {
             self.commit_handler.as_ref(),
             &transaction,
             &Default::default(),
-            &Default::default(),
+            &CommitConfig {
+                num_retries: params.commit_retries,
+            },
         )
         .await?;
 
@@ -576,6 +679,9 @@ impl Dataset {
 
     /// Append to existing [Dataset] with a stream of [RecordBatch]s
     ///
+    /// The batches may have their columns in a different order than the dataset and may
+    /// leave out nullable columns, which are written as nulls.
+    ///
     /// Returns void result or Returns [Error]
     pub async fn append(
         &mut self,
@@ -680,6 +786,18 @@ impl Dataset {
         cleanup::cleanup_old_versions(self, before, delete_unverified).boxed()
     }
 
+    /// Removes the versions that are not kept by a [`CleanupPolicy`]
+    ///
+    /// Unlike [`Self::cleanup_old_versions`] this can keep the most recent versions regardless
+    /// of their age and can be run as a dry run.  The returned report lists the removed files
+    /// (or the files that would be removed by a dry run).
+    pub fn cleanup_with_policy(
+        &self,
+        policy: CleanupPolicy,
+    ) -> BoxFuture<'_, Result<CleanupReport>> {
+        async move { cleanup::cleanup_with_policy(self, &policy).await }.boxed()
+    }
+
     /// Commit changes to the dataset
     ///
     /// This operation is not needed if you are using append/write/delete to manipulate the dataset.
@@ -945,6 +1063,34 @@ impl Dataset {
         take::take_rows(self, row_ids, projection).await
     }
 
+    /// Take rows by their stable row ids.
+    ///
+    /// Like [`Self::take_rows`], but only the row id sequences of the fragments that may
+    /// contain the row ids are loaded, instead of the row id sequences of every fragment.
+    /// This is much cheaper for point lookups in datasets with many fragments.  Row ids
+    /// that don't exist (or were deleted) are skipped.
+    pub async fn take_by_row_ids(
+        &self,
+        row_ids: &[u64],
+        projection: &Schema,
+    ) -> Result<RecordBatch> {
+        take::take_by_row_ids(self, row_ids, projection).await
+    }
+
+    /// Take rows by their row addresses, such as the `_rowaddr` values of a previous scan.
+    ///
+    /// Unlike [`Self::take_rows`] the output always has one row per address, in the
+    /// requested order and including duplicates.  `deleted_rows` decides whether an
+    /// address of a deleted row is an error or produces a row of nulls.
+    pub async fn take_rows_by_address(
+        &self,
+        row_addrs: &[u64],
+        projection: &Schema,
+        deleted_rows: DeletedRowPolicy,
+    ) -> Result<RecordBatch> {
+        take::take_rows_by_address(self, row_addrs, projection, deleted_rows).await
+    }
+
     /// Get a stream of batches based on iterator of ranges of row numbers.
     ///
     /// This is an experimental API. It may change at any time.
@@ -957,29 +1103,85 @@ impl Dataset {
         take::take_scan(self, row_ranges, projection, batch_readahead)
     }
 
-    /// Sample `n` rows from the dataset.
-    pub(crate) async fn sample(&self, n: usize, projection: &Schema) -> Result<RecordBatch> {
-        use rand::seq::IteratorRandom;
+    /// Sample `n` rows uniformly at random (without replacement) from the dataset.
+    ///
+    /// The rows are picked by their offsets using the row counts of the fragments (which
+    /// account for deleted rows) and then only the picked rows are read.  The rows are
+    /// returned in dataset order.  If `n` is larger than the number of rows then every
+    /// row is returned.  The same `seed` gives the same sample of the same version.
+    pub async fn sample(
+        &self,
+        n: usize,
+        projection: &Schema,
+        seed: Option<u64>,
+    ) -> Result<RecordBatch> {
+        use rand::{rngs::StdRng, SeedableRng};
         let num_rows = self.count_rows(None).await?;
-        let ids = (0..num_rows as u64).choose_multiple(&mut rand::thread_rng(), n);
-        self.take(&ids, projection).await
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut offsets = rand::seq::index::sample(&mut rng, num_rows, n.min(num_rows))
+            .into_iter()
+            .map(|offset| offset as u64)
+            .collect::<Vec<_>>();
+        // Taking the rows in order reads each fragment sequentially
+        offsets.sort_unstable();
+        self.take(&offsets, projection).await
     }
 
     /// Delete rows based on a predicate.
-    pub async fn delete(&mut self, predicate: &str) -> Result<()> {
+    ///
+    /// Returns a summary of the rows and fragments affected by the delete
+    pub async fn delete(&mut self, predicate: &str) -> Result<DeleteResult> {
+        let (result, _) = self.delete_impl(predicate, false).await?;
+        Ok(result)
+    }
+
+    /// Delete rows based on a predicate, also returning the deleted rows
+    ///
+    /// The stream has a `_rowid` and a `_rowaddr` column with the ids and addresses of
+    /// the deleted rows (e.g. to invalidate caches keyed by row id).  The ids are collected
+    /// while deleting and so the stream is available once the delete has been committed.
+    pub async fn delete_returning_row_ids(
+        &mut self,
+        predicate: &str,
+    ) -> Result<(DeleteResult, SendableRecordBatchStream)> {
+        let (result, batches) = self.delete_impl(predicate, true).await?;
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ROW_ID_FIELD.clone(),
+            ROW_ADDR_FIELD.clone(),
+        ]));
+        let stream = RecordBatchStreamAdapter::new(
+            schema,
+            futures::stream::iter(batches.into_iter().map(Ok)),
+        );
+        Ok((result, Box::pin(stream)))
+    }
+
+    async fn delete_impl(
+        &mut self,
+        predicate: &str,
+        with_row_ids: bool,
+    ) -> Result<(DeleteResult, Vec<RecordBatch>)> {
         let mut updated_fragments: Vec<Fragment> = Vec::new();
         let mut deleted_fragment_ids: Vec<u64> = Vec::new();
+        let mut result = DeleteResult::default();
+        let mut deleted_row_ids = Vec::new();
         stream::iter(self.get_fragments())
             .map(|f| async move {
                 let old_fragment = f.metadata.clone();
-                let new_fragment = f.delete(predicate).await?.map(|f| f.metadata);
-                Ok((old_fragment, new_fragment))
+                let deletion = f.delete_rows(predicate, with_row_ids).await?;
+                Ok((old_fragment, deletion))
             })
             .buffer_unordered(num_cpus::get())
             // Drop the fragments that were deleted.
-            .try_for_each(|(old_fragment, new_fragment)| {
-                if let Some(new_fragment) = new_fragment {
+            .try_for_each(|(old_fragment, deletion)| {
+                result.num_deleted_rows += deletion.num_deleted_rows;
+                deleted_row_ids.extend(deletion.deleted_row_ids);
+                if let Some(new_fragment) = deletion.fragment.map(|f| f.metadata) {
                     if new_fragment != old_fragment {
+                        result.updated_fragment_ids.push(new_fragment.id);
                         updated_fragments.push(new_fragment);
                     }
                 } else {
@@ -988,6 +1190,9 @@ impl Dataset {
                 futures::future::ready(Ok::<_, crate::Error>(()))
             })
             .await?;
+        result.updated_fragment_ids.sort();
+        result.removed_fragment_ids = deleted_fragment_ids.clone();
+        result.removed_fragment_ids.sort();
 
         let transaction = Transaction::new(
             self.manifest.version,
@@ -1011,7 +1216,7 @@ impl Dataset {
 
         self.manifest = Arc::new(manifest);
 
-        Ok(())
+        Ok((result, deleted_row_ids))
     }
 
     pub async fn count_deleted_rows(&self) -> Result<usize> {
@@ -1036,6 +1241,17 @@ impl Dataset {
         self.base.child(DATA_DIR)
     }
 
+    /// The directory of a data file
+    ///
+    /// This is the data directory of this dataset unless the file is shared with the
+    /// source of a shallow clone.
+    pub(crate) fn data_file_dir(&self, data_file: &DataFile) -> Path {
+        match &data_file.base {
+            Some(base) => Path::from(base.as_str()).child(DATA_DIR),
+            None => self.data_dir(),
+        }
+    }
+
     pub(crate) fn indices_dir(&self) -> Path {
         self.base.child(INDICES_DIR)
     }
@@ -1112,6 +1328,23 @@ impl Dataset {
             .collect()
     }
 
+    /// The fragments whose custom metadata satisfies `predicate`
+    ///
+    /// For example, this can find the fragments ingested before some date in order to
+    /// compact or delete them.
+    pub fn get_fragments_by_metadata(
+        &self,
+        predicate: impl Fn(&HashMap<String, String>) -> bool,
+    ) -> Vec<FileFragment> {
+        let dataset = Arc::new(self.clone());
+        self.manifest
+            .fragments
+            .iter()
+            .filter(|f| predicate(&f.custom_metadata))
+            .map(|f| FileFragment::new(dataset.clone(), f.clone()))
+            .collect()
+    }
+
     pub fn get_fragment(&self, fragment_id: usize) -> Option<FileFragment> {
         let dataset = Arc::new(self.clone());
         let fragment = self
@@ -1126,6 +1359,43 @@ impl Dataset {
         &self.manifest.fragments
     }
 
+    /// The columns (ascending, nulls first) that the rows of the dataset are sorted by
+    ///
+    /// This is only known if every fragment was written with the same sort order (see
+    /// [`WriteParams::sort_order`]).  Scans ordered by a prefix of these columns read the
+    /// fragments in order instead of sorting.
+    pub fn sort_order(&self) -> Option<Vec<String>> {
+        let (first, rest) = self.manifest.fragments.split_first()?;
+        let sorted = !first.sort_order.is_empty()
+            && rest
+                .iter()
+                .all(|fragment| fragment.sort_order == first.sort_order);
+        if !sorted {
+            return None;
+        }
+        self.sort_order_columns(&first.sort_order)
+    }
+
+    /// The paths of the columns of a fragment's sort order
+    ///
+    /// Returns `None` if one of the fields is no longer in the schema (e.g. it was dropped
+    /// or rewritten with a new type), in which case the rows are not known to be sorted.
+    pub(crate) fn sort_order_columns(&self, field_ids: &[i32]) -> Option<Vec<String>> {
+        field_ids
+            .iter()
+            .map(|id| {
+                let ancestry = self.schema().field_ancestry_by_id(*id)?;
+                Some(
+                    ancestry
+                        .iter()
+                        .map(|field| field.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                )
+            })
+            .collect()
+    }
+
     /// Gets the number of files that are so small they don't even have a full
     /// group. These are considered too small because reading many of them is
     /// much less efficient than reading a single file because the separate files
@@ -1177,6 +1447,10 @@ impl Dataset {
 /// # Schema Evolution
 impl Dataset {
     /// Append new columns to the dataset.
+    ///
+    /// Only the data for the new columns is written, existing data files are left untouched.
+    /// Rows that have been deleted are skipped, and the new values are aligned with the
+    /// remaining rows.
     pub async fn add_columns(
         &mut self,
         transforms: NewColumnTransform,
@@ -1192,12 +1466,26 @@ impl Dataset {
         schema_evolution::alter_columns(self, alterations).await
     }
 
+    /// Rename columns in the dataset.
+    ///
+    /// Each rename is a pair of the current path of the column and its new name, e.g.
+    /// `("meta.ts", "timestamp")`.  The new name can also be given as a path with the same
+    /// parent, e.g. `("meta.ts", "meta.timestamp")`.
+    ///
+    /// This is a metadata-only operation.  Field ids don't change and so existing data
+    /// files and indices remain valid.
+    pub async fn rename_columns(&mut self, renames: &[(&str, &str)]) -> Result<()> {
+        schema_evolution::rename_columns(self, renames).await
+    }
+
     /// Remove columns from the dataset.
     ///
     /// This is a metadata-only operation and does not remove the data from the
     /// underlying storage. In order to remove the data, you must subsequently
     /// call `compact_files` to rewrite the data without the removed columns and
     /// then call `cleanup_files` to remove the old files.
+    ///
+    /// Any indices on the removed columns are removed as well.
     pub async fn drop_columns(&mut self, columns: &[&str]) -> Result<()> {
         schema_evolution::drop_columns(self, columns).await
     }
@@ -1242,20 +1530,17 @@ pub(crate) async fn write_manifest_file(
     indices: Option<Vec<Index>>,
     config: &ManifestWriteConfig,
 ) -> std::result::Result<(), CommitError> {
-    let was_using_legacy = should_use_legacy_format(manifest.writer_feature_flags);
     if config.auto_set_feature_flags {
         apply_feature_flags(manifest, config.use_move_stable_row_ids)?;
     }
     // For now, we don't auto-detect use_v2_format.  Instead, if the user
     // asks for it, we set it.  Otherwise we use what was there before.
     if let Some(use_legacy_format) = config.use_legacy_format {
-        if !use_legacy_format {
+        if use_legacy_format {
+            manifest.writer_feature_flags &= !FLAG_USE_V2_FORMAT;
+        } else {
             manifest.writer_feature_flags |= FLAG_USE_V2_FORMAT;
         }
-    } else if was_using_legacy {
-        manifest.writer_feature_flags &= !FLAG_USE_V2_FORMAT;
-    } else {
-        manifest.writer_feature_flags |= FLAG_USE_V2_FORMAT;
     }
     manifest.set_timestamp(timestamp_to_nanos(config.timestamp));
 
@@ -1310,7 +1595,10 @@ mod tests {
         DictionaryArray, Float32Array, Int32Array, Int64Array, Int8Array, Int8DictionaryArray,
         RecordBatchIterator, StringArray, UInt16Array, UInt32Array,
     };
-    use arrow_array::{FixedSizeListArray, Int16Array, Int16DictionaryArray, StructArray};
+    use arrow_array::{
+        cast::AsArray, Array, BinaryArray, FixedSizeListArray, Int16Array, Int16DictionaryArray,
+        StructArray,
+    };
     use arrow_ord::sort::sort_to_indices;
     use arrow_schema::{
         DataType, Field as ArrowField, Fields as ArrowFields, Schema as ArrowSchema,
@@ -1705,9 +1993,14 @@ mod tests {
         )
         .await
         .unwrap();
+        let format_flag = if use_legacy_format {
+            0
+        } else {
+            FLAG_USE_V2_FORMAT
+        };
         assert_eq!(
             manifest.writer_feature_flags,
-            feature_flags::FLAG_DELETION_FILES
+            feature_flags::FLAG_DELETION_FILES | format_flag
         );
         assert_eq!(
             manifest.reader_feature_flags,
@@ -1971,6 +2264,218 @@ mod tests {
         assert!(matches!(result, Err(Error::SchemaMismatch { .. })))
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_write_dictionary_and_run_end_encoded(
+        #[values(false, true)] use_legacy_format: bool,
+    ) {
+        use arrow_array::{Int32DictionaryArray, RunArray};
+
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let ree_type = DataType::RunEndEncoded(
+            Arc::new(ArrowField::new("run_ends", DataType::Int32, false)),
+            Arc::new(ArrowField::new("values", DataType::Int64, true)),
+        );
+        let encoded_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("s", dict_type, true),
+            ArrowField::new("n", ree_type, true),
+        ]));
+        let plain_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("n", DataType::Int64, true),
+        ]));
+        // Each batch has its own dictionary
+        let encoded_batch = |dictionary: &[&str], keys: Vec<Option<i32>>| {
+            let num_rows = keys.len() as i32;
+            let strings = Int32DictionaryArray::new(
+                Int32Array::from(keys),
+                Arc::new(StringArray::from(dictionary.to_vec())),
+            );
+            let runs = RunArray::try_new(
+                &Int32Array::from(vec![2, num_rows - 1, num_rows]),
+                &Int64Array::from(vec![7, 8, 9]),
+            )
+            .unwrap();
+            RecordBatch::try_new(
+                encoded_schema.clone(),
+                vec![Arc::new(strings), Arc::new(runs)],
+            )
+            .unwrap()
+        };
+        let batches = vec![
+            encoded_batch(
+                &["a", "b"],
+                vec![Some(0), Some(1), Some(0), Some(1), Some(0)],
+            ),
+            encoded_batch(&["c", "b", "a"], vec![Some(2), None, Some(0), Some(1)]),
+        ];
+        // The values of the batches
+        let expected = RecordBatch::try_new(
+            plain_schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("b"),
+                    Some("a"),
+                    Some("b"),
+                    Some("a"),
+                    Some("a"),
+                    None,
+                    Some("c"),
+                    Some("b"),
+                ])),
+                Arc::new(Int64Array::from(vec![7, 7, 8, 8, 9, 7, 7, 8, 9])),
+            ],
+        )
+        .unwrap();
+
+        // Appending to a dataset with the plain types
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let write_params = WriteParams {
+            use_legacy_format,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(expected.slice(0, 1))], plain_schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params.clone()))
+            .await
+            .unwrap();
+        dataset
+            .append(
+                RecordBatchIterator::new(
+                    batches.clone().into_iter().map(Ok),
+                    encoded_schema.clone(),
+                ),
+                Some(write_params.clone()),
+            )
+            .await
+            .unwrap();
+        let scanned = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(scanned.schema(), plain_schema);
+        assert_eq!(
+            scanned,
+            concat_batches(&plain_schema, &[expected.slice(0, 1), expected.clone()]).unwrap()
+        );
+
+        // A new dataset has the plain types, except that the legacy format keeps
+        // dictionary fields (which only support a single dictionary)
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batches[0].clone())], encoded_schema.clone());
+        let dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        let scanned = dataset.scan().try_into_batch().await.unwrap();
+        let expected_type = if use_legacy_format {
+            encoded_schema.field(0).data_type()
+        } else {
+            &DataType::Utf8
+        };
+        assert_eq!(scanned.schema().field(0).data_type(), expected_type);
+        assert_eq!(scanned.schema().field(1).data_type(), &DataType::Int64);
+        let strings = arrow::compute::cast(&scanned["s"], &DataType::Utf8).unwrap();
+        assert_eq!(strings.as_ref(), expected["s"].slice(0, 5).as_ref());
+        assert_eq!(scanned["n"].as_ref(), expected["n"].slice(0, 5).as_ref());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_append_partial_schema(#[values(false, true)] use_legacy_format: bool) {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("b", DataType::Binary, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..10).map(|i| format!("s{}", i)),
+                )),
+                Arc::new(BinaryArray::from_iter_values((0..10).map(|i| [i as u8]))),
+            ],
+        )
+        .unwrap();
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut write_params = WriteParams {
+            use_legacy_format,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params.clone()))
+            .await
+            .unwrap();
+
+        // Leave out `s` and reorder the remaining columns
+        let subset_schema = Arc::new(schema.project(&[2, 0]).unwrap());
+        let subset = RecordBatch::try_new(
+            subset_schema.clone(),
+            vec![
+                Arc::new(BinaryArray::from_iter_values((10..15).map(|i| [i as u8]))),
+                Arc::new(Int32Array::from_iter_values(10..15)),
+            ],
+        )
+        .unwrap();
+        dataset
+            .append(
+                RecordBatchIterator::new(vec![Ok(subset)], subset_schema),
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Only `i` through the write path
+        let i_schema = Arc::new(schema.project(&[0]).unwrap());
+        let only_i = RecordBatch::try_new(
+            i_schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(15..20))],
+        )
+        .unwrap();
+        write_params.mode = WriteMode::Append;
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(only_i)], i_schema),
+            test_uri,
+            Some(write_params.clone()),
+        )
+        .await
+        .unwrap();
+
+        let scanned = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(scanned.schema().as_ref(), schema.as_ref());
+        assert_eq!(
+            scanned["i"].as_primitive::<Int32Type>().values(),
+            &(0..20).collect::<Vec<_>>()
+        );
+        let strings = scanned["s"].as_string::<i32>();
+        assert_eq!(strings.null_count(), 10);
+        assert_eq!(&strings.slice(0, 10), batch["s"].as_string::<i32>());
+        let binaries = scanned["b"].as_binary::<i32>();
+        assert_eq!(binaries.null_count(), 5);
+        assert_eq!(binaries.slice(15, 5).null_count(), 5);
+        assert_eq!(binaries.value(12), [12]);
+
+        // A non-nullable column can't be left out
+        let s_schema = Arc::new(schema.project(&[1]).unwrap());
+        let only_s = RecordBatch::try_new(
+            s_schema.clone(),
+            vec![Arc::new(StringArray::from(vec![Some("x")]))],
+        )
+        .unwrap();
+        let result = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(only_s)], s_schema),
+            test_uri,
+            Some(write_params),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::SchemaMismatch { .. })),
+            "{:?}",
+            result
+        );
+    }
+
     #[tokio::test]
     async fn append_dictionary() {
         // We store the dictionary as part of the schema, so we check that the
@@ -2576,7 +3081,8 @@ mod tests {
             .await;
 
         // Delete nothing
-        dataset.delete("i < 0").await.unwrap();
+        let result = dataset.delete("i < 0").await.unwrap();
+        assert_eq!(result, DeleteResult::default());
         dataset.validate().await.unwrap();
 
         // We should not have any deletion file still
@@ -2589,7 +3095,15 @@ mod tests {
         assert!(fragments[1].metadata.deletion_file.is_none());
 
         // Delete rows
-        dataset.delete("i < 10 OR i >= 90").await.unwrap();
+        let result = dataset.delete("i < 10 OR i >= 90").await.unwrap();
+        assert_eq!(
+            result,
+            DeleteResult {
+                num_deleted_rows: 20,
+                updated_fragment_ids: vec![0, 1],
+                removed_fragment_ids: vec![],
+            }
+        );
         dataset.validate().await.unwrap();
 
         // Verify result:
@@ -2645,8 +3159,16 @@ mod tests {
         );
         let second_deletion_file = fragments[1].metadata.deletion_file.clone().unwrap();
 
-        // Delete more rows
-        dataset.delete("i < 20").await.unwrap();
+        // Delete more rows, rows that were already deleted are not counted again
+        let result = dataset.delete("i < 20").await.unwrap();
+        assert_eq!(
+            result,
+            DeleteResult {
+                num_deleted_rows: 10,
+                updated_fragment_ids: vec![0],
+                removed_fragment_ids: vec![],
+            }
+        );
         dataset.validate().await.unwrap();
 
         // Verify result
@@ -2670,7 +3192,15 @@ mod tests {
         );
 
         // Delete full fragment
-        dataset.delete("i >= 50").await.unwrap();
+        let result = dataset.delete("i >= 50").await.unwrap();
+        assert_eq!(
+            result,
+            DeleteResult {
+                num_deleted_rows: 40,
+                updated_fragment_ids: vec![],
+                removed_fragment_ids: vec![1],
+            }
+        );
         dataset.validate().await.unwrap();
 
         // Verify second fragment is fully gone
@@ -2705,6 +3235,92 @@ mod tests {
         assert_eq!(dataset.manifest.max_fragment_id(), Some(2));
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_delete_returning_row_ids(
+        #[values(false, true)] enable_move_stable_row_ids: bool,
+    ) {
+        use arrow_array::{cast::AsArray, types::UInt64Type};
+        use lance_core::ROW_ADDR;
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::UInt32,
+            false,
+        )]));
+        let data = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(UInt32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            enable_move_stable_row_ids,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(data)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        async fn collect_ids(stream: SendableRecordBatchStream) -> (Vec<u64>, Vec<u64>) {
+            let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+            let mut ids = Vec::new();
+            let mut addrs = Vec::new();
+            for batch in batches {
+                ids.extend(batch[ROW_ID].as_primitive::<UInt64Type>().values());
+                addrs.extend(batch[ROW_ADDR].as_primitive::<UInt64Type>().values());
+            }
+            ids.sort();
+            addrs.sort();
+            (ids, addrs)
+        }
+
+        let (result, stream) = dataset
+            .delete_returning_row_ids("i >= 45 AND i < 55")
+            .await
+            .unwrap();
+        assert_eq!(result.num_deleted_rows, 10);
+        assert_eq!(result.updated_fragment_ids, vec![0, 1]);
+        let (ids, addrs) = collect_ids(stream).await;
+        let expected_addrs = (45..50).chain((1 << 32)..(1 << 32) + 5).collect::<Vec<_>>();
+        if enable_move_stable_row_ids {
+            assert_eq!(ids, (45..55).collect::<Vec<_>>());
+        } else {
+            assert_eq!(ids, expected_addrs);
+        }
+        assert_eq!(addrs, expected_addrs);
+
+        // Overlaps the previous delete and empties the second fragment
+        let (result, stream) = dataset.delete_returning_row_ids("i >= 50").await.unwrap();
+        assert_eq!(
+            result,
+            DeleteResult {
+                num_deleted_rows: 45,
+                updated_fragment_ids: vec![],
+                removed_fragment_ids: vec![1],
+            }
+        );
+        let (_, addrs) = collect_ids(stream).await;
+        assert_eq!(addrs, ((1 << 32) + 5..(1 << 32) + 50).collect::<Vec<_>>());
+
+        // Deleting everything that is left
+        let (result, stream) = dataset.delete_returning_row_ids("true").await.unwrap();
+        assert_eq!(
+            result,
+            DeleteResult {
+                num_deleted_rows: 45,
+                updated_fragment_ids: vec![],
+                removed_fragment_ids: vec![0],
+            }
+        );
+        let (_, addrs) = collect_ids(stream).await;
+        assert_eq!(addrs, (0..45).collect::<Vec<_>>());
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 0);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_restore(#[values(false, true)] use_legacy_format: bool) {
@@ -3490,4 +4106,328 @@ mod tests {
             dataset.latest_version_id().await.unwrap()
         );
     }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_blob_columns(#[values(false, true)] use_legacy_format: bool) {
+        use crate::utils::test::IoTrackingStore;
+        use lance_arrow::RecordBatchExt;
+        use std::collections::HashSet;
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("blob", DataType::Binary, true),
+            ArrowField::new("label", DataType::Utf8, true),
+        ]));
+        let blob = |i: i32| vec![i as u8; 1000 + i as usize];
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(BinaryArray::from_iter_values((0..100).map(blob))),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("label_{}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let dataset = Dataset::write(
+            reader,
+            "memory://test",
+            Some(WriteParams {
+                max_rows_per_file: 40,
+                use_legacy_format,
+                blob_columns: vec!["blob".to_string()],
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        // Local files are not read through the object store, so a memory store is used
+        let memory_store = dataset.object_store.inner.clone();
+        let (io_stats_wrapper, io_stats) = IoTrackingStore::new_wrapper();
+        let dataset = DatasetBuilder::from_uri("memory://test")
+            .with_read_params(ReadParams {
+                store_options: Some(ObjectStoreParams {
+                    object_store_wrapper: Some(io_stats_wrapper),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .with_object_store(
+                memory_store,
+                Url::parse("memory://test").unwrap(),
+                Arc::new(RenameCommitHandler),
+            )
+            .load()
+            .await
+            .unwrap();
+
+        // Each fragment stores the blob column in its own file
+        let blob_field_id = dataset.schema().field("blob").unwrap().id;
+        let mut blob_paths = HashSet::new();
+        for fragment in dataset.get_fragments() {
+            let files = &fragment.metadata().files;
+            assert_eq!(files.len(), 2);
+            assert!(!files[0].fields.contains(&blob_field_id));
+            assert_eq!(files[1].fields, vec![blob_field_id]);
+            blob_paths.insert(
+                dataset
+                    .data_file_dir(&files[1])
+                    .child(files[1].path.as_str()),
+            );
+        }
+        assert_eq!(blob_paths.len(), 3);
+
+        // Scans that don't project the blob column don't read the blob files
+        let scanned = dataset
+            .scan()
+            .project(&["id", "label"])
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(
+            scanned,
+            batch.project_by_schema(scanned.schema().as_ref()).unwrap()
+        );
+        let read_paths = std::mem::take(&mut io_stats.lock().unwrap().read_paths);
+        assert!(!read_paths.is_empty());
+        assert!(read_paths.is_disjoint(&blob_paths));
+
+        // Take reads the blobs from the blob files
+        let projection = dataset.schema().project(&["blob"]).unwrap();
+        let taken = dataset.take(&[3, 41, 99, 41], &projection).await.unwrap();
+        assert_eq!(
+            taken["blob"].as_binary::<i32>(),
+            &BinaryArray::from_iter_values([3, 41, 99, 41].into_iter().map(blob))
+        );
+        let read_paths = std::mem::take(&mut io_stats.lock().unwrap().read_paths);
+        assert!(read_paths.iter().any(|path| blob_paths.contains(path)));
+
+        let taken = dataset.take(&[5, 80], dataset.schema()).await.unwrap();
+        assert_eq!(
+            taken,
+            concat_batches(&taken.schema(), &[batch.slice(5, 1), batch.slice(80, 1)]).unwrap()
+        );
+
+        // The designation is recorded in the schema and later writes keep splitting the
+        // blob column out, without being asked to
+        let is_split = |dataset: &Dataset| {
+            dataset.get_fragments().iter().all(|fragment| {
+                let files = &fragment.metadata().files;
+                files.len() == 2 && files[1].fields == vec![blob_field_id]
+            })
+        };
+        assert!(dataset.schema().field("blob").unwrap().metadata[BLOB_COLUMN_META_KEY] == "true");
+        assert!(!dataset
+            .schema()
+            .field("id")
+            .unwrap()
+            .metadata
+            .contains_key(BLOB_COLUMN_META_KEY));
+        let mut dataset = dataset;
+        let reader = RecordBatchIterator::new(vec![Ok(batch.slice(0, 10))], schema.clone());
+        dataset.append(reader, None).await.unwrap();
+        assert_eq!(dataset.get_fragments().len(), 4);
+        assert!(is_split(&dataset));
+
+        let mut dataset = UpdateBuilder::new(Arc::new(dataset))
+            .update_where("id < 50")
+            .unwrap()
+            .set("label", "'updated'")
+            .unwrap()
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap()
+            .as_ref()
+            .clone();
+        assert!(is_split(&dataset));
+
+        compact_files(&mut dataset, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(dataset.get_fragments().len(), 1);
+        assert!(is_split(&dataset));
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 110);
+
+        // Blob columns must be top-level columns of the data
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let result = Dataset::write(
+            reader,
+            "memory://other",
+            Some(WriteParams {
+                blob_columns: vec!["missing".to_string()],
+                ..Default::default()
+            }),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_sample() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        // Fragments of very different sizes, one of which has deleted rows
+        let mut start = 0;
+        for num_rows in [1000, 100, 10] {
+            let data = gen()
+                .col("i", array::step_custom::<Int32Type>(start, 1))
+                .into_reader_rows(RowCount::from(num_rows), BatchCount::from(1));
+            let mode = if start == 0 {
+                WriteMode::Create
+            } else {
+                WriteMode::Append
+            };
+            let write_params = WriteParams {
+                mode,
+                ..Default::default()
+            };
+            Dataset::write(data, test_uri, Some(write_params))
+                .await
+                .unwrap();
+            start += num_rows as i32;
+        }
+        let mut dataset = Dataset::open(test_uri).await.unwrap();
+        dataset.delete("i < 1000 AND i % 2 = 0").await.unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 610);
+
+        let projection = dataset.schema().clone();
+        let sample = |n: usize, seed: u64| {
+            let dataset = dataset.clone();
+            let projection = projection.clone();
+            async move {
+                let batch = dataset.sample(n, &projection, Some(seed)).await.unwrap();
+                batch["i"].as_primitive::<Int32Type>().values().to_vec()
+            }
+        };
+
+        // Each fragment is sampled in proportion to its (live) rows
+        let mut counts = [0; 3];
+        for seed in 0..500 {
+            let values = sample(61, seed).await;
+            assert_eq!(values.len(), 61);
+            // Rows are sampled without replacement, are returned in order and are not
+            // deleted rows
+            assert!(values.windows(2).all(|w| w[0] < w[1]));
+            assert!(values.iter().all(|v| *v >= 1000 || v % 2 == 1));
+            for v in values {
+                counts[match v {
+                    0..=999 => 0,
+                    1000..=1099 => 1,
+                    _ => 2,
+                }] += 1;
+            }
+        }
+        let total = (61 * 500) as f64;
+        for (count, num_rows) in counts.iter().zip([500, 100, 10]) {
+            let expected = total * num_rows as f64 / 610.0;
+            assert!(
+                (*count as f64 - expected).abs() < expected * 0.2,
+                "{:?}",
+                counts
+            );
+        }
+
+        // Samples are deterministic for a seed
+        assert_eq!(sample(20, 7).await, sample(20, 7).await);
+        assert_ne!(sample(20, 7).await, sample(20, 8).await);
+
+        // Asking for more rows than the dataset has returns every row
+        let all = sample(1000, 1).await;
+        assert_eq!(all.len(), 610);
+        assert_eq!(
+            all,
+            (1..1000).step_by(2).chain(1000..1110).collect::<Vec<i32>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fragment_custom_metadata() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let metadata = |source: &str, date: &str| {
+            HashMap::from([
+                ("source".to_string(), source.to_string()),
+                ("source_date".to_string(), date.to_string()),
+                ("job".to_string(), "1".to_string()),
+            ])
+        };
+        let data = || {
+            gen()
+                .col("i", array::step::<Int32Type>())
+                .into_reader_rows(RowCount::from(50), BatchCount::from(2))
+        };
+
+        Dataset::write(
+            data(),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 50,
+                fragment_metadata: metadata("a.csv", "2024-01-01"),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        let mut dataset = Dataset::write(
+            data(),
+            test_uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                fragment_metadata: metadata("b.csv", "2024-02-01"),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        // An unrelated commit keeps the metadata
+        dataset.delete("i < 10").await.unwrap();
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), 3);
+        assert!(fragments[0].metadata().deletion_file.is_some());
+        assert_eq!(
+            fragments[0].custom_metadata(),
+            &metadata("a.csv", "2024-01-01")
+        );
+        assert_eq!(
+            fragments[1].custom_metadata(),
+            &metadata("a.csv", "2024-01-01")
+        );
+        assert_eq!(
+            fragments[2].custom_metadata(),
+            &metadata("b.csv", "2024-02-01")
+        );
+
+        let old = dataset.get_fragments_by_metadata(|metadata| {
+            metadata
+                .get("source_date")
+                .is_some_and(|date| date.as_str() < "2024-02-01")
+        });
+        assert_eq!(
+            old.iter().map(|f| f.id()).collect::<Vec<_>>(),
+            vec![fragments[0].id(), fragments[1].id()]
+        );
+
+        // Compaction only keeps the key/values shared by all of the compacted fragments
+        let mut dataset = dataset;
+        compact_files(&mut dataset, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(
+            fragments[0].custom_metadata(),
+            &HashMap::from([("job".to_string(), "1".to_string())])
+        );
+    }
 }
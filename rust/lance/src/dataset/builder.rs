@@ -14,7 +14,9 @@ use snafu::{location, Location};
 use tracing::instrument;
 use url::Url;
 
-use super::{ReadParams, WriteParams, DEFAULT_INDEX_CACHE_SIZE, DEFAULT_METADATA_CACHE_SIZE};
+use super::{
+    refs::Tags, ReadParams, WriteParams, DEFAULT_INDEX_CACHE_SIZE, DEFAULT_METADATA_CACHE_SIZE,
+};
 use crate::{
     error::{Error, Result},
     session::Session,
@@ -34,6 +36,7 @@ pub struct DatasetBuilder {
     commit_handler: Option<Arc<dyn CommitHandler>>,
     options: ObjectStoreParams,
     version: Option<u64>,
+    tag: Option<String>,
     table_uri: String,
 }
 
@@ -47,6 +50,7 @@ impl DatasetBuilder {
             commit_handler: None,
             session: None,
             version: None,
+            tag: None,
             manifest: None,
         }
     }
@@ -82,6 +86,12 @@ impl DatasetBuilder {
         self
     }
 
+    /// Loads the version that the tag points to, cannot be combined with a version
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
     pub fn with_commit_handler(mut self, commit_handler: Arc<dyn CommitHandler>) -> Self {
         self.commit_handler = Some(commit_handler);
         self
@@ -232,12 +242,29 @@ impl DatasetBuilder {
             )),
         };
 
-        let version = self.version;
+        let mut version = self.version;
+        let tag = self.tag.take();
         let table_uri = self.table_uri.clone();
 
         let manifest = self.manifest.take();
 
         let (object_store, base_path, commit_handler) = self.build_object_store().await?;
+        let object_store = Arc::new(object_store);
+
+        if let Some(tag) = tag {
+            if version.is_some() || manifest.is_some() {
+                return Err(Error::invalid_input(
+                    "A dataset can be loaded at a tag or at a version but not both",
+                    location!(),
+                ));
+            }
+            let tags = Tags::new(
+                object_store.clone(),
+                commit_handler.clone(),
+                base_path.clone(),
+            );
+            version = Some(tags.get(&tag).await?.version);
+        }
 
         let manifest = if manifest.is_some() {
             let mut manifest = manifest.unwrap();
@@ -275,7 +302,7 @@ impl DatasetBuilder {
         };
 
         Dataset::checkout_manifest(
-            Arc::new(object_store),
+            object_store,
             base_path,
             table_uri,
             manifest,
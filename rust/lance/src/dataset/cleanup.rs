@@ -32,10 +32,14 @@
 //! Otherwise we will leave the file unless delete_unverified is set to true.
 //! (which should only be done if the caller can guarantee there are no updates
 //! happening at the same time)
+//!
+//! Which versions are old is decided by a [`CleanupPolicy`].  The latest version
+//! and tagged versions are always kept.  A cleanup can also be run as a dry run
+//! which reports what would be removed without deleting anything.
 
 use chrono::{DateTime, TimeDelta, Utc};
 use futures::{stream, StreamExt, TryStreamExt};
-use lance_core::Result;
+use lance_core::{Error, Result};
 use lance_table::{
     format::{Index, Manifest},
     io::{
@@ -66,6 +70,86 @@ pub struct RemovalStats {
     pub old_versions: u64,
 }
 
+impl From<CleanupReport> for RemovalStats {
+    fn from(report: CleanupReport) -> Self {
+        Self {
+            bytes_removed: report.bytes_removed,
+            old_versions: report.old_versions.len() as u64,
+        }
+    }
+}
+
+/// Decides which versions are kept by [`cleanup_with_policy`]
+///
+/// A version is kept if any of the rules keep it.  The latest version and tagged versions
+/// are always kept.  If no rules are set then every other version is removed.
+#[derive(Clone, Debug, Default)]
+pub struct CleanupPolicy {
+    /// Keep this many of the most recent versions
+    pub keep_versions: Option<u64>,
+    /// Keep versions created less than this long ago
+    pub keep_newer_than: Option<TimeDelta>,
+    /// If true, delete files that are not referenced by any manifest even if they are recent
+    ///
+    /// Such files can't be told apart from files of an in-progress write and so this should
+    /// only be set if there are no concurrent writers.
+    pub delete_unverified: bool,
+    /// If true, report what would be removed without deleting anything
+    pub dry_run: bool,
+}
+
+/// The files removed by a cleanup, or the files that would be removed by a dry run
+///
+/// Paths are relative to the object store, like the paths of the dataset files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// The removed versions, in increasing order
+    pub old_versions: Vec<u64>,
+    /// Manifests of the removed versions and leftover temporary manifests
+    pub manifest_files: Vec<Path>,
+    pub data_files: Vec<Path>,
+    pub deletion_files: Vec<Path>,
+    pub index_files: Vec<Path>,
+    pub transaction_files: Vec<Path>,
+    /// The total size of the removed files
+    pub bytes_removed: u64,
+    /// True if nothing was actually deleted
+    pub dry_run: bool,
+}
+
+impl CleanupReport {
+    fn add_file(&mut self, path: Path, relative_path: &Path, size: u64) {
+        self.bytes_removed += size;
+        let files = match relative_path
+            .parts()
+            .next()
+            .as_ref()
+            .map(|part| part.as_ref())
+        {
+            Some("_versions") => &mut self.manifest_files,
+            Some("data") => &mut self.data_files,
+            Some("_deletions") => &mut self.deletion_files,
+            Some("_indices") => &mut self.index_files,
+            // Only files from the directories above are ever removed
+            _ => &mut self.transaction_files,
+        };
+        files.push(path);
+    }
+
+    /// Iterates over all of the removed files
+    ///
+    /// Manifests come last so that, if a cleanup fails part way, the files of a removed
+    /// version are still verified by its manifest the next time.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.data_files
+            .iter()
+            .chain(&self.deletion_files)
+            .chain(&self.index_files)
+            .chain(&self.transaction_files)
+            .chain(&self.manifest_files)
+    }
+}
+
 fn remove_prefix(path: &Path, prefix: &Path) -> Path {
     let relative_parts = path.prefix_match(prefix);
     if relative_parts.is_none() {
@@ -79,14 +163,21 @@ struct CleanupTask<'a> {
     dataset: &'a Dataset,
     /// Cleanup all versions before this time
     before: DateTime<Utc>,
+    /// Keep this many of the most recent versions, even if they are older than `before`
+    keep_versions: Option<u64>,
     /// If true, delete unverified data files even if they are recent
     delete_unverified: bool,
+    /// If true, only report what would be deleted
+    dry_run: bool,
+    /// Tagged versions are always kept
+    tagged_versions: HashSet<u64>,
 }
 
 /// Information about the dataset that we learn by inspecting all of the manifests
 #[derive(Clone, Debug, Default)]
 struct CleanupInspection {
-    old_manifests: Vec<Path>,
+    /// The versions that will be removed and their manifests
+    old_manifests: Vec<(u64, Path)>,
     /// Referenced files are part of our working set
     referenced_files: ReferencedFiles,
     /// Verified files may or may not be part of the working set but they are
@@ -104,11 +195,34 @@ impl<'a> CleanupTask<'a> {
         Self {
             dataset,
             before,
+            keep_versions: None,
             delete_unverified,
+            dry_run: false,
+            tagged_versions: HashSet::new(),
+        }
+    }
+
+    fn from_policy(dataset: &'a Dataset, policy: &CleanupPolicy) -> Self {
+        let before = utc_now() - policy.keep_newer_than.unwrap_or_default();
+        Self {
+            dataset,
+            before,
+            keep_versions: policy.keep_versions,
+            delete_unverified: policy.delete_unverified,
+            dry_run: policy.dry_run,
+            tagged_versions: HashSet::new(),
         }
     }
 
-    async fn run(self) -> Result<RemovalStats> {
+    async fn run(mut self) -> Result<CleanupReport> {
+        self.tagged_versions = self
+            .dataset
+            .tags()
+            .list()
+            .await?
+            .into_values()
+            .map(|contents| contents.version)
+            .collect();
         // First we process all manifest files in parallel to figure
         // out which files are referenced by valid manifests
         let inspection = self.process_manifests().await?;
@@ -145,14 +259,22 @@ impl<'a> CleanupTask<'a> {
         // if their version is newer than the dataset version.  These are either in-progress
         // or newly added since we started.
         let is_latest = dataset_version <= manifest.version;
-        let in_working_set = is_latest || manifest.timestamp() >= self.before;
+        let is_recent = self
+            .keep_versions
+            .is_some_and(|keep_versions| manifest.version + keep_versions > dataset_version);
+        let in_working_set = is_latest
+            || is_recent
+            || self.tagged_versions.contains(&manifest.version)
+            || manifest.timestamp() >= self.before;
         let indexes = read_manifest_indexes(&self.dataset.object_store, &path, &manifest).await?;
 
         let mut inspection = inspection.lock().unwrap();
 
         self.process_manifest(&manifest, &indexes, in_working_set, &mut inspection)?;
         if !in_working_set {
-            inspection.old_manifests.push(path.clone());
+            inspection
+                .old_manifests
+                .push((manifest.version, path.clone()));
         }
         Ok(())
     }
@@ -174,7 +296,7 @@ impl<'a> CleanupTask<'a> {
 
         for fragment in manifest.fragments.iter() {
             for file in fragment.files.iter() {
-                let full_data_path = self.dataset.data_dir().child(file.path.as_str());
+                let full_data_path = self.dataset.data_file_dir(file).child(file.path.as_str());
                 let relative_data_path = remove_prefix(&full_data_path, &self.dataset.base);
                 referenced_files.data_paths.insert(relative_data_path);
             }
@@ -203,11 +325,10 @@ impl<'a> CleanupTask<'a> {
     async fn delete_unreferenced_files(
         &self,
         inspection: CleanupInspection,
-    ) -> Result<RemovalStats> {
-        let removal_stats = Mutex::new(RemovalStats::default());
+    ) -> Result<CleanupReport> {
         let verification_threshold = utc_now()
             - TimeDelta::try_days(UNVERIFIED_THRESHOLD_DAYS).expect("TimeDelta::try_days");
-        let unreferenced_paths = self
+        let unreferenced_files = self
             .dataset
             .object_store
             .read_dir_all(&self.dataset.base, Some(self.before))
@@ -217,45 +338,68 @@ impl<'a> CleanupTask<'a> {
                 // delete it if we can verify it is part of an old version.
                 let maybe_in_progress =
                     !self.delete_unverified && obj_meta.last_modified >= verification_threshold;
+                let size = obj_meta.size as u64;
                 let path_to_remove =
                     self.path_if_not_referenced(obj_meta.location, maybe_in_progress, &inspection);
-                if matches!(path_to_remove, Ok(Some(..))) {
-                    removal_stats.lock().unwrap().bytes_removed += obj_meta.size as u64;
-                }
-                future::ready(path_to_remove)
+                future::ready(path_to_remove.map(|path| path.map(|path| (path, size))))
             })
-            .boxed();
-
-        let old_manifests = inspection.old_manifests.clone();
-        let num_old_manifests = old_manifests.len();
+            .try_collect::<Vec<_>>()
+            .await?;
 
+        let mut old_manifests = inspection.old_manifests;
+        old_manifests.sort_unstable();
         // Ideally this collect shouldn't be needed here but it seems necessary
         // to avoid https://github.com/rust-lang/rust/issues/102211
-        let manifest_bytes_removed = stream::iter(&old_manifests)
-            .map(|path| self.dataset.object_store.size(path))
+        let manifest_sizes = stream::iter(&old_manifests)
+            .map(|(_, path)| self.file_size(path))
             .collect::<Vec<_>>()
             .await;
-        let manifest_bytes_removed = stream::iter(manifest_bytes_removed)
-            .buffer_unordered(num_cpus::get())
-            .try_fold(0, |acc, size| async move { Ok(acc + (size as u64)) })
-            .await;
-
-        let old_manifests_stream = stream::iter(old_manifests).map(Result::<Path>::Ok).boxed();
-        let all_paths_to_remove =
-            stream::iter(vec![unreferenced_paths, old_manifests_stream]).flatten();
+        let manifest_sizes = stream::iter(manifest_sizes)
+            .buffered(num_cpus::get())
+            .try_collect::<Vec<_>>()
+            .await?;
 
-        let delete_fut = self
-            .dataset
-            .object_store
-            .remove_stream(all_paths_to_remove.boxed())
-            .try_for_each(|_| future::ready(Ok(())));
+        let mut report = CleanupReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        for ((version, path), size) in old_manifests.into_iter().zip(manifest_sizes) {
+            report.old_versions.push(version);
+            if let Some(size) = size {
+                let relative_path = remove_prefix(&path, &self.dataset.base);
+                report.add_file(path, &relative_path, size);
+            }
+        }
+        for (path, size) in unreferenced_files {
+            let relative_path = remove_prefix(&path, &self.dataset.base);
+            report.add_file(path, &relative_path, size);
+        }
 
-        delete_fut.await?;
+        if !self.dry_run {
+            // Files that were removed since they were listed (e.g. by a concurrent cleanup)
+            // are not an error
+            let paths = stream::iter(report.files().cloned().map(Ok)).boxed();
+            self.dataset
+                .object_store
+                .inner
+                .delete_stream(paths)
+                .map(|result| match result {
+                    Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                    Err(e) => Err(Error::from(e)),
+                })
+                .try_collect::<()>()
+                .await?;
+        }
+        Ok(report)
+    }
 
-        let mut removal_stats = removal_stats.into_inner().unwrap();
-        removal_stats.old_versions = num_old_manifests as u64;
-        removal_stats.bytes_removed += manifest_bytes_removed?;
-        Ok(removal_stats)
+    /// Returns `None` if the file is already gone (e.g. removed by a concurrent cleanup)
+    async fn file_size(&self, path: &Path) -> Result<Option<u64>> {
+        match self.dataset.object_store.inner.head(path).await {
+            Ok(meta) => Ok(Some(meta.size as u64)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
     }
 
     fn path_if_not_referenced(
@@ -380,8 +524,8 @@ impl<'a> CleanupTask<'a> {
 ///
 /// It will only remove files that are not referenced by any valid manifest.
 ///
-/// The latest manifest is always considered valid and will not be removed
-/// even if it is older than the `before` parameter.
+/// The latest manifest and tagged versions are always considered valid and
+/// will not be removed even if they are older than the `before` parameter.
 ///
 /// The `before` parameter must be at least 7 days before the current date.
 pub async fn cleanup_old_versions(
@@ -390,7 +534,18 @@ pub async fn cleanup_old_versions(
     delete_unverified: Option<bool>,
 ) -> Result<RemovalStats> {
     let cleanup = CleanupTask::new(dataset, before, delete_unverified.unwrap_or(false));
-    cleanup.run().await
+    Ok(cleanup.run().await?.into())
+}
+
+/// Removes the versions of a dataset that are not kept by `policy`
+///
+/// Files are removed in the same way as [`cleanup_old_versions`].  If the policy is a
+/// dry run then nothing is deleted but the report lists the files that would be removed.
+pub async fn cleanup_with_policy(
+    dataset: &Dataset,
+    policy: &CleanupPolicy,
+) -> Result<CleanupReport> {
+    CleanupTask::from_policy(dataset, policy).run().await
 }
 
 #[cfg(test)]
@@ -409,7 +564,11 @@ mod tests {
     use snafu::{location, Location};
 
     use crate::{
-        dataset::{builder::DatasetBuilder, ReadParams, WriteMode, WriteParams},
+        dataset::{
+            builder::DatasetBuilder,
+            optimize::{compact_files, CompactionOptions},
+            ReadParams, WriteMode, WriteParams,
+        },
         index::vector::VectorIndexParams,
     };
     use all_asserts::{assert_gt, assert_lt};
@@ -836,6 +995,127 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn dont_cleanup_tagged_version() {
+        let fixture = MockDatasetFixture::try_new().unwrap();
+        fixture.create_some_data().await.unwrap();
+        fixture.append_some_data().await.unwrap();
+        let mut db = fixture.open().await.unwrap();
+        db.tags().create("prod", 1).await.unwrap();
+        // Tags survive compaction, which rewrites the data of the tagged version
+        compact_files(&mut db, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        fixture
+            .clock
+            .set_system_time(TimeDelta::try_seconds(1).unwrap());
+
+        let latest_version = db.version().version;
+        let before_count = fixture.count_files().await.unwrap();
+        // Every version plus _latest.manifest
+        assert_eq!(before_count.num_manifest_files, latest_version as usize + 1);
+
+        // The tagged version and the latest version are kept
+        let removed = fixture.run_cleanup(utc_now()).await.unwrap();
+        assert_eq!(removed.old_versions, latest_version - 2);
+        let after_count = fixture.count_files().await.unwrap();
+        assert_eq!(after_count.num_manifest_files, 3);
+        // Only the appended file, which was compacted away, is removed
+        assert_eq!(after_count.num_data_files, before_count.num_data_files - 1);
+
+        let db = fixture.open().await.unwrap();
+        let prod = db.checkout_tag("prod").await.unwrap();
+        assert_eq!(prod.version().version, 1);
+        assert_eq!(
+            prod.count_rows(None).await.unwrap(),
+            fixture.count_rows().await.unwrap() / 2
+        );
+
+        // Once the tag is deleted the version can be cleaned up
+        db.tags().delete("prod").await.unwrap();
+        let removed = fixture.run_cleanup(utc_now()).await.unwrap();
+        assert_eq!(removed.old_versions, 1);
+        assert_eq!(fixture.count_files().await.unwrap().num_manifest_files, 2);
+    }
+
+    #[tokio::test]
+    async fn cleanup_with_retention_policy() {
+        let fixture = MockDatasetFixture::try_new().unwrap();
+        fixture.create_some_data().await.unwrap();
+        for _ in 0..11 {
+            fixture.append_some_data().await.unwrap();
+        }
+        fixture.delete_data("true").await.unwrap();
+        fixture.append_some_data().await.unwrap();
+        let db = fixture.open().await.unwrap();
+        assert_eq!(db.version().version, 14);
+        db.tags().create("old", 3).await.unwrap();
+        fixture
+            .clock
+            .set_system_time(TimeDelta::try_days(1).unwrap());
+
+        let before_count = fixture.count_files().await.unwrap();
+        let policy = CleanupPolicy {
+            keep_versions: Some(2),
+            keep_newer_than: Some(TimeDelta::try_hours(1).unwrap()),
+            delete_unverified: false,
+            dry_run: true,
+        };
+
+        // A dry run reports the files without deleting them
+        let dry_run = cleanup_with_policy(&db, &policy).await.unwrap();
+        assert!(dry_run.dry_run);
+        assert_eq!(
+            dry_run.old_versions,
+            vec![1, 2, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+        assert_eq!(dry_run.manifest_files.len(), 11);
+        // The data of every version before the delete is gone, except for the tagged version
+        assert_eq!(dry_run.data_files.len(), 12 - 3);
+        assert!(dry_run.deletion_files.is_empty());
+        assert_eq!(dry_run.transaction_files.len(), 11);
+        assert_eq!(fixture.count_files().await.unwrap(), before_count);
+
+        let report = cleanup_with_policy(
+            &db,
+            &CleanupPolicy {
+                dry_run: false,
+                ..policy.clone()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report,
+            CleanupReport {
+                dry_run: false,
+                ..dry_run
+            }
+        );
+        let after_count = fixture.count_files().await.unwrap();
+        assert_eq!(
+            report.bytes_removed,
+            before_count.num_bytes - after_count.num_bytes
+        );
+        // Versions 3, 13 and 14 plus _latest.manifest
+        assert_eq!(after_count.num_manifest_files, 4);
+        assert_eq!(after_count.num_data_files, 4);
+
+        // The tagged version can still be read
+        let tagged = db.checkout_tag("old").await.unwrap();
+        assert_eq!(
+            tagged.count_rows(None).await.unwrap(),
+            3 * fixture.count_rows().await.unwrap()
+        );
+        assert_eq!(
+            cleanup_with_policy(&db, &policy).await.unwrap(),
+            CleanupReport {
+                dry_run: true,
+                ..Default::default()
+            }
+        );
+    }
+
     #[tokio::test]
     async fn cleanup_old_index() {
         let fixture = MockDatasetFixture::try_new().unwrap();
@@ -0,0 +1,350 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Shallow clones of a dataset
+//!
+//! A shallow clone is a new dataset that starts out with the data of a version of another
+//! (source) dataset without copying it.  The data files of the source are referenced by the
+//! manifest of the clone (see [`DataFile::base`](lance_table::format::DataFile::base)).
+//! Deletion files, index files and row id files are small, and are rewritten by later
+//! changes, so they are copied.
+//!
+//! The clone is independent of the source from then on.  New data, deletion and index files
+//! are written to the clone, and the clone never removes files of the source.  Compaction
+//! leaves the shared fragments alone so that their data is not copied.  However, the
+//! source does not know about the clone, and so the shared files must not be removed from
+//! the source (e.g. by cleaning up old versions) while the clone still needs them.  Tagging
+//! the cloned version in the source prevents this.
+
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use lance_index::DatasetIndexExt;
+use lance_io::object_store::ObjectStore;
+use lance_table::{format::RowIdMeta, io::deletion::deletion_file_path};
+use object_store::path::Path;
+use snafu::{location, Location};
+use url::Url;
+
+use super::{write_manifest_file, Dataset, ManifestWriteConfig};
+use crate::{Error, Result};
+
+// The object store a URI refers to, as (scheme, bucket), all local paths share a store
+fn store_of(uri: &str) -> (String, String) {
+    match Url::parse(uri) {
+        // On Windows, the drive is parsed as a scheme
+        Ok(url) if !(url.scheme().len() == 1 && cfg!(windows)) && url.scheme() != "file" => (
+            url.scheme().to_string(),
+            url.host_str().unwrap_or_default().to_string(),
+        ),
+        _ => ("file".to_string(), String::new()),
+    }
+}
+
+// The path of a URI within its object store, the same as `ObjectStore::from_uri` returns
+fn path_of(uri: &str) -> Result<Path> {
+    match Url::parse(uri) {
+        Ok(url) if !(url.scheme().len() == 1 && cfg!(windows)) => Ok(Path::from(url.path())),
+        _ => Ok(ObjectStore::from_path(uri)?.1),
+    }
+}
+
+/// Creates a shallow clone of `version` of `dataset` at `target_uri`, see [`Dataset::shallow_clone`]
+pub async fn shallow_clone(dataset: &Dataset, target_uri: &str, version: u64) -> Result<Dataset> {
+    // The clone reads the shared files through its own object store and so it has to be
+    // the same store
+    if store_of(&dataset.uri) != store_of(target_uri) {
+        return Err(Error::invalid_input(
+            format!(
+                "Cannot shallow clone {} to {}, the clone must be in the same object store as the source",
+                dataset.uri, target_uri
+            ),
+            location!(),
+        ));
+    }
+    let target_base = path_of(target_uri)?;
+    if target_base == dataset.base {
+        return Err(Error::invalid_input(
+            "Cannot shallow clone a dataset into itself",
+            location!(),
+        ));
+    }
+    let object_store = dataset.object_store.clone();
+    if dataset
+        .commit_handler
+        .resolve_latest_version(&target_base, &object_store)
+        .await
+        .is_ok()
+    {
+        return Err(Error::DatasetAlreadyExists {
+            uri: target_uri.to_string(),
+            location: location!(),
+        });
+    }
+
+    let source = dataset.checkout_version(version).await?;
+    let mut manifest = source.manifest.as_ref().clone();
+    let mut files_to_copy = Vec::new();
+    for fragment in Arc::make_mut(&mut manifest.fragments).iter_mut() {
+        for data_file in fragment.files.iter_mut() {
+            // Files that the source shares with its own source keep pointing there
+            if data_file.base.is_none() {
+                data_file.base = Some(source.base.to_string());
+            }
+        }
+        if let Some(deletion_file) = &fragment.deletion_file {
+            files_to_copy.push((
+                deletion_file_path(&source.base, fragment.id, deletion_file),
+                deletion_file_path(&target_base, fragment.id, deletion_file),
+            ));
+        }
+        if let Some(RowIdMeta::External(file)) = &fragment.row_id_meta {
+            files_to_copy.push((
+                source.base.child(file.path.as_str()),
+                target_base.child(file.path.as_str()),
+            ));
+        }
+    }
+    let indices = source.load_indices().await?.as_ref().clone();
+    for index in indices.iter() {
+        let index_dir = source.indices_dir().child(index.uuid.to_string());
+        let index_files = object_store
+            .read_dir_all(&index_dir, None)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        for index_file in index_files {
+            let Some(relative_path) = index_file.location.prefix_match(&source.base) else {
+                continue;
+            };
+            files_to_copy.push((
+                index_file.location.clone(),
+                Path::from_iter(target_base.parts().chain(relative_path)),
+            ));
+        }
+    }
+    futures::stream::iter(files_to_copy)
+        .map(|(from, to)| {
+            let object_store = object_store.clone();
+            async move { object_store.copy(&from, &to).await }
+        })
+        .buffer_unordered(object_store.io_parallelism()? as usize)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // The clone has its own history, starting with the cloned version
+    manifest.version = 1;
+    manifest.transaction_file = None;
+    write_manifest_file(
+        &object_store,
+        dataset.commit_handler.as_ref(),
+        &target_base,
+        &mut manifest,
+        (!indices.is_empty()).then_some(indices),
+        &ManifestWriteConfig::default(),
+    )
+    .await?;
+
+    Dataset::checkout_manifest(
+        object_store,
+        target_base,
+        target_uri.to_string(),
+        manifest,
+        dataset.session.clone(),
+        dataset.commit_handler.clone(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::dataset::cleanup::CleanupPolicy;
+    use crate::dataset::optimize::{compact_files, CompactionOptions};
+    use crate::dataset::{WriteMode, WriteParams};
+
+    async fn append(uri: &str, values: Vec<i32>) -> Dataset {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))]).unwrap();
+        Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema),
+            uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn list_files(dataset: &Dataset, dir: &str) -> HashSet<Path> {
+        dataset
+            .object_store
+            .read_dir_all(&dataset.base.child(dir), None)
+            .await
+            .unwrap()
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    async fn values(dataset: &Dataset) -> Vec<i32> {
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        let mut values = batch["i"]
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap()
+            .values()
+            .to_vec();
+        values.sort();
+        values
+    }
+
+    #[tokio::test]
+    async fn test_shallow_clone() {
+        let test_dir = tempdir().unwrap();
+        let source_uri = format!("{}/source", test_dir.path().to_str().unwrap());
+        let clone_uri = format!("{}/clone", test_dir.path().to_str().unwrap());
+        append(&source_uri, (0..10).collect()).await;
+        let mut source = append(&source_uri, (10..20).collect()).await;
+        source.delete("i % 5 = 0").await.unwrap();
+        let cloned_version = source.version().version;
+        append(&source_uri, (20..30).collect()).await;
+
+        let source_data = list_files(&source, "data").await;
+        let source_deletions = list_files(&source, "_deletions").await;
+        let expected = values(&source.checkout_version(cloned_version).await.unwrap()).await;
+
+        let mut clone = source
+            .shallow_clone(&clone_uri, cloned_version)
+            .await
+            .unwrap();
+        assert_eq!(clone.version().version, 1);
+        assert_eq!(values(&clone).await, expected);
+        // The data is not copied but the deletion files are
+        assert!(list_files(&clone, "data").await.is_empty());
+        assert_eq!(list_files(&clone, "_deletions").await.len(), 2);
+        clone.validate().await.unwrap();
+        // The clone can be opened on its own
+        assert_eq!(
+            values(&Dataset::open(&clone_uri).await.unwrap()).await,
+            expected
+        );
+
+        // Changes to the clone don't affect the source
+        clone.delete("i < 12").await.unwrap();
+        let clone = append(&clone_uri, vec![100, 101]).await;
+        let expected_clone = expected
+            .iter()
+            .copied()
+            .filter(|i| *i >= 12)
+            .chain([100, 101])
+            .collect::<Vec<_>>();
+        assert_eq!(values(&clone).await, expected_clone);
+        assert_eq!(
+            values(&source.checkout_version(cloned_version).await.unwrap()).await,
+            expected
+        );
+        assert_eq!(
+            values(&Dataset::open(&source_uri).await.unwrap())
+                .await
+                .len(),
+            26
+        );
+
+        // Compaction only rewrites the fragments of the clone, the shared data is not
+        // copied into it
+        let clone = append(&clone_uri, vec![102, 103]).await;
+        let expected_clone = expected_clone
+            .into_iter()
+            .chain([102, 103])
+            .collect::<Vec<_>>();
+        // Fragment 0 was removed when all its rows were deleted
+        let shared_fragments = clone.fragments()[..1].to_vec();
+        assert!(shared_fragments
+            .iter()
+            .all(|fragment| fragment.files[0].base.is_some()));
+        let mut clone = Dataset::open(&clone_uri).await.unwrap();
+        let metrics = compact_files(&mut clone, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(metrics.fragments_removed, 2);
+        assert_eq!(metrics.fragments_added, 1);
+        assert_eq!(values(&clone).await, expected_clone);
+        assert_eq!(clone.fragments()[..1], shared_fragments);
+        clone
+            .cleanup_with_policy(CleanupPolicy {
+                keep_versions: Some(1),
+                delete_unverified: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(values(&clone).await, expected_clone);
+
+        // Changes to the source don't affect the clone
+        source.delete("true").await.unwrap();
+        assert_eq!(values(&clone).await, expected_clone);
+        assert_eq!(list_files(&source, "data").await, source_data);
+        assert_eq!(list_files(&source, "_deletions").await, source_deletions);
+    }
+
+    #[tokio::test]
+    async fn test_shallow_clone_of_clone() {
+        let test_dir = tempdir().unwrap();
+        let source_uri = format!("{}/source", test_dir.path().to_str().unwrap());
+        let clone_uri = format!("{}/clone", test_dir.path().to_str().unwrap());
+        let clone2_uri = format!("{}/clone2", test_dir.path().to_str().unwrap());
+        let source = append(&source_uri, (0..10).collect()).await;
+        let clone = source.shallow_clone(&clone_uri, 1).await.unwrap();
+        append(&clone_uri, (10..20).collect()).await;
+        let clone = clone.checkout_version(2).await.unwrap();
+        let clone2 = clone.shallow_clone(&clone2_uri, 2).await.unwrap();
+        assert_eq!(values(&clone2).await, (0..20).collect::<Vec<_>>());
+        // The first file is read from the original source
+        let bases = clone2
+            .fragments()
+            .iter()
+            .map(|fragment| fragment.files[0].base.clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bases, vec![source.base.to_string(), clone.base.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_shallow_clone() {
+        let test_dir = tempdir().unwrap();
+        let source_uri = format!("{}/source", test_dir.path().to_str().unwrap());
+        let clone_uri = format!("{}/clone", test_dir.path().to_str().unwrap());
+        let source = append(&source_uri, (0..10).collect()).await;
+
+        // The clone must be able to read the files of the source
+        assert!(matches!(
+            source.shallow_clone("s3://bucket/clone", 1).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            source.shallow_clone(&source_uri, 1).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(source.shallow_clone(&clone_uri, 2).await.is_err());
+
+        source.shallow_clone(&clone_uri, 1).await.unwrap();
+        assert!(matches!(
+            source.shallow_clone(&clone_uri, 1).await,
+            Err(Error::DatasetAlreadyExists { .. })
+        ));
+    }
+}
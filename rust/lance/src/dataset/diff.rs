@@ -0,0 +1,463 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Changes between two versions of a dataset
+//!
+//! A diff is computed from the manifests, the transactions and the deletion files of the
+//! versions between the two versions, the data is never read.  Rows are tracked by fragment:
+//! fragments that are added hold inserted rows, fragments that are removed and rows that are
+//! added to deletion files are deleted.  Updates (including merge inserts) delete the old rows
+//! and insert new ones and so they show up as both.
+//!
+//! Compaction (and any other [`Operation::Rewrite`]) moves rows to new fragments without
+//! changing them.  The rows of the new fragments are the remaining rows of the old fragments,
+//! in order, and so the moved rows are followed to their new address and are not reported.
+
+use std::collections::{HashMap, HashSet};
+
+use futures::stream::{BoxStream, StreamExt};
+use lance_core::utils::address::RowAddress;
+use lance_table::{format::Fragment, io::deletion::read_deletion_file};
+use roaring::RoaringTreemap;
+use snafu::{location, Location};
+
+use super::transaction::Operation;
+use super::Dataset;
+use crate::{Error, Result};
+
+/// The rows that changed between two versions of a dataset, see [`Dataset::diff`]
+#[derive(Debug, Clone)]
+pub struct VersionDiff {
+    /// The version the changes are relative to
+    pub from_version: u64,
+    /// The version the changes lead to
+    pub to_version: u64,
+    /// True if the schema of `to_version` is different from the schema of `from_version`
+    pub schema_changed: bool,
+    inserted: RoaringTreemap,
+    deleted: RoaringTreemap,
+}
+
+impl VersionDiff {
+    /// The addresses, in `to_version`, of the rows that were inserted, in ascending order
+    pub fn inserted(&self) -> BoxStream<'static, u64> {
+        futures::stream::iter(self.inserted.clone()).boxed()
+    }
+
+    /// The addresses, in `from_version`, of the rows that were deleted, in ascending order
+    pub fn deleted(&self) -> BoxStream<'static, u64> {
+        futures::stream::iter(self.deleted.clone()).boxed()
+    }
+
+    /// The number of rows that were inserted
+    pub fn num_inserted(&self) -> u64 {
+        self.inserted.len()
+    }
+
+    /// The number of rows that were deleted
+    pub fn num_deleted(&self) -> u64 {
+        self.deleted.len()
+    }
+
+    /// True if no rows were inserted or deleted
+    ///
+    /// The schema may still have changed.
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.deleted.is_empty()
+    }
+}
+
+// Marks a row that was inserted after `from_version` in a rewritten fragment
+const INSERTED: u64 = u64::MAX;
+
+// Where the rows of a fragment of the version being walked come from
+enum Origin {
+    // The fragment is in `from_version`, its rows have the same addresses there
+    Unchanged,
+    // The fragment was added after `from_version`
+    Inserted,
+    // The fragment was written by a rewrite, holds the `from_version` address (or
+    // `INSERTED`) of each row of the fragment
+    Rewritten(Vec<u64>),
+}
+
+fn physical_rows(fragment: &Fragment) -> Result<u64> {
+    fragment.physical_rows.map(|rows| rows as u64).ok_or_else(|| {
+        Error::NotSupported {
+            source: format!(
+                "fragment {} does not record its number of rows, versions written by older versions of Lance cannot be diffed",
+                fragment.id
+            )
+            .into(),
+            location: location!(),
+        }
+    })
+}
+
+// The addresses of the rows of `fragment` that are not deleted
+async fn live_rows(dataset: &Dataset, fragment: &Fragment) -> Result<RoaringTreemap> {
+    let mut rows = RoaringTreemap::new();
+    let first_row = RowAddress::first_row(fragment.id as u32);
+    rows.insert_range(u64::from(first_row)..u64::from(first_row) + physical_rows(fragment)?);
+    if let Some(deletion_vector) =
+        read_deletion_file(&dataset.base, fragment, &dataset.object_store).await?
+    {
+        for offset in deletion_vector {
+            rows.remove(u64::from(first_row) + offset as u64);
+        }
+    }
+    Ok(rows)
+}
+
+// The origins of the rows of the fragments that `group` rewrote, in the order they were
+// written to the new fragments
+async fn rewritten_origins(
+    dataset: &Dataset,
+    old_fragments: &[Fragment],
+    origins: &HashMap<u64, Origin>,
+) -> Result<Vec<u64>> {
+    let mut rewritten = Vec::new();
+    for fragment in old_fragments {
+        let first_row = u64::from(RowAddress::first_row(fragment.id as u32));
+        for address in live_rows(dataset, fragment).await? {
+            let offset = address - first_row;
+            rewritten.push(match origins.get(&fragment.id) {
+                Some(Origin::Unchanged) => address,
+                Some(Origin::Rewritten(rows)) => rows[offset as usize],
+                Some(Origin::Inserted) | None => INSERTED,
+            });
+        }
+    }
+    Ok(rewritten)
+}
+
+/// Computes the changes from `from_version` to `to_version` of `dataset`, see [`Dataset::diff`]
+pub async fn diff(dataset: &Dataset, from_version: u64, to_version: u64) -> Result<VersionDiff> {
+    if from_version > to_version {
+        return Err(Error::invalid_input(
+            format!(
+                "Cannot diff from version {} to the older version {}",
+                from_version, to_version
+            ),
+            location!(),
+        ));
+    }
+    let from = dataset.checkout_version(from_version).await?;
+    let from_files = from
+        .fragments()
+        .iter()
+        .map(|fragment| (fragment.id, fragment.files.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut origins = from_files
+        .keys()
+        .map(|id| (*id, Origin::Unchanged))
+        .collect::<HashMap<_, _>>();
+
+    // Walk the versions in between to follow the rows that are moved by rewrites
+    let mut to = from.clone();
+    for version in from_version + 1..=to_version {
+        to = dataset.checkout_version(version).await?;
+        if let Some(transaction) = to.read_transaction().await? {
+            match &transaction.operation {
+                Operation::Rewrite { groups, .. } => {
+                    for group in groups {
+                        let rewritten =
+                            rewritten_origins(&to, &group.old_fragments, &origins).await?;
+                        let num_rows = group
+                            .new_fragments
+                            .iter()
+                            .map(physical_rows)
+                            .sum::<Result<u64>>()?;
+                        if num_rows != rewritten.len() as u64 {
+                            return Err(Error::Internal {
+                                message: format!(
+                                    "version {} rewrote {} rows into fragments with {} rows",
+                                    version,
+                                    rewritten.len(),
+                                    num_rows
+                                ),
+                                location: location!(),
+                            });
+                        }
+                        let mut rewritten = rewritten.into_iter();
+                        for fragment in &group.new_fragments {
+                            let rows = rewritten
+                                .by_ref()
+                                .take(physical_rows(fragment)? as usize)
+                                .collect();
+                            origins.insert(fragment.id, Origin::Rewritten(rows));
+                        }
+                    }
+                }
+                // An overwrite reuses the ids of the fragments it replaces and a restore
+                // brings back the fragments of another version, so the origins of every
+                // fragment are worked out again
+                Operation::Overwrite { .. } | Operation::Restore { .. } => origins.clear(),
+                _ => {}
+            }
+        }
+        let fragment_ids = to
+            .fragments()
+            .iter()
+            .map(|fragment| fragment.id)
+            .collect::<HashSet<_>>();
+        origins.retain(|id, _| fragment_ids.contains(id));
+        for fragment in to.fragments().iter() {
+            // A restore can bring back fragments of `from_version`, these are the only
+            // fragments with the same id and the same data files
+            origins.entry(fragment.id).or_insert_with(|| {
+                if from_files.get(&fragment.id) == Some(&fragment.files) {
+                    Origin::Unchanged
+                } else {
+                    Origin::Inserted
+                }
+            });
+        }
+    }
+
+    let mut from_rows = RoaringTreemap::new();
+    for fragment in from.fragments().iter() {
+        from_rows |= live_rows(&from, fragment).await?;
+    }
+    let mut inserted = RoaringTreemap::new();
+    let mut kept = RoaringTreemap::new();
+    for fragment in to.fragments().iter() {
+        let rows = live_rows(&to, fragment).await?;
+        match &origins[&fragment.id] {
+            Origin::Unchanged => {
+                inserted |= &rows - &from_rows;
+                kept |= rows & &from_rows;
+            }
+            Origin::Inserted => inserted |= rows,
+            Origin::Rewritten(origins) => {
+                let first_row = u64::from(RowAddress::first_row(fragment.id as u32));
+                for address in rows {
+                    match origins[(address - first_row) as usize] {
+                        INSERTED => inserted.insert(address),
+                        origin => kept.insert(origin),
+                    };
+                }
+            }
+        }
+    }
+
+    Ok(VersionDiff {
+        from_version,
+        to_version,
+        schema_changed: from.schema() != to.schema(),
+        inserted,
+        deleted: from_rows - kept,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, RecordBatch};
+    use arrow_array::{RecordBatchIterator, RecordBatchReader};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_datafusion::utils::reader_to_stream;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::dataset::optimize::{compact_files, CompactionOptions};
+    use crate::dataset::{
+        DeletedRowPolicy, MergeInsertBuilder, NewColumnTransform, WhenMatched, WriteMode,
+        WriteParams,
+    };
+
+    fn batches(values: Vec<i32>) -> impl RecordBatchReader + Send + 'static {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))]).unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], schema)
+    }
+
+    async fn append(uri: &str, values: Vec<i32>) -> Dataset {
+        Dataset::write(
+            batches(values),
+            uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                max_rows_per_file: 50,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap()
+    }
+
+    // The sorted values of the rows at `addresses` in `version`
+    async fn values_at(dataset: &Dataset, version: u64, addresses: BoxStream<'_, u64>) -> Vec<i32> {
+        let dataset = dataset.checkout_version(version).await.unwrap();
+        let addresses = addresses.collect::<Vec<_>>().await;
+        let projection = dataset.schema().project(&["i"]).unwrap();
+        let batch = dataset
+            .take_rows_by_address(&addresses, &projection, DeletedRowPolicy::Error)
+            .await
+            .unwrap();
+        let mut values = batch["i"].as_primitive::<Int32Type>().values().to_vec();
+        values.sort();
+        values
+    }
+
+    async fn check_diff(
+        dataset: &Dataset,
+        from_version: u64,
+        to_version: u64,
+        inserted: Vec<i32>,
+        deleted: Vec<i32>,
+    ) {
+        let diff = dataset.diff(from_version, to_version).await.unwrap();
+        assert_eq!(diff.num_inserted(), inserted.len() as u64);
+        assert_eq!(diff.num_deleted(), deleted.len() as u64);
+        assert_eq!(
+            values_at(dataset, to_version, diff.inserted()).await,
+            inserted
+        );
+        assert_eq!(
+            values_at(dataset, from_version, diff.deleted()).await,
+            deleted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        append(uri, (0..100).collect()).await;
+
+        // Appends
+        let mut dataset = append(uri, (100..120).collect()).await;
+        check_diff(&dataset, 1, 2, (100..120).collect(), vec![]).await;
+        check_diff(&dataset, 2, 2, vec![], vec![]).await;
+
+        // Deletes, including a whole fragment
+        dataset.delete("i < 10 or i >= 110").await.unwrap();
+        check_diff(&dataset, 2, 3, vec![], (0..10).chain(110..120).collect()).await;
+        check_diff(&dataset, 1, 3, (100..110).collect(), (0..10).collect()).await;
+
+        // Compaction moves rows but doesn't change them
+        compact_files(
+            &mut dataset,
+            CompactionOptions {
+                target_rows_per_fragment: 1000,
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        let compacted = dataset.version().version;
+        assert_eq!(dataset.fragments().len(), 1);
+        let diff = dataset.diff(3, compacted).await.unwrap();
+        assert!(diff.is_empty());
+        assert!(!diff.schema_changed);
+        check_diff(
+            &dataset,
+            1,
+            compacted,
+            (100..110).collect(),
+            (0..10).collect(),
+        )
+        .await;
+
+        // Changes after a compaction are relative to the rows before it
+        dataset.delete("i = 50").await.unwrap();
+        check_diff(
+            &dataset,
+            2,
+            compacted + 1,
+            vec![],
+            (0..10).chain([50]).chain(110..120).collect(),
+        )
+        .await;
+
+        // Updates are deletes and inserts
+        let mut new_values = (40..45).collect::<Vec<_>>();
+        new_values.extend(200..203);
+        let job = MergeInsertBuilder::try_new(Arc::new(dataset.clone()), vec!["i".to_string()])
+            .unwrap()
+            .when_matched(WhenMatched::UpdateAll)
+            .try_build()
+            .unwrap();
+        let (dataset, _) = job
+            .execute(reader_to_stream(Box::new(batches(new_values))))
+            .await
+            .unwrap();
+        let version = dataset.version().version;
+        let mut inserted = (40..45).collect::<Vec<_>>();
+        inserted.extend(200..203);
+        check_diff(&dataset, version - 1, version, inserted, (40..45).collect()).await;
+        let mut inserted = (40..45).chain(100..110).collect::<Vec<_>>();
+        inserted.extend(200..203);
+        check_diff(
+            &dataset,
+            1,
+            version,
+            inserted,
+            (0..10).chain(40..45).chain([50]).collect(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_diff_overwrite() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        append(uri, (0..100).collect()).await;
+
+        // The new fragments reuse the ids of the fragments they replace
+        let mut dataset = Dataset::write(
+            batches((1000..1030).collect()),
+            uri,
+            Some(WriteParams {
+                mode: WriteMode::Overwrite,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(dataset.fragments()[0].id, 0);
+        check_diff(&dataset, 1, 2, (1000..1030).collect(), (0..100).collect()).await;
+
+        dataset.delete("i < 1010").await.unwrap();
+        check_diff(&dataset, 1, 3, (1010..1030).collect(), (0..100).collect()).await;
+
+        // Restoring brings back the fragments of the first version
+        let mut restored = dataset.checkout_version(1).await.unwrap();
+        restored.restore().await.unwrap();
+        assert_eq!(restored.version().version, 4);
+        check_diff(&restored, 1, 4, vec![], vec![]).await;
+        check_diff(&restored, 3, 4, (0..100).collect(), (1010..1030).collect()).await;
+    }
+
+    #[tokio::test]
+    async fn test_diff_schema_change() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let mut dataset = append(uri, (0..100).collect()).await;
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("j".into(), "i * 2".into())]),
+                None,
+            )
+            .await
+            .unwrap();
+        let diff = dataset.diff(1, 2).await.unwrap();
+        assert!(diff.schema_changed);
+        assert!(diff.is_empty());
+        assert!(!dataset.diff(2, 2).await.unwrap().schema_changed);
+
+        assert!(matches!(
+            dataset.diff(2, 1).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(dataset.diff(1, 3).await.is_err());
+    }
+}
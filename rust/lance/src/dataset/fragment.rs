@@ -6,25 +6,26 @@
 pub mod write;
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Range;
 use std::sync::Arc;
 
 use arrow::compute::concat_batches;
 use arrow_array::cast::as_primitive_array;
 use arrow_array::{RecordBatch, RecordBatchReader, StructArray, UInt32Array, UInt64Array};
-use arrow_schema::Schema as ArrowSchema;
+use arrow_schema::{DataType, Schema as ArrowSchema};
 use datafusion::logical_expr::Expr;
 use datafusion::scalar::ScalarValue;
 use futures::future::try_join_all;
 use futures::{join, stream, FutureExt, StreamExt, TryFutureExt, TryStreamExt};
 use lance_core::utils::deletion::DeletionVector;
 use lance_core::{datatypes::Schema, Error, Result};
-use lance_core::{ROW_ADDR, ROW_ADDR_FIELD, ROW_ID_FIELD};
+use lance_core::{ROW_ADDR, ROW_ADDR_FIELD, ROW_ID, ROW_ID_FIELD};
 use lance_encoding::decoder::DecoderMiddlewareChain;
+use lance_encoding::stats::DecodeStats;
 use lance_file::reader::{read_batch, FileReader};
 use lance_file::v2;
-use lance_file::v2::reader::ReaderProjection;
+use lance_file::v2::reader::{FileReaderOptions, ReaderProjection};
 use lance_io::object_store::ObjectStore;
 use lance_io::scheduler::ScanScheduler;
 use lance_io::ReadBatchParams;
@@ -234,6 +235,7 @@ impl GenericFileReader for FileReader {
 }
 
 mod v2_adapter {
+    use arrow::compute::CastOptions;
     use lance_encoding::decoder::FilterExpression;
 
     use super::*;
@@ -256,6 +258,23 @@ mod v2_adapter {
         }
 
         pub fn projection_from_lance(&self, schema: &Schema) -> ReaderProjection {
+            // Columns whose type was altered without rewriting the data are read with the
+            // type they were written with (see `read_tasks`)
+            let file_schema = self.reader.schema();
+            let mut schema = schema.clone();
+            for field in schema.fields.iter_mut() {
+                // Deferred casts only apply to top-level leaf columns.  A struct can differ
+                // from the file because only some of its children are projected.
+                if matches!(field.data_type(), DataType::Struct(_)) {
+                    continue;
+                }
+                if let Some(file_field) = file_schema.field_by_id(field.id) {
+                    if file_field.data_type() != field.data_type() {
+                        field.logical_type = file_field.logical_type.clone();
+                        field.children = file_field.children.clone();
+                    }
+                }
+            }
             let column_indices = schema
                 .fields
                 .iter()
@@ -269,56 +288,95 @@ mod v2_adapter {
                 })
                 .collect::<Vec<_>>();
             ReaderProjection {
-                schema: Arc::new(schema.clone()),
+                schema: Arc::new(schema),
                 column_indices,
             }
         }
-    }
 
-    #[async_trait::async_trait]
-    impl GenericFileReader for Reader {
-        /// Reads the requested range of rows from the file, returning as a stream
-        fn read_range_tasks(
+        fn read_tasks(
             &self,
-            range: Range<u64>,
+            params: ReadBatchParams,
             batch_size: u32,
-            projection: Arc<Schema>,
+            projection: &Schema,
         ) -> Result<ReadBatchTaskStream> {
-            let projection = self.projection_from_lance(projection.as_ref());
+            let reader_projection = self.projection_from_lance(projection);
+            let output_schema = (reader_projection.schema.as_ref() != projection)
+                .then(|| Arc::new(ArrowSchema::from(projection)));
             Ok(self
                 .reader
                 .read_tasks(
-                    ReadBatchParams::Range(range.start as usize..range.end as usize),
+                    params,
                     batch_size,
-                    &projection,
+                    &reader_projection,
                     FilterExpression::no_filter(),
                 )?
-                .map(|v2_task| ReadBatchTask {
-                    task: v2_task.task.map_err(Error::from).boxed(),
-                    num_rows: v2_task.num_rows,
+                .map(move |v2_task| {
+                    let output_schema = output_schema.clone();
+                    ReadBatchTask {
+                        task: v2_task
+                            .task
+                            .map_err(Error::from)
+                            .and_then(|batch| async move {
+                                match output_schema {
+                                    Some(output_schema) => cast_batch(batch, output_schema),
+                                    None => Ok(batch),
+                                }
+                            })
+                            .boxed(),
+                        num_rows: v2_task.num_rows,
+                    }
                 })
                 .boxed())
         }
+    }
+
+    fn cast_batch(batch: RecordBatch, output_schema: Arc<ArrowSchema>) -> Result<RecordBatch> {
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(output_schema.fields())
+            .map(|(column, field)| {
+                if column.data_type() == field.data_type() {
+                    Ok(column.clone())
+                } else {
+                    Ok(lance_arrow::cast::cast_with_options(
+                        column,
+                        field.data_type(),
+                        // Unsafe casts fail rather than introducing nulls, which would be
+                        // invalid for non-nullable fields
+                        &CastOptions {
+                            safe: false,
+                            ..Default::default()
+                        },
+                    )?)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(output_schema, columns)?)
+    }
+
+    #[async_trait::async_trait]
+    impl GenericFileReader for Reader {
+        /// Reads the requested range of rows from the file, returning as a stream
+        fn read_range_tasks(
+            &self,
+            range: Range<u64>,
+            batch_size: u32,
+            projection: Arc<Schema>,
+        ) -> Result<ReadBatchTaskStream> {
+            self.read_tasks(
+                ReadBatchParams::Range(range.start as usize..range.end as usize),
+                batch_size,
+                projection.as_ref(),
+            )
+        }
 
         fn read_all_tasks(
             &self,
             batch_size: u32,
             projection: Arc<Schema>,
         ) -> Result<ReadBatchTaskStream> {
-            let projection = self.projection_from_lance(projection.as_ref());
-            Ok(self
-                .reader
-                .read_tasks(
-                    ReadBatchParams::RangeFull,
-                    batch_size,
-                    &projection,
-                    FilterExpression::no_filter(),
-                )?
-                .map(|v2_task| ReadBatchTask {
-                    task: v2_task.task.map_err(Error::from).boxed(),
-                    num_rows: v2_task.num_rows,
-                })
-                .boxed())
+            self.read_tasks(ReadBatchParams::RangeFull, batch_size, projection.as_ref())
         }
 
         fn take_all_tasks(
@@ -328,20 +386,11 @@ mod v2_adapter {
             projection: Arc<Schema>,
         ) -> Result<ReadBatchTaskStream> {
             let indices = UInt32Array::from(indices.to_vec());
-            let projection = self.projection_from_lance(projection.as_ref());
-            Ok(self
-                .reader
-                .read_tasks(
-                    ReadBatchParams::Indices(indices),
-                    batch_size,
-                    &projection,
-                    FilterExpression::no_filter(),
-                )?
-                .map(|v2_task| ReadBatchTask {
-                    task: v2_task.task.map_err(Error::from).boxed(),
-                    num_rows: v2_task.num_rows,
-                })
-                .boxed())
+            self.read_tasks(
+                ReadBatchParams::Indices(indices),
+                batch_size,
+                projection.as_ref(),
+            )
         }
 
         /// Return the number of rows in the file
@@ -418,6 +467,12 @@ impl FileFragment {
         &self.metadata
     }
 
+    /// The custom key/values recorded for this fragment (see
+    /// [`crate::dataset::WriteParams::fragment_metadata`])
+    pub fn custom_metadata(&self) -> &HashMap<String, String> {
+        &self.metadata.custom_metadata
+    }
+
     /// The id of this [`FileFragment`].
     pub fn id(&self) -> usize {
         self.metadata.id as usize
@@ -445,7 +500,27 @@ impl FileFragment {
         with_row_address: bool,
         scan_scheduler: Option<Arc<ScanScheduler>>,
     ) -> Result<FragmentReader> {
-        let open_files = self.open_readers(projection, scan_scheduler);
+        self.open_with_decode_stats(
+            projection,
+            with_row_id,
+            with_row_address,
+            scan_scheduler,
+            None,
+        )
+        .await
+    }
+
+    /// Opens the fragment, see [`Self::open`], reporting the I/O and decode work of reads
+    /// from v2 data files to `decode_stats`
+    pub(crate) async fn open_with_decode_stats(
+        &self,
+        projection: &Schema,
+        with_row_id: bool,
+        with_row_address: bool,
+        scan_scheduler: Option<Arc<ScanScheduler>>,
+        decode_stats: Option<Arc<DecodeStats>>,
+    ) -> Result<FragmentReader> {
+        let open_files = self.open_readers(projection, scan_scheduler, decode_stats);
         let deletion_vec_load =
             self.load_deletion_vector(&self.dataset.object_store, &self.metadata);
 
@@ -505,6 +580,7 @@ impl FileFragment {
         data_file: &DataFile,
         projection: Option<&Schema>,
         scan_scheduler: Option<Arc<ScanScheduler>>,
+        decode_stats: Option<Arc<DecodeStats>>,
     ) -> Result<Option<(Box<dyn GenericFileReader>, Arc<Schema>)>> {
         let full_schema = self.dataset.schema();
         // The data file may contain fields that are not part of the dataset any longer, remove those
@@ -516,7 +592,10 @@ impl FileFragment {
         if data_file.is_legacy_file() {
             let max_field_id = data_file.fields.iter().max().unwrap();
             if !schema_per_file.fields.is_empty() {
-                let path = self.dataset.data_dir().child(data_file.path.as_str());
+                let path = self
+                    .dataset
+                    .data_file_dir(data_file)
+                    .child(data_file.path.as_str());
                 let field_id_offset = Self::get_field_id_offset(data_file);
                 let reader = FileReader::try_new_with_fragment_id(
                     &self.dataset.object_store,
@@ -538,15 +617,23 @@ impl FileFragment {
         } else if schema_per_file.fields.is_empty() {
             Ok(None)
         } else {
-            let path = self.dataset.data_dir().child(data_file.path.as_str());
+            let path = self
+                .dataset
+                .data_file_dir(data_file)
+                .child(data_file.path.as_str());
             let store_scheduler = scan_scheduler
                 .unwrap_or_else(|| ScanScheduler::new(self.dataset.object_store.clone()));
             let file_scheduler = store_scheduler.open_file(&path).await?;
+            let options = FileReaderOptions {
+                decode_stats,
+                ..Default::default()
+            };
             let reader = Arc::new(
-                v2::reader::FileReader::try_open(
+                v2::reader::FileReader::try_open_with_options(
                     file_scheduler,
                     None,
                     DecoderMiddlewareChain::default(),
+                    &options,
                 )
                 .await?,
             );
@@ -573,11 +660,17 @@ impl FileFragment {
         &self,
         projection: &Schema,
         scan_scheduler: Option<Arc<ScanScheduler>>,
+        decode_stats: Option<Arc<DecodeStats>>,
     ) -> Result<Vec<(Box<dyn GenericFileReader>, Arc<Schema>)>> {
         let mut opened_files = vec![];
         for data_file in &self.metadata.files {
             if let Some((reader, schema)) = self
-                .open_reader(data_file, Some(projection), scan_scheduler.clone())
+                .open_reader(
+                    data_file,
+                    Some(projection),
+                    scan_scheduler.clone(),
+                    decode_stats.clone(),
+                )
                 .await?
             {
                 opened_files.push((reader, schema));
@@ -672,7 +765,7 @@ impl FileFragment {
         // Just open any file. All of them should have same size.
         let some_file = &self.metadata.files[0];
         let (reader, _) = self
-            .open_reader(some_file, None, None)
+            .open_reader(some_file, None, None, None)
             .await?
             .ok_or_else(|| Error::Internal {
                 message: format!(
@@ -761,16 +854,18 @@ impl FileFragment {
         }
 
         for data_file in &self.metadata.files {
-            data_file.validate(&self.dataset.data_dir())?;
+            data_file.validate(&self.dataset.data_file_dir(data_file))?;
         }
 
         let get_lengths = self.metadata.files.iter().map(|data_file| async move {
             let (reader, _) = self
-                .open_reader(data_file, None, None)
+                .open_reader(data_file, None, None, None)
                 .await?
                 .ok_or_else(|| {
                     Error::corrupt_file(
-                        self.dataset.data_dir().child(data_file.path.clone()),
+                        self.dataset
+                            .data_file_dir(data_file)
+                            .child(data_file.path.clone()),
                         "did not have any fields in common with the dataset schema",
                         location!(),
                     )
@@ -791,7 +886,10 @@ impl FileFragment {
         let expected_length = get_lengths.first().unwrap_or(&0);
         for (length, data_file) in get_lengths.iter().zip(self.metadata.files.iter()) {
             if length != expected_length {
-                let path = self.dataset.data_dir().child(data_file.path.as_str());
+                let path = self
+                    .dataset
+                    .data_file_dir(data_file)
+                    .child(data_file.path.as_str());
                 return Err(Error::corrupt_file(
                     path,
                     format!(
@@ -961,9 +1059,31 @@ impl FileFragment {
         row_offsets: &[u32],
         projection: &Schema,
         with_row_address: bool,
+    ) -> Result<RecordBatch> {
+        self.take_rows_with_decode_stats(row_offsets, projection, with_row_address, None, None)
+            .await
+    }
+
+    /// Take rows based on internal local row offsets, see [`Self::take_rows`], reading
+    /// v2 data files with `scan_scheduler` and reporting the decode work to `decode_stats`
+    pub(crate) async fn take_rows_with_decode_stats(
+        &self,
+        row_offsets: &[u32],
+        projection: &Schema,
+        with_row_address: bool,
+        scan_scheduler: Option<Arc<ScanScheduler>>,
+        decode_stats: Option<Arc<DecodeStats>>,
     ) -> Result<RecordBatch> {
         // TODO: support taking row addresses
-        let reader = self.open(projection, false, with_row_address, None).await?;
+        let reader = self
+            .open_with_decode_stats(
+                projection,
+                false,
+                with_row_address,
+                scan_scheduler,
+                decode_stats,
+            )
+            .await?;
 
         if row_offsets.len() > 1 && Self::row_ids_contiguous(row_offsets) {
             let range =
@@ -1052,6 +1172,18 @@ impl FileFragment {
     /// fragment with the updated deletion vector. This must be persisted to
     /// the manifest.
     pub async fn delete(self, predicate: &str) -> Result<Option<Self>> {
+        Ok(self.delete_rows(predicate, false).await?.fragment)
+    }
+
+    /// Delete rows from the fragment, reporting how many rows were deleted
+    ///
+    /// If `with_row_ids` is true then the `_rowid` and `_rowaddr` of the deleted rows
+    /// are collected as well.
+    pub(crate) async fn delete_rows(
+        self,
+        predicate: &str,
+        with_row_ids: bool,
+    ) -> Result<FragmentDeletion> {
         // Load existing deletion vector
         let mut deletion_vector = read_deletion_file(
             &self.dataset.base,
@@ -1068,15 +1200,16 @@ impl FileFragment {
 
         let predicate_lower = predicate.trim().to_lowercase();
         if predicate_lower == "true" {
-            return Ok(None);
+            return self.delete_all(with_row_ids).await;
         } else if predicate_lower == "false" {
-            return Ok(Some(self));
+            return Ok(FragmentDeletion::unchanged(self));
         }
 
-        scanner
-            .with_row_address()
-            .filter(predicate)?
-            .project::<&str>(&[])?;
+        scanner.with_row_address().filter(predicate)?;
+        if with_row_ids {
+            scanner.with_row_id();
+        }
+        scanner.project::<&str>(&[])?;
 
         // if predicate is `true`, delete the whole fragment
         // else if predicate is `false`, filter the predicate
@@ -1084,14 +1217,15 @@ impl FileFragment {
         // occurred so we also catch expressions that are equivalent to `true`
         if let Some(predicate) = &scanner.filter {
             if matches!(predicate, Expr::Literal(ScalarValue::Boolean(Some(false)))) {
-                return Ok(Some(self));
+                return Ok(FragmentDeletion::unchanged(self));
             }
             if matches!(predicate, Expr::Literal(ScalarValue::Boolean(Some(true)))) {
-                return Ok(None);
+                return self.delete_all(with_row_ids).await;
             }
         }
 
         // As we get row addrs, add them into our deletion vector
+        let mut deleted_row_ids = Vec::new();
         scanner
             .try_into_stream()
             .await?
@@ -1105,16 +1239,48 @@ impl FileFragment {
                 let local_row_ids = int_array.values().iter().map(|v| *v as u32);
 
                 deletion_vector.extend(local_row_ids);
+                if with_row_ids {
+                    deleted_row_ids.push(FragmentDeletion::row_ids_batch(&batch));
+                }
                 futures::future::ready(Ok(()))
             })
             .await?;
 
         // If we haven't deleted any additional rows, we can return the fragment as-is.
-        if deletion_vector.len() == starting_length {
-            return Ok(Some(self));
+        let num_deleted_rows = (deletion_vector.len() - starting_length) as u64;
+        if num_deleted_rows == 0 {
+            return Ok(FragmentDeletion::unchanged(self));
         }
 
-        self.write_deletions(deletion_vector).await
+        Ok(FragmentDeletion {
+            fragment: self.write_deletions(deletion_vector).await?,
+            num_deleted_rows,
+            deleted_row_ids,
+        })
+    }
+
+    // Deletes every remaining row of the fragment
+    async fn delete_all(self, with_row_ids: bool) -> Result<FragmentDeletion> {
+        let num_deleted_rows = self.count_rows().await? as u64;
+        let mut deleted_row_ids = Vec::new();
+        if with_row_ids {
+            let mut scanner = self.scan();
+            scanner
+                .with_row_id()
+                .with_row_address()
+                .project::<&str>(&[])?;
+            deleted_row_ids = scanner
+                .try_into_stream()
+                .await?
+                .map_ok(|batch| FragmentDeletion::row_ids_batch(&batch))
+                .try_collect()
+                .await?;
+        }
+        Ok(FragmentDeletion {
+            fragment: None,
+            num_deleted_rows,
+            deleted_row_ids,
+        })
     }
 
     pub(crate) async fn extend_deletions(
@@ -1171,6 +1337,37 @@ impl FileFragment {
     }
 }
 
+/// The result of deleting rows from a single fragment
+pub(crate) struct FragmentDeletion {
+    /// The updated fragment, or None if every row of the fragment was deleted
+    pub fragment: Option<FileFragment>,
+    /// The number of rows deleted, rows that were already deleted are not counted
+    pub num_deleted_rows: u64,
+    /// Batches of the `_rowid` and `_rowaddr` of the deleted rows, if requested
+    pub deleted_row_ids: Vec<RecordBatch>,
+}
+
+impl FragmentDeletion {
+    fn unchanged(fragment: FileFragment) -> Self {
+        Self {
+            fragment: Some(fragment),
+            num_deleted_rows: 0,
+            deleted_row_ids: Vec::new(),
+        }
+    }
+
+    fn row_ids_batch(batch: &RecordBatch) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                ROW_ID_FIELD.clone(),
+                ROW_ADDR_FIELD.clone(),
+            ])),
+            vec![batch[ROW_ID].clone(), batch[ROW_ADDR].clone()],
+        )
+        .unwrap()
+    }
+}
+
 impl From<FileFragment> for Fragment {
     fn from(fragment: FileFragment) -> Self {
         fragment.metadata
@@ -1632,6 +1829,25 @@ impl FragmentReader {
         )
     }
 
+    /// Read the fragment, skipping the first `offset` rows.
+    ///
+    /// `offset` counts rows that have not been deleted.  The deletion vector is
+    /// used to translate it into a physical start row so the skipped rows are
+    /// never decoded.
+    pub fn read_from_offset(&self, offset: usize, batch_size: u32) -> Result<ReadBatchFutStream> {
+        let mut start = offset;
+        if let Some(deletion_vec) = self.deletion_vec.as_ref() {
+            for deleted in deletion_vec.to_sorted_iter() {
+                if deleted as usize > start {
+                    break;
+                }
+                start += 1;
+            }
+        }
+        let start = start.min(self.num_physical_rows);
+        self.read_range(start as u32..self.num_physical_rows as u32, batch_size)
+    }
+
     // Legacy function that reads a range of data and concatenates the results
     // into a single batch
     //
@@ -1675,8 +1891,9 @@ impl FragmentReader {
 mod tests {
 
     use arrow_arith::numeric::mul;
+    use arrow_array::cast::AsArray;
     use arrow_array::{ArrayRef, Int32Array, RecordBatchIterator, StringArray};
-    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
     use lance_core::ROW_ID;
     use pretty_assertions::assert_eq;
     use rstest::rstest;
@@ -1831,6 +2048,63 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_fragment_read_struct_children_v2() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let a_field = ArrowField::new("a", DataType::Int32, true);
+        let b_field = ArrowField::new("b", DataType::Utf8, true);
+        let struct_type = DataType::Struct(vec![a_field.clone(), b_field.clone()].into());
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "st",
+            struct_type,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StructArray::from(vec![
+                (
+                    Arc::new(a_field),
+                    Arc::new(Int32Array::from_iter_values(0..10)) as ArrayRef,
+                ),
+                (
+                    Arc::new(b_field),
+                    Arc::new(StringArray::from_iter_values(
+                        (0..10).map(|v| format!("b-{}", v)),
+                    )) as ArrayRef,
+                ),
+            ]))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            use_legacy_format: false,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        // Only one child of the struct is projected, so the struct type differs
+        // from the one in the file.
+        let fragment = &dataset.get_fragments()[0];
+        let projection = dataset.schema().project(&["st.b"]).unwrap();
+        let batch = fragment
+            .open(&projection, false, false, None)
+            .await
+            .unwrap()
+            .legacy_read_range_as_batch(0..10)
+            .await
+            .unwrap();
+        let st = batch["st"].as_struct();
+        assert_eq!(st.num_columns(), 1);
+        assert_eq!(
+            st.column_by_name("b").unwrap().as_ref(),
+            &StringArray::from_iter_values((0..10).map(|v| format!("b-{}", v)))
+        );
+    }
+
     #[tokio::test]
     async fn test_out_of_range() {
         let test_dir = tempdir().unwrap();
@@ -87,6 +87,7 @@ impl<'a> FragmentCreateBuilder<'a> {
         .await?;
         let filename = format!("{}.lance", Uuid::new_v4());
         let mut fragment = Fragment::new(id);
+        fragment.custom_metadata = params.fragment_metadata.clone();
         let full_path = base_path.child(DATA_DIR).child(filename.clone());
         let obj_writer = object_store.create(&full_path).await?;
         let mut writer = lance_file::v2::writer::FileWriter::try_new(
@@ -107,7 +108,7 @@ impl<'a> FragmentCreateBuilder<'a> {
             writer.write_batches(batch_chunk.iter()).await?;
         }
 
-        fragment.physical_rows = Some(writer.finish().await? as usize);
+        fragment.physical_rows = Some(writer.finish().await?.num_rows as usize);
 
         let field_ids = writer
             .field_id_to_column_indices()
@@ -157,6 +158,7 @@ impl<'a> FragmentCreateBuilder<'a> {
         .await?;
         let filename = format!("{}.lance", Uuid::new_v4());
         let mut fragment = Fragment::with_file_legacy(id, &filename, &schema, None);
+        fragment.custom_metadata = params.fragment_metadata.clone();
         let full_path = base_path.child(DATA_DIR).child(filename.clone());
         let mut writer = FileWriter::<ManifestDescribing>::try_new(
             &object_store,
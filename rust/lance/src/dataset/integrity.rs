@@ -0,0 +1,564 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Checks of the files of a dataset, see [`Dataset::validate_integrity`]
+//!
+//! Unlike [`Dataset::validate`], which checks the invariants of the manifest and stops at
+//! the first problem, this checks the files themselves (e.g. after an incident with the
+//! object store) and reports every problem that is found.
+
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use lance_core::datatypes::Field;
+use lance_encoding::checksum;
+use lance_encoding::decoder::DecoderMiddlewareChain;
+use lance_file::reader::FileReader;
+use lance_file::v2;
+use lance_io::scheduler::ScanScheduler;
+use lance_table::format::DataFile;
+use lance_table::io::deletion::{deletion_file_path, read_deletion_file};
+use object_store::path::Path;
+
+use super::fragment::FileFragment;
+use super::Dataset;
+use crate::Result;
+
+/// A problem found by [`Dataset::validate_integrity`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityViolation {
+    /// A data file does not exist
+    MissingFile { fragment_id: u64, path: Path },
+    /// The metadata of a data file cannot be read (e.g. the file is truncated)
+    UnreadableFile {
+        fragment_id: u64,
+        path: Path,
+        message: String,
+    },
+    /// A buffer of a page (or of the column, if `page` is `None`) ends past the end of the file
+    ///
+    /// In files of the legacy format the `column` is the field id and the `page` is the
+    /// batch.
+    BufferOutOfBounds {
+        fragment_id: u64,
+        path: Path,
+        column: usize,
+        page: Option<usize>,
+        buffer_end: u64,
+        file_size: u64,
+    },
+    /// The number of rows of a data file (or of one of its columns) doesn't match the
+    /// number of rows of the fragment
+    RowCountMismatch {
+        fragment_id: u64,
+        path: Path,
+        column: Option<usize>,
+        expected: u64,
+        actual: u64,
+    },
+    /// The deletion file cannot be read or deletes rows that are not in the fragment
+    InvalidDeletionFile {
+        fragment_id: u64,
+        path: Path,
+        message: String,
+    },
+    /// The bytes of a page buffer don't match the checksum recorded when it was written
+    ///
+    /// This is only checked by a deep validation.
+    ChecksumMismatch {
+        fragment_id: u64,
+        path: Path,
+        column: usize,
+        page: usize,
+        buffer: usize,
+    },
+    /// Reading the data of the fragment failed
+    ///
+    /// This is only checked by a deep validation.
+    DecodeFailed { fragment_id: u64, message: String },
+}
+
+impl IntegrityViolation {
+    /// The fragment with the problem
+    pub fn fragment_id(&self) -> u64 {
+        match self {
+            Self::MissingFile { fragment_id, .. }
+            | Self::UnreadableFile { fragment_id, .. }
+            | Self::BufferOutOfBounds { fragment_id, .. }
+            | Self::RowCountMismatch { fragment_id, .. }
+            | Self::InvalidDeletionFile { fragment_id, .. }
+            | Self::ChecksumMismatch { fragment_id, .. }
+            | Self::DecodeFailed { fragment_id, .. } => *fragment_id,
+        }
+    }
+}
+
+/// The result of [`Dataset::validate_integrity`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub num_fragments: usize,
+    pub num_data_files: usize,
+    pub violations: Vec<IntegrityViolation>,
+}
+
+impl IntegrityReport {
+    /// Returns true if no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+// The number of columns a field is written to in the v2 format
+fn num_v2_columns(field: &Field) -> usize {
+    1 + field.children.iter().map(num_v2_columns).sum::<usize>()
+}
+
+// Checks the buffers and the row counts of the columns of a file in the v2 format.
+// Returns the number of rows of the file, if its metadata could be read.
+async fn check_v2_file(
+    fragment: &FileFragment,
+    path: &Path,
+    file_size: u64,
+    deep: bool,
+    scheduler: &Arc<ScanScheduler>,
+    violations: &mut Vec<IntegrityViolation>,
+) -> Result<Option<u64>> {
+    let dataset = fragment.dataset();
+    let fragment_id = fragment.id() as u64;
+    let file_scheduler = scheduler.open_file(path).await?;
+    let reader = match v2::reader::FileReader::try_open(
+        file_scheduler,
+        None,
+        DecoderMiddlewareChain::default(),
+    )
+    .await
+    {
+        Ok(reader) => reader,
+        Err(err) => {
+            violations.push(IntegrityViolation::UnreadableFile {
+                fragment_id,
+                path: path.clone(),
+                message: err.to_string(),
+            });
+            return Ok(None);
+        }
+    };
+    let metadata = reader.metadata();
+
+    let mut in_bounds = true;
+    for (column_idx, column) in metadata.column_infos.iter().enumerate() {
+        let page_buffers = column
+            .page_infos
+            .iter()
+            .enumerate()
+            .flat_map(|(page_idx, page)| {
+                page.buffer_offsets_and_sizes
+                    .iter()
+                    .map(move |buffer| (Some(page_idx), buffer))
+            });
+        let column_buffers = column
+            .buffer_offsets_and_sizes
+            .iter()
+            .map(|buffer| (None, buffer));
+        for (page, (offset, size)) in page_buffers.chain(column_buffers) {
+            if offset + size > file_size {
+                in_bounds = false;
+                violations.push(IntegrityViolation::BufferOutOfBounds {
+                    fragment_id,
+                    path: path.clone(),
+                    column: column_idx,
+                    page,
+                    buffer_end: offset + size,
+                    file_size,
+                });
+            }
+        }
+    }
+
+    // The pages of a top-level column without children cover every row.  This is not the
+    // case for the columns of lists and the header pages of structs.
+    if let Some(expected) = fragment.metadata().physical_rows {
+        let mut column_idx = 0;
+        for field in &metadata.file_schema.fields {
+            if let Some(column) = metadata.column_infos.get(column_idx) {
+                let actual = column.page_infos.iter().map(|page| page.num_rows).sum();
+                if field.children.is_empty() && actual != expected as u64 {
+                    violations.push(IntegrityViolation::RowCountMismatch {
+                        fragment_id,
+                        path: path.clone(),
+                        column: Some(column_idx),
+                        expected: expected as u64,
+                        actual,
+                    });
+                }
+            }
+            column_idx += num_v2_columns(field);
+        }
+    }
+
+    if deep && in_bounds {
+        let object_reader = dataset.object_store.open(path).await?;
+        for (column_idx, column) in metadata.column_metadatas.iter().enumerate() {
+            for (page_idx, page) in column.pages.iter().enumerate() {
+                // Files written by older versions have no checksums
+                for (buffer_idx, expected) in page.buffer_checksums.iter().enumerate() {
+                    let offset = page.buffer_offsets[buffer_idx] as usize;
+                    let size = page.buffer_sizes[buffer_idx] as usize;
+                    let bytes = object_reader.get_range(offset..offset + size).await?;
+                    let actual = checksum::checksum_parallel(&bytes, checksum::CHECKSUM_BLOCK_SIZE);
+                    if actual != *expected {
+                        violations.push(IntegrityViolation::ChecksumMismatch {
+                            fragment_id,
+                            path: path.clone(),
+                            column: column_idx,
+                            page: page_idx,
+                            buffer: buffer_idx,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    Ok(Some(metadata.num_rows))
+}
+
+async fn check_data_file(
+    fragment: &FileFragment,
+    data_file: &DataFile,
+    deep: bool,
+    scheduler: &Arc<ScanScheduler>,
+    violations: &mut Vec<IntegrityViolation>,
+) -> Result<()> {
+    let dataset = fragment.dataset();
+    let fragment_id = fragment.id() as u64;
+    let path = dataset
+        .data_file_dir(data_file)
+        .child(data_file.path.as_str());
+    if !dataset.object_store.exists(&path).await? {
+        violations.push(IntegrityViolation::MissingFile { fragment_id, path });
+        return Ok(());
+    }
+    let file_size = dataset.object_store.size(&path).await? as u64;
+
+    let num_rows = if data_file.is_legacy_file() {
+        let reader = FileReader::try_new_with_fragment_id(
+            &dataset.object_store,
+            &path,
+            data_file.schema(dataset.schema()),
+            fragment.id() as u32,
+            data_file.fields.first().copied().unwrap_or_default(),
+            data_file.fields.iter().copied().max().unwrap_or_default(),
+            None,
+        )
+        .await;
+        match reader {
+            Ok(reader) => {
+                for (field_id, batch_id, page_end) in reader.page_ends() {
+                    if page_end > file_size {
+                        violations.push(IntegrityViolation::BufferOutOfBounds {
+                            fragment_id,
+                            path: path.clone(),
+                            column: field_id as usize,
+                            page: Some(batch_id as usize),
+                            buffer_end: page_end,
+                            file_size,
+                        });
+                    }
+                }
+                Some(reader.len() as u64)
+            }
+            Err(err) => {
+                violations.push(IntegrityViolation::UnreadableFile {
+                    fragment_id,
+                    path: path.clone(),
+                    message: err.to_string(),
+                });
+                None
+            }
+        }
+    } else {
+        check_v2_file(fragment, &path, file_size, deep, scheduler, violations).await?
+    };
+
+    if let (Some(expected), Some(actual)) = (fragment.metadata().physical_rows, num_rows) {
+        if expected as u64 != actual {
+            violations.push(IntegrityViolation::RowCountMismatch {
+                fragment_id,
+                path,
+                column: None,
+                expected: expected as u64,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+async fn check_deletion_file(fragment: &FileFragment, violations: &mut Vec<IntegrityViolation>) {
+    let dataset = fragment.dataset();
+    let fragment_id = fragment.id() as u64;
+    let Some(deletion_file) = &fragment.metadata().deletion_file else {
+        return;
+    };
+    let path = deletion_file_path(&dataset.base, fragment_id, deletion_file);
+    let mut invalid = |message: String| {
+        violations.push(IntegrityViolation::InvalidDeletionFile {
+            fragment_id,
+            path: path.clone(),
+            message,
+        })
+    };
+    let deletion_vector =
+        match read_deletion_file(&dataset.base, fragment.metadata(), &dataset.object_store).await {
+            Ok(deletion_vector) => deletion_vector.unwrap_or_default(),
+            Err(err) => return invalid(err.to_string()),
+        };
+    if let Some(num_deleted_rows) = deletion_file.num_deleted_rows {
+        if num_deleted_rows != deletion_vector.len() {
+            invalid(format!(
+                "the fragment records {} deleted rows but the file deletes {}",
+                num_deleted_rows,
+                deletion_vector.len()
+            ));
+        }
+    }
+    if let Some(physical_rows) = fragment.metadata().physical_rows {
+        if let Some(max_offset) = deletion_vector.into_sorted_iter().last() {
+            if max_offset as usize >= physical_rows {
+                invalid(format!(
+                    "row offset {} is deleted but the fragment has {} rows",
+                    max_offset, physical_rows
+                ));
+            }
+        }
+    }
+}
+
+async fn check_fragment(
+    fragment: FileFragment,
+    deep: bool,
+    scheduler: Arc<ScanScheduler>,
+) -> Result<Vec<IntegrityViolation>> {
+    let mut violations = Vec::new();
+    for data_file in &fragment.metadata().files {
+        check_data_file(&fragment, data_file, deep, &scheduler, &mut violations).await?;
+    }
+    check_deletion_file(&fragment, &mut violations).await;
+
+    // Decoding a fragment with missing or unreadable files would only repeat those problems
+    if deep && violations.is_empty() {
+        let scan = async {
+            let mut stream = fragment.scan().try_into_stream().await?;
+            while stream.try_next().await?.is_some() {}
+            Result::Ok(())
+        };
+        if let Err(err) = scan.await {
+            violations.push(IntegrityViolation::DecodeFailed {
+                fragment_id: fragment.id() as u64,
+                message: err.to_string(),
+            });
+        }
+    }
+    Ok(violations)
+}
+
+/// Checks the files of every fragment of `dataset`, see [`Dataset::validate_integrity`]
+pub async fn validate_integrity(dataset: &Dataset, deep: bool) -> Result<IntegrityReport> {
+    let scheduler = ScanScheduler::new(dataset.object_store.clone());
+    let fragments = dataset.get_fragments();
+    let num_fragments = fragments.len();
+    let num_data_files = fragments.iter().map(|f| f.metadata().files.len()).sum();
+    let mut violations = futures::stream::iter(fragments)
+        .map(|fragment| check_fragment(fragment, deep, scheduler.clone()))
+        .buffered(dataset.object_store.io_parallelism()? as usize)
+        .try_concat()
+        .await?;
+    violations.sort_by_key(|violation| violation.fragment_id());
+    Ok(IntegrityReport {
+        num_fragments,
+        num_data_files,
+        violations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::types::{Float32Type, Int64Type};
+    use lance_datagen::{array, gen, BatchCount, Dimension, RowCount};
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::dataset::transaction::Operation;
+    use crate::dataset::WriteParams;
+
+    async fn create_dataset(test_uri: &str, use_legacy_format: bool) -> Dataset {
+        let data = gen()
+            .col("i", array::step::<Int64Type>())
+            .col("vec", array::rand_vec::<Float32Type>(Dimension::from(8)))
+            .into_reader_rows(RowCount::from(100), BatchCount::from(3));
+        let mut dataset = Dataset::write(
+            data,
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 100,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        dataset.delete("i % 10 = 0").await.unwrap();
+        dataset
+    }
+
+    // The local path of a data file of the fragment
+    fn data_file_path(
+        test_uri: &str,
+        dataset: &Dataset,
+        fragment_idx: usize,
+    ) -> std::path::PathBuf {
+        std::path::Path::new(test_uri)
+            .join("data")
+            .join(&dataset.get_fragments()[fragment_idx].metadata().files[0].path)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_valid_dataset(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_dataset(test_uri, use_legacy_format).await;
+        for deep in [false, true] {
+            let report = dataset.validate_integrity(deep).await.unwrap();
+            assert_eq!(report.num_fragments, 3);
+            assert_eq!(report.num_data_files, 3);
+            assert!(report.is_valid(), "{:?}", report.violations);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_files() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_dataset(test_uri, false).await;
+
+        // Fragment 0 is deleted, fragment 1 is truncated and a data page of fragment 2 has
+        // a flipped byte
+        std::fs::remove_file(data_file_path(test_uri, &dataset, 0)).unwrap();
+        let truncated = data_file_path(test_uri, &dataset, 1);
+        let len = std::fs::metadata(&truncated).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&truncated)
+            .unwrap()
+            .set_len(len - 100)
+            .unwrap();
+        let flipped = data_file_path(test_uri, &dataset, 2);
+        let path = dataset
+            .data_dir()
+            .child(dataset.get_fragments()[2].metadata().files[0].path.as_str());
+        let scheduler = ScanScheduler::new(dataset.object_store.clone());
+        let reader = v2::reader::FileReader::try_open(
+            scheduler.open_file(&path).await.unwrap(),
+            None,
+            DecoderMiddlewareChain::default(),
+        )
+        .await
+        .unwrap();
+        // The first buffer of the first page of the vector column
+        let offset = reader.metadata().column_infos[1].page_infos[0].buffer_offsets_and_sizes[0].0;
+        let mut bytes = std::fs::read(&flipped).unwrap();
+        bytes[offset as usize + 10] ^= 0xFF;
+        std::fs::write(&flipped, bytes).unwrap();
+
+        let report = dataset.validate_integrity(false).await.unwrap();
+        assert_eq!(report.violations.len(), 2, "{:?}", report.violations);
+        assert!(matches!(
+            &report.violations[0],
+            IntegrityViolation::MissingFile { fragment_id: 0, .. }
+        ));
+        assert!(matches!(
+            &report.violations[1],
+            IntegrityViolation::UnreadableFile { fragment_id: 1, .. }
+        ));
+
+        let report = dataset.validate_integrity(true).await.unwrap();
+        assert_eq!(report.violations.len(), 3, "{:?}", report.violations);
+        assert_eq!(
+            report.violations[2],
+            IntegrityViolation::ChecksumMismatch {
+                fragment_id: 2,
+                path,
+                column: 1,
+                page: 0,
+                buffer: 0,
+            }
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_row_count_mismatch(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_dataset(test_uri, use_legacy_format).await;
+
+        // Fragment 0 claims to have more rows than its data file
+        let mut fragments = dataset
+            .get_fragments()
+            .iter()
+            .map(|fragment| fragment.metadata().clone())
+            .collect::<Vec<_>>();
+        fragments[0].physical_rows = Some(120);
+        let operation = Operation::Overwrite {
+            schema: dataset.schema().clone(),
+            fragments,
+        };
+        let dataset = Dataset::commit(test_uri, operation, None, None, None)
+            .await
+            .unwrap();
+
+        let report = dataset.validate_integrity(false).await.unwrap();
+        let mismatched_columns = report
+            .violations
+            .iter()
+            .map(|violation| match violation {
+                IntegrityViolation::RowCountMismatch {
+                    fragment_id: 0,
+                    column,
+                    expected: 120,
+                    actual: 100,
+                    ..
+                } => *column,
+                violation => panic!("unexpected violation {:?}", violation),
+            })
+            .collect::<Vec<_>>();
+        if use_legacy_format {
+            assert_eq!(mismatched_columns, vec![None]);
+        } else {
+            // Both columns of the file are reported
+            assert_eq!(mismatched_columns, vec![Some(0), Some(1), None]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_deletion_file() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_dataset(test_uri, false).await;
+
+        // The deletion file of fragment 1 is gone
+        let fragment = dataset.get_fragments()[1].metadata().clone();
+        let deletion_file = fragment.deletion_file.as_ref().unwrap();
+        let path = deletion_file_path(&dataset.base, fragment.id, deletion_file);
+        dataset.object_store.delete(&path).await.unwrap();
+
+        let report = dataset.validate_integrity(false).await.unwrap();
+        assert_eq!(report.violations.len(), 1, "{:?}", report.violations);
+        assert!(matches!(
+            &report.violations[0],
+            IntegrityViolation::InvalidDeletionFile { fragment_id: 1, .. }
+        ));
+    }
+}
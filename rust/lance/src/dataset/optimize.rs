@@ -23,6 +23,10 @@
 //! that fragment will be remapped.  However, we cannot combine indexed fragments
 //! with unindexed fragments.
 //!
+//! Compacted fragments keep only the custom fragment metadata (see
+//! [`crate::dataset::WriteParams::fragment_metadata`]) that all of the fragments they
+//! replace share with the same value.  Any other key/value is dropped.
+//!
 //! ```rust
 //! # use std::sync::Arc;
 //! # use tokio::runtime::Runtime;
@@ -81,10 +85,13 @@
 //! you wish. As long as the tasks don't rewrite any of the same fragments,
 //! they can be committed in any order.
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::{AddAssign, Range};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
+use arrow_array::cast::AsArray;
+use arrow_array::types::UInt64Type;
 use async_trait::async_trait;
 use datafusion::physical_plan::SendableRecordBatchStream;
 use futures::{StreamExt, TryStreamExt};
@@ -95,14 +102,23 @@ use uuid::Uuid;
 
 use crate::io::commit::{commit_transaction, migrate_fragments};
 use crate::Dataset;
-use crate::Result;
+use crate::{Error, Result};
 use lance_core::utils::address::RowAddress;
+use lance_core::ROW_ADDR;
+use lance_table::feature_flags::should_use_legacy_format;
 use lance_table::format::Fragment;
+use lance_table::io::deletion::{deletion_file_path, read_deletion_file};
+use lance_table::rowids::rechunk_sequences;
+use snafu::{location, Location};
 
 use super::fragment::FileFragment;
 use super::index::DatasetIndexRemapperOptions;
+use super::progress::{ProgressMonitor, ProgressStage};
+use super::rowids::load_row_id_sequence;
+use super::scanner::escape_column_name;
 use super::transaction::{Operation, RewriteGroup, RewrittenIndex, Transaction};
 use super::utils::make_rowid_capture_stream;
+use super::write::remove_data_files;
 use super::{write_fragments_internal, WriteMode, WriteParams};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -162,6 +178,80 @@ pub struct CompactionOptions {
     pub materialize_deletions_threshold: f32,
     /// The number of threads to use. Defaults to the number of cores.
     pub num_threads: usize,
+    /// Target size of the data files of a fragment, in bytes. Defaults to `None`, in
+    /// which case only `target_rows_per_fragment` is used.
+    ///
+    /// When set, fragments are only candidates for compaction if they are also smaller
+    /// than this, and rewritten fragments are limited to about this many bytes.
+    #[serde(default)]
+    pub target_bytes_per_fragment: Option<u64>,
+    /// Only compact fragments that have rows in this range of values. Defaults to
+    /// `None`, which considers every fragment.
+    #[serde(default)]
+    pub value_range: Option<CompactionValueRange>,
+    /// Reports the fragments rewritten and can cancel the compaction
+    ///
+    /// The cancellation token is checked before each task and while each task writes.  A
+    /// cancelled compaction fails with [`Error::Stop`], does not commit and removes the files
+    /// it wrote.  This is not serialized and so distributed tasks are not monitored.
+    #[serde(skip)]
+    pub progress_monitor: ProgressMonitor,
+}
+
+/// A range of values of a column, see [`CompactionOptions::value_range`]
+///
+/// The bounds are inclusive SQL literals, such as `10` or `'2024-01-01'`.  A missing bound
+/// leaves the range open on that side.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompactionValueRange {
+    pub column: String,
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+impl CompactionValueRange {
+    /// The filter that selects the rows in the range
+    fn filter(&self) -> String {
+        let column = escape_column_name(&self.column);
+        let bounds = [
+            self.min
+                .as_ref()
+                .map(|min| format!("{} >= {}", column, min)),
+            self.max
+                .as_ref()
+                .map(|max| format!("{} <= {}", column, max)),
+        ];
+        let bounds = bounds.into_iter().flatten().collect::<Vec<_>>();
+        if bounds.is_empty() {
+            format!("{} IS NOT NULL", column)
+        } else {
+            bounds.join(" AND ")
+        }
+    }
+
+    /// The ids of the fragments of `dataset` that have rows in the range
+    ///
+    /// This is a single scan of the column over the whole dataset.
+    async fn fragment_ids(&self, dataset: &Dataset) -> Result<HashSet<u32>> {
+        let mut scanner = dataset.scan();
+        scanner
+            .project::<&str>(&[])?
+            .with_row_address()
+            .scan_in_order(false)
+            .filter(&self.filter())?;
+        let mut batches = scanner.try_into_stream().await?;
+        let mut fragment_ids = HashSet::new();
+        while let Some(batch) = batches.try_next().await? {
+            let row_addrs = batch[ROW_ADDR].as_primitive::<UInt64Type>();
+            fragment_ids.extend(
+                row_addrs
+                    .values()
+                    .iter()
+                    .map(|row_addr| RowAddress::new_from_id(*row_addr).fragment_id()),
+            );
+        }
+        Ok(fragment_ids)
+    }
 }
 
 impl Default for CompactionOptions {
@@ -173,6 +263,9 @@ impl Default for CompactionOptions {
             materialize_deletions: true,
             materialize_deletions_threshold: 0.1,
             num_threads: num_cpus::get(),
+            target_bytes_per_fragment: None,
+            value_range: None,
+            progress_monitor: ProgressMonitor::default(),
         }
     }
 }
@@ -218,6 +311,19 @@ pub struct CompactionMetrics {
     /// The number of files that have been added, which is always equal to the
     /// number of fragments.
     pub files_added: usize,
+    /// The size of the files that have been removed, including deletion files.
+    #[serde(default)]
+    pub bytes_removed: u64,
+    /// The size of the files that have been added.
+    #[serde(default)]
+    pub bytes_added: u64,
+}
+
+impl CompactionMetrics {
+    /// The number of bytes that compaction saved, once the old files are cleaned up
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_removed.saturating_sub(self.bytes_added)
+    }
 }
 
 impl AddAssign for CompactionMetrics {
@@ -226,6 +332,8 @@ impl AddAssign for CompactionMetrics {
         self.fragments_added += rhs.fragments_added;
         self.files_removed += rhs.files_removed;
         self.files_added += rhs.files_added;
+        self.bytes_removed += rhs.bytes_removed;
+        self.bytes_added += rhs.bytes_added;
     }
 }
 
@@ -254,12 +362,83 @@ pub async fn compact_files(
     }
 
     let dataset_ref = &dataset.clone();
+    let progress = RewriteProgress::new(
+        compaction_plan
+            .tasks
+            .iter()
+            .map(|task| task.fragments.len() as u64)
+            .sum(),
+    );
 
-    let result_stream = futures::stream::iter(compaction_plan.tasks.into_iter())
-        .map(|task| rewrite_files(Cow::Borrowed(dataset_ref), task, &options))
+    // Once a task fails (e.g. the compaction is cancelled) no more tasks are started but the
+    // running tasks are waited for, so that the files they write can be removed
+    let failed = AtomicBool::new(false);
+    let mut result_stream = futures::stream::iter(compaction_plan.tasks.into_iter())
+        .map(|task| {
+            let failed = &failed;
+            let progress = &progress;
+            let options = &options;
+            async move {
+                if failed.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+                rewrite_files(dataset_ref, task, options, progress)
+                    .await
+                    .map(Some)
+            }
+        })
         .buffer_unordered(options.num_threads);
-
-    let completed_tasks: Vec<RewriteResult> = result_stream.try_collect().await?;
+    let mut rewritten_tasks = Vec::new();
+    let mut result = Ok(());
+    while let Some(task_result) = result_stream.next().await {
+        match task_result {
+            Ok(Some(rewritten)) => rewritten_tasks.push(rewritten),
+            Ok(None) => {}
+            Err(err) => {
+                failed.store(true, Ordering::Relaxed);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+    }
+    drop(result_stream);
+
+    // Reserving the fragment ids commits a new version and so it is done once, for all of
+    // the tasks, when the compaction can no longer be cancelled
+    let result = async {
+        result?;
+        options.progress_monitor.check_cancelled()?;
+        reserve_fragment_ids(
+            dataset_ref,
+            rewritten_tasks
+                .iter_mut()
+                .flat_map(|rewritten| rewritten.new_fragments.iter_mut())
+                .collect(),
+        )
+        .await?;
+        let mut completed_tasks = Vec::with_capacity(rewritten_tasks.len());
+        for rewritten in rewritten_tasks.iter_mut() {
+            completed_tasks.push(finish_rewrite(dataset_ref, rewritten).await?);
+        }
+        Result::Ok(completed_tasks)
+    }
+    .await;
+    let completed_tasks = match result {
+        Ok(completed_tasks) => completed_tasks,
+        Err(err) => {
+            // Nothing is committed and so the files written by the tasks are removed
+            for rewritten in &rewritten_tasks {
+                remove_data_files(
+                    &dataset.object_store,
+                    &dataset.base,
+                    &rewritten.new_fragments,
+                )
+                .await;
+            }
+            return Err(err);
+        }
+    };
     let remap_options = remap_options.unwrap_or(Arc::new(DatasetIndexRemapperOptions::default()));
     let metrics = commit_compaction(dataset, completed_tasks, remap_options).await?;
 
@@ -273,6 +452,10 @@ struct FragmentMetrics {
     pub physical_rows: usize,
     /// The number of rows that have been deleted
     pub num_deletions: usize,
+    /// The size of the data files, only collected if there is a target size
+    pub num_bytes: Option<u64>,
+    /// False if the fragment has no rows in the value range of the options
+    pub in_value_range: bool,
 }
 
 impl FragmentMetrics {
@@ -289,16 +472,67 @@ impl FragmentMetrics {
     fn num_rows(&self) -> usize {
         self.physical_rows - self.num_deletions
     }
+
+    /// The approximate size of the rows that are still in the fragment
+    fn num_live_bytes(&self) -> u64 {
+        let num_bytes = self.num_bytes.unwrap_or_default();
+        if self.physical_rows > 0 {
+            (num_bytes as f64 * self.num_rows() as f64 / self.physical_rows as f64) as u64
+        } else {
+            num_bytes
+        }
+    }
+}
+
+/// The total size of the files of `fragment`, including its deletion file if
+/// `with_deletions` is true
+async fn fragment_size(
+    dataset: &Dataset,
+    fragment: &Fragment,
+    with_deletions: bool,
+) -> Result<u64> {
+    let mut paths = fragment
+        .files
+        .iter()
+        .map(|file| dataset.data_file_dir(file).child(file.path.as_str()))
+        .collect::<Vec<_>>();
+    if let Some(deletion_file) = fragment.deletion_file.as_ref().filter(|_| with_deletions) {
+        paths.push(deletion_file_path(
+            &dataset.base,
+            fragment.id,
+            deletion_file,
+        ));
+    }
+    let mut num_bytes = 0;
+    for path in paths {
+        num_bytes += dataset.object_store.size(&path).await? as u64;
+    }
+    Ok(num_bytes)
 }
 
-async fn collect_metrics(fragment: &FileFragment) -> Result<FragmentMetrics> {
+// `fragments_in_value_range` holds the fragments with rows in the value range of the
+// options, if there is one
+async fn collect_metrics(
+    fragment: &FileFragment,
+    options: &CompactionOptions,
+    fragments_in_value_range: Option<&HashSet<u32>>,
+) -> Result<FragmentMetrics> {
     let physical_rows = fragment.physical_rows();
     let num_deletions = fragment.count_deletions();
     let (physical_rows, num_deletions) =
         futures::future::try_join(physical_rows, num_deletions).await?;
+    let num_bytes = match options.target_bytes_per_fragment {
+        Some(_) => Some(fragment_size(fragment.dataset(), &fragment.metadata, false).await?),
+        None => None,
+    };
+    let in_value_range = fragments_in_value_range.map_or(true, |fragment_ids| {
+        fragment_ids.contains(&(fragment.id() as u32))
+    });
     Ok(FragmentMetrics {
         physical_rows,
         num_deletions,
+        num_bytes,
+        in_value_range,
     })
 }
 
@@ -373,7 +607,33 @@ impl CompactionTask {
         } else {
             Cow::Owned(dataset.checkout_version(self.read_version).await?)
         };
-        rewrite_files(dataset, self.task.clone(), &self.options).await
+        let progress = RewriteProgress::new(self.task.fragments.len() as u64);
+        let mut rewritten = rewrite_files(
+            dataset.as_ref(),
+            self.task.clone(),
+            &self.options,
+            &progress,
+        )
+        .await?;
+        let result = async {
+            self.options.progress_monitor.check_cancelled()?;
+            reserve_fragment_ids(
+                dataset.as_ref(),
+                rewritten.new_fragments.iter_mut().collect(),
+            )
+            .await?;
+            finish_rewrite(dataset.as_ref(), &mut rewritten).await
+        }
+        .await;
+        if result.is_err() {
+            remove_data_files(
+                &dataset.object_store,
+                &dataset.base,
+                &rewritten.new_fragments,
+            )
+            .await;
+        }
+        result
     }
 }
 
@@ -409,6 +669,8 @@ struct CandidateBin {
     pub pos_range: Range<usize>,
     pub candidacy: Vec<CompactionCandidacy>,
     pub row_counts: Vec<usize>,
+    /// The approximate size of each fragment once compacted, 0 if there is no target size
+    pub byte_sizes: Vec<u64>,
     pub indices: Vec<usize>,
 }
 
@@ -426,26 +688,35 @@ impl CandidateBin {
         }
     }
 
-    /// Split into one or more bins with at least `min_num_rows` in them.
-    fn split_for_size(mut self, min_num_rows: usize) -> Vec<Self> {
+    /// Split into one or more bins with at least `min_num_rows` rows or at least
+    /// `min_num_bytes` bytes in them.
+    fn split_for_size(mut self, min_num_rows: usize, min_num_bytes: u64) -> Vec<Self> {
         let mut bins = Vec::new();
 
         loop {
             let mut bin_len = 0;
             let mut bin_row_count = 0;
-            while bin_row_count < min_num_rows && bin_len < self.row_counts.len() {
+            let mut bin_byte_size = 0;
+            while bin_row_count < min_num_rows
+                && bin_byte_size < min_num_bytes
+                && bin_len < self.row_counts.len()
+            {
                 bin_row_count += self.row_counts[bin_len];
+                bin_byte_size += self.byte_sizes[bin_len];
                 bin_len += 1;
             }
 
             // If there's enough remaining to make another worthwhile bin, then
             // push what we have as a bin.
-            if self.row_counts[bin_len..].iter().sum::<usize>() >= min_num_rows {
+            if self.row_counts[bin_len..].iter().sum::<usize>() >= min_num_rows
+                || self.byte_sizes[bin_len..].iter().sum::<u64>() >= min_num_bytes
+            {
                 bins.push(Self {
                     fragments: self.fragments.drain(0..bin_len).collect(),
                     pos_range: self.pos_range.start..(self.pos_range.start + bin_len),
                     candidacy: self.candidacy.drain(0..bin_len).collect(),
                     row_counts: self.row_counts.drain(0..bin_len).collect(),
+                    byte_sizes: self.byte_sizes.drain(0..bin_len).collect(),
                     // By the time we are splitting for size we are done considering indices
                     indices: Vec::new(),
                 });
@@ -497,9 +768,14 @@ pub async fn plan_compaction(
             .all(|w| w[0].id() < w[1].id()),
         "fragments in manifest are not sorted"
     );
+    let fragments_in_value_range = match &options.value_range {
+        Some(value_range) => Some(value_range.fragment_ids(dataset).await?),
+        None => None,
+    };
+    let fragments_in_value_range = fragments_in_value_range.as_ref();
     let mut fragment_metrics = futures::stream::iter(dataset.get_fragments())
         .map(|fragment| async move {
-            match collect_metrics(&fragment).await {
+            match collect_metrics(&fragment, options, fragments_in_value_range).await {
                 Ok(metrics) => Ok((fragment.metadata, metrics)),
                 Err(e) => Err(e),
             }
@@ -523,11 +799,21 @@ pub async fn plan_compaction(
     while let Some(res) = fragment_metrics.next().await {
         let (fragment, metrics) = res?;
 
-        let candidacy = if options.materialize_deletions
+        let is_small = metrics.physical_rows < options.target_rows_per_fragment
+            && metrics.num_bytes.unwrap_or_default()
+                < options.target_bytes_per_fragment.unwrap_or(u64::MAX);
+        let candidacy = if !metrics.in_value_range {
+            // Not a candidate, whatever else is true about it
+            None
+        } else if fragment.files.iter().any(|file| file.base.is_some()) {
+            // The data is shared with the source of a shallow clone, rewriting it would
+            // copy it into the clone
+            None
+        } else if options.materialize_deletions
             && metrics.deletion_percentage() > options.materialize_deletions_threshold
         {
             Some(CompactionCandidacy::CompactItself)
-        } else if metrics.physical_rows < options.target_rows_per_fragment {
+        } else if is_small {
             // Only want to compact if their are neighbors to compact such that
             // we can get a larger fragment.
             Some(CompactionCandidacy::CompactWithNeighbors)
@@ -547,6 +833,7 @@ pub async fn plan_compaction(
                     pos_range: i..(i + 1),
                     candidacy: vec![candidacy],
                     row_counts: vec![metrics.num_rows()],
+                    byte_sizes: vec![metrics.num_live_bytes()],
                     indices,
                 });
             }
@@ -559,6 +846,7 @@ pub async fn plan_compaction(
                     bin.pos_range.end += 1;
                     bin.candidacy.push(candidacy);
                     bin.row_counts.push(metrics.num_rows());
+                    bin.byte_sizes.push(metrics.num_live_bytes());
                 } else {
                     // Index set is different.  Complete previous bin and start new one
                     candidate_bins.push(current_bin.take().unwrap());
@@ -567,6 +855,7 @@ pub async fn plan_compaction(
                         pos_range: i..(i + 1),
                         candidacy: vec![candidacy],
                         row_counts: vec![metrics.num_rows()],
+                        byte_sizes: vec![metrics.num_live_bytes()],
                         indices,
                     });
                 }
@@ -588,7 +877,12 @@ pub async fn plan_compaction(
     let final_bins = candidate_bins
         .into_iter()
         .filter(|bin| !bin.is_noop())
-        .flat_map(|bin| bin.split_for_size(options.target_rows_per_fragment))
+        .flat_map(|bin| {
+            bin.split_for_size(
+                options.target_rows_per_fragment,
+                options.target_bytes_per_fragment.unwrap_or(u64::MAX),
+            )
+        })
         .map(|bin| TaskData {
             fragments: bin.fragments,
         });
@@ -721,7 +1015,10 @@ fn transpose_row_ids(
     mapping
 }
 
-async fn reserve_fragment_ids(dataset: &Dataset, fragments: &mut [Fragment]) -> Result<()> {
+async fn reserve_fragment_ids(dataset: &Dataset, mut fragments: Vec<&mut Fragment>) -> Result<()> {
+    if fragments.is_empty() {
+        return Ok(());
+    }
     let transaction = Transaction::new(
         dataset.manifest.version,
         Operation::ReserveFragments {
@@ -751,23 +1048,110 @@ async fn reserve_fragment_ids(dataset: &Dataset, fragments: &mut [Fragment]) ->
     Ok(())
 }
 
+/// Gives the rewritten rows of `old_fragments` their stable row ids in `new_fragments`
+///
+/// The rows are rewritten in order, skipping deleted rows, and so the row ids of the new
+/// fragments are the row ids of the remaining rows of the old fragments.
+async fn move_row_ids(
+    dataset: &Dataset,
+    old_fragments: &[Fragment],
+    new_fragments: &mut [Fragment],
+) -> Result<()> {
+    let mut sequences = Vec::with_capacity(old_fragments.len());
+    for fragment in old_fragments {
+        let mut sequence = load_row_id_sequence(dataset, fragment)
+            .await?
+            .as_ref()
+            .clone();
+        let deletion_vector =
+            read_deletion_file(&dataset.base, fragment, &dataset.object_store).await?;
+        if let Some(deletion_vector) = deletion_vector {
+            let deleted_ids = deletion_vector
+                .into_sorted_iter()
+                .filter_map(|offset| sequence.get(offset as usize))
+                .collect::<Vec<_>>();
+            sequence.delete(deleted_ids);
+        }
+        sequences.push(sequence);
+    }
+    let num_rows = new_fragments
+        .iter()
+        .map(|fragment| {
+            fragment
+                .physical_rows
+                .map(|rows| rows as u64)
+                .ok_or_else(|| Error::Internal {
+                    message: "Rewritten fragment does not have physical rows".into(),
+                    location: location!(),
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let sequences = rechunk_sequences(sequences, num_rows)?;
+    for (fragment, sequence) in new_fragments.iter_mut().zip(sequences) {
+        fragment.set_row_id_sequence(&sequence);
+    }
+    Ok(())
+}
+
+/// Counts the fragments rewritten by the tasks of a compaction
+struct RewriteProgress {
+    num_rewritten: AtomicU64,
+    total: u64,
+}
+
+impl RewriteProgress {
+    fn new(total: u64) -> Self {
+        Self {
+            num_rewritten: AtomicU64::new(0),
+            total,
+        }
+    }
+
+    fn rewritten(&self, monitor: &ProgressMonitor, num_fragments: usize) {
+        let num_rewritten = self
+            .num_rewritten
+            .fetch_add(num_fragments as u64, Ordering::Relaxed)
+            + num_fragments as u64;
+        monitor.report(
+            ProgressStage::CompactFragments,
+            num_rewritten,
+            Some(self.total),
+        );
+    }
+}
+
+// The files written by a compaction task, see `rewrite_files`
+struct RewrittenFiles {
+    // The fragments that are replaced, as planned and with their stats recomputed
+    original_fragments: Vec<Fragment>,
+    fragments: Vec<Fragment>,
+    // Fragment ids are only assigned to these once they are reserved
+    new_fragments: Vec<Fragment>,
+    // The row ids of the rows that were rewritten
+    row_ids: RoaringTreemap,
+    read_version: u64,
+}
+
 /// Rewrite the files in a single task.
 ///
-/// This assumes that the dataset is the correct read version to be compacted.
+/// This assumes that the dataset is the correct read version to be compacted.  The new
+/// fragments don't have ids yet, see [`finish_rewrite`].  If this fails then the files
+/// that were written are removed.
 async fn rewrite_files(
-    dataset: Cow<'_, Dataset>,
+    dataset: &Dataset,
     task: TaskData,
     options: &CompactionOptions,
-) -> Result<RewriteResult> {
-    let mut metrics = CompactionMetrics::default();
+    progress: &RewriteProgress,
+) -> Result<RewrittenFiles> {
+    options.progress_monitor.check_cancelled()?;
 
     if task.fragments.is_empty() {
-        return Ok(RewriteResult {
-            metrics,
+        return Ok(RewrittenFiles {
+            original_fragments: task.fragments,
+            fragments: Vec::new(),
             new_fragments: Vec::new(),
+            row_ids: RoaringTreemap::new(),
             read_version: dataset.manifest.version,
-            original_fragments: task.fragments,
-            row_id_map: HashMap::new(),
         });
     }
 
@@ -781,7 +1165,7 @@ async fn rewrite_files(
     // It's possible the fragments are old and don't have physical rows or
     // num deletions recorded. If that's the case, we need to grab and set that
     // information.
-    let fragments = migrate_fragments(dataset.as_ref(), &task.fragments, recompute_stats).await?;
+    let fragments = migrate_fragments(dataset, &task.fragments, recompute_stats).await?;
     let mut scanner = dataset.scan();
     scanner
         .with_fragments(fragments.clone())
@@ -792,14 +1176,19 @@ async fn rewrite_files(
     let row_ids = Arc::new(RwLock::new(RoaringTreemap::new()));
     let data_no_row_ids = make_rowid_capture_stream(row_ids.clone(), data)?;
 
-    let params = WriteParams {
+    let mut params = WriteParams {
         max_rows_per_file: options.target_rows_per_fragment,
         max_rows_per_group: options.max_rows_per_group,
         mode: WriteMode::Append,
+        use_legacy_format: should_use_legacy_format(dataset.manifest.writer_feature_flags),
+        progress_monitor: options.progress_monitor.cancellation_only(),
         ..Default::default()
     };
+    if let Some(target_bytes) = options.target_bytes_per_fragment {
+        params.max_bytes_per_file = target_bytes as usize;
+    }
     let mut new_fragments = write_fragments_internal(
-        Some(dataset.as_ref()),
+        Some(dataset),
         dataset.object_store.clone(),
         &dataset.base,
         dataset.schema(),
@@ -813,28 +1202,88 @@ async fn rewrite_files(
         .into_inner()
         .expect("Row ids mutex still locked");
 
-    reserve_fragment_ids(&dataset, &mut new_fragments).await?;
+    // The rows are rewritten in the same order and so, if the old fragments share a sort
+    // order, the new fragments (which replace them) have that sort order as well
+    let sort_order = &task.fragments[0].sort_order;
+    if task
+        .fragments
+        .iter()
+        .all(|fragment| &fragment.sort_order == sort_order)
+    {
+        for fragment in new_fragments.iter_mut() {
+            fragment.sort_order = sort_order.clone();
+        }
+    }
+
+    // The new fragments only keep the custom metadata that describes all of the old fragments
+    let custom_metadata = Fragment::merge_custom_metadata(&task.fragments);
+    for fragment in new_fragments.iter_mut() {
+        fragment.custom_metadata = custom_metadata.clone();
+    }
 
-    let row_id_map: HashMap<u64, Option<u64>> =
-        transpose_row_ids(row_ids, &fragments, &new_fragments);
+    progress.rewritten(&options.progress_monitor, task.fragments.len());
+    Ok(RewrittenFiles {
+        original_fragments: task.fragments,
+        fragments,
+        new_fragments,
+        row_ids,
+        read_version: dataset.manifest.version,
+    })
+}
 
-    metrics.files_removed = task
-        .fragments
+/// Maps the row ids of a rewritten task to its new fragments, once fragment ids have been
+/// reserved for them, and measures the rewrite.
+async fn finish_rewrite(
+    dataset: &Dataset,
+    rewritten: &mut RewrittenFiles,
+) -> Result<RewriteResult> {
+    let mut metrics = CompactionMetrics::default();
+    let mut new_fragments = rewritten.new_fragments.clone();
+    if rewritten.original_fragments.is_empty() {
+        return Ok(RewriteResult {
+            metrics,
+            new_fragments,
+            read_version: rewritten.read_version,
+            original_fragments: Vec::new(),
+            row_id_map: HashMap::new(),
+        });
+    }
+
+    // Stable row ids move with the rows and so indices don't need to be remapped
+    let row_id_map: HashMap<u64, Option<u64>> = if dataset.manifest.uses_move_stable_row_ids() {
+        move_row_ids(dataset, &rewritten.fragments, &mut new_fragments).await?;
+        HashMap::new()
+    } else {
+        transpose_row_ids(
+            std::mem::take(&mut rewritten.row_ids),
+            &rewritten.fragments,
+            &new_fragments,
+        )
+    };
+
+    metrics.files_removed = rewritten
+        .original_fragments
         .iter()
         .map(|f| f.files.len() + f.deletion_file.is_some() as usize)
         .sum();
-    metrics.fragments_removed = task.fragments.len();
+    metrics.fragments_removed = rewritten.original_fragments.len();
     metrics.fragments_added = new_fragments.len();
     metrics.files_added = new_fragments
         .iter()
         .map(|f| f.files.len() + f.deletion_file.is_some() as usize)
         .sum();
+    for fragment in &rewritten.original_fragments {
+        metrics.bytes_removed += fragment_size(dataset, fragment, true).await?;
+    }
+    for fragment in &new_fragments {
+        metrics.bytes_added += fragment_size(dataset, fragment, true).await?;
+    }
 
     Ok(RewriteResult {
         metrics,
         new_fragments,
-        read_version: dataset.manifest.version,
-        original_fragments: task.fragments,
+        read_version: rewritten.read_version,
+        original_fragments: rewritten.original_fragments.clone(),
         row_id_map,
     })
 }
@@ -932,6 +1381,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(5),
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
             Fragment {
                 id: 3,
@@ -939,6 +1391,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(3),
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
         ];
         let rows = [(0, 1), (0, 3), (0, 4), (3, 0), (3, 2)]
@@ -1055,6 +1510,7 @@ mod tests {
             pos_range: 0..0,
             candidacy: vec![],
             row_counts: vec![],
+            byte_sizes: vec![],
             indices: vec![],
         };
         assert!(empty_bin.is_noop());
@@ -1065,12 +1521,16 @@ mod tests {
             deletion_file: None,
             row_id_meta: None,
             physical_rows: Some(0),
+            sort_order: vec![],
+            row_id_ranges: vec![],
+            custom_metadata: HashMap::new(),
         };
         let single_bin = CandidateBin {
             fragments: vec![fragment.clone()],
             pos_range: 0..1,
             candidacy: vec![CompactionCandidacy::CompactWithNeighbors],
             row_counts: vec![100],
+            byte_sizes: vec![0],
             indices: vec![],
         };
         assert!(single_bin.is_noop());
@@ -1080,6 +1540,7 @@ mod tests {
             pos_range: 0..1,
             candidacy: vec![CompactionCandidacy::CompactItself],
             row_counts: vec![100],
+            byte_sizes: vec![0],
             indices: vec![],
         };
         // Not a no-op because it's CompactItself
@@ -1092,12 +1553,13 @@ mod tests {
                 .take(8)
                 .collect(),
             row_counts: vec![100, 400, 200, 200, 400, 300, 300, 100],
+            byte_sizes: vec![0; 8],
             indices: vec![],
             // Will group into: [[100, 400], [200, 200, 400], [300, 300, 100]]
             // with size = 500
         };
         assert!(!big_bin.is_noop());
-        let split = big_bin.split_for_size(500);
+        let split = big_bin.split_for_size(500, u64::MAX);
         assert_eq!(split.len(), 3);
         assert_eq!(split[0].pos_range, 0..2);
         assert_eq!(split[1].pos_range, 2..5);
@@ -1512,6 +1974,226 @@ mod tests {
         assert!(fragments[0].metadata.deletion_file.is_none());
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_compact_deletion_threshold(#[values(false, true)] use_legacy_format: bool) {
+        // Fragments that are not small are only rewritten once enough of their rows are
+        // deleted
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let data = sample_data();
+        let reader = RecordBatchIterator::new(vec![Ok(data.slice(0, 3000))], data.schema());
+        let write_params = WriteParams {
+            max_rows_per_file: 1000,
+            use_legacy_format,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        // 30% of the first fragment and 5% of the second are deleted
+        dataset
+            .delete("a < 300 or (a >= 1000 and a < 1050)")
+            .await
+            .unwrap();
+
+        let mut options = CompactionOptions {
+            target_rows_per_fragment: 1000,
+            materialize_deletions_threshold: 0.5,
+            ..Default::default()
+        };
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 0);
+
+        options.materialize_deletions_threshold = 0.2;
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 1);
+        assert_eq!(plan.tasks()[0].fragments.len(), 1);
+        assert_eq!(plan.tasks()[0].fragments[0].id, 0);
+
+        let metrics = compact_files(&mut dataset, options, None).await.unwrap();
+        assert_eq!(metrics.fragments_removed, 1);
+        assert_eq!(metrics.fragments_added, 1);
+        assert!(metrics.bytes_added > 0);
+        assert!(metrics.bytes_reclaimed() > 0);
+        assert_eq!(
+            metrics.bytes_reclaimed(),
+            metrics.bytes_removed - metrics.bytes_added
+        );
+
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), 3);
+        // The rewritten fragment is added after the fragments that are kept
+        assert!(fragments[0].metadata.deletion_file.is_some());
+        assert!(fragments[2].metadata.deletion_file.is_none());
+        assert_eq!(fragments[2].count_rows().await.unwrap(), 700);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 2650);
+    }
+
+    #[tokio::test]
+    async fn test_compact_target_bytes() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let data = sample_data();
+        let reader = RecordBatchIterator::new(vec![Ok(data.slice(0, 4000))], data.schema());
+        let write_params = WriteParams {
+            max_rows_per_file: 1000,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        let fragment_bytes = fragment_size(&dataset, &dataset.get_fragments()[0].metadata, false)
+            .await
+            .unwrap();
+
+        // Only the row target, all of the fragments are compacted together
+        let plan = plan_compaction(&dataset, &CompactionOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(plan.tasks().len(), 1);
+
+        // Fragments are grouped into files of (about) two fragments
+        let options = CompactionOptions {
+            target_bytes_per_fragment: Some(2 * fragment_bytes),
+            ..Default::default()
+        };
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 2);
+        assert!(plan.tasks().iter().all(|task| task.fragments.len() == 2));
+
+        let metrics = compact_files(&mut dataset, options.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(metrics.fragments_removed, 4);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 4000);
+
+        // Fragments as large as the target are not compacted again
+        let options = CompactionOptions {
+            target_bytes_per_fragment: Some(fragment_bytes),
+            ..Default::default()
+        };
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_compact_value_range() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let data = sample_data();
+        let reader = RecordBatchIterator::new(vec![Ok(data.slice(0, 4000))], data.schema());
+        let write_params = WriteParams {
+            max_rows_per_file: 1000,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let options = CompactionOptions {
+            value_range: Some(CompactionValueRange {
+                column: "a".to_string(),
+                min: Some("1500".to_string()),
+                max: Some("2500".to_string()),
+            }),
+            ..Default::default()
+        };
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 1);
+        let ids = plan.tasks()[0]
+            .fragments
+            .iter()
+            .map(|fragment| fragment.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2]);
+
+        // An open range
+        let options = CompactionOptions {
+            value_range: Some(CompactionValueRange {
+                column: "a".to_string(),
+                min: Some("3000".to_string()),
+                max: None,
+            }),
+            ..Default::default()
+        };
+        // A single fragment is only compacted if it has deletions to materialize
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 0);
+        dataset.delete("a >= 3000 and a < 3500").await.unwrap();
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.tasks().len(), 1);
+        assert_eq!(plan.tasks()[0].fragments[0].id, 3);
+    }
+
+    #[test]
+    fn test_value_range_filter() {
+        let value_range = CompactionValueRange {
+            column: "select".to_string(),
+            min: Some("1".to_string()),
+            max: Some("2".to_string()),
+        };
+        assert_eq!(value_range.filter(), "`select` >= 1 AND `select` <= 2");
+        let value_range = CompactionValueRange {
+            column: "s.b".to_string(),
+            min: None,
+            max: None,
+        };
+        assert_eq!(value_range.filter(), "`s`.`b` IS NOT NULL");
+    }
+
+    #[tokio::test]
+    async fn test_compact_stable_row_ids() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let data = sample_data();
+        let reader = RecordBatchIterator::new(vec![Ok(data.slice(0, 3000))], data.schema());
+        let write_params = WriteParams {
+            max_rows_per_file: 1000,
+            enable_move_stable_row_ids: true,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("a % 7 = 0").await.unwrap();
+
+        let rows_with_ids = |dataset: Dataset| async move {
+            let batch = dataset.scan().with_row_id().try_into_batch().await.unwrap();
+            let mut rows = batch["a"]
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .values()
+                .iter()
+                .copied()
+                .zip(
+                    batch[lance_core::ROW_ID]
+                        .as_any()
+                        .downcast_ref::<arrow_array::UInt64Array>()
+                        .unwrap()
+                        .values()
+                        .iter()
+                        .copied(),
+                )
+                .collect::<Vec<_>>();
+            rows.sort();
+            rows
+        };
+        let expected = rows_with_ids(dataset.clone()).await;
+
+        let metrics = compact_files(&mut dataset, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(metrics.fragments_removed, 3);
+        assert_eq!(dataset.get_fragments().len(), 1);
+        assert_eq!(rows_with_ids(dataset.clone()).await, expected);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_compact_distributed(#[values(false, true)] use_legacy_format: bool) {
@@ -1595,6 +2277,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(5),
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
             Fragment {
                 id: 3,
@@ -1602,6 +2287,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(3),
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
             Fragment {
                 id: 1,
@@ -1609,6 +2297,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(3),
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
         ];
 
@@ -1,10 +1,15 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use async_trait::async_trait;
+use futures::StreamExt;
+use lance_io::stream::{RecordBatchStream, RecordBatchStreamAdapter};
 use lance_table::format::Fragment;
 
-use crate::Result;
+use crate::{Error, Result};
 
 /// Progress of writing a [Fragment].
 ///
@@ -48,3 +53,341 @@ impl WriteFragmentProgress for NoopFragmentWriteProgress {
         Ok(())
     }
 }
+
+/// A stage of a long running operation, see [`ProgressCallback`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStage {
+    /// Writing data files, counted in fragments
+    ///
+    /// The total is not known because the data is streamed.
+    WriteFragments,
+    /// Rewriting fragments during compaction, counted in the fragments that are replaced
+    CompactFragments,
+    /// Reading the data to index, counted in rows
+    IndexData,
+    /// Building the partitions of an IVF index, counted in partitions
+    IndexPartitions,
+}
+
+/// Receives the progress of a long running operation (e.g. a write, a compaction or an
+/// index build)
+///
+/// This is called once a unit of work (e.g. a fragment) is completed.  Some operations do
+/// their work concurrently and so this may be called concurrently.  The callback should
+/// return quickly as the operation waits for it.
+pub trait ProgressCallback: std::fmt::Debug + Sync + Send {
+    /// Called when `completed` units of `stage` are done, out of `total` if it is known
+    fn on_progress(&self, stage: ProgressStage, completed: u64, total: Option<u64>);
+}
+
+/// A token that cooperatively cancels long running operations
+///
+/// Clones share the same state and so a clone can be given to an operation and the
+/// operation cancelled, from any thread, by cancelling the original.  Operations check the
+/// token between units of work and, once it is cancelled, stop with [`Error::Stop`].  A
+/// cancelled operation does not commit and removes the files it wrote.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operations that use this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// The progress callback and cancellation token of an operation
+///
+/// Both are optional, the default reports nothing and cannot be cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressMonitor {
+    pub callback: Option<Arc<dyn ProgressCallback>>,
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl ProgressMonitor {
+    pub fn new(callback: Arc<dyn ProgressCallback>) -> Self {
+        Self {
+            callback: Some(callback),
+            cancellation: None,
+        }
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// A monitor for part of the operation, which only shares the cancellation token
+    pub(crate) fn cancellation_only(&self) -> Self {
+        Self {
+            callback: None,
+            cancellation: self.cancellation.clone(),
+        }
+    }
+
+    pub(crate) fn report(&self, stage: ProgressStage, completed: u64, total: Option<u64>) {
+        if let Some(callback) = &self.callback {
+            callback.on_progress(stage, completed, total);
+        }
+    }
+
+    /// Returns [`Error::Stop`] if the operation was cancelled
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match &self.cancellation {
+            Some(cancellation) if cancellation.is_cancelled() => Err(Error::Stop),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports the rows read from `stream`, which fails once the operation is cancelled
+    pub(crate) fn monitor_stream(
+        &self,
+        stream: impl RecordBatchStream + Unpin + 'static,
+        stage: ProgressStage,
+        total_rows: Option<u64>,
+    ) -> impl RecordBatchStream + Unpin + 'static {
+        let schema = stream.schema();
+        let monitor = self.clone();
+        let mut rows_read = 0;
+        let stream = stream.map(move |batch| {
+            monitor.check_cancelled()?;
+            let batch = batch?;
+            rows_read += batch.num_rows() as u64;
+            monitor.report(stage, rows_read, total_rows);
+            Ok(batch)
+        });
+        RecordBatchStreamAdapter::new(schema, stream)
+    }
+}
+
+// Pointer equality, this is only needed for options that are compared
+impl PartialEq for ProgressMonitor {
+    fn eq(&self, other: &Self) -> bool {
+        let same_callback = match (&self.callback, &other.callback) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_cancellation = match (&self.cancellation, &other.cancellation) {
+            (Some(a), Some(b)) => Arc::ptr_eq(&a.cancelled, &b.cancelled),
+            (None, None) => true,
+            _ => false,
+        };
+        same_callback && same_cancellation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use arrow_array::types::{Float32Type, Int32Type};
+    use lance_datagen::{array, gen, BatchCount, Dimension, RowCount};
+    use lance_index::{DatasetIndexExt, IndexType};
+    use lance_linalg::distance::MetricType;
+    use object_store::path::Path;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::dataset::optimize::{compact_files, CompactionOptions};
+    use crate::dataset::{Dataset, WriteMode, WriteParams};
+    use crate::index::vector::VectorIndexParams;
+
+    // Records the progress and cancels the operation once `cancel_on` is reported
+    #[derive(Debug, Default)]
+    struct RecordingCallback {
+        reports: Mutex<Vec<(ProgressStage, u64, Option<u64>)>>,
+        cancel_on: Option<(ProgressStage, CancellationToken)>,
+    }
+
+    impl ProgressCallback for RecordingCallback {
+        fn on_progress(&self, stage: ProgressStage, completed: u64, total: Option<u64>) {
+            self.reports.lock().unwrap().push((stage, completed, total));
+            if let Some((cancel_stage, token)) = &self.cancel_on {
+                if *cancel_stage == stage {
+                    token.cancel();
+                }
+            }
+        }
+    }
+
+    fn cancelling_monitor(stage: ProgressStage) -> ProgressMonitor {
+        let token = CancellationToken::new();
+        ProgressMonitor::new(Arc::new(RecordingCallback {
+            cancel_on: Some((stage, token.clone())),
+            ..Default::default()
+        }))
+        .with_cancellation(token)
+    }
+
+    fn data(num_batches: u32) -> impl arrow_array::RecordBatchReader + Send + 'static {
+        gen()
+            .col("i", array::step::<Int32Type>())
+            .col("vec", array::rand_vec::<Float32Type>(Dimension::from(16)))
+            .into_reader_rows(RowCount::from(100), BatchCount::from(num_batches))
+    }
+
+    async fn list_files(dataset_dir: &str, dir: &str) -> Vec<Path> {
+        let (object_store, base) =
+            lance_io::object_store::ObjectStore::from_path(dataset_dir).unwrap();
+        let mut files = object_store
+            .read_dir_all(&base.child(dir), None)
+            .await
+            .unwrap()
+            .map(|meta| meta.unwrap().location)
+            .collect::<Vec<_>>()
+            .await;
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_write_progress() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let callback = Arc::new(RecordingCallback::default());
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            progress_monitor: ProgressMonitor::new(callback.clone()),
+            ..Default::default()
+        };
+        let dataset = Dataset::write(data(3), test_uri, Some(params))
+            .await
+            .unwrap();
+        assert_eq!(dataset.get_fragments().len(), 3);
+        assert_eq!(
+            *callback.reports.lock().unwrap(),
+            vec![
+                (ProgressStage::WriteFragments, 1, None),
+                (ProgressStage::WriteFragments, 2, None),
+                (ProgressStage::WriteFragments, 3, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_write() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            progress_monitor: cancelling_monitor(ProgressStage::WriteFragments),
+            ..Default::default()
+        };
+        let result = Dataset::write(data(5), test_uri, Some(params)).await;
+        assert!(matches!(result, Err(Error::Stop)), "{:?}", result.err());
+        assert!(Dataset::open(test_uri).await.is_err());
+        assert!(list_files(test_uri, "data").await.is_empty());
+
+        // A cancelled append leaves the dataset as it was
+        let dataset = Dataset::write(data(1), test_uri, None).await.unwrap();
+        let data_files = list_files(test_uri, "data").await;
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            max_rows_per_file: 100,
+            progress_monitor: cancelling_monitor(ProgressStage::WriteFragments),
+            ..Default::default()
+        };
+        let result = Dataset::write(data(5), test_uri, Some(params)).await;
+        assert!(matches!(result, Err(Error::Stop)), "{:?}", result.err());
+        let latest = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(latest.version().version, dataset.version().version);
+        assert_eq!(latest.count_rows(None).await.unwrap(), 100);
+        assert_eq!(list_files(test_uri, "data").await, data_files);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_compaction() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(data(4), test_uri, Some(params))
+            .await
+            .unwrap();
+        let version = dataset.version().version;
+        let data_files = list_files(test_uri, "data").await;
+
+        // With several threads the files of the tasks still running are removed too
+        for num_threads in [1, 4] {
+            let options = CompactionOptions {
+                target_rows_per_fragment: 100,
+                num_threads,
+                progress_monitor: cancelling_monitor(ProgressStage::CompactFragments),
+                ..Default::default()
+            };
+            let result = compact_files(&mut dataset, options, None).await;
+            assert!(matches!(result, Err(Error::Stop)), "{:?}", result.err());
+            let latest = Dataset::open(test_uri).await.unwrap();
+            assert_eq!(latest.version().version, version);
+            assert_eq!(latest.get_fragments().len(), 40);
+            assert_eq!(list_files(test_uri, "data").await, data_files);
+        }
+
+        let callback = Arc::new(RecordingCallback::default());
+        let options = CompactionOptions {
+            target_rows_per_fragment: 100,
+            num_threads: 1,
+            progress_monitor: ProgressMonitor::new(callback.clone()),
+            ..Default::default()
+        };
+        compact_files(&mut dataset, options, None).await.unwrap();
+        assert_eq!(dataset.get_fragments().len(), 4);
+        let reports = callback.reports.lock().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(
+            reports.last(),
+            Some(&(ProgressStage::CompactFragments, 40, Some(40)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_index_build() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(data(10), test_uri, None).await.unwrap();
+
+        let mut params = VectorIndexParams::ivf_pq(2, 8, 2, MetricType::L2, 10);
+        params.progress_monitor = cancelling_monitor(ProgressStage::IndexData);
+        let result = dataset
+            .create_index(&["vec"], IndexType::Vector, None, &params, true)
+            .await;
+        assert!(matches!(result, Err(Error::Stop)), "{:?}", result.err());
+        assert!(dataset.load_indices().await.unwrap().is_empty());
+        assert!(list_files(test_uri, "_indices").await.is_empty());
+
+        params.progress_monitor = cancelling_monitor(ProgressStage::IndexPartitions);
+        let result = dataset
+            .create_index(&["vec"], IndexType::Vector, None, &params, true)
+            .await;
+        assert!(matches!(result, Err(Error::Stop)), "{:?}", result.err());
+        assert!(dataset.load_indices().await.unwrap().is_empty());
+        assert!(list_files(test_uri, "_indices").await.is_empty());
+
+        let callback = Arc::new(RecordingCallback::default());
+        params.progress_monitor = ProgressMonitor::new(callback.clone());
+        dataset
+            .create_index(&["vec"], IndexType::Vector, None, &params, true)
+            .await
+            .unwrap();
+        let reports = callback.reports.lock().unwrap();
+        assert!(reports.contains(&(ProgressStage::IndexData, 1000, Some(1000))));
+        assert_eq!(
+            reports.last(),
+            Some(&(ProgressStage::IndexPartitions, 2, Some(2)))
+        );
+    }
+}
@@ -0,0 +1,473 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Named references (tags) to versions of a dataset
+//!
+//! Each change to a tag (creating, updating or deleting it) writes a new small JSON file
+//! `_refs/tags/<name>/<n>.json`, where `n` counts the changes of the tag, and the file
+//! with the highest `n` holds the current state of the tag.  The files are written with
+//! [`CommitHandler::put_if_not_exists`], so concurrent changes of the same tag conflict
+//! instead of overwriting each other, as long as the commit handler of the dataset
+//! prevents conflicting commits.
+//!
+//! Tags are not part of the manifest and so they are not affected by writes or
+//! compaction.  A tagged version is not removed by [`Dataset::cleanup_old_versions`]
+//! until the tag is deleted.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use lance_io::object_store::ObjectStore;
+use lance_table::io::commit::{CommitError, CommitHandler};
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+use snafu::{location, Location};
+
+use crate::error::{Error, Result};
+#[cfg(doc)]
+use crate::Dataset;
+
+const REFS_DIR: &str = "_refs";
+const TAGS_DIR: &str = "tags";
+const MAX_TAG_LENGTH: usize = 255;
+
+/// The contents of a tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagContents {
+    /// The version of the dataset the tag points to
+    pub version: u64,
+}
+
+// The file written by a change of a tag, a deleted tag has no version
+#[derive(Debug, Serialize, Deserialize)]
+struct TagFile {
+    version: Option<u64>,
+}
+
+/// Checks that a tag name is valid
+///
+/// Tag names are 1 to 255 characters made of ASCII letters, digits, `-`, `_` and `.`.
+/// They must start with a letter or digit and cannot contain `..`.
+pub fn check_valid_tag(tag: &str) -> Result<()> {
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LENGTH
+        && tag.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !tag.contains("..");
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::invalid_input(
+            format!(
+                "Invalid tag name {:?}, tags must be 1 to {} ASCII letters, digits, '-', '_' or '.', start with a letter or digit, and not contain '..'",
+                tag, MAX_TAG_LENGTH
+            ),
+            location!(),
+        ))
+    }
+}
+
+/// The tags of a dataset, see [`Dataset::tags`]
+#[derive(Debug, Clone)]
+pub struct Tags {
+    object_store: Arc<ObjectStore>,
+    commit_handler: Arc<dyn CommitHandler>,
+    base: Path,
+}
+
+impl Tags {
+    pub(crate) fn new(
+        object_store: Arc<ObjectStore>,
+        commit_handler: Arc<dyn CommitHandler>,
+        base: Path,
+    ) -> Self {
+        Self {
+            object_store,
+            commit_handler,
+            base,
+        }
+    }
+
+    fn tags_dir(&self) -> Path {
+        self.base.child(REFS_DIR).child(TAGS_DIR)
+    }
+
+    fn tag_dir(&self, tag: &str) -> Path {
+        self.tags_dir().child(tag)
+    }
+
+    fn tag_file_path(&self, tag: &str, change: u64) -> Path {
+        self.tag_dir(tag).child(format!("{}.json", change))
+    }
+
+    /// Lists all tags, sorted by name
+    pub async fn list(&self) -> Result<BTreeMap<String, TagContents>> {
+        let mut tags = BTreeMap::new();
+        let names = match self.object_store.read_dir(self.tags_dir()).await {
+            Ok(names) => names,
+            Err(Error::NotFound { .. }) => return Ok(tags),
+            Err(e) => return Err(e),
+        };
+        for tag in names {
+            if check_valid_tag(&tag).is_err() {
+                continue;
+            }
+            if let Some((_, Some(version))) = self.latest_change(&tag).await? {
+                tags.insert(tag, TagContents { version });
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Returns the contents of a tag
+    pub async fn get(&self, tag: &str) -> Result<TagContents> {
+        check_valid_tag(tag)?;
+        match self.latest_change(tag).await? {
+            Some((_, Some(version))) => Ok(TagContents { version }),
+            _ => Err(tag_not_found(tag)),
+        }
+    }
+
+    // Returns the number of the latest change of a tag and the version the tag points to
+    // after it, if the tag was ever created
+    async fn latest_change(&self, tag: &str) -> Result<Option<(u64, Option<u64>)>> {
+        let file_names = match self.object_store.read_dir(self.tag_dir(tag)).await {
+            Ok(file_names) => file_names,
+            Err(Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // Temporary files of changes that are being written are skipped
+        let Some(change) = file_names
+            .iter()
+            .filter_map(|name| name.strip_suffix(".json")?.parse::<u64>().ok())
+            .max()
+        else {
+            return Ok(None);
+        };
+        let path = self.tag_file_path(tag, change);
+        let data = self.object_store.inner.get(&path).await?.bytes().await?;
+        let file: TagFile = serde_json::from_slice(&data).map_err(|e| Error::CorruptFile {
+            path: path.clone(),
+            source: e.into(),
+            location: location!(),
+        })?;
+        Ok(Some((change, file.version)))
+    }
+
+    // Checks that the version exists
+    async fn check_version_exists(&self, version: u64) -> Result<()> {
+        let manifest_path = self
+            .commit_handler
+            .resolve_version(&self.base, version, &self.object_store.inner)
+            .await?;
+        if !self.object_store.exists(&manifest_path).await? {
+            return Err(Error::invalid_input(
+                format!("Cannot tag version {}, it does not exist", version),
+                location!(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Writes a change of a tag, fails with `CommitConflict` if another writer already
+    // wrote a change with the same number
+    async fn write_change(
+        &self,
+        tag: &str,
+        change: u64,
+        version: Option<u64>,
+    ) -> std::result::Result<(), CommitError> {
+        let data = serde_json::to_vec(&TagFile { version }).map_err(Error::from)?;
+        self.commit_handler
+            .put_if_not_exists(&self.tag_file_path(tag, change), &data, &self.object_store)
+            .await
+    }
+
+    /// Creates a tag pointing at `version`
+    ///
+    /// Fails if the tag already exists, even if it is created concurrently by another
+    /// writer, or if the version does not exist.
+    pub async fn create(&self, tag: &str, version: u64) -> Result<()> {
+        check_valid_tag(tag)?;
+        let change = match self.latest_change(tag).await? {
+            Some((_, Some(_))) => return Err(tag_exists(tag)),
+            // The tag was deleted before
+            Some((change, None)) => change + 1,
+            None => 0,
+        };
+        self.check_version_exists(version).await?;
+        match self.write_change(tag, change, Some(version)).await {
+            Ok(()) => Ok(()),
+            Err(CommitError::CommitConflict) => Err(tag_exists(tag)),
+            Err(CommitError::OtherError(e)) => Err(e),
+        }
+    }
+
+    /// Points an existing tag at another version
+    ///
+    /// Fails if the tag is changed concurrently by another writer.
+    pub async fn update(&self, tag: &str, version: u64) -> Result<()> {
+        let change = self.existing_change(tag).await?;
+        self.check_version_exists(version).await?;
+        self.write_existing_change(tag, change + 1, Some(version))
+            .await
+    }
+
+    /// Deletes a tag, the version it pointed to is not affected
+    ///
+    /// Fails if the tag is changed concurrently by another writer.
+    pub async fn delete(&self, tag: &str) -> Result<()> {
+        let change = self.existing_change(tag).await?;
+        self.write_existing_change(tag, change + 1, None).await
+    }
+
+    // Returns the number of the latest change of a tag that exists
+    async fn existing_change(&self, tag: &str) -> Result<u64> {
+        check_valid_tag(tag)?;
+        match self.latest_change(tag).await? {
+            Some((change, Some(_))) => Ok(change),
+            _ => Err(tag_not_found(tag)),
+        }
+    }
+
+    async fn write_existing_change(
+        &self,
+        tag: &str,
+        change: u64,
+        version: Option<u64>,
+    ) -> Result<()> {
+        match self.write_change(tag, change, version).await {
+            Ok(()) => Ok(()),
+            Err(CommitError::CommitConflict) => Err(Error::PrerequisiteFailed {
+                message: format!("Tag {} was changed concurrently by another writer", tag),
+                location: location!(),
+            }),
+            Err(CommitError::OtherError(e)) => Err(e),
+        }
+    }
+}
+
+fn tag_not_found(tag: &str) -> Error {
+    Error::NotFound {
+        uri: format!("tag {}", tag),
+        location: location!(),
+    }
+}
+
+fn tag_exists(tag: &str) -> Error {
+    Error::invalid_input(
+        format!(
+            "Tag {} already exists, use update to point it at another version",
+            tag
+        ),
+        location!(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use futures::future::join_all;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::dataset::builder::DatasetBuilder;
+    use crate::dataset::{WriteMode, WriteParams};
+    use crate::Dataset;
+
+    async fn append(uri: &str, values: Vec<i32>) -> Dataset {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))]).unwrap();
+        Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema),
+            uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_valid_tag() {
+        for tag in ["prod", "v1.2.3", "release_2024-01", "0"] {
+            assert!(check_valid_tag(tag).is_ok(), "{}", tag);
+        }
+        let too_long = "a".repeat(MAX_TAG_LENGTH + 1);
+        for tag in [
+            "",
+            ".hidden",
+            "-prod",
+            "a..b",
+            "a/b",
+            "with space",
+            "ünïcode",
+            too_long.as_str(),
+        ] {
+            assert!(check_valid_tag(tag).is_err(), "{}", tag);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tags() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = append(test_uri, vec![1, 2, 3]).await;
+        append(test_uri, vec![4, 5]).await;
+
+        let tags = dataset.tags();
+        assert!(tags.list().await.unwrap().is_empty());
+
+        tags.create("prod", 1).await.unwrap();
+        tags.create("staging", 2).await.unwrap();
+        // Creating an existing tag doesn't overwrite it
+        assert!(matches!(
+            tags.create("prod", 2).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert_eq!(tags.get("prod").await.unwrap().version, 1);
+        // Only existing versions can be tagged
+        assert!(tags.create("dev", 3).await.is_err());
+        assert!(tags.create("bad name", 1).await.is_err());
+
+        assert_eq!(
+            tags.list().await.unwrap(),
+            BTreeMap::from([
+                ("prod".to_string(), TagContents { version: 1 }),
+                ("staging".to_string(), TagContents { version: 2 }),
+            ])
+        );
+
+        // Tags keep pointing at the same version after later writes
+        append(test_uri, vec![6]).await;
+        let prod = Dataset::open(test_uri)
+            .await
+            .unwrap()
+            .checkout_tag("prod")
+            .await
+            .unwrap();
+        assert_eq!(prod.version().version, 1);
+        assert_eq!(prod.count_rows(None).await.unwrap(), 3);
+        let staging = DatasetBuilder::from_uri(test_uri)
+            .with_tag("staging")
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(staging.version().version, 2);
+        assert_eq!(staging.count_rows(None).await.unwrap(), 5);
+        assert!(DatasetBuilder::from_uri(test_uri)
+            .with_tag("staging")
+            .with_version(1)
+            .load()
+            .await
+            .is_err());
+
+        tags.update("prod", 3).await.unwrap();
+        assert_eq!(
+            dataset
+                .checkout_tag("prod")
+                .await
+                .unwrap()
+                .version()
+                .version,
+            3
+        );
+        assert!(matches!(
+            tags.update("missing", 1).await,
+            Err(Error::NotFound { .. })
+        ));
+
+        tags.delete("staging").await.unwrap();
+        assert!(matches!(
+            dataset.checkout_tag("staging").await,
+            Err(Error::NotFound { .. })
+        ));
+        assert!(tags.delete("staging").await.is_err());
+        assert_eq!(tags.list().await.unwrap().len(), 1);
+        // Deleting a tag doesn't affect the version
+        assert_eq!(
+            dataset.checkout_version(2).await.unwrap().version().version,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_tag_create() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        append(test_uri, vec![1]).await;
+        let dataset = append(test_uri, vec![2]).await;
+
+        let tags = dataset.tags();
+        let results = join_all((1..=2).cycle().take(10).map(|version| {
+            let tags = tags.clone();
+            async move { tags.create("prod", version).await }
+        }))
+        .await;
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        // No temporary files are left behind
+        assert_eq!(tags.list().await.unwrap().len(), 1);
+        assert_eq!(
+            dataset
+                .object_store
+                .read_dir(tags.tag_dir("prod"))
+                .await
+                .unwrap(),
+            vec!["0.json".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_tag_update() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        append(test_uri, vec![1]).await;
+        append(test_uri, vec![2]).await;
+        let dataset = append(test_uri, vec![3]).await;
+
+        let tags = dataset.tags();
+        tags.create("prod", 1).await.unwrap();
+        let results = join_all((0..10).map(|i| {
+            let tags = tags.clone();
+            async move {
+                if i % 3 == 0 {
+                    tags.delete("prod").await
+                } else {
+                    tags.update("prod", 2 + i % 2).await
+                }
+            }
+        }))
+        .await;
+        // Changes that were based on the same state of the tag conflict, and no change
+        // that succeeded is overwritten by another
+        let succeeded = results.iter().filter(|result| result.is_ok()).count();
+        assert!(succeeded >= 1);
+        assert!(results.iter().all(|result| matches!(
+            result,
+            Ok(_) | Err(Error::PrerequisiteFailed { .. }) | Err(Error::NotFound { .. })
+        )));
+        let file_names = dataset
+            .object_store
+            .read_dir(tags.tag_dir("prod"))
+            .await
+            .unwrap();
+        assert_eq!(file_names.len(), 1 + succeeded);
+
+        // A deleted tag can be created again
+        tags.delete("prod").await.ok();
+        tags.create("prod", 3).await.unwrap();
+        assert_eq!(tags.get("prod").await.unwrap().version, 3);
+    }
+}
@@ -4,6 +4,7 @@
 use super::Dataset;
 use crate::{Error, Result};
 use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
+use lance_core::utils::address::RowAddress;
 use snafu::{location, Location};
 use std::sync::Arc;
 
@@ -87,6 +88,33 @@ pub async fn get_row_id_index(
     }
 }
 
+/// Get the addresses of the given row ids
+///
+/// Unlike [`get_row_id_index`], only the row id sequences of the fragments whose row id
+/// ranges contain one of the row ids are loaded.  Row ids that don't exist have no address.
+pub async fn get_row_addresses(
+    dataset: &Dataset,
+    row_ids: &[u64],
+) -> Result<Vec<Option<RowAddress>>> {
+    let mut sorted_row_ids = row_ids.to_vec();
+    sorted_row_ids.sort_unstable();
+    sorted_row_ids.dedup();
+    let fragments = dataset
+        .manifest
+        .fragments
+        .iter()
+        .filter(|fragment| fragment.may_contain_any_row_id(&sorted_row_ids))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let sequences = load_row_id_sequences(dataset, &fragments)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let index = RowIdIndex::new(&sequences)?;
+
+    Ok(row_ids.iter().map(|row_id| index.get(*row_id)).collect())
+}
+
 async fn load_row_id_index(dataset: &Dataset) -> Result<lance_table::rowids::RowIdIndex> {
     let sequences = load_row_id_sequences(dataset, &dataset.manifest.fragments)
         .try_collect::<Vec<_>>()
@@ -101,6 +129,7 @@ async fn load_row_id_index(dataset: &Dataset) -> Result<lance_table::rowids::Row
 mod test {
     use std::ops::Range;
 
+    use crate::dataset::optimize::{compact_files, CompactionOptions};
     use crate::dataset::{builder::DatasetBuilder, UpdateBuilder, WriteMode, WriteParams};
 
     use super::*;
@@ -415,5 +444,66 @@ mod test {
         assert_eq!(index.get(5), Some(RowAddress::new_from_parts(1, 0)));
     }
 
+    #[tokio::test]
+    async fn test_take_by_row_ids_after_compaction() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let tmp_path = tmp_dir.path().to_str().unwrap();
+        let batch = sequence_batch(0..100);
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema());
+        let write_params = WriteParams {
+            enable_move_stable_row_ids: true,
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, tmp_path, Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("id % 10 = 0").await.unwrap();
+        // Merge every pair of fragments, which moves the rows
+        compact_files(
+            &mut dataset,
+            CompactionOptions {
+                target_rows_per_fragment: 18,
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(dataset.get_fragments().len(), 5);
+
+        // A fresh dataset doesn't have any row id sequences cached
+        let dataset = DatasetBuilder::from_uri(tmp_path).load().await.unwrap();
+        let row_id_sequence_cached = |fragment: &Fragment| {
+            let path = dataset.base.child(fragment.id.to_string()).child("row_ids");
+            dataset
+                .session
+                .file_metadata_cache
+                .get::<RowIdSequence>(&path)
+                .is_some()
+        };
+
+        let projection = dataset.schema().project(&["id"]).unwrap();
+        let rows = dataset
+            .take_by_row_ids(&[45, 43, 10, 1000], &projection)
+            .await
+            .unwrap();
+        // Row ids that are deleted or don't exist are skipped
+        assert_eq!(
+            rows.column(0).as_ref(),
+            &Int32Array::from(vec![45, 43]) as &dyn arrow_array::Array
+        );
+
+        // Only the row id sequence of the fragment with the rows is loaded
+        let loaded = dataset
+            .manifest
+            .fragments
+            .iter()
+            .filter(|fragment| row_id_sequence_cached(fragment))
+            .map(|fragment| fragment.id)
+            .collect::<Vec<_>>();
+        assert_eq!(loaded.len(), 1);
+    }
+
     // TODO: query / scan / take after deletion, compaction, then deletion
 }
@@ -5,6 +5,7 @@ use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use arrow_array::{Array, Float32Array, Int64Array, RecordBatch};
 use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef, SortOptions};
@@ -12,7 +13,7 @@ use arrow_select::concat::concat_batches;
 use async_recursion::async_recursion;
 use datafusion::common::DFSchema;
 use datafusion::functions_aggregate::count::count_udaf;
-use datafusion::logical_expr::{lit, Expr};
+use datafusion::logical_expr::{lit, Expr, Operator};
 use datafusion::physical_expr::PhysicalSortExpr;
 use datafusion::physical_plan::expressions;
 use datafusion::physical_plan::projection::ProjectionExec as DFProjectionExec;
@@ -48,6 +49,10 @@ use super::Dataset;
 use crate::datatypes::Schema;
 use crate::index::DatasetIndexInternalExt;
 use crate::io::exec::scalar_index::{MaterializeIndexExec, ScalarIndexExec};
+use crate::io::exec::scan::{
+    BYTES_DECODED_METRIC, BYTES_READ_METRIC, DECODE_TIME_METRIC, INDEX_SEARCHES_METRIC,
+    IOPS_METRIC, PAGES_SCHEDULED_METRIC, PAGES_SKIPPED_METRIC,
+};
 use crate::io::exec::{
     knn::new_knn_exec, FilterPlan, KNNVectorDistanceExec, LancePushdownScanExec, LanceScanExec,
     Planner, PreFilterSource, ProjectionExec, ScanConfig, TakeExec,
@@ -66,10 +71,14 @@ pub const DEFAULT_BATCH_READAHEAD: usize = 16;
 // Same as pyarrow Dataset::scanner()
 pub const DEFAULT_FRAGMENT_READAHEAD: usize = 4;
 
+/// The default cap on the number of results of a range search
+pub const DEFAULT_RANGE_SEARCH_MAX_RESULTS: usize = 10_000;
+
 /// Defines an ordering for a single column
 ///
 /// Floats are sorted using the IEEE 754 total ordering
 /// Strings are sorted using UTF-8 lexicographic order (i.e. we sort the binary)
+#[derive(Clone)]
 pub struct ColumnOrdering {
     pub ascending: bool,
     pub nulls_first: bool,
@@ -123,6 +132,7 @@ impl ColumnOrdering {
 ///   .buffered(16)
 ///   .sum()
 /// ```
+#[derive(Clone)]
 pub struct Scanner {
     dataset: Arc<Dataset>,
 
@@ -191,7 +201,7 @@ pub struct Scanner {
     fast_search: bool,
 }
 
-fn escape_column_name(name: &str) -> String {
+pub(crate) fn escape_column_name(name: &str) -> String {
     name.split('.')
         .map(|s| format!("`{}`", s))
         .collect::<Vec<_>>()
@@ -282,12 +292,18 @@ impl Scanner {
 
     /// Projection with transform
     ///
-    /// Only select the specified columns with the given transform.
+    /// Only select the specified columns with the given transform.  Each entry is an
+    /// (output name, SQL expression) pair, e.g. `("total", "price * quantity")`.  The
+    /// columns referenced by the expressions are read but only the named outputs are
+    /// returned.
     pub fn project_with_transform(
         &mut self,
         columns: &[(impl AsRef<str>, impl AsRef<str>)],
     ) -> Result<&mut Self> {
         let planner = Planner::new(Arc::new(self.dataset.schema().into()));
+        let physical_schema = self.physical_schema(true)?;
+        let schema_with_meta_columns = self.dataset.schema().merge(physical_schema.as_ref())?;
+        let coercion_planner = Planner::new(Arc::new((&schema_with_meta_columns).into()));
         let mut output = HashMap::new();
         let mut physical_cols_set = HashSet::new();
         let mut physical_cols = vec![];
@@ -299,7 +315,19 @@ impl Scanner {
                 ));
             }
             let expr = planner.parse_expr(raw_expr.as_ref())?;
-            for col in Planner::column_names_in_expr(&expr) {
+            let columns_in_expr = Planner::column_names_in_expr(&expr);
+            // Operands of different types (e.g. `price * quantity`) must be coerced
+            // before the expression can be evaluated.  Columns that only appear
+            // later (e.g. `_distance`) can't be coerced yet and are left as is.
+            let expr = if columns_in_expr
+                .iter()
+                .all(|col| schema_with_meta_columns.field(col).is_some())
+            {
+                coercion_planner.optimize_expr(expr)?
+            } else {
+                expr
+            };
+            for col in columns_in_expr {
                 if physical_cols_set.contains(&col) {
                     continue;
                 }
@@ -309,8 +337,6 @@ impl Scanner {
             output.insert(output_name.as_ref().to_string(), expr);
         }
 
-        let physical_schema = self.physical_schema(true)?;
-        let schema_with_meta_columns = self.dataset.schema().merge(physical_schema.as_ref())?;
         self.physical_columns = schema_with_meta_columns.project(&physical_cols)?;
 
         let mut output_cols = vec![];
@@ -325,6 +351,8 @@ impl Scanner {
     ///
     /// If true then the filter will be applied before the vector index.  This
     /// means the results will be accurate but the overall query may be more expensive.
+    /// If at least `k` rows match the filter then `k` results will be returned, IVF
+    /// indices will probe more than `nprobes` partitions if needed to find them.
     ///
     /// If false then the filter will be applied to the nearest results.  This means
     /// you may get back fewer results than you ask for (or none at all) if the closest
@@ -419,6 +447,8 @@ impl Scanner {
     /// only the provided number of rows will be returned. These can be set
     /// independently. For example, setting offset to 10 and limit to None will
     /// skip the first 10 rows and return the rest of the rows in the dataset.
+    ///
+    /// An offset of None keeps any offset set with [Self::offset].
     pub fn limit(&mut self, limit: Option<i64>, offset: Option<i64>) -> Result<&mut Self> {
         if limit.unwrap_or_default() < 0 {
             return Err(Error::io(
@@ -435,7 +465,35 @@ impl Scanner {
             }
         }
         self.limit = limit;
-        self.offset = offset;
+        if offset.is_some() {
+            self.offset = offset;
+        }
+        Ok(self)
+    }
+
+    /// Skip the first `offset` rows of the result.
+    ///
+    /// Any limit set with [Self::limit] is kept and applies to the rows after
+    /// the offset.
+    ///
+    /// Without a filter or an ordering the offset is applied to the rows in
+    /// the order they are stored in the dataset (even if [Self::scan_in_order]
+    /// is false) and it is pushed into the scan: fragments that are entirely
+    /// skipped are not opened and the rows skipped in the first remaining
+    /// fragment are not decoded.
+    ///
+    /// With a filter the offset applies to the filtered rows and, with an
+    /// ordering (see [Self::order_by]), to the sorted rows.  The number of
+    /// rows to skip cannot be known before reading in these cases and the
+    /// rows are skipped as they stream out of the plan.
+    pub fn offset(&mut self, offset: i64) -> Result<&mut Self> {
+        if offset < 0 {
+            return Err(Error::io(
+                "Offset must be non-negative".to_string(),
+                location!(),
+            ));
+        }
+        self.offset = Some(offset);
         Ok(self)
     }
 
@@ -493,10 +551,53 @@ impl Scanner {
             refine_factor: None,
             metric_type: MetricType::L2,
             use_index: true,
+            radius: None,
         });
         Ok(self)
     }
 
+    /// Find all rows whose vector is within `radius` of the query vector.
+    ///
+    /// The radius is compared against the `_distance` column so, for L2, it is a
+    /// squared distance.  This composes with filters (pre or post), projections and
+    /// the other vector search options like [Self::nprobs] and [Self::refine].
+    ///
+    /// At most [DEFAULT_RANGE_SEARCH_MAX_RESULTS] rows, the closest ones, are returned
+    /// unless changed with [Self::max_range_results].  Use [Self::try_into_range_batch]
+    /// to find out if the results were truncated.
+    pub fn nearest_within(
+        &mut self,
+        column: &str,
+        q: &Float32Array,
+        radius: f32,
+    ) -> Result<&mut Self> {
+        if radius.is_nan() || radius <= 0.0 {
+            return Err(Error::io(
+                format!("Radius must be positive, got {}", radius),
+                location!(),
+            ));
+        }
+        self.nearest(column, q, DEFAULT_RANGE_SEARCH_MAX_RESULTS)?;
+        if let Some(q) = self.nearest.as_mut() {
+            q.radius = Some(radius);
+        }
+        Ok(self)
+    }
+
+    /// Set the maximum number of results of a range search (see [Self::nearest_within])
+    pub fn max_range_results(&mut self, n: usize) -> Result<&mut Self> {
+        if n == 0 {
+            return Err(Error::io(
+                "max_range_results must be positive".to_string(),
+                location!(),
+            ));
+        }
+        if let Some(q) = self.nearest.as_mut().filter(|q| q.radius.is_some()) {
+            q.k = n;
+        }
+        Ok(self)
+    }
+
     pub fn nprobs(&mut self, n: usize) -> &mut Self {
         if let Some(q) = self.nearest.as_mut() {
             q.nprobes = n;
@@ -713,9 +814,10 @@ impl Scanner {
     pub async fn try_into_stream(&self) -> Result<DatasetRecordBatchStream> {
         let plan = self.create_plan().await?;
         Ok(DatasetRecordBatchStream::new(execute_plan(
-            plan,
+            plan.clone(),
             LanceExecutionOptions::default(),
-        )?))
+        )?)
+        .with_plan(plan))
     }
 
     pub(crate) async fn try_into_dfstream(
@@ -733,10 +835,155 @@ impl Scanner {
         Ok(concat_batches(&schema, &batches)?)
     }
 
+    /// Run a range search (see [Self::nearest_within]) and collect the results
+    ///
+    /// The search looks for one more row than the cap on the number of results so
+    /// that it can tell if rows within the radius were left out.  A postfilter would
+    /// run after the cap and hide the rows that were left out, so a filter must be
+    /// applied as a prefilter.
+    pub async fn try_into_range_batch(&self) -> Result<RangeSearchResult> {
+        let Some(max_results) = self
+            .nearest
+            .as_ref()
+            .filter(|q| q.radius.is_some())
+            .map(|q| q.k)
+        else {
+            return Err(Error::invalid_input(
+                "try_into_range_batch requires a range search, see nearest_within",
+                location!(),
+            ));
+        };
+        if self.filter.is_some() && !self.prefilter {
+            return Err(Error::invalid_input(
+                "try_into_range_batch does not support a postfilter, use prefilter(true)",
+                location!(),
+            ));
+        }
+        let mut scanner = self.clone();
+        if let Some(q) = scanner.nearest.as_mut() {
+            q.k = max_results + 1;
+        }
+        let batch = scanner.try_into_batch().await?;
+        let truncated = batch.num_rows() > max_results;
+        let batch = if truncated {
+            batch.slice(0, max_results)
+        } else {
+            batch
+        };
+        Ok(RangeSearchResult { batch, truncated })
+    }
+
     /// Scan and return the number of matching rows
+    ///
+    /// When the only restriction is a filter the count is computed without
+    /// running a scan where possible: scalar indices answer predicates they
+    /// fully cover and statistics resolve pages that entirely match (or
+    /// don't match) the filter.  Only the filter columns of the remaining
+    /// pages are read.
     #[instrument(skip_all)]
     pub async fn count_rows(&self) -> Result<u64> {
+        if let Some(count) = self.count_filtered_rows().await? {
+            return Ok(count);
+        }
         let plan = self.create_plan().await?;
+        Self::count_plan_rows(plan).await
+    }
+
+    /// Count the rows matching the filter, or None if the query can't be counted
+    /// from the filter alone.
+    async fn count_filtered_rows(&self) -> Result<Option<u64>> {
+        // Limits, offsets and vector searches change which rows are counted
+        if self.filter.is_none()
+            || self.nearest.is_some()
+            || self.limit.is_some()
+            || self.offset.is_some()
+        {
+            return Ok(None);
+        }
+        let filter_plan = self.create_filter_plan(true).await?;
+        let fragments = if let Some(fragments) = self.fragments.as_ref() {
+            fragments.clone()
+        } else {
+            (**self.dataset.fragments()).clone()
+        };
+        match (filter_plan.index_query, filter_plan.refine_expr) {
+            (Some(index_query), None) => {
+                let covered_frags = self.fragments_covered_by_index_query(&index_query).await?;
+                let (indexed_frags, unindexed_frags): (Vec<_>, Vec<_>) = fragments
+                    .into_iter()
+                    .partition(|fragment| covered_frags.contains(fragment.id as u32));
+                let indexed_count = if indexed_frags.is_empty() {
+                    0
+                } else {
+                    MaterializeIndexExec::new(
+                        self.dataset.clone(),
+                        index_query.clone(),
+                        Arc::new(indexed_frags),
+                    )
+                    .count_rows()
+                    .await?
+                };
+                let unindexed_count = self
+                    .count_matching_rows(unindexed_frags, index_query.to_expr())
+                    .await?;
+                Ok(Some(indexed_count + unindexed_count))
+            }
+            (None, Some(predicate)) => {
+                Ok(Some(self.count_matching_rows(fragments, predicate).await?))
+            }
+            // TODO: combine the index results with a refine count
+            _ => Ok(None),
+        }
+    }
+
+    /// Count the rows in `fragments` that match `predicate`.
+    ///
+    /// Legacy fragments are counted with the help of their page statistics, other
+    /// fragments are counted by scanning only the filter columns.
+    async fn count_matching_rows(&self, fragments: Vec<Fragment>, predicate: Expr) -> Result<u64> {
+        let (stats_frags, scan_frags): (Vec<_>, Vec<_>) = fragments
+            .into_iter()
+            .partition(|fragment| self.use_stats && fragment.has_legacy_files());
+
+        let mut count = 0;
+        if !stats_frags.is_empty() {
+            let config = ScanConfig {
+                batch_readahead: self.batch_readahead,
+                fragment_readahead: self
+                    .fragment_readahead
+                    .unwrap_or(DEFAULT_FRAGMENT_READAHEAD),
+                ..Default::default()
+            };
+            let pushdown = LancePushdownScanExec::try_new(
+                self.dataset.clone(),
+                Arc::new(stats_frags),
+                Arc::new(Schema::default()),
+                predicate.clone(),
+                config,
+            )?;
+            count += pushdown.count_rows().await?;
+        }
+        if !scan_frags.is_empty() {
+            let columns = Planner::column_names_in_expr(&predicate);
+            let filter_schema = Arc::new(self.dataset.schema().project(&columns)?);
+            let scan = self.scan_fragments(
+                true,
+                false,
+                false,
+                filter_schema.clone(),
+                Arc::new(scan_frags),
+                false,
+            );
+            let planner = Planner::new(Arc::new(filter_schema.as_ref().into()));
+            let optimized_filter = planner.optimize_expr(predicate)?;
+            let physical_filter = planner.create_physical_expr(&optimized_filter)?;
+            let plan = Arc::new(FilterExec::try_new(physical_filter, scan)?);
+            count += Self::count_plan_rows(plan).await?;
+        }
+        Ok(count)
+    }
+
+    async fn count_plan_rows(plan: Arc<dyn ExecutionPlan>) -> Result<u64> {
         // Datafusion interprets COUNT(*) as COUNT(1)
         let one = Arc::new(Literal::new(ScalarValue::UInt8(Some(1))));
         let count_expr = create_aggregate_expr(
@@ -778,6 +1025,46 @@ impl Scanner {
         }
     }
 
+    /// Split the filter into the part answered by scalar indices and the part that
+    /// must be evaluated against the data
+    async fn create_filter_plan(&self, use_scalar_index: bool) -> Result<FilterPlan> {
+        let planner = Planner::new(Arc::new(self.dataset.schema().into()));
+
+        if let Some(filter) = self.filter.as_ref() {
+            let index_info = self.dataset.scalar_index_info().await?;
+            let filter_plan =
+                planner.create_filter_plan(filter.clone(), &index_info, use_scalar_index)?;
+
+            // This tests if any of the fragments are missing the physical_rows property (old style)
+            // If they are then we cannot use scalar indices
+            if filter_plan.index_query.is_some() {
+                let fragments = if let Some(fragments) = self.fragments.as_ref() {
+                    fragments
+                } else {
+                    self.dataset.fragments()
+                };
+                let mut has_missing_row_count = false;
+                for frag in fragments {
+                    if frag.physical_rows.is_none() {
+                        has_missing_row_count = true;
+                        break;
+                    }
+                }
+                if has_missing_row_count {
+                    // We need row counts to use scalar indices.  If we don't have them then
+                    // fallback to a non-indexed filter
+                    Ok(planner.create_filter_plan(filter.clone(), &index_info, false)?)
+                } else {
+                    Ok(filter_plan)
+                }
+            } else {
+                Ok(filter_plan)
+            }
+        } else {
+            Ok(FilterPlan::default())
+        }
+    }
+
     /// Given a base schema and a list of desired fields figure out which fields, if any, still need loaded
     fn calc_new_fields<S: AsRef<str>>(
         &self,
@@ -851,40 +1138,25 @@ impl Scanner {
         // TODO: Should we use them when postfiltering if there is no vector search?
         let use_scalar_index = self.prefilter || self.nearest.is_none();
 
-        let planner = Planner::new(Arc::new(self.dataset.schema().into()));
-
-        let mut filter_plan = if let Some(filter) = self.filter.as_ref() {
-            let index_info = self.dataset.scalar_index_info().await?;
-            let filter_plan =
-                planner.create_filter_plan(filter.clone(), &index_info, use_scalar_index)?;
+        let mut filter_plan = self.create_filter_plan(use_scalar_index).await?;
 
-            // This tests if any of the fragments are missing the physical_rows property (old style)
-            // If they are then we cannot use scalar indices
-            if filter_plan.index_query.is_some() {
-                let fragments = if let Some(fragments) = self.fragments.as_ref() {
-                    fragments
-                } else {
-                    self.dataset.fragments()
-                };
-                let mut has_missing_row_count = false;
-                for frag in fragments {
-                    if frag.physical_rows.is_none() {
-                        has_missing_row_count = true;
-                        break;
-                    }
-                }
-                if has_missing_row_count {
-                    // We need row counts to use scalar indices.  If we don't have them then
-                    // fallback to a non-indexed filter
-                    planner.create_filter_plan(filter.clone(), &index_info, false)?
-                } else {
-                    filter_plan
-                }
-            } else {
-                filter_plan
+        // The offset can only be pushed into the scan if it applies to the rows
+        // exactly as they are stored.
+        let offset_skip = match (self.offset, &self.nearest, &self.ordering) {
+            (Some(offset), None, None)
+                if offset > 0 && filter_plan.index_query.is_none() && !filter_plan.has_refine() =>
+            {
+                self.skip_fragments(offset as usize)
             }
+            _ => None,
+        };
+
+        // If the fragments are already sorted by the requested ordering then they can be
+        // read in order instead of sorting, which lets a limit stop the scan early
+        let presorted_fragments = if self.nearest.is_none() && filter_plan.index_query.is_none() {
+            self.presorted_fragments()
         } else {
-            FilterPlan::default()
+            None
         };
 
         // Stage 1: source (either an (K|A)NN search or a (full|indexed) scan)
@@ -924,7 +1196,9 @@ impl Scanner {
                     self.scalar_indexed_scan(&filter_schema, index_query)
                         .await?
                 }
-                (None, Some(_)) if use_stats && self.batch_size.is_none() => {
+                (None, Some(_))
+                    if use_stats && self.batch_size.is_none() && presorted_fragments.is_none() =>
+                {
                     self.pushdown_scan(false, filter_plan.refine_expr.take().unwrap())?
                 }
                 (None, _) => {
@@ -938,7 +1212,26 @@ impl Scanner {
                     } else {
                         Arc::new(self.physical_columns.clone())
                     };
-                    self.scan(with_row_id, self.with_row_address, false, schema)
+                    if let Some(fragments) = presorted_fragments.clone() {
+                        self.scan_fragments(
+                            with_row_id,
+                            self.with_row_address,
+                            false,
+                            schema,
+                            fragments,
+                            true,
+                        )
+                    } else if let Some((fragments, first_fragment_offset)) = offset_skip.clone() {
+                        self.scan_with_offset(
+                            with_row_id,
+                            self.with_row_address,
+                            schema,
+                            fragments,
+                            first_fragment_offset,
+                        )
+                    } else {
+                        self.scan(with_row_id, self.with_row_address, false, schema)
+                    }
                 }
             }
         };
@@ -976,8 +1269,8 @@ impl Scanner {
             plan = Arc::new(FilterExec::try_new(physical_refine_expr, plan)?);
         }
 
-        // Stage 3: sort
-        if let Some(ordering) = &self.ordering {
+        // Stage 3: sort (unless the scan is already in the requested order)
+        if let (Some(ordering), None) = (&self.ordering, &presorted_fragments) {
             let order_by_schema = Arc::new(
                 self.dataset.schema().project(
                     &ordering
@@ -1007,8 +1300,14 @@ impl Scanner {
         }
 
         // Stage 4: limit / offset
-        if (self.limit.unwrap_or(0) > 0) || self.offset.is_some() {
-            plan = self.limit_node(plan);
+        let offset = if offset_skip.is_some() {
+            // Already applied by the scan
+            None
+        } else {
+            self.offset
+        };
+        if (self.limit.unwrap_or(0) > 0) || offset.is_some() {
+            plan = self.limit_node(plan, offset.unwrap_or(0) as usize);
         }
 
         // Stage 5: take remaining columns required for projection
@@ -1347,6 +1646,46 @@ impl Scanner {
         )
     }
 
+    /// Returns the fragments to scan, in dataset order, if they are already sorted by the
+    /// requested ordering
+    ///
+    /// This is the case if all of the fragments share a sort order (see
+    /// [`crate::dataset::WriteParams::sort_order`]) and the ordering is an ascending,
+    /// nulls first, prefix of it.
+    fn presorted_fragments(&self) -> Option<Arc<Vec<Fragment>>> {
+        let ordering = self.ordering.as_ref()?;
+        let fragments = if let Some(fragments) = self.fragments.as_ref() {
+            // Fragments are only sorted relative to each other in the order they appear in
+            // the dataset
+            let positions = self
+                .dataset
+                .fragments()
+                .iter()
+                .enumerate()
+                .map(|(position, fragment)| (fragment.id, position))
+                .collect::<HashMap<_, _>>();
+            if fragments.iter().any(|f| !positions.contains_key(&f.id)) {
+                return None;
+            }
+            let mut fragments = fragments.clone();
+            fragments.sort_by_key(|f| positions[&f.id]);
+            Arc::new(fragments)
+        } else {
+            self.dataset.fragments().clone()
+        };
+        let (first, rest) = fragments.split_first()?;
+        if ordering.len() > first.sort_order.len()
+            || rest.iter().any(|f| f.sort_order != first.sort_order)
+        {
+            return None;
+        }
+        let sort_order = self.dataset.sort_order_columns(&first.sort_order)?;
+        let matches = ordering.iter().zip(&sort_order).all(|(ordering, column)| {
+            ordering.ascending && ordering.nulls_first && &ordering.column_name == column
+        });
+        matches.then_some(fragments)
+    }
+
     fn scan_fragments(
         &self,
         with_row_id: bool,
@@ -1370,6 +1709,56 @@ impl Scanner {
         ))
     }
 
+    /// Drop the fragments that are entirely covered by `offset`.
+    ///
+    /// Returns the remaining fragments and the number of rows to skip in the
+    /// first of them.  Returns None if a fragment is missing the row counts
+    /// needed to skip it without reading it.
+    fn skip_fragments(&self, offset: usize) -> Option<(Vec<Fragment>, usize)> {
+        let fragments = if let Some(fragments) = self.fragments.as_ref() {
+            fragments.as_slice()
+        } else {
+            self.dataset.fragments().as_slice()
+        };
+        let mut remaining = offset;
+        for (idx, fragment) in fragments.iter().enumerate() {
+            // Deletion adjusted, the offset counts rows that are visible
+            let num_rows = fragment.num_rows()?;
+            if remaining < num_rows {
+                return Some((fragments[idx..].to_vec(), remaining));
+            }
+            remaining -= num_rows;
+        }
+        Some((Vec::new(), 0))
+    }
+
+    /// Full scan of `fragments` that skips the first `first_fragment_offset`
+    /// rows of the first fragment.
+    fn scan_with_offset(
+        &self,
+        with_row_id: bool,
+        with_row_address: bool,
+        projection: Arc<Schema>,
+        fragments: Vec<Fragment>,
+        first_fragment_offset: usize,
+    ) -> Arc<dyn ExecutionPlan> {
+        Arc::new(
+            LanceScanExec::new(
+                self.dataset.clone(),
+                Arc::new(fragments),
+                projection,
+                self.get_batch_size(),
+                self.batch_readahead,
+                self.fragment_readahead,
+                with_row_id,
+                with_row_address,
+                false,
+                self.ordered,
+            )
+            .with_first_fragment_offset(first_fragment_offset),
+        )
+    }
+
     fn pushdown_scan(
         &self,
         make_deletions_null: bool,
@@ -1423,10 +1812,19 @@ impl Scanner {
         )
         .with_fetch(Some(q.k));
 
-        let not_nulls = FilterExec::try_new(
-            expressions::is_not_null(expressions::col(DIST_COL, sort.schema().as_ref())?)?,
-            Arc::new(sort),
-        )?;
+        let dist_col = expressions::col(DIST_COL, sort.schema().as_ref())?;
+        let predicate = if let Some(radius) = q.radius {
+            // A range search keeps the rows within the radius, which are never null
+            expressions::binary(
+                dist_col,
+                Operator::Lt,
+                expressions::lit(radius),
+                sort.schema().as_ref(),
+            )?
+        } else {
+            expressions::is_not_null(dist_col)?
+        };
+        let not_nulls = FilterExec::try_new(predicate, Arc::new(sort))?;
 
         Ok(Arc::new(not_nulls))
     }
@@ -1515,10 +1913,10 @@ impl Scanner {
     }
 
     /// Global offset-limit of the result of the input plan
-    fn limit_node(&self, plan: Arc<dyn ExecutionPlan>) -> Arc<dyn ExecutionPlan> {
+    fn limit_node(&self, plan: Arc<dyn ExecutionPlan>, offset: usize) -> Arc<dyn ExecutionPlan> {
         Arc::new(GlobalLimitExec::new(
             plan,
-            *self.offset.as_ref().unwrap_or(&0) as usize,
+            offset,
             self.limit.map(|l| l as usize),
         ))
     }
@@ -1529,6 +1927,80 @@ impl Scanner {
 
         Ok(format!("{}", display.indent(verbose)))
     }
+
+    /// Runs the scan, discarding the results, and returns the plan annotated with the
+    /// metrics (e.g. bytes read) of each node
+    ///
+    /// The totals for the whole scan can be found with [`ScanStatistics::from_plan`].
+    pub async fn explain_analyze(&self) -> Result<String> {
+        let plan = self.create_plan().await?;
+        let mut stream = execute_plan(plan.clone(), LanceExecutionOptions::default())?;
+        while stream.try_next().await?.is_some() {}
+        drop(stream);
+        let display = DisplayableExecutionPlan::with_metrics(plan.as_ref());
+
+        Ok(format!("{}", display.indent(true)))
+    }
+}
+
+/// The results of a range search, see [Scanner::try_into_range_batch]
+#[derive(Debug, Clone)]
+pub struct RangeSearchResult {
+    /// The rows within the radius, closest first
+    pub batch: RecordBatch,
+    /// True if more rows than the cap on the number of results were within the
+    /// radius, only the closest ones are in `batch`
+    pub truncated: bool,
+}
+
+/// The work done by a scan
+///
+/// I/O and decode work is only counted for data files in the v2 format.  The statistics of
+/// a scan can be retrieved from [`DatasetRecordBatchStream::statistics`] once the stream is
+/// finished, or shown per plan node with [`Scanner::explain_analyze`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanStatistics {
+    /// The number of I/O requests issued to read data files
+    pub iops: u64,
+    /// The number of bytes read from data files
+    pub bytes_read: u64,
+    /// The number of pages that were scheduled for decoding
+    pub pages_scheduled: u64,
+    /// The number of bytes of decoded (decompressed / unpacked) data produced
+    pub bytes_decoded: u64,
+    /// The time spent decoding, summed across all decode tasks
+    pub decode_time: Duration,
+    /// The number of pages that were not read because their statistics show that no row
+    /// matches the filter
+    pub pages_skipped: u64,
+    /// The number of scalar index searches
+    pub index_searches: u64,
+}
+
+impl ScanStatistics {
+    /// Sums the metrics of all the nodes of an executed plan
+    pub fn from_plan(plan: &dyn ExecutionPlan) -> Self {
+        let sum = |name: &str| -> u64 {
+            let mut total = 0;
+            let mut to_visit = vec![plan];
+            while let Some(node) = to_visit.pop() {
+                if let Some(value) = node.metrics().and_then(|metrics| metrics.sum_by_name(name)) {
+                    total += value.as_usize() as u64;
+                }
+                to_visit.extend(node.children().into_iter().map(|child| child.as_ref()));
+            }
+            total
+        };
+        Self {
+            iops: sum(IOPS_METRIC),
+            bytes_read: sum(BYTES_READ_METRIC),
+            pages_scheduled: sum(PAGES_SCHEDULED_METRIC),
+            bytes_decoded: sum(BYTES_DECODED_METRIC),
+            decode_time: Duration::from_nanos(sum(DECODE_TIME_METRIC)),
+            pages_skipped: sum(PAGES_SKIPPED_METRIC),
+            index_searches: sum(INDEX_SEARCHES_METRIC),
+        }
+    }
 }
 
 /// [`DatasetRecordBatchStream`] wraps the dataset into a [`RecordBatchStream`] for
@@ -1539,12 +2011,34 @@ pub struct DatasetRecordBatchStream {
     #[pin]
     exec_node: SendableRecordBatchStream,
     span: Span,
+    plan: Option<Arc<dyn ExecutionPlan>>,
 }
 
 impl DatasetRecordBatchStream {
     pub fn new(exec_node: SendableRecordBatchStream) -> Self {
         let span = info_span!("DatasetRecordBatchStream");
-        Self { exec_node, span }
+        Self {
+            exec_node,
+            span,
+            plan: None,
+        }
+    }
+
+    /// Keeps the plan that produces the stream so that its statistics can be retrieved
+    pub(crate) fn with_plan(mut self, plan: Arc<dyn ExecutionPlan>) -> Self {
+        self.plan = Some(plan);
+        self
+    }
+
+    /// The work done by the scan so far
+    ///
+    /// The I/O and decode counters are only updated when the stream finishes and so
+    /// this should be called after the stream is exhausted.  Returns None if the stream
+    /// was not created by a [`Scanner`].
+    pub fn statistics(&self) -> Option<ScanStatistics> {
+        self.plan
+            .as_ref()
+            .map(|plan| ScanStatistics::from_plan(plan.as_ref()))
     }
 }
 
@@ -1727,10 +2221,10 @@ mod test {
     use arrow::array::as_primitive_array;
     use arrow::datatypes::Int32Type;
     use arrow_array::cast::AsArray;
-    use arrow_array::types::{Float32Type, UInt64Type};
+    use arrow_array::types::{Float32Type, Float64Type, UInt64Type};
     use arrow_array::{
-        ArrayRef, FixedSizeListArray, Float16Array, Int32Array, LargeStringArray, PrimitiveArray,
-        RecordBatchIterator, StringArray, StructArray,
+        ArrayRef, FixedSizeListArray, Float16Array, Float64Array, Int32Array, LargeStringArray,
+        PrimitiveArray, RecordBatchIterator, StringArray, StructArray,
     };
     use arrow_ord::sort::sort_to_indices;
     use arrow_select::take;
@@ -1910,37 +2404,156 @@ mod test {
 
     #[rstest]
     #[tokio::test]
-    async fn test_knn_nodes(
-        #[values(false, true)] use_legacy_format: bool,
-        #[values(false, true)] stable_row_ids: bool,
-    ) {
-        for build_index in &[true, false] {
-            let mut test_ds = TestVectorDataset::new(use_legacy_format, stable_row_ids)
-                .await
-                .unwrap();
-            if *build_index {
-                test_ds.make_vector_index().await.unwrap();
-            }
-            let dataset = &test_ds.dataset;
+    async fn test_offset(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
 
-            let mut scan = dataset.scan();
-            let key: Float32Array = (32..64).map(|v| v as f32).collect();
-            scan.nearest("vec", &key, 5).unwrap();
-            scan.refine(5);
+        // 4 fragments of 50 rows
+        let data = gen().col("i", array::step::<Int32Type>());
+        let mut dataset = Dataset::write(
+            data.into_reader_rows(RowCount::from(50), BatchCount::from(4)),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 50,
+                max_rows_per_group: 10,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        dataset.delete("i % 7 = 0").await?;
+        let dataset = Arc::new(dataset);
+        let live = (0..200).filter(|i| i % 7 != 0).collect::<Vec<i32>>();
 
-            let results = scan
-                .try_into_stream()
-                .await
-                .unwrap()
-                .try_collect::<Vec<_>>()
-                .await
-                .unwrap();
+        async fn scan_values(scanner: &Scanner) -> Result<Vec<i32>> {
+            let batch = scanner.try_into_batch().await?;
+            Ok(batch["i"].as_primitive::<Int32Type>().values().to_vec())
+        }
 
-            assert_eq!(results.len(), 1);
-            let batch = &results[0];
+        // 42 is the first row of the second fragment, 171 is the number of rows
+        for offset in [0, 1, 30, 42, 60, 170, 171, 500] {
+            for limit in [None, Some(10)] {
+                let mut scanner = dataset.scan();
+                scanner.limit(limit, None)?.offset(offset as i64)?;
+                let expected = live
+                    .iter()
+                    .copied()
+                    .skip(offset)
+                    .take(limit.unwrap_or(i64::MAX) as usize)
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    scan_values(&scanner).await?,
+                    expected,
+                    "offset={} limit={:?}",
+                    offset,
+                    limit
+                );
+            }
+        }
 
-            assert_eq!(batch.num_rows(), 5);
-            assert_eq!(
+        // Setting a limit without an offset keeps the offset
+        let mut scanner = dataset.scan();
+        scanner.offset(30)?.limit(Some(10), None)?;
+        assert_eq!(scan_values(&scanner).await?, live[30..40]);
+
+        // Without a filter the offset is applied by the scan
+        let mut scanner = dataset.scan();
+        scanner.offset(60)?;
+        let plan = scanner.explain_plan(false).await?;
+        assert!(plan.contains("first_fragment_offset=18"), "{}", plan);
+        assert!(!plan.contains("GlobalLimitExec"), "{}", plan);
+
+        let mut scanner = dataset.scan();
+        scanner.offset(60)?.scan_in_order(false);
+        let batch = scanner.try_into_batch().await?;
+        let mut values = batch["i"].as_primitive::<Int32Type>().values().to_vec();
+        values.sort();
+        assert_eq!(values, live[60..]);
+
+        // With a filter the offset applies to the filtered rows
+        let mut scanner = dataset.scan();
+        scanner
+            .filter("i % 2 = 0")?
+            .limit(Some(3), None)?
+            .offset(5)?;
+        let plan = scanner.explain_plan(false).await?;
+        assert!(
+            plan.contains("GlobalLimitExec: skip=5, fetch=3"),
+            "{}",
+            plan
+        );
+        assert!(!plan.contains("first_fragment_offset"), "{}", plan);
+        let expected = live
+            .iter()
+            .copied()
+            .filter(|i| i % 2 == 0)
+            .skip(5)
+            .take(3)
+            .collect::<Vec<_>>();
+        assert_eq!(scan_values(&scanner).await?, expected);
+
+        // With an ordering the offset applies to the sorted rows
+        let mut scanner = dataset.scan();
+        scanner
+            .order_by(Some(vec![ColumnOrdering {
+                column_name: "i".to_string(),
+                ascending: false,
+                nulls_first: false,
+            }]))?
+            .limit(Some(3), None)?
+            .offset(5)?;
+        let plan = scanner.explain_plan(false).await?;
+        assert!(
+            plan.contains("GlobalLimitExec: skip=5, fetch=3"),
+            "{}",
+            plan
+        );
+        let expected = live
+            .iter()
+            .rev()
+            .copied()
+            .skip(5)
+            .take(3)
+            .collect::<Vec<_>>();
+        assert_eq!(scan_values(&scanner).await?, expected);
+
+        assert!(dataset.scan().offset(-1).is_err());
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_knn_nodes(
+        #[values(false, true)] use_legacy_format: bool,
+        #[values(false, true)] stable_row_ids: bool,
+    ) {
+        for build_index in &[true, false] {
+            let mut test_ds = TestVectorDataset::new(use_legacy_format, stable_row_ids)
+                .await
+                .unwrap();
+            if *build_index {
+                test_ds.make_vector_index().await.unwrap();
+            }
+            let dataset = &test_ds.dataset;
+
+            let mut scan = dataset.scan();
+            let key: Float32Array = (32..64).map(|v| v as f32).collect();
+            scan.nearest("vec", &key, 5).unwrap();
+            scan.refine(5);
+
+            let results = scan
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+
+            assert_eq!(results.len(), 1);
+            let batch = &results[0];
+
+            assert_eq!(batch.num_rows(), 5);
+            assert_eq!(
                 batch.schema().as_ref(),
                 &ArrowSchema::new(vec![
                     ArrowField::new("i", DataType::Int32, true),
@@ -2586,6 +3199,181 @@ mod test {
         assert_eq!(batches_by_int_then_float[0], sorted_by_int_then_float);
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_order_by_presorted(#[values(false, true)] use_legacy_format: bool) {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("ts", DataType::Int32, false),
+            ArrowField::new("value", DataType::Int32, false),
+        ]));
+        let make_data = |ts: Vec<i32>| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ts.clone())),
+                    Arc::new(Int32Array::from(ts)),
+                ],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let sorted_params = |mode| WriteParams {
+            mode,
+            max_rows_per_file: 100,
+            use_legacy_format,
+            sort_order: Some(vec!["ts".to_string()]),
+            ..Default::default()
+        };
+
+        let (io_stats_wrapper, io_stats) = IoTrackingStore::new_wrapper();
+        let mut dataset = Dataset::write(
+            make_data((0..10_000).collect()),
+            "memory://test",
+            Some(WriteParams {
+                store_params: Some(ObjectStoreParams {
+                    object_store_wrapper: Some(io_stats_wrapper),
+                    ..Default::default()
+                }),
+                ..sorted_params(WriteMode::Create)
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(dataset.get_fragments().len(), 100);
+        assert_eq!(dataset.sort_order(), Some(vec!["ts".to_string()]));
+
+        let ordered_scan = |dataset: &Dataset, ordering: ColumnOrdering| {
+            let mut scan = dataset.scan();
+            scan.order_by(Some(vec![ordering]))
+                .unwrap()
+                .limit(Some(10), None)
+                .unwrap()
+                .fragment_readahead(1)
+                .batch_readahead(1);
+            scan
+        };
+        let ts_values =
+            |batch: &RecordBatch| batch["ts"].as_primitive::<Int32Type>().values().to_vec();
+        let get_bytes = || io_stats.lock().unwrap().read_bytes;
+
+        // The fragments are read in order and the scan stops once the limit is reached
+        let scan = ordered_scan(&dataset, ColumnOrdering::asc_nulls_first("ts".to_string()));
+        let plan = scan.explain_plan(false).await.unwrap();
+        assert!(!plan.contains("SortExec"), "{}", plan);
+        assert!(plan.contains("ordered=true"), "{}", plan);
+        let start_bytes = get_bytes();
+        let batch = scan.try_into_batch().await.unwrap();
+        let presorted_bytes = get_bytes() - start_bytes;
+        assert_eq!(ts_values(&batch), (0..10).collect::<Vec<_>>());
+
+        // Descending order does not match the sort order and so everything is read and sorted
+        let scan = ordered_scan(&dataset, ColumnOrdering::desc_nulls_first("ts".to_string()));
+        assert!(scan.explain_plan(false).await.unwrap().contains("SortExec"));
+        let start_bytes = get_bytes();
+        let batch = scan.try_into_batch().await.unwrap();
+        let sorted_bytes = get_bytes() - start_bytes;
+        assert_eq!(ts_values(&batch), (9_990..10_000).rev().collect::<Vec<_>>());
+        assert!(
+            presorted_bytes * 10 < sorted_bytes,
+            "presorted: {}, sorted: {}",
+            presorted_bytes,
+            sorted_bytes
+        );
+
+        // In order appends and compaction keep the sort order
+        dataset
+            .append(
+                make_data((10_000..10_050).collect()),
+                Some(sorted_params(WriteMode::Append)),
+            )
+            .await
+            .unwrap();
+        compact_files(&mut dataset, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(dataset.sort_order(), Some(vec!["ts".to_string()]));
+        let scan = ordered_scan(&dataset, ColumnOrdering::asc_nulls_first("ts".to_string()));
+        assert!(!scan.explain_plan(false).await.unwrap().contains("SortExec"));
+
+        // Rows that sort before the existing rows are not marked as sorted
+        dataset
+            .append(
+                make_data((0..50).collect()),
+                Some(sorted_params(WriteMode::Append)),
+            )
+            .await
+            .unwrap();
+        let fragments = dataset.get_fragments();
+        assert!(fragments.last().unwrap().metadata().sort_order.is_empty());
+        assert_eq!(dataset.sort_order(), None);
+        let scan = ordered_scan(&dataset, ColumnOrdering::asc_nulls_first("ts".to_string()));
+        assert!(scan.explain_plan(false).await.unwrap().contains("SortExec"));
+        let batch = scan.try_into_batch().await.unwrap();
+        assert_eq!(ts_values(&batch), vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_sort_order_schema_changes() {
+        use crate::dataset::{ColumnAlteration, NewColumnTransform};
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("value", DataType::Int32, false),
+            ArrowField::new("ts", DataType::Int32, false),
+        ]));
+        let write = |uri: String| {
+            let schema = schema.clone();
+            async move {
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(0..100)),
+                        Arc::new(Int32Array::from_iter_values(0..100)),
+                    ],
+                )
+                .unwrap();
+                Dataset::write(
+                    RecordBatchIterator::new(vec![Ok(batch)], schema),
+                    &uri,
+                    Some(WriteParams {
+                        sort_order: Some(vec!["ts".to_string()]),
+                        ..Default::default()
+                    }),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // The sort order follows a renamed column
+        let mut dataset = write("memory://renamed".to_string()).await;
+        dataset
+            .alter_columns(&[ColumnAlteration::new("ts".into()).rename("time".into())])
+            .await
+            .unwrap();
+        assert_eq!(dataset.sort_order(), Some(vec!["time".to_string()]));
+
+        // A cast may change the order of the values
+        dataset
+            .alter_columns(&[ColumnAlteration::new("time".into()).cast_to(DataType::Int64)])
+            .await
+            .unwrap();
+        assert_eq!(dataset.sort_order(), None);
+        assert!(dataset.fragments()[0].sort_order.is_empty());
+
+        // A column that is dropped and added again is not sorted, even if the new field
+        // reuses the id of the dropped one
+        let mut dataset = write("memory://readded".to_string()).await;
+        dataset.drop_columns(&["ts"]).await.unwrap();
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("ts".into(), "100 - value".into())]),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(dataset.sort_order(), None);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_ann_prefilter(
@@ -2667,6 +3455,192 @@ mod test {
         assert_eq!(6, first_match);
     }
 
+    fn diagonal_schema() -> Arc<ArrowSchema> {
+        Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("vector", fixed_size_list_type(2, DataType::Float32), true),
+        ]))
+    }
+
+    /// Rows with the vector `[id + offset, id + offset]`, so that the distance between
+    /// rows grows with the difference of their ids
+    fn diagonal_batch(ids: std::ops::Range<i32>, offset: f32) -> RecordBatch {
+        let vectors = Float32Array::from_iter_values(
+            ids.clone()
+                .flat_map(|id| [id as f32 + offset, id as f32 + offset]),
+        );
+        RecordBatch::try_new(
+            diagonal_schema(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(ids)),
+                Arc::new(FixedSizeListArray::try_new_from_values(vectors, 2).unwrap()),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_ann_prefilter_recall(
+        #[values(
+            VectorIndexParams::ivf_flat(8, MetricType::L2),
+            VectorIndexParams::ivf_pq(8, 8, 2, MetricType::L2, 2),
+            VectorIndexParams::with_ivf_hnsw_sq_params(
+                MetricType::L2,
+                IvfBuildParams::new(8),
+                HnswBuildParams::default(),
+                SQBuildParams::default()
+            )
+        )]
+        index_params: VectorIndexParams,
+    ) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = diagonal_schema();
+
+        let batches =
+            RecordBatchIterator::new(vec![Ok(diagonal_batch(0..1000, 0.0))], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &index_params, false)
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["id"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        // Some of the closest matching rows are deleted and some are in an unindexed
+        // fragment
+        dataset.delete("id = 900 OR id = 903").await.unwrap();
+        let batches = RecordBatchIterator::new(
+            vec![Ok(diagonal_batch(2000..2003, -1095.5))],
+            schema.clone(),
+        );
+        dataset.append(batches, None).await.unwrap();
+
+        // The query is far from every matching row so the closest partition has none
+        let query_key = Arc::new(Float32Array::from(vec![0.0, 0.0]));
+        let k = 10;
+        let search = |use_index: bool| {
+            let dataset = &dataset;
+            let query_key = query_key.clone();
+            async move {
+                let mut scan = dataset.scan();
+                scan.filter("id >= 900").unwrap();
+                scan.nearest("vector", &query_key, k).unwrap();
+                scan.nprobs(1);
+                scan.prefilter(true);
+                scan.use_index(use_index);
+                let batch = scan.try_into_batch().await.unwrap();
+                batch["id"]
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .iter()
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+            }
+        };
+
+        let expected = search(false).await;
+        assert_eq!(
+            expected,
+            BTreeSet::from([901, 902, 904, 905, 906, 907, 908, 2000, 2001, 2002])
+        );
+        let actual = search(true).await;
+        assert_eq!(actual.len(), k);
+        let recall = actual.intersection(&expected).count() as f32 / k as f32;
+        assert!(recall >= 0.9, "recall {} for {:?}", recall, actual);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_nearest_within(
+        #[values(false, true)] use_index: bool,
+        #[values(false, true)] prefilter: bool,
+    ) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = diagonal_schema();
+
+        let indexed = diagonal_batch(0..100, 0.0);
+        let batches = RecordBatchIterator::new(vec![Ok(indexed.clone())], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+        dataset
+            .create_index(
+                &["vector"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::ivf_flat(4, MetricType::L2),
+                false,
+            )
+            .await
+            .unwrap();
+        // The unindexed rows are searched with a flat search
+        let unindexed = diagonal_batch(100..106, -92.0);
+        let batches = RecordBatchIterator::new(vec![Ok(unindexed.clone())], schema.clone());
+        dataset.append(batches, None).await.unwrap();
+
+        let query_key = Float32Array::from(vec![10.0, 10.0]);
+        let radius = 60.5;
+        let mut expected = Vec::new();
+        for batch in [&indexed, &unindexed] {
+            let ids = batch["id"].as_primitive::<Int32Type>();
+            let vectors = batch["vector"].as_fixed_size_list();
+            for (row, id) in ids.values().iter().enumerate() {
+                let vector = vectors.value(row);
+                let vector = vector.as_primitive::<Float32Type>();
+                let distance = MetricType::L2.func()(query_key.values(), vector.values());
+                if distance < radius && id % 2 == 0 {
+                    expected.push((distance, *id));
+                }
+            }
+        }
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let expected_ids = expected.iter().map(|(_, id)| *id).collect::<BTreeSet<_>>();
+
+        let mut scan = dataset.scan();
+        scan.project(&["id"]).unwrap();
+        scan.filter("id % 2 = 0").unwrap();
+        scan.prefilter(prefilter);
+        scan.nearest_within("vector", &query_key, radius).unwrap();
+        scan.nprobs(4);
+        scan.use_index(use_index);
+        if !prefilter {
+            // The rows removed by a postfilter would hide whether the cap was reached
+            let res = scan.try_into_range_batch().await;
+            assert!(matches!(res, Err(Error::InvalidInput { .. })), "{:?}", res);
+            scan.prefilter(true);
+        }
+        let result = scan.try_into_range_batch().await.unwrap();
+        assert!(!result.truncated);
+        let ids = result.batch["id"]
+            .as_primitive::<Int32Type>()
+            .values()
+            .iter()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        assert_eq!(ids, expected_ids);
+        let distances = result.batch[DIST_COL].as_primitive::<Float32Type>();
+        assert!(distances.values().iter().all(|d| *d < radius));
+
+        // Only the closest rows are kept when the cap is reached
+        scan.max_range_results(3).unwrap();
+        let result = scan.try_into_range_batch().await.unwrap();
+        assert!(result.truncated);
+        let distances = result.batch[DIST_COL].as_primitive::<Float32Type>();
+        let expected_distances = expected.iter().take(3).map(|(d, _)| *d).collect::<Vec<_>>();
+        assert_eq!(distances.values().to_vec(), expected_distances);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_filter_on_large_utf8(#[values(false, true)] use_legacy_format: bool) {
@@ -3089,6 +4063,113 @@ mod test {
         );
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_count_rows_matches_brute_force(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("c", DataType::Int32, false),
+        ]));
+        let make_batch = |range: std::ops::Range<i32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(range.clone())),
+                    // Every 5th value is null and the last page only has nulls
+                    Arc::new(StringArray::from_iter(range.clone().map(|i| {
+                        if i % 5 == 0 || i >= 3900 {
+                            None
+                        } else {
+                            Some(format!("s{}", i % 7))
+                        }
+                    }))),
+                    Arc::new(Int32Array::from_iter_values(range.map(|i| i % 10))),
+                ],
+            )
+            .unwrap()
+        };
+        let write_params = |mode| WriteParams {
+            mode,
+            max_rows_per_file: 1000,
+            max_rows_per_group: 100,
+            use_legacy_format,
+            ..Default::default()
+        };
+
+        let reader = RecordBatchIterator::new(vec![Ok(make_batch(0..3000))], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params(WriteMode::Create)))
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["c"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                true,
+            )
+            .await
+            .unwrap();
+        // The new fragment isn't covered by the index
+        let reader = RecordBatchIterator::new(vec![Ok(make_batch(3000..4000))], schema.clone());
+        dataset
+            .append(reader, Some(write_params(WriteMode::Append)))
+            .await
+            .unwrap();
+        dataset.delete("a % 3 = 0 AND a < 1500").await.unwrap();
+        dataset.delete("a >= 3950").await.unwrap();
+        dataset.delete("a >= 2000 AND a < 3000").await.unwrap();
+
+        let all_rows = dataset.scan().try_into_batch().await.unwrap();
+        let planner = Planner::new(schema.clone());
+        let brute_force_count = |filter: &str| {
+            let expr = planner.parse_filter(filter).unwrap();
+            let expr = planner.optimize_expr(expr).unwrap();
+            let physical_expr = planner.create_physical_expr(&expr).unwrap();
+            let result = physical_expr
+                .evaluate(&all_rows)
+                .unwrap()
+                .into_array(all_rows.num_rows())
+                .unwrap();
+            result.as_boolean().true_count() as u64
+        };
+
+        assert!(brute_force_count("s IS NULL") > 0);
+
+        for filter in [
+            "a < 1234",
+            "a >= 3500",
+            "a > 100000",
+            "a >= 0",
+            "s IS NULL",
+            "s IS NOT NULL",
+            "s > 's3'",
+            "NOT (s > 's3')",
+            "s != 's2'",
+            "s = 's2' OR a < 50",
+            "a > 500 AND s IS NULL",
+            "a >= 3900 AND s = 's1'",
+            "c = 4",
+            "c IN (1, 2)",
+            "c > 7",
+            "NOT (c = 3)",
+            "c = 4 AND s > 's2'",
+            "c = 4 OR s IS NULL",
+        ] {
+            let count = dataset
+                .scan()
+                .filter(filter)
+                .unwrap()
+                .count_rows()
+                .await
+                .unwrap();
+            assert_eq!(count, brute_force_count(filter), "filter: {}", filter);
+        }
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_dynamic_projection(#[values(false, true)] use_legacy_format: bool) {
@@ -3134,6 +4215,96 @@ mod test {
         }
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_computed_projection(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let struct_fields = arrow_schema::Fields::from(vec![
+            ArrowField::new("x", DataType::Int32, true),
+            ArrowField::new("y", DataType::Int32, true),
+        ]);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("price", DataType::Float64, false),
+            ArrowField::new("quantity", DataType::Int32, false),
+            ArrowField::new("name", DataType::Utf8, false),
+            ArrowField::new("point", DataType::Struct(struct_fields.clone()), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float64Array::from_iter_values(
+                    (0..10).map(|i| i as f64 / 2.0),
+                )),
+                Arc::new(Int32Array::from_iter_values(0..10)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..10).map(|i| format!("name-{}", i)),
+                )),
+                Arc::new(StructArray::new(
+                    struct_fields,
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(0..10)),
+                        Arc::new(Int32Array::from_iter_values((0..10).map(|i| i * 10))),
+                    ],
+                    None,
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let write_params = WriteParams {
+            use_legacy_format,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let batch = dataset
+            .scan()
+            .project_with_transform(&[
+                ("quantity", "quantity"),
+                ("total", "price * quantity"),
+                ("prefix", "substr(name, 1, 4)"),
+                ("sum", "point.x + point.y"),
+            ])
+            .unwrap()
+            .filter("quantity > 5")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+
+        // Columns only used by the expressions are not part of the output
+        let field_names = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(field_names, vec!["quantity", "total", "prefix", "sum"]);
+        assert_eq!(
+            batch["quantity"].as_primitive::<Int32Type>().values(),
+            &[6, 7, 8, 9]
+        );
+        assert_eq!(
+            batch["total"].as_primitive::<Float64Type>().values(),
+            &[18.0, 24.5, 32.0, 40.5]
+        );
+        assert_eq!(
+            batch["prefix"]
+                .as_string::<i32>()
+                .iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            vec!["name"; 4]
+        );
+        assert_eq!(
+            batch["sum"].as_primitive::<Int32Type>().values(),
+            &[66, 77, 88, 99]
+        );
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_column_casting_function(#[values(false, true)] use_legacy_format: bool) {
@@ -3885,6 +5056,175 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_filter_nested_and_list(
+        #[values(false, true)] use_legacy_format: bool,
+    ) -> Result<()> {
+        use arrow_array::builder::{ListBuilder, StringBuilder};
+
+        // meta: {score, user: {age, address: {zip, city}, country}}
+        let zip = ArrowField::new("zip", DataType::Int32, true);
+        let city = ArrowField::new("city", DataType::Utf8, true);
+        let address = ArrowField::new(
+            "address",
+            DataType::Struct(vec![zip.clone(), city.clone()].into()),
+            true,
+        );
+        let age = ArrowField::new("age", DataType::Int32, true);
+        let country = ArrowField::new("country", DataType::Utf8, true);
+        let user = ArrowField::new(
+            "user",
+            DataType::Struct(vec![age.clone(), address.clone(), country.clone()].into()),
+            true,
+        );
+        let score = ArrowField::new("score", DataType::Float32, true);
+        let meta = ArrowField::new(
+            "meta",
+            DataType::Struct(vec![score.clone(), user.clone()].into()),
+            true,
+        );
+        let tags = ArrowField::new(
+            "tags",
+            DataType::List(Arc::new(ArrowField::new("item", DataType::Utf8, true))),
+            true,
+        );
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, true),
+            meta,
+            tags,
+        ]));
+
+        let num_rows = 100;
+        let address_arr = StructArray::from(vec![
+            (
+                Arc::new(zip),
+                Arc::new(Int32Array::from_iter_values(0..num_rows)) as ArrayRef,
+            ),
+            (
+                Arc::new(city),
+                Arc::new(StringArray::from_iter_values(
+                    (0..num_rows).map(|i| ["Berlin", "Paris"][i as usize % 2]),
+                )) as ArrayRef,
+            ),
+        ]);
+        let user_arr = StructArray::from(vec![
+            (
+                Arc::new(age),
+                Arc::new(Int32Array::from_iter_values((0..num_rows).map(|i| i % 50))) as ArrayRef,
+            ),
+            (Arc::new(address), Arc::new(address_arr) as ArrayRef),
+            (
+                Arc::new(country),
+                Arc::new(StringArray::from_iter_values(
+                    (0..num_rows).map(|i| ["DE", "FR", "US"][i as usize % 3]),
+                )) as ArrayRef,
+            ),
+        ]);
+        let meta_arr = StructArray::from(vec![
+            (
+                Arc::new(score),
+                Arc::new(Float32Array::from_iter_values(
+                    (0..num_rows).map(|i| i as f32),
+                )) as ArrayRef,
+            ),
+            (Arc::new(user), Arc::new(user_arr) as ArrayRef),
+        ]);
+        let mut tags_builder = ListBuilder::new(StringBuilder::new());
+        for i in 0..num_rows {
+            if i % 4 == 0 {
+                tags_builder.values().append_value("gpu");
+            }
+            tags_builder.values().append_value("cpu");
+            tags_builder.append(true);
+        }
+        let data = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..num_rows)),
+                Arc::new(meta_arr),
+                Arc::new(tags_builder.finish()),
+            ],
+        )
+        .unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 40,
+            max_rows_per_group: 10,
+            use_legacy_format,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(data.clone())], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, Some(write_params)).await?;
+
+        type ExpectedFn = fn(i32) -> bool;
+        let cases: Vec<(&str, ExpectedFn)> = vec![
+            ("meta.user.country = 'DE'", |i| i % 3 == 0),
+            ("meta.user.address.city = 'Paris'", |i| i % 2 == 1),
+            ("array_has(tags, 'gpu')", |i| i % 4 == 0),
+            (
+                "meta.user.address.city = 'Berlin' AND id > 50 AND array_has(tags, 'gpu')",
+                |i| i % 2 == 0 && i > 50 && i % 4 == 0,
+            ),
+            ("meta.user.age > 40 AND meta.score < 90", |i| {
+                i % 50 > 40 && i < 90
+            }),
+        ];
+        for (filter, expected_fn) in cases {
+            let mask =
+                arrow_array::BooleanArray::from_iter((0..num_rows).map(|i| Some(expected_fn(i))));
+            let expected = arrow_select::filter::filter_record_batch(&data, &mask).unwrap();
+
+            let mut scan = dataset.scan();
+            scan.filter(filter)?;
+            assert_eq!(scan.try_into_batch().await?, expected, "{}", filter);
+
+            let mut scan = dataset.scan();
+            scan.project(&["id"])?.filter(filter)?;
+            let actual = scan.try_into_batch().await?;
+            assert_eq!(actual["id"].as_ref(), expected["id"].as_ref(), "{}", filter);
+        }
+
+        if !use_legacy_format {
+            // Only the leaf column of the filter is read before the filter
+            let mut scan = dataset.scan();
+            scan.filter("meta.user.address.city = 'Paris'")?;
+            let plan = scan.create_plan().await?;
+            let mut node = plan;
+            while node.name() != "LanceScanExec" {
+                node = node.children()[0].clone();
+            }
+            let leaf_schema = ArrowSchema::new(vec![ArrowField::new(
+                "meta",
+                DataType::Struct(
+                    vec![ArrowField::new(
+                        "user",
+                        DataType::Struct(
+                            vec![ArrowField::new(
+                                "address",
+                                DataType::Struct(
+                                    vec![ArrowField::new("city", DataType::Utf8, true)].into(),
+                                ),
+                                true,
+                            )]
+                            .into(),
+                        ),
+                        true,
+                    )]
+                    .into(),
+                ),
+                true,
+            )]);
+            assert_eq!(
+                node.schema().as_ref(),
+                &leaf_schema.try_with_column(ROW_ID_FIELD.clone())?
+            );
+        }
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_plans(
@@ -4306,4 +5646,106 @@ mod test {
         .await
         .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_scan_statistics() {
+        let data = gen()
+            .col("i", array::step::<Int32Type>())
+            .col("vec", array::rand_vec::<Float32Type>(Dimension::from(16)))
+            .into_reader_rows(RowCount::from(500), BatchCount::from(4));
+        let (io_stats_wrapper, io_stats) = IoTrackingStore::new_wrapper();
+        let dataset = Dataset::write(
+            data,
+            "memory://test",
+            Some(WriteParams {
+                store_params: Some(ObjectStoreParams {
+                    object_store_wrapper: Some(io_stats_wrapper),
+                    ..Default::default()
+                }),
+                max_rows_per_file: 500,
+                use_legacy_format: false,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        let get_bytes = || io_stats.lock().unwrap().read_bytes;
+
+        let mut scan = dataset.scan();
+        scan.filter("i >= 1200").unwrap();
+        let start_bytes = get_bytes();
+        let mut stream = scan.try_into_stream().await.unwrap();
+        let mut num_rows = 0;
+        while let Some(batch) = stream.try_next().await.unwrap() {
+            num_rows += batch.num_rows();
+        }
+        assert_eq!(num_rows, 800);
+        let statistics = stream.statistics().unwrap();
+        // Everything the scan read (including the late materialization of `vec`) went
+        // through the scheduler
+        assert_eq!(statistics.bytes_read, get_bytes() - start_bytes);
+        assert!(statistics.iops > 0);
+        // `i` is read from all four fragments but `vec` is only taken from the two
+        // fragments with matching rows
+        assert_eq!(statistics.pages_scheduled, 6);
+        assert_eq!(statistics.bytes_decoded, 2000 * 4 + 800 * 16 * 4);
+        assert_eq!(statistics.index_searches, 0);
+
+        // The metrics are reported per node of the plan
+        let analyzed = scan.explain_analyze().await.unwrap();
+        for node in ["LanceScan", "Take"] {
+            let line = analyzed
+                .lines()
+                .find(|line| line.trim_start().starts_with(node))
+                .unwrap_or_else(|| panic!("{}", analyzed));
+            assert!(line.contains("bytes_read="), "{}", analyzed);
+            assert!(line.contains("pages_scheduled="), "{}", analyzed);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_statistics_skipped_pages_and_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let data = gen()
+            .col("indexed", array::step::<Int32Type>())
+            .col("not_indexed", array::step::<Int32Type>())
+            .into_reader_rows(RowCount::from(100), BatchCount::from(10));
+        let mut dataset = Dataset::write(
+            data,
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_group: 100,
+                use_legacy_format: true,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        dataset
+            .create_index(
+                &["indexed"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        // The statistics of all but one page show that they don't match
+        let mut scan = dataset.scan();
+        scan.filter("not_indexed >= 950").unwrap();
+        let mut stream = scan.try_into_stream().await.unwrap();
+        while stream.try_next().await.unwrap().is_some() {}
+        let statistics = stream.statistics().unwrap();
+        assert_eq!(statistics.pages_skipped, 9);
+        assert_eq!(statistics.index_searches, 0);
+
+        let mut scan = dataset.scan();
+        scan.filter("indexed = 50").unwrap();
+        let mut stream = scan.try_into_stream().await.unwrap();
+        while stream.try_next().await.unwrap().is_some() {}
+        assert_eq!(stream.statistics().unwrap().index_searches, 1);
+    }
 }
@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::io::commit::commit_transaction;
 use crate::{io::exec::Planner, Error, Result};
@@ -11,6 +14,7 @@ use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
 use futures::stream::{StreamExt, TryStreamExt};
 use lance_arrow::SchemaExt;
 use lance_core::datatypes::{Field, Schema};
+use lance_index::DatasetIndexExt;
 use lance_table::format::Fragment;
 use snafu::{location, Location};
 
@@ -65,6 +69,10 @@ pub struct ColumnAlteration {
     pub nullable: Option<bool>,
     /// The new data type of the column. If None, the data type will not be changed.
     pub data_type: Option<DataType>,
+    // If true, a new data type only changes the schema.  Existing data is cast when it
+    // is read and is rewritten with the new type when the fragments are compacted.  Set
+    // with `defer_rewrite()`.
+    defer_rewrite: bool,
 }
 
 impl ColumnAlteration {
@@ -74,6 +82,7 @@ impl ColumnAlteration {
             rename: None,
             nullable: None,
             data_type: None,
+            defer_rewrite: false,
         }
     }
 
@@ -91,12 +100,79 @@ impl ColumnAlteration {
         self.data_type = Some(data_type);
         self
     }
+
+    /// Cast existing data when it is read instead of rewriting it when the column is altered
+    ///
+    /// This is only supported for top-level columns without children (e.g. not structs or
+    /// lists), without an index, in datasets that do not use the legacy file format.
+    pub fn defer_rewrite(mut self) -> Self {
+        self.defer_rewrite = true;
+        self
+    }
+}
+
+/// The number of rows read to check that a deferred cast will succeed
+const DEFERRED_CAST_SAMPLE_SIZE: i64 = 10_000;
+
+/// Checks that the column can be cast without rewriting it, by casting a sample of the data
+async fn validate_deferred_cast(
+    dataset: &Dataset,
+    field: &Field,
+    path: &str,
+    data_type: &DataType,
+) -> Result<()> {
+    let reject = |reason: &str| {
+        Err(Error::invalid_input(
+            format!("Cannot defer the cast of column \"{}\": {}", path, reason),
+            location!(),
+        ))
+    };
+    if field.parent_id >= 0 || !field.children.is_empty() {
+        return reject("only top-level columns without children can be cast lazily");
+    }
+    if dataset
+        .fragments()
+        .iter()
+        .flat_map(|fragment| &fragment.files)
+        .any(|file| file.is_legacy_file())
+    {
+        return reject("the dataset uses the legacy file format");
+    }
+    if dataset
+        .load_indices()
+        .await?
+        .iter()
+        .any(|index| index.fields.contains(&field.id))
+    {
+        return reject("the column has an index, drop the index or cast without deferring");
+    }
+
+    let mut scanner = dataset.scan();
+    scanner
+        .project(&[path])?
+        .limit(Some(DEFERRED_CAST_SAMPLE_SIZE), None)?;
+    let sample = scanner.try_into_batch().await?;
+    if let Err(err) = lance_arrow::cast::cast_with_options(
+        sample.column(0),
+        data_type,
+        &CastOptions {
+            safe: false,
+            ..Default::default()
+        },
+    ) {
+        return reject(&format!("casting a sample of the data failed: {}", err));
+    }
+    Ok(())
 }
 
 /// Limit casts to same type. This is mostly to filter out weird casts like
 /// casting a string to a boolean or float to string.
 fn is_upcast_downcast(from_type: &DataType, to_type: &DataType) -> bool {
     use DataType::*;
+    if let Dictionary(_, value_type) = to_type {
+        // Dictionary encoding the values doesn't change them
+        return is_upcast_downcast(from_type, value_type);
+    }
     match from_type {
         from_type if from_type.is_integer() => to_type.is_integer(),
         from_type if from_type.is_floating() => to_type.is_floating(),
@@ -364,6 +440,22 @@ pub(super) async fn alter_columns(
                     location!(),
                 ));
             }
+            // Legacy files store a single dictionary per column, in the schema
+            if matches!(data_type, DataType::Dictionary(_, _))
+                && dataset
+                    .fragments()
+                    .iter()
+                    .flat_map(|fragment| &fragment.files)
+                    .any(|file| file.is_legacy_file())
+            {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot cast column \"{}\" to a dictionary, the dataset uses the legacy file format",
+                        alteration.path
+                    ),
+                    location!(),
+                ));
+            }
 
             let arrow_field = ArrowField::new(
                 field_dest.name.clone(),
@@ -371,9 +463,16 @@ pub(super) async fn alter_columns(
                 field_dest.nullable,
             );
             *field_dest = Field::try_from(&arrow_field)?;
-            field_dest.set_id(field_src.parent_id, &mut next_field_id);
-
-            cast_fields.push((field_src.clone(), field_dest.clone()));
+            if alteration.defer_rewrite {
+                validate_deferred_cast(dataset, field_src, &alteration.path, data_type).await?;
+                // The data files are not changed and so the field keeps its id, readers
+                // cast any data that was written with the old type
+                field_dest.id = field_src.id;
+                field_dest.parent_id = field_src.parent_id;
+            } else {
+                field_dest.set_id(field_src.parent_id, &mut next_field_id);
+                cast_fields.push((field_src.clone(), field_dest.clone()));
+            }
         }
     }
 
@@ -477,6 +576,35 @@ pub(super) async fn alter_columns(
     Ok(())
 }
 
+/// Rename columns in the dataset, each given as a pair of the current path and the new
+/// name (or the new path, which must have the same parent).
+///
+/// This is a metadata-only operation, field ids are kept and so data files and indices
+/// remain valid.
+pub(super) async fn rename_columns(dataset: &mut Dataset, renames: &[(&str, &str)]) -> Result<()> {
+    let alterations = renames
+        .iter()
+        .map(|(path, new_path)| {
+            let (parent, _) = path.rsplit_once('.').unwrap_or(("", path));
+            let new_name = match new_path.rsplit_once('.') {
+                None => *new_path,
+                Some((new_parent, new_name)) if new_parent == parent => new_name,
+                Some(_) => {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Cannot rename column \"{}\" to \"{}\": renaming cannot move a column to a different parent",
+                            path, new_path
+                        ),
+                        location!(),
+                    ))
+                }
+            };
+            Ok(ColumnAlteration::new(path.to_string()).rename(new_name.to_string()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    alter_columns(dataset, &alterations).await
+}
+
 /// Remove columns from the dataset.
 ///
 /// This is a metadata-only operation and does not remove the data from the
@@ -620,6 +748,99 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_append_columns_with_deletions(
+        #[values(false, true)] use_legacy_format: bool,
+    ) -> Result<()> {
+        use arrow_array::{
+            cast::AsArray,
+            types::{Int32Type, Int64Type},
+            Int64Array, StructArray,
+        };
+
+        let num_rows = 100;
+        let struct_fields = ArrowFields::from(vec![ArrowField::new("x", DataType::Int32, false)]);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("s", DataType::Struct(struct_fields.clone()), false),
+        ]));
+        let ids = Int32Array::from_iter_values(0..num_rows);
+        let xs = Int32Array::from_iter_values((0..num_rows).map(|i| i * 10));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(ids),
+                Arc::new(StructArray::new(struct_fields, vec![Arc::new(xs)], None)),
+            ],
+        )?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            reader,
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 40,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        // Deletions at the start, middle and end of fragments
+        dataset.delete("id % 7 = 0 OR id >= 95").await?;
+
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![
+                    ("id_plus_x".into(), "id + s.x".into()),
+                    ("odd_id".into(), "nullif(id % 2, 0) * id".into()),
+                ]),
+                None,
+            )
+            .await?;
+        dataset.validate().await?;
+
+        let data = dataset.scan().try_into_batch().await?;
+        let expected_schema = ArrowSchema::new(vec![
+            schema.field(0).clone(),
+            schema.field(1).clone(),
+            // Nested field access is always reported as nullable
+            ArrowField::new("id_plus_x", DataType::Int32, true),
+            ArrowField::new("odd_id", DataType::Int64, true),
+        ]);
+        assert_eq!(data.schema().as_ref(), &expected_schema);
+
+        // The new columns line up with the rows that were not deleted
+        let expected_ids = (0..num_rows)
+            .filter(|i| i % 7 != 0 && *i < 95)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            data["id"].as_primitive::<Int32Type>().values(),
+            expected_ids.as_slice()
+        );
+        assert_eq!(
+            data["id_plus_x"].as_primitive::<Int32Type>(),
+            &Int32Array::from_iter_values(expected_ids.iter().map(|i| i * 11))
+        );
+        // The legacy format does not store nulls for primitive columns
+        let odd_ids = expected_ids
+            .iter()
+            .map(|i| (i % 2 == 1).then_some(*i as i64));
+        let expected_odd_ids = if use_legacy_format {
+            odd_ids.map(|i| Some(i.unwrap_or_default())).collect()
+        } else {
+            odd_ids.collect::<Int64Array>()
+        };
+        assert_eq!(
+            data["odd_id"].as_primitive::<Int64Type>(),
+            &expected_odd_ids
+        );
+
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_append_columns_udf(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
@@ -797,6 +1018,9 @@ mod test {
                         deletion_file: None,
                         row_id_meta: None,
                         physical_rows: Some(50),
+                        sort_order: vec![],
+                        row_id_ranges: vec![],
+                        custom_metadata: HashMap::new(),
                     }))
                 } else {
                     Ok(None)
@@ -1007,6 +1231,178 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_rename_columns_metadata_only(
+        #[values(false, true)] use_legacy_format: bool,
+    ) -> Result<()> {
+        use arrow_array::{
+            cast::AsArray,
+            types::{Int32Type, Int64Type},
+            ArrayRef, Int64Array, ListArray, StringArray, StructArray,
+        };
+        use arrow_buffer::OffsetBuffer;
+        use lance_index::IndexType;
+
+        use crate::index::scalar::ScalarIndexParams;
+
+        let meta_fields = ArrowFields::from(vec![
+            ArrowField::new("ts", DataType::Int64, true),
+            ArrowField::new("source", DataType::Utf8, true),
+        ]);
+        let item_field = Arc::new(ArrowField::new(
+            "item",
+            DataType::Struct(vec![ArrowField::new("x", DataType::Int32, true)].into()),
+            true,
+        ));
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("meta", DataType::Struct(meta_fields.clone()), true),
+            ArrowField::new("l", DataType::List(item_field.clone()), true),
+        ]));
+        let items = StructArray::from(vec![(
+            Arc::new(ArrowField::new("x", DataType::Int32, true)),
+            Arc::new(Int32Array::from_iter_values(0..200)) as ArrayRef,
+        )]);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StructArray::new(
+                    meta_fields,
+                    vec![
+                        Arc::new(Int64Array::from_iter_values(0..100)),
+                        Arc::new(StringArray::from_iter_values(
+                            (0..100).map(|i| format!("s{}", i)),
+                        )),
+                    ],
+                    None,
+                )),
+                Arc::new(ListArray::new(
+                    item_field,
+                    OffsetBuffer::from_lengths(std::iter::repeat(2).take(100)),
+                    Arc::new(items),
+                    None,
+                )),
+            ],
+        )?;
+
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 50,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        dataset
+            .create_index(
+                &["id"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await?;
+        let original_fragments = dataset.fragments().to_vec();
+        let original_indices = dataset.load_indices().await?;
+        let original_field_ids = dataset.schema().field_ids();
+        let version = dataset.version().version;
+
+        dataset
+            .rename_columns(&[
+                ("id", "key"),
+                ("meta.ts", "meta.timestamp"),
+                ("l.item.x", "z"),
+            ])
+            .await?;
+        dataset.validate().await?;
+
+        // Only the schema changed
+        assert_eq!(dataset.version().version, version + 1);
+        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+        assert_eq!(dataset.schema().field_ids(), original_field_ids);
+        let index_ids = |indices: &[lance_table::format::Index]| {
+            indices
+                .iter()
+                .map(|index| (index.uuid, index.fields.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            index_ids(&dataset.load_indices().await?),
+            index_ids(&original_indices)
+        );
+        assert_eq!(
+            dataset.schema().field("meta.timestamp").unwrap().id,
+            original_field_ids[2]
+        );
+        assert!(dataset.schema().field("l.item.z").is_some());
+        assert!(dataset.schema().field("id").is_none());
+
+        // The data is read with the new names
+        let data = dataset.scan().try_into_batch().await?;
+        assert_eq!(data.schema().field(0).name(), "key", "{:?}", data.schema());
+        assert_eq!(data["key"].as_ref(), batch["id"].as_ref());
+        let meta = data["meta"].as_struct();
+        assert_eq!(
+            meta["timestamp"].as_primitive::<Int64Type>().values(),
+            batch["meta"].as_struct()["ts"]
+                .as_primitive::<Int64Type>()
+                .values()
+        );
+        let items = data["l"].as_list::<i32>().values().as_struct();
+        assert_eq!(
+            items["z"].as_primitive::<Int32Type>().values(),
+            &(0..200).collect::<Vec<_>>()
+        );
+
+        // Filters resolve the new names, and the index is still used
+        let mut scan = dataset.scan();
+        scan.filter("key >= 90")?.project(&["key"])?;
+        let plan = scan.explain_plan(true).await?;
+        assert!(plan.contains("MaterializeIndex"), "{}", plan);
+        assert_eq!(scan.try_into_batch().await?.num_rows(), 10);
+        let mut scan = dataset.scan();
+        scan.filter("meta.timestamp < 5")?;
+        assert_eq!(scan.try_into_batch().await?.num_rows(), 5);
+
+        // Renames can't collide with existing columns, at any level
+        for (path, new_name) in [("key", "meta"), ("meta.timestamp", "source")] {
+            let err = dataset
+                .rename_columns(&[(path, new_name)])
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("Duplicate field name"), "{}", err);
+        }
+        // Renames can't move a column
+        let err = dataset
+            .rename_columns(&[("meta.source", "key.source")])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("different parent"), "{}", err);
+        let err = dataset
+            .rename_columns(&[("missing", "other")])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{}", err);
+        assert_eq!(dataset.version().version, version + 1);
+
+        // A rename conflicts with a concurrent schema change
+        let mut other = dataset.checkout_version(version + 1).await?;
+        dataset.rename_columns(&[("key", "id")]).await?;
+        let err = other
+            .rename_columns(&[("meta.source", "origin")])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { .. }), "{}", err);
+
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_cast_column(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
@@ -1210,6 +1606,298 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_deferred_cast() -> Result<()> {
+        use arrow_array::{cast::AsArray, types::Int64Type, Int64Array, LargeStringArray};
+        use lance_encoding::decoder::DecoderMiddlewareChain;
+        use lance_io::scheduler::ScanScheduler;
+
+        use crate::dataset::optimize::{compact_files, CompactionOptions};
+        use crate::dataset::WriteMode;
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..300)),
+                Arc::new(arrow_array::StringArray::from_iter_values(
+                    (0..300).map(|i| i.to_string()),
+                )),
+            ],
+        )?;
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 100,
+                use_legacy_format: false,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        let original_fragments = dataset.fragments().to_vec();
+
+        // Casts that fail on the existing data are rejected before anything is changed
+        let res = dataset
+            .alter_columns(&[ColumnAlteration::new("i".into())
+                .cast_to(DataType::UInt8)
+                .defer_rewrite()])
+            .await;
+        assert!(
+            matches!(&res, Err(Error::InvalidInput { source, .. }) if source.to_string().contains("sample")),
+            "{:?}",
+            res
+        );
+
+        dataset
+            .alter_columns(&[
+                ColumnAlteration::new("i".into())
+                    .cast_to(DataType::Int64)
+                    .defer_rewrite(),
+                ColumnAlteration::new("s".into())
+                    .cast_to(DataType::LargeUtf8)
+                    .defer_rewrite(),
+            ])
+            .await?;
+        dataset.validate().await?;
+        let new_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int64, false),
+            ArrowField::new("s", DataType::LargeUtf8, true),
+        ]));
+        assert_eq!(&ArrowSchema::from(dataset.schema()), new_schema.as_ref());
+        // No data was rewritten
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+
+        // New data is written with the new types, next to the fragments with the old types
+        let batch = RecordBatch::try_new(
+            new_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(300..400)),
+                Arc::new(LargeStringArray::from_iter_values(
+                    (300..400).map(|i| i.to_string()),
+                )),
+            ],
+        )?;
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], new_schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                use_legacy_format: false,
+                ..Default::default()
+            }),
+        )
+        .await?;
+
+        // The new data is written in the same file format
+        assert!(dataset
+            .fragments()
+            .iter()
+            .flat_map(|fragment| &fragment.files)
+            .all(|file| !file.is_legacy_file()));
+        let expected = RecordBatch::try_new(
+            new_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..400)),
+                Arc::new(LargeStringArray::from_iter_values(
+                    (0..400).map(|i| i.to_string()),
+                )),
+            ],
+        )?;
+        assert_eq!(dataset.scan().try_into_batch().await?, expected);
+        let taken = dataset
+            .take(&[5, 150, 399], &dataset.schema().project(&["i"])?)
+            .await?;
+        assert_eq!(
+            taken["i"].as_primitive::<Int64Type>().values(),
+            &[5, 150, 399]
+        );
+
+        // Compaction rewrites the old fragments with the new types
+        compact_files(&mut dataset, CompactionOptions::default(), None).await?;
+        dataset.validate().await?;
+        assert_eq!(dataset.scan().try_into_batch().await?, expected);
+        let scheduler = ScanScheduler::new(dataset.object_store.clone());
+        for fragment in dataset.fragments().iter() {
+            for file in &fragment.files {
+                let path = dataset.data_dir().child(file.path.as_str());
+                let reader = lance_file::v2::reader::FileReader::try_open(
+                    scheduler.open_file(&path).await?,
+                    None,
+                    DecoderMiddlewareChain::default(),
+                )
+                .await?;
+                assert_eq!(
+                    &ArrowSchema::from(reader.schema().as_ref()),
+                    new_schema.as_ref()
+                );
+            }
+        }
+
+        // Deferred casts need to know the type of the data in each file
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )?;
+        let test_dir = tempfile::tempdir()?;
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_dir.path().to_str().unwrap(),
+            Some(WriteParams {
+                use_legacy_format: true,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        let res = dataset
+            .alter_columns(&[ColumnAlteration::new("i".into())
+                .cast_to(DataType::Int64)
+                .defer_rewrite()])
+            .await;
+        assert!(matches!(res, Err(Error::InvalidInput { .. })), "{:?}", res);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deferred_cast_to_dictionary() -> Result<()> {
+        use arrow_array::{types::Int32Type, DictionaryArray};
+        use lance_encoding::decoder::DecoderMiddlewareChain;
+        use lance_io::scheduler::ScanScheduler;
+
+        use crate::dataset::optimize::{compact_files, CompactionOptions};
+        use crate::dataset::WriteMode;
+
+        let label = |i: i32| format!("label_{}", i % 7);
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Utf8,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(arrow_array::StringArray::from_iter_values(
+                (0..300).map(label),
+            ))],
+        )?;
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 100,
+                use_legacy_format: false,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        let original_fragments = dataset.fragments().to_vec();
+
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        dataset
+            .alter_columns(&[ColumnAlteration::new("s".into())
+                .cast_to(dict_type.clone())
+                .defer_rewrite()])
+            .await?;
+        let new_schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            dict_type.clone(),
+            true,
+        )]));
+        assert_eq!(&ArrowSchema::from(dataset.schema()), new_schema.as_ref());
+        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+
+        // Old and new data are read as dictionaries
+        let batch = RecordBatch::try_new(
+            new_schema.clone(),
+            vec![Arc::new(
+                (300..400)
+                    .map(label)
+                    .map(Some)
+                    .collect::<DictionaryArray<Int32Type>>(),
+            )],
+        )?;
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], new_schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                use_legacy_format: false,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        let expected = RecordBatch::try_new(
+            new_schema.clone(),
+            vec![Arc::new(
+                (0..400)
+                    .map(label)
+                    .map(Some)
+                    .collect::<DictionaryArray<Int32Type>>(),
+            )],
+        )?;
+        let scanned = dataset.scan().try_into_batch().await?;
+        assert_eq!(scanned["s"].data_type(), &dict_type);
+        assert_eq!(scanned, expected);
+
+        // Compaction rewrites the old fragments, the files store the dictionary type
+        compact_files(&mut dataset, CompactionOptions::default(), None).await?;
+        dataset.validate().await?;
+        assert_eq!(dataset.scan().try_into_batch().await?, expected);
+        let scheduler = ScanScheduler::new(dataset.object_store.clone());
+        for fragment in dataset.fragments().iter() {
+            for file in &fragment.files {
+                let path = dataset.data_dir().child(file.path.as_str());
+                let reader = lance_file::v2::reader::FileReader::try_open(
+                    scheduler.open_file(&path).await?,
+                    None,
+                    DecoderMiddlewareChain::default(),
+                )
+                .await?;
+                assert_eq!(
+                    &ArrowSchema::from(reader.schema().as_ref()),
+                    new_schema.as_ref()
+                );
+            }
+        }
+
+        // Legacy files keep their dictionary in the schema and so can't be cast to one
+        let test_dir = tempfile::tempdir()?;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(arrow_array::StringArray::from_iter_values(
+                (0..10).map(label),
+            ))],
+        )?;
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_dir.path().to_str().unwrap(),
+            Some(WriteParams {
+                use_legacy_format: true,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        let res = dataset
+            .alter_columns(&[ColumnAlteration::new("s".into()).cast_to(dict_type)])
+            .await;
+        assert!(matches!(res, Err(Error::InvalidInput { .. })), "{:?}", res);
+
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_drop_columns(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
@@ -1407,4 +2095,107 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_drop_columns_reclaim_space(
+        #[values(false, true)] use_legacy_format: bool,
+    ) -> Result<()> {
+        use arrow_array::{ArrayRef, StructArray};
+        use lance_index::{DatasetIndexExt, IndexType};
+
+        use crate::dataset::optimize::{compact_files, CompactionOptions};
+        use crate::index::scalar::ScalarIndexParams;
+
+        let struct_fields = ArrowFields::from(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Int32, false),
+        ]);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Struct(struct_fields.clone()), false),
+            ArrowField::new("x", DataType::Int32, false),
+        ]));
+        let column = |offset: i32| Arc::new(Int32Array::from_iter_values(offset..offset + 100));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                column(0),
+                Arc::new(StructArray::new(
+                    struct_fields,
+                    vec![column(1000) as ArrayRef, column(2000)],
+                    None,
+                )),
+                column(3000),
+            ],
+        )?;
+
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 25,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        dataset
+            .create_index(
+                &["x"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await?;
+        assert_eq!(dataset.load_indices().await?.len(), 1);
+
+        let dropped_ids = ["s.a", "x"]
+            .iter()
+            .map(|name| dataset.schema().field(name).unwrap().id)
+            .collect::<Vec<_>>();
+        dataset.drop_columns(&["s.a", "x"]).await?;
+        dataset.validate().await?;
+
+        // Indices on dropped columns are dropped too
+        assert!(dataset.load_indices().await?.is_empty());
+        let data = dataset.scan().try_into_batch().await?;
+        assert_eq!(
+            data.schema().as_ref(),
+            &ArrowSchema::new(vec![
+                ArrowField::new("i", DataType::Int32, false),
+                ArrowField::new(
+                    "s",
+                    DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                        "b",
+                        DataType::Int32,
+                        false
+                    )])),
+                    false
+                ),
+            ])
+        );
+
+        // The dropped data remains in the data files until they are rewritten
+        let references_dropped = |dataset: &Dataset| {
+            dataset.fragments().iter().any(|fragment| {
+                fragment.files.iter().any(|file| {
+                    file.fields
+                        .iter()
+                        .any(|field_id| dropped_ids.contains(field_id))
+                })
+            })
+        };
+        assert!(references_dropped(&dataset));
+
+        compact_files(&mut dataset, CompactionOptions::default(), None).await?;
+        dataset.validate().await?;
+        assert!(!references_dropped(&dataset));
+        assert_eq!(dataset.scan().try_into_batch().await?, data);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Storage statistics of a dataset, see [`Dataset::stats`]
+//!
+//! The statistics are computed from the metadata of the data files (the page table of
+//! legacy files and the column metadata of newer files) and from the listing of the index
+//! files.  No data pages are read.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use lance_core::datatypes::Field;
+use lance_encoding::decoder::DecoderMiddlewareChain;
+use lance_file::reader::FileReader;
+use lance_file::v2;
+use lance_index::DatasetIndexExt;
+use lance_io::scheduler::ScanScheduler;
+use uuid::Uuid;
+
+use super::fragment::FileFragment;
+use super::Dataset;
+use crate::Result;
+
+/// Data files smaller than this (in bytes) are counted as small files
+///
+/// Many small files make scans slower, compaction rewrites them into larger files.
+pub const SMALL_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Storage statistics of a dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetStatistics {
+    /// The number of rows, not including deleted rows
+    pub num_rows: u64,
+    /// The number of deleted rows that are still stored in the data files
+    pub num_deleted_rows: u64,
+    pub num_fragments: u64,
+    pub num_data_files: u64,
+    /// The number of data files smaller than [`SMALL_FILE_SIZE`]
+    pub num_small_files: u64,
+    /// The size of all data files, including the file metadata
+    pub data_files_bytes: u64,
+    /// The top-level fields of the schema
+    pub fields: Vec<FieldStatistics>,
+    pub indices: Vec<IndexStatistics>,
+}
+
+impl DatasetStatistics {
+    /// The statistics of the field at `path`, nested fields are separated by `.`
+    pub fn field(&self, path: &str) -> Option<&FieldStatistics> {
+        let mut parts = path.split('.');
+        let name = parts.next()?;
+        let mut field = self.fields.iter().find(|f| f.name == name)?;
+        for part in parts {
+            field = field.children.iter().find(|f| f.name == part)?;
+        }
+        Some(field)
+    }
+}
+
+/// Storage statistics of a field
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldStatistics {
+    pub id: i32,
+    pub name: String,
+    /// The number of rows stored for the field, including deleted rows
+    pub num_rows: u64,
+    /// The size of the data pages of the field and of all of its children
+    ///
+    /// This does not include the file metadata.  In legacy files the size of the pages is
+    /// inferred from the data type and the dictionary values are not counted.
+    pub bytes_on_disk: u64,
+    pub children: Vec<FieldStatistics>,
+}
+
+/// Storage statistics of an index
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStatistics {
+    pub name: String,
+    pub uuid: Uuid,
+    /// The ids of the indexed fields
+    pub fields: Vec<i32>,
+    /// The size of all files of the index
+    pub bytes_on_disk: u64,
+}
+
+// The statistics of the data files of one fragment
+#[derive(Debug, Default)]
+struct FragmentStatistics {
+    physical_rows: u64,
+    deleted_rows: u64,
+    // The fields that are stored in the fragment
+    field_ids: HashSet<i32>,
+    // The bytes of the pages of each field, not including its children
+    field_bytes: BTreeMap<i32, u64>,
+    file_sizes: Vec<u64>,
+}
+
+async fn fragment_statistics(
+    fragment: FileFragment,
+    scheduler: Arc<ScanScheduler>,
+) -> Result<FragmentStatistics> {
+    let dataset = fragment.dataset();
+    let mut stats = FragmentStatistics::default();
+    for data_file in &fragment.metadata().files {
+        let path = dataset
+            .data_file_dir(data_file)
+            .child(data_file.path.as_str());
+        stats
+            .file_sizes
+            .push(dataset.object_store.size(&path).await? as u64);
+        // The file may still contain fields that were dropped from the dataset
+        let file_schema = data_file.schema(dataset.schema());
+        if file_schema.fields.is_empty() {
+            continue;
+        }
+        let (num_rows, field_bytes) = if data_file.is_legacy_file() {
+            let reader = FileReader::try_new_with_fragment_id(
+                &dataset.object_store,
+                &path,
+                file_schema.clone(),
+                fragment.id() as u32,
+                data_file.fields.first().copied().unwrap_or_default(),
+                data_file.fields.iter().copied().max().unwrap_or_default(),
+                Some(&dataset.session.file_metadata_cache),
+            )
+            .await?;
+            (reader.len() as u64, reader.field_data_sizes())
+        } else {
+            let file_scheduler = scheduler.open_file(&path).await?;
+            let reader = v2::reader::FileReader::try_open(
+                file_scheduler,
+                None,
+                DecoderMiddlewareChain::default(),
+            )
+            .await?;
+            let metadata = reader.metadata();
+            let field_bytes = data_file
+                .fields
+                .iter()
+                .zip(data_file.column_indices.iter())
+                .filter(|(field_id, column_index)| {
+                    **column_index >= 0 && file_schema.field_by_id(**field_id).is_some()
+                })
+                .map(|(field_id, column_index)| {
+                    let column = &metadata.column_infos[*column_index as usize];
+                    let page_bytes = column
+                        .page_infos
+                        .iter()
+                        .flat_map(|page| page.buffer_offsets_and_sizes.iter());
+                    let bytes = page_bytes
+                        .chain(column.buffer_offsets_and_sizes.iter())
+                        .map(|(_, size)| size)
+                        .sum::<u64>();
+                    (*field_id, bytes)
+                })
+                .collect();
+            (metadata.num_rows, field_bytes)
+        };
+        stats.physical_rows = num_rows;
+        stats
+            .field_ids
+            .extend(file_schema.fields_pre_order().map(|field| field.id));
+        for (field_id, bytes) in field_bytes {
+            *stats.field_bytes.entry(field_id).or_default() += bytes;
+        }
+    }
+    stats.deleted_rows = fragment.count_deletions().await? as u64;
+    Ok(stats)
+}
+
+fn field_statistics(
+    field: &Field,
+    field_rows: &HashMap<i32, u64>,
+    field_bytes: &HashMap<i32, u64>,
+) -> FieldStatistics {
+    let children = field
+        .children
+        .iter()
+        .map(|child| field_statistics(child, field_rows, field_bytes))
+        .collect::<Vec<_>>();
+    FieldStatistics {
+        id: field.id,
+        name: field.name.clone(),
+        num_rows: field_rows.get(&field.id).copied().unwrap_or_default(),
+        bytes_on_disk: field_bytes.get(&field.id).copied().unwrap_or_default()
+            + children
+                .iter()
+                .map(|child| child.bytes_on_disk)
+                .sum::<u64>(),
+        children,
+    }
+}
+
+async fn index_statistics(dataset: &Dataset) -> Result<Vec<IndexStatistics>> {
+    let indices = dataset.load_indices().await?;
+    futures::stream::iter(indices.iter())
+        .map(|index| async move {
+            let index_dir = dataset.indices_dir().child(index.uuid.to_string());
+            let bytes_on_disk = dataset
+                .object_store
+                .read_dir_all(&index_dir, None)
+                .await?
+                .try_fold(0, |bytes, meta| async move { Ok(bytes + meta.size as u64) })
+                .await?;
+            Ok(IndexStatistics {
+                name: index.name.clone(),
+                uuid: index.uuid,
+                fields: index.fields.clone(),
+                bytes_on_disk,
+            })
+        })
+        .buffered(dataset.object_store.io_parallelism()? as usize)
+        .try_collect()
+        .await
+}
+
+/// Computes the storage statistics of `dataset`, see [`Dataset::stats`]
+pub async fn dataset_statistics(dataset: &Dataset) -> Result<DatasetStatistics> {
+    let scheduler = ScanScheduler::new(dataset.object_store.clone());
+    let fragments = futures::stream::iter(dataset.get_fragments())
+        .map(|fragment| fragment_statistics(fragment, scheduler.clone()))
+        .buffer_unordered(dataset.object_store.io_parallelism()? as usize)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut field_rows = HashMap::new();
+    let mut field_bytes = HashMap::new();
+    for fragment in &fragments {
+        for field_id in &fragment.field_ids {
+            *field_rows.entry(*field_id).or_default() += fragment.physical_rows;
+        }
+        for (field_id, bytes) in &fragment.field_bytes {
+            *field_bytes.entry(*field_id).or_default() += *bytes;
+        }
+    }
+    let file_sizes = fragments
+        .iter()
+        .flat_map(|fragment| fragment.file_sizes.iter().copied())
+        .collect::<Vec<_>>();
+    let num_deleted_rows = fragments.iter().map(|f| f.deleted_rows).sum::<u64>();
+
+    Ok(DatasetStatistics {
+        num_rows: fragments.iter().map(|f| f.physical_rows).sum::<u64>() - num_deleted_rows,
+        num_deleted_rows,
+        num_fragments: fragments.len() as u64,
+        num_data_files: file_sizes.len() as u64,
+        num_small_files: file_sizes
+            .iter()
+            .filter(|size| **size < SMALL_FILE_SIZE)
+            .count() as u64,
+        data_files_bytes: file_sizes.iter().sum(),
+        fields: dataset
+            .schema()
+            .fields
+            .iter()
+            .map(|field| field_statistics(field, &field_rows, &field_bytes))
+            .collect(),
+        indices: index_statistics(dataset).await?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::types::{Float32Type, Int64Type};
+    use arrow_schema::{DataType, Field as ArrowField};
+    use lance_datagen::{array, gen, BatchCount, Dimension, RowCount};
+    use lance_index::IndexType;
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_dataset_statistics(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        // Random values so that nothing can be compressed
+        let data = gen()
+            .col("i", array::rand::<Int64Type>())
+            .col("vec", array::rand_vec::<Float32Type>(Dimension::from(16)))
+            .col(
+                "st",
+                array::rand_struct(
+                    vec![
+                        ArrowField::new("a", DataType::Int32, true),
+                        ArrowField::new("b", DataType::Utf8, true),
+                    ]
+                    .into(),
+                ),
+            )
+            .into_reader_rows(RowCount::from(250), BatchCount::from(4));
+        let mut dataset = Dataset::write(
+            data,
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 500,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        dataset.delete("i < 0").await.unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let stats = dataset.stats().await.unwrap();
+        let num_deleted = 1000 - dataset.count_rows(None).await.unwrap() as u64;
+        assert_eq!(stats.num_rows, 1000 - num_deleted);
+        assert_eq!(stats.num_deleted_rows, num_deleted);
+        assert_eq!(stats.num_fragments, 2);
+        assert_eq!(stats.num_data_files, 2);
+        assert_eq!(stats.num_small_files, 2);
+
+        let assert_bytes = |path: &str, min: u64, max: u64| {
+            let field = stats.field(path).unwrap();
+            assert_eq!(field.num_rows, 1000, "{}", path);
+            assert!(
+                (min..=max).contains(&field.bytes_on_disk),
+                "{} has {} bytes, expected {}..={}",
+                path,
+                field.bytes_on_disk,
+                min,
+                max
+            );
+        };
+        // Some bytes for validity and padding
+        assert_bytes("i", 8000, 8500);
+        assert_bytes("vec", 64000, 65000);
+        assert_bytes("st.a", 4000, 4500);
+        // 12 bytes per value and an offset of up to 8 bytes
+        assert_bytes("st.b", 12_000, 21_000);
+        let st = stats.field("st").unwrap();
+        assert!(st.bytes_on_disk >= st.children.iter().map(|c| c.bytes_on_disk).sum());
+        let fields_bytes = stats.fields.iter().map(|f| f.bytes_on_disk).sum::<u64>();
+        assert!(fields_bytes <= stats.data_files_bytes);
+
+        assert_eq!(stats.indices.len(), 1);
+        assert_eq!(stats.indices[0].fields, vec![stats.field("i").unwrap().id]);
+        assert!(stats.indices[0].bytes_on_disk > 8000);
+    }
+}
@@ -4,11 +4,11 @@
 use std::borrow::Cow;
 use std::{collections::BTreeMap, ops::Range, pin::Pin, sync::Arc};
 
-use crate::dataset::rowids::get_row_id_index;
+use crate::dataset::rowids::{get_row_addresses, get_row_id_index};
 use crate::{Error, Result};
 use arrow::{array::as_struct_array, compute::concat_batches, datatypes::UInt64Type};
 use arrow_array::cast::AsArray;
-use arrow_array::{Array, RecordBatch, StructArray, UInt64Array};
+use arrow_array::{new_null_array, Array, RecordBatch, StructArray, UInt64Array};
 use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
 use arrow_select::interleave::interleave;
 use datafusion::error::DataFusionError;
@@ -17,6 +17,8 @@ use futures::{Future, Stream, StreamExt, TryStreamExt};
 use lance_core::datatypes::Schema;
 use lance_core::utils::address::RowAddress;
 use lance_core::ROW_ADDR;
+use lance_encoding::stats::DecodeStats;
+use lance_io::scheduler::ScanScheduler;
 use snafu::{location, Location};
 
 use super::{fragment::FileFragment, scanner::DatasetRecordBatchStream, Dataset};
@@ -136,6 +138,18 @@ pub async fn take_rows(
     dataset: &Dataset,
     row_ids: &[u64],
     projection: &Schema,
+) -> Result<RecordBatch> {
+    take_rows_with_decode_stats(dataset, row_ids, projection, None, None).await
+}
+
+/// Take rows by the internal ROW ids, see [`take_rows`], reading v2 data files with
+/// `scan_scheduler` and reporting the decode work to `decode_stats`
+pub async fn take_rows_with_decode_stats(
+    dataset: &Dataset,
+    row_ids: &[u64],
+    projection: &Schema,
+    scan_scheduler: Option<Arc<ScanScheduler>>,
+    decode_stats: Option<Arc<DecodeStats>>,
 ) -> Result<RecordBatch> {
     if row_ids.is_empty() {
         return Ok(RecordBatch::new_empty(Arc::new(projection.into())));
@@ -151,6 +165,46 @@ pub async fn take_rows(
         Cow::Borrowed(row_ids)
     };
 
+    take_row_addrs(
+        dataset,
+        &row_addrs,
+        projection,
+        scan_scheduler,
+        decode_stats,
+    )
+    .await
+}
+
+/// Take rows by their stable row ids, see [`Dataset::take_by_row_ids`]
+pub async fn take_by_row_ids(
+    dataset: &Dataset,
+    row_ids: &[u64],
+    projection: &Schema,
+) -> Result<RecordBatch> {
+    if !dataset.manifest.uses_move_stable_row_ids() {
+        return take_rows(dataset, row_ids, projection).await;
+    }
+    let row_addrs = get_row_addresses(dataset, row_ids)
+        .await?
+        .into_iter()
+        .flatten()
+        .map(u64::from)
+        .collect::<Vec<_>>();
+    take_row_addrs(dataset, &row_addrs, projection, None, None).await
+}
+
+/// Take rows by their row addresses, skipping the addresses of deleted rows
+async fn take_row_addrs(
+    dataset: &Dataset,
+    row_addrs: &[u64],
+    projection: &Schema,
+    scan_scheduler: Option<Arc<ScanScheduler>>,
+    decode_stats: Option<Arc<DecodeStats>>,
+) -> Result<RecordBatch> {
+    if row_addrs.is_empty() {
+        return Ok(RecordBatch::new_empty(Arc::new(projection.into())));
+    }
+
     let projection = Arc::new(projection.clone());
     let row_addr_stats = check_row_addrs(&row_addrs);
 
@@ -163,10 +217,18 @@ pub async fn take_rows(
         row_offsets: Vec<u32>,
         projection: Arc<Schema>,
         with_row_addresses: bool,
+        scan_scheduler: Option<Arc<ScanScheduler>>,
+        decode_stats: Option<Arc<DecodeStats>>,
     ) -> impl Future<Output = Result<RecordBatch>> + Send {
         async move {
             fragment
-                .take_rows(&row_offsets, projection.as_ref(), with_row_addresses)
+                .take_rows_with_decode_stats(
+                    &row_offsets,
+                    projection.as_ref(),
+                    with_row_addresses,
+                    scan_scheduler,
+                    decode_stats,
+                )
                 .await
         }
     }
@@ -187,7 +249,13 @@ pub async fn take_rows(
         })?;
 
         let reader = fragment
-            .open(projection.as_ref(), false, false, None)
+            .open_with_decode_stats(
+                projection.as_ref(),
+                false,
+                false,
+                scan_scheduler,
+                decode_stats,
+            )
             .await?;
         reader.legacy_read_range_as_batch(range).await
     } else if row_addr_stats.sorted {
@@ -227,7 +295,14 @@ pub async fn take_rows(
             })?;
             let row_offsets: Vec<u32> = row_addrs[range].iter().map(|x| *x as u32).collect();
 
-            let batch_fut = do_take(fragment, row_offsets, projection.clone(), false);
+            let batch_fut = do_take(
+                fragment,
+                row_offsets,
+                projection.clone(),
+                false,
+                scan_scheduler.clone(),
+                decode_stats.clone(),
+            );
             batches.push(batch_fut);
         }
         let batches: Vec<RecordBatch> = futures::stream::iter(batches)
@@ -268,7 +343,16 @@ pub async fn take_rows(
         });
 
         let mut batches = futures::stream::iter(fragment_and_indices)
-            .map(|(fragment, indices)| do_take(fragment, indices, projection.clone(), true))
+            .map(|(fragment, indices)| {
+                do_take(
+                    fragment,
+                    indices,
+                    projection.clone(),
+                    true,
+                    scan_scheduler.clone(),
+                    decode_stats.clone(),
+                )
+            })
             .buffered(4 * num_cpus::get())
             .try_collect::<Vec<_>>()
             .await?;
@@ -313,6 +397,159 @@ pub async fn take_rows(
     }
 }
 
+/// How [`take_rows_by_address`] treats addresses of deleted rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeletedRowPolicy {
+    /// Fail the take with an error
+    #[default]
+    Error,
+    /// Return a row where every column is null.  This includes the rows of fragments
+    /// that were removed from the dataset (e.g. because all of their rows were deleted)
+    Null,
+}
+
+/// Take rows by their row addresses (fragment id + offset in the fragment).
+///
+/// The output has one row per address, in the order of `row_addrs`, including
+/// duplicates.  Each fragment is read once, with the distinct offsets it is asked
+/// for, and the rows are then rearranged into the requested order.
+pub async fn take_rows_by_address(
+    dataset: &Dataset,
+    row_addrs: &[u64],
+    projection: &Schema,
+    deleted_rows: DeletedRowPolicy,
+) -> Result<RecordBatch> {
+    let schema = Arc::new(ArrowSchema::from(projection));
+    if row_addrs.is_empty() {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+
+    // Group the distinct offsets by fragment, each group is sorted
+    let mut offsets_per_fragment: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for row_addr in row_addrs {
+        let row_addr = RowAddress::new_from_id(*row_addr);
+        offsets_per_fragment
+            .entry(row_addr.fragment_id())
+            .or_default()
+            .push(row_addr.row_id());
+    }
+
+    let mut requests = Vec::with_capacity(offsets_per_fragment.len());
+    for (fragment_id, mut offsets) in offsets_per_fragment {
+        offsets.sort_unstable();
+        offsets.dedup();
+        match dataset.get_fragment(fragment_id as usize) {
+            Some(fragment) => requests.push((fragment, offsets)),
+            // The rows of a removed fragment are deleted rows
+            None if deleted_rows == DeletedRowPolicy::Null => {}
+            None => {
+                return Err(Error::invalid_input(
+                    format!(
+                        "_rowaddr {} belongs to non-existent fragment: {}",
+                        RowAddress::new_from_parts(fragment_id, offsets[0]),
+                        fragment_id
+                    ),
+                    location!(),
+                ))
+            }
+        }
+    }
+
+    let projection = Arc::new(projection.clone());
+    let batches = futures::stream::iter(requests)
+        .map(|(fragment, offsets)| {
+            let projection = projection.clone();
+            async move {
+                // Deleted rows are silently skipped by the take, so they are removed
+                // from the request (or rejected) up front
+                let live_offsets = match fragment.get_deletion_vector().await? {
+                    Some(deletion_vector) => {
+                        let mut live_offsets = Vec::with_capacity(offsets.len());
+                        for offset in &offsets {
+                            if !deletion_vector.contains(*offset) {
+                                live_offsets.push(*offset);
+                            } else if deleted_rows == DeletedRowPolicy::Error {
+                                return Err(Error::invalid_input(
+                                    format!(
+                                        "_rowaddr {} refers to a deleted row",
+                                        RowAddress::new_from_parts(fragment.id() as u32, *offset)
+                                    ),
+                                    location!(),
+                                ));
+                            }
+                        }
+                        live_offsets
+                    }
+                    None => offsets,
+                };
+                let batch = if live_offsets.is_empty() {
+                    RecordBatch::new_empty(Arc::new(projection.as_ref().into()))
+                } else {
+                    fragment
+                        .take_rows(&live_offsets, projection.as_ref(), false)
+                        .await?
+                };
+                Ok::<_, Error>((fragment.id() as u32, live_offsets, batch))
+            }
+        })
+        .buffered(4 * num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // Position of every requested address in the batches read above.  Addresses
+    // of deleted rows (and of removed fragments) point at an extra all-null row
+    // after the last batch.
+    let null_source = batches.len();
+    let positions = row_addrs
+        .iter()
+        .map(|row_addr| {
+            let row_addr = RowAddress::new_from_id(*row_addr);
+            batches
+                .binary_search_by_key(&row_addr.fragment_id(), |(id, _, _)| *id)
+                .ok()
+                .and_then(|batch_idx| {
+                    let row_idx = batches[batch_idx]
+                        .1
+                        .binary_search(&row_addr.row_id())
+                        .ok()?;
+                    Some((batch_idx, row_idx))
+                })
+                .unwrap_or((null_source, 0))
+        })
+        .collect::<Vec<_>>();
+    let has_null_rows = positions
+        .iter()
+        .any(|(batch_idx, _)| *batch_idx == null_source);
+
+    let schema = if has_null_rows {
+        Arc::new(ArrowSchema::new_with_metadata(
+            schema
+                .fields()
+                .iter()
+                .map(|field| field.as_ref().clone().with_nullable(true))
+                .collect::<Vec<_>>(),
+            schema.metadata().clone(),
+        ))
+    } else {
+        schema
+    };
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(column_idx, field)| {
+            let null_row = new_null_array(field.data_type(), 1);
+            let sources = batches
+                .iter()
+                .map(|(_, _, batch)| batch.column(column_idx).as_ref())
+                .chain(std::iter::once(null_row.as_ref()))
+                .collect::<Vec<_>>();
+            interleave(&sources, &positions)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
 /// Get a stream of batches based on iterator of ranges of row numbers.
 ///
 /// This is an experimental API. It may change at any time.
@@ -376,7 +613,7 @@ fn check_row_addrs(row_ids: &[u64]) -> RowAddressStats {
 #[cfg(test)]
 mod test {
     use arrow_array::{Int32Array, RecordBatchIterator, StringArray};
-    use arrow_schema::DataType;
+    use arrow_schema::{DataType, SchemaRef};
     use pretty_assertions::assert_eq;
     use rstest::rstest;
 
@@ -564,6 +801,107 @@ mod test {
         assert_eq!(RecordBatch::new_empty(data.schema()), values);
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_take_rows_by_address(#[values(false, true)] use_legacy_format: bool) {
+        let data = test_batch(0..400);
+        let write_params = WriteParams {
+            max_rows_per_file: 40,
+            max_rows_per_group: 10,
+            use_legacy_format,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new([Ok(data.clone())], data.schema());
+        let mut dataset = Dataset::write(batches, "memory://", Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("i in (13, 251)").await.unwrap();
+        let projection = Schema::try_from(data.schema().as_ref()).unwrap();
+        let addr = |i: u64| ((i / 40) << 32) | (i % 40);
+        let expected_batch = |schema: SchemaRef, values: &[Option<i32>]| {
+            RecordBatch::try_new(
+                schema,
+                vec![
+                    Arc::new(Int32Array::from(values.to_vec())),
+                    Arc::new(StringArray::from(
+                        values
+                            .iter()
+                            .map(|v| v.map(|v| format!("str-{v}")))
+                            .collect::<Vec<_>>(),
+                    )),
+                ],
+            )
+            .unwrap()
+        };
+
+        // Unordered, spanning many fragments and with duplicates
+        let requested = [399, 5, 120, 5, 399, 0, 39, 40, 260, 120, 77];
+        let addrs = requested.iter().map(|i| addr(*i)).collect::<Vec<_>>();
+        let values = dataset
+            .take_rows_by_address(&addrs, &projection, DeletedRowPolicy::Error)
+            .await
+            .unwrap();
+        let expected = requested
+            .iter()
+            .map(|i| Some(*i as i32))
+            .collect::<Vec<_>>();
+        assert_eq!(expected_batch(data.schema(), &expected), values);
+
+        // Deleted rows are an error unless they are requested as nulls
+        let addrs = [addr(12), addr(13), addr(300), addr(13), addr(251)];
+        let err = dataset
+            .take_rows_by_address(&addrs, &projection, DeletedRowPolicy::Error)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("deleted row"), "{}", err);
+        let values = dataset
+            .take_rows_by_address(&addrs, &projection, DeletedRowPolicy::Null)
+            .await
+            .unwrap();
+        // The null rows make every column nullable
+        assert!(values.schema().fields().iter().all(|f| f.is_nullable()));
+        assert_eq!(
+            expected_batch(values.schema(), &[Some(12), None, Some(300), None, None]),
+            values
+        );
+
+        // Addresses in fragments that don't exist are an error unless they are
+        // requested as nulls, like the rows of a fragment that was removed
+        let err = dataset
+            .take_rows_by_address(&[addr(0), 1000 << 32], &projection, DeletedRowPolicy::Error)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("non-existent fragment"), "{}", err);
+        dataset.delete("i < 40").await.unwrap();
+        assert!(dataset.get_fragment(0).is_none());
+        let addrs = [addr(5), addr(41), 1000 << 32, addr(0)];
+        let err = dataset
+            .take_rows_by_address(&addrs, &projection, DeletedRowPolicy::Error)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("non-existent fragment"), "{}", err);
+        let values = dataset
+            .take_rows_by_address(&addrs, &projection, DeletedRowPolicy::Null)
+            .await
+            .unwrap();
+        assert_eq!(
+            expected_batch(values.schema(), &[None, Some(41), None, None]),
+            values
+        );
+        // Even when none of the requested fragments exist
+        let values = dataset
+            .take_rows_by_address(&[addr(5)], &projection, DeletedRowPolicy::Null)
+            .await
+            .unwrap();
+        assert_eq!(expected_batch(values.schema(), &[None]), values);
+
+        let values = dataset
+            .take_rows_by_address(&[], &projection, DeletedRowPolicy::Error)
+            .await
+            .unwrap();
+        assert_eq!(RecordBatch::new_empty(data.schema()), values);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn take_scan_dataset(#[values(false, true)] use_legacy_format: bool) {
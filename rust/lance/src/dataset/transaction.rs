@@ -34,8 +34,13 @@
 //!
 //! (1) Delete and rewrite are compatible with each other and themselves only if
 //! they affect distinct fragments. Otherwise, they conflict.
+//!
+//! When a commit finds concurrent transactions that are all compatible it rebuilds
+//! its manifest on top of the latest version and tries again.  Otherwise it fails
+//! with a [`TransactionConflict`] (as the source of the commit conflict error) that
+//! describes why the transactions conflict.
 
-use std::{collections::HashSet, sync::Arc};
+use std::{collections::HashSet, fmt, sync::Arc};
 
 use lance_core::{datatypes::Schema, Error, Result};
 use lance_file::datatypes::Fields;
@@ -43,13 +48,13 @@ use lance_io::object_store::ObjectStore;
 use lance_table::{
     format::{
         pb::{self, IndexMetadata},
-        Fragment, Index, Manifest, RowIdMeta,
+        Fragment, Index, Manifest,
     },
     io::{
         commit::CommitHandler,
         manifest::{read_manifest, read_manifest_indexes},
     },
-    rowids::{write_row_ids, RowIdSequence},
+    rowids::RowIdSequence,
 };
 use object_store::path::Path;
 use roaring::RoaringBitmap;
@@ -218,6 +223,57 @@ impl Operation {
     }
 }
 
+/// Why a transaction cannot be committed on top of a concurrent transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// The concurrent transaction replaced the table (an overwrite or a restore)
+    Replaced,
+    /// One of the transactions changed the schema
+    SchemaChange,
+    /// Both transactions modified or removed rows in the same fragments
+    OverlappingFragments,
+    /// An index was built on rows that the other transaction rewrote
+    IndexInvalidated,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::Replaced => "the table was replaced",
+            Self::SchemaChange => "the schema was changed",
+            Self::OverlappingFragments => "both modify the same fragments",
+            Self::IndexInvalidated => "the indexed rows were rewritten",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// A conflict with a concurrent transaction that could not be resolved automatically
+///
+/// This is the source of the [`Error::CommitConflict`] returned when a commit fails
+/// because of a conflict, it can be recovered with `downcast_ref`.
+#[derive(Debug, Clone)]
+pub struct TransactionConflict {
+    pub kind: ConflictKind,
+    /// The name of the operation that was being committed
+    pub operation: String,
+    /// The name of the operation of the concurrent transaction
+    pub other_operation: String,
+}
+
+impl fmt::Display for TransactionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} conflicts with a concurrent {} because {} and it cannot be automatically \
+            resolved. Please rerun the operation off the latest version of the table.",
+            self.operation, self.other_operation, self.kind
+        )
+    }
+}
+
+impl std::error::Error for TransactionConflict {}
+
 impl Transaction {
     pub fn new(read_version: u64, operation: Operation, tag: Option<String>) -> Self {
         let uuid = uuid::Uuid::new_v4().hyphenated().to_string();
@@ -232,6 +288,24 @@ impl Transaction {
     /// Returns true if the transaction cannot be committed if the other
     /// transaction is committed first.
     pub fn conflicts_with(&self, other: &Self) -> bool {
+        self.conflict_kind(other).is_some()
+    }
+
+    /// Returns why the transaction cannot be committed after `other`, or None if the
+    /// transactions are compatible
+    pub fn conflict_kind(&self, other: &Self) -> Option<ConflictKind> {
+        // Any conflict with an overwrite or restore is because the table was replaced
+        let conflict = |kind| {
+            Some(match &other.operation {
+                Operation::Overwrite { .. } | Operation::Restore { .. } => ConflictKind::Replaced,
+                _ => kind,
+            })
+        };
+        let overlapping = || {
+            self.operation
+                .modifies_same_ids(&other.operation)
+                .then_some(ConflictKind::OverlappingFragments)
+        };
         // This assumes IsolationLevel is Snapshot Isolation, which is more
         // permissive than Serializable. In particular, it allows a Delete
         // transaction to succeed after a concurrent Append, even if the Append
@@ -239,76 +313,79 @@ impl Transaction {
         match &self.operation {
             Operation::Append { .. } => match &other.operation {
                 // Append is compatible with anything that doesn't change the schema
-                Operation::Append { .. } => false,
-                Operation::Rewrite { .. } => false,
-                Operation::CreateIndex { .. } => false,
-                Operation::Delete { .. } | Operation::Update { .. } => false,
-                Operation::ReserveFragments { .. } => false,
-                Operation::Project { .. } => false,
-                _ => true,
+                Operation::Append { .. } => None,
+                Operation::Rewrite { .. } => None,
+                Operation::CreateIndex { .. } => None,
+                Operation::Delete { .. } | Operation::Update { .. } => None,
+                Operation::ReserveFragments { .. } => None,
+                Operation::Project { .. } => None,
+                _ => conflict(ConflictKind::SchemaChange),
             },
             Operation::Rewrite { .. } => match &other.operation {
                 // Rewrite is only compatible with operations that don't touch
                 // existing fragments.
                 // TODO: it could also be compatible with operations that update
                 // fragments we don't touch.
-                Operation::Append { .. } => false,
-                Operation::ReserveFragments { .. } => false,
+                Operation::Append { .. } => None,
+                Operation::ReserveFragments { .. } => None,
                 Operation::Delete { .. } | Operation::Rewrite { .. } | Operation::Update { .. } => {
                     // As long as they rewrite disjoint fragments they shouldn't conflict.
-                    self.operation.modifies_same_ids(&other.operation)
+                    overlapping()
                 }
-                Operation::Project { .. } => false,
-                _ => true,
+                Operation::Project { .. } => None,
+                Operation::CreateIndex { .. } => Some(ConflictKind::IndexInvalidated),
+                _ => conflict(ConflictKind::SchemaChange),
             },
             // Overwrite and Restore always succeed
-            Operation::Overwrite { .. } => false,
-            Operation::Restore { .. } => false,
+            Operation::Overwrite { .. } => None,
+            Operation::Restore { .. } => None,
             // ReserveFragments is compatible with anything that doesn't reset the
             // max fragment id.
-            Operation::ReserveFragments { .. } => matches!(
-                &other.operation,
-                Operation::Overwrite { .. } | Operation::Restore { .. }
-            ),
+            Operation::ReserveFragments { .. } => match &other.operation {
+                Operation::Overwrite { .. } | Operation::Restore { .. } => {
+                    Some(ConflictKind::Replaced)
+                }
+                _ => None,
+            },
             Operation::CreateIndex { .. } => match &other.operation {
-                Operation::Append { .. } => false,
+                Operation::Append { .. } => None,
                 // Indices are identified by UUIDs, so they shouldn't conflict.
-                Operation::CreateIndex { .. } => false,
+                Operation::CreateIndex { .. } => None,
                 // Although some of the rows we indexed may have been deleted / moved,
                 // row ids are still valid, so we allow this optimistically.
-                Operation::Delete { .. } | Operation::Update { .. } => false,
+                Operation::Delete { .. } | Operation::Update { .. } => None,
                 // Merge & reserve don't change row ids, so this should be fine.
-                Operation::Merge { .. } => false,
-                Operation::ReserveFragments { .. } => false,
+                Operation::Merge { .. } => None,
+                Operation::ReserveFragments { .. } => None,
                 // Rewrite likely changed many of the row ids, so our index is
                 // likely useless. It should be rebuilt.
                 // TODO: we could be smarter here and only invalidate the index
                 // if the rewrite changed more than X% of row ids.
-                Operation::Rewrite { .. } => true,
-                _ => true,
+                Operation::Rewrite { .. } => Some(ConflictKind::IndexInvalidated),
+                _ => conflict(ConflictKind::SchemaChange),
             },
             Operation::Delete { .. } | Operation::Update { .. } => match &other.operation {
-                Operation::CreateIndex { .. } => false,
-                Operation::ReserveFragments { .. } => false,
+                Operation::CreateIndex { .. } => None,
+                Operation::ReserveFragments { .. } => None,
                 Operation::Delete { .. } | Operation::Rewrite { .. } | Operation::Update { .. } => {
                     // If we update the same fragments, we conflict.
-                    self.operation.modifies_same_ids(&other.operation)
+                    overlapping()
                 }
-                Operation::Project { .. } => false,
-                Operation::Append { .. } => false,
-                _ => true,
+                Operation::Project { .. } => None,
+                Operation::Append { .. } => None,
+                _ => conflict(ConflictKind::SchemaChange),
             },
             // Merge changes the schema, but preserves row ids, so the only operations
             // it's compatible with is CreateIndex and ReserveFragments.
-            Operation::Merge { .. } => !matches!(
-                &other.operation,
-                Operation::CreateIndex { .. } | Operation::ReserveFragments { .. }
-            ),
+            Operation::Merge { .. } => match &other.operation {
+                Operation::CreateIndex { .. } | Operation::ReserveFragments { .. } => None,
+                _ => conflict(ConflictKind::SchemaChange),
+            },
             Operation::Project { .. } => match &other.operation {
                 // Project is compatible with anything that doesn't change the schema
-                Operation::CreateIndex { .. } => false,
-                Operation::Overwrite { .. } => false,
-                _ => true,
+                Operation::CreateIndex { .. } => None,
+                Operation::Overwrite { .. } => None,
+                _ => conflict(ConflictKind::SchemaChange),
             },
         }
     }
@@ -432,6 +509,7 @@ impl Transaction {
                 let mut new_fragments =
                     Self::fragments_with_ids(fragments.clone(), &mut fragment_id)
                         .collect::<Vec<_>>();
+                self.discard_stale_sort_order(current_manifest, &mut new_fragments);
                 if let Some(next_row_id) = &mut next_row_id {
                     Self::assign_row_ids(next_row_id, new_fragments.as_mut_slice())?;
                 }
@@ -471,6 +549,7 @@ impl Transaction {
                 let mut new_fragments =
                     Self::fragments_with_ids(new_fragments.clone(), &mut fragment_id)
                         .collect::<Vec<_>>();
+                self.discard_stale_sort_order(current_manifest, &mut new_fragments);
                 if let Some(next_row_id) = &mut next_row_id {
                     Self::assign_row_ids(next_row_id, new_fragments.as_mut_slice())?;
                 }
@@ -520,6 +599,7 @@ impl Transaction {
             }
             Operation::Merge { ref fragments, .. } => {
                 final_fragments.extend(fragments.clone());
+                Self::discard_altered_sort_order(current_manifest, &schema, &mut final_fragments);
 
                 // Some fields that have indices may have been removed, so we should
                 // remove those indices as well.
@@ -527,6 +607,7 @@ impl Transaction {
             }
             Operation::Project { .. } => {
                 final_fragments.extend(maybe_existing_fragments?.clone());
+                Self::discard_altered_sort_order(current_manifest, &schema, &mut final_fragments);
 
                 // We might have removed all fields for certain data files, so
                 // we should remove the data files that are no longer relevant.
@@ -704,12 +785,53 @@ impl Transaction {
                 for fragment in group.old_fragments.iter() {
                     final_fragments.retain(|f| f.id != fragment.id);
                 }
-                final_fragments.extend(new_fragments);
+                // The new fragments are moved to the end and so they are no longer in order
+                final_fragments.extend(new_fragments.map(|mut fragment| {
+                    fragment.sort_order.clear();
+                    fragment
+                }));
             }
         }
         Ok(())
     }
 
+    /// The sort order of new fragments is only verified against the rows that were in
+    /// the dataset when they were written.  If other transactions have been committed
+    /// since then the new fragments may no longer be in order.
+    fn discard_stale_sort_order(
+        &self,
+        current_manifest: Option<&Manifest>,
+        new_fragments: &mut [Fragment],
+    ) {
+        if current_manifest.is_some_and(|manifest| manifest.version != self.read_version) {
+            for fragment in new_fragments {
+                fragment.sort_order.clear();
+            }
+        }
+    }
+
+    /// The sort order of a fragment refers to fields by id.  If one of these fields is
+    /// dropped (its id may then be given to a new field) or its type changes (e.g. a cast
+    /// that keeps the field id) then the rows are no longer known to be sorted.
+    fn discard_altered_sort_order(
+        current_manifest: Option<&Manifest>,
+        schema: &Schema,
+        fragments: &mut [Fragment],
+    ) {
+        let unchanged = |id: &i32| {
+            let previous = current_manifest.and_then(|m| m.schema.field_by_id(*id));
+            match (previous, schema.field_by_id(*id)) {
+                (Some(previous), Some(field)) => previous.data_type() == field.data_type(),
+                _ => false,
+            }
+        };
+        for fragment in fragments {
+            if !fragment.sort_order.iter().all(unchanged) {
+                fragment.sort_order.clear();
+            }
+        }
+    }
+
     fn assign_row_ids(next_row_id: &mut u64, fragments: &mut [Fragment]) -> Result<()> {
         for fragment in fragments {
             let physical_rows = fragment.physical_rows.ok_or_else(|| Error::Internal {
@@ -719,8 +841,7 @@ impl Transaction {
             let row_ids = *next_row_id..(*next_row_id + physical_rows);
             let sequence = RowIdSequence::from(row_ids);
             // TODO: write to a separate file if large. Possibly share a file with other fragments.
-            let serialized = write_row_ids(&sequence);
-            fragment.row_id_meta = Some(RowIdMeta::Inline(serialized));
+            fragment.set_row_id_sequence(&sequence);
             *next_row_id += physical_rows;
         }
         Ok(())
@@ -1268,6 +1389,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_conflict_kind() {
+        let transaction = |operation| Transaction::new(0, operation, None);
+        let append = transaction(Operation::Append {
+            fragments: vec![Fragment::new(0)],
+        });
+        let delete = |fragment_id| {
+            transaction(Operation::Delete {
+                updated_fragments: vec![Fragment::new(fragment_id)],
+                deleted_fragment_ids: vec![],
+                predicate: "x > 2".to_string(),
+            })
+        };
+        let overwrite = transaction(Operation::Overwrite {
+            fragments: vec![],
+            schema: Schema::default(),
+        });
+        let merge = transaction(Operation::Merge {
+            fragments: vec![],
+            schema: Schema::default(),
+        });
+        let rewrite = transaction(Operation::Rewrite {
+            groups: vec![RewriteGroup {
+                old_fragments: vec![Fragment::new(0)],
+                new_fragments: vec![Fragment::new(1)],
+            }],
+            rewritten_indices: vec![],
+        });
+        let create_index = transaction(Operation::CreateIndex {
+            new_indices: vec![],
+            removed_indices: vec![],
+        });
+
+        assert_eq!(append.conflict_kind(&append), None);
+        assert_eq!(append.conflict_kind(&delete(0)), None);
+        assert_eq!(delete(0).conflict_kind(&delete(1)), None);
+        assert_eq!(
+            delete(0).conflict_kind(&delete(0)),
+            Some(ConflictKind::OverlappingFragments)
+        );
+        assert_eq!(
+            append.conflict_kind(&overwrite),
+            Some(ConflictKind::Replaced)
+        );
+        assert_eq!(
+            merge.conflict_kind(&overwrite),
+            Some(ConflictKind::Replaced)
+        );
+        assert_eq!(
+            append.conflict_kind(&merge),
+            Some(ConflictKind::SchemaChange)
+        );
+        assert_eq!(
+            delete(0).conflict_kind(&merge),
+            Some(ConflictKind::SchemaChange)
+        );
+        assert_eq!(
+            create_index.conflict_kind(&rewrite),
+            Some(ConflictKind::IndexInvalidated)
+        );
+        assert_eq!(
+            rewrite.conflict_kind(&create_index),
+            Some(ConflictKind::IndexInvalidated)
+        );
+    }
+
     #[test]
     fn test_rewrite_fragments() {
         let existing_fragments: Vec<Fragment> = (0..10).map(Fragment::new).collect();
@@ -192,8 +192,8 @@ impl Updater {
     /// Finish updating this fragment, and returns the updated [`Fragment`].
     pub async fn finish(&mut self) -> Result<Fragment> {
         if let Some(writer) = self.writer.as_mut() {
-            let (_, data_file) = writer.finish().await?;
-            self.fragment.metadata.files.push(data_file);
+            let (_, data_files) = writer.finish().await?;
+            self.fragment.metadata.files.extend(data_files);
         }
 
         Ok(self.fragment.metadata().clone())
@@ -1,21 +1,36 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use arrow_array::{RecordBatch, RecordBatchReader};
-use datafusion::physical_plan::SendableRecordBatchStream;
+use arrow::compute::cast;
+use arrow_array::{
+    cast::AsArray,
+    make_array, new_empty_array, new_null_array,
+    types::{Int16Type, Int32Type, Int64Type, RunEndIndexType},
+    Array, ArrayRef, RecordBatch, RecordBatchIterator, RecordBatchReader, RunArray, UInt64Array,
+};
+use arrow_buffer::ArrowNativeType;
+use arrow_row::{OwnedRow, RowConverter, SortField};
+use arrow_schema::{ArrowError, DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use arrow_select::take::take;
+use datafusion::physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream};
 use futures::{StreamExt, TryStreamExt};
-use lance_core::{datatypes::Schema, Error, Result};
+use lance_arrow::RecordBatchExt;
+use lance_core::{
+    datatypes::{Schema, SchemaCompareOptions},
+    Error, Result,
+};
 use lance_datafusion::chunker::{break_stream, chunk_stream};
 use lance_datafusion::utils::{peek_reader_schema, reader_to_stream};
 use lance_file::format::{MAJOR_VERSION, MINOR_VERSION_NEXT};
 use lance_file::v2;
-use lance_file::v2::writer::FileWriterOptions;
+use lance_file::v2::writer::{FileWriterOptions, DEFAULT_DATA_CACHE_BYTES_PER_COLUMN};
 use lance_file::writer::{FileWriter, ManifestProvider};
 use lance_io::object_store::{ObjectStore, ObjectStoreParams};
 use lance_table::format::{DataFile, Fragment};
-use lance_table::io::commit::CommitHandler;
+use lance_table::io::commit::{CommitConfig, CommitHandler};
 use lance_table::io::manifest::ManifestDescribing;
 use object_store::path::Path;
 use snafu::{location, Location};
@@ -25,12 +40,19 @@ use uuid::Uuid;
 use crate::Dataset;
 
 use super::builder::DatasetBuilder;
-use super::progress::{NoopFragmentWriteProgress, WriteFragmentProgress};
+use super::progress::{
+    NoopFragmentWriteProgress, ProgressMonitor, ProgressStage, WriteFragmentProgress,
+};
 use super::DATA_DIR;
 
 pub mod merge_insert;
 pub mod update;
 
+/// Field metadata key that marks a top-level column as a blob column
+///
+/// See [`WriteParams::blob_columns`].
+pub const BLOB_COLUMN_META_KEY: &str = "lance:blob";
+
 /// The mode to write dataset.
 #[derive(Debug, Clone, Copy)]
 pub enum WriteMode {
@@ -73,8 +95,12 @@ pub struct WriteParams {
     /// by a few megabytes, since once we detect we hit this limit, we still
     /// need to flush the footer.
     ///
-    /// This limit is checked after writing each group, so if max_rows_per_group
-    /// is set to a large value, this limit may be exceeded by a large amount.
+    /// With the legacy format this limit is checked after writing each group, so if
+    /// max_rows_per_group is set to a large value, this limit may be exceeded by a
+    /// large amount.  With the v2 format the size is tracked from the pages the encoder
+    /// has written and batches are split so that a new file can start part way
+    /// through a batch.  The writer also buffers less data per file when this limit is
+    /// small.  In either case the last file may be arbitrarily small.
     ///
     /// The default is 90 GB. If you are using an object store such as S3, we
     /// currently have a hard 100 GB limit.
@@ -87,6 +113,12 @@ pub struct WriteParams {
 
     pub progress: Arc<dyn WriteFragmentProgress>,
 
+    /// Reports the fragments written and can cancel the write
+    ///
+    /// The cancellation token is checked before each batch is written.  A cancelled write
+    /// fails with [`Error::Stop`], does not commit and removes the data files it wrote.
+    pub progress_monitor: ProgressMonitor,
+
     /// If present, dataset will use this to update the latest version
     ///
     /// If not set, the default will be based on the object store.  Generally this will
@@ -107,6 +139,46 @@ pub struct WriteParams {
     /// This makes compaction more efficient, since with stable row ids no
     /// secondary indices need to be updated to point to new row ids.
     pub enable_move_stable_row_ids: bool,
+
+    /// The columns the data is sorted by (ascending, nulls first), if any
+    ///
+    /// The written fragments record this sort order so that scans ordered by these
+    /// columns do not need to sort.  The order is verified as the data is written.  When
+    /// appending, the new rows must also sort at or after the rows already in the dataset
+    /// with the same sort order.  Fragments written after the order is violated do not
+    /// record a sort order.
+    pub sort_order: Option<Vec<String>>,
+
+    /// Custom key/values recorded in the metadata of every fragment written
+    ///
+    /// Lance does not interpret these.  They can be used to record the provenance of the
+    /// data (e.g. the source file or ingestion job) and are available from
+    /// [`crate::dataset::fragment::FileFragment::custom_metadata`].
+    pub fragment_metadata: HashMap<String, String>,
+
+    /// Top-level columns that are stored in their own data files
+    ///
+    /// This is meant for columns with very large values (e.g. images or documents).
+    /// Each fragment written stores these columns in one data file and the remaining
+    /// columns in another.  Scans that do not project a blob column never read the blob
+    /// files and take() of a blob column only reads the blob file.
+    ///
+    /// The designation is recorded in the schema (see [`BLOB_COLUMN_META_KEY`]) when a
+    /// dataset is created or overwritten and every later write (appends, updates,
+    /// merge_insert and compaction) keeps storing these columns separately.  It is
+    /// ignored when appending to an existing dataset.
+    pub blob_columns: Vec<String>,
+
+    /// The number of times to retry the commit if a concurrent writer commits first
+    ///
+    /// Concurrent transactions that don't conflict with this write (e.g. other appends)
+    /// are resolved by rebuilding the manifest on top of the latest version and trying
+    /// again.  Conflicting transactions fail the write immediately.
+    ///
+    /// This applies to writes and appends.  Updates and merge inserts are configured with
+    /// `commit_retries` on their builders.  Deletes, compaction and index operations always
+    /// use the default.
+    pub commit_retries: u32,
 }
 
 impl Default for WriteParams {
@@ -120,13 +192,43 @@ impl Default for WriteParams {
             mode: WriteMode::Create,
             store_params: None,
             progress: Arc::new(NoopFragmentWriteProgress::new()),
+            progress_monitor: ProgressMonitor::default(),
             commit_handler: None,
             use_legacy_format: true,
             enable_move_stable_row_ids: false,
+            sort_order: None,
+            fragment_metadata: HashMap::new(),
+            blob_columns: Vec::new(),
+            commit_retries: CommitConfig::default().num_retries,
         }
     }
 }
 
+/// Marks `blob_columns` as blob columns in the metadata of `schema`
+///
+/// The columns must be top-level columns of the schema.
+pub(crate) fn mark_blob_columns(schema: &mut Schema, blob_columns: &[String]) -> Result<()> {
+    for column in blob_columns {
+        let field = schema
+            .fields
+            .iter_mut()
+            .find(|field| &field.name == column)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "The blob column {} is not a top-level column of the schema",
+                        column
+                    ),
+                    location!(),
+                )
+            })?;
+        field
+            .metadata
+            .insert(BLOB_COLUMN_META_KEY.to_string(), "true".to_string());
+    }
+    Ok(())
+}
+
 /// Writes the given data to the dataset and returns fragments.
 ///
 /// NOTE: the fragments have not yet been assigned an ID. That must be done
@@ -167,7 +269,15 @@ pub async fn write_fragments(
         (None, object_store, base)
     };
 
-    let (data, schema) = peek_reader_schema(Box::new(data)).await?;
+    let target_schema = dataset
+        .as_ref()
+        .filter(|_| matches!(params.mode, WriteMode::Append))
+        .map(|dataset| dataset.schema());
+    let data = decode_encoded_columns(Box::new(data), target_schema, params.use_legacy_format)?;
+    let (data, mut schema) = peek_reader_schema(data).await?;
+    if dataset.is_none() {
+        mark_blob_columns(&mut schema, &params.blob_columns)?;
+    }
     let stream = reader_to_stream(data);
     write_fragments_internal(
         dataset.as_ref(),
@@ -195,7 +305,7 @@ pub async fn write_fragments_internal(
     object_store: Arc<ObjectStore>,
     base_dir: &Path,
     schema: &Schema,
-    data: SendableRecordBatchStream,
+    mut data: SendableRecordBatchStream,
     mut params: WriteParams,
 ) -> Result<Vec<Fragment>> {
     // Make sure the max rows per group is not larger than the max rows per file
@@ -204,7 +314,10 @@ pub async fn write_fragments_internal(
     let schema = if let Some(dataset) = dataset {
         if matches!(params.mode, WriteMode::Append) {
             // Append mode, so we need to check compatibility
-            schema.check_compatible(dataset.schema(), &Default::default())?;
+            schema.check_compatible(dataset.schema(), &append_compare_options())?;
+            if !schema.compare_with_options(dataset.schema(), &Default::default()) {
+                data = fill_missing_columns(data, dataset.schema());
+            }
             // Use the schema from the dataset, because it has the correct
             // field ids.
             dataset.schema()
@@ -219,56 +332,580 @@ pub async fn write_fragments_internal(
         chunk_stream(data, params.max_rows_per_group)
     } else {
         // In v2 we don't care about group size but we do want to break
-        // the stream on file boundaries
+        // the stream on file boundaries (batches are split further as they are written,
+        // see `rows_to_write`)
         break_stream(data, params.max_rows_per_file)
             .map_ok(|batch| vec![batch])
             .boxed()
     };
 
+    let mut sort_order_tracker = if let Some(sort_order) = &params.sort_order {
+        let dataset = dataset.filter(|_| matches!(params.mode, WriteMode::Append));
+        Some(SortOrderTracker::try_new(sort_order.clone(), schema, dataset).await?)
+    } else {
+        None
+    };
+
     let writer_generator =
-        WriterGenerator::new(object_store, base_dir, schema, params.use_legacy_format);
+        WriterGenerator::try_new(object_store.clone(), base_dir, schema, &params)?;
     let mut writer: Option<Box<dyn GenericWriter>> = None;
-    let mut num_rows_in_current_file = 0;
     let mut fragments = Vec::new();
-    while let Some(batch_chunk) = buffered_reader.next().await {
-        let batch_chunk = batch_chunk?;
-
-        if writer.is_none() {
-            let (new_writer, new_fragment) = writer_generator.new_writer().await?;
-            params.progress.begin(&new_fragment).await?;
-            writer = Some(new_writer);
-            fragments.push(new_fragment);
-        }
-
-        writer.as_mut().unwrap().write(&batch_chunk).await?;
-        for batch in batch_chunk {
-            num_rows_in_current_file += batch.num_rows() as u32;
+    let result = async {
+        let mut num_rows_in_current_file = 0;
+        while let Some(batch_chunk) = buffered_reader.next().await {
+            params.progress_monitor.check_cancelled()?;
+            let mut batch_chunk = batch_chunk?;
+
+            while !batch_chunk.is_empty() {
+                if writer.is_none() {
+                    let (new_writer, mut new_fragment) = writer_generator.new_writer().await?;
+                    new_fragment.custom_metadata = params.fragment_metadata.clone();
+                    params.progress.begin(&new_fragment).await?;
+                    writer = Some(new_writer);
+                    fragments.push(new_fragment);
+                }
+
+                // v2 chunks are a single batch, which may be split across files
+                let mut remainder = Vec::new();
+                if !params.use_legacy_format {
+                    let batch = &batch_chunk[0];
+                    let num_rows = rows_to_write(
+                        batch,
+                        params.max_rows_per_file - num_rows_in_current_file as usize,
+                        (params.max_bytes_per_file as u64)
+                            .saturating_sub(writer.as_mut().unwrap().tell().await?),
+                    )?;
+                    if num_rows < batch.num_rows() {
+                        remainder.push(batch.slice(num_rows, batch.num_rows() - num_rows));
+                        batch_chunk[0] = batch.slice(0, num_rows);
+                    }
+                }
+
+                writer.as_mut().unwrap().write(&batch_chunk).await?;
+                for batch in batch_chunk {
+                    num_rows_in_current_file += batch.num_rows() as u32;
+                    if let Some(tracker) = sort_order_tracker.as_mut() {
+                        tracker.update(&batch)?;
+                    }
+                }
+                batch_chunk = remainder;
+
+                if num_rows_in_current_file >= params.max_rows_per_file as u32
+                    || writer.as_mut().unwrap().tell().await? >= params.max_bytes_per_file as u64
+                {
+                    let (num_rows, data_files) = writer.as_mut().unwrap().finish().await?;
+                    writer = None;
+                    debug_assert_eq!(num_rows, num_rows_in_current_file);
+                    params.progress.complete(fragments.last().unwrap()).await?;
+                    let last_fragment = fragments.last_mut().unwrap();
+                    last_fragment.physical_rows = Some(num_rows as usize);
+                    last_fragment.files.extend(data_files);
+                    if let Some(tracker) = &sort_order_tracker {
+                        last_fragment.sort_order = tracker.sort_order();
+                    }
+                    num_rows_in_current_file = 0;
+                    params.progress_monitor.report(
+                        ProgressStage::WriteFragments,
+                        fragments.len() as u64,
+                        None,
+                    );
+                }
+            }
         }
 
-        if num_rows_in_current_file >= params.max_rows_per_file as u32
-            || writer.as_mut().unwrap().tell().await? >= params.max_bytes_per_file as u64
-        {
-            let (num_rows, data_file) = writer.take().unwrap().finish().await?;
-            debug_assert_eq!(num_rows, num_rows_in_current_file);
-            params.progress.complete(fragments.last().unwrap()).await?;
+        // Complete the final writer
+        if let Some(final_writer) = writer.as_mut() {
+            let (num_rows, data_files) = final_writer.finish().await?;
+            writer = None;
             let last_fragment = fragments.last_mut().unwrap();
             last_fragment.physical_rows = Some(num_rows as usize);
-            last_fragment.files.push(data_file);
-            num_rows_in_current_file = 0;
+            last_fragment.files.extend(data_files);
+            if let Some(tracker) = &sort_order_tracker {
+                last_fragment.sort_order = tracker.sort_order();
+            }
+            params.progress_monitor.report(
+                ProgressStage::WriteFragments,
+                fragments.len() as u64,
+                None,
+            );
         }
+        params.progress_monitor.check_cancelled()
     }
-
-    // Complete the final writer
-    if let Some(mut writer) = writer.take() {
-        let (num_rows, data_file) = writer.finish().await?;
-        let last_fragment = fragments.last_mut().unwrap();
-        last_fragment.physical_rows = Some(num_rows as usize);
-        last_fragment.files.push(data_file);
+    .await;
+
+    if let Err(err) = result {
+        // The file that is being written is never completed, so its upload is stopped
+        // and only the completed files have to be removed
+        if let Some(mut writer) = writer {
+            if let Err(abort_err) = writer.abort().await {
+                log::warn!("Failed to abort the data file being written: {}", abort_err);
+            }
+        }
+        remove_data_files(&object_store, base_dir, &fragments).await;
+        return Err(err);
     }
 
     Ok(fragments)
 }
 
+/// Removes the data files of fragments that were written but will not be committed
+///
+/// This is best effort, files that cannot be removed are left for cleanup to remove.
+pub(crate) async fn remove_data_files(
+    object_store: &ObjectStore,
+    base_dir: &Path,
+    fragments: &[Fragment],
+) {
+    let data_dir = base_dir.child(DATA_DIR);
+    let paths = fragments
+        .iter()
+        .flat_map(|fragment| &fragment.files)
+        .filter(|file| file.base.is_none())
+        .map(|file| data_dir.child(file.path.as_str()))
+        .collect::<Vec<_>>();
+    futures::stream::iter(paths)
+        .for_each_concurrent(
+            object_store.io_parallelism().unwrap_or(1) as usize,
+            |path| async move {
+                if let Err(err) = object_store.delete(&path).await {
+                    log::warn!("Failed to remove data file {}: {}", path, err);
+                }
+            },
+        )
+        .await;
+}
+
+/// The options used to check the schema of data appended to a dataset
+///
+/// Appended data may leave out nullable columns, which are written as nulls, and may
+/// have its columns in any order.
+pub fn append_compare_options() -> SchemaCompareOptions {
+    SchemaCompareOptions {
+        allow_missing_if_nullable: true,
+        ignore_field_order: true,
+        ..Default::default()
+    }
+}
+
+// Reorders the columns of appended batches to match `schema`, filling in the columns
+// they leave out with nulls
+fn fill_missing_columns(
+    data: SendableRecordBatchStream,
+    schema: &Schema,
+) -> SendableRecordBatchStream {
+    let data_schema = data.schema();
+    // Present columns keep their own field so their type matches exactly
+    let fields = schema
+        .fields
+        .iter()
+        .map(|field| {
+            data_schema
+                .field_with_name(&field.name)
+                .map(|data_field| Arc::new(data_field.clone()))
+                .unwrap_or_else(|_| Arc::new(ArrowField::from(field)))
+        })
+        .collect::<Vec<_>>();
+    let filled_schema = Arc::new(ArrowSchema::new_with_metadata(
+        fields,
+        data_schema.metadata().clone(),
+    ));
+    let stream_schema = filled_schema.clone();
+    let stream = data.map(move |batch| {
+        let batch = batch?;
+        let columns = filled_schema
+            .fields()
+            .iter()
+            .map(|field| {
+                batch
+                    .column_by_name(field.name())
+                    .cloned()
+                    .unwrap_or_else(|| new_null_array(field.data_type(), batch.num_rows()))
+            })
+            .collect();
+        Ok(RecordBatch::try_new(filled_schema.clone(), columns)?)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(stream_schema, stream))
+}
+
+/// The approximate size of the slices that dictionary and run-end encoded columns are
+/// decoded in, see [`decode_encoded_columns`]
+const DECODED_SLICE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Replaces the dictionary and run-end encoded columns of the data with their value type
+///
+/// Run-end encoded columns are always decoded since they cannot be stored as they are.  A
+/// dictionary column is decoded if the column has the value type in `target` (the schema of
+/// the dataset being appended to).  When there is no target the column is decoded unless
+/// `use_legacy_format` is set, since the v2 format stores dictionaries as their values (and
+/// dictionary encodes strings with few distinct values by itself).  Only top-level columns
+/// are considered.
+///
+/// Each batch is decoded in slices of about 8MiB so that a large column is never
+/// materialized all at once.  Dictionaries may differ from batch to batch.  A legacy file
+/// stores a single dictionary per column (the dictionary of the first batch), so the keys of
+/// the dictionary columns that are kept are remapped onto the dictionary of the first batch,
+/// which fails if a later batch has values that are not in it.
+pub fn decode_encoded_columns(
+    data: Box<dyn RecordBatchReader + Send>,
+    target: Option<&Schema>,
+    use_legacy_format: bool,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let data_schema = data.schema();
+    let mut columns = Vec::new();
+    let mut dictionary_columns = Vec::new();
+    let fields = data_schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let value_type = match field.data_type() {
+                DataType::RunEndEncoded(_, values) => Some(values.data_type()),
+                DataType::Dictionary(_, value_type) => {
+                    let decode = match target {
+                        Some(target) => target.field(field.name()).is_some_and(|target_field| {
+                            !matches!(target_field.data_type(), DataType::Dictionary(_, _))
+                        }),
+                        None => !use_legacy_format,
+                    };
+                    decode.then_some(value_type.as_ref())
+                }
+                _ => None,
+            };
+            match value_type {
+                Some(value_type) => {
+                    columns.push(idx);
+                    Arc::new(field.as_ref().clone().with_data_type(value_type.clone()))
+                }
+                None => {
+                    if use_legacy_format && matches!(field.data_type(), DataType::Dictionary(_, _))
+                    {
+                        dictionary_columns.push(idx);
+                    }
+                    field.clone()
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    if columns.is_empty() && dictionary_columns.is_empty() {
+        return Ok(data);
+    }
+    let schema = Arc::new(ArrowSchema::new_with_metadata(
+        fields,
+        data_schema.metadata().clone(),
+    ));
+    let batches_schema = schema.clone();
+    let mut dictionaries = Vec::new();
+    let batches =
+        data.flat_map(
+            move |batch| -> Box<
+                dyn Iterator<Item = std::result::Result<RecordBatch, ArrowError>> + Send,
+            > {
+                let batch = batch.and_then(|batch| {
+                    unify_dictionaries(batch, &dictionary_columns, &mut dictionaries)
+                });
+                match batch {
+                    Ok(batch) if columns.is_empty() => Box::new(std::iter::once(Ok(batch))),
+                    Ok(batch) => Box::new(decode_in_slices(
+                        batch,
+                        columns.clone(),
+                        schema.clone(),
+                        DECODED_SLICE_BYTES,
+                    )),
+                    Err(err) => Box::new(std::iter::once(Err(err))),
+                }
+            },
+        );
+    Ok(Box::new(RecordBatchIterator::new(batches, batches_schema)))
+}
+
+// Remaps the keys of the dictionary `columns` of `batch` onto `dictionaries`, the
+// dictionaries of the first batch (which are recorded when `dictionaries` is empty)
+fn unify_dictionaries(
+    batch: RecordBatch,
+    columns: &[usize],
+    dictionaries: &mut Vec<ArrayRef>,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    if columns.is_empty() {
+        return Ok(batch);
+    }
+    if dictionaries.is_empty() {
+        dictionaries.extend(
+            columns
+                .iter()
+                .map(|idx| batch.column(*idx).as_any_dictionary().values().clone()),
+        );
+        return Ok(batch);
+    }
+    let mut arrays = batch.columns().to_vec();
+    for (idx, dictionary) in columns.iter().zip(dictionaries.iter()) {
+        if Arc::ptr_eq(arrays[*idx].as_any_dictionary().values(), dictionary) {
+            continue;
+        }
+        arrays[*idx] = remap_dictionary(&arrays[*idx], dictionary)?.ok_or_else(|| {
+            ArrowError::InvalidArgumentError(format!(
+                "Column '{}' has values that are not in the dictionary of the first batch, \
+                 but the legacy format holds a single dictionary per column",
+                batch.schema().field(*idx).name()
+            ))
+        })?;
+    }
+    RecordBatch::try_new(batch.schema(), arrays)
+}
+
+// Replaces the dictionary of `array` with `dictionary`, or returns None if `dictionary`
+// does not contain the values of `array`
+fn remap_dictionary(
+    array: &ArrayRef,
+    dictionary: &ArrayRef,
+) -> std::result::Result<Option<ArrayRef>, ArrowError> {
+    let DataType::Dictionary(key_type, _) = array.data_type() else {
+        unreachable!("only dictionary columns are remapped")
+    };
+    let array_dictionary = array.as_any_dictionary();
+    let keys = if array_dictionary.values().as_ref() == dictionary.as_ref() {
+        array_dictionary.keys().to_data()
+    } else {
+        let converter = RowConverter::new(vec![SortField::new(dictionary.data_type().clone())])?;
+        let known_values = converter.convert_columns(&[dictionary.clone()])?;
+        let mut positions = HashMap::with_capacity(known_values.num_rows());
+        for (pos, row) in known_values.iter().enumerate() {
+            positions.entry(row).or_insert(pos as u64);
+        }
+        let values = converter.convert_columns(&[array_dictionary.values().clone()])?;
+        let mapping = UInt64Array::from_iter(values.iter().map(|row| positions.get(&row).copied()));
+        let keys = take(&mapping, array_dictionary.keys(), None)?;
+        if keys.null_count() != array_dictionary.keys().null_count() {
+            return Ok(None);
+        }
+        cast(&keys, key_type)?.to_data()
+    };
+    // Every batch shares the same dictionary, so that it is not merged when batches are
+    // concatenated
+    let data = keys
+        .into_builder()
+        .data_type(array.data_type().clone())
+        .child_data(vec![dictionary.to_data()])
+        .build()?;
+    Ok(Some(make_array(data)))
+}
+
+// Decodes the `columns` of `batch` in slices of about `slice_bytes`
+fn decode_in_slices(
+    batch: RecordBatch,
+    columns: Vec<usize>,
+    schema: SchemaRef,
+    slice_bytes: usize,
+) -> impl Iterator<Item = std::result::Result<RecordBatch, ArrowError>> + Send {
+    // The size of a decoded row is estimated from the average size of the values
+    let bytes_per_row = columns
+        .iter()
+        .map(|idx| {
+            let column = batch.column(*idx);
+            let values = match column.data_type() {
+                DataType::Dictionary(_, _) => column.as_any_dictionary().values(),
+                _ => run_values(column.as_ref()),
+            };
+            values.get_array_memory_size() / values.len().max(1)
+        })
+        .sum::<usize>();
+    let rows_per_slice = (slice_bytes / bytes_per_row.max(1)).max(1);
+    (0..batch.num_rows())
+        .step_by(rows_per_slice)
+        .map(move |offset| {
+            let slice = batch.slice(offset, rows_per_slice.min(batch.num_rows() - offset));
+            let mut arrays = slice.columns().to_vec();
+            for idx in &columns {
+                arrays[*idx] = match arrays[*idx].data_type() {
+                    DataType::Dictionary(_, value_type) => cast(&arrays[*idx], value_type)?,
+                    _ => decode_run_array(arrays[*idx].as_ref())?,
+                };
+            }
+            RecordBatch::try_new(schema.clone(), arrays)
+        })
+}
+
+fn as_run_array<R: RunEndIndexType>(array: &dyn Array) -> &RunArray<R> {
+    array.as_any().downcast_ref::<RunArray<R>>().unwrap()
+}
+
+fn run_values(array: &dyn Array) -> &ArrayRef {
+    match array.data_type() {
+        DataType::RunEndEncoded(run_ends, _) => match run_ends.data_type() {
+            DataType::Int16 => as_run_array::<Int16Type>(array).values(),
+            DataType::Int32 => as_run_array::<Int32Type>(array).values(),
+            _ => as_run_array::<Int64Type>(array).values(),
+        },
+        _ => unreachable!("only run-end encoded arrays have run values"),
+    }
+}
+
+// Expands the runs of a run-end encoded array
+fn decode_run_array(array: &dyn Array) -> std::result::Result<ArrayRef, ArrowError> {
+    fn value_indices<R: RunEndIndexType>(array: &RunArray<R>) -> UInt64Array {
+        let run_ends = array.run_ends();
+        let end = run_ends.offset() + run_ends.len();
+        let mut indices = Vec::with_capacity(run_ends.len());
+        let mut pos = run_ends.offset();
+        for physical in run_ends.get_start_physical_index()..=run_ends.get_end_physical_index() {
+            let run_end = run_ends.values()[physical].as_usize().min(end);
+            indices.extend(std::iter::repeat(physical as u64).take(run_end - pos));
+            pos = run_end;
+        }
+        UInt64Array::from(indices)
+    }
+    if array.is_empty() {
+        return Ok(new_empty_array(run_values(array).data_type()));
+    }
+    let indices = match array.data_type() {
+        DataType::RunEndEncoded(run_ends, _) => match run_ends.data_type() {
+            DataType::Int16 => value_indices(as_run_array::<Int16Type>(array)),
+            DataType::Int32 => value_indices(as_run_array::<Int32Type>(array)),
+            _ => value_indices(as_run_array::<Int64Type>(array)),
+        },
+        _ => unreachable!("only run-end encoded arrays are decoded"),
+    };
+    take(run_values(array).as_ref(), &indices, None)
+}
+
+// The number of rows at the start of `batch` to write to a v2 file before the file
+// limits are checked again
+//
+// The rows are limited to those expected to fit in the file, judged by their in-memory
+// size, so that a single large batch does not overshoot the byte limit.  At least one
+// row is always written.
+fn rows_to_write(
+    batch: &RecordBatch,
+    rows_remaining: usize,
+    bytes_remaining: u64,
+) -> Result<usize> {
+    if batch.num_rows() == 0 {
+        return Ok(0);
+    }
+    let batch_bytes = batch
+        .columns()
+        .iter()
+        .map(|column| column.to_data().get_slice_memory_size())
+        .sum::<std::result::Result<usize, _>>()?;
+    let bytes_per_row = (batch_bytes / batch.num_rows()).max(1) as u64;
+    let rows_that_fit = (bytes_remaining / bytes_per_row).max(1);
+    Ok(batch
+        .num_rows()
+        .min(rows_remaining.max(1))
+        .min(rows_that_fit.try_into().unwrap_or(usize::MAX)))
+}
+
+/// Checks whether the rows being written are sorted by a sort order
+struct SortOrderTracker {
+    columns: Vec<String>,
+    // The ids of the fields of `columns`, which are recorded in the fragments
+    field_ids: Vec<i32>,
+    converter: RowConverter,
+    // The last row written (or, when appending, the last row of the dataset)
+    last_row: Option<OwnedRow>,
+    sorted: bool,
+}
+
+impl SortOrderTracker {
+    async fn try_new(
+        columns: Vec<String>,
+        schema: &Schema,
+        append_to: Option<&Dataset>,
+    ) -> Result<Self> {
+        if columns.is_empty() {
+            return Err(Error::InvalidInput {
+                source: "the sort order must contain at least one column".into(),
+                location: location!(),
+            });
+        }
+        let fields = columns
+            .iter()
+            .map(|column| {
+                schema.field(column).ok_or_else(|| Error::InvalidInput {
+                    source: format!("sort order column '{}' does not exist", column).into(),
+                    location: location!(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let sort_fields = fields
+            .iter()
+            .map(|field| SortField::new(field.data_type()))
+            .collect();
+        let mut tracker = Self {
+            field_ids: fields.iter().map(|field| field.id).collect(),
+            columns,
+            converter: RowConverter::new(sort_fields)?,
+            last_row: None,
+            sorted: true,
+        };
+        if let Some(dataset) = append_to {
+            if let Some(last_batch) = tracker.last_sorted_row(dataset).await? {
+                let rows = tracker
+                    .converter
+                    .convert_columns(&tracker.sort_columns(&last_batch)?)?;
+                tracker.last_row = Some(rows.row(0).owned());
+            }
+        }
+        Ok(tracker)
+    }
+
+    /// Reads the last row of the dataset's fragments that have the same sort order
+    async fn last_sorted_row(&self, dataset: &Dataset) -> Result<Option<RecordBatch>> {
+        let projection = dataset.schema().project(&self.columns)?;
+        for fragment in dataset.get_fragments().iter().rev() {
+            if fragment.metadata().sort_order != self.field_ids {
+                continue;
+            }
+            let num_rows = fragment.count_rows().await?;
+            if num_rows > 0 {
+                let batch = fragment.take(&[num_rows as u32 - 1], &projection).await?;
+                return Ok(Some(batch));
+            }
+        }
+        Ok(None)
+    }
+
+    fn sort_columns(&self, batch: &RecordBatch) -> Result<Vec<ArrayRef>> {
+        self.columns
+            .iter()
+            .map(|column| {
+                batch
+                    .column_by_qualified_name(column)
+                    .cloned()
+                    .ok_or_else(|| Error::InvalidInput {
+                        source: format!("sort order column '{}' does not exist", column).into(),
+                        location: location!(),
+                    })
+            })
+            .collect()
+    }
+
+    fn update(&mut self, batch: &RecordBatch) -> Result<()> {
+        if !self.sorted || batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let rows = self.converter.convert_columns(&self.sort_columns(batch)?)?;
+        let mut previous = self.last_row.as_ref().map(|row| row.row());
+        for row in rows.iter() {
+            if previous.map(|previous| row < previous).unwrap_or(false) {
+                self.sorted = false;
+                return Ok(());
+            }
+            previous = Some(row);
+        }
+        self.last_row = Some(rows.row(rows.num_rows() - 1).owned());
+        Ok(())
+    }
+
+    /// The sort order of the rows written so far (empty if they are not sorted)
+    fn sort_order(&self) -> Vec<i32> {
+        if self.sorted {
+            self.field_ids.clone()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
 #[async_trait::async_trait]
 pub trait GenericWriter: Send {
     /// Write the given batches to the file
@@ -279,7 +916,14 @@ pub trait GenericWriter: Send {
     /// a new file
     async fn tell(&mut self) -> Result<u64>;
     /// Finish writing the file (flush the remaining data and write footer)
-    async fn finish(&mut self) -> Result<(u32, DataFile)>;
+    ///
+    /// Returns the number of rows written and the data files, which is more than one
+    /// file if the columns are split across files.
+    async fn finish(&mut self) -> Result<(u32, Vec<DataFile>)>;
+    /// Stop writing the file without finishing it
+    ///
+    /// Uploads in progress are cancelled, the file isn't created.
+    async fn abort(&mut self) -> Result<()>;
 }
 
 #[async_trait::async_trait]
@@ -290,12 +934,15 @@ impl<M: ManifestProvider + Send + Sync> GenericWriter for (FileWriter<M>, String
     async fn tell(&mut self) -> Result<u64> {
         Ok(self.0.tell().await? as u64)
     }
-    async fn finish(&mut self) -> Result<(u32, DataFile)> {
+    async fn finish(&mut self) -> Result<(u32, Vec<DataFile>)> {
         Ok((
             self.0.finish().await? as u32,
-            DataFile::new_legacy(self.1.clone(), self.0.schema()),
+            vec![DataFile::new_legacy(self.1.clone(), self.0.schema())],
         ))
     }
+    async fn abort(&mut self) -> Result<()> {
+        self.0.abort().await
+    }
 }
 
 struct V2WriterAdapter {
@@ -314,7 +961,7 @@ impl GenericWriter for V2WriterAdapter {
     async fn tell(&mut self) -> Result<u64> {
         Ok(self.writer.tell().await?)
     }
-    async fn finish(&mut self) -> Result<(u32, DataFile)> {
+    async fn finish(&mut self) -> Result<(u32, Vec<DataFile>)> {
         let field_ids = self
             .writer
             .field_id_to_column_indices()
@@ -334,8 +981,56 @@ impl GenericWriter for V2WriterAdapter {
             MAJOR_VERSION as u32,
             MINOR_VERSION_NEXT as u32,
         );
-        let num_rows = self.writer.finish().await? as u32;
-        Ok((num_rows, data_file))
+        let num_rows = self.writer.finish().await?.num_rows as u32;
+        Ok((num_rows, vec![data_file]))
+    }
+    async fn abort(&mut self) -> Result<()> {
+        self.writer.abort().await
+    }
+}
+
+// Writes the blob columns to one file and the remaining columns to another
+struct BlobSplitWriter {
+    writer: Box<dyn GenericWriter>,
+    schema: ArrowSchema,
+    blob_writer: Box<dyn GenericWriter>,
+    blob_schema: ArrowSchema,
+}
+
+#[async_trait::async_trait]
+impl GenericWriter for BlobSplitWriter {
+    async fn write(&mut self, batches: &[RecordBatch]) -> Result<()> {
+        let (batches, blob_batches) = batches
+            .iter()
+            .map(|batch| {
+                Ok((
+                    batch.project_by_schema(&self.schema)?,
+                    batch.project_by_schema(&self.blob_schema)?,
+                ))
+            })
+            .collect::<Result<(Vec<_>, Vec<_>)>>()?;
+        self.writer.write(&batches).await?;
+        self.blob_writer.write(&blob_batches).await
+    }
+    async fn tell(&mut self) -> Result<u64> {
+        // The size limit applies to each file
+        Ok(self
+            .writer
+            .tell()
+            .await?
+            .max(self.blob_writer.tell().await?))
+    }
+    async fn finish(&mut self) -> Result<(u32, Vec<DataFile>)> {
+        let (num_rows, mut data_files) = self.writer.finish().await?;
+        let (num_blob_rows, blob_data_files) = self.blob_writer.finish().await?;
+        debug_assert_eq!(num_rows, num_blob_rows);
+        data_files.extend(blob_data_files);
+        Ok((num_rows, data_files))
+    }
+    async fn abort(&mut self) -> Result<()> {
+        let result = self.writer.abort().await;
+        self.blob_writer.abort().await?;
+        result
     }
 }
 
@@ -344,6 +1039,24 @@ pub async fn open_writer(
     schema: &Schema,
     base_dir: &Path,
     use_legacy_format: bool,
+) -> Result<Box<dyn GenericWriter>> {
+    open_writer_with_options(
+        object_store,
+        schema,
+        base_dir,
+        use_legacy_format,
+        FileWriterOptions::default(),
+    )
+    .await
+}
+
+// Like `open_writer` but with the options for v2 files
+async fn open_writer_with_options(
+    object_store: &ObjectStore,
+    schema: &Schema,
+    base_dir: &Path,
+    use_legacy_format: bool,
+    v2_options: FileWriterOptions,
 ) -> Result<Box<dyn GenericWriter>> {
     let filename = format!("{}.lance", Uuid::new_v4());
 
@@ -362,8 +1075,7 @@ pub async fn open_writer(
         ))
     } else {
         let writer = object_store.create(&full_path).await?;
-        let file_writer =
-            v2::writer::FileWriter::try_new(writer, schema.clone(), FileWriterOptions::default())?;
+        let file_writer = v2::writer::FileWriter::try_new(writer, schema.clone(), v2_options)?;
         let writer_adapter = V2WriterAdapter {
             writer: file_writer,
             path: filename,
@@ -378,35 +1090,93 @@ struct WriterGenerator {
     object_store: Arc<ObjectStore>,
     base_dir: Path,
     schema: Schema,
+    // If set, the schemas of the non-blob and blob columns, which are written to
+    // separate files
+    split_schemas: Option<(Schema, Schema)>,
     use_legacy_format: bool,
+    v2_options: FileWriterOptions,
 }
 
 impl WriterGenerator {
-    pub fn new(
+    pub fn try_new(
         object_store: Arc<ObjectStore>,
         base_dir: &Path,
         schema: &Schema,
-        use_legacy_format: bool,
-    ) -> Self {
-        Self {
+        params: &WriteParams,
+    ) -> Result<Self> {
+        // Data buffered by the writer is not seen by `tell` and so, when files are
+        // small, the buffer is capped to keep files near `max_bytes_per_file`
+        let default_cache_bytes =
+            DEFAULT_DATA_CACHE_BYTES_PER_COLUMN.saturating_mul(schema.fields.len() as u64);
+        let max_cache_bytes = params.max_bytes_per_file as u64 / 4;
+        let v2_options = FileWriterOptions {
+            data_cache_bytes: (max_cache_bytes < default_cache_bytes)
+                .then_some(max_cache_bytes.max(1)),
+            ..Default::default()
+        };
+        Ok(Self {
             object_store,
             base_dir: base_dir.clone(),
             schema: schema.clone(),
-            use_legacy_format,
+            split_schemas: Self::split_schemas(schema)?,
+            use_legacy_format: params.use_legacy_format,
+            v2_options,
+        })
+    }
+
+    fn split_schemas(schema: &Schema) -> Result<Option<(Schema, Schema)>> {
+        let blob_columns = schema
+            .fields
+            .iter()
+            .filter(|field| field.metadata.contains_key(BLOB_COLUMN_META_KEY))
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>();
+        let blob_schema = schema.project(&blob_columns)?;
+        let other_schema = schema.exclude(&blob_schema)?;
+        if blob_schema.fields.is_empty() || other_schema.fields.is_empty() {
+            // Nothing to split
+            return Ok(None);
         }
+        Ok(Some((other_schema, blob_schema)))
     }
 
     pub async fn new_writer(&self) -> Result<(Box<dyn GenericWriter>, Fragment)> {
         // Use temporary ID 0; will assign ID later.
         let fragment = Fragment::new(0);
 
-        let writer = open_writer(
-            &self.object_store,
-            &self.schema,
-            &self.base_dir,
-            self.use_legacy_format,
-        )
-        .await?;
+        let writer = if let Some((schema, blob_schema)) = &self.split_schemas {
+            let writer = open_writer_with_options(
+                &self.object_store,
+                schema,
+                &self.base_dir,
+                self.use_legacy_format,
+                self.v2_options.clone(),
+            )
+            .await?;
+            let blob_writer = open_writer_with_options(
+                &self.object_store,
+                blob_schema,
+                &self.base_dir,
+                self.use_legacy_format,
+                self.v2_options.clone(),
+            )
+            .await?;
+            Box::new(BlobSplitWriter {
+                writer,
+                schema: schema.into(),
+                blob_writer,
+                blob_schema: blob_schema.into(),
+            })
+        } else {
+            open_writer_with_options(
+                &self.object_store,
+                &self.schema,
+                &self.base_dir,
+                self.use_legacy_format,
+                self.v2_options.clone(),
+            )
+            .await?
+        };
 
         Ok((writer, fragment))
     }
@@ -416,12 +1186,16 @@ impl WriterGenerator {
 mod tests {
     use super::*;
 
-    use arrow_array::{Int32Array, StructArray};
+    use arrow_array::{
+        cast::AsArray, types::Int32Type, Int16Array, Int32Array, Int32DictionaryArray, Int64Array,
+        LargeBinaryArray, RecordBatchIterator, StringArray, StructArray,
+    };
     use arrow_schema::{DataType, Field as ArrowField, Fields, Schema as ArrowSchema};
     use datafusion::{error::DataFusionError, physical_plan::stream::RecordBatchStreamAdapter};
     use futures::TryStreamExt;
     use lance_file::reader::FileReader;
     use lance_io::traits::Reader;
+    use rand::Rng;
 
     #[tokio::test]
     async fn test_chunking_large_batches() {
@@ -596,6 +1370,88 @@ mod tests {
         assert_eq!(fragment.files[0].file_minor_version, 3);
     }
 
+    #[tokio::test]
+    async fn test_file_size_v2() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("blob", DataType::LargeBinary, false),
+        ]));
+        // 400 rows of 10KiB that don't compress, in batches of 3MiB.  Most files have to
+        // end part way through a batch.
+        const ROW_SIZE: usize = 10 * 1024;
+        let mut rng = rand::thread_rng();
+        let batches = (0..400)
+            .step_by(300)
+            .map(|start| {
+                let num_rows = 300.min(400 - start);
+                let blobs = (0..num_rows)
+                    .map(|_| (0..ROW_SIZE).map(|_| rng.gen::<u8>()).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(start..start + num_rows)),
+                        Arc::new(LargeBinaryArray::from_iter_values(blobs)),
+                    ],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        const MAX_BYTES: usize = 1024 * 1024;
+        let write_params = WriteParams {
+            max_bytes_per_file: MAX_BYTES,
+            use_legacy_format: false,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let dataset = Dataset::write(reader, "memory://test_file_size_v2", Some(write_params))
+            .await
+            .unwrap();
+
+        let fragments = dataset.get_fragments();
+        assert!(fragments.len() >= 3, "{} fragments", fragments.len());
+        let mut next_id = 0;
+        for (idx, fragment) in fragments.iter().enumerate() {
+            let path = dataset
+                .base
+                .child(DATA_DIR)
+                .child(fragment.metadata().files[0].path.as_str());
+            let file_size = dataset.object_store.size(&path).await.unwrap();
+            assert!(
+                file_size <= MAX_BYTES * 3 / 2,
+                "file {} is {} bytes",
+                idx,
+                file_size
+            );
+            if idx + 1 < fragments.len() {
+                assert!(
+                    file_size >= MAX_BYTES / 2,
+                    "file {} is {} bytes",
+                    idx,
+                    file_size
+                );
+            }
+
+            // The recorded row counts match the rows in each file
+            let ids = fragment
+                .scan()
+                .project(&["id"])
+                .unwrap()
+                .try_into_batch()
+                .await
+                .unwrap();
+            let num_rows = ids.num_rows() as i32;
+            assert_eq!(fragment.metadata().physical_rows, Some(num_rows as usize));
+            assert_eq!(
+                ids["id"].as_primitive::<Int32Type>().values(),
+                &(next_id..next_id + num_rows).collect::<Vec<_>>()
+            );
+            next_id += num_rows;
+        }
+        assert_eq!(next_id, 400);
+    }
+
     #[tokio::test]
     async fn test_file_v1_schema_order() {
         // Create a schema where fields ids are not in order and contain holes.
@@ -678,4 +1534,155 @@ mod tests {
         let batch = reader.read_batch(0, .., &schema).await.unwrap();
         assert_eq!(batch, data);
     }
+
+    #[test]
+    fn test_decode_dictionary_in_slices() {
+        // 60k rows of 1KiB strings is about 60MiB once decoded
+        let dictionary: Vec<String> = (0..10).map(|i| i.to_string().repeat(1024)).collect();
+        let keys = Int32Array::from_iter_values((0..60_000).map(|i| i % 10));
+        let strings =
+            Int32DictionaryArray::new(keys, Arc::new(StringArray::from(dictionary.clone())));
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            strings.data_type().clone(),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(strings)]).unwrap();
+        let expected = cast(batch.column(0), &DataType::Utf8).unwrap();
+        let decoded_bytes = expected.get_array_memory_size();
+
+        let mut num_slices = 0;
+        let mut num_rows = 0;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        for decoded in decode_encoded_columns(Box::new(reader), None, false).unwrap() {
+            let decoded = decoded.unwrap();
+            assert_eq!(decoded.schema().field(0).data_type(), &DataType::Utf8);
+            assert_eq!(
+                decoded.column(0).as_ref(),
+                expected.slice(num_rows, decoded.num_rows()).as_ref()
+            );
+            // The whole column is never decoded at once
+            let slice_bytes = decoded.column(0).get_array_memory_size();
+            assert!(
+                slice_bytes < 2 * DECODED_SLICE_BYTES,
+                "decoded a slice of {} bytes",
+                slice_bytes
+            );
+            num_slices += 1;
+            num_rows += decoded.num_rows();
+        }
+        assert_eq!(num_rows, expected.len());
+        assert!(
+            num_slices >= decoded_bytes / DECODED_SLICE_BYTES,
+            "decoded {} bytes in {} slices",
+            decoded_bytes,
+            num_slices
+        );
+    }
+
+    #[test]
+    fn test_unify_legacy_dictionaries() {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            true,
+        )]));
+        let batch = |dictionary: Vec<&str>, keys: Vec<Option<i32>>| {
+            let strings = Int32DictionaryArray::new(
+                Int32Array::from(keys),
+                Arc::new(StringArray::from(dictionary)),
+            );
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(strings)]).unwrap()
+        };
+        let batches = vec![
+            batch(vec!["a", "b", "c"], vec![Some(0), Some(1)]),
+            // A different dictionary with values of the first one
+            batch(vec!["c", "a"], vec![Some(0), None, Some(1)]),
+            batch(vec!["a", "b", "c"], vec![Some(2)]),
+        ];
+
+        let reader = RecordBatchIterator::new(batches.clone().into_iter().map(Ok), schema.clone());
+        let unified = decode_encoded_columns(Box::new(reader), None, true)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(unified.len(), 3);
+        for (unified, batch) in unified.iter().zip(&batches) {
+            assert_eq!(unified.schema(), schema);
+            let dictionary = unified.column(0).as_dictionary::<Int32Type>();
+            assert_eq!(
+                dictionary.values().as_ref(),
+                &StringArray::from(vec!["a", "b", "c"]) as &dyn Array
+            );
+            assert_eq!(
+                cast(unified.column(0), &DataType::Utf8).unwrap().as_ref(),
+                cast(batch.column(0), &DataType::Utf8).unwrap().as_ref()
+            );
+        }
+        assert_eq!(
+            unified[1].column(0).as_dictionary::<Int32Type>().keys(),
+            &Int32Array::from(vec![Some(2), None, Some(0)])
+        );
+
+        // A value that is not in the first dictionary
+        let batches = vec![
+            batch(vec!["a", "b"], vec![Some(0)]),
+            batch(vec!["c", "b"], vec![Some(1), Some(0)]),
+        ];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let err = decode_encoded_columns(Box::new(reader), None, true)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap_err();
+        assert!(err.to_string().contains("Column 's' has values"), "{}", err);
+
+        // The v2 format decodes the dictionaries instead
+        let reader = RecordBatchIterator::new(
+            vec![
+                Ok(batch(vec!["a"], vec![Some(0)])),
+                Ok(batch(vec!["b"], vec![Some(0)])),
+            ],
+            schema,
+        );
+        let decoded = decode_encoded_columns(Box::new(reader), None, false)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded[1].column(0).as_string::<i32>().value(0), "b");
+    }
+
+    #[test]
+    fn test_decode_run_end_encoded() {
+        let values = Int64Array::from(vec![Some(1), None, Some(3)]);
+        let runs = RunArray::try_new(&Int16Array::from(vec![2, 3, 6]), &values).unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "n",
+            runs.data_type().clone(),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(runs)]).unwrap();
+
+        // Small slices split runs
+        let decoded = decode_in_slices(
+            batch,
+            vec![0],
+            Arc::new(ArrowSchema::new(vec![ArrowField::new(
+                "n",
+                DataType::Int64,
+                true,
+            )])),
+            16,
+        )
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(decoded.len(), 6);
+        let columns = decoded
+            .iter()
+            .map(|batch| batch.column(0).as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            arrow_select::concat::concat(&columns).unwrap().as_ref(),
+            &Int64Array::from(vec![Some(1), Some(1), None, Some(3), Some(3), Some(3)])
+        );
+    }
 }
@@ -15,19 +15,29 @@
 //! This match condition is currently limited to an key-match.  This means we consider a row to be a match if the
 //! key columns are identical in both the source and the target.  This means that you will need some kind of
 //! meaningful key column to be able to perform a merge insert.
+//!
+//! The key may be composite (e.g. `tenant_id` and `entity_id`), in which case rows match only when every key
+//! column is equal.  Following SQL semantics, a row with a null in any key column never matches, unless the
+//! job is configured to treat null keys as equal.  The key is expected to be unique in the source; duplicate
+//! source keys either fail the operation or resolve to the last occurrence (see [`DuplicateSourceKeys`]).
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     sync::{Arc, Mutex},
 };
 
 use arrow_array::{
     cast::AsArray, types::UInt64Type, BooleanArray, RecordBatch, RecordBatchReader, StructArray,
+    UInt32Array,
 };
+use arrow_row::{OwnedRow, RowConverter, Rows, SortField};
 use arrow_schema::{DataType, Field, Schema};
+use arrow_select::concat::concat_batches;
 use datafusion::{
+    dataframe::DataFrame,
+    error::DataFusionError,
     execution::context::{SessionConfig, SessionContext},
-    logical_expr::{Expr, JoinType},
+    logical_expr::{Expr, JoinType, LogicalPlanBuilder},
     physical_plan::{
         joins::{HashJoinExec, PartitionMode},
         repartition::RepartitionExec,
@@ -54,7 +64,11 @@ use lance_datafusion::{
     utils::reader_to_stream,
 };
 use lance_index::DatasetIndexExt;
-use lance_table::format::{Fragment, Index};
+use lance_table::{
+    feature_flags::should_use_legacy_format,
+    format::{Fragment, Index},
+    io::commit::CommitConfig,
+};
 use log::info;
 use roaring::RoaringTreemap;
 use snafu::{location, Location, ResultExt};
@@ -70,7 +84,7 @@ use crate::{
     Dataset,
 };
 
-use super::write_fragments_internal;
+use super::{write_fragments_internal, WriteParams};
 
 // "update if" expressions typically compare fields from the source table to the target table.
 // These tables have the same schema and so filter expressions need to differentiate.  To do that
@@ -158,10 +172,58 @@ pub enum WhenMatched {
     /// The row is updated (similar to UpdateAll) only for rows where the expression evaluates to
     /// true
     UpdateIf(Expr),
+    /// Only the given columns are updated from the source table, all other columns keep the
+    /// values from the target table
+    ///
+    /// If a condition is given then only rows where the condition evaluates to true are updated
+    UpdateColumns {
+        columns: Vec<String>,
+        condition: Option<Expr>,
+    },
 }
 
 impl WhenMatched {
     pub fn update_if(dataset: &Dataset, expr: &str) -> Result<Self> {
+        Ok(Self::UpdateIf(Self::parse_condition(dataset, expr)?))
+    }
+
+    /// Create an instance of WhenMatched::UpdateColumns
+    ///
+    /// The columns must be top-level columns of the dataset.  The condition, if given, is an
+    /// SQL filter string that can refer to both `source` and `target` columns (e.g.
+    /// `source.updated_at > target.updated_at`)
+    pub fn update_columns(
+        dataset: &Dataset,
+        columns: &[&str],
+        condition: Option<&str>,
+    ) -> Result<Self> {
+        if columns.is_empty() {
+            return Err(Error::invalid_input(
+                "A merge insert update must specify at least one column to update",
+                location!(),
+            ));
+        }
+        for column in columns {
+            if !dataset.schema().fields.iter().any(|f| f.name == *column) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot update column '{}' in merge insert, it is not a top-level column of the dataset",
+                        column
+                    ),
+                    location!(),
+                ));
+            }
+        }
+        let condition = condition
+            .map(|expr| Self::parse_condition(dataset, expr))
+            .transpose()?;
+        Ok(Self::UpdateColumns {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            condition,
+        })
+    }
+
+    fn parse_condition(dataset: &Dataset, expr: &str) -> Result<Expr> {
         let dataset_schema: Schema = dataset.schema().into();
         let combined_schema = combined_schema(&dataset_schema);
         let planner = Planner::new(Arc::new(combined_schema));
@@ -169,11 +231,18 @@ impl WhenMatched {
             .parse_filter(expr)
             .map_err(box_error)
             .context(InvalidInputSnafu)?;
-        let expr = planner
+        planner
             .optimize_expr(expr)
             .map_err(box_error)
-            .context(InvalidInputSnafu)?;
-        Ok(Self::UpdateIf(expr))
+            .context(InvalidInputSnafu)
+    }
+
+    fn condition(&self) -> Option<&Expr> {
+        match self {
+            Self::UpdateIf(expr) => Some(expr),
+            Self::UpdateColumns { condition, .. } => condition.as_ref(),
+            Self::UpdateAll | Self::DoNothing => None,
+        }
     }
 }
 
@@ -189,16 +258,36 @@ pub enum WhenNotMatched {
     DoNothing,
 }
 
+/// Describes how the source is handled when several of its rows share the same key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateSourceKeys {
+    /// The merge insert fails if a key appears more than once in the source
+    ///
+    /// This is the default.  Before this option was added duplicate source keys were not
+    /// detected: every occurrence was merged, and so a matching target row was updated
+    /// once per occurrence (leaving several copies of it) and a new key was inserted
+    /// several times.  [`Self::LastWriteWins`] keeps a single row per key instead.
+    Fail,
+    /// Only the last source row with a given key is merged, earlier rows are discarded
+    LastWriteWins,
+}
+
 #[derive(Debug, Clone)]
 struct MergeInsertParams {
     // The column(s) to join on
     on: Vec<String>,
+    // If true, then null key values are considered equal to each other when matching rows
+    null_keys_match: bool,
+    // Controls what happens when a key appears more than once in the source
+    duplicate_source_keys: DuplicateSourceKeys,
     // If true, then update all columns of the old data to the new data when there is a match
     when_matched: WhenMatched,
     // If true, then insert all columns of the new data when there is no match in the old data
     insert_not_matched: bool,
     // Controls whether data that is not matched by the source is deleted or not
     delete_not_matched_by_source: WhenNotMatchedBySource,
+    // The number of times to retry the commit if a concurrent writer commits first
+    commit_retries: u32,
 }
 
 /// A MergeInsertJob inserts new rows, deletes old rows, and updates existing rows all as
@@ -243,6 +332,14 @@ pub struct MergeInsertJob {
 ///     .build()?
 ///     .execute(new_data)
 ///     .await?;
+///
+/// // only update the "value" column, and only if the new data is more recent
+/// let builder = MergeInsertBuilder::new(dataset, vec!["my_key"]);
+/// let dataset = builder
+///     .when_matched_update(&["value"], Some("source.updated_at > target.updated_at"))?
+///     .build()?
+///     .execute(new_data)
+///     .await?;
 /// ```
 ///
 #[derive(Debug, Clone)]
@@ -258,6 +355,7 @@ impl MergeInsertBuilder {
     ///  - matching rows will be kept as-is
     ///  - new rows in the new data will be inserted
     ///  - rows in the old data that do not match will be left as-is
+    ///  - a key that appears more than once in the new data fails the job
     ///
     /// Use the methods on this builder to customize that behavior
     pub fn try_new(dataset: Arc<Dataset>, on: Vec<String>) -> Result<Self> {
@@ -267,13 +365,33 @@ impl MergeInsertBuilder {
                 location!(),
             ));
         }
+        for (idx, key) in on.iter().enumerate() {
+            if !dataset.schema().fields.iter().any(|f| &f.name == key) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot merge insert on column '{}', it is not a top-level column of the dataset",
+                        key
+                    ),
+                    location!(),
+                ));
+            }
+            if on[..idx].contains(key) {
+                return Err(Error::invalid_input(
+                    format!("The merge insert on key '{}' was given more than once", key),
+                    location!(),
+                ));
+            }
+        }
         Ok(Self {
             dataset,
             params: MergeInsertParams {
                 on,
+                null_keys_match: false,
+                duplicate_source_keys: DuplicateSourceKeys::Fail,
                 when_matched: WhenMatched::DoNothing,
                 insert_not_matched: true,
                 delete_not_matched_by_source: WhenNotMatchedBySource::Keep,
+                commit_retries: CommitConfig::default().num_retries,
             },
         })
     }
@@ -284,6 +402,20 @@ impl MergeInsertBuilder {
         self
     }
 
+    /// Update only the given columns of target rows that match a row in the source
+    ///
+    /// If a condition is given (e.g. `source.updated_at > target.updated_at`) then only
+    /// matching rows for which the condition is true are updated.  See
+    /// [`WhenMatched::update_columns`]
+    pub fn when_matched_update(
+        &mut self,
+        columns: &[&str],
+        condition: Option<&str>,
+    ) -> Result<&mut Self> {
+        self.params.when_matched = WhenMatched::update_columns(&self.dataset, columns, condition)?;
+        Ok(self)
+    }
+
     /// Specify what should happen when a source row has no match in the target
     ///
     /// These are typically "new rows"
@@ -303,6 +435,47 @@ impl MergeInsertBuilder {
         self
     }
 
+    /// Delete target rows that have no match in the source
+    ///
+    /// If a condition is given (an SQL filter over the dataset's columns) then only unmatched
+    /// rows for which the condition is true are deleted
+    pub fn when_not_matched_by_source_delete(
+        &mut self,
+        condition: Option<&str>,
+    ) -> Result<&mut Self> {
+        self.params.delete_not_matched_by_source = match condition {
+            Some(expr) => WhenNotMatchedBySource::delete_if(&self.dataset, expr)?,
+            None => WhenNotMatchedBySource::Delete,
+        };
+        Ok(self)
+    }
+
+    /// Specify whether null key values match each other
+    ///
+    /// By default (as in SQL) a row with a null in any of its key columns matches no other row.
+    /// If this is true then null key values compare equal to each other
+    pub fn null_keys_match(&mut self, null_keys_match: bool) -> &mut Self {
+        self.params.null_keys_match = null_keys_match;
+        self
+    }
+
+    /// Specify what should happen when the same key appears more than once in the source
+    ///
+    /// By default the merge insert fails.  Rows with a null key are never considered
+    /// duplicates unless [`Self::null_keys_match`] is set
+    pub fn when_duplicate_source_keys(&mut self, behavior: DuplicateSourceKeys) -> &mut Self {
+        self.params.duplicate_source_keys = behavior;
+        self
+    }
+
+    /// Set the number of times to retry the commit if a concurrent writer commits first
+    ///
+    /// See [`crate::dataset::WriteParams::commit_retries`]
+    pub fn commit_retries(&mut self, commit_retries: u32) -> &mut Self {
+        self.params.commit_retries = commit_retries;
+        self
+    }
+
     /// Crate a merge insert job
     pub fn try_build(&mut self) -> Result<MergeInsertJob> {
         if !self.params.insert_not_matched
@@ -341,13 +514,47 @@ impl MergeInsertJob {
         )
     }
 
+    // The index on the leading key column, if there is one
+    //
+    // For a composite key the index locates candidate rows using the leading column only.  The
+    // join that follows compares the remaining key columns.
     async fn join_key_as_scalar_index(&self) -> Result<Option<Index>> {
-        if self.params.on.len() != 1 {
-            // joining on more than one column
-            Ok(None)
-        } else {
-            let col = &self.params.on[0];
-            self.dataset.load_scalar_index_for_column(col).await
+        let col = &self.params.on[0];
+        self.dataset.load_scalar_index_for_column(col).await
+    }
+
+    // Makes sure that each key appears at most once in the source, according to the
+    // duplicate_source_keys parameter
+    async fn dedupe_source(
+        &self,
+        source: SendableRecordBatchStream,
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = source.schema();
+        let mut keys = SourceKeys::try_new(&schema, &self.params)?;
+        match self.params.duplicate_source_keys {
+            DuplicateSourceKeys::Fail => {
+                let stream = source.map(move |batch| {
+                    let batch = batch?;
+                    keys.check_unique(&batch)?;
+                    Ok(batch)
+                });
+                Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+            }
+            DuplicateSourceKeys::LastWriteWins => {
+                // The last occurrence of a key may be anywhere in the source and so we need to
+                // read all of it before we can decide which rows to keep
+                let batches = source.try_collect::<Vec<_>>().await?;
+                let batch = concat_batches(&schema, &batches)?;
+                let batches = if batch.num_rows() > 0 {
+                    vec![Ok(keys.keep_last(&batch)?)]
+                } else {
+                    vec![]
+                };
+                Ok(Box::pin(RecordBatchStreamAdapter::new(
+                    schema,
+                    stream::iter(batches),
+                )))
+            }
         }
     }
 
@@ -423,19 +630,31 @@ impl MergeInsertJob {
             )?);
         }
 
-        // 6 - Finally, join the input (source table) with the taken data (target table)
-        let source_key = Column::new_with_schema(&index_column, shared_input.schema().as_ref())?;
-        let target_key = Column::new_with_schema(&index_column, target.schema().as_ref())?;
+        // 6 - Finally, join the input (source table) with the taken data (target table) on all of
+        //     the key columns
+        let join_on = self
+            .params
+            .on
+            .iter()
+            .map(|key| {
+                let source_key = Column::new_with_schema(key, shared_input.schema().as_ref())?;
+                let target_key = Column::new_with_schema(key, target.schema().as_ref())?;
+                Ok((
+                    Arc::new(target_key) as Arc<dyn PhysicalExpr>,
+                    Arc::new(source_key) as Arc<dyn PhysicalExpr>,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
         let joined = Arc::new(
             HashJoinExec::try_new(
                 shared_input,
                 target,
-                vec![(Arc::new(target_key), Arc::new(source_key))],
+                join_on,
                 None,
                 &JoinType::Full,
                 None,
                 PartitionMode::CollectLeft,
-                true,
+                self.params.null_keys_match,
             )
             .unwrap(),
         );
@@ -465,7 +684,16 @@ impl MergeInsertJob {
             .iter()
             .map(|c| c.as_str())
             .collect::<Vec<_>>(); // vector of strings of col names to join
-        let joined = new_data.join(existing, JoinType::Full, &join_cols, &join_cols, None)?; // full join
+        let joined = LogicalPlanBuilder::from(new_data.into_unoptimized_plan())
+            .join_detailed(
+                existing.into_unoptimized_plan(),
+                JoinType::Full,
+                (join_cols.clone(), join_cols),
+                None,
+                self.params.null_keys_match,
+            )?
+            .build()?; // full join
+        let joined = DataFrame::new(session_ctx.state(), joined);
         Ok(joined.execute_stream().await?)
     }
 
@@ -501,6 +729,7 @@ impl MergeInsertJob {
     ) -> Result<(Arc<Dataset>, MergeStats)> {
         let schema = source.schema();
 
+        let source = self.dedupe_source(source).await?;
         let joined = self.create_joined_stream(source).await?;
         let merger = Merger::try_new(self.params, schema.clone())?;
         let merge_statistics = merger.merge_stats.clone();
@@ -516,7 +745,13 @@ impl MergeInsertJob {
             &self.dataset.base,
             self.dataset.schema(),
             Box::pin(stream),
-            Default::default(),
+            WriteParams {
+                // New rows are written in the same file format as the rest of the dataset
+                use_legacy_format: should_use_legacy_format(
+                    self.dataset.manifest.writer_feature_flags,
+                ),
+                ..Default::default()
+            },
         )
         .await?;
 
@@ -529,6 +764,7 @@ impl MergeInsertJob {
         // Commit updated and new fragments
         let committed_ds = Self::commit(
             self.dataset,
+            self.params.commit_retries,
             removed_fragment_ids,
             old_fragments,
             new_fragments,
@@ -593,6 +829,7 @@ impl MergeInsertJob {
     // Commit the operation
     async fn commit(
         dataset: Arc<Dataset>,
+        commit_retries: u32,
         removed_fragment_ids: Vec<u64>,
         updated_fragments: Vec<Fragment>,
         new_fragments: Vec<Fragment>,
@@ -610,7 +847,9 @@ impl MergeInsertJob {
             dataset.commit_handler.as_ref(),
             &transaction,
             &Default::default(),
-            &Default::default(),
+            &CommitConfig {
+                num_retries: commit_retries,
+            },
         )
         .await?;
 
@@ -621,6 +860,104 @@ impl MergeInsertJob {
     }
 }
 
+// The keys of the source rows that have been seen so far
+struct SourceKeys {
+    // The positions of the key columns in the source schema
+    key_cols: Vec<usize>,
+    key_names: Vec<String>,
+    converter: RowConverter,
+    null_keys_match: bool,
+    seen: HashSet<OwnedRow>,
+}
+
+impl SourceKeys {
+    fn try_new(schema: &Schema, params: &MergeInsertParams) -> Result<Self> {
+        let key_cols = params
+            .on
+            .iter()
+            .map(|key| schema.index_of(key))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let converter = RowConverter::new(
+            key_cols
+                .iter()
+                .map(|idx| SortField::new(schema.field(*idx).data_type().clone()))
+                .collect(),
+        )?;
+        Ok(Self {
+            key_cols,
+            key_names: params.on.clone(),
+            converter,
+            null_keys_match: params.null_keys_match,
+            seen: HashSet::new(),
+        })
+    }
+
+    // Converts the keys of a batch into comparable rows
+    //
+    // Also returns a bitmap of the rows whose keys can match other rows (i.e. the rows that
+    // do not have a null key, unless null keys match)
+    fn convert(&self, batch: &RecordBatch) -> Result<(Rows, BooleanArray)> {
+        let columns = self
+            .key_cols
+            .iter()
+            .map(|idx| batch.column(*idx).clone())
+            .collect::<Vec<_>>();
+        let rows = self.converter.convert_columns(&columns)?;
+        let mut comparable = BooleanArray::from(vec![true; batch.num_rows()]);
+        if !self.null_keys_match {
+            for column in &columns {
+                let is_valid = arrow::compute::is_not_null(column)?;
+                comparable = arrow::compute::and(&comparable, &is_valid)?;
+            }
+        }
+        Ok((rows, comparable))
+    }
+
+    fn describe_key(&self, batch: &RecordBatch, row: usize) -> Result<String> {
+        let values = self
+            .key_cols
+            .iter()
+            .zip(&self.key_names)
+            .map(|(idx, name)| {
+                let value = ScalarValue::try_from_array(batch.column(*idx), row)?;
+                Ok(format!("{}={}", name, value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(values.join(", "))
+    }
+
+    // Fails if a key in the batch has already been seen
+    fn check_unique(&mut self, batch: &RecordBatch) -> Result<()> {
+        let (rows, comparable) = self.convert(batch)?;
+        for (idx, row) in rows.iter().enumerate() {
+            if comparable.value(idx) && !self.seen.insert(row.owned()) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "The merge insert source contains more than one row with the key ({})",
+                        self.describe_key(batch, idx)?
+                    ),
+                    location!(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Discards all but the last row for each key
+    fn keep_last(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let (rows, comparable) = self.convert(batch)?;
+        let mut keep = Vec::with_capacity(batch.num_rows());
+        for idx in (0..batch.num_rows()).rev() {
+            if !comparable.value(idx) || self.seen.insert(rows.row(idx).owned()) {
+                keep.push(idx as u32);
+            }
+        }
+        keep.reverse();
+        let indices = UInt32Array::from(keep);
+        Ok(arrow_select::take::take_record_batch(batch, &indices)?)
+    }
+}
+
 /// Merger will store these statistics as it runs (for each batch)
 #[derive(Debug, Default, Clone)]
 pub struct MergeStats {
@@ -646,7 +983,7 @@ struct Merger {
     delete_expr: Option<Arc<dyn PhysicalExpr>>,
     // User statistics for merging
     merge_stats: Arc<Mutex<MergeStats>>,
-    // Physical "when matched update if" expression, only set if params.when_matched is UpdateIf
+    // Physical "when matched update if" expression, only set if params.when_matched has a condition
     match_filter_expr: Option<Arc<dyn PhysicalExpr>>,
     // The parameters controlling the merge
     params: MergeInsertParams,
@@ -671,7 +1008,7 @@ impl Merger {
         } else {
             None
         };
-        let match_filter_expr = if let WhenMatched::UpdateIf(expr) = &params.when_matched {
+        let match_filter_expr = if let Some(expr) = params.when_matched.condition() {
             let combined_schema = Arc::new(combined_schema(&schema));
             let planner = Planner::new(combined_schema.clone());
             let expr = planner.optimize_expr(expr.clone())?;
@@ -702,7 +1039,7 @@ impl Merger {
         col_offset: usize,
         num_cols: usize,
     ) -> Result<BooleanArray> {
-        // For our purposes we know there is always at least 1 on key
+        // For our purposes we know there is always at least 1 column
         debug_assert_ne!(num_cols, 0);
         let mut at_least_one_valid = arrow::compute::is_not_null(batch.column(col_offset))?;
         for idx in col_offset + 1..col_offset + num_cols {
@@ -720,7 +1057,9 @@ impl Merger {
     // | ********* | ************ | ********** | ************* | <- when matched
     // | ********* | ************ | NULL       | NULL          | <- when not matched by source
     //
-    // To test which case we are in we check to see if all of LEFT KEYS or RIGHT KEYS are null
+    // To test which case we are in we check the row id, which is only null when there is no
+    // target row, and whether all of the LEFT columns are null.  We can't rely on the keys alone
+    // since key values may themselves be null
     //
     // This returns three selection bitmaps
     //
@@ -731,10 +1070,13 @@ impl Merger {
         &self,
         combined_batch: &RecordBatch,
         right_offset: usize,
-        num_keys: usize,
+        row_id_col: usize,
     ) -> Result<(BooleanArray, BooleanArray, BooleanArray)> {
-        let in_left = Self::not_all_null(combined_batch, 0, num_keys)?;
-        let in_right = Self::not_all_null(combined_batch, right_offset, num_keys)?;
+        let in_right = arrow::compute::is_not_null(combined_batch.column(row_id_col))?;
+        let in_left = arrow::compute::or(
+            &arrow::compute::not(&in_right)?,
+            &Self::not_all_null(combined_batch, 0, right_offset)?,
+        )?;
         let in_both = arrow::compute::and(&in_left, &in_right)?;
         let left_only = arrow::compute::and(&in_left, &arrow::compute::not(&in_right)?)?;
         let right_only = arrow::compute::and(&arrow::compute::not(&in_left)?, &in_right)?;
@@ -760,14 +1102,13 @@ impl Merger {
         debug_assert_eq!(num_fields % 2, 1);
         let row_id_col = num_fields - 1;
         let right_offset = num_fields / 2;
-        let num_keys = self.params.on.len();
 
         let left_cols = Vec::from_iter(0..right_offset);
         let right_cols_with_id = Vec::from_iter(right_offset..num_fields);
 
         let mut batches = Vec::with_capacity(2);
         let (left_only, in_both, right_only) =
-            self.extract_selections(&batch, right_offset, num_keys)?;
+            self.extract_selections(&batch, right_offset, row_id_col)?;
 
         // There is no contention on this mutex.  We're only using it to bypass the rust
         // borrow checker (the stream needs to be `sync` since it crosses an await point)
@@ -795,14 +1136,45 @@ impl Merger {
                 }
             }
 
-            merge_statistics.num_updated_rows = matched.num_rows() as u64;
+            merge_statistics.num_updated_rows += matched.num_rows() as u64;
 
             // If the filter eliminated all rows then its important we don't try and write
             // the batch at all.  Writing an empty batch currently panics
             if matched.num_rows() > 0 {
                 let row_ids = matched.column(row_id_col).as_primitive::<UInt64Type>();
                 deleted_row_ids.extend(row_ids.values());
-                let matched = matched.project(&left_cols)?;
+                let matched = match &self.params.when_matched {
+                    // Columns that are not updated are carried over from the target side of
+                    // the join as-is
+                    WhenMatched::UpdateColumns { columns, .. } => {
+                        let joined_schema = matched.schema();
+                        let target_fields = &joined_schema.fields()[right_offset..row_id_col];
+                        let cols = self
+                            .schema
+                            .fields()
+                            .iter()
+                            .enumerate()
+                            .map(|(idx, field)| {
+                                if columns.contains(field.name()) {
+                                    Ok(idx)
+                                } else {
+                                    target_fields
+                                        .iter()
+                                        .position(|f| f.name() == field.name())
+                                        .map(|pos| right_offset + pos)
+                                        .ok_or_else(|| {
+                                            DataFusionError::Internal(format!(
+                                                "Column {} is missing from the target data",
+                                                field.name()
+                                            ))
+                                        })
+                                }
+                            })
+                            .collect::<datafusion::common::Result<Vec<_>>>()?;
+                        matched.project(&cols)?
+                    }
+                    _ => matched.project(&left_cols)?,
+                };
                 // The payload columns of an outer join are always nullable.  We need to restore
                 // non-nullable to columns that were originally non-nullable.  This should be safe
                 // since the not_matched rows should all be valid on the right_cols
@@ -824,13 +1196,13 @@ impl Merger {
                 Vec::from_iter(not_matched.columns().iter().cloned()),
             )?;
 
-            merge_statistics.num_inserted_rows = not_matched.num_rows() as u64;
+            merge_statistics.num_inserted_rows += not_matched.num_rows() as u64;
             batches.push(Ok(not_matched));
         }
         match self.params.delete_not_matched_by_source {
             WhenNotMatchedBySource::Delete => {
                 let unmatched = arrow::compute::filter(batch.column(row_id_col), &right_only)?;
-                merge_statistics.num_deleted_rows = unmatched.len() as u64;
+                merge_statistics.num_deleted_rows += unmatched.len() as u64;
                 let row_ids = unmatched.as_primitive::<UInt64Type>();
                 deleted_row_ids.extend(row_ids.values());
             }
@@ -847,13 +1219,13 @@ impl Merger {
                             mask.as_boolean(),
                         )?;
                         let row_ids = row_ids.as_primitive::<UInt64Type>();
-                        merge_statistics.num_deleted_rows = row_ids.len() as u64;
+                        merge_statistics.num_deleted_rows += row_ids.len() as u64;
                         deleted_row_ids.extend(row_ids.values());
                     }
                     ColumnarValue::Scalar(scalar) => {
                         if let ScalarValue::Boolean(Some(true)) = scalar {
                             let row_ids = unmatched.column(row_id_col).as_primitive::<UInt64Type>();
-                            merge_statistics.num_deleted_rows = row_ids.len() as u64;
+                            merge_statistics.num_deleted_rows += row_ids.len() as u64;
                             deleted_row_ids.extend(row_ids.values());
                         }
                     }
@@ -870,6 +1242,7 @@ impl Merger {
 mod tests {
 
     use arrow_array::{types::UInt32Type, RecordBatchIterator, StringArray, UInt32Array};
+    use arrow_schema::Fields;
     use arrow_select::concat::concat_batches;
     use datafusion::common::Column;
     use lance_datagen::{array, BatchCount, RowCount, Seed};
@@ -1198,7 +1571,7 @@ mod tests {
 
         // Sample 2048 random indices and then paste on a column of 9999999's
         let some_indices = ds
-            .sample(2048, &(&just_index_col).try_into().unwrap())
+            .sample(2048, &(&just_index_col).try_into().unwrap(), None)
             .await
             .unwrap();
         let some_indices = some_indices.column(0).clone();
@@ -1280,4 +1653,467 @@ mod tests {
 
         assert_eq!(ds.count_rows(None).await.unwrap(), 2048);
     }
+
+    #[tokio::test]
+    async fn test_merge_insert_update_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::UInt32, false),
+            Field::new("value", DataType::UInt32, false),
+            Field::new("updated_at", DataType::UInt32, false),
+            Field::new("payload", DataType::Utf8, true),
+        ]));
+        let make_batch = |keys: Vec<u32>, value: u32, updated_at: Vec<u32>, prefix: &str| {
+            let payload = keys
+                .iter()
+                .map(|k| format!("{}{}", prefix, k))
+                .collect::<Vec<_>>();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt32Array::from(keys.clone())),
+                    Arc::new(UInt32Array::from(vec![value; keys.len()])),
+                    Arc::new(UInt32Array::from(updated_at)),
+                    Arc::new(StringArray::from(payload)),
+                ],
+            )
+            .unwrap()
+        };
+        let read_sorted = |ds: Arc<Dataset>| async move {
+            let batches = ds
+                .scan()
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let mut rows = Vec::new();
+            for batch in batches {
+                for i in 0..batch.num_rows() {
+                    rows.push((
+                        batch.column(0).as_primitive::<UInt32Type>().value(i),
+                        batch.column(1).as_primitive::<UInt32Type>().value(i),
+                        batch.column(2).as_primitive::<UInt32Type>().value(i),
+                        batch.column(3).as_string::<i32>().value(i).to_string(),
+                    ));
+                }
+            }
+            rows.sort();
+            rows
+        };
+        async fn restored(ds: &Dataset) -> Arc<Dataset> {
+            let mut ds = ds.clone();
+            ds.restore().await.unwrap();
+            Arc::new(ds)
+        }
+        let row = |key: u32, value: u32, updated_at: u32, payload: &str| {
+            (key, value, updated_at, payload.to_string())
+        };
+
+        for indexed in [false, true] {
+            let test_dir = tempdir().unwrap();
+            let test_uri = test_dir.path().to_str().unwrap();
+            let batch = make_batch((1..=6).collect(), 1, vec![10; 6], "old");
+            let batches = RecordBatchIterator::new([Ok(batch)], schema.clone());
+            let mut ds = Dataset::write(batches, test_uri, None).await.unwrap();
+            // Key 2 is deleted from the target and so should be treated as a new row
+            ds.delete("key = 2").await.unwrap();
+            if indexed {
+                ds.create_index(
+                    &["key"],
+                    IndexType::Scalar,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+            let ds = Arc::new(ds);
+
+            // Key 3 has an older timestamp in the source and should not be updated
+            let new_batch = make_batch(vec![2, 3, 4, 5, 7], 2, vec![20, 5, 20, 20, 20], "new");
+
+            let job = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
+                .unwrap()
+                .when_matched_update(
+                    &["value", "updated_at"],
+                    Some("source.updated_at > target.updated_at"),
+                )
+                .unwrap()
+                .try_build()
+                .unwrap();
+            let reader = Box::new(RecordBatchIterator::new(
+                [Ok(new_batch.clone())],
+                schema.clone(),
+            ));
+            let (updated, stats) = job.execute_reader(reader).await.unwrap();
+            assert_eq!(stats.num_inserted_rows, 2);
+            assert_eq!(stats.num_updated_rows, 2);
+            assert_eq!(stats.num_deleted_rows, 0);
+            // The payload column is not updated and keeps the target values
+            assert_eq!(
+                read_sorted(updated).await,
+                vec![
+                    row(1, 1, 10, "old1"),
+                    row(2, 2, 20, "new2"),
+                    row(3, 1, 10, "old3"),
+                    row(4, 2, 20, "old4"),
+                    row(5, 2, 20, "old5"),
+                    row(6, 1, 10, "old6"),
+                    row(7, 2, 20, "new7"),
+                ]
+            );
+
+            // The condition filters out every match.  Each job runs against the original data
+            let job = MergeInsertBuilder::try_new(restored(&ds).await, vec!["key".to_string()])
+                .unwrap()
+                .when_matched_update(&["payload"], Some("target.updated_at > 100"))
+                .unwrap()
+                .when_not_matched(WhenNotMatched::DoNothing)
+                .when_not_matched_by_source_delete(None)
+                .unwrap()
+                .try_build()
+                .unwrap();
+            let reader = Box::new(RecordBatchIterator::new(
+                [Ok(new_batch.clone())],
+                schema.clone(),
+            ));
+            let (updated, stats) = job.execute_reader(reader).await.unwrap();
+            assert_eq!(stats.num_inserted_rows, 0);
+            assert_eq!(stats.num_updated_rows, 0);
+            assert_eq!(stats.num_deleted_rows, 2);
+            assert_eq!(
+                read_sorted(updated).await,
+                vec![
+                    row(3, 1, 10, "old3"),
+                    row(4, 1, 10, "old4"),
+                    row(5, 1, 10, "old5"),
+                ]
+            );
+
+            // Unconditional column update with a conditional delete
+            let job = MergeInsertBuilder::try_new(restored(&ds).await, vec!["key".to_string()])
+                .unwrap()
+                .when_matched_update(&["payload"], None)
+                .unwrap()
+                .when_not_matched_by_source_delete(Some("key > 5"))
+                .unwrap()
+                .try_build()
+                .unwrap();
+            let reader = Box::new(RecordBatchIterator::new([Ok(new_batch)], schema.clone()));
+            let (updated, stats) = job.execute_reader(reader).await.unwrap();
+            assert_eq!(stats.num_inserted_rows, 2);
+            assert_eq!(stats.num_updated_rows, 3);
+            assert_eq!(stats.num_deleted_rows, 1);
+            assert_eq!(
+                read_sorted(updated).await,
+                vec![
+                    row(1, 1, 10, "old1"),
+                    row(2, 2, 20, "new2"),
+                    row(3, 1, 10, "new3"),
+                    row(4, 1, 10, "new4"),
+                    row(5, 1, 10, "new5"),
+                    row(7, 2, 20, "new7"),
+                ]
+            );
+
+            // Invalid columns and conditions are rejected by the builder
+            let mut builder =
+                MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()]).unwrap();
+            assert!(builder.when_matched_update(&["missing"], None).is_err());
+            assert!(builder.when_matched_update(&[], None).is_err());
+            assert!(builder
+                .when_matched_update(&["value"], Some("source.missing > 1"))
+                .is_err());
+            assert!(builder
+                .when_not_matched_by_source_delete(Some("missing > 1"))
+                .is_err());
+        }
+    }
+
+    type CompositeRow = (Option<u32>, Option<u32>, u32);
+
+    fn composite_batch(rows: &[CompositeRow]) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("tenant", DataType::UInt32, true),
+            Field::new("entity", DataType::UInt32, true),
+            Field::new("value", DataType::UInt32, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(UInt32Array::from_iter(rows.iter().map(|r| r.0))),
+                Arc::new(UInt32Array::from_iter(rows.iter().map(|r| r.1))),
+                Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.2))),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn composite_dataset(test_uri: &str, rows: &[CompositeRow]) -> Arc<Dataset> {
+        let batch = composite_batch(rows);
+        let schema = batch.schema();
+        let reader = RecordBatchIterator::new([Ok(batch)], schema);
+        // The legacy format can't store null keys
+        let params = WriteParams {
+            use_legacy_format: false,
+            ..Default::default()
+        };
+        Arc::new(
+            Dataset::write(reader, test_uri, Some(params))
+                .await
+                .unwrap(),
+        )
+    }
+
+    async fn merge_composite(
+        builder: &mut MergeInsertBuilder,
+        rows: &[CompositeRow],
+    ) -> Result<(Arc<Dataset>, MergeStats)> {
+        let batch = composite_batch(rows);
+        let schema = batch.schema();
+        let reader = Box::new(RecordBatchIterator::new([Ok(batch)], schema));
+        builder.try_build()?.execute_reader(reader).await
+    }
+
+    async fn read_composite(dataset: &Dataset) -> Vec<CompositeRow> {
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        let tenants = batch.column(0).as_primitive::<UInt32Type>();
+        let entities = batch.column(1).as_primitive::<UInt32Type>();
+        let values = batch.column(2).as_primitive::<UInt32Type>();
+        let mut rows = tenants
+            .iter()
+            .zip(entities.iter())
+            .zip(values.values().iter())
+            .map(|((tenant, entity), value)| (tenant, entity, *value))
+            .collect::<Vec<_>>();
+        rows.sort();
+        rows
+    }
+
+    fn composite_keys() -> Vec<String> {
+        vec!["tenant".to_string(), "entity".to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_composite_key() {
+        for indexed in [false, true] {
+            let test_dir = tempdir().unwrap();
+            let test_uri = test_dir.path().to_str().unwrap();
+
+            let target = (0..2)
+                .flat_map(|tenant| (0..4).map(move |entity| (Some(tenant), Some(entity), 1)))
+                .collect::<Vec<_>>();
+            let mut ds = composite_dataset(test_uri, &target).await;
+            if indexed {
+                let mut dataset = (*ds).clone();
+                dataset
+                    .create_index(
+                        &["tenant"],
+                        IndexType::Scalar,
+                        None,
+                        &ScalarIndexParams::default(),
+                        false,
+                    )
+                    .await
+                    .unwrap();
+                ds = Arc::new(dataset);
+            }
+
+            // (0, 9) and (2, 1) share part of their key with existing rows but are new rows.
+            // (0, 1) is split across two source batches from (0, 9) to make sure the leading
+            // column lookup doesn't produce duplicate matches
+            let source = [
+                (Some(0), Some(1), 2),
+                (Some(1), Some(3), 2),
+                (Some(0), Some(9), 2),
+                (Some(2), Some(1), 2),
+            ];
+            let schema = composite_batch(&source).schema();
+            let reader = Box::new(RecordBatchIterator::new(
+                [
+                    Ok(composite_batch(&source[..2])),
+                    Ok(composite_batch(&source[2..])),
+                ],
+                schema,
+            ));
+            let (merged, stats) = MergeInsertBuilder::try_new(ds.clone(), composite_keys())
+                .unwrap()
+                .when_matched(WhenMatched::UpdateAll)
+                .try_build()
+                .unwrap()
+                .execute_reader(reader)
+                .await
+                .unwrap();
+            assert_eq!(stats.num_inserted_rows, 2);
+            assert_eq!(stats.num_updated_rows, 2);
+            assert_eq!(stats.num_deleted_rows, 0);
+
+            let mut expected = target.clone();
+            expected.retain(|r| *r != (Some(0), Some(1), 1) && *r != (Some(1), Some(3), 1));
+            expected.extend(source);
+            expected.sort();
+            assert_eq!(read_composite(&merged).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_null_keys() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let ds = composite_dataset(test_uri, &[(None, Some(1), 1), (Some(0), Some(1), 1)]).await;
+
+        // By default a null in any key column never matches
+        let (merged, stats) = merge_composite(
+            MergeInsertBuilder::try_new(ds.clone(), composite_keys())
+                .unwrap()
+                .when_matched(WhenMatched::UpdateAll),
+            &[(None, Some(1), 2), (Some(0), Some(1), 2)],
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.num_inserted_rows, 1);
+        assert_eq!(stats.num_updated_rows, 1);
+        assert_eq!(
+            read_composite(&merged).await,
+            vec![
+                (None, Some(1), 1),
+                (None, Some(1), 2),
+                (Some(0), Some(1), 2)
+            ]
+        );
+
+        // Null keys can be configured to match each other
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let ds = composite_dataset(test_uri, &[(None, Some(1), 1), (Some(0), Some(1), 1)]).await;
+        let (merged, stats) = merge_composite(
+            MergeInsertBuilder::try_new(ds.clone(), composite_keys())
+                .unwrap()
+                .when_matched(WhenMatched::UpdateAll)
+                .null_keys_match(true),
+            &[(None, Some(1), 2)],
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.num_inserted_rows, 0);
+        assert_eq!(stats.num_updated_rows, 1);
+        assert_eq!(
+            read_composite(&merged).await,
+            vec![(None, Some(1), 2), (Some(0), Some(1), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_duplicate_source_keys() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let ds = composite_dataset(test_uri, &[(Some(0), Some(1), 1)]).await;
+
+        let source = [
+            (Some(0), Some(1), 2),
+            (Some(0), Some(2), 2),
+            (Some(0), Some(1), 3),
+        ];
+
+        // Duplicate keys in the source fail by default
+        let err = merge_composite(
+            MergeInsertBuilder::try_new(ds.clone(), composite_keys())
+                .unwrap()
+                .when_matched(WhenMatched::UpdateAll),
+            &source,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("tenant=0, entity=1"),
+            "unexpected error: {}",
+            err
+        );
+
+        // Or the last row with a given key wins
+        let (merged, stats) = merge_composite(
+            MergeInsertBuilder::try_new(ds.clone(), composite_keys())
+                .unwrap()
+                .when_matched(WhenMatched::UpdateAll)
+                .when_duplicate_source_keys(DuplicateSourceKeys::LastWriteWins),
+            &source,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.num_inserted_rows, 1);
+        assert_eq!(stats.num_updated_rows, 1);
+        assert_eq!(
+            read_composite(&merged).await,
+            vec![(Some(0), Some(1), 3), (Some(0), Some(2), 2)]
+        );
+
+        // Rows with null keys never match and so are not duplicates of each other
+        let (merged, stats) = merge_composite(
+            &mut MergeInsertBuilder::try_new(merged.clone(), composite_keys()).unwrap(),
+            &[(None, Some(1), 4), (None, Some(1), 5)],
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.num_inserted_rows, 2);
+        assert_eq!(read_composite(&merged).await.len(), 4);
+
+        // Unless null keys are configured to match
+        let result = merge_composite(
+            MergeInsertBuilder::try_new(merged.clone(), composite_keys())
+                .unwrap()
+                .null_keys_match(true),
+            &[(None, Some(7), 4), (None, Some(7), 5)],
+        )
+        .await;
+        assert!(result.is_err());
+
+        // Keys must be columns of the dataset and can't be repeated
+        assert!(MergeInsertBuilder::try_new(ds.clone(), vec!["missing".to_string()]).is_err());
+        assert!(MergeInsertBuilder::try_new(
+            ds.clone(),
+            vec!["tenant".to_string(), "tenant".to_string()]
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_nested_columns() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let inner = Fields::from(vec![Field::new("a", DataType::UInt32, false)]);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::UInt32, false),
+            Field::new("s", DataType::Struct(inner.clone()), false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from(vec![1, 2])),
+                Arc::new(StructArray::new(
+                    inner,
+                    vec![Arc::new(UInt32Array::from(vec![10, 20]))],
+                    None,
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new([Ok(batch)], schema);
+        let ds = Arc::new(Dataset::write(reader, test_uri, None).await.unwrap());
+
+        // Only whole top-level columns can be updated
+        let mut builder = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()]).unwrap();
+        assert!(builder.when_matched_update(&["s"], None).is_ok());
+        assert!(matches!(
+            builder.when_matched_update(&["s.a"], None),
+            Err(Error::InvalidInput { .. })
+        ));
+
+        // Keys must be top-level columns as well
+        assert!(matches!(
+            MergeInsertBuilder::try_new(ds.clone(), vec!["s.a".to_string()]),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
 }
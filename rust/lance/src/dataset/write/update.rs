@@ -20,6 +20,7 @@ use lance_arrow::RecordBatchExt;
 use lance_core::error::{box_error, InvalidInputSnafu};
 use lance_datafusion::expr::safe_coerce_scalar;
 use lance_table::format::Fragment;
+use lance_table::io::commit::CommitConfig;
 use roaring::RoaringTreemap;
 use snafu::{location, Location, ResultExt};
 
@@ -52,6 +53,8 @@ pub struct UpdateBuilder {
     condition: Option<Expr>,
     /// The updates to apply to matching rows.
     updates: HashMap<String, Expr>,
+    /// The number of times to retry the commit if a concurrent writer commits first.
+    commit_retries: u32,
 }
 
 impl UpdateBuilder {
@@ -60,6 +63,7 @@ impl UpdateBuilder {
             dataset,
             condition: None,
             updates: HashMap::new(),
+            commit_retries: CommitConfig::default().num_retries,
         }
     }
 
@@ -155,6 +159,14 @@ impl UpdateBuilder {
         Ok(self)
     }
 
+    /// Set the number of times to retry the commit if a concurrent writer commits first
+    ///
+    /// See [`crate::dataset::WriteParams::commit_retries`]
+    pub fn commit_retries(mut self, commit_retries: u32) -> Self {
+        self.commit_retries = commit_retries;
+        self
+    }
+
     // TODO: set write params
     // pub fn with_write_params(mut self, params: WriteParams) -> Self { ... }
 
@@ -178,6 +190,7 @@ impl UpdateBuilder {
             dataset: self.dataset,
             condition: self.condition,
             updates,
+            commit_retries: self.commit_retries,
         })
     }
 }
@@ -189,6 +202,7 @@ pub struct UpdateJob {
     dataset: Arc<Dataset>,
     condition: Option<Expr>,
     updates: Arc<HashMap<String, Arc<dyn PhysicalExpr>>>,
+    commit_retries: u32,
 }
 
 impl UpdateJob {
@@ -333,7 +347,9 @@ impl UpdateJob {
             self.dataset.commit_handler.as_ref(),
             &transaction,
             &Default::default(),
-            &Default::default(),
+            &CommitConfig {
+                num_retries: self.commit_retries,
+            },
         )
         .await?;
 
@@ -468,6 +484,34 @@ mod tests {
         assert_eq!(dataset.get_fragments().len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_update_commit_retries() {
+        let (dataset, _test_dir) = make_test_dataset().await;
+
+        // Without any attempts the update is never committed
+        let result = UpdateBuilder::new(dataset.clone())
+            .set("name", "'bar'")
+            .unwrap()
+            .commit_retries(0)
+            .build()
+            .unwrap()
+            .execute()
+            .await;
+        assert!(matches!(result, Err(Error::CommitConflict { .. })));
+        assert_eq!(dataset.latest_version_id().await.unwrap(), 1);
+
+        let dataset = UpdateBuilder::new(dataset)
+            .set("name", "'bar'")
+            .unwrap()
+            .commit_retries(1)
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 2);
+    }
+
     #[tokio::test]
     async fn test_update_conditional() {
         let (dataset, _test_dir) = make_test_dataset().await;
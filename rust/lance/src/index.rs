@@ -225,8 +225,29 @@ impl DatasetIndexExt for Dataset {
                         location: location!(),
                     })?;
 
-                build_vector_index(self, column, &index_name, &index_id.to_string(), vec_params)
-                    .await?;
+                let monitor = &vec_params.progress_monitor;
+                monitor.check_cancelled()?;
+                let result = build_vector_index(
+                    self,
+                    column,
+                    &index_name,
+                    &index_id.to_string(),
+                    vec_params,
+                )
+                .await
+                .and_then(|_| monitor.check_cancelled());
+                if let Err(err) = result {
+                    // Best effort, the files of an index that isn't committed are never read
+                    let index_dir = self.indices_dir().child(index_id.to_string());
+                    if let Err(cleanup_err) = self.object_store.remove_dir_all(index_dir).await {
+                        log::warn!(
+                            "Failed to clean up the files of index {}: {}",
+                            index_id,
+                            cleanup_err
+                        );
+                    }
+                    return Err(err);
+                }
             }
             // Can't use if let Some(...) here because it's not stable yet.
             // TODO: fix after https://github.com/rust-lang/rust/issues/51114
@@ -622,6 +643,17 @@ impl DatasetIndexInternalExt for Dataset {
                         }),
                     },
 
+                    "FLAT_SQ" => {
+                        let ivf = IVFIndex::<FlatIndex, ScalarQuantizer>::try_new(
+                            self.object_store.clone(),
+                            self.indices_dir(),
+                            uuid.to_owned(),
+                            Arc::downgrade(&self.session),
+                        )
+                        .await?;
+                        Ok(Arc::new(ivf) as Arc<dyn VectorIndex>)
+                    }
+
                     "HNSW" => {
                         let ivf = IVFIndex::<HNSW, ScalarQuantizer>::try_new(
                             self.object_store.clone(),
@@ -898,6 +930,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 0, // Just create index for delta
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -914,6 +947,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 2,
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -998,6 +1032,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 0, // Just create index for delta
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -1013,6 +1048,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 2,
+                ..Default::default()
             })
             .await
             .unwrap();
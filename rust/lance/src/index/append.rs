@@ -7,12 +7,13 @@ use lance_core::{Error, Result};
 use lance_index::optimize::OptimizeOptions;
 use lance_index::scalar::lance_format::LanceIndexStore;
 use lance_index::IndexType;
-use lance_table::format::Index as IndexMetadata;
+use lance_table::format::{Fragment, Index as IndexMetadata};
 use roaring::RoaringBitmap;
 use snafu::{location, Location};
 use uuid::Uuid;
 
-use super::vector::ivf::optimize_vector_indices;
+use super::vector::build_vector_index;
+use super::vector::ivf::{optimize_vector_indices, retrain_params};
 use super::DatasetIndexInternalExt;
 use crate::dataset::index::LanceIndexStoreExt;
 use crate::dataset::scanner::ColumnOrdering;
@@ -102,6 +103,24 @@ pub async fn merge_indices<'a>(
 
             Ok((new_uuid, 1))
         }
+        IndexType::Vector
+            if options.retrain_threshold.is_some_and(|threshold| {
+                delta_fraction(&dataset, &frag_bitmap, &unindexed) > threshold
+            }) =>
+        {
+            // Train a new index over all of the data, replacing every delta
+            let new_uuid = Uuid::new_v4();
+            let params = retrain_params(&indices[0])?;
+            build_vector_index(
+                dataset.as_ref(),
+                &column.name,
+                &old_indices[0].name,
+                &new_uuid.to_string(),
+                &params,
+            )
+            .await?;
+            Ok((new_uuid, old_indices.len()))
+        }
         IndexType::Vector => {
             let new_data_stream = if unindexed.is_empty() {
                 None
@@ -132,6 +151,34 @@ pub async fn merge_indices<'a>(
     )))
 }
 
+// The number of unindexed rows as a fraction of the rows covered by the index
+//
+// `covered` holds the ids of the fragments covered by the index once the unindexed
+// fragments are added to it.
+fn delta_fraction(dataset: &Dataset, covered: &RoaringBitmap, unindexed: &[Fragment]) -> f64 {
+    // Fragments written by old versions may not record their number of rows, these are
+    // not counted
+    let num_unindexed = unindexed
+        .iter()
+        .filter_map(|frag| frag.physical_rows)
+        .sum::<usize>();
+    let num_indexed = dataset
+        .fragments()
+        .iter()
+        .filter(|frag| covered.contains(frag.id as u32))
+        .filter_map(|frag| frag.physical_rows)
+        .sum::<usize>()
+        - num_unindexed;
+    if num_indexed == 0 {
+        return if num_unindexed == 0 {
+            0.0
+        } else {
+            f64::INFINITY
+        };
+    }
+    num_unindexed as f64 / num_indexed as f64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,7 +190,10 @@ mod tests {
     use futures::{stream, StreamExt, TryStreamExt};
     use lance_arrow::FixedSizeListArrayExt;
     use lance_index::{
-        vector::{ivf::IvfBuildParams, pq::PQBuildParams},
+        vector::{
+            hnsw::builder::HnswBuildParams, ivf::IvfBuildParams, pq::PQBuildParams,
+            sq::builder::SQBuildParams,
+        },
         DatasetIndexExt,
     };
     use lance_linalg::distance::MetricType;
@@ -152,7 +202,7 @@ mod tests {
 
     use crate::dataset::builder::DatasetBuilder;
     use crate::index::vector::ivf::IVFIndex;
-    use crate::index::vector::{pq::PQIndex, VectorIndexParams};
+    use crate::index::vector::{pq::PQIndex, StageParams, VectorIndexParams};
 
     #[tokio::test]
     async fn test_append_index() {
@@ -353,6 +403,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 0,
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -377,4 +428,233 @@ mod tests {
         id_arr.sort();
         assert_eq!(id_arr, vec![0, 1000]);
     }
+
+    #[tokio::test]
+    async fn test_retrain_index() {
+        const DIM: usize = 64;
+        const IVF_PARTITIONS: usize = 2;
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(
+                    Arc::new(Field::new("item", DataType::Float32, true)),
+                    DIM as i32,
+                ),
+                true,
+            ),
+            Field::new("id", DataType::UInt32, false),
+        ]));
+        let make_batches = |ids: std::ops::Range<u32>| {
+            let vectors = generate_random_array(ids.len() * DIM);
+            let array =
+                Arc::new(FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap());
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![array, Arc::new(UInt32Array::from_iter_values(ids))],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let index_stats = |dataset: &Dataset| {
+            let dataset = dataset.clone();
+            async move {
+                serde_json::from_str::<serde_json::Value>(
+                    &dataset.index_statistics("vector_idx").await.unwrap(),
+                )
+                .unwrap()
+            }
+        };
+        let options = OptimizeOptions {
+            num_indices_to_merge: 0,
+            retrain_threshold: Some(0.5),
+        };
+
+        let mut dataset = Dataset::write(make_batches(0..500), test_uri, None)
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["vector"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::with_ivf_pq_params(
+                    MetricType::L2,
+                    IvfBuildParams::new(IVF_PARTITIONS),
+                    PQBuildParams {
+                        num_sub_vectors: 2,
+                        ..Default::default()
+                    },
+                ),
+                true,
+            )
+            .await
+            .unwrap();
+
+        // A little new data is added to a delta index
+        dataset.append(make_batches(500..600), None).await.unwrap();
+        dataset.optimize_indices(&options).await.unwrap();
+        let stats = index_stats(&dataset).await;
+        assert_eq!(stats["num_indices"], 2);
+        assert_eq!(stats["num_unindexed_fragments"], 0);
+
+        // Lots of new data retrains the index, which replaces the deltas
+        dataset.append(make_batches(600..2000), None).await.unwrap();
+        dataset.delete("id = 1500").await.unwrap();
+        dataset.optimize_indices(&options).await.unwrap();
+        let stats = index_stats(&dataset).await;
+        assert_eq!(stats["num_indices"], 1);
+        assert_eq!(stats["num_indexed_fragments"], 3);
+        assert_eq!(stats["num_unindexed_fragments"], 0);
+
+        // The retrained index finds the new rows, but not the deleted ones
+        let dataset = DatasetBuilder::from_uri(test_uri).load().await.unwrap();
+        let vectors = dataset
+            .scan()
+            .project(&["vector", "id"])
+            .unwrap()
+            .filter("id = 1000 OR id = 1500")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(vectors.num_rows(), 1);
+        let query = vectors["vector"].as_fixed_size_list().value(0);
+        let results = dataset
+            .scan()
+            .project(&["id"])
+            .unwrap()
+            .nearest("vector", query.as_primitive(), 10)
+            .unwrap()
+            .nprobs(IVF_PARTITIONS)
+            .try_into_batch()
+            .await
+            .unwrap();
+        let ids = results["id"].as_primitive::<UInt32Type>().values();
+        assert!(ids.contains(&1000));
+        assert!(!ids.contains(&1500));
+
+        // The retrained index is about as good as an index built from scratch
+        let retrained_recall = recall(&dataset).await;
+        let mut dataset = dataset;
+        dataset
+            .create_index(
+                &["vector"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::with_ivf_pq_params(
+                    MetricType::L2,
+                    IvfBuildParams::new(IVF_PARTITIONS),
+                    PQBuildParams {
+                        num_sub_vectors: 2,
+                        ..Default::default()
+                    },
+                ),
+                true,
+            )
+            .await
+            .unwrap();
+        let rebuilt_recall = recall(&dataset).await;
+        assert!(
+            retrained_recall >= rebuilt_recall - 0.1,
+            "retrained recall {} rebuilt recall {}",
+            retrained_recall,
+            rebuilt_recall
+        );
+    }
+
+    // The mean recall@10 of the index for some of the vectors of the dataset
+    async fn recall(dataset: &Dataset) -> f32 {
+        let queries = dataset
+            .scan()
+            .project(&["vector"])
+            .unwrap()
+            .filter("id % 100 = 0")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        let queries = queries["vector"].as_fixed_size_list();
+        let mut num_found = 0;
+        for query in queries.iter() {
+            let query = query.unwrap();
+            let search = |use_index| {
+                let query = query.clone();
+                async move {
+                    let results = dataset
+                        .scan()
+                        .project(&["id"])
+                        .unwrap()
+                        .nearest("vector", query.as_primitive(), 10)
+                        .unwrap()
+                        .nprobs(2)
+                        .use_index(use_index)
+                        .try_into_batch()
+                        .await
+                        .unwrap();
+                    results["id"].as_primitive::<UInt32Type>().values().to_vec()
+                }
+            };
+            let expected = search(false).await;
+            let actual = search(true).await;
+            num_found += actual.iter().filter(|id| expected.contains(id)).count();
+        }
+        num_found as f32 / (queries.len() * 10) as f32
+    }
+
+    #[tokio::test]
+    async fn test_retrain_keeps_hnsw_params() {
+        const DIM: usize = 32;
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let vectors = generate_random_array(500 * DIM);
+        let array = Arc::new(FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap());
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            array.data_type().clone(),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![array]).unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        let hnsw_params = HnswBuildParams::default().num_edges(12).ef_construction(80);
+        dataset
+            .create_index(
+                &["vector"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::with_ivf_hnsw_sq_params(
+                    MetricType::L2,
+                    IvfBuildParams::new(2),
+                    hnsw_params,
+                    SQBuildParams::default(),
+                ),
+                true,
+            )
+            .await
+            .unwrap();
+
+        let index_meta = dataset.load_indices().await.unwrap()[0].clone();
+        let index = dataset
+            .open_generic_index("vector", &index_meta.uuid.to_string())
+            .await
+            .unwrap();
+        let params = retrain_params(&index).unwrap();
+        let retrained_hnsw_params = params
+            .stages
+            .iter()
+            .find_map(|stage| match stage {
+                StageParams::Hnsw(params) => Some(params),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(retrained_hnsw_params.m, 12);
+        assert_eq!(retrained_hnsw_params.ef_construction, 80);
+    }
 }
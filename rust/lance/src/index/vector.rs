@@ -46,6 +46,7 @@ use uuid::Uuid;
 use self::{ivf::*, pq::PQIndex};
 
 use super::{pb, DatasetIndexInternalExt, IndexParams};
+use crate::dataset::progress::ProgressMonitor;
 use crate::{dataset::Dataset, index::pb::vector_index_stage::Stage, Error, Result};
 pub use traits::*;
 
@@ -67,6 +68,13 @@ pub struct VectorIndexParams {
 
     /// Vector distance metrics type.
     pub metric_type: MetricType,
+
+    /// Reports the progress of the build and can cancel it
+    ///
+    /// The cancellation token is checked as the data is read and between the partitions
+    /// of the index.  A cancelled build fails with [`Error::Stop`] and removes the index
+    /// files it wrote.
+    pub progress_monitor: ProgressMonitor,
 }
 
 impl VectorIndexParams {
@@ -76,6 +84,7 @@ impl VectorIndexParams {
         Self {
             stages,
             metric_type,
+            progress_monitor: ProgressMonitor::default(),
         }
     }
 
@@ -108,6 +117,7 @@ impl VectorIndexParams {
         Self {
             stages,
             metric_type,
+            progress_monitor: ProgressMonitor::default(),
         }
     }
 
@@ -121,6 +131,22 @@ impl VectorIndexParams {
         Self {
             stages,
             metric_type,
+            progress_monitor: ProgressMonitor::default(),
+        }
+    }
+
+    /// Create index parameters with `IVF` and `SQ` parameters, respectively.
+    /// This is used for `IVF_SQ` index.
+    pub fn with_ivf_sq_params(
+        metric_type: MetricType,
+        ivf: IvfBuildParams,
+        sq: SQBuildParams,
+    ) -> Self {
+        let stages = vec![StageParams::Ivf(ivf), StageParams::SQ(sq)];
+        Self {
+            stages,
+            metric_type,
+            progress_monitor: ProgressMonitor::default(),
         }
     }
 
@@ -140,6 +166,7 @@ impl VectorIndexParams {
         Self {
             stages,
             metric_type,
+            progress_monitor: ProgressMonitor::default(),
         }
     }
 
@@ -159,6 +186,7 @@ impl VectorIndexParams {
         Self {
             stages,
             metric_type,
+            progress_monitor: ProgressMonitor::default(),
         }
     }
 }
@@ -195,6 +223,14 @@ fn is_ivf_pq(stages: &[StageParams]) -> bool {
         && matches!(&stages[len - 2], StageParams::Ivf(_))
 }
 
+fn is_ivf_sq(stages: &[StageParams]) -> bool {
+    if stages.len() != 2 {
+        return false;
+    }
+
+    matches!(&stages[0], StageParams::Ivf(_)) && matches!(&stages[1], StageParams::SQ(_))
+}
+
 fn is_ivf_hnsw(stages: &[StageParams]) -> bool {
     if stages.len() < 2 {
         return false;
@@ -242,6 +278,7 @@ pub(crate) async fn build_vector_index(
             Some(()),
             (),
         )?
+        .with_progress_monitor(params.progress_monitor.clone())
         .build()
         .await?;
     } else if is_ivf_pq(stages) {
@@ -268,8 +305,37 @@ pub(crate) async fn build_vector_index(
             params.metric_type,
             ivf_params,
             pq_params,
+            &params.progress_monitor,
         )
         .await?
+    } else if is_ivf_sq(stages) {
+        let StageParams::Ivf(ivf_params) = &stages[0] else {
+            return Err(Error::Index {
+                message: format!("Build Vector Index: invalid stages: {:?}", stages),
+                location: location!(),
+            });
+        };
+        let StageParams::SQ(sq_params) = &stages[1] else {
+            return Err(Error::Index {
+                message: format!("Build Vector Index: invalid stages: {:?}", stages),
+                location: location!(),
+            });
+        };
+
+        let shuffler = IvfShuffler::new(temp_dir_path, ivf_params.num_partitions);
+        IvfIndexBuilder::<FlatIndex, ScalarQuantizer>::new(
+            dataset.clone(),
+            column.to_owned(),
+            dataset.indices_dir().child(uuid),
+            params.metric_type,
+            Box::new(shuffler),
+            Some(ivf_params.clone()),
+            Some(sq_params.clone()),
+            (),
+        )?
+        .with_progress_monitor(params.progress_monitor.clone())
+        .build()
+        .await?;
     } else if is_ivf_hnsw(stages) {
         let len = stages.len();
         let StageParams::Ivf(ivf_params) = &stages[0] else {
@@ -299,6 +365,7 @@ pub(crate) async fn build_vector_index(
                         ivf_params,
                         hnsw_params,
                         pq_params,
+                        &params.progress_monitor,
                     )
                     .await?
                 }
@@ -313,6 +380,7 @@ pub(crate) async fn build_vector_index(
                         Some(sq_params.clone()),
                         hnsw_params.clone(),
                     )?
+                    .with_progress_monitor(params.progress_monitor.clone())
                     .build()
                     .await?;
                 }
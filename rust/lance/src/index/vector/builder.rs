@@ -11,6 +11,7 @@ use lance_arrow::RecordBatchExt;
 use lance_core::{Error, Result, ROW_ID_FIELD};
 use lance_encoding::decoder::{DecoderMiddlewareChain, FilterExpression};
 use lance_file::v2::{reader::FileReader, writer::FileWriter};
+use lance_index::vector::flat::index::FlatIndex;
 use lance_index::vector::flat::storage::FlatStorage;
 use lance_index::vector::ivf::storage::IvfModel;
 use lance_index::vector::quantizer::QuantizerBuildParams;
@@ -21,7 +22,7 @@ use lance_index::{
     pb,
     vector::{
         ivf::{storage::IVF_METADATA_KEY, IvfBuildParams},
-        quantizer::Quantization,
+        quantizer::{Quantization, QuantizationType},
         storage::{StorageBuilder, VectorStore},
         transform::Transformer,
         v3::{
@@ -45,6 +46,7 @@ use prost::Message;
 use snafu::{location, Location};
 use tempfile::{tempdir, TempDir};
 
+use crate::dataset::progress::{ProgressMonitor, ProgressStage};
 use crate::Dataset;
 
 use super::utils;
@@ -77,6 +79,8 @@ pub struct IvfIndexBuilder<S: IvfSubIndex, Q: Quantization + Clone> {
 
     // fields for merging indices
     existing_indices: Vec<Arc<dyn VectorIndex>>,
+
+    progress_monitor: ProgressMonitor,
 }
 
 impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilder<S, Q> {
@@ -110,9 +114,16 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
             shuffle_reader: None,
             partition_sizes: Vec::new(),
             existing_indices: Vec::new(),
+            progress_monitor: ProgressMonitor::default(),
         })
     }
 
+    /// Reports the progress of the build, which is cancelled if the monitor is cancelled
+    pub fn with_progress_monitor(mut self, progress_monitor: ProgressMonitor) -> Self {
+        self.progress_monitor = progress_monitor;
+        self
+    }
+
     pub fn new_incremental(
         dataset: Dataset,
         column: String,
@@ -238,6 +249,10 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
             .with_row_id()
             .try_into_stream()
             .await?;
+        let num_rows = self.dataset.count_rows(None).await? as u64;
+        let stream =
+            self.progress_monitor
+                .monitor_stream(stream, ProgressStage::IndexData, Some(num_rows));
         self.shuffle_data(Some(stream)).await?;
         Ok(())
     }
@@ -332,6 +347,7 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
 
         let mut partition_sizes = vec![(0, 0); ivf.num_partitions()];
         for (i, &partition) in partition_build_order.iter().enumerate() {
+            self.progress_monitor.check_cancelled()?;
             log::info!(
                 "building partition {}, progress {}/{}",
                 partition,
@@ -369,6 +385,11 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
 
             let num_rows = batches.iter().map(|b| b.num_rows()).sum::<usize>();
             if num_rows == 0 {
+                self.progress_monitor.report(
+                    ProgressStage::IndexPartitions,
+                    i as u64 + 1,
+                    Some(ivf.num_partitions() as u64),
+                );
                 continue;
             }
             let mut batch = arrow::compute::concat_batches(&batches[0].schema(), batches.iter())?;
@@ -392,6 +413,11 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
                 i + 1,
                 ivf.num_partitions()
             );
+            self.progress_monitor.report(
+                ProgressStage::IndexPartitions,
+                i as u64 + 1,
+                Some(ivf.num_partitions() as u64),
+            );
         }
         self.partition_sizes = partition_sizes;
         Ok(self)
@@ -418,7 +444,7 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
             for batch in storage.to_batches()? {
                 writer.write_batch(&batch).await?;
             }
-            writer.finish().await? as usize
+            writer.finish().await?.num_rows as usize
         };
 
         // build the sub index, with in-memory storage
@@ -435,7 +461,7 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
                 Default::default(),
             )?;
             writer.write_batch(&index_batch).await?;
-            writer.finish().await? as usize
+            writer.finish().await?.num_rows as usize
         };
 
         Ok((storage_len, index_len))
@@ -561,8 +587,18 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + Clone + 'static> IvfIndexBuilde
         );
 
         let index_ivf_pb = pb::Ivf::try_from(&index_ivf)?;
+        // The flat sub index can be paired with a quantizer, which decides how to
+        // open the index, so record it in the index type, e.g. `FLAT_SQ`.
+        let quantization_type = Q::quantization_type();
+        let index_type = if S::name() == FlatIndex::name()
+            && !matches!(quantization_type, QuantizationType::Flat)
+        {
+            format!("{}_{}", S::name(), quantization_type)
+        } else {
+            S::name().to_string()
+        };
         let index_metadata = IndexMetadata {
-            index_type: S::name().to_string(),
+            index_type,
             distance_type: self.distance_type.to_string(),
         };
         index_writer.add_schema_metadata(
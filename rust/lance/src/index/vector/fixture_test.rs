@@ -238,6 +238,7 @@ mod test {
                 refine_factor: None,
                 metric_type: metric,
                 use_index: true,
+                radius: None,
             };
             let idx = make_idx.clone()(expected_query_at_subindex, metric).await;
             idx.search(
@@ -13,16 +13,17 @@ use arrow_arith::numeric::sub;
 use arrow_array::{
     cast::{as_struct_array, AsArray},
     types::{Float16Type, Float32Type, Float64Type},
-    Array, FixedSizeListArray, RecordBatch, StructArray, UInt32Array,
+    Array, FixedSizeListArray, Float32Array, RecordBatch, StructArray, UInt32Array,
 };
-use arrow_ord::sort::sort_to_indices;
+use arrow_ord::{cmp::lt, sort::sort_to_indices};
 use arrow_schema::{DataType, Schema};
-use arrow_select::{concat::concat_batches, take::take};
+use arrow_select::{concat::concat_batches, filter::filter_record_batch, take::take};
 use async_trait::async_trait;
 use deepsize::DeepSizeOf;
 use futures::{
+    future::BoxFuture,
     stream::{self, StreamExt},
-    TryStreamExt,
+    FutureExt, TryStreamExt,
 };
 use io::write_hnsw_quantization_index_partitions;
 use lance_arrow::*;
@@ -46,7 +47,7 @@ use lance_index::{
         },
         pq::{PQBuildParams, ProductQuantizer},
         quantizer::{Quantization, QuantizationMetadata, Quantizer},
-        sq::ScalarQuantizer,
+        sq::{builder::SQBuildParams, ScalarQuantizer},
         Query, VectorIndex, DIST_COL,
     },
     Index, IndexMetadata, IndexType, INDEX_AUXILIARY_FILE_NAME, INDEX_METADATA_SCHEMA_KEY,
@@ -79,12 +80,13 @@ use super::{
     utils::maybe_sample_training_data,
 };
 use crate::dataset::builder::DatasetBuilder;
+use crate::dataset::progress::{ProgressMonitor, ProgressStage};
 use crate::{
     dataset::Dataset,
     index::{
         pb,
         prefilter::PreFilter,
-        vector::{ivf::io::write_pq_partitions, Transformer},
+        vector::{ivf::io::write_pq_partitions, Transformer, VectorIndexParams},
         INDEX_FILE_NAME,
     },
     session::Session,
@@ -383,6 +385,24 @@ pub(crate) async fn optimize_vector_indices_v2(
             .await?;
         }
 
+        (SubIndexType::Flat, QuantizationType::Scalar) => {
+            IvfIndexBuilder::<FlatIndex, ScalarQuantizer>::new_incremental(
+                dataset.clone(),
+                vector_column.to_owned(),
+                index_dir,
+                distance_type,
+                shuffler,
+                (),
+            )?
+            .with_ivf(ivf_model)
+            .with_quantizer(quantizer.try_into()?)
+            .with_existing_indices(indices_to_merge)
+            .shuffle_data(unindexed)
+            .await?
+            .build()
+            .await?;
+        }
+
         (SubIndexType::Hnsw, QuantizationType::Scalar) => {
             IvfIndexBuilder::<HNSW, ScalarQuantizer>::new(
                 dataset.clone(),
@@ -392,8 +412,7 @@ pub(crate) async fn optimize_vector_indices_v2(
                 shuffler,
                 None,
                 None,
-                // TODO: get the HNSW parameters from the existing indices
-                HnswBuildParams::default(),
+                hnsw_params(existing_indices[0].as_ref())?,
             )?
             .with_ivf(ivf_model)
             .with_quantizer(quantizer.try_into()?)
@@ -418,6 +437,98 @@ pub(crate) async fn optimize_vector_indices_v2(
     Ok((new_uuid, merged_num))
 }
 
+// The parameters the HNSW graphs of a v2 IVF_HNSW_SQ index were built with
+fn hnsw_params(index: &dyn VectorIndex) -> Result<HnswBuildParams> {
+    match index
+        .as_any()
+        .downcast_ref::<v2::IVFIndex<HNSW, ScalarQuantizer>>()
+    {
+        Some(ivf_index) => ivf_index.hnsw_params(),
+        None => Ok(HnswBuildParams::default()),
+    }
+}
+
+/// The parameters to retrain an index like `index` from scratch
+///
+/// The new index keeps the number of partitions, the distance type, the quantizer
+/// settings and the HNSW parameters of `index` but trains new centroids and codebooks.
+pub(crate) fn retrain_params(index: &Arc<dyn Index>) -> Result<VectorIndexParams> {
+    if let Some(ivf_index) = index.as_any().downcast_ref::<IVFIndex>() {
+        let ivf_params = IvfBuildParams::new(ivf_index.ivf.num_partitions());
+        if let Some(pq_index) = ivf_index.sub_index.as_any().downcast_ref::<PQIndex>() {
+            let pq_params = PQBuildParams {
+                num_sub_vectors: pq_index.pq.num_sub_vectors,
+                num_bits: pq_index.pq.num_bits as usize,
+                ..Default::default()
+            };
+            return Ok(VectorIndexParams::with_ivf_pq_params(
+                ivf_index.metric_type,
+                ivf_params,
+                pq_params,
+            ));
+        }
+        if let Some(hnsw_index) = ivf_index
+            .sub_index
+            .as_any()
+            .downcast_ref::<HNSWIndex<ScalarQuantizer>>()
+        {
+            let mut sq_params = SQBuildParams::default();
+            if let Quantizer::Scalar(sq) = hnsw_index.quantizer() {
+                sq_params.num_bits = sq.num_bits;
+            }
+            return Ok(VectorIndexParams::with_ivf_hnsw_sq_params(
+                ivf_index.metric_type,
+                ivf_params,
+                hnsw_index.metadata().params,
+                sq_params,
+            ));
+        }
+        return Err(Error::Index {
+            message: "retraining vector index: the sub index isn't PQ or HNSW".to_string(),
+            location: location!(),
+        });
+    }
+
+    let index = index.clone().as_vector_index()?;
+    let ivf_params = IvfBuildParams::new(index.ivf_model().num_partitions());
+    match index.sub_index_type() {
+        (SubIndexType::Flat, QuantizationType::Flat) => Ok(VectorIndexParams::ivf_flat(
+            ivf_params.num_partitions,
+            index.metric_type(),
+        )),
+        (SubIndexType::Flat, QuantizationType::Scalar) => {
+            let mut sq_params = SQBuildParams::default();
+            if let Quantizer::Scalar(sq) = index.quantizer() {
+                sq_params.num_bits = sq.num_bits;
+            }
+            Ok(VectorIndexParams::with_ivf_sq_params(
+                index.metric_type(),
+                ivf_params,
+                sq_params,
+            ))
+        }
+        (SubIndexType::Hnsw, QuantizationType::Scalar) => {
+            let mut sq_params = SQBuildParams::default();
+            if let Quantizer::Scalar(sq) = index.quantizer() {
+                sq_params.num_bits = sq.num_bits;
+            }
+            Ok(VectorIndexParams::with_ivf_hnsw_sq_params(
+                index.metric_type(),
+                ivf_params,
+                hnsw_params(index.as_ref())?,
+                sq_params,
+            ))
+        }
+        (sub_index_type, quantizer_type) => Err(Error::Index {
+            message: format!(
+                "retraining vector index: unsupported index type IVF_{}_{}",
+                sub_index_type, quantizer_type
+            ),
+            location: location!(),
+        }),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn optimize_ivf_pq_indices(
     first_idx: &IVFIndex,
@@ -476,7 +587,14 @@ async fn optimize_ivf_pq_indices(
             })
         })
         .collect::<Result<Vec<_>>>()?;
-    write_pq_partitions(&mut writer, &mut ivf_mut, shuffled, Some(&indices_to_merge)).await?;
+    write_pq_partitions(
+        &mut writer,
+        &mut ivf_mut,
+        shuffled,
+        Some(&indices_to_merge),
+        &ProgressMonitor::default(),
+    )
+    .await?;
     let metadata = IvfPQIndexMetadata {
         name: format!("_{}_idx", vector_column),
         column: vector_column.to_string(),
@@ -667,6 +785,79 @@ pub struct IvfIndexStatistics {
     centroids: Vec<Vec<f32>>,
 }
 
+/// Searches the closest `nprobes` partitions of an IVF index
+///
+/// `partition_ids` are the closest `nprobes` partitions to the query.  If `rank_partitions`
+/// is given (the search has a prefilter) and these partitions yield fewer than `limit` rows
+/// then every partition is ranked and the next closest partitions are searched, `nprobes`
+/// at a time, until `limit` rows are found or every partition has been searched.  A
+/// selective filter would otherwise return fewer than `k` results even though enough
+/// rows match.
+async fn search_partitions<'a, F>(
+    partition_ids: &[u32],
+    nprobes: usize,
+    limit: usize,
+    rank_partitions: Option<&(dyn Fn() -> Result<UInt32Array> + Sync)>,
+    search_in_partition: F,
+) -> Result<Vec<RecordBatch>>
+where
+    F: Fn(usize) -> BoxFuture<'a, Result<RecordBatch>>,
+{
+    let probe = |probes: &[u32]| {
+        let probes = probes
+            .iter()
+            .map(|part_id| *part_id as usize)
+            .collect::<Vec<_>>();
+        stream::iter(probes)
+            .map(&search_in_partition)
+            .buffer_unordered(num_cpus::get())
+            .try_collect::<Vec<_>>()
+    };
+    let mut batches = probe(partition_ids).await?;
+    let mut num_rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
+    let Some(rank_partitions) = rank_partitions.filter(|_| num_rows < limit) else {
+        return Ok(batches);
+    };
+
+    let remaining = rank_partitions()?
+        .values()
+        .iter()
+        .filter(|part_id| !partition_ids.contains(part_id))
+        .copied()
+        .collect::<Vec<_>>();
+    for probes in remaining.chunks(nprobes.max(1)) {
+        let probed = probe(probes).await?;
+        num_rows += probed.iter().map(|batch| batch.num_rows()).sum::<usize>();
+        batches.extend(probed);
+        if num_rows >= limit {
+            break;
+        }
+    }
+    Ok(batches)
+}
+
+/// Drops the rows of a partition's search results that are not within the query's radius
+///
+/// A range search (see [Query::radius]) keeps the candidates below the threshold
+/// rather than the `k` closest.  With a refine step the approximate distances are
+/// kept as they are, the refined distances are filtered instead.
+fn filter_by_radius(batch: RecordBatch, query: &Query) -> Result<RecordBatch> {
+    let Some(radius) = query.radius.filter(|_| query.refine_factor.is_none()) else {
+        return Ok(batch);
+    };
+    let dist_col = batch.column_by_name(DIST_COL).ok_or_else(|| {
+        Error::io(
+            format!(
+                "_distance column does not exist in batch: {}",
+                batch.schema()
+            ),
+            location!(),
+        )
+    })?;
+    let within = lt(dist_col, &Float32Array::new_scalar(radius))?;
+    Ok(filter_record_batch(&batch, &within)?)
+}
+
 fn centroids_to_vectors(centroids: &FixedSizeListArray) -> Result<Vec<Vec<f32>>> {
     centroids
         .iter()
@@ -766,12 +957,28 @@ impl VectorIndex for IVFIndex {
 
         let partition_ids = self.find_partitions(&query)?;
         assert!(partition_ids.len() <= query.nprobes);
-        let part_ids = partition_ids.values().to_vec();
-        let batches = stream::iter(part_ids)
-            .map(|part_id| self.search_in_partition(part_id as usize, &query, pre_filter.clone()))
-            .buffer_unordered(num_cpus::get())
-            .try_collect::<Vec<_>>()
-            .await?;
+        // With a prefilter keep probing if the closest partitions don't have enough
+        // matching rows.  A range search wants the rows within the radius rather than
+        // `k` rows and so it only searches the closest partitions.
+        let rank_partitions = || {
+            self.find_partitions(&Query {
+                nprobes: self.ivf.num_partitions(),
+                ..query.clone()
+            })
+        };
+        let widen = !pre_filter.is_empty() && query.radius.is_none();
+        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let batches = search_partitions(
+            partition_ids.values(),
+            query.nprobes,
+            limit,
+            widen.then_some(&rank_partitions as &(dyn Fn() -> Result<UInt32Array> + Sync)),
+            |part_id| {
+                self.search_in_partition(part_id, &query, pre_filter.clone())
+                    .boxed()
+            },
+        )
+        .await?;
         let batch = concat_batches(&batches[0].schema(), &batches)?;
 
         let dist_col = batch.column_by_name(DIST_COL).ok_or_else(|| {
@@ -785,7 +992,6 @@ impl VectorIndex for IVFIndex {
         })?;
 
         // TODO: Use a heap sort to get the top-k.
-        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
         let selection = sort_to_indices(dist_col, None, Some(limit))?;
         let struct_arr = StructArray::from(batch);
         let taken_distances = take(&struct_arr, &selection, None)?;
@@ -817,7 +1023,7 @@ impl VectorIndex for IVFIndex {
 
         let query = self.preprocess_query(partition_id, query)?;
         let batch = part_index.search(&query, pre_filter).await?;
-        Ok(batch)
+        filter_by_radius(batch, &query)
     }
 
     fn is_loadable(&self) -> bool {
@@ -1140,12 +1346,18 @@ async fn build_ivf_model_and_pq(
 async fn scan_index_field_stream(
     dataset: &Dataset,
     column: &str,
+    progress_monitor: &ProgressMonitor,
 ) -> Result<impl RecordBatchStream + Unpin + 'static> {
     let mut scanner = dataset.scan();
     scanner.batch_readahead(num_cpus::get() * 2);
     scanner.project(&[column])?;
     scanner.with_row_id();
-    scanner.try_into_stream().await
+    let num_rows = dataset.count_rows(None).await? as u64;
+    Ok(progress_monitor.monitor_stream(
+        scanner.try_into_stream().await?,
+        ProgressStage::IndexData,
+        Some(num_rows),
+    ))
 }
 
 async fn load_precomputed_partitions_if_available(
@@ -1176,10 +1388,13 @@ pub async fn build_ivf_pq_index(
     metric_type: MetricType,
     ivf_params: &IvfBuildParams,
     pq_params: &PQBuildParams,
+    progress_monitor: &ProgressMonitor,
 ) -> Result<()> {
+    progress_monitor.check_cancelled()?;
     let (ivf_model, pq) =
         build_ivf_model_and_pq(dataset, column, metric_type, ivf_params, pq_params).await?;
-    let stream = scan_index_field_stream(dataset, column).await?;
+    progress_monitor.check_cancelled()?;
+    let stream = scan_index_field_stream(dataset, column, progress_monitor).await?;
     let precomputed_partitions = load_precomputed_partitions_if_available(ivf_params).await?;
 
     write_ivf_pq_file(
@@ -1196,6 +1411,7 @@ pub async fn build_ivf_pq_index(
         ivf_params.shuffle_partition_batches,
         ivf_params.shuffle_partition_concurrency,
         ivf_params.precomputed_shuffle_buffers.clone(),
+        progress_monitor,
     )
     .await
 }
@@ -1210,10 +1426,13 @@ pub async fn build_ivf_hnsw_pq_index(
     ivf_params: &IvfBuildParams,
     hnsw_params: &HnswBuildParams,
     pq_params: &PQBuildParams,
+    progress_monitor: &ProgressMonitor,
 ) -> Result<()> {
+    progress_monitor.check_cancelled()?;
     let (ivf_model, pq) =
         build_ivf_model_and_pq(dataset, column, metric_type, ivf_params, pq_params).await?;
-    let stream = scan_index_field_stream(dataset, column).await?;
+    progress_monitor.check_cancelled()?;
+    let stream = scan_index_field_stream(dataset, column, progress_monitor).await?;
     let precomputed_partitions = load_precomputed_partitions_if_available(ivf_params).await?;
 
     write_ivf_hnsw_file(
@@ -1373,6 +1592,7 @@ async fn write_ivf_pq_file(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    progress_monitor: &ProgressMonitor,
 ) -> Result<()> {
     let object_store = dataset.object_store();
     let path = dataset.indices_dir().child(uuid).child(INDEX_FILE_NAME);
@@ -1392,6 +1612,7 @@ async fn write_ivf_pq_file(
         shuffle_partition_batches,
         shuffle_partition_concurrency,
         precomputed_shuffle_buffers,
+        progress_monitor,
     )
     .await?;
     info!("Built IVF partitions: {}s", start.elapsed().as_secs_f32());
@@ -1824,6 +2045,7 @@ mod tests {
                     refine_factor: None,
                     metric_type: MetricType::L2,
                     use_index: true,
+                    radius: None,
                 };
                 let search_result = index.search(&query, prefilter.clone()).await.unwrap();
 
@@ -1997,6 +2219,7 @@ mod tests {
             MetricType::L2,
             &ivf_params,
             &pq_params,
+            &ProgressMonitor::default(),
         )
         .await
         .unwrap();
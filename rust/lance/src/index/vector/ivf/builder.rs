@@ -22,6 +22,7 @@ use lance_index::vector::{
 use lance_io::{stream::RecordBatchStream, traits::Writer};
 use lance_linalg::distance::MetricType;
 
+use crate::dataset::progress::ProgressMonitor;
 use crate::index::vector::ivf::io::write_pq_partitions;
 
 use super::io::write_hnsw_quantization_index_partitions;
@@ -43,6 +44,7 @@ pub(super) async fn build_partitions(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    progress_monitor: &ProgressMonitor,
 ) -> Result<()> {
     let schema = data.schema();
     if schema.column_with_name(column).is_none() {
@@ -78,7 +80,7 @@ pub(super) async fn build_partitions(
     )
     .await?;
 
-    write_pq_partitions(writer, ivf, Some(stream), None).await?;
+    write_pq_partitions(writer, ivf, Some(stream), None, progress_monitor).await?;
 
     Ok(())
 }
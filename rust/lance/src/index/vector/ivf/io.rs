@@ -42,6 +42,7 @@ use tempfile::TempDir;
 use tokio::sync::Semaphore;
 
 use super::IVFIndex;
+use crate::dataset::progress::{ProgressMonitor, ProgressStage};
 use crate::dataset::ROW_ID;
 use crate::index::vector::pq::{build_pq_storage, PQIndex};
 
@@ -146,6 +147,7 @@ pub(super) async fn write_pq_partitions(
     ivf: &mut IvfModel,
     streams: Option<Vec<impl Stream<Item = Result<RecordBatch>>>>,
     existing_indices: Option<&[&IVFIndex]>,
+    progress_monitor: &ProgressMonitor,
 ) -> Result<()> {
     // build the initial heap
     // TODO: extract heap sort to a separate function.
@@ -182,7 +184,9 @@ pub(super) async fn write_pq_partitions(
         }
     }
 
+    let num_partitions = ivf.num_partitions() as u64;
     for part_id in 0..ivf.num_partitions() as u32 {
+        progress_monitor.check_cancelled()?;
         let start = Instant::now();
         let mut pq_array: Vec<Arc<dyn Array>> = vec![];
         let mut row_id_array: Vec<Arc<dyn Array>> = vec![];
@@ -237,6 +241,11 @@ pub(super) async fn write_pq_partitions(
             part_id,
             start.elapsed().as_millis()
         );
+        progress_monitor.report(
+            ProgressStage::IndexPartitions,
+            part_id as u64 + 1,
+            Some(num_partitions),
+        );
     }
     Ok(())
 }
@@ -19,13 +19,14 @@ use arrow_arith::numeric::sub;
 use arrow_array::{RecordBatch, StructArray, UInt32Array};
 use async_trait::async_trait;
 use deepsize::DeepSizeOf;
-use futures::prelude::stream::{self, StreamExt, TryStreamExt};
+use futures::prelude::stream::TryStreamExt;
+use futures::FutureExt;
 use lance_arrow::RecordBatchExt;
 use lance_core::{cache::DEFAULT_INDEX_CACHE_SIZE, Error, Result};
 use lance_encoding::decoder::{DecoderMiddlewareChain, FilterExpression};
 use lance_file::v2::reader::FileReader;
 use lance_index::vector::flat::index::{FlatIndex, FlatQuantizer};
-use lance_index::vector::hnsw::HNSW;
+use lance_index::vector::hnsw::{builder::HnswBuildParams, HnswMetadata, HNSW};
 use lance_index::vector::ivf::storage::IvfModel;
 use lance_index::vector::quantizer::{QuantizationType, Quantizer};
 use lance_index::vector::sq::ScalarQuantizer;
@@ -59,7 +60,10 @@ use crate::{
     session::Session,
 };
 
-use super::{centroids_to_vectors, IvfIndexPartitionStatistics, IvfIndexStatistics};
+use super::{
+    centroids_to_vectors, filter_by_radius, search_partitions, IvfIndexPartitionStatistics,
+    IvfIndexStatistics,
+};
 
 #[derive(Debug)]
 struct PartitionEntry<S: IvfSubIndex, Q: Quantization> {
@@ -283,6 +287,16 @@ impl<S: IvfSubIndex + 'static, Q: Quantization> IVFIndex<S, Q> {
     }
 }
 
+impl<Q: Quantization> IVFIndex<HNSW, Q> {
+    /// The parameters the HNSW graphs of the partitions were built with
+    pub(crate) fn hnsw_params(&self) -> Result<HnswBuildParams> {
+        match self.sub_index_metadata.iter().find(|m| !m.is_empty()) {
+            Some(metadata) => Ok(serde_json::from_str::<HnswMetadata>(metadata)?.params),
+            None => Ok(HnswBuildParams::default()),
+        }
+    }
+}
+
 #[async_trait]
 impl<S: IvfSubIndex + 'static, Q: Quantization + 'static> Index for IVFIndex<S, Q> {
     fn as_any(&self) -> &dyn Any {
@@ -312,6 +326,7 @@ impl<S: IvfSubIndex + 'static, Q: Quantization + 'static> Index for IVFIndex<S,
 
         let index_type = match self.sub_index_type() {
             (sub_index_type, QuantizationType::Flat) => format!("IVF_{}", sub_index_type), // ignore FLAT quantization
+            (SubIndexType::Flat, quantization_type) => format!("IVF_{}", quantization_type), // ignore FLAT sub index
             (sub_index_type, quantization_type) => {
                 if sub_index_type.to_string() == quantization_type.to_string() {
                     // ignore redundant quantization type
@@ -361,12 +376,28 @@ impl<S: IvfSubIndex + fmt::Debug + 'static, Q: Quantization + fmt::Debug + 'stat
 
         let partition_ids = self.find_partitions(&query)?;
         assert!(partition_ids.len() <= query.nprobes);
-        let part_ids = partition_ids.values().to_vec();
-        let batches = stream::iter(part_ids)
-            .map(|part_id| self.search_in_partition(part_id as usize, &query, pre_filter.clone()))
-            .buffer_unordered(num_cpus::get())
-            .try_collect::<Vec<_>>()
-            .await?;
+        // With a prefilter keep probing if the closest partitions don't have enough
+        // matching rows.  A range search wants the rows within the radius rather than
+        // `k` rows and so it only searches the closest partitions.
+        let rank_partitions = || {
+            self.find_partitions(&Query {
+                nprobes: self.ivf.num_partitions(),
+                ..query.clone()
+            })
+        };
+        let widen = !pre_filter.is_empty() && query.radius.is_none();
+        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let batches = search_partitions(
+            partition_ids.values(),
+            query.nprobes,
+            limit,
+            widen.then_some(&rank_partitions as &(dyn Fn() -> Result<UInt32Array> + Sync)),
+            |part_id| {
+                self.search_in_partition(part_id, &query, pre_filter.clone())
+                    .boxed()
+            },
+        )
+        .await?;
         let batch = concat_batches(&batches[0].schema(), &batches)?;
 
         let dist_col = batch.column_by_name(DIST_COL).ok_or_else(|| {
@@ -380,7 +411,6 @@ impl<S: IvfSubIndex + fmt::Debug + 'static, Q: Quantization + fmt::Debug + 'stat
         })?;
 
         // TODO: Use a heap sort to get the top-k.
-        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
         let selection = sort_to_indices(dist_col, None, Some(limit))?;
         let struct_arr = StructArray::from(batch);
         let taken_distances = take(&struct_arr, &selection, None)?;
@@ -423,9 +453,14 @@ impl<S: IvfSubIndex + fmt::Debug + 'static, Q: Quantization + fmt::Debug + 'stat
         let query = self.preprocess_query(partition_id, query)?;
         let param = (&query).into();
         // pre_filter.wait_for_ready().await?;
-        part_entry
-            .index
-            .search(query.key, query.k, param, &part_entry.storage, pre_filter)
+        let batch = part_entry.index.search(
+            query.key.clone(),
+            query.k,
+            param,
+            &part_entry.storage,
+            pre_filter,
+        )?;
+        filter_by_radius(batch, &query)
     }
 
     fn is_loadable(&self) -> bool {
@@ -489,6 +524,7 @@ impl<S: IvfSubIndex + fmt::Debug + 'static, Q: Quantization + fmt::Debug + 'stat
 
 pub type IvfFlatIndex = IVFIndex<FlatIndex, FlatQuantizer>;
 pub type IvfHnswSqIndex = IVFIndex<HNSW, ScalarQuantizer>;
+pub type IvfSqIndex = IVFIndex<FlatIndex, ScalarQuantizer>;
 
 #[cfg(test)]
 mod tests {
@@ -497,20 +533,22 @@ mod tests {
 
     use arrow::datatypes::UInt64Type;
     use arrow::{array::AsArray, datatypes::Float32Type};
-    use arrow_array::{Array, FixedSizeListArray, RecordBatch, RecordBatchIterator};
+    use arrow_array::{Array, FixedSizeListArray, Float32Array, RecordBatch, RecordBatchIterator};
     use arrow_schema::{DataType, Field, Schema};
     use lance_arrow::FixedSizeListArrayExt;
 
     use lance_core::ROW_ID;
     use lance_index::vector::hnsw::builder::HnswBuildParams;
     use lance_index::vector::ivf::IvfBuildParams;
+    use lance_index::vector::quantizer::Quantizer;
     use lance_index::vector::sq::builder::SQBuildParams;
-    use lance_index::vector::DIST_COL;
+    use lance_index::vector::{VectorIndex, DIST_COL};
     use lance_index::{DatasetIndexExt, IndexType};
     use lance_linalg::distance::DistanceType;
     use lance_testing::datagen::generate_random_array_with_range;
     use tempfile::tempdir;
 
+    use crate::index::DatasetIndexInternalExt;
     use crate::{index::vector::VectorIndexParams, Dataset};
 
     const DIM: usize = 32;
@@ -520,6 +558,35 @@ mod tests {
         range: Range<f32>,
     ) -> (Dataset, Arc<FixedSizeListArray>) {
         let vectors = generate_random_array_with_range::<Float32Type>(1000 * DIM, range);
+        let fsl = FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap();
+        let fsl = lance_linalg::kernels::normalize_fsl(&fsl).unwrap();
+        write_test_dataset(test_uri, fsl).await
+    }
+
+    // Vectors around `num_clusters` random centers
+    async fn generate_clustered_dataset(
+        test_uri: &str,
+        num_clusters: usize,
+    ) -> (Dataset, Arc<FixedSizeListArray>) {
+        write_test_dataset(test_uri, generate_clustered_vectors(num_clusters)).await
+    }
+
+    fn generate_clustered_vectors(num_clusters: usize) -> FixedSizeListArray {
+        let centers =
+            generate_random_array_with_range::<Float32Type>(num_clusters * DIM, -1.0..1.0);
+        let noise = generate_random_array_with_range::<Float32Type>(1000 * DIM, -0.05..0.05);
+        let vectors =
+            Float32Array::from_iter_values(noise.values().iter().enumerate().map(|(i, v)| {
+                let cluster = (i / DIM) % num_clusters;
+                centers.value(cluster * DIM + i % DIM) + v
+            }));
+        FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap()
+    }
+
+    async fn write_test_dataset(
+        test_uri: &str,
+        fsl: FixedSizeListArray,
+    ) -> (Dataset, Arc<FixedSizeListArray>) {
         let metadata: HashMap<String, String> = vec![("test".to_string(), "ivf_pq".to_string())]
             .into_iter()
             .collect();
@@ -534,8 +601,6 @@ mod tests {
         )])
         .with_metadata(metadata)
         .into();
-        let fsl = FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap();
-        let fsl = lance_linalg::kernels::normalize_fsl(&fsl).unwrap();
         let array = Arc::new(fsl);
         let batch = RecordBatch::try_new(schema.clone(), vec![array.clone()]).unwrap();
 
@@ -703,6 +768,166 @@ mod tests {
         test_create_ivf_hnsw_sq(DistanceType::Dot).await
     }
 
+    async fn test_create_ivf_sq(distance_type: DistanceType, clustered: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let nlist = 4;
+        let (mut dataset, vectors) = if clustered {
+            generate_clustered_dataset(test_uri, 16).await
+        } else {
+            generate_test_dataset(test_uri, 0.0..1.0).await
+        };
+
+        let params = VectorIndexParams::with_ivf_sq_params(
+            distance_type,
+            IvfBuildParams::new(nlist),
+            SQBuildParams::default(),
+        );
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, true)
+            .await
+            .unwrap();
+
+        let query = vectors.value(0);
+        let k = 100;
+        let gt = ground_truth(
+            &vectors,
+            query.as_primitive::<Float32Type>().values(),
+            k,
+            distance_type,
+        );
+        let gt_set = gt.iter().map(|r| r.1).collect::<HashSet<_>>();
+
+        // The refine step re-ranks the candidates by their original vectors
+        for (refine_factor, min_recall) in [(None, 0.9), (Some(5), 1.0)] {
+            let mut scanner = dataset.scan();
+            scanner
+                .nearest("vector", query.as_primitive::<Float32Type>(), k)
+                .unwrap()
+                .nprobs(nlist)
+                .distance_metric(distance_type)
+                .with_row_id();
+            if let Some(refine_factor) = refine_factor {
+                scanner.refine(refine_factor);
+            }
+            let result = scanner.try_into_batch().await.unwrap();
+
+            let row_ids = result[ROW_ID]
+                .as_primitive::<UInt64Type>()
+                .values()
+                .iter()
+                .copied()
+                .collect::<HashSet<_>>();
+            let recall = row_ids.intersection(&gt_set).count() as f32 / k as f32;
+            assert!(
+                recall >= min_recall,
+                "refine_factor: {:?}, recall: {}\n results: {:?}\n\ngt: {:?}",
+                refine_factor,
+                recall,
+                result,
+                gt,
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_ivf_sq_l2() {
+        test_create_ivf_sq(DistanceType::L2, false).await
+    }
+
+    #[tokio::test]
+    async fn test_create_ivf_sq_cosine() {
+        test_create_ivf_sq(DistanceType::Cosine, false).await
+    }
+
+    #[tokio::test]
+    async fn test_create_ivf_sq_dot() {
+        test_create_ivf_sq(DistanceType::Dot, false).await
+    }
+
+    #[tokio::test]
+    async fn test_create_ivf_sq_clustered() {
+        test_create_ivf_sq(DistanceType::L2, true).await
+    }
+
+    #[tokio::test]
+    async fn test_ivf_sq_index_metadata() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let nlist = 4;
+        let (mut dataset, vectors) = generate_clustered_dataset(test_uri, 16).await;
+
+        let params = VectorIndexParams::with_ivf_sq_params(
+            DistanceType::L2,
+            IvfBuildParams::new(nlist),
+            SQBuildParams::default(),
+        );
+        dataset
+            .create_index(
+                &["vector"],
+                IndexType::Vector,
+                Some("test_index".to_owned()),
+                &params,
+                true,
+            )
+            .await
+            .unwrap();
+
+        let stats = dataset.index_statistics("test_index").await.unwrap();
+        let stats: serde_json::Value = serde_json::from_str(stats.as_str()).unwrap();
+        assert_eq!(stats["index_type"].as_str().unwrap(), "IVF_SQ");
+        for index in stats["indices"].as_array().unwrap() {
+            assert_eq!(index["index_type"].as_str().unwrap(), "IVF_SQ");
+            assert_eq!(index["sub_index"]["index_type"].as_str().unwrap(), "FLAT");
+        }
+
+        // The value range of each dimension is learned from all the vectors
+        let values = vectors.values().as_primitive::<Float32Type>().values();
+        let expected_bounds = (0..DIM)
+            .map(|d| {
+                let dim_values = values.iter().skip(d).step_by(DIM).map(|v| *v as f64);
+                dim_values.clone().fold(f64::MAX, f64::min)..dim_values.fold(f64::MIN, f64::max)
+            })
+            .collect::<Vec<_>>();
+
+        check_sq_quantizer(&dataset, &expected_bounds).await;
+
+        // Merging new data into the index keeps the trained quantizer
+        let schema: Arc<Schema> = Arc::new(dataset.schema().into());
+        let new_data = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(generate_clustered_vectors(16))],
+        )
+        .unwrap();
+        dataset
+            .append(RecordBatchIterator::new(vec![Ok(new_data)], schema), None)
+            .await
+            .unwrap();
+        dataset.optimize_indices(&Default::default()).await.unwrap();
+
+        let stats = dataset.index_statistics("test_index").await.unwrap();
+        let stats: serde_json::Value = serde_json::from_str(stats.as_str()).unwrap();
+        assert_eq!(stats["index_type"].as_str().unwrap(), "IVF_SQ");
+        assert_eq!(stats["num_unindexed_rows"], 0);
+        check_sq_quantizer(&dataset, &expected_bounds).await;
+    }
+
+    async fn check_sq_quantizer(dataset: &Dataset, expected_bounds: &[Range<f64>]) {
+        let indices = dataset.load_indices().await.unwrap();
+        let index = dataset
+            .open_vector_index("vector", indices[0].uuid.to_string().as_str())
+            .await
+            .unwrap();
+        let Quantizer::Scalar(sq) = index.quantizer() else {
+            panic!("expect a scalar quantizer");
+        };
+        assert_eq!(sq.num_bits, 8);
+        assert_eq!(sq.dim, DIM);
+        assert_eq!(sq.dim_bounds, expected_bounds);
+    }
+
     #[tokio::test]
     async fn test_index_stats() {
         let test_dir = tempdir().unwrap();
@@ -45,7 +45,7 @@ pub async fn maybe_sample_training_data(
     let num_rows = dataset.count_rows(None).await?;
     let projection = dataset.schema().project(&[column])?;
     let batch = if num_rows > sample_size_hint {
-        dataset.sample(sample_size_hint, &projection).await?
+        dataset.sample(sample_size_hint, &projection, None).await?
     } else {
         let mut scanner = dataset.scan();
         scanner.project(&[column])?;
@@ -40,7 +40,7 @@ use prost::Message;
 
 use super::ObjectStore;
 use crate::dataset::fragment::FileFragment;
-use crate::dataset::transaction::{Operation, Transaction};
+use crate::dataset::transaction::{Operation, Transaction, TransactionConflict};
 use crate::dataset::{write_manifest_file, ManifestWriteConfig};
 use crate::index::DatasetIndexInternalExt;
 use crate::Dataset;
@@ -95,16 +95,15 @@ fn check_transaction(
         });
     }
 
-    if transaction.conflicts_with(other_transaction.as_ref().unwrap()) {
+    let other_transaction = other_transaction.as_ref().unwrap();
+    if let Some(kind) = transaction.conflict_kind(other_transaction) {
         return Err(crate::Error::CommitConflict {
             version: other_version,
-            source: format!(
-                "There was a concurrent commit that conflicts with this one and it \
-                cannot be automatically resolved. Please rerun the operation off the latest version \
-                of the table.\n Transaction: {:?}\n Conflicting Transaction: {:?}",
-                transaction, other_transaction
-            )
-            .into(),
+            source: Box::new(TransactionConflict {
+                kind,
+                operation: transaction.operation.name().to_string(),
+                other_operation: other_transaction.operation.name().to_string(),
+            }),
             location: location!(),
         });
     }
@@ -536,7 +535,8 @@ mod tests {
 
     use super::*;
 
-    use crate::dataset::{WriteMode, WriteParams};
+    use crate::dataset::transaction::ConflictKind;
+    use crate::dataset::{NewColumnTransform, WriteMode, WriteParams};
     use crate::index::vector::VectorIndexParams;
 
     async fn test_commit_handler(handler: Arc<dyn CommitHandler>, should_succeed: bool) {
@@ -856,6 +856,156 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_many_concurrent_appends() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![].into_iter().map(Ok), schema.clone()),
+            test_uri,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Every appender starts from the same version so all but the first have to
+        // rebase onto the appends that beat them
+        const NUM_APPENDERS: i32 = 16;
+        let futures: Vec<_> = (0..NUM_APPENDERS)
+            .map(|writer_idx| {
+                let mut dataset = dataset.clone();
+                let schema = schema.clone();
+                tokio::spawn(async move {
+                    let batch = RecordBatch::try_new(
+                        schema.clone(),
+                        vec![Arc::new(Int32Array::from_iter_values(
+                            writer_idx * 10..(writer_idx + 1) * 10,
+                        ))],
+                    )
+                    .unwrap();
+                    let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+                    dataset
+                        .append(
+                            reader,
+                            Some(WriteParams {
+                                commit_retries: NUM_APPENDERS as u32,
+                                ..Default::default()
+                            }),
+                        )
+                        .await
+                })
+            })
+            .collect();
+        for result in join_all(futures).await {
+            assert!(matches!(result, Ok(Ok(_))), "{:?}", result);
+        }
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 1 + NUM_APPENDERS as u64);
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), NUM_APPENDERS as usize);
+        let fragment_ids = fragments.iter().map(|f| f.id()).collect::<HashSet<_>>();
+        assert_eq!(fragment_ids.len(), NUM_APPENDERS as usize);
+        assert_eq!(
+            dataset.count_rows(None).await.unwrap(),
+            10 * NUM_APPENDERS as usize
+        );
+        let mut values = dataset
+            .scan()
+            .try_into_batch()
+            .await
+            .unwrap()
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap()
+            .values()
+            .to_vec();
+        values.sort();
+        assert_eq!(values, (0..10 * NUM_APPENDERS).collect::<Vec<_>>());
+        dataset.validate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deletes() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 50,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(dataset.get_fragments().len(), 2);
+
+        // Deletes in different fragments are rebased onto each other
+        let mut first = dataset.clone();
+        let mut second = dataset.clone();
+        first.delete("i < 10").await.unwrap();
+        second.delete("i >= 90").await.unwrap();
+        let latest = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(latest.count_rows(None).await.unwrap(), 80);
+
+        // Deletes in the same fragment conflict
+        let mut first = latest.clone();
+        let mut second = latest.clone();
+        first.delete("i = 20").await.unwrap();
+        let err = second.delete("i = 21").await.unwrap_err();
+        let crate::Error::CommitConflict { source, .. } = err else {
+            panic!("Expected a commit conflict, got {:?}", err)
+        };
+        let conflict = source.downcast_ref::<TransactionConflict>().unwrap();
+        assert_eq!(conflict.kind, ConflictKind::OverlappingFragments);
+        assert_eq!(conflict.operation, "Delete");
+        assert_eq!(conflict.other_operation, "Delete");
+
+        // An append can't be rebased onto a schema change
+        let mut appender = Dataset::open(test_uri).await.unwrap();
+        let mut projected = appender.clone();
+        projected
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("j".into(), "i * 2".into())]),
+                None,
+            )
+            .await
+            .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(100..110))],
+        )
+        .unwrap();
+        let err = appender
+            .append(RecordBatchIterator::new(vec![Ok(batch)], schema), None)
+            .await
+            .unwrap_err();
+        let crate::Error::CommitConflict { source, .. } = err else {
+            panic!("Expected a commit conflict, got {:?}", err)
+        };
+        let conflict = source.downcast_ref::<TransactionConflict>().unwrap();
+        assert_eq!(conflict.kind, ConflictKind::SchemaChange);
+    }
+
     #[test]
     fn test_fix_schema() {
         // Manifest has a fragment with no fields in use
@@ -881,6 +1031,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
             Fragment {
                 id: 1,
@@ -891,6 +1044,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
         ];
 
@@ -917,6 +1073,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
             Fragment {
                 id: 1,
@@ -927,6 +1086,9 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                sort_order: vec![],
+                row_id_ranges: vec![],
+                custom_metadata: HashMap::new(),
             },
         ];
         assert_eq!(manifest.fragments.as_ref(), &expected_fragments);
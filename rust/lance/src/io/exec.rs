@@ -11,7 +11,7 @@ mod planner;
 mod projection;
 mod pushdown_scan;
 pub mod scalar_index;
-mod scan;
+pub(crate) mod scan;
 mod take;
 #[cfg(test)]
 pub mod testing;
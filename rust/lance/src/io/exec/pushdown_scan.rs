@@ -4,6 +4,7 @@
 use std::collections::HashMap;
 use std::{any::Any, sync::Arc};
 
+use arrow_arith::boolean::{and, is_not_null};
 use arrow_array::cast::AsArray;
 use arrow_array::types::{Int64Type, UInt64Type};
 use arrow_array::{Array, BooleanArray, Int64Array, PrimitiveArray, RecordBatch, UInt32Array};
@@ -15,6 +16,9 @@ use datafusion::logical_expr::col;
 use datafusion::logical_expr::interval_arithmetic::{Interval, NullableInterval};
 use datafusion::optimizer::simplify_expressions::{ExprSimplifier, SimplifyContext};
 use datafusion::physical_expr::execution_props::ExecutionProps;
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
 use datafusion::physical_plan::{ColumnarValue, ExecutionMode, PlanProperties};
 use datafusion::scalar::ScalarValue;
 use datafusion::{
@@ -31,6 +35,7 @@ use lance_arrow::{RecordBatchExt, SchemaExt};
 use lance_core::{ROW_ADDR, ROW_ADDR_FIELD, ROW_ID_FIELD};
 use lance_io::ReadBatchParams;
 use lance_table::format::Fragment;
+use roaring::RoaringBitmap;
 use snafu::{location, Location};
 
 use crate::dataset::scanner::{DEFAULT_BATCH_READAHEAD, DEFAULT_FRAGMENT_READAHEAD};
@@ -44,6 +49,7 @@ use crate::{
     Dataset,
 };
 
+use super::scan::PAGES_SKIPPED_METRIC;
 use super::Planner;
 
 #[derive(Debug, Clone)]
@@ -91,6 +97,7 @@ pub struct LancePushdownScanExec {
     config: ScanConfig,
     output_schema: Arc<ArrowSchema>,
     properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl LancePushdownScanExec {
@@ -101,12 +108,9 @@ impl LancePushdownScanExec {
         predicate: Expr,
         config: ScanConfig,
     ) -> Result<Self> {
-        // This should be infallible.
-        let columns: Vec<_> = predicate
-            .column_refs()
-            .into_iter()
-            .map(|col| col.name.as_str())
-            .collect();
+        // Nested references are resolved to the leaf fields so that only those
+        // are loaded (and only their statistics are used).
+        let columns = Planner::column_names_in_expr(&predicate);
         let dataset_schema = dataset.schema();
         let predicate_projection = Arc::new(dataset_schema.project(&columns)
             .map_err(|err| Error::invalid_input(format!("Filter predicate '{:?}' references columns {:?}, but some of them were not found in the dataset schema: {}\nInner error: {:?}", predicate, columns, dataset_schema, err), location!()))?);
@@ -141,10 +145,38 @@ impl LancePushdownScanExec {
             config,
             output_schema,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         })
     }
 }
 
+impl LancePushdownScanExec {
+    /// Count the rows that match the predicate without producing any batches.
+    ///
+    /// Pages whose statistics decide the predicate are counted without reading
+    /// them.  Only the filter columns of the remaining pages are read.  Deleted
+    /// rows are never counted.
+    pub async fn count_rows(&self) -> Result<u64> {
+        let counts = futures::stream::iter(self.fragments.iter().cloned())
+            .map(|fragment| async move {
+                let frag_scanner = FragmentScanner::open(
+                    fragment,
+                    self.dataset.clone(),
+                    self.projection.clone(),
+                    self.predicate_projection.clone(),
+                    self.predicate.clone(),
+                    self.config.clone(),
+                )
+                .await?;
+                frag_scanner.count_rows().await
+            })
+            .buffer_unordered(self.config.fragment_readahead);
+        counts
+            .try_fold(0, |acc, count| async move { Ok(acc + count) })
+            .await
+    }
+}
+
 impl ExecutionPlan for LancePushdownScanExec {
     fn name(&self) -> &str {
         "LancePushdownScanExec"
@@ -164,9 +196,15 @@ impl ExecutionPlan for LancePushdownScanExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
-        todo!()
+        if children.is_empty() {
+            Ok(self)
+        } else {
+            Err(DataFusionError::Internal(
+                "LancePushdownScanExec cannot be assigned children".to_string(),
+            ))
+        }
     }
 
     fn statistics(&self) -> datafusion::error::Result<datafusion::physical_plan::Statistics> {
@@ -175,9 +213,11 @@ impl ExecutionPlan for LancePushdownScanExec {
 
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<datafusion::execution::context::TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
+        let pages_skipped =
+            MetricBuilder::new(&self.metrics).counter(PAGES_SKIPPED_METRIC, partition);
         // To get a stream with a static lifetime, we clone self put it into
         // a stream.
         let state = (self.clone(), 0);
@@ -193,18 +233,21 @@ impl ExecutionPlan for LancePushdownScanExec {
             }
         });
 
-        let batch_stream = fragment_stream.map(|(exec, fragment)| async move {
-            let frag_scanner = FragmentScanner::open(
-                fragment,
-                exec.dataset,
-                exec.projection,
-                exec.predicate_projection,
-                exec.predicate,
-                exec.config.clone(),
-            )
-            .await?;
+        let batch_stream = fragment_stream.map(move |(exec, fragment)| {
+            let pages_skipped = pages_skipped.clone();
+            async move {
+                let frag_scanner = FragmentScanner::open(
+                    fragment,
+                    exec.dataset,
+                    exec.projection,
+                    exec.predicate_projection,
+                    exec.predicate,
+                    exec.config.clone(),
+                )
+                .await?;
 
-            frag_scanner.scan().await
+                frag_scanner.scan(pages_skipped).await
+            }
         });
 
         let batch_stream = batch_stream
@@ -220,6 +263,10 @@ impl ExecutionPlan for LancePushdownScanExec {
     fn properties(&self) -> &PlanProperties {
         &self.properties
     }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
 }
 
 impl DisplayAs for LancePushdownScanExec {
@@ -293,7 +340,12 @@ impl FragmentScanner {
         })
     }
 
-    pub async fn scan(self) -> Result<impl Stream<Item = Result<RecordBatch>> + 'static + Send> {
+    /// Scans the fragment, counting the pages that statistics show cannot match in
+    /// `pages_skipped`
+    pub async fn scan(
+        self,
+        pages_skipped: Count,
+    ) -> Result<impl Stream<Item = Result<RecordBatch>> + 'static + Send> {
         let batch_readahead = self.config.batch_readahead;
         let simplified_predicates = self.simplified_predicates()?;
         let ordered_output = self.config.ordered_output;
@@ -304,11 +356,12 @@ impl FragmentScanner {
             // We can skip any batches where the predicate is guaranteed to be unsatisfied.
             // By skipping at this point, we prevent these batches from taking a slot in
             // the batch readahead buffer.
-            .filter(|(_, predicate)| {
-                futures::future::ready(!matches!(
-                    predicate,
-                    Expr::Literal(ScalarValue::Boolean(Some(false)))
-                ))
+            .filter(move |(_, predicate)| {
+                let skip = matches!(predicate, Expr::Literal(ScalarValue::Boolean(Some(false))));
+                if skip {
+                    pages_skipped.add(1);
+                }
+                futures::future::ready(!skip)
             })
             .map(move |(batch_id, predicate)| {
                 let scanner_ref = scanner.clone();
@@ -364,11 +417,7 @@ impl FragmentScanner {
 
                 // 1. Load needed filter columns, which might be a subset of all filter
                 //    columns if statistics obviated the need for some columns.
-                let columns: Vec<_> = predicate
-                    .column_refs()
-                    .into_iter()
-                    .map(|col| col.name.as_str())
-                    .collect();
+                let columns = Planner::column_names_in_expr(&predicate);
                 let predicate_projection =
                     Arc::new(self.fragment.dataset().schema().project(&columns).unwrap());
                 let mut reader = self.reader.clone();
@@ -495,6 +544,86 @@ impl FragmentScanner {
         }
     }
 
+    async fn count_rows(self) -> Result<u64> {
+        let num_batches = self.reader.legacy_num_batches();
+        let batch_sizes = self.batch_sizes(num_batches);
+        let deleted = self
+            .fragment
+            .get_deletion_vector()
+            .await?
+            .map(|deletion_vector| RoaringBitmap::from(deletion_vector.as_ref()))
+            .unwrap_or_default();
+        if deleted.len() as usize >= batch_sizes.iter().sum::<usize>() {
+            return Ok(0);
+        }
+
+        // Try to decide the whole fragment at once before looking at each page.
+        let predicates = match self.simplified_fragment_predicate(&batch_sizes)? {
+            Some(predicate) if is_literal_bool(&predicate) => vec![predicate; num_batches],
+            _ => self.simplified_predicates()?,
+        };
+
+        let mut batch_offset = 0;
+        let mut batch_ranges = Vec::with_capacity(num_batches);
+        for batch_size in &batch_sizes {
+            batch_ranges.push(batch_offset..batch_offset + *batch_size as u32);
+            batch_offset += *batch_size as u32;
+        }
+
+        let batch_readahead = self.config.batch_readahead;
+        let scanner = Arc::new(self);
+        let counts = futures::stream::iter(predicates.into_iter().zip(batch_ranges).enumerate())
+            .map(|(batch_id, (predicate, range))| {
+                let scanner = scanner.clone();
+                let deleted = &deleted;
+                async move {
+                    match predicate {
+                        Expr::Literal(ScalarValue::Boolean(Some(true))) => {
+                            Ok(range.len() as u64 - deleted.range_cardinality(range))
+                        }
+                        // A null predicate is not a match
+                        Expr::Literal(ScalarValue::Boolean(_)) => Ok(0),
+                        _ => scanner.count_batch(batch_id, predicate).await,
+                    }
+                }
+            })
+            .buffer_unordered(batch_readahead);
+        counts
+            .try_fold(0, |acc, count| async move { Ok(acc + count) })
+            .await
+    }
+
+    /// Count the live rows of a single batch that match the predicate by
+    /// reading the columns the predicate references.
+    async fn count_batch(&self, batch_id: usize, predicate: Expr) -> Result<u64> {
+        let columns = Planner::column_names_in_expr(&predicate);
+        let predicate_projection = self.fragment.dataset().schema().project(&columns)?;
+        let mut reader = self.reader.clone();
+        // Deleted rows get a null row address so they can be told apart
+        reader.with_make_deletions_null();
+        reader.with_row_address();
+        let batch = reader
+            .legacy_read_batch_projected(batch_id, .., &predicate_projection)
+            .await?;
+        let live_rows = batch[ROW_ADDR].len() - batch[ROW_ADDR].null_count();
+
+        let planner = Planner::new(batch.schema());
+        let physical_expr = planner.create_physical_expr(&predicate)?;
+        match physical_expr.evaluate(&batch)? {
+            ColumnarValue::Scalar(ScalarValue::Boolean(Some(true))) => Ok(live_rows as u64),
+            ColumnarValue::Scalar(ScalarValue::Boolean(_)) => Ok(0),
+            ColumnarValue::Array(array) => {
+                let matched = array.as_boolean();
+                let live = is_not_null(&batch[ROW_ADDR])?;
+                Ok(and(matched, &live)?.true_count() as u64)
+            }
+            result => Err(DataFusionError::Internal(format!(
+                "Unexpected result from predicate evaluation: {:?}",
+                result
+            ))),
+        }
+    }
+
     fn final_projection(&self, batch: RecordBatch) -> Result<RecordBatch> {
         let row_id_column = batch.column_by_name(ROW_ID).cloned();
         let row_addr_column = batch.column_by_name(ROW_ADDR).cloned();
@@ -625,23 +754,49 @@ impl FragmentScanner {
             })
     }
 
+    fn batch_sizes(&self, num_batches: usize) -> Vec<usize> {
+        (0..num_batches as u32)
+            .map(|batch_id| {
+                self.reader
+                    .legacy_num_rows_in_batch(batch_id)
+                    .expect("Operation does not yet support v2 fragments") as usize
+            })
+            .collect()
+    }
+
+    fn simplifier<'a>(
+        &self,
+        props: &'a ExecutionProps,
+    ) -> Result<ExprSimplifier<SimplifyContext<'a>>> {
+        let schema = Arc::new(ArrowSchema::from(self.predicate_projection.as_ref()).try_into()?);
+        let context = SimplifyContext::new(props).with_schema(schema);
+        Ok(ExprSimplifier::new(context))
+    }
+
+    /// Simplify the predicate using guarantees that hold for the entire fragment.
+    ///
+    /// Returns None if the fragment has no statistics.
+    fn simplified_fragment_predicate(&self, batch_sizes: &[usize]) -> Result<Option<Expr>> {
+        let Some(stats) = &self.stats else {
+            return Ok(None);
+        };
+        let guarantees = fragment_guarantees(Self::extract_guarantees(
+            &self.predicate_projection,
+            batch_sizes,
+            stats,
+        ));
+        let props = ExecutionProps::new();
+        let simplifier = self.simplifier(&props)?.with_guarantees(guarantees);
+        Ok(simplifier.simplify(self.predicate.clone()).ok())
+    }
+
     fn simplified_predicates(&self) -> Result<Vec<Expr>> {
         let num_batches = self.reader.legacy_num_batches();
 
         if let Some(stats) = &self.stats {
-            let batch_sizes: Vec<usize> = (0..num_batches as u32)
-                .map(|batch_id| {
-                    self.reader
-                        .legacy_num_rows_in_batch(batch_id)
-                        .expect("Operation does not yet support v2 fragments")
-                        as usize
-                })
-                .collect();
-            let schema =
-                Arc::new(ArrowSchema::from(self.predicate_projection.as_ref()).try_into()?);
+            let batch_sizes = self.batch_sizes(num_batches);
             let props = ExecutionProps::new();
-            let context = SimplifyContext::new(&props).with_schema(schema);
-            let mut simplifier = ExprSimplifier::new(context);
+            let mut simplifier = self.simplifier(&props)?;
 
             let mut predicates = Vec::with_capacity(num_batches);
             for guarantees in
@@ -667,6 +822,78 @@ impl FragmentScanner {
     }
 }
 
+fn is_literal_bool(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(ScalarValue::Boolean(_)))
+}
+
+/// Fold per-page guarantees into guarantees that hold for every page.
+///
+/// A column only keeps a guarantee if every page has one for it.
+fn fragment_guarantees(
+    page_guarantees: impl Iterator<Item = Vec<(Expr, NullableInterval)>>,
+) -> Vec<(Expr, NullableInterval)> {
+    let mut folded: Option<Vec<(Expr, NullableInterval)>> = None;
+    for guarantees in page_guarantees {
+        folded = Some(match folded {
+            None => guarantees,
+            Some(folded) => folded
+                .into_iter()
+                .filter_map(|(expr, interval)| {
+                    let (_, other) = guarantees.iter().find(|(other, _)| other == &expr)?;
+                    let union = union_nullable_intervals(&interval, other)?;
+                    Some((expr, union))
+                })
+                .collect(),
+        });
+    }
+    folded.unwrap_or_default()
+}
+
+fn union_nullable_intervals(
+    lhs: &NullableInterval,
+    rhs: &NullableInterval,
+) -> Option<NullableInterval> {
+    match (lhs, rhs) {
+        (NullableInterval::Null { datatype }, NullableInterval::Null { .. }) => {
+            Some(NullableInterval::Null {
+                datatype: datatype.clone(),
+            })
+        }
+        (NullableInterval::NotNull { values: lhs }, NullableInterval::NotNull { values: rhs }) => {
+            Some(NullableInterval::NotNull {
+                values: union_intervals(lhs, rhs)?,
+            })
+        }
+        _ => {
+            let values = match (lhs.values(), rhs.values()) {
+                (Some(lhs), Some(rhs)) => union_intervals(lhs, rhs)?,
+                (Some(values), None) | (None, Some(values)) => values.clone(),
+                (None, None) => return None,
+            };
+            Some(NullableInterval::MaybeNull { values })
+        }
+    }
+}
+
+/// The smallest interval containing both intervals (null bounds are unbounded)
+fn union_intervals(lhs: &Interval, rhs: &Interval) -> Option<Interval> {
+    let lower = if lhs.lower().is_null() || rhs.lower().is_null() {
+        ScalarValue::try_from(lhs.data_type()).ok()?
+    } else if lhs.lower().partial_cmp(rhs.lower())?.is_le() {
+        lhs.lower().clone()
+    } else {
+        rhs.lower().clone()
+    };
+    let upper = if lhs.upper().is_null() || rhs.upper().is_null() {
+        ScalarValue::try_from(lhs.data_type()).ok()?
+    } else if lhs.upper().partial_cmp(rhs.upper())?.is_ge() {
+        lhs.upper().clone()
+    } else {
+        rhs.upper().clone()
+    };
+    Interval::try_new(lower, upper).ok()
+}
+
 #[cfg(test)]
 mod test {
     use arrow_array::{
@@ -686,9 +913,6 @@ mod test {
 
     use super::*;
 
-    // TODO: test pushdown with nested column once https://github.com/apache/arrow-datafusion/pull/8256
-    // is released.
-
     #[tokio::test]
     async fn test_empty_result() {
         // Test we can get no results
@@ -875,6 +1099,97 @@ mod test {
         assert_eq!(results[0].schema().as_ref(), expected_schema.as_ref());
     }
 
+    #[tokio::test]
+    async fn test_nested_filter_stats() {
+        // Page statistics of nested fields are used to skip pages
+        let c_field = Field::new("c", DataType::Int32, false);
+        let d_field = Field::new("d", DataType::Int32, false);
+        let b_field = Field::new(
+            "b",
+            DataType::Struct(vec![d_field.clone(), c_field.clone()].into()),
+            false,
+        );
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "a",
+            DataType::Struct(vec![b_field.clone()].into()),
+            false,
+        )]));
+        let b_array = StructArray::from(vec![
+            (
+                Arc::new(d_field),
+                Arc::new(Int32Array::from_iter_values((0..100).rev())) as ArrayRef,
+            ),
+            (
+                Arc::new(c_field),
+                Arc::new(Int32Array::from_iter_values(0..100)) as ArrayRef,
+            ),
+        ]);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StructArray::from(vec![(
+                Arc::new(b_field),
+                Arc::new(b_array) as ArrayRef,
+            )]))],
+        )
+        .unwrap();
+        let params = WriteParams {
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Arc::new(
+            Dataset::write(batches, "memory://test", Some(params))
+                .await
+                .unwrap(),
+        );
+
+        let planner = Planner::new(schema);
+        let predicate = planner
+            .optimize_expr(planner.parse_filter("a.b.c < 25").unwrap())
+            .unwrap();
+        let exec = LancePushdownScanExec::try_new(
+            dataset.clone(),
+            dataset.fragments().clone(),
+            Arc::new(dataset.schema().clone()),
+            predicate.clone(),
+            ScanConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            exec.predicate_projection.as_ref(),
+            &dataset.schema().project(&["a.b.c"]).unwrap()
+        );
+
+        let scanner = FragmentScanner::open(
+            dataset.fragments()[0].clone(),
+            dataset.clone(),
+            exec.projection.clone(),
+            exec.predicate_projection.clone(),
+            predicate,
+            ScanConfig::default(),
+        )
+        .await
+        .unwrap();
+        let predicates = scanner.simplified_predicates().unwrap();
+        assert_eq!(predicates.len(), 10);
+        for (batch_id, predicate) in predicates.iter().enumerate() {
+            match batch_id {
+                0 | 1 => assert_eq!(predicate, &lit(true)),
+                2 => assert!(!matches!(predicate, Expr::Literal(_))),
+                _ => assert_eq!(predicate, &lit(false)),
+            }
+        }
+
+        let ctx = SessionContext::new();
+        let results = exec
+            .execute(0, ctx.task_ctx())
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 25);
+    }
+
     #[tokio::test]
     async fn test_with_row_id() {
         // Want to hit all three code paths: all filtered out, partially filtered
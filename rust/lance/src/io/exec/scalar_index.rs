@@ -3,14 +3,15 @@
 
 use std::sync::Arc;
 
-use arrow_array::{RecordBatch, UInt64Array};
+use arrow_array::{cast::AsArray, types::UInt64Type, BooleanArray, RecordBatch, UInt64Array};
 use arrow_schema::{DataType, Field, Schema, SchemaRef};
 use async_trait::async_trait;
 use datafusion::{
     common::{stats::Precision, Statistics},
     physical_plan::{
-        stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionMode,
-        ExecutionPlan, Partitioning, PlanProperties,
+        metrics::{ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
     },
     scalar::ScalarValue,
 };
@@ -31,7 +32,7 @@ use lance_index::{
     DatasetIndexExt,
 };
 use lance_table::format::Fragment;
-use roaring::RoaringBitmap;
+use roaring::{RoaringBitmap, RoaringTreemap};
 use snafu::{location, Location};
 use tracing::{debug_span, instrument};
 
@@ -41,6 +42,8 @@ use crate::{
     Dataset,
 };
 
+use super::scan::INDEX_SEARCHES_METRIC;
+
 lazy_static::lazy_static! {
     pub static ref SCALAR_INDEX_SCHEMA: SchemaRef = Arc::new(Schema::new(vec![Field::new("result".to_string(), DataType::Binary, true)]));
 }
@@ -71,6 +74,7 @@ pub struct ScalarIndexExec {
     dataset: Arc<Dataset>,
     expr: ScalarIndexExpr,
     properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl DisplayAs for ScalarIndexExec {
@@ -94,6 +98,7 @@ impl ScalarIndexExec {
             dataset,
             expr,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
 
@@ -133,10 +138,13 @@ impl ExecutionPlan for ScalarIndexExec {
 
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<datafusion::execution::context::TaskContext>,
     ) -> datafusion::error::Result<datafusion::physical_plan::SendableRecordBatchStream> {
-        let batch_fut = Self::do_execute(self.expr.clone(), self.dataset.clone());
+        let index_searches =
+            MetricBuilder::new(&self.metrics).counter(INDEX_SEARCHES_METRIC, partition);
+        let batch_fut = Self::do_execute(self.expr.clone(), self.dataset.clone())
+            .inspect_ok(move |_| index_searches.add(1));
         let stream = futures::stream::iter(vec![batch_fut])
             .then(|batch_fut| batch_fut.map_err(|err| err.into()))
             .boxed()
@@ -157,6 +165,10 @@ impl ExecutionPlan for ScalarIndexExec {
     fn properties(&self) -> &PlanProperties {
         &self.properties
     }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
 }
 
 lazy_static::lazy_static! {
@@ -262,12 +274,26 @@ impl MapIndexExec {
         } else {
             None
         };
-        Ok(input.and_then(move |res| {
-            let column_name = column_name.clone();
-            let dataset = dataset.clone();
-            let deletion_mask = deletion_mask.clone();
-            Self::map_batch(column_name, dataset, deletion_mask, res)
-        }))
+        // Input batches may share values (e.g. the leading column of a composite key) and a row
+        // address should only be emitted once
+        let mut seen = RoaringTreemap::new();
+        Ok(input
+            .and_then(move |res| {
+                let column_name = column_name.clone();
+                let dataset = dataset.clone();
+                let deletion_mask = deletion_mask.clone();
+                Self::map_batch(column_name, dataset, deletion_mask, res)
+            })
+            .map(move |batch| {
+                let batch = batch?;
+                let addrs = batch.column(0).as_primitive::<UInt64Type>();
+                let unseen = addrs
+                    .values()
+                    .iter()
+                    .map(|addr| Some(seen.insert(*addr)))
+                    .collect::<BooleanArray>();
+                Ok(arrow::compute::filter_record_batch(&batch, &unseen)?)
+            }))
     }
 }
 
@@ -333,6 +359,7 @@ pub struct MaterializeIndexExec {
     expr: ScalarIndexExpr,
     fragments: Arc<Vec<Fragment>>,
     properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl DisplayAs for MaterializeIndexExec {
@@ -400,9 +427,23 @@ impl MaterializeIndexExec {
             expr,
             fragments,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
 
+    /// Count the rows that match the index query, excluding deleted rows.
+    ///
+    /// This only consults the index and the deletion files, no data is read.
+    pub async fn count_rows(&self) -> Result<u64> {
+        let ids = Self::matching_row_ids(
+            self.expr.clone(),
+            self.dataset.clone(),
+            self.fragments.clone(),
+        )
+        .await?;
+        Ok(ids.len() as u64)
+    }
+
     #[instrument(name = "materialize_scalar_index", skip_all, level = "debug")]
     async fn do_execute(
         expr: ScalarIndexExpr,
@@ -410,6 +451,19 @@ impl MaterializeIndexExec {
         fragments: Arc<Vec<Fragment>>,
     ) -> Result<RecordBatch> {
         // TODO: multiple batches, stream without materializing all row ids in memory
+        let ids = Self::matching_row_ids(expr, dataset, fragments).await?;
+        let ids = UInt64Array::from(ids);
+        Ok(RecordBatch::try_new(
+            MATERIALIZE_INDEX_SCHEMA.clone(),
+            vec![Arc::new(ids)],
+        )?)
+    }
+
+    async fn matching_row_ids(
+        expr: ScalarIndexExpr,
+        dataset: Arc<Dataset>,
+        fragments: Arc<Vec<Fragment>>,
+    ) -> Result<Vec<u64>> {
         let mask = expr.evaluate(dataset.as_ref());
         let span = debug_span!("create_prefilter");
         let prefilter = span.in_scope(|| {
@@ -426,12 +480,7 @@ impl MaterializeIndexExec {
         } else {
             mask.await?
         };
-        let ids = row_ids_for_mask(mask, &dataset, &fragments).await?;
-        let ids = UInt64Array::from(ids);
-        Ok(RecordBatch::try_new(
-            MATERIALIZE_INDEX_SCHEMA.clone(),
-            vec![Arc::new(ids)],
-        )?)
+        row_ids_for_mask(mask, &dataset, &fragments).await
     }
 }
 
@@ -570,14 +619,17 @@ impl ExecutionPlan for MaterializeIndexExec {
 
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<datafusion::execution::context::TaskContext>,
     ) -> datafusion::error::Result<datafusion::physical_plan::SendableRecordBatchStream> {
+        let index_searches =
+            MetricBuilder::new(&self.metrics).counter(INDEX_SEARCHES_METRIC, partition);
         let batch_fut = Self::do_execute(
             self.expr.clone(),
             self.dataset.clone(),
             self.fragments.clone(),
-        );
+        )
+        .inspect_ok(move |_| index_searches.add(1));
         let stream = futures::stream::iter(vec![batch_fut])
             .then(|batch_fut| batch_fut.map_err(|err| err.into()))
             .boxed()
@@ -595,4 +647,8 @@ impl ExecutionPlan for MaterializeIndexExec {
     fn properties(&self) -> &PlanProperties {
         &self.properties
     }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
 }
@@ -11,6 +11,9 @@ use arrow_buffer::bit_util;
 use arrow_schema::{Schema as ArrowSchema, SchemaRef};
 use datafusion::common::stats::Precision;
 use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet, Time,
+};
 use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties, RecordBatchStream,
     SendableRecordBatchStream, Statistics,
@@ -24,8 +27,10 @@ use lance_arrow::SchemaExt;
 use lance_core::utils::tokio::get_num_compute_intensive_cpus;
 use lance_core::utils::tracing::StreamTracingExt;
 use lance_core::{ROW_ADDR_FIELD, ROW_ID_FIELD};
-use lance_io::scheduler::ScanScheduler;
+use lance_encoding::stats::DecodeStats;
+use lance_io::scheduler::{ScanIoStats, ScanScheduler};
 use lance_table::format::Fragment;
+use lance_table::utils::stream::ReadBatchFutStream;
 use log::debug;
 
 use crate::dataset::fragment::{FileFragment, FragmentReader};
@@ -33,6 +38,65 @@ use crate::dataset::scanner::DEFAULT_FRAGMENT_READAHEAD;
 use crate::dataset::Dataset;
 use crate::datatypes::Schema;
 
+/// The name of the metric with the number of I/O requests issued by a scan
+pub const IOPS_METRIC: &str = "iops";
+/// The name of the metric with the number of bytes read by a scan
+pub const BYTES_READ_METRIC: &str = "bytes_read";
+/// The name of the metric with the number of pages scheduled by a scan
+pub const PAGES_SCHEDULED_METRIC: &str = "pages_scheduled";
+/// The name of the metric with the number of bytes of decoded data produced by a scan
+pub const BYTES_DECODED_METRIC: &str = "bytes_decoded";
+/// The name of the metric with the time spent decoding by a scan
+pub const DECODE_TIME_METRIC: &str = "decode_time";
+/// The name of the metric with the number of pages a scan skipped because their
+/// statistics show that no row matches the filter
+pub const PAGES_SKIPPED_METRIC: &str = "pages_skipped";
+/// The name of the metric with the number of scalar index searches done by a scan
+pub const INDEX_SEARCHES_METRIC: &str = "index_searches";
+
+/// The I/O and decode counters of a [`LanceStream`] (or of the stream of a
+/// [`super::TakeExec`])
+///
+/// The counters are shared by the readers of all the fragments of the scan and are added to
+/// the metrics of the [`LanceScanExec`] once the stream finishes (or is dropped).  Only reads
+/// of data files in the v2 format are counted.
+#[derive(Debug)]
+pub struct ScanMetrics {
+    pub(crate) io_stats: Arc<ScanIoStats>,
+    pub(crate) decode_stats: Arc<DecodeStats>,
+    iops: Count,
+    bytes_read: Count,
+    pages_scheduled: Count,
+    bytes_decoded: Count,
+    decode_time: Time,
+    pub(crate) output_rows: Count,
+}
+
+impl ScanMetrics {
+    pub fn new(metrics: &ExecutionPlanMetricsSet, partition: usize) -> Self {
+        Self {
+            io_stats: Arc::new(ScanIoStats::new()),
+            decode_stats: Arc::new(DecodeStats::new()),
+            iops: MetricBuilder::new(metrics).counter(IOPS_METRIC, partition),
+            bytes_read: MetricBuilder::new(metrics).counter(BYTES_READ_METRIC, partition),
+            pages_scheduled: MetricBuilder::new(metrics).counter(PAGES_SCHEDULED_METRIC, partition),
+            bytes_decoded: MetricBuilder::new(metrics).counter(BYTES_DECODED_METRIC, partition),
+            decode_time: MetricBuilder::new(metrics).subset_time(DECODE_TIME_METRIC, partition),
+            output_rows: MetricBuilder::new(metrics).output_rows(partition),
+        }
+    }
+
+    pub(crate) fn record(&self) {
+        let decode_stats = self.decode_stats.snapshot();
+        self.iops.add(self.io_stats.iops() as usize);
+        self.bytes_read.add(self.io_stats.bytes_read() as usize);
+        self.pages_scheduled
+            .add(decode_stats.pages_scheduled as usize);
+        self.bytes_decoded.add(decode_stats.bytes_decoded as usize);
+        self.decode_time.add_duration(decode_stats.decode_time());
+    }
+}
+
 async fn open_file(
     file_fragment: FileFragment,
     projection: Arc<Schema>,
@@ -40,13 +104,15 @@ async fn open_file(
     with_row_address: bool,
     with_make_deletions_null: bool,
     scan_scheduler: Option<Arc<ScanScheduler>>,
+    decode_stats: Option<Arc<DecodeStats>>,
 ) -> Result<FragmentReader> {
     let mut reader = file_fragment
-        .open(
+        .open_with_decode_stats(
             projection.as_ref(),
             with_row_id,
             with_row_address,
             scan_scheduler,
+            decode_stats,
         )
         .await?;
 
@@ -56,6 +122,19 @@ async fn open_file(
     Ok(reader)
 }
 
+/// Read a fragment, skipping the first `skip_rows` (non-deleted) rows.
+fn read_fragment(
+    reader: &FragmentReader,
+    skip_rows: usize,
+    batch_size: u32,
+) -> crate::Result<ReadBatchFutStream> {
+    if skip_rows > 0 {
+        reader.read_from_offset(skip_rows, batch_size)
+    } else {
+        reader.read_all(batch_size)
+    }
+}
+
 /// Dataset Scan Node.
 pub struct LanceStream {
     inner_stream: stream::BoxStream<'static, Result<RecordBatch>>,
@@ -66,6 +145,9 @@ pub struct LanceStream {
     with_row_id: bool,
 
     with_row_address: bool,
+
+    /// Recorded (once) when the stream finishes or is dropped
+    metrics: Option<ScanMetrics>,
 }
 
 impl LanceStream {
@@ -84,6 +166,9 @@ impl LanceStream {
     ///  - ***with_row_address***: load row address from the datasets.
     ///  - ***with_make_deletions_null***: make deletions null.
     ///  - ***scan_in_order***: whether to scan the fragments in the provided order.
+    ///  - ***first_fragment_offset***: the number of rows to skip at the start of
+    ///    the first fragment.
+    ///  - ***metrics***: the counters to report the work done by the scan to.
     #[allow(clippy::too_many_arguments)]
     pub fn try_new(
         dataset: Arc<Dataset>,
@@ -96,6 +181,8 @@ impl LanceStream {
         with_row_address: bool,
         with_make_deletions_null: bool,
         scan_in_order: bool,
+        first_fragment_offset: usize,
+        metrics: Option<ScanMetrics>,
     ) -> Result<Self> {
         let is_v2_scan = fragments
             .iter()
@@ -112,6 +199,8 @@ impl LanceStream {
                 with_row_id,
                 with_row_address,
                 with_make_deletions_null,
+                first_fragment_offset,
+                metrics,
             )
         } else {
             Self::try_new_v1(
@@ -125,6 +214,8 @@ impl LanceStream {
                 with_row_address,
                 with_make_deletions_null,
                 scan_in_order,
+                first_fragment_offset,
+                metrics,
             )
         }
     }
@@ -139,6 +230,8 @@ impl LanceStream {
         with_row_id: bool,
         with_row_address: bool,
         with_make_deletions_null: bool,
+        first_fragment_offset: usize,
+        metrics: Option<ScanMetrics>,
     ) -> Result<Self> {
         let project_schema = projection.clone();
         let io_parallelism = dataset.object_store.io_parallelism()?;
@@ -165,12 +258,17 @@ impl LanceStream {
             .map(|fragment| FileFragment::new(dataset.clone(), fragment.clone()))
             .collect::<Vec<_>>();
 
-        let scan_scheduler = ScanScheduler::new(dataset.object_store.clone());
+        let scan_scheduler = ScanScheduler::new_with_io_stats(
+            dataset.object_store.clone(),
+            metrics.as_ref().map(|metrics| metrics.io_stats.clone()),
+        );
+        let decode_stats = metrics.as_ref().map(|metrics| metrics.decode_stats.clone());
 
-        let batches = stream::iter(file_fragments)
-            .map(move |file_fragment| {
+        let batches = stream::iter(file_fragments.into_iter().enumerate())
+            .map(move |(frag_idx, file_fragment)| {
                 let project_schema = project_schema.clone();
                 let scan_scheduler = scan_scheduler.clone();
+                let decode_stats = decode_stats.clone();
                 #[allow(clippy::type_complexity)]
                 let frag_task: BoxFuture<
                     Result<BoxStream<Result<BoxFuture<Result<RecordBatch>>>>>,
@@ -182,9 +280,16 @@ impl LanceStream {
                         with_row_address,
                         with_make_deletions_null,
                         Some(scan_scheduler),
+                        decode_stats,
                     )
                     .await?;
-                    let batch_stream = reader.read_all(batch_size as u32)?.boxed();
+                    let skip_rows = if frag_idx == 0 {
+                        first_fragment_offset
+                    } else {
+                        0
+                    };
+                    let batch_stream =
+                        read_fragment(&reader, skip_rows, batch_size as u32)?.boxed();
                     let batch_stream: BoxStream<Result<BoxFuture<Result<RecordBatch>>>> =
                         batch_stream
                             .map(|fut| {
@@ -222,6 +327,7 @@ impl LanceStream {
             projection,
             with_row_id,
             with_row_address,
+            metrics,
         })
     }
 
@@ -237,6 +343,8 @@ impl LanceStream {
         with_row_address: bool,
         with_make_deletions_null: bool,
         scan_in_order: bool,
+        first_fragment_offset: usize,
+        metrics: Option<ScanMetrics>,
     ) -> Result<Self> {
         let project_schema = projection.clone();
         debug!(
@@ -250,8 +358,13 @@ impl LanceStream {
             .collect::<Vec<_>>();
 
         let inner_stream = if scan_in_order {
-            let readers = stream::iter(file_fragments)
-                .map(move |file_fragment| {
+            let readers = stream::iter(file_fragments.into_iter().enumerate())
+                .map(move |(frag_idx, file_fragment)| {
+                    let skip_rows = if frag_idx == 0 {
+                        first_fragment_offset
+                    } else {
+                        0
+                    };
                     Ok(open_file(
                         file_fragment,
                         project_schema.clone(),
@@ -259,13 +372,14 @@ impl LanceStream {
                         with_row_address,
                         with_make_deletions_null,
                         None,
-                    ))
+                        None,
+                    )
+                    .map_ok(move |reader| (reader, skip_rows)))
                 })
                 .try_buffered(fragment_readahead);
-            let tasks = readers.and_then(move |reader| {
+            let tasks = readers.and_then(move |(reader, skip_rows)| {
                 std::future::ready(
-                    reader
-                        .read_all(read_size as u32)
+                    read_fragment(&reader, skip_rows, read_size as u32)
                         .map(|task_stream| task_stream.map(Ok))
                         .map_err(DataFusionError::from),
                 )
@@ -278,8 +392,13 @@ impl LanceStream {
                 .stream_in_current_span()
                 .boxed()
         } else {
-            let readers = stream::iter(file_fragments)
-                .map(move |file_fragment| {
+            let readers = stream::iter(file_fragments.into_iter().enumerate())
+                .map(move |(frag_idx, file_fragment)| {
+                    let skip_rows = if frag_idx == 0 {
+                        first_fragment_offset
+                    } else {
+                        0
+                    };
                     Ok(open_file(
                         file_fragment,
                         project_schema.clone(),
@@ -287,13 +406,14 @@ impl LanceStream {
                         with_row_address,
                         with_make_deletions_null,
                         None,
-                    ))
+                        None,
+                    )
+                    .map_ok(move |reader| (reader, skip_rows)))
                 })
                 .try_buffered(fragment_readahead);
-            let tasks = readers.and_then(move |reader| {
+            let tasks = readers.and_then(move |(reader, skip_rows)| {
                 std::future::ready(
-                    reader
-                        .read_all(read_size as u32)
+                    read_fragment(&reader, skip_rows, read_size as u32)
                         .map(|task_stream| task_stream.map(Ok))
                         .map_err(DataFusionError::from),
                 )
@@ -317,6 +437,7 @@ impl LanceStream {
             projection,
             with_row_id,
             with_row_address,
+            metrics,
         })
     }
 }
@@ -348,7 +469,30 @@ impl Stream for LanceStream {
     type Item = std::result::Result<RecordBatch, datafusion::error::DataFusionError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        Pin::into_inner(self).inner_stream.poll_next_unpin(cx)
+        let this = Pin::into_inner(self);
+        let poll = this.inner_stream.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(batch))) => {
+                if let Some(metrics) = &this.metrics {
+                    metrics.output_rows.add(batch.num_rows());
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(metrics) = this.metrics.take() {
+                    metrics.record();
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for LanceStream {
+    fn drop(&mut self) {
+        if let Some(metrics) = self.metrics.take() {
+            metrics.record();
+        }
     }
 }
 
@@ -365,8 +509,10 @@ pub struct LanceScanExec {
     with_row_address: bool,
     with_make_deletions_null: bool,
     ordered_output: bool,
+    first_fragment_offset: usize,
     output_schema: Arc<ArrowSchema>,
     properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl DisplayAs for LanceScanExec {
@@ -388,7 +534,11 @@ impl DisplayAs for LanceScanExec {
                     self.with_row_id,
                     self.with_row_address,
                     self.ordered_output
-                )
+                )?;
+                if self.first_fragment_offset > 0 {
+                    write!(f, ", first_fragment_offset={}", self.first_fragment_offset)?;
+                }
+                Ok(())
             }
         }
     }
@@ -436,10 +586,21 @@ impl LanceScanExec {
             with_row_address,
             with_make_deletions_null,
             ordered_output: ordered_ouput,
+            first_fragment_offset: 0,
             output_schema,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
+
+    /// Skip the first `offset` rows of the first fragment.
+    ///
+    /// The offset counts rows that have not been deleted.  Skipped rows are
+    /// not decoded.
+    pub fn with_first_fragment_offset(mut self, offset: usize) -> Self {
+        self.first_fragment_offset = offset;
+        self
+    }
 }
 
 impl ExecutionPlan for LanceScanExec {
@@ -475,7 +636,7 @@ impl ExecutionPlan for LanceScanExec {
 
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<datafusion::execution::context::TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
         Ok(Box::pin(LanceStream::try_new(
@@ -489,9 +650,15 @@ impl ExecutionPlan for LanceScanExec {
             self.with_row_address,
             self.with_make_deletions_null,
             self.ordered_output,
+            self.first_fragment_offset,
+            Some(ScanMetrics::new(&self.metrics, partition)),
         )?))
     }
 
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
     fn statistics(&self) -> datafusion::error::Result<Statistics> {
         // Some fragments from older datasets might have the row count stats missing.
         let (row_count, is_exact) =
@@ -505,7 +672,7 @@ impl ExecutionPlan for LanceScanExec {
                     },
                 );
         let num_rows = match is_exact {
-            true => Precision::Exact(row_count),
+            true => Precision::Exact(row_count.saturating_sub(self.first_fragment_offset)),
             false => Precision::Absent,
         };
 
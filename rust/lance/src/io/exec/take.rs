@@ -9,6 +9,7 @@ use arrow_array::{cast::as_primitive_array, RecordBatch, UInt64Array};
 use arrow_schema::{Schema as ArrowSchema, SchemaRef};
 use datafusion::common::Statistics;
 use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricsSet};
 use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties, RecordBatchStream,
     SendableRecordBatchStream,
@@ -16,10 +17,14 @@ use datafusion::physical_plan::{
 use datafusion_physical_expr::EquivalenceProperties;
 use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use futures::{Future, FutureExt};
+use lance_encoding::stats::DecodeStats;
+use lance_io::scheduler::ScanScheduler;
 use tokio::sync::mpsc::{self, Receiver};
 use tokio::task::JoinHandle;
 use tracing::{instrument, Instrument};
 
+use super::scan::ScanMetrics;
+use crate::dataset::take::take_rows_with_decode_stats;
 use crate::dataset::{Dataset, ROW_ID};
 use crate::datatypes::Schema;
 use crate::{arrow::*, Error};
@@ -34,6 +39,8 @@ pub struct Take {
     bg_thread: Option<JoinHandle<()>>,
 
     output_schema: SchemaRef,
+
+    metrics: Option<ScanMetrics>,
 }
 
 impl Take {
@@ -44,6 +51,7 @@ impl Take {
     ///  - output_schema: the output schema of the take node.
     ///  - child: the upstream stream to feed data in.
     ///  - batch_readahead: max number of batches to readahead, potentially concurrently
+    ///  - metrics: the counters to report the work done by the take to.
     #[instrument(level = "debug", skip_all, name = "Take::new")]
     fn new(
         dataset: Arc<Dataset>,
@@ -51,18 +59,35 @@ impl Take {
         output_schema: SchemaRef,
         child: SendableRecordBatchStream,
         batch_readahead: usize,
+        metrics: Option<ScanMetrics>,
     ) -> Self {
         let (tx, rx) = mpsc::channel(4);
 
+        let scan_scheduler = metrics.as_ref().map(|metrics| {
+            ScanScheduler::new_with_io_stats(
+                dataset.object_store.clone(),
+                Some(metrics.io_stats.clone()),
+            )
+        });
+        let decode_stats = metrics.as_ref().map(|metrics| metrics.decode_stats.clone());
+
         let bg_thread = tokio::spawn(
             async move {
                 if let Err(e) = child
                     .zip(stream::repeat_with(|| {
-                        (dataset.clone(), projection.clone())
+                        (
+                            dataset.clone(),
+                            projection.clone(),
+                            scan_scheduler.clone(),
+                            decode_stats.clone(),
+                        )
                     }))
-                    .map(|(batch, (dataset, extra))| async move {
-                        Self::take_batch(batch?, dataset, extra).await
-                    })
+                    .map(
+                        |(batch, (dataset, extra, scan_scheduler, decode_stats))| async move {
+                            Self::take_batch(batch?, dataset, extra, scan_scheduler, decode_stats)
+                                .await
+                        },
+                    )
                     .buffered(batch_readahead)
                     .map(|r| r.map_err(|e| DataFusionError::Execution(e.to_string())))
                     .try_for_each(|b| async {
@@ -96,6 +121,7 @@ impl Take {
             rx,
             bg_thread: Some(bg_thread),
             output_schema,
+            metrics,
         }
     }
 
@@ -109,6 +135,8 @@ impl Take {
         batch: RecordBatch,
         dataset: Arc<Dataset>,
         extra: Arc<Schema>,
+        scan_scheduler: Option<Arc<ScanScheduler>>,
+        decode_stats: Option<Arc<DecodeStats>>,
     ) -> impl Future<Output = Result<RecordBatch, Error>> + Send {
         async move {
             let row_id_arr = batch.column_by_name(ROW_ID).unwrap();
@@ -116,7 +144,14 @@ impl Take {
             let rows = if extra.fields.is_empty() {
                 batch
             } else {
-                let new_columns = dataset.take_rows(row_ids.values(), &extra).await?;
+                let new_columns = take_rows_with_decode_stats(
+                    dataset.as_ref(),
+                    row_ids.values(),
+                    &extra,
+                    scan_scheduler,
+                    decode_stats,
+                )
+                .await?;
                 debug_assert_eq!(batch.num_rows(), new_columns.num_rows());
                 batch.merge(&new_columns)?
             };
@@ -150,8 +185,29 @@ impl Stream for Take {
             // Need to take it, since we aren't allowed to poll if again after.
             this.bg_thread.take();
         }
-        // this.rx.
-        this.rx.poll_recv(cx)
+        let poll = this.rx.poll_recv(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(batch))) => {
+                if let Some(metrics) = &this.metrics {
+                    metrics.output_rows.add(batch.num_rows());
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(metrics) = this.metrics.take() {
+                    metrics.record();
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for Take {
+    fn drop(&mut self) {
+        if let Some(metrics) = self.metrics.take() {
+            metrics.record();
+        }
     }
 }
 
@@ -182,6 +238,8 @@ pub struct TakeExec {
     batch_readahead: usize,
 
     properties: PlanProperties,
+
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl DisplayAs for TakeExec {
@@ -237,6 +295,7 @@ impl TakeExec {
             output_schema,
             batch_readahead,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         })
     }
 
@@ -302,9 +361,14 @@ impl ExecutionPlan for TakeExec {
             self.schema(),
             input_stream,
             self.batch_readahead,
+            Some(ScanMetrics::new(&self.metrics, partition)),
         )))
     }
 
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
     fn statistics(&self) -> Result<datafusion::physical_plan::Statistics> {
         Ok(Statistics {
             num_rows: self.input.statistics()?.num_rows,
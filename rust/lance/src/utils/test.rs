@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
@@ -232,6 +233,9 @@ impl TestDatasetGenerator {
             deletion_file: None,
             row_id_meta: None,
             physical_rows: Some(batch.num_rows()),
+            sort_order: vec![],
+            row_id_ranges: vec![],
+            custom_metadata: HashMap::new(),
         }
     }
 }
@@ -256,6 +260,8 @@ fn field_structure(fragment: &Fragment) -> Vec<Vec<i32>> {
 pub struct IoStats {
     pub read_iops: u64,
     pub read_bytes: u64,
+    /// The paths of the objects that were read
+    pub read_paths: HashSet<Path>,
 }
 
 impl Display for IoStats {
@@ -294,10 +300,13 @@ impl IoTrackingStore {
         (Arc::new(StatsHolder(stats.clone())), stats)
     }
 
-    fn record_read(&self, num_bytes: u64) {
+    fn record_read(&self, location: Option<&Path>, num_bytes: u64) {
         let mut stats = self.stats.lock().unwrap();
         stats.read_iops += 1;
         stats.read_bytes += num_bytes;
+        if let Some(location) = location {
+            stats.read_paths.insert(location.clone());
+        }
     }
 }
 
@@ -328,7 +337,7 @@ impl ObjectStore for IoTrackingStore {
         let result = self.target.get_opts(location, options).await;
         if let Ok(result) = &result {
             let num_bytes = result.range.end - result.range.start;
-            self.record_read(num_bytes as u64);
+            self.record_read(Some(location), num_bytes as u64);
         }
         result
     }
@@ -336,7 +345,7 @@ impl ObjectStore for IoTrackingStore {
     async fn get_range(&self, location: &Path, range: Range<usize>) -> OSResult<Bytes> {
         let result = self.target.get_range(location, range).await;
         if let Ok(result) = &result {
-            self.record_read(result.len() as u64);
+            self.record_read(Some(location), result.len() as u64);
         }
         result
     }
@@ -344,13 +353,13 @@ impl ObjectStore for IoTrackingStore {
     async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> OSResult<Vec<Bytes>> {
         let result = self.target.get_ranges(location, ranges).await;
         if let Ok(result) = &result {
-            self.record_read(result.iter().map(|b| b.len() as u64).sum());
+            self.record_read(Some(location), result.iter().map(|b| b.len() as u64).sum());
         }
         result
     }
 
     async fn head(&self, location: &Path) -> OSResult<ObjectMeta> {
-        self.record_read(0);
+        self.record_read(Some(location), 0);
         self.target.head(location).await
     }
 
@@ -366,12 +375,12 @@ impl ObjectStore for IoTrackingStore {
     }
 
     fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, OSResult<ObjectMeta>> {
-        self.record_read(0);
+        self.record_read(None, 0);
         self.target.list(prefix)
     }
 
     async fn list_with_delimiter(&self, prefix: Option<&Path>) -> OSResult<ListResult> {
-        self.record_read(0);
+        self.record_read(None, 0);
         self.target.list_with_delimiter(prefix).await
     }
 